@@ -0,0 +1,53 @@
+drop index agent_updated_at_idx;
+drop index activity_updated_at_idx;
+drop index entity_updated_at_idx;
+
+alter table agent drop column created_at;
+alter table agent drop column created_block;
+alter table agent drop column updated_at;
+alter table agent drop column updated_block;
+
+alter table activity drop column created_at;
+alter table activity drop column created_block;
+alter table activity drop column updated_at;
+alter table activity drop column updated_block;
+
+alter table entity drop column created_at;
+alter table entity drop column created_block;
+alter table entity drop column updated_at;
+alter table entity drop column updated_block;
+
+alter table association drop column created_at;
+alter table association drop column created_block;
+alter table association drop column updated_at;
+alter table association drop column updated_block;
+
+alter table attribution drop column created_at;
+alter table attribution drop column created_block;
+alter table attribution drop column updated_at;
+alter table attribution drop column updated_block;
+
+alter table delegation drop column created_at;
+alter table delegation drop column created_block;
+alter table delegation drop column updated_at;
+alter table delegation drop column updated_block;
+
+alter table derivation drop column created_at;
+alter table derivation drop column created_block;
+alter table derivation drop column updated_at;
+alter table derivation drop column updated_block;
+
+alter table generation drop column created_at;
+alter table generation drop column created_block;
+alter table generation drop column updated_at;
+alter table generation drop column updated_block;
+
+alter table usage drop column created_at;
+alter table usage drop column created_block;
+alter table usage drop column updated_at;
+alter table usage drop column updated_block;
+
+alter table wasinformedby drop column created_at;
+alter table wasinformedby drop column created_block;
+alter table wasinformedby drop column updated_at;
+alter table wasinformedby drop column updated_block;
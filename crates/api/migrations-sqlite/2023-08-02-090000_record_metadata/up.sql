@@ -0,0 +1,75 @@
+alter table agent add column created_at timestamp;
+alter table agent add column created_block text;
+alter table agent add column updated_at timestamp;
+alter table agent add column updated_block text;
+
+update agent set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table activity add column created_at timestamp;
+alter table activity add column created_block text;
+alter table activity add column updated_at timestamp;
+alter table activity add column updated_block text;
+
+update activity set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table entity add column created_at timestamp;
+alter table entity add column created_block text;
+alter table entity add column updated_at timestamp;
+alter table entity add column updated_block text;
+
+update entity set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table association add column created_at timestamp;
+alter table association add column created_block text;
+alter table association add column updated_at timestamp;
+alter table association add column updated_block text;
+
+update association set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table attribution add column created_at timestamp;
+alter table attribution add column created_block text;
+alter table attribution add column updated_at timestamp;
+alter table attribution add column updated_block text;
+
+update attribution set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table delegation add column created_at timestamp;
+alter table delegation add column created_block text;
+alter table delegation add column updated_at timestamp;
+alter table delegation add column updated_block text;
+
+update delegation set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table derivation add column created_at timestamp;
+alter table derivation add column created_block text;
+alter table derivation add column updated_at timestamp;
+alter table derivation add column updated_block text;
+
+update derivation set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table generation add column created_at timestamp;
+alter table generation add column created_block text;
+alter table generation add column updated_at timestamp;
+alter table generation add column updated_block text;
+
+update generation set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table usage add column created_at timestamp;
+alter table usage add column created_block text;
+alter table usage add column updated_at timestamp;
+alter table usage add column updated_block text;
+
+update usage set created_at = current_timestamp, updated_at = current_timestamp;
+
+alter table wasinformedby add column created_at timestamp;
+alter table wasinformedby add column created_block text;
+alter table wasinformedby add column updated_at timestamp;
+alter table wasinformedby add column updated_block text;
+
+update wasinformedby set created_at = current_timestamp, updated_at = current_timestamp;
+
+create index agent_updated_at_idx on agent(updated_at);
+
+create index activity_updated_at_idx on activity(updated_at);
+
+create index entity_updated_at_idx on entity(updated_at);
@@ -0,0 +1,11 @@
+create table idempotency_key (
+    identity text not null,
+    key text not null,
+    command_digest text not null,
+    subject text not null,
+    tx_id text not null,
+    created_at timestamp not null,
+    primary key (identity, key)
+);
+
+create index idempotency_key_created_at_idx on idempotency_key(created_at);
@@ -0,0 +1,8 @@
+create table outbox (
+    id text primary key,
+    command text not null,
+    identity text not null,
+    created_at timestamp not null
+);
+
+create index outbox_created_at_idx on outbox(created_at);
@@ -0,0 +1,10 @@
+-- The operation digest of each transaction submitted by this deployment,
+-- with the block it committed in, and the chronicle key's signature and the id
+-- of that key once it has
+create table receipt (
+    tx_id text primary key,
+    operation_digest text not null,
+    block_id text,
+    key_id text,
+    signature text
+);
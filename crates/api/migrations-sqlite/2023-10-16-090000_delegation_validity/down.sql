@@ -0,0 +1,2 @@
+alter table delegation drop column valid_from;
+alter table delegation drop column valid_until;
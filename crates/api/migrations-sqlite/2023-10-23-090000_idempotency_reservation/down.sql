@@ -0,0 +1,15 @@
+create table idempotency_key_submitted (
+    identity text not null,
+    key text not null,
+    command_digest text not null,
+    subject text not null,
+    tx_id text not null,
+    created_at timestamp not null,
+    primary key (identity, key)
+);
+
+insert into idempotency_key_submitted select * from idempotency_key where tx_id is not null;
+drop table idempotency_key;
+alter table idempotency_key_submitted rename to idempotency_key;
+
+create index idempotency_key_created_at_idx on idempotency_key(created_at);
@@ -0,0 +1,17 @@
+-- Keys are reserved before their command is submitted, so the submission is
+-- unknown until it completes
+create table idempotency_key_reserved (
+    identity text not null,
+    key text not null,
+    command_digest text not null,
+    subject text,
+    tx_id text,
+    created_at timestamp not null,
+    primary key (identity, key)
+);
+
+insert into idempotency_key_reserved select * from idempotency_key;
+drop table idempotency_key;
+alter table idempotency_key_reserved rename to idempotency_key;
+
+create index idempotency_key_created_at_idx on idempotency_key(created_at);
@@ -0,0 +1,3 @@
+drop index audit_log_operation_idx;
+
+alter table audit_log drop column operation_index;
@@ -0,0 +1,3 @@
+drop index ledgersync_ledger_sequence_idx;
+
+alter table ledgersync drop column ledger_sequence;
@@ -0,0 +1,16 @@
+-- The order transactions were committed in, by block and then position in
+-- the block. Transactions are synchronized in that order, so each is given
+-- the next in sequence as it is first applied
+alter table ledgersync add column ledger_sequence bigint not null default 0;
+
+-- Transactions already synchronized keep the order they were synchronized in
+update ledgersync set ledger_sequence = (
+    select count(*) from ledgersync earlier
+    where coalesce(earlier.sync_time, '1970-01-01') < coalesce(ledgersync.sync_time, '1970-01-01')
+    or (
+        coalesce(earlier.sync_time, '1970-01-01') = coalesce(ledgersync.sync_time, '1970-01-01')
+        and earlier.tx_id <= ledgersync.tx_id
+    )
+);
+
+create unique index ledgersync_ledger_sequence_idx on ledgersync(ledger_sequence);
@@ -0,0 +1,3 @@
+drop index attribute_history_superseded_idx;
+drop index attribute_history_record_idx;
+drop table attribute_history;
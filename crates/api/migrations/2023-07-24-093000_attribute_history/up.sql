@@ -0,0 +1,12 @@
+create table attribute_history (
+    id serial primary key,
+    record_type text not null,
+    record_id integer not null,
+    typename text not null,
+    value text not null,
+    superseded_at timestamp not null
+);
+
+create index attribute_history_record_idx on attribute_history(record_type,record_id);
+
+create index attribute_history_superseded_idx on attribute_history(superseded_at);
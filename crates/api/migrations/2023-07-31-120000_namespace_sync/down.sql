@@ -0,0 +1 @@
+drop table namespace_sync;
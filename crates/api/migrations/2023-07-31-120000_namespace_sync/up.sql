@@ -0,0 +1,7 @@
+create table namespace_sync (
+    namespace_id integer primary key,
+    bc_offset text not null,
+    tx_id text not null,
+    sync_time timestamp not null,
+    foreign key(namespace_id) references namespace(id)
+);
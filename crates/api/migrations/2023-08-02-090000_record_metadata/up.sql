@@ -0,0 +1,85 @@
+alter table agent
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update agent set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table activity
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update activity set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table entity
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update entity set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table association
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update association set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table attribution
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update attribution set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table delegation
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update delegation set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table derivation
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update derivation set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table generation
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update generation set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table usage
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update usage set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+alter table wasinformedby
+    add column created_at timestamp,
+    add column created_block text,
+    add column updated_at timestamp,
+    add column updated_block text;
+
+update wasinformedby set created_at = (now() at time zone 'utc'), updated_at = (now() at time zone 'utc');
+
+create index agent_updated_at_idx on agent(updated_at);
+
+create index activity_updated_at_idx on activity(updated_at);
+
+create index entity_updated_at_idx on entity(updated_at);
@@ -0,0 +1,15 @@
+create table export_job (
+    id text primary key,
+    namespace_id integer not null,
+    format text not null,
+    status text not null,
+    modified_since timestamp,
+    callback text,
+    artifact text,
+    error text,
+    created_at timestamp not null,
+    updated_at timestamp not null,
+    foreign key(namespace_id) references namespace(id)
+);
+
+create index export_job_status_idx on export_job(status);
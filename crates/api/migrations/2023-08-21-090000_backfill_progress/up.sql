@@ -0,0 +1,8 @@
+create table backfill_progress (
+    task text not null,
+    record_type text not null,
+    last_id integer not null,
+    processed bigint not null,
+    updated_at timestamp not null,
+    primary key (task, record_type)
+);
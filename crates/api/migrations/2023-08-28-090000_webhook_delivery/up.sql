@@ -0,0 +1,15 @@
+create table webhook_delivery (
+    id text primary key,
+    url text not null,
+    tx_id text not null,
+    payload text not null,
+    signature text not null,
+    status text not null,
+    attempts integer not null,
+    response_status integer,
+    error text,
+    created_at timestamp not null,
+    updated_at timestamp not null
+);
+
+create index webhook_delivery_status_idx on webhook_delivery(url, status);
@@ -0,0 +1 @@
+drop table prov_history;
@@ -0,0 +1,13 @@
+create table prov_history (
+    id serial primary key,
+    namespace_id integer not null,
+    block_id text not null,
+    tx_id text not null,
+    delta text not null,
+    recorded_at timestamp not null,
+    foreign key(namespace_id) references namespace(id)
+);
+
+create index prov_history_namespace_idx on prov_history(namespace_id,id);
+
+create index prov_history_block_idx on prov_history(block_id);
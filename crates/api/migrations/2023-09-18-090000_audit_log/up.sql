@@ -0,0 +1,14 @@
+create table audit_log (
+    id serial primary key,
+    tx_id text not null,
+    namespace text not null,
+    subject text not null,
+    operation text not null,
+    detail text not null,
+    identity text not null,
+    submitted_at timestamp not null
+);
+
+create index audit_log_subject_idx on audit_log(namespace,subject);
+
+create index audit_log_tx_id_idx on audit_log(tx_id);
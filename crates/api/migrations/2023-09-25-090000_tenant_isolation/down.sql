@@ -0,0 +1,25 @@
+drop policy audit_log_tenant on audit_log;
+alter table audit_log no force row level security;
+alter table audit_log disable row level security;
+
+drop policy prov_history_tenant on prov_history;
+alter table prov_history no force row level security;
+alter table prov_history disable row level security;
+
+drop policy identity_tenant on identity;
+alter table identity no force row level security;
+alter table identity disable row level security;
+
+drop policy entity_tenant on entity;
+alter table entity no force row level security;
+alter table entity disable row level security;
+
+drop policy activity_tenant on activity;
+alter table activity no force row level security;
+alter table activity disable row level security;
+
+drop policy agent_tenant on agent;
+alter table agent no force row level security;
+alter table agent disable row level security;
+
+drop function chronicle_tenant_visible;
@@ -0,0 +1,38 @@
+-- Row-level security scoping records to a tenant namespace. A transaction
+-- that sets chronicle.tenant to the external id of a namespace sees only the
+-- records in it, while one that does not set it sees every record.
+create function chronicle_tenant_visible(record_namespace integer) returns boolean
+language sql stable as $$
+    select coalesce(current_setting('chronicle.tenant', true), '') = ''
+        or record_namespace in (
+            select id from namespace
+            where external_id = current_setting('chronicle.tenant', true)
+        )
+$$;
+
+alter table agent enable row level security;
+alter table agent force row level security;
+create policy agent_tenant on agent using (chronicle_tenant_visible(namespace_id));
+
+alter table activity enable row level security;
+alter table activity force row level security;
+create policy activity_tenant on activity using (chronicle_tenant_visible(namespace_id));
+
+alter table entity enable row level security;
+alter table entity force row level security;
+create policy entity_tenant on entity using (chronicle_tenant_visible(namespace_id));
+
+alter table identity enable row level security;
+alter table identity force row level security;
+create policy identity_tenant on identity using (chronicle_tenant_visible(namespace_id));
+
+alter table prov_history enable row level security;
+alter table prov_history force row level security;
+create policy prov_history_tenant on prov_history using (chronicle_tenant_visible(namespace_id));
+
+alter table audit_log enable row level security;
+alter table audit_log force row level security;
+create policy audit_log_tenant on audit_log using (
+    coalesce(current_setting('chronicle.tenant', true), '') = ''
+    or namespace = current_setting('chronicle.tenant', true)
+);
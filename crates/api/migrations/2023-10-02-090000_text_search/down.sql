@@ -0,0 +1,7 @@
+drop index entity_attribute_value_search;
+drop index activity_attribute_value_search;
+drop index agent_attribute_value_search;
+
+drop index entity_external_id_search;
+drop index activity_external_id_search;
+drop index agent_external_id_search;
@@ -0,0 +1,10 @@
+-- Text search over the external ids and attribute values of records. The
+-- 'simple' configuration neither stems nor drops stop words, as external ids
+-- and attribute values are identifiers as often as they are prose.
+create index agent_external_id_search on agent using gin (to_tsvector('simple', external_id));
+create index activity_external_id_search on activity using gin (to_tsvector('simple', external_id));
+create index entity_external_id_search on entity using gin (to_tsvector('simple', external_id));
+
+create index agent_attribute_value_search on agent_attribute using gin (to_tsvector('simple', value));
+create index activity_attribute_value_search on activity_attribute using gin (to_tsvector('simple', value));
+create index entity_attribute_value_search on entity_attribute using gin (to_tsvector('simple', value));
@@ -0,0 +1,3 @@
+alter table delegation
+    drop column valid_from,
+    drop column valid_until;
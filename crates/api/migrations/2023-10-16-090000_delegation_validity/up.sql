@@ -0,0 +1,4 @@
+-- The period over which a delegation holds, open where a bound is null
+alter table delegation
+    add column valid_from timestamp,
+    add column valid_until timestamp;
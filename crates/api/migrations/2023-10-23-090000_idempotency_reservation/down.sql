@@ -0,0 +1,3 @@
+delete from idempotency_key where tx_id is null;
+alter table idempotency_key alter column subject set not null;
+alter table idempotency_key alter column tx_id set not null;
@@ -0,0 +1,4 @@
+-- Keys are reserved before their command is submitted, so the submission is
+-- unknown until it completes
+alter table idempotency_key alter column subject drop not null;
+alter table idempotency_key alter column tx_id drop not null;
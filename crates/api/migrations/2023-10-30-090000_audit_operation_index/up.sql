@@ -0,0 +1,25 @@
+-- The position of the operation in its transaction, so that an operation
+-- is logged once for each of its subjects however often it is synced
+alter table audit_log add column operation_index integer not null default 0;
+
+-- Operations were logged in order, each for its subjects in turn, so an
+-- operation's position is the number of distinct operations logged before
+-- its first row
+update audit_log set operation_index = (
+    select count(distinct earlier.detail) from audit_log earlier
+    where earlier.tx_id = audit_log.tx_id
+    and earlier.id < (
+        select min(logged.id) from audit_log logged
+        where logged.tx_id = audit_log.tx_id and logged.detail = audit_log.detail
+    )
+);
+
+delete from audit_log where exists (
+    select 1 from audit_log earlier
+    where earlier.tx_id = audit_log.tx_id
+    and earlier.subject = audit_log.subject
+    and earlier.operation_index = audit_log.operation_index
+    and earlier.id < audit_log.id
+);
+
+create unique index audit_log_operation_idx on audit_log(tx_id,subject,operation_index);
@@ -0,0 +1,3 @@
+drop policy export_job_tenant on export_job;
+alter table export_job no force row level security;
+alter table export_job disable row level security;
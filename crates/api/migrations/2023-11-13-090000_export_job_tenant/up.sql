@@ -0,0 +1,4 @@
+-- Scope export jobs to a tenant namespace, as the provenance they export is
+alter table export_job enable row level security;
+alter table export_job force row level security;
+create policy export_job_tenant on export_job using (chronicle_tenant_visible(namespace_id));
@@ -0,0 +1,3 @@
+drop index webhook_delivery_due_idx;
+
+alter table webhook_delivery drop column next_attempt_at;
@@ -0,0 +1,6 @@
+-- When a queued delivery is next due to be attempted. A worker claims a
+-- delivery by moving this past the time it needs for the attempt, so that
+-- other replicas leave the delivery alone meanwhile
+alter table webhook_delivery add column next_attempt_at timestamp not null default '1970-01-01 00:00:00';
+
+create index webhook_delivery_due_idx on webhook_delivery(url, status, next_attempt_at);
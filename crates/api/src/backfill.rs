@@ -0,0 +1,218 @@
+//! Backfills bring a derived store up to date with records written before it
+//! was configured, such as a search index or graph mirror enabled on an
+//! existing deployment, which only receive new commits. Agents, activities and
+//! entities are read in chunks in row order, and the progress of each task is
+//! recorded after every chunk, so an interrupted backfill resumes where it
+//! stopped. Chunks can be paced to a number of records per second to spare the
+//! database and the target.
+
+use std::time::{Duration, Instant};
+
+use common::prov::ProvModel;
+use diesel::r2d2::{ConnectionManager, Pool};
+use thiserror::Error;
+use tokio::task::JoinError;
+use tracing::{info, instrument};
+
+use crate::{
+    chronicle_graphql::search::{self, SearchConf, SearchError},
+    graph_mirror::{self, GraphMirrorConf, GraphMirrorError},
+    persistence::{RecordType, Store},
+    prometheus, DatabaseConnection, StoreError,
+};
+
+const RECORD_TYPES: [RecordType; 3] = [RecordType::Agent, RecordType::Activity, RecordType::Entity];
+
+#[derive(Error, Debug)]
+pub enum BackfillError {
+    #[error("Storage: {0}")]
+    Store(#[from] StoreError),
+
+    #[error("Search: {0}")]
+    Search(#[from] SearchError),
+
+    #[error("Graph mirror: {0}")]
+    GraphMirror(#[from] GraphMirrorError),
+
+    #[error("Blocking thread pool: {0}")]
+    Join(#[from] JoinError),
+}
+
+/// A derived store to fill with the records already in the database
+#[derive(Debug, Clone)]
+pub enum BackfillTask {
+    Search(SearchConf),
+    GraphMirror(GraphMirrorConf),
+}
+
+impl BackfillTask {
+    /// The name progress is recorded under
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackfillTask::Search(_) => "search",
+            BackfillTask::GraphMirror(_) => "graph-mirror",
+        }
+    }
+
+    async fn prepare(&self, client: &reqwest::Client) -> Result<(), BackfillError> {
+        match self {
+            BackfillTask::Search(conf) => search::ensure_index(client, conf).await?,
+            BackfillTask::GraphMirror(conf) => graph_mirror::ensure_indexes(client, conf).await?,
+        }
+
+        Ok(())
+    }
+
+    async fn write(
+        &self,
+        client: &reqwest::Client,
+        model: &ProvModel,
+    ) -> Result<(), BackfillError> {
+        match self {
+            BackfillTask::Search(conf) => {
+                search::index_model(client, conf, model).await?;
+            }
+            BackfillTask::GraphMirror(conf) => {
+                graph_mirror::mirror_model(client, conf, model).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BackfillConf {
+    /// The most records to read and write at once
+    pub chunk_size: i64,
+    /// The most records to process per second, unlimited if `None`
+    pub rate: Option<f64>,
+    /// Discard recorded progress and start from the first record
+    pub restart: bool,
+}
+
+impl Default for BackfillConf {
+    fn default() -> Self {
+        Self {
+            chunk_size: 500,
+            rate: None,
+            restart: false,
+        }
+    }
+}
+
+/// How long to wait after processing `records` in `elapsed`, so as not to
+/// exceed `rate` records per second
+fn pace(records: usize, rate: Option<f64>, elapsed: Duration) -> Duration {
+    match rate {
+        Some(rate) if rate > 0.0 => {
+            Duration::from_secs_f64(records as f64 / rate).saturating_sub(elapsed)
+        }
+        _ => Duration::ZERO,
+    }
+}
+
+/// Run `task` over every record not yet processed, returning the number of
+/// records processed by this run
+#[instrument(skip(pool, task), fields(task = task.name()))]
+pub async fn backfill(
+    pool: Pool<ConnectionManager<DatabaseConnection>>,
+    task: BackfillTask,
+    conf: BackfillConf,
+) -> Result<u64, BackfillError> {
+    let store = Store::new(pool)?;
+    let client = reqwest::Client::new();
+    let name = task.name();
+
+    if conf.restart {
+        store.reset_backfill_progress(name)?;
+    }
+    task.prepare(&client).await?;
+
+    let mut processed_by_run = 0;
+    for record_type in RECORD_TYPES {
+        let (mut last_id, mut processed) = store
+            .backfill_progress(name, record_type)?
+            .unwrap_or_default();
+        let mut remaining = store.count_records_after(record_type, last_id)?;
+        info!(
+            task = name,
+            record_type = record_type.as_str(),
+            processed,
+            remaining,
+            "Backfilling"
+        );
+
+        loop {
+            let started = Instant::now();
+            let chunk_store = store.clone();
+            let chunk_size = conf.chunk_size;
+            let chunk = tokio::task::spawn_blocking(move || {
+                chunk_store.read_only(|connection| {
+                    chunk_store.prov_model_for_records_after(
+                        connection,
+                        record_type,
+                        last_id,
+                        chunk_size,
+                    )
+                })
+            })
+            .await??;
+
+            let chunk = match chunk {
+                Some(chunk) => chunk,
+                None => break,
+            };
+
+            task.write(&client, &chunk.model).await?;
+
+            last_id = chunk.last_id;
+            processed += chunk.records as i64;
+            remaining = (remaining - chunk.records as i64).max(0);
+            processed_by_run += chunk.records as u64;
+            store.set_backfill_progress(name, record_type, last_id, processed)?;
+            prometheus::record_backfill(
+                name,
+                record_type.as_str(),
+                chunk.records as u64,
+                remaining,
+            );
+            info!(
+                task = name,
+                record_type = record_type.as_str(),
+                processed,
+                remaining,
+                "Backfilled chunk"
+            );
+
+            tokio::time::sleep(pace(chunk.records, conf.rate, started.elapsed())).await;
+        }
+    }
+
+    info!(
+        task = name,
+        processed = processed_by_run,
+        "Backfill complete"
+    );
+    Ok(processed_by_run)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::pace;
+
+    #[test]
+    fn chunks_are_paced_to_the_rate() {
+        assert_eq!(
+            pace(500, Some(100.0), Duration::from_secs(2)),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            pace(500, Some(100.0), Duration::from_secs(6)),
+            Duration::ZERO
+        );
+        assert_eq!(pace(500, None, Duration::ZERO), Duration::ZERO);
+    }
+}
@@ -1,7 +1,11 @@
-use crate::chronicle_graphql::Entity;
-
-use super::{Agent, Identity, Namespace, Store};
-use async_graphql::Context;
+use crate::{chronicle_graphql::Entity, DatabaseBackend};
+
+use super::{
+    loader::{AgentAttribute, Attributed, RelationLoader},
+    Agent, Identity, Namespace, Store,
+};
+use async_graphql::{dataloader::DataLoader, Context};
+use chrono::{DateTime, Utc};
 use common::prov::Role;
 use diesel::prelude::*;
 
@@ -12,11 +16,11 @@ pub async fn namespace<'a>(
     use crate::persistence::schema::namespace::{self, dsl};
     let store = ctx.data_unchecked::<Store>();
 
-    let mut connection = store.pool.get()?;
-
-    Ok(namespace::table
-        .filter(dsl::id.eq(namespace_id))
-        .first::<Namespace>(&mut connection)?)
+    store.read_only(|connection| {
+        Ok(namespace::table
+            .filter(dsl::id.eq(namespace_id))
+            .first::<Namespace>(connection)?)
+    })
 }
 
 pub async fn identity<'a>(
@@ -26,20 +30,23 @@ pub async fn identity<'a>(
     use crate::persistence::schema::identity::{self, dsl};
     let store = ctx.data_unchecked::<Store>();
 
-    let mut connection = store.pool.get()?;
-
-    if let Some(identity_id) = identity_id {
-        Ok(identity::table
-            .filter(dsl::id.eq(identity_id))
-            .first::<Identity>(&mut connection)
-            .optional()?)
-    } else {
-        Ok(None)
-    }
+    store.read_only(|connection| {
+        if let Some(identity_id) = identity_id {
+            Ok(identity::table
+                .filter(dsl::id.eq(identity_id))
+                .first::<Identity>(connection)
+                .optional()?)
+        } else {
+            Ok(None)
+        }
+    })
 }
 
+/// Return the agents an agent acted on behalf of along with the roles in which
+/// they did, limited to the delegations that held `at` a time if one is given
 pub async fn acted_on_behalf_of<'a>(
     id: i32,
+    at: Option<DateTime<Utc>>,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Vec<(Agent, Option<Role>)>> {
     use crate::persistence::schema::{
@@ -49,17 +56,27 @@ pub async fn acted_on_behalf_of<'a>(
 
     let store = ctx.data_unchecked::<Store>();
 
-    let mut connection = store.pool.get()?;
-
-    Ok(delegation::table
-        .filter(dsl::delegate_id.eq(id))
-        .inner_join(agentdsl::table.on(dsl::responsible_id.eq(agentdsl::id)))
-        .order(agentdsl::external_id)
-        .select((Agent::as_select(), dsl::role))
-        .load::<(Agent, Role)>(&mut connection)?
-        .into_iter()
-        .map(|(a, r)| (a, if r.0.is_empty() { None } else { Some(r) }))
-        .collect())
+    store.read_only(|connection| {
+        let mut sql_query = delegation::table
+            .filter(dsl::delegate_id.eq(id))
+            .inner_join(agentdsl::table.on(dsl::responsible_id.eq(agentdsl::id)))
+            .order(agentdsl::external_id)
+            .select((Agent::as_select(), dsl::role))
+            .into_boxed::<DatabaseBackend>();
+
+        if let Some(at) = at {
+            let at = at.naive_utc();
+            sql_query = sql_query
+                .filter(dsl::valid_from.is_null().or(dsl::valid_from.le(at)))
+                .filter(dsl::valid_until.is_null().or(dsl::valid_until.gt(at)));
+        }
+
+        Ok(sql_query
+            .load::<(Agent, Role)>(connection)?
+            .into_iter()
+            .map(|(a, r)| (a, if r.0.is_empty() { None } else { Some(r) }))
+            .collect())
+    })
 }
 
 /// Return the entities an agent has attributed to it along with the roles in which they were attributed
@@ -67,24 +84,9 @@ pub async fn attribution<'a>(
     id: i32,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Vec<(Entity, Option<Role>)>> {
-    use crate::persistence::schema::{
-        attribution::{self, dsl},
-        entity as entity_dsl,
-    };
+    let loader = ctx.data_unchecked::<DataLoader<RelationLoader>>();
 
-    let store = ctx.data_unchecked::<Store>();
-
-    let mut connection = store.pool.get()?;
-
-    Ok(attribution::table
-        .filter(dsl::agent_id.eq(id))
-        .inner_join(entity_dsl::table.on(dsl::entity_id.eq(entity_dsl::id)))
-        .order(entity_dsl::external_id)
-        .select((Entity::as_select(), dsl::role))
-        .load::<(Entity, Role)>(&mut connection)?
-        .into_iter()
-        .map(|(entity, role)| (entity, if role.0.is_empty() { None } else { Some(role) }))
-        .collect())
+    Ok(loader.load_one(Attributed(id)).await?.unwrap_or_default())
 }
 
 pub async fn load_attribute<'a>(
@@ -92,22 +94,9 @@ pub async fn load_attribute<'a>(
     external_id: &str,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Option<serde_json::Value>> {
-    use crate::persistence::schema::agent_attribute;
-
-    let store = ctx.data_unchecked::<Store>();
-
-    let mut connection = store.pool.get()?;
+    let loader = ctx.data_unchecked::<DataLoader<RelationLoader>>();
 
-    Ok(agent_attribute::table
-        .filter(
-            agent_attribute::agent_id
-                .eq(id)
-                .and(agent_attribute::typename.eq(external_id)),
-        )
-        .select(agent_attribute::value)
-        .first::<String>(&mut connection)
-        .optional()?
-        .as_deref()
-        .map(serde_json::from_str)
-        .transpose()?)
+    Ok(loader
+        .load_one(AgentAttribute(id, external_id.to_owned()))
+        .await?)
 }
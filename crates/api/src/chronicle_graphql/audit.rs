@@ -0,0 +1,82 @@
+//! The audit trail of the operations applied to records, with the identity
+//! that submitted each of them and the block that carried it. Operations are
+//! recorded as they are submitted through this deployment, or as they are
+//! synced from the ledger if they were submitted elsewhere.
+
+use async_graphql::{Context, SimpleObject};
+use chrono::{DateTime, Utc};
+use common::prov::{ActivityId, AgentId, ChronicleIri, ChronicleJSON, EntityId};
+use tracing::instrument;
+
+use super::{readable_namespace, Store};
+use crate::ReadFrom;
+
+/// # `AuditRecord`
+///
+/// An operation applied to a record, as submitted by `identity` in the
+/// transaction `txId`. `detail` is the operation itself.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct AuditRecord {
+    /// The name of the operation, such as `WasGeneratedBy`
+    pub operation: String,
+    /// The id of the agent, activity or entity the operation applied to
+    pub subject: String,
+    pub namespace: String,
+    pub detail: ChronicleJSON,
+    /// The principal that submitted the operation, `Chronicle`, `Anonymous` or
+    /// the external id of a JWT identity
+    pub identity: String,
+    pub tx_id: String,
+    pub block_id: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+    pub committed_at: Option<DateTime<Utc>>,
+}
+
+/// The operations applied to the given agent, activity or entity, or to any of
+/// them that are given, most recent first. At most `first` are returned, by
+/// default 100.
+#[instrument(skip(ctx))]
+pub async fn audit_trail<'a>(
+    ctx: &Context<'a>,
+    agent_id: Option<AgentId>,
+    activity_id: Option<ActivityId>,
+    entity_id: Option<EntityId>,
+    namespace: Option<String>,
+    first: Option<i32>,
+) -> async_graphql::Result<Vec<AuditRecord>> {
+    let store = ctx.data_unchecked::<Store>().persistence()?;
+    let ns: String = readable_namespace(ctx, namespace).await?;
+    let subjects = agent_id
+        .map(ChronicleIri::from)
+        .into_iter()
+        .chain(activity_id.map(ChronicleIri::from))
+        .chain(entity_id.map(ChronicleIri::from))
+        .map(|subject| subject.to_string())
+        .collect::<Vec<_>>();
+    let limit = first.unwrap_or(100).clamp(0, 1000) as i64;
+
+    // Operations are recorded on the primary as they are submitted, ahead of
+    // any replica
+    let records = store.read_only_from(ReadFrom::Primary, |connection| {
+        store.audit_trail(connection, &ns, &subjects, limit)
+    })?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| {
+            Ok(AuditRecord {
+                operation: record.operation,
+                subject: record.subject,
+                namespace: record.namespace,
+                detail: ChronicleJSON(serde_json::from_str(&record.detail)?),
+                identity: record.identity,
+                tx_id: record.tx_id,
+                block_id: record.block_id,
+                submitted_at: DateTime::from_naive_utc_and_offset(record.submitted_at, Utc),
+                committed_at: record
+                    .synced_at
+                    .map(|synced_at| DateTime::from_naive_utc_and_offset(synced_at, Utc)),
+            })
+        })
+        .collect::<Result<_, serde_json::Error>>()?)
+}
@@ -2,10 +2,12 @@ use async_graphql::{
     connection::{Edge, EmptyFields},
     OutputType,
 };
-use diesel::{pg::Pg, prelude::*, query_builder::*, r2d2::ConnectionManager, sql_types::BigInt};
+use diesel::{prelude::*, query_builder::*, r2d2::ConnectionManager, sql_types::BigInt};
 use r2d2::PooledConnection;
 
-type Conn = PooledConnection<ConnectionManager<PgConnection>>;
+use crate::{DatabaseBackend, DatabaseConnection};
+
+type Conn = PooledConnection<ConnectionManager<DatabaseConnection>>;
 
 const DEFAULT_PAGE_SIZE: i32 = 10;
 
@@ -79,11 +81,11 @@ impl<T> Cursorize for T {
     }
 }
 
-impl<T> QueryFragment<Pg> for CursorPosition<T>
+impl<T> QueryFragment<DatabaseBackend> for CursorPosition<T>
 where
-    T: QueryFragment<Pg>,
+    T: QueryFragment<DatabaseBackend>,
 {
-    fn walk_ast<'a>(&'a self, mut out: AstPass<'_, 'a, Pg>) -> QueryResult<()> {
+    fn walk_ast<'a>(&'a self, mut out: AstPass<'_, 'a, DatabaseBackend>) -> QueryResult<()> {
         out.push_sql("SELECT *, COUNT(*) OVER () FROM (");
         self.query.walk_ast(out.reborrow())?;
         out.push_sql(") t LIMIT ");
@@ -1,5 +1,8 @@
-use super::{Activity, Agent, Entity, Namespace, Store};
-use async_graphql::Context;
+use super::{
+    loader::{AttributedTo, DerivedFrom, EntityAttribute, GeneratedBy, RelationLoader},
+    Activity, Agent, Entity, Namespace, Store,
+};
+use async_graphql::{dataloader::DataLoader, Context};
 use common::prov::{operations::DerivationType, Role};
 use diesel::prelude::*;
 
@@ -8,22 +11,12 @@ async fn typed_derivation<'a>(
     ctx: &Context<'a>,
     typ: DerivationType,
 ) -> async_graphql::Result<Vec<Entity>> {
-    use crate::persistence::schema::{
-        derivation::{self, dsl},
-        entity as entitydsl,
-    };
+    let loader = ctx.data_unchecked::<DataLoader<RelationLoader>>();
 
-    let store = ctx.data_unchecked::<Store>();
-
-    let mut connection = store.pool.get()?;
-
-    let res = derivation::table
-        .filter(dsl::generated_entity_id.eq(id).and(dsl::typ.eq(typ)))
-        .inner_join(entitydsl::table.on(dsl::used_entity_id.eq(entitydsl::id)))
-        .select(Entity::as_select())
-        .load::<Entity>(&mut connection)?;
-
-    Ok(res)
+    Ok(loader
+        .load_one(DerivedFrom(id, Some(typ)))
+        .await?
+        .unwrap_or_default())
 }
 
 pub async fn namespace<'a>(
@@ -34,11 +27,11 @@ pub async fn namespace<'a>(
 
     let store = ctx.data_unchecked::<Store>();
 
-    let mut connection = store.pool.get()?;
-
-    Ok(namespace::table
-        .filter(dsl::id.eq(namespace_id))
-        .first::<Namespace>(&mut connection)?)
+    store.read_only(|connection| {
+        Ok(namespace::table
+            .filter(dsl::id.eq(namespace_id))
+            .first::<Namespace>(connection)?)
+    })
 }
 
 /// Return the agents to which an entity was attributed along with the roles in which it was attributed
@@ -46,66 +39,30 @@ pub async fn was_attributed_to<'a>(
     id: i32,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Vec<(Agent, Option<Role>)>> {
-    use crate::persistence::schema::{agent, attribution};
+    let loader = ctx.data_unchecked::<DataLoader<RelationLoader>>();
 
-    let store = ctx.data_unchecked::<Store>();
-    let mut connection = store.pool.get()?;
-
-    let res = attribution::table
-        .filter(attribution::dsl::entity_id.eq(id))
-        .inner_join(agent::table)
-        .order(agent::external_id)
-        .select((Agent::as_select(), attribution::role))
-        .load::<(Agent, Role)>(&mut connection)?
-        .into_iter()
-        .map(|(agent, role)| {
-            let role = if role.0.is_empty() { None } else { Some(role) };
-            (agent, role)
-        })
-        .collect();
-
-    Ok(res)
+    Ok(loader.load_one(AttributedTo(id)).await?.unwrap_or_default())
 }
 
 pub async fn was_generated_by<'a>(
     id: i32,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Vec<Activity>> {
-    use crate::persistence::schema::generation::{self, dsl};
-
-    let store = ctx.data_unchecked::<Store>();
+    let loader = ctx.data_unchecked::<DataLoader<RelationLoader>>();
 
-    let mut connection = store.pool.get()?;
-
-    let res = generation::table
-        .filter(dsl::generated_entity_id.eq(id))
-        .inner_join(crate::persistence::schema::activity::table)
-        .select(Activity::as_select())
-        .load::<Activity>(&mut connection)?;
-
-    Ok(res)
+    Ok(loader.load_one(GeneratedBy(id)).await?.unwrap_or_default())
 }
 
 pub async fn was_derived_from<'a>(
     id: i32,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Vec<Entity>> {
-    use crate::persistence::schema::{
-        derivation::{self, dsl},
-        entity as entitydsl,
-    };
-
-    let store = ctx.data_unchecked::<Store>();
-
-    let mut connection = store.pool.get()?;
+    let loader = ctx.data_unchecked::<DataLoader<RelationLoader>>();
 
-    let res = derivation::table
-        .filter(dsl::generated_entity_id.eq(id))
-        .inner_join(entitydsl::table.on(dsl::used_entity_id.eq(entitydsl::id)))
-        .select(Entity::as_select())
-        .load::<Entity>(&mut connection)?;
-
-    Ok(res)
+    Ok(loader
+        .load_one(DerivedFrom(id, None))
+        .await?
+        .unwrap_or_default())
 }
 
 pub async fn had_primary_source<'a>(
@@ -127,22 +84,9 @@ pub async fn load_attribute<'a>(
     external_id: &str,
     ctx: &Context<'a>,
 ) -> async_graphql::Result<Option<serde_json::Value>> {
-    use crate::persistence::schema::entity_attribute;
-
-    let store = ctx.data_unchecked::<Store>();
-
-    let mut connection = store.pool.get()?;
+    let loader = ctx.data_unchecked::<DataLoader<RelationLoader>>();
 
-    Ok(entity_attribute::table
-        .filter(
-            entity_attribute::entity_id
-                .eq(id)
-                .and(entity_attribute::typename.eq(external_id)),
-        )
-        .select(entity_attribute::value)
-        .first::<String>(&mut connection)
-        .optional()?
-        .as_deref()
-        .map(serde_json::from_str)
-        .transpose()?)
+    Ok(loader
+        .load_one(EntityAttribute(id, external_id.to_owned()))
+        .await?)
 }
@@ -0,0 +1,650 @@
+//! Explanations of a record's provenance as sentences, for readers who do not
+//! read PROV. Starting from the record, each agent, activity and entity of its
+//! provenance is described by one sentence, such as "Item X was generated by
+//! ItemManufactured 42" followed by "ItemManufactured 42 was performed by
+//! Contractor A acting for Contractor B".
+//! The sentence for records of a domain type can be replaced by a template
+//! with `{placeholder}`s for the record's relations and attributes.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    path::Path,
+};
+
+use async_graphql::{Context, SimpleObject};
+use common::prov::{
+    ActivityId, AgentId, ChronicleIri, DomaintypeId, EntityId, ExternalId, ExternalIdPart,
+    NamespaceId, ProvModel, Role,
+};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{info, instrument};
+
+use super::{readable_namespace, Store};
+
+/// The hops of lineage an entity's explanation follows, unless asked for more
+const DEFAULT_DEPTH: u32 = 3;
+
+/// The most hops of lineage an explanation follows
+const MAX_DEPTH: u32 = 10;
+
+#[derive(Error, Debug)]
+pub enum ExplainError {
+    #[error("Could not read explanation templates {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Explanation templates {path} are not valid: {source}")]
+    Invalid {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("Explanation template for {domaintype} has an unclosed placeholder")]
+    UnclosedPlaceholder { domaintype: String },
+}
+
+/// Templates for the sentences describing records, keyed by domain type
+#[derive(Debug, Clone, Default)]
+pub struct ExplainTemplates {
+    templates: BTreeMap<String, String>,
+}
+
+impl ExplainTemplates {
+    pub fn new(templates: BTreeMap<String, String>) -> Result<Self, ExplainError> {
+        for (domaintype, template) in &templates {
+            if !placeholders_closed(template) {
+                return Err(ExplainError::UnclosedPlaceholder {
+                    domaintype: domaintype.clone(),
+                });
+            }
+        }
+
+        Ok(Self { templates })
+    }
+
+    /// Read templates from a JSON object of domain types and templates
+    pub fn from_file(path: &Path) -> Result<Self, ExplainError> {
+        let json = std::fs::read_to_string(path).map_err(|source| ExplainError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let templates = serde_json::from_str(&json).map_err(|source| ExplainError::Invalid {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let templates = Self::new(templates)?;
+        info!(
+            templates = templates.templates.len(),
+            "Loaded explanation templates"
+        );
+
+        Ok(templates)
+    }
+
+    /// The template's sentence for a record of `domaintype`, if there is a
+    /// template for the type and `vars` has a value for each of its
+    /// placeholders
+    fn render(
+        &self,
+        domaintype: Option<&DomaintypeId>,
+        vars: &BTreeMap<String, String>,
+    ) -> Option<String> {
+        let template = self
+            .templates
+            .get(domaintype?.external_id_part().as_str())?;
+        let mut sentence = String::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = start + rest[start..].find('}')?;
+            let value = vars
+                .get(&rest[start + 1..end])
+                .filter(|value| !value.is_empty())?;
+            sentence.push_str(&rest[..start]);
+            sentence.push_str(value);
+            rest = &rest[end + 1..];
+        }
+        sentence.push_str(rest);
+
+        Some(sentence)
+    }
+}
+
+/// Whether each placeholder of `template` is closed
+fn placeholders_closed(template: &str) -> bool {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        match rest[start..].find('}') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// `a`, `a and b`, or `a, b and c`
+fn join(items: Vec<String>) -> String {
+    let mut items = items.into_iter().fold(vec![], |mut items, item| {
+        if !items.contains(&item) {
+            items.push(item);
+        }
+        items
+    });
+    match items.pop() {
+        Some(last) if !items.is_empty() => format!("{} and {last}", items.join(", ")),
+        Some(last) => last,
+        None => String::new(),
+    }
+}
+
+fn capitalize(sentence: String) -> String {
+    let mut chars = sentence.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => sentence,
+    }
+}
+
+fn label(kind: &str, domaintype: Option<&DomaintypeId>, external_id: &ExternalId) -> String {
+    match domaintype {
+        Some(domaintype) => format!("{} {external_id}", domaintype.external_id_part()),
+        None => format!("{kind} {external_id}"),
+    }
+}
+
+/// The values related to `id` in one of the model's relations, whatever
+/// namespace they are in
+fn related<'a, K: PartialEq, V>(
+    relation: &'a BTreeMap<(NamespaceId, K), BTreeSet<V>>,
+    id: &'a K,
+) -> impl Iterator<Item = &'a V> {
+    relation
+        .iter()
+        .filter(move |((_, key), _)| key == id)
+        .flat_map(|(_, values)| values)
+}
+
+fn attribute_vars(
+    vars: &mut BTreeMap<String, String>,
+    attributes: &BTreeMap<String, common::attributes::Attribute>,
+) {
+    for (name, attribute) in attributes {
+        let value = match &attribute.value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        vars.insert(format!("attributes.{name}"), value);
+    }
+}
+
+struct Explainer<'a> {
+    model: &'a ProvModel,
+    templates: &'a ExplainTemplates,
+}
+
+impl Explainer<'_> {
+    /// One sentence for each record of the provenance of `subject`, starting
+    /// with the subject and following its relations breadth first
+    fn explain(&self, subject: ChronicleIri) -> Vec<String> {
+        let mut sentences = vec![];
+        let mut visited = BTreeSet::new();
+        let mut queue = VecDeque::from([subject.clone()]);
+
+        while let Some(record) = queue.pop_front() {
+            if !visited.insert(record.to_string()) {
+                continue;
+            }
+            let described = match &record {
+                ChronicleIri::Entity(id) => self.entity(id),
+                ChronicleIri::Activity(id) => self.activity(id),
+                ChronicleIri::Agent(id) => self.agent(id, record == subject),
+                _ => None,
+            };
+            if let Some((sentence, next)) = described {
+                sentences.extend(sentence.map(capitalize));
+                queue.extend(next);
+            }
+        }
+
+        sentences
+    }
+
+    fn entity_label(&self, id: &EntityId) -> String {
+        let domaintype = self
+            .model
+            .entities
+            .iter()
+            .find(|((_, key), _)| key == id)
+            .and_then(|(_, entity)| entity.domaintypeid.as_ref());
+        label("Entity", domaintype, id.external_id_part())
+    }
+
+    fn activity_label(&self, id: &ActivityId) -> String {
+        let domaintype = self
+            .model
+            .activities
+            .iter()
+            .find(|((_, key), _)| key == id)
+            .and_then(|(_, activity)| activity.domaintypeid.as_ref());
+        label("Activity", domaintype, id.external_id_part())
+    }
+
+    fn agent_label(&self, id: &AgentId) -> String {
+        let domaintype = self
+            .model
+            .agents
+            .iter()
+            .find(|((_, key), _)| key == id)
+            .and_then(|(_, agent)| agent.domaintypeid.as_ref());
+        label("Agent", domaintype, id.external_id_part())
+    }
+
+    /// The agent, in the role it played, and the agents it acted for
+    fn agent_phrase(&self, id: &AgentId, role: Option<&Role>) -> String {
+        let mut phrase = self.agent_label(id);
+        if let Some(role) = role.filter(|role| !role.0.is_empty()) {
+            phrase = format!("{phrase} as {role}");
+        }
+        let responsible = related(&self.model.acted_on_behalf_of, id)
+            .map(|delegation| self.agent_label(&delegation.responsible_id))
+            .collect::<Vec<_>>();
+        if !responsible.is_empty() {
+            phrase = format!("{phrase} acting for {}", join(responsible));
+        }
+        phrase
+    }
+
+    fn entity(&self, id: &EntityId) -> Option<(Option<String>, Vec<ChronicleIri>)> {
+        let (_, entity) = self.model.entities.iter().find(|((_, key), _)| key == id)?;
+
+        let generated_by = related(&self.model.generation, id)
+            .map(|generation| generation.activity_id.clone())
+            .collect::<Vec<_>>();
+        let derived_from = related(&self.model.derivation, id)
+            .map(|derivation| derivation.used_id.clone())
+            .collect::<Vec<_>>();
+        let attributions = related(&self.model.attribution, id).collect::<Vec<_>>();
+
+        let label = self.entity_label(id);
+        let mut vars = BTreeMap::from([
+            ("id".to_owned(), id.external_id_part().to_string()),
+            ("label".to_owned(), label.clone()),
+            (
+                "generatedBy".to_owned(),
+                join(
+                    generated_by
+                        .iter()
+                        .map(|id| self.activity_label(id))
+                        .collect(),
+                ),
+            ),
+            (
+                "derivedFrom".to_owned(),
+                join(
+                    derived_from
+                        .iter()
+                        .map(|id| self.entity_label(id))
+                        .collect(),
+                ),
+            ),
+            (
+                "attributedTo".to_owned(),
+                join(
+                    attributions
+                        .iter()
+                        .map(|attribution| {
+                            self.agent_phrase(&attribution.agent_id, attribution.role.as_ref())
+                        })
+                        .collect(),
+                ),
+            ),
+        ]);
+        attribute_vars(&mut vars, &entity.attributes);
+
+        let sentence = self
+            .templates
+            .render(entity.domaintypeid.as_ref(), &vars)
+            .unwrap_or_else(|| {
+                let clauses = [
+                    ("was generated by", &vars["generatedBy"]),
+                    ("was derived from", &vars["derivedFrom"]),
+                    ("is attributed to", &vars["attributedTo"]),
+                ]
+                .into_iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(clause, value)| format!("{clause} {value}"))
+                .collect::<Vec<_>>();
+                if clauses.is_empty() {
+                    format!("{label} has no recorded provenance.")
+                } else {
+                    format!("{label} {}.", join(clauses))
+                }
+            });
+
+        let next = generated_by
+            .into_iter()
+            .map(ChronicleIri::from)
+            .chain(derived_from.into_iter().map(ChronicleIri::from))
+            .chain(
+                attributions
+                    .into_iter()
+                    .map(|attribution| attribution.agent_id.clone().into()),
+            )
+            .collect();
+
+        Some((Some(sentence), next))
+    }
+
+    fn activity(&self, id: &ActivityId) -> Option<(Option<String>, Vec<ChronicleIri>)> {
+        let (_, activity) = self
+            .model
+            .activities
+            .iter()
+            .find(|((_, key), _)| key == id)?;
+
+        let associations = related(&self.model.association, id).collect::<Vec<_>>();
+        let used = related(&self.model.usage, id)
+            .map(|usage| usage.entity_id.clone())
+            .collect::<Vec<_>>();
+        let informed_by = related(&self.model.was_informed_by, id)
+            .cloned()
+            .collect::<Vec<_>>();
+        let generated = related(&self.model.generated, id)
+            .map(|generated| self.entity_label(&generated.entity_id))
+            .collect::<Vec<_>>();
+        let time = |time: Option<chrono::DateTime<chrono::Utc>>| {
+            time.map(|time| time.format("%-d %B %Y %H:%M UTC").to_string())
+                .unwrap_or_default()
+        };
+
+        let label = self.activity_label(id);
+        let mut vars = BTreeMap::from([
+            ("id".to_owned(), id.external_id_part().to_string()),
+            ("label".to_owned(), label.clone()),
+            (
+                "associatedWith".to_owned(),
+                join(
+                    associations
+                        .iter()
+                        .map(|association| {
+                            self.agent_phrase(&association.agent_id, association.role.as_ref())
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "used".to_owned(),
+                join(used.iter().map(|id| self.entity_label(id)).collect()),
+            ),
+            ("generated".to_owned(), join(generated)),
+            (
+                "informedBy".to_owned(),
+                join(
+                    informed_by
+                        .iter()
+                        .map(|id| self.activity_label(id))
+                        .collect(),
+                ),
+            ),
+            ("started".to_owned(), time(activity.started)),
+            ("ended".to_owned(), time(activity.ended)),
+        ]);
+        attribute_vars(&mut vars, &activity.attributes);
+
+        let sentence = self
+            .templates
+            .render(activity.domaintypeid.as_ref(), &vars)
+            .unwrap_or_else(|| {
+                let clauses = [
+                    ("was performed by", &vars["associatedWith"]),
+                    ("used", &vars["used"]),
+                    ("generated", &vars["generated"]),
+                    ("was informed by", &vars["informedBy"]),
+                    ("started on", &vars["started"]),
+                    ("ended on", &vars["ended"]),
+                ]
+                .into_iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(clause, value)| format!("{clause} {value}"))
+                .collect::<Vec<_>>();
+                if clauses.is_empty() {
+                    format!("{label} has no recorded provenance.")
+                } else {
+                    format!("{label} {}.", join(clauses))
+                }
+            });
+
+        let next = associations
+            .into_iter()
+            .map(|association| ChronicleIri::from(association.agent_id.clone()))
+            .chain(used.into_iter().map(ChronicleIri::from))
+            .chain(informed_by.into_iter().map(ChronicleIri::from))
+            .collect();
+
+        Some((Some(sentence), next))
+    }
+
+    /// Agents other than the subject are described only when they acted for
+    /// another, or their type has a template
+    fn agent(&self, id: &AgentId, subject: bool) -> Option<(Option<String>, Vec<ChronicleIri>)> {
+        let (_, agent) = self.model.agents.iter().find(|((_, key), _)| key == id)?;
+
+        let responsible = related(&self.model.acted_on_behalf_of, id)
+            .map(|delegation| delegation.responsible_id.clone())
+            .collect::<Vec<_>>();
+
+        let label = self.agent_label(id);
+        let mut vars = BTreeMap::from([
+            ("id".to_owned(), id.external_id_part().to_string()),
+            ("label".to_owned(), label.clone()),
+            (
+                "actedOnBehalfOf".to_owned(),
+                join(responsible.iter().map(|id| self.agent_label(id)).collect()),
+            ),
+        ]);
+        attribute_vars(&mut vars, &agent.attributes);
+
+        let sentence = self
+            .templates
+            .render(agent.domaintypeid.as_ref(), &vars)
+            .or_else(|| match &vars["actedOnBehalfOf"] {
+                responsible if !responsible.is_empty() => {
+                    Some(format!("{label} acted on behalf of {responsible}."))
+                }
+                _ if subject => Some(format!("{label} has no recorded provenance.")),
+                _ => None,
+            });
+
+        let next = responsible.into_iter().map(ChronicleIri::from).collect();
+
+        Some((sentence, next))
+    }
+}
+
+/// # `Explanation`
+///
+/// The provenance of `subject` as sentences, the first describing the subject
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Explanation {
+    pub subject: String,
+    pub sentences: Vec<String>,
+    /// The sentences as one paragraph
+    pub text: String,
+}
+
+/// Explain the provenance of the given agent, activity or entity. The
+/// lineage of an entity is followed for at most `depth` hops, by default 3.
+#[instrument(skip(ctx))]
+pub async fn explain<'a>(
+    ctx: &Context<'a>,
+    agent_id: Option<AgentId>,
+    activity_id: Option<ActivityId>,
+    entity_id: Option<EntityId>,
+    depth: Option<u32>,
+    namespace: Option<String>,
+) -> async_graphql::Result<Explanation> {
+    let subject = match (agent_id, activity_id, entity_id) {
+        (Some(id), None, None) => ChronicleIri::from(id),
+        (None, Some(id), None) => ChronicleIri::from(id),
+        (None, None, Some(id)) => ChronicleIri::from(id),
+        _ => {
+            return Err(async_graphql::Error::new(
+                "Give exactly one of agentId, activityId and entityId",
+            ))
+        }
+    };
+    let store = ctx.data_unchecked::<Store>().persistence()?;
+    let ns = ExternalId::from(readable_namespace(ctx, namespace).await?);
+    let depth = depth.unwrap_or(DEFAULT_DEPTH).min(MAX_DEPTH);
+
+    let model = store.read_only(|connection| {
+        store.prov_model_for_explanation(connection, &subject, &ns, depth)
+    })?;
+
+    let default_templates = ExplainTemplates::default();
+    let sentences = Explainer {
+        model: &model,
+        templates: ctx
+            .data_opt::<ExplainTemplates>()
+            .unwrap_or(&default_templates),
+    }
+    .explain(subject.clone());
+
+    Ok(Explanation {
+        subject: subject.to_string(),
+        text: sentences.join(" "),
+        sentences,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use common::{
+        attributes::{Attribute, Attributes},
+        prov::{
+            operations::{
+                ActsOnBehalfOf, ChronicleOperation, EntityDerive, SetAttributes, WasAssociatedWith,
+                WasGeneratedBy,
+            },
+            ActivityId, AgentId, ChronicleIri, DomaintypeId, EntityId, NamespaceId, ProvModel,
+            Role,
+        },
+    };
+    use uuid::Uuid;
+
+    use super::{ExplainTemplates, Explainer};
+
+    #[test]
+    fn provenance_is_explained_with_templates_for_domain_types() {
+        let ns = NamespaceId::from_external_id("testns", Uuid::new_v4());
+        let item = EntityId::from_external_id("X");
+        let manufactured = ActivityId::from_external_id("42");
+        let contractor = AgentId::from_external_id("A");
+        let client = AgentId::from_external_id("B");
+        let attributes = |domaintype: &str, attributes: Vec<Attribute>| Attributes {
+            typ: Some(DomaintypeId::from_external_id(domaintype)),
+            attributes: attributes
+                .into_iter()
+                .map(|attribute| (attribute.typ.clone(), attribute))
+                .collect(),
+        };
+
+        let model = ProvModel::from_tx(&[
+            ChronicleOperation::SetAttributes(SetAttributes::Entity {
+                namespace: ns.clone(),
+                id: item.clone(),
+                attributes: attributes(
+                    "Item",
+                    vec![Attribute::new("serial", serde_json::json!("SN-1"))],
+                ),
+            }),
+            ChronicleOperation::SetAttributes(SetAttributes::Activity {
+                namespace: ns.clone(),
+                id: manufactured.clone(),
+                attributes: attributes("ItemManufactured", vec![]),
+            }),
+            ChronicleOperation::SetAttributes(SetAttributes::Agent {
+                namespace: ns.clone(),
+                id: contractor.clone(),
+                attributes: attributes("Contractor", vec![]),
+            }),
+            ChronicleOperation::WasGeneratedBy(WasGeneratedBy {
+                namespace: ns.clone(),
+                id: item.clone(),
+                activity: manufactured.clone(),
+            }),
+            ChronicleOperation::WasAssociatedWith(WasAssociatedWith::new(
+                &ns,
+                &manufactured,
+                &contractor,
+                None,
+            )),
+            ChronicleOperation::AgentActsOnBehalfOf(ActsOnBehalfOf::new(
+                &ns,
+                &client,
+                &contractor,
+                None,
+                Some(Role::from("supplier")),
+            )),
+            ChronicleOperation::EntityDerive(EntityDerive {
+                namespace: ns.clone(),
+                id: item.clone(),
+                used_id: EntityId::from_external_id("design"),
+                activity_id: None,
+                typ: common::prov::operations::DerivationType::None,
+            }),
+        ])
+        .unwrap();
+
+        let default_templates = ExplainTemplates::default();
+        let explainer = Explainer {
+            model: &model,
+            templates: &default_templates,
+        };
+        assert_eq!(
+            explainer.explain(ChronicleIri::from(item.clone())),
+            vec![
+                "Item X was generated by ItemManufactured 42 and was derived from Entity design.",
+                "ItemManufactured 42 was performed by Contractor A acting for Agent B.",
+                "Entity design has no recorded provenance.",
+                "Contractor A acted on behalf of Agent B.",
+            ]
+        );
+
+        let templates = ExplainTemplates::new(BTreeMap::from([
+            (
+                "Item".to_owned(),
+                "{label} ({attributes.serial}) was manufactured by {generatedBy}.".to_owned(),
+            ),
+            (
+                "ItemManufactured".to_owned(),
+                "{label} was certified by {certifiedBy}.".to_owned(),
+            ),
+        ]))
+        .unwrap();
+        let explainer = Explainer {
+            model: &model,
+            templates: &templates,
+        };
+        assert_eq!(
+            explainer.explain(ChronicleIri::from(item)),
+            vec![
+                "Item X (SN-1) was manufactured by ItemManufactured 42.",
+                "ItemManufactured 42 was performed by Contractor A acting for Agent B.",
+                "Entity design has no recorded provenance.",
+                "Contractor A acted on behalf of Agent B.",
+            ]
+        );
+
+        assert!(ExplainTemplates::new(BTreeMap::from([(
+            "Item".to_owned(),
+            "{label was made".to_owned()
+        )]))
+        .is_err());
+    }
+}
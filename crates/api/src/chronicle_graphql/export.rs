@@ -0,0 +1,527 @@
+//! Exports of a namespace's provenance that run in the background, so that
+//! large namespaces are not bound by request timeouts. A client starts a job,
+//! then polls its status or supplies a callback url to be told when it ends.
+
+use std::path::PathBuf;
+
+use async_graphql::{Context, Enum, SimpleObject, ID};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use common::prov::{to_json_ld::ToJson, CompactionError, ExternalId};
+use diesel::prelude::*;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+use super::{readable_namespace, Store};
+use crate::{persistence::schema::export_job, ReadFrom, StoreError};
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Exports are not enabled, start the api with --export-dir")]
+    NotEnabled,
+
+    #[error("No export job with id {0}")]
+    NotFound(String),
+
+    #[error("Export job {0} has already finished")]
+    Finished(String),
+
+    #[error("Unrecognized export job {0} in store: {1}")]
+    InvalidRecord(&'static str, String),
+
+    #[error("Invalid callback url: {0}")]
+    Callback(#[from] url::ParseError),
+
+    #[error("Could not write export: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Could not serialize export: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Could not compact export: {0}")]
+    Compaction(#[from] CompactionError),
+
+    #[error("Export task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+
+    #[error("Store: {0}")]
+    Store(#[from] StoreError),
+
+    #[error("Database operation failed: {0}")]
+    Db(#[from] diesel::result::Error),
+
+    #[error("Connection pool error: {0}")]
+    DbPool(#[from] r2d2::Error),
+}
+
+/// Where export artifacts are written, exports are disabled unless configured
+#[derive(Debug, Clone)]
+pub struct ExportConf {
+    pub directory: PathBuf,
+}
+
+impl ExportConf {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+#[derive(Enum, Serialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExportFormat {
+    /// Compacted JSON-LD, as served by the `/data` endpoints
+    JsonLd,
+    /// Expanded JSON-LD
+    ExpandedJsonLd,
+}
+
+impl ExportFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::JsonLd => "json-ld",
+            ExportFormat::ExpandedJsonLd => "expanded-json-ld",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, ExportError> {
+        match s {
+            "json-ld" => Ok(ExportFormat::JsonLd),
+            "expanded-json-ld" => Ok(ExportFormat::ExpandedJsonLd),
+            other => Err(ExportError::InvalidRecord("format", other.to_owned())),
+        }
+    }
+}
+
+#[derive(Enum, Serialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExportStatus {
+    Queued,
+    Running,
+    Complete,
+    Failed,
+    Cancelled,
+}
+
+impl ExportStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ExportStatus::Queued => "queued",
+            ExportStatus::Running => "running",
+            ExportStatus::Complete => "complete",
+            ExportStatus::Failed => "failed",
+            ExportStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, ExportError> {
+        match s {
+            "queued" => Ok(ExportStatus::Queued),
+            "running" => Ok(ExportStatus::Running),
+            "complete" => Ok(ExportStatus::Complete),
+            "failed" => Ok(ExportStatus::Failed),
+            "cancelled" => Ok(ExportStatus::Cancelled),
+            other => Err(ExportError::InvalidRecord("status", other.to_owned())),
+        }
+    }
+}
+
+#[derive(Queryable)]
+struct ExportJobRecord {
+    id: String,
+    namespace: String,
+    format: String,
+    status: String,
+    modified_since: Option<NaiveDateTime>,
+    callback: Option<String>,
+    artifact: Option<String>,
+    error: Option<String>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+/// # `ExportJob`
+///
+/// A background export of a namespace. `artifact` is the path of the written
+/// file once the job is `COMPLETE`, and `error` the reason a `FAILED` job failed.
+#[derive(SimpleObject, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportJob {
+    pub id: ID,
+    pub namespace: String,
+    pub format: ExportFormat,
+    pub status: ExportStatus,
+    pub modified_since: Option<DateTime<Utc>>,
+    pub artifact: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[graphql(skip)]
+    #[serde(skip)]
+    callback: Option<String>,
+}
+
+impl TryFrom<ExportJobRecord> for ExportJob {
+    type Error = ExportError;
+
+    fn try_from(record: ExportJobRecord) -> Result<Self, Self::Error> {
+        Ok(ExportJob {
+            id: record.id.into(),
+            namespace: record.namespace,
+            format: ExportFormat::parse(&record.format)?,
+            status: ExportStatus::parse(&record.status)?,
+            modified_since: record
+                .modified_since
+                .map(|x| DateTime::from_naive_utc_and_offset(x, Utc)),
+            artifact: record.artifact,
+            error: record.error,
+            created_at: DateTime::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::from_naive_utc_and_offset(record.updated_at, Utc),
+            callback: record.callback,
+        })
+    }
+}
+
+impl Store {
+    #[instrument(skip(self))]
+    fn insert_export_job(
+        &self,
+        namespace: &str,
+        format: ExportFormat,
+        modified_since: Option<NaiveDateTime>,
+        callback: Option<&str>,
+    ) -> Result<String, ExportError> {
+        use crate::persistence::schema::namespace;
+
+        self.transaction(|connection| {
+            let namespace_id = namespace::table
+                .filter(namespace::external_id.eq(namespace))
+                .select(namespace::id)
+                .first::<i32>(connection)
+                .optional()?
+                .ok_or(StoreError::InvalidNamespace)?;
+
+            let id = Uuid::new_v4().to_string();
+            let now = Utc::now().naive_utc();
+
+            diesel::insert_into(export_job::table)
+                .values((
+                    export_job::id.eq(&id),
+                    export_job::namespace_id.eq(namespace_id),
+                    export_job::format.eq(format.as_str()),
+                    export_job::status.eq(ExportStatus::Queued.as_str()),
+                    export_job::modified_since.eq(modified_since),
+                    export_job::callback.eq(callback),
+                    export_job::created_at.eq(now),
+                    export_job::updated_at.eq(now),
+                ))
+                .execute(connection)?;
+
+            Ok(id)
+        })
+    }
+
+    fn export_job(&self, id: &str) -> Result<Option<ExportJob>, ExportError> {
+        use crate::persistence::schema::namespace;
+
+        // Jobs change state outside the ledger, so a replica may not have seen
+        // their latest state
+        self.read_only_from(ReadFrom::Primary, |connection| {
+            export_job::table
+                .inner_join(namespace::table)
+                .filter(export_job::id.eq(id))
+                .select((
+                    export_job::id,
+                    namespace::external_id,
+                    export_job::format,
+                    export_job::status,
+                    export_job::modified_since,
+                    export_job::callback,
+                    export_job::artifact,
+                    export_job::error,
+                    export_job::created_at,
+                    export_job::updated_at,
+                ))
+                .first::<ExportJobRecord>(connection)
+                .optional()?
+                .map(ExportJob::try_from)
+                .transpose()
+        })
+    }
+
+    /// Move a job from one of `from` to `to`, returning false if the job was
+    /// not in any of those states, as when it has been cancelled
+    fn transition_export_job(
+        &self,
+        id: &str,
+        from: &[ExportStatus],
+        to: ExportStatus,
+        artifact: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<bool, ExportError> {
+        let updated = self.transaction(|connection| {
+            diesel::update(
+                export_job::table
+                    .filter(export_job::id.eq(id))
+                    .filter(export_job::status.eq_any(from.iter().map(|status| status.as_str()))),
+            )
+            .set((
+                export_job::status.eq(to.as_str()),
+                export_job::artifact.eq(artifact),
+                export_job::error.eq(error),
+                export_job::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(connection)
+            .map_err(ExportError::from)
+        })?;
+
+        Ok(updated > 0)
+    }
+
+    /// Jobs that were queued or running when the api last stopped
+    fn unfinished_export_jobs(&self) -> Result<Vec<String>, ExportError> {
+        self.read_only_from(ReadFrom::Primary, |connection| {
+            Ok(export_job::table
+                .filter(export_job::status.eq_any([
+                    ExportStatus::Queued.as_str(),
+                    ExportStatus::Running.as_str(),
+                ]))
+                .order(export_job::created_at)
+                .select(export_job::id)
+                .load(connection)?)
+        })
+    }
+}
+
+/// Write the namespace's provenance to a file in the export directory,
+/// returning the file's path, or `None` if the job was cancelled
+async fn write_artifact(
+    store: &Store,
+    conf: &ExportConf,
+    job: &ExportJob,
+) -> Result<Option<String>, ExportError> {
+    // Jobs resumed at startup run on an unscoped store, so the export is
+    // scoped to the job's own namespace here
+    let persistence = store.for_tenant(&job.namespace).persistence()?;
+    let namespace = ExternalId::from(&job.namespace);
+    let since = job.modified_since.map(|since| since.naive_utc());
+
+    let model = tokio::task::spawn_blocking(move || {
+        persistence.read_only(|connection| {
+            let (namespace, _) = persistence.namespace_by_external_id(connection, &namespace)?;
+            persistence.prov_model_for_namespace_since(connection, &namespace, since)
+        })
+    })
+    .await??;
+
+    let json = match job.format {
+        ExportFormat::JsonLd => model.to_json().compact().await?,
+        ExportFormat::ExpandedJsonLd => model.to_json().0,
+    };
+
+    let directory = conf.directory.clone();
+    let artifact = directory.join(format!("{}.jsonld", &*job.id));
+    let partial = artifact.with_extension("jsonld.partial");
+    let contents = serde_json::to_vec(&json)?;
+
+    {
+        let partial = partial.clone();
+        tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(directory)?;
+            std::fs::write(partial, contents)
+        })
+        .await??;
+    }
+
+    // A cancellation while the export was being assembled discards it
+    if store.export_job(&job.id)?.map(|job| job.status) != Some(ExportStatus::Running) {
+        std::fs::remove_file(&partial)?;
+        return Ok(None);
+    }
+
+    std::fs::rename(&partial, &artifact)?;
+
+    Ok(Some(artifact.to_string_lossy().into_owned()))
+}
+
+/// POST the finished job to its callback url, failures are only logged as the
+/// job's status can still be polled
+async fn notify(job: &ExportJob) {
+    if let Some(callback) = &job.callback {
+        let body = match serde_json::to_string(job) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!(id = %&*job.id, ?e, "Could not serialize export job for callback");
+                return;
+            }
+        };
+
+        if let Err(e) = reqwest::Client::new()
+            .post(callback)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            warn!(id = %&*job.id, %callback, ?e, "Export job callback failed");
+        }
+    }
+}
+
+#[instrument(skip(store, conf))]
+async fn run_export(store: Store, conf: ExportConf, id: String) {
+    let run = async {
+        if !store.transition_export_job(
+            &id,
+            &[ExportStatus::Queued],
+            ExportStatus::Running,
+            None,
+            None,
+        )? {
+            return Ok(None);
+        }
+
+        let job = store
+            .export_job(&id)?
+            .ok_or_else(|| ExportError::NotFound(id.clone()))?;
+
+        info!(namespace = %job.namespace, format = ?job.format, "Starting export");
+
+        // Jobs cancelled part way through have already been reported
+        let finished = match write_artifact(&store, &conf, &job).await {
+            Ok(Some(artifact)) => store.transition_export_job(
+                &id,
+                &[ExportStatus::Running],
+                ExportStatus::Complete,
+                Some(&artifact),
+                None,
+            )?,
+            Ok(None) => false,
+            Err(e) => {
+                error!(?e, "Export failed");
+                store.transition_export_job(
+                    &id,
+                    &[ExportStatus::Running],
+                    ExportStatus::Failed,
+                    None,
+                    Some(&e.to_string()),
+                )?
+            }
+        };
+
+        if finished {
+            store.export_job(&id)
+        } else {
+            Ok(None)
+        }
+    };
+
+    match run.await {
+        Ok(Some(job)) => notify(&job).await,
+        Ok(None) => {}
+        Err(e) => error!(?e, "Could not record export job outcome"),
+    }
+}
+
+/// Restart jobs left unfinished by a previous run of the api, jobs that were
+/// running are started again from the beginning
+pub fn resume_exports(store: Store, conf: ExportConf) -> Result<(), ExportError> {
+    for id in store.unfinished_export_jobs()? {
+        store.transition_export_job(
+            &id,
+            &[ExportStatus::Running],
+            ExportStatus::Queued,
+            None,
+            None,
+        )?;
+        info!(%id, "Resuming export job");
+        tokio::spawn(run_export(store.clone(), conf.clone(), id));
+    }
+
+    Ok(())
+}
+
+fn export_conf<'a>(ctx: &Context<'a>) -> Result<&'a ExportConf, ExportError> {
+    ctx.data_opt::<ExportConf>().ok_or(ExportError::NotEnabled)
+}
+
+/// The job with `id`, once the namespace policy, if configured, permits the
+/// calling identity to read the job's namespace
+async fn readable_export_job<'a>(
+    ctx: &Context<'a>,
+    id: &str,
+) -> async_graphql::Result<Option<ExportJob>> {
+    match ctx.data_unchecked::<Store>().export_job(id)? {
+        Some(job) => {
+            readable_namespace(ctx, Some(job.namespace.clone())).await?;
+            Ok(Some(job))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Queue an export of the namespace, limited to the records updated at or
+/// after `modified_since` if supplied
+pub async fn start_export<'a>(
+    ctx: &Context<'a>,
+    namespace: Option<String>,
+    format: ExportFormat,
+    modified_since: Option<DateTime<Utc>>,
+    callback: Option<String>,
+) -> async_graphql::Result<ExportJob> {
+    let conf = export_conf(ctx)?;
+    let store = ctx.data_unchecked::<Store>();
+    let namespace: String = readable_namespace(ctx, namespace).await?;
+
+    if let Some(callback) = &callback {
+        url::Url::parse(callback).map_err(ExportError::from)?;
+    }
+
+    let id = store.insert_export_job(
+        &namespace,
+        format,
+        modified_since.map(|since| since.naive_utc()),
+        callback.as_deref(),
+    )?;
+
+    tokio::spawn(run_export(store.clone(), conf.clone(), id.clone()));
+
+    Ok(store.export_job(&id)?.ok_or(ExportError::NotFound(id))?)
+}
+
+/// Cancel a queued or running export, any partially written artifact is
+/// discarded
+pub async fn cancel_export<'a>(ctx: &Context<'a>, id: ID) -> async_graphql::Result<ExportJob> {
+    let store = ctx.data_unchecked::<Store>();
+
+    readable_export_job(ctx, &id)
+        .await?
+        .ok_or_else(|| ExportError::NotFound(id.to_string()))?;
+
+    let cancelled = store.transition_export_job(
+        &id,
+        &[ExportStatus::Queued, ExportStatus::Running],
+        ExportStatus::Cancelled,
+        None,
+        None,
+    )?;
+
+    let job = store
+        .export_job(&id)?
+        .ok_or_else(|| ExportError::NotFound(id.to_string()))?;
+
+    if !cancelled {
+        return Err(ExportError::Finished(id.to_string()).into());
+    }
+
+    notify(&job).await;
+
+    Ok(job)
+}
+
+pub async fn export_job<'a>(ctx: &Context<'a>, id: ID) -> async_graphql::Result<Option<ExportJob>> {
+    readable_export_job(ctx, &id).await
+}
@@ -0,0 +1,143 @@
+//! Entity resolution for Apollo Federation, so that Chronicle can be composed
+//! as a subgraph and its agents, activities and entities extended by others.
+//! Each type is keyed by its external id and the external id of its
+//! namespace, as `@key(fields: "externalId namespace { externalId }")`.
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use async_graphql::{Context, InputObject};
+use common::prov::{ActivityId, AgentId, EntityId};
+use thiserror::Error;
+
+use super::{query, Activity, Agent, Entity};
+
+#[derive(Error, Debug)]
+pub enum FederationError {
+    #[error("Federation of {0} is not enabled, start the api with --federate {0}")]
+    NotEnabled(FederatedKind),
+
+    #[error("Unrecognized kind {0}, expected agent, activity or entity")]
+    UnknownKind(String),
+}
+
+/// The kinds of record that a gateway may resolve by key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FederatedKind {
+    Agent,
+    Activity,
+    Entity,
+}
+
+impl fmt::Display for FederatedKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FederatedKind::Agent => "agent",
+            FederatedKind::Activity => "activity",
+            FederatedKind::Entity => "entity",
+        })
+    }
+}
+
+impl FromStr for FederatedKind {
+    type Err = FederationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "agent" => Ok(FederatedKind::Agent),
+            "activity" => Ok(FederatedKind::Activity),
+            "entity" => Ok(FederatedKind::Entity),
+            _ => Err(FederationError::UnknownKind(s.to_owned())),
+        }
+    }
+}
+
+/// The kinds of record exposed as federated entities, none unless configured
+#[derive(Debug, Clone, Default)]
+pub struct FederationConf {
+    pub kinds: BTreeSet<FederatedKind>,
+}
+
+impl FederationConf {
+    pub fn new(kinds: impl IntoIterator<Item = FederatedKind>) -> Self {
+        Self {
+            kinds: kinds.into_iter().collect(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.kinds.is_empty()
+    }
+}
+
+/// The namespace part of a federated entity's key
+#[derive(InputObject)]
+pub struct NamespaceKey {
+    pub external_id: String,
+}
+
+fn ensure_federated(ctx: &Context<'_>, kind: FederatedKind) -> Result<(), FederationError> {
+    match ctx.data_opt::<FederationConf>() {
+        Some(conf) if conf.kinds.contains(&kind) => Ok(()),
+        _ => Err(FederationError::NotEnabled(kind)),
+    }
+}
+
+/// Resolve an agent by key, if it has the domain type `domaintype`, which is
+/// `None` for a `ProvAgent`
+pub async fn agent<'a>(
+    ctx: &Context<'a>,
+    external_id: String,
+    namespace: NamespaceKey,
+    domaintype: Option<&str>,
+) -> async_graphql::Result<Option<Agent>> {
+    ensure_federated(ctx, FederatedKind::Agent)?;
+
+    let agent = query::agent_by_id(
+        ctx,
+        AgentId::from_external_id(external_id),
+        Some(namespace.external_id),
+    )
+    .await?;
+
+    Ok(agent.filter(|agent| agent.domaintype.as_deref() == domaintype))
+}
+
+/// Resolve an activity by key, if it has the domain type `domaintype`, which
+/// is `None` for a `ProvActivity`
+pub async fn activity<'a>(
+    ctx: &Context<'a>,
+    external_id: String,
+    namespace: NamespaceKey,
+    domaintype: Option<&str>,
+) -> async_graphql::Result<Option<Activity>> {
+    ensure_federated(ctx, FederatedKind::Activity)?;
+
+    let activity = query::activity_by_id(
+        ctx,
+        ActivityId::from_external_id(external_id),
+        Some(namespace.external_id),
+    )
+    .await?;
+
+    Ok(activity.filter(|activity| activity.domaintype.as_deref() == domaintype))
+}
+
+/// Resolve an entity by key, if it has the domain type `domaintype`, which is
+/// `None` for a `ProvEntity`
+pub async fn entity<'a>(
+    ctx: &Context<'a>,
+    external_id: String,
+    namespace: NamespaceKey,
+    domaintype: Option<&str>,
+) -> async_graphql::Result<Option<Entity>> {
+    ensure_federated(ctx, FederatedKind::Entity)?;
+
+    let entity = query::entity_by_id(
+        ctx,
+        EntityId::from_external_id(external_id),
+        Some(namespace.external_id),
+    )
+    .await?;
+
+    Ok(entity.filter(|entity| entity.domaintype.as_deref() == domaintype))
+}
@@ -0,0 +1,156 @@
+//! Limits on the depth and complexity of GraphQL queries, so that a query
+//! recursing through provenance cannot exhaust the api. Queries over either
+//! limit are refused before they run, with an error whose `code` extension is
+//! `QUERY_TOO_DEEP` or `QUERY_TOO_COMPLEX`, and whose `limit` extension is the
+//! limit that was exceeded.
+
+use std::sync::Arc;
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextRequest},
+    ObjectType, Response, SchemaBuilder, ServerError, SubscriptionType,
+};
+
+/// The messages async-graphql refuses queries over its limits with
+const TOO_DEEP: &str = "Query is nested too deep.";
+const TOO_COMPLEX: &str = "Query is too complex.";
+
+/// The deepest and most complex queries the api will run, each unlimited if
+/// not set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueryLimits {
+    pub max_depth: Option<usize>,
+    pub max_complexity: Option<usize>,
+}
+
+impl QueryLimits {
+    /// Apply the limits to the schema being built
+    pub fn apply<Query, Mutation, Subscription>(
+        self,
+        mut schema: SchemaBuilder<Query, Mutation, Subscription>,
+    ) -> SchemaBuilder<Query, Mutation, Subscription>
+    where
+        Query: ObjectType + 'static,
+        Mutation: ObjectType + 'static,
+        Subscription: SubscriptionType + 'static,
+    {
+        if let Some(max_depth) = self.max_depth {
+            schema = schema.limit_depth(max_depth);
+        }
+        if let Some(max_complexity) = self.max_complexity {
+            schema = schema.limit_complexity(max_complexity);
+        }
+        if self.max_depth.is_some() || self.max_complexity.is_some() {
+            schema = schema.extension(self);
+        }
+        schema
+    }
+
+    /// Add the `code` and `limit` extensions to an error refusing a query
+    /// over a limit
+    fn structure(&self, error: &mut ServerError) {
+        let (code, limit) = match error.message.as_str() {
+            TOO_DEEP => ("QUERY_TOO_DEEP", self.max_depth),
+            TOO_COMPLEX => ("QUERY_TOO_COMPLEX", self.max_complexity),
+            _ => return,
+        };
+
+        let extensions = error.extensions.get_or_insert_with(Default::default);
+        extensions.set("code", code);
+        if let Some(limit) = limit {
+            extensions.set("limit", limit as u64);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for QueryLimits {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let mut response = next.run(ctx).await;
+        for error in response.errors.iter_mut() {
+            self.structure(error);
+        }
+        response
+    }
+}
+
+impl ExtensionFactory for QueryLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema, Value};
+
+    use super::QueryLimits;
+
+    #[derive(Clone, Copy)]
+    struct Node;
+
+    #[Object]
+    impl Node {
+        async fn child(&self) -> Node {
+            Node
+        }
+
+        async fn value(&self) -> i32 {
+            1
+        }
+    }
+
+    async fn error_code(limits: QueryLimits, query: &str) -> Option<(Value, Value)> {
+        let schema = limits
+            .apply(Schema::build(Node, EmptyMutation, EmptySubscription))
+            .finish();
+        let response = schema.execute(query).await;
+        response.errors.first().map(|error| {
+            let extensions = error.extensions.as_ref().unwrap();
+            (
+                extensions.get("code").unwrap().clone(),
+                extensions.get("limit").unwrap().clone(),
+            )
+        })
+    }
+
+    #[tokio::test]
+    async fn queries_over_a_limit_are_refused_with_its_code() {
+        let query = "{ child { child { child { value } } } }";
+
+        assert_eq!(error_code(QueryLimits::default(), query).await, None);
+        assert_eq!(
+            error_code(
+                QueryLimits {
+                    max_depth: Some(2),
+                    max_complexity: None,
+                },
+                query
+            )
+            .await,
+            Some((Value::from("QUERY_TOO_DEEP"), Value::from(2u64)))
+        );
+        assert_eq!(
+            error_code(
+                QueryLimits {
+                    max_depth: None,
+                    max_complexity: Some(3),
+                },
+                query
+            )
+            .await,
+            Some((Value::from("QUERY_TOO_COMPLEX"), Value::from(3u64)))
+        );
+        assert_eq!(
+            error_code(
+                QueryLimits {
+                    max_depth: Some(4),
+                    max_complexity: Some(4),
+                },
+                query
+            )
+            .await,
+            None
+        );
+    }
+}
@@ -0,0 +1,630 @@
+//! Batched loading of entity relations and record attributes, so that
+//! resolving a page of records issues one query per relation rather than one
+//! per record. Attribute loads are keyed by attribute name, so only the
+//! attributes in the query's selection set are read from the store.
+
+use std::{collections::HashMap, hash::Hash, sync::Arc};
+
+use async_graphql::dataloader::Loader;
+use common::prov::{operations::DerivationType, Role};
+use diesel::r2d2::{ConnectionManager, Pool};
+
+use super::{Activity, Agent, Entity, Store};
+use crate::{DatabaseConnection, StoreError};
+
+/// Agents to which the entity with this id was attributed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AttributedTo(pub i32);
+
+/// Activities that generated the entity with this id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GeneratedBy(pub i32);
+
+/// Entities from which the entity with this id was derived, optionally only by
+/// derivations of the given type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DerivedFrom(pub i32, pub Option<DerivationType>);
+
+/// Entities used by the activity with this id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Used(pub i32);
+
+/// Entities generated by the activity with this id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Generated(pub i32);
+
+/// Agents associated with the activity with this id, with their delegates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssociatedWith(pub i32);
+
+/// Entities attributed to the agent with this id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Attributed(pub i32);
+
+/// An agent associated with an activity in a role, and the agent it delegated
+/// the activity to, if any, in the delegate's role
+pub type Association = (Agent, Option<Role>, Option<Agent>, Option<Role>);
+
+/// The value of the named attribute of the agent with this id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AgentAttribute(pub i32, pub String);
+
+/// The value of the named attribute of the activity with this id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActivityAttribute(pub i32, pub String);
+
+/// The value of the named attribute of the entity with this id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntityAttribute(pub i32, pub String);
+
+pub struct RelationLoader {
+    store: Store,
+}
+
+impl RelationLoader {
+    pub fn new(pool: Pool<ConnectionManager<DatabaseConnection>>) -> Self {
+        Self {
+            store: Store::new(pool),
+        }
+    }
+
+    /// A loader reading through `store`, and so scoped to its tenant
+    pub fn for_store(store: Store) -> Self {
+        Self { store }
+    }
+}
+
+fn optional_role(role: Role) -> Option<Role> {
+    if role.0.is_empty() {
+        None
+    } else {
+        Some(role)
+    }
+}
+
+fn group_by_id<K: Eq + Hash, V>(
+    rows: impl IntoIterator<Item = (i32, V)>,
+    key: impl Fn(i32) -> K,
+) -> HashMap<K, Vec<V>> {
+    let mut grouped: HashMap<K, Vec<V>> = HashMap::new();
+    for (id, value) in rows {
+        grouped.entry(key(id)).or_default().push(value);
+    }
+    grouped
+}
+
+/// Pair the agents associated with each activity, as (activity id, agent,
+/// role), with those they delegated it to, as (activity id, responsible id,
+/// delegate, role)
+fn associations_with_delegates(
+    associations: Vec<(i32, Agent, Role)>,
+    delegations: Vec<(i32, i32, Agent, Role)>,
+) -> HashMap<AssociatedWith, Vec<Association>> {
+    let delegates = delegations
+        .into_iter()
+        .map(|(activity_id, responsible_id, delegate, role)| {
+            (
+                (activity_id, responsible_id),
+                (delegate, optional_role(role)),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    group_by_id(
+        associations.into_iter().map(|(activity_id, agent, role)| {
+            let (delegate, delegate_role) = match delegates.get(&(activity_id, agent.id)) {
+                Some((delegate, delegate_role)) => (Some(delegate.clone()), delegate_role.clone()),
+                None => (None, None),
+            };
+            (
+                activity_id,
+                (agent, optional_role(role), delegate, delegate_role),
+            )
+        }),
+        AssociatedWith,
+    )
+}
+
+/// The distinct record ids and attribute names among a set of attribute keys
+fn ids_and_typenames<'a>(keys: impl Iterator<Item = (i32, &'a str)>) -> (Vec<i32>, Vec<&'a str>) {
+    let (mut ids, mut typenames): (Vec<_>, Vec<_>) = keys.unzip();
+    ids.sort_unstable();
+    ids.dedup();
+    typenames.sort_unstable();
+    typenames.dedup();
+    (ids, typenames)
+}
+
+fn parse_attributes<K: Eq + Hash>(
+    rows: Vec<(i32, String, String)>,
+    key: impl Fn(i32, String) -> K,
+) -> Result<HashMap<K, serde_json::Value>, Arc<StoreError>> {
+    rows.into_iter()
+        .map(|(id, typename, value)| {
+            serde_json::from_str(&value)
+                .map(|value| (key(id, typename), value))
+                .map_err(|e| Arc::new(StoreError::from(e)))
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl Loader<AttributedTo> for RelationLoader {
+    type Value = Vec<(Agent, Option<Role>)>;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[AttributedTo],
+    ) -> Result<HashMap<AttributedTo, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        let rows = self.store.attributions_for_entities(&ids)?;
+
+        Ok(group_by_id(
+            rows.into_iter()
+                .map(|(id, agent, role)| (id, (agent, optional_role(role)))),
+            AttributedTo,
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<GeneratedBy> for RelationLoader {
+    type Value = Vec<Activity>;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[GeneratedBy],
+    ) -> Result<HashMap<GeneratedBy, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+
+        Ok(group_by_id(
+            self.store.generations_for_entities(&ids)?,
+            GeneratedBy,
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<DerivedFrom> for RelationLoader {
+    type Value = Vec<Entity>;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[DerivedFrom],
+    ) -> Result<HashMap<DerivedFrom, Self::Value>, Self::Error> {
+        let mut ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let rows = self.store.derivations_for_entities(&ids)?;
+
+        let mut loaded = HashMap::new();
+        for key in keys {
+            let DerivedFrom(id, typ) = *key;
+            let used = rows
+                .iter()
+                .filter(|(generated, derivation, _)| {
+                    *generated == id && (typ.is_none() || typ == Some(*derivation))
+                })
+                .map(|(_, _, used)| used.clone())
+                .collect();
+            loaded.insert(*key, used);
+        }
+
+        Ok(loaded)
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<Used> for RelationLoader {
+    type Value = Vec<Entity>;
+    type Error = Arc<StoreError>;
+
+    async fn load(&self, keys: &[Used]) -> Result<HashMap<Used, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+
+        Ok(group_by_id(self.store.usages_for_activities(&ids)?, Used))
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<Generated> for RelationLoader {
+    type Value = Vec<Entity>;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[Generated],
+    ) -> Result<HashMap<Generated, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+
+        Ok(group_by_id(
+            self.store.generations_for_activities(&ids)?,
+            Generated,
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<AssociatedWith> for RelationLoader {
+    type Value = Vec<Association>;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[AssociatedWith],
+    ) -> Result<HashMap<AssociatedWith, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+
+        Ok(associations_with_delegates(
+            self.store.associations_for_activities(&ids)?,
+            self.store.delegations_for_activities(&ids)?,
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<Attributed> for RelationLoader {
+    type Value = Vec<(Entity, Option<Role>)>;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[Attributed],
+    ) -> Result<HashMap<Attributed, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        let rows = self.store.attributions_for_agents(&ids)?;
+
+        Ok(group_by_id(
+            rows.into_iter()
+                .map(|(id, entity, role)| (id, (entity, optional_role(role)))),
+            Attributed,
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<AgentAttribute> for RelationLoader {
+    type Value = serde_json::Value;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[AgentAttribute],
+    ) -> Result<HashMap<AgentAttribute, Self::Value>, Self::Error> {
+        let (ids, typenames) = ids_and_typenames(keys.iter().map(|key| (key.0, &*key.1)));
+
+        parse_attributes(
+            self.store.attributes_for_agents(&ids, &typenames)?,
+            AgentAttribute,
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<ActivityAttribute> for RelationLoader {
+    type Value = serde_json::Value;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[ActivityAttribute],
+    ) -> Result<HashMap<ActivityAttribute, Self::Value>, Self::Error> {
+        let (ids, typenames) = ids_and_typenames(keys.iter().map(|key| (key.0, &*key.1)));
+
+        parse_attributes(
+            self.store.attributes_for_activities(&ids, &typenames)?,
+            ActivityAttribute,
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<EntityAttribute> for RelationLoader {
+    type Value = serde_json::Value;
+    type Error = Arc<StoreError>;
+
+    async fn load(
+        &self,
+        keys: &[EntityAttribute],
+    ) -> Result<HashMap<EntityAttribute, Self::Value>, Self::Error> {
+        let (ids, typenames) = ids_and_typenames(keys.iter().map(|key| (key.0, &*key.1)));
+
+        parse_attributes(
+            self.store.attributes_for_entities(&ids, &typenames)?,
+            EntityAttribute,
+        )
+    }
+}
+
+impl Store {
+    /// The named attributes of each of the agents, as (agent id, name, json)
+    pub fn attributes_for_agents(
+        &self,
+        agent_ids: &[i32],
+        typenames: &[&str],
+    ) -> Result<Vec<(i32, String, String)>, StoreError> {
+        use crate::persistence::schema::agent_attribute;
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(agent_attribute::table
+                .filter(agent_attribute::agent_id.eq_any(agent_ids))
+                .filter(agent_attribute::typename.eq_any(typenames))
+                .select((
+                    agent_attribute::agent_id,
+                    agent_attribute::typename,
+                    agent_attribute::value,
+                ))
+                .load(connection)?)
+        })
+    }
+
+    /// The named attributes of each of the activities, as (activity id, name,
+    /// json)
+    pub fn attributes_for_activities(
+        &self,
+        activity_ids: &[i32],
+        typenames: &[&str],
+    ) -> Result<Vec<(i32, String, String)>, StoreError> {
+        use crate::persistence::schema::activity_attribute;
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(activity_attribute::table
+                .filter(activity_attribute::activity_id.eq_any(activity_ids))
+                .filter(activity_attribute::typename.eq_any(typenames))
+                .select((
+                    activity_attribute::activity_id,
+                    activity_attribute::typename,
+                    activity_attribute::value,
+                ))
+                .load(connection)?)
+        })
+    }
+
+    /// The named attributes of each of the entities, as (entity id, name, json)
+    pub fn attributes_for_entities(
+        &self,
+        entity_ids: &[i32],
+        typenames: &[&str],
+    ) -> Result<Vec<(i32, String, String)>, StoreError> {
+        use crate::persistence::schema::entity_attribute;
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(entity_attribute::table
+                .filter(entity_attribute::entity_id.eq_any(entity_ids))
+                .filter(entity_attribute::typename.eq_any(typenames))
+                .select((
+                    entity_attribute::entity_id,
+                    entity_attribute::typename,
+                    entity_attribute::value,
+                ))
+                .load(connection)?)
+        })
+    }
+
+    /// Agents to which each of the entities was attributed, keyed by entity id
+    /// and ordered by agent external id
+    pub fn attributions_for_entities(
+        &self,
+        entity_ids: &[i32],
+    ) -> Result<Vec<(i32, Agent, Role)>, StoreError> {
+        use crate::persistence::schema::{agent, attribution};
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(attribution::table
+                .filter(attribution::entity_id.eq_any(entity_ids))
+                .inner_join(agent::table)
+                .order(agent::external_id)
+                .select((
+                    attribution::entity_id,
+                    Agent::as_select(),
+                    attribution::role,
+                ))
+                .load::<(i32, Agent, Role)>(connection)?)
+        })
+    }
+
+    /// Activities that generated each of the entities, keyed by entity id
+    pub fn generations_for_entities(
+        &self,
+        entity_ids: &[i32],
+    ) -> Result<Vec<(i32, Activity)>, StoreError> {
+        use crate::persistence::schema::{activity, generation};
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(generation::table
+                .filter(generation::generated_entity_id.eq_any(entity_ids))
+                .inner_join(activity::table)
+                .select((generation::generated_entity_id, Activity::as_select()))
+                .load::<(i32, Activity)>(connection)?)
+        })
+    }
+
+    /// Entities used by each of the activities, keyed by activity id and
+    /// ordered by entity external id
+    pub fn usages_for_activities(
+        &self,
+        activity_ids: &[i32],
+    ) -> Result<Vec<(i32, Entity)>, StoreError> {
+        use crate::persistence::schema::{entity, usage};
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(usage::table
+                .filter(usage::activity_id.eq_any(activity_ids))
+                .inner_join(entity::table)
+                .order(entity::external_id)
+                .select((usage::activity_id, Entity::as_select()))
+                .load::<(i32, Entity)>(connection)?)
+        })
+    }
+
+    /// Entities generated by each of the activities, keyed by activity id
+    pub fn generations_for_activities(
+        &self,
+        activity_ids: &[i32],
+    ) -> Result<Vec<(i32, Entity)>, StoreError> {
+        use crate::persistence::schema::{entity, generation};
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(generation::table
+                .filter(generation::activity_id.eq_any(activity_ids))
+                .inner_join(entity::table)
+                .select((generation::activity_id, Entity::as_select()))
+                .load::<(i32, Entity)>(connection)?)
+        })
+    }
+
+    /// Agents associated with each of the activities, keyed by activity id and
+    /// ordered by agent external id
+    pub fn associations_for_activities(
+        &self,
+        activity_ids: &[i32],
+    ) -> Result<Vec<(i32, Agent, Role)>, StoreError> {
+        use crate::persistence::schema::{agent, association};
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(association::table
+                .filter(association::activity_id.eq_any(activity_ids))
+                .inner_join(agent::table)
+                .order(agent::external_id)
+                .select((
+                    association::activity_id,
+                    Agent::as_select(),
+                    association::role,
+                ))
+                .load::<(i32, Agent, Role)>(connection)?)
+        })
+    }
+
+    /// Delegates acting for agents in each of the activities, keyed by activity
+    /// id and the id of the responsible agent
+    pub fn delegations_for_activities(
+        &self,
+        activity_ids: &[i32],
+    ) -> Result<Vec<(i32, i32, Agent, Role)>, StoreError> {
+        use crate::persistence::schema::{agent, delegation};
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(delegation::table
+                .filter(delegation::activity_id.eq_any(activity_ids))
+                .inner_join(agent::table.on(agent::id.eq(delegation::delegate_id)))
+                .select((
+                    delegation::activity_id,
+                    delegation::responsible_id,
+                    Agent::as_select(),
+                    delegation::role,
+                ))
+                .load::<(i32, i32, Agent, Role)>(connection)?)
+        })
+    }
+
+    /// Entities attributed to each of the agents, keyed by agent id and ordered
+    /// by entity external id
+    pub fn attributions_for_agents(
+        &self,
+        agent_ids: &[i32],
+    ) -> Result<Vec<(i32, Entity, Role)>, StoreError> {
+        use crate::persistence::schema::{attribution, entity};
+        use diesel::prelude::*;
+
+        self.read_only(|connection| {
+            Ok(attribution::table
+                .filter(attribution::agent_id.eq_any(agent_ids))
+                .inner_join(entity::table.on(attribution::entity_id.eq(entity::id)))
+                .order(entity::external_id)
+                .select((
+                    attribution::agent_id,
+                    Entity::as_select(),
+                    attribution::role,
+                ))
+                .load::<(i32, Entity, Role)>(connection)?)
+        })
+    }
+
+    /// Entities used in derivations of each of the entities, keyed by generated
+    /// entity id along with the type of derivation
+    pub fn derivations_for_entities(
+        &self,
+        entity_ids: &[i32],
+    ) -> Result<Vec<(i32, DerivationType, Entity)>, StoreError> {
+        use crate::persistence::schema::{derivation, entity};
+        use diesel::prelude::*;
+
+        let rows = self.read_only(|connection| {
+            Ok::<_, StoreError>(
+                derivation::table
+                    .filter(derivation::generated_entity_id.eq_any(entity_ids))
+                    .inner_join(entity::table.on(derivation::used_entity_id.eq(entity::id)))
+                    .select((
+                        derivation::generated_entity_id,
+                        derivation::typ,
+                        Entity::as_select(),
+                    ))
+                    .load::<(i32, i32, Entity)>(connection)?,
+            )
+        })?;
+
+        rows.into_iter()
+            .map(|(id, typ, used)| {
+                DerivationType::try_from(typ)
+                    .map(|typ| (id, typ, used))
+                    .map_err(|_| StoreError::InvalidDerivationTypeRecord(typ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::prov::Role;
+
+    use super::{associations_with_delegates, Agent, AssociatedWith};
+
+    fn agent(id: i32, external_id: &str) -> Agent {
+        Agent {
+            id,
+            external_id: external_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn associations_are_paired_with_delegates_in_the_same_activity() {
+        let associations = associations_with_delegates(
+            vec![
+                (1, agent(10, "alice"), Role("author".to_owned())),
+                (1, agent(11, "bob"), Role(String::new())),
+                (2, agent(10, "alice"), Role(String::new())),
+            ],
+            vec![(1, 10, agent(12, "carol"), Role("editor".to_owned()))],
+        );
+
+        let first = &associations[&AssociatedWith(1)];
+        assert_eq!(first.len(), 2);
+        let (responsible, role, delegate, delegate_role) = &first[0];
+        assert_eq!(responsible.external_id, "alice");
+        assert_eq!(role.as_ref().map(|role| &*role.0), Some("author"));
+        assert_eq!(
+            delegate.as_ref().map(|delegate| &*delegate.external_id),
+            Some("carol")
+        );
+        assert_eq!(delegate_role.as_ref().map(|role| &*role.0), Some("editor"));
+        assert!(first[1].2.is_none() && first[1].1.is_none());
+
+        let second = &associations[&AssociatedWith(2)];
+        assert_eq!(second.len(), 1);
+        assert!(second[0].2.is_none());
+    }
+}
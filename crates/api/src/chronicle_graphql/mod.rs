@@ -1,34 +1,41 @@
 use async_graphql::{
+    dataloader::DataLoader,
     extensions::OpenTelemetry,
     http::{playground_source, GraphQLPlaygroundConfig, ALL_WEBSOCKET_PROTOCOLS},
-    scalar, Context, Enum, Error, ErrorExtensions, Object, ObjectType, Schema, ServerError,
-    SimpleObject, Subscription, SubscriptionType,
+    scalar, Context, Enum, Error, ErrorExtensions, Object, ObjectType, SDLExportOptions, Schema,
+    ServerError, SimpleObject, Subscription, SubscriptionType,
 };
 use async_graphql_poem::{
-    GraphQL, GraphQLBatchRequest, GraphQLBatchResponse, GraphQLProtocol, GraphQLSubscription,
+    GraphQLBatchRequest, GraphQLBatchResponse, GraphQLProtocol, GraphQLSubscription,
     GraphQLWebSocket,
 };
 use chrono::NaiveDateTime;
 use common::{
-    identity::{AuthId, IdentityError, JwtClaims, OpaData, SignedIdentity},
+    commands::{ApiCommand, OutboxCommand},
+    identity::{
+        AgentClaim, AuthId, DefaultNamespaces, IdentityError, JwtClaims, OpaData, SignedIdentity,
+    },
     ledger::{SubmissionError, SubmissionStage},
     opa::{ExecutorContext, OpaExecutorError},
     prov::{
-        to_json_ld::ToJson, ChronicleIri, ChronicleTransactionId, ExternalId, ExternalIdPart,
-        ProvModel,
+        to_json_ld::ToJson, ChronicleIri, ChronicleTransactionId, Contradiction, ExternalId,
+        ExternalIdPart, NamespaceId, ProvModel,
     },
 };
 use derivative::*;
 use diesel::{
     prelude::*,
     r2d2::{ConnectionManager, Pool},
-    PgConnection, Queryable,
+    Queryable,
 };
 use futures::Stream;
 use lazy_static::lazy_static;
 use poem::{
     get, handler,
-    http::{HeaderValue, StatusCode},
+    http::{
+        header::{ETAG, IF_NONE_MATCH, RETRY_AFTER},
+        HeaderValue, StatusCode,
+    },
     listener::{Listener, TcpListener},
     post,
     web::{
@@ -37,36 +44,76 @@ use poem::{
     },
     Endpoint, IntoResponse, Route, Server,
 };
-use r2d2::PooledConnection;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
     fmt::Display,
     net::SocketAddr,
     str::FromStr,
     sync::Arc,
+    time::Duration,
 };
 use thiserror::Error;
 use tokio::sync::{broadcast::error::RecvError, Semaphore};
-use tracing::{debug, error, instrument, warn};
+use tracing::{debug, error, info, instrument, warn};
 use url::Url;
 
-use self::authorization::TokenChecker;
-use crate::{ApiDispatch, ApiError, StoreError};
+use self::{
+    authorization::TokenChecker,
+    explain::ExplainTemplates,
+    export::ExportConf,
+    federation::FederationConf,
+    limits::QueryLimits,
+    loader::RelationLoader,
+    mutation::IdempotencyKey,
+    persisted::PersistedQueries,
+    playground::{PlaygroundConf, PlaygroundEndpoint},
+    query_cache::QueryCache,
+    rate_limits::{retry_after_seconds, RateLimits, RATE_LIMITED},
+    rest::{DefineEndpoint, DomainTypes, RecordEndpoint},
+    roles::{Permission, RolePermissions},
+    search::SearchConf,
+    server_info::ServerInfo,
+    tenant::TenantIsolation,
+};
+use crate::{
+    bind_tenant, health::MAX_SYNC_LAG, read_only_transaction, ApiDispatch, ApiError,
+    DatabaseConnection, ReadFrom, StoreError,
+};
 
 #[macro_use]
 pub mod activity;
 pub mod agent;
+pub mod audit;
 mod authorization;
 mod cursor_query;
 pub mod entity;
+pub mod explain;
+pub mod export;
+pub mod federation;
+pub mod limits;
+pub mod loader;
 pub mod mutation;
+pub mod persisted;
+pub mod playground;
 pub mod query;
+pub mod query_cache;
+pub mod rate_limits;
+pub mod receipt;
+pub mod rest;
+pub mod roles;
+pub mod search;
+pub mod server_info;
+mod tenant;
 
 pub type AuthorizationError = authorization::Error;
 
-#[derive(Default, Queryable, Selectable, SimpleObject)]
+/// The header a client retrying mutations sends the same key in, so that
+/// they are submitted only once
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+#[derive(Default, Clone, Queryable, Selectable, SimpleObject)]
 #[diesel(table_name = crate::persistence::schema::agent)]
 pub struct Agent {
     pub id: i32,
@@ -75,6 +122,14 @@ pub struct Agent {
     pub domaintype: Option<String>,
     pub current: i32,
     pub identity_id: Option<i32>,
+    /// When this record was first synchronized from the ledger
+    pub created_at: Option<NaiveDateTime>,
+    /// The block in which this record was first recorded
+    pub created_block: Option<String>,
+    /// When a transaction affecting this record was last synchronized
+    pub updated_at: Option<NaiveDateTime>,
+    /// The block of the last transaction affecting this record
+    pub updated_block: Option<String>,
 }
 
 #[derive(Default, Queryable, Selectable)]
@@ -97,7 +152,7 @@ impl Identity {
     }
 }
 
-#[derive(Default, Queryable, Selectable, SimpleObject)]
+#[derive(Default, Clone, Queryable, Selectable, SimpleObject)]
 #[diesel(table_name = crate::persistence::schema::activity)]
 pub struct Activity {
     pub id: i32,
@@ -106,15 +161,31 @@ pub struct Activity {
     pub domaintype: Option<String>,
     pub started: Option<NaiveDateTime>,
     pub ended: Option<NaiveDateTime>,
+    /// When this record was first synchronized from the ledger
+    pub created_at: Option<NaiveDateTime>,
+    /// The block in which this record was first recorded
+    pub created_block: Option<String>,
+    /// When a transaction affecting this record was last synchronized
+    pub updated_at: Option<NaiveDateTime>,
+    /// The block of the last transaction affecting this record
+    pub updated_block: Option<String>,
 }
 
-#[derive(Queryable, Selectable, SimpleObject)]
+#[derive(Clone, Queryable, Selectable, SimpleObject)]
 #[diesel(table_name = crate::persistence::schema::entity)]
 pub struct Entity {
     pub id: i32,
     pub external_id: String,
     pub namespace_id: i32,
     pub domaintype: Option<String>,
+    /// When this record was first synchronized from the ledger
+    pub created_at: Option<NaiveDateTime>,
+    /// The block in which this record was first recorded
+    pub created_block: Option<String>,
+    /// When a transaction affecting this record was last synchronized
+    pub updated_at: Option<NaiveDateTime>,
+    /// The block of the last transaction affecting this record
+    pub updated_block: Option<String>,
 }
 
 #[derive(Default, Queryable)]
@@ -145,16 +216,22 @@ impl Namespace {
 ///
 /// ## Fields
 ///
-/// * `context` - the activity, agent, or entity to which the operation relates
+/// * `context` - the activity, agent, or entity to which the operation relates, or the id of
+/// the outbox entry holding it if `submission_result` is `SubmissionResult::Queued`
 ///
 /// * `submission_result` - result type of an operation
 ///
 /// * `tx_id` - transaction id for a submitted operation; returns `null` if `submission_result`
-/// is `SubmissionResult::AlreadyRecorded`
+/// is `SubmissionResult::AlreadyRecorded` or `SubmissionResult::Queued`
+///
+/// * `operation_digest` - hex SHA-256 of the body of the submitted transaction, its operations
+/// as compact JSON-LD, which the transaction's receipt will carry once it commits; returns
+/// `null` unless the operation was submitted by this request
 pub struct Submission {
     context: String,
     submission_result: SubmissionResult,
     tx_id: Option<String>,
+    operation_digest: Option<String>,
 }
 
 #[derive(Enum, PartialEq, Eq, Clone, Copy)]
@@ -164,17 +241,25 @@ pub struct Submission {
 ///
 /// * `Submission` - operation has been submitted
 /// * `AlreadyRecorded` - operation will not result in data changes and has not been submitted
+/// * `Queued` - the ledger is unreachable, so the operation is held in the outbox and will be
+/// submitted once it can be reached
 pub enum SubmissionResult {
     Submission,
     AlreadyRecorded,
+    Queued,
 }
 
 impl Submission {
-    pub fn from_submission(subject: &ChronicleIri, tx_id: &ChronicleTransactionId) -> Self {
+    pub fn from_submission(
+        subject: &ChronicleIri,
+        tx_id: &ChronicleTransactionId,
+        operation_digest: Option<&str>,
+    ) -> Self {
         Submission {
             context: subject.to_string(),
             submission_result: SubmissionResult::Submission,
             tx_id: Some(tx_id.to_string()),
+            operation_digest: operation_digest.map(ToOwned::to_owned),
         }
     }
 
@@ -183,6 +268,16 @@ impl Submission {
             context: subject.to_string(),
             submission_result: SubmissionResult::AlreadyRecorded,
             tx_id: None,
+            operation_digest: None,
+        }
+    }
+
+    pub fn from_queued(outbox_id: &str) -> Self {
+        Submission {
+            context: outbox_id.to_owned(),
+            submission_result: SubmissionResult::Queued,
+            tx_id: None,
+            operation_digest: None,
         }
     }
 }
@@ -244,20 +339,117 @@ impl ErrorExtensions for GraphQlError {
                     i += 1;
                 }
             }
+            if let GraphQlError::Api(crate::ApiError::Validation(validation)) = self {
+                e.set("attribute", validation.attribute.clone());
+                e.set("value", validation.value.to_string());
+                e.set("violation", validation.violation.to_string());
+            }
+            if let GraphQlError::Api(crate::ApiError::NamespaceAccessDenied { .. }) = self {
+                e.set("code", "FORBIDDEN");
+            }
         })
     }
 }
 
-#[derive(Derivative)]
+#[derive(Clone, Derivative)]
 #[derivative(Debug)]
 pub struct Store {
     #[derivative(Debug = "ignore")]
-    pub pool: Pool<ConnectionManager<PgConnection>>,
+    pub pool: Pool<ConnectionManager<DatabaseConnection>>,
+    /// A pool of connections to a read replica of the database, if any
+    #[derivative(Debug = "ignore")]
+    pub read_pool: Option<Pool<ConnectionManager<DatabaseConnection>>>,
+    /// The namespace reads are scoped to, if any
+    tenant: Option<String>,
 }
 
 impl Store {
-    pub fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
-        Store { pool }
+    pub fn new(pool: Pool<ConnectionManager<DatabaseConnection>>) -> Self {
+        Store {
+            pool,
+            read_pool: None,
+            tenant: None,
+        }
+    }
+
+    /// Direct the reads made through [Store::read_only] to `read_pool`, a
+    /// pool of connections to a read replica, leaving writes on the primary
+    pub fn with_read_pool(
+        mut self,
+        read_pool: Option<Pool<ConnectionManager<DatabaseConnection>>>,
+    ) -> Self {
+        self.read_pool = read_pool;
+        self
+    }
+
+    /// A store whose reads see only the records of the namespace with
+    /// external id `tenant`
+    pub fn for_tenant(&self, tenant: impl Into<String>) -> Self {
+        Store {
+            pool: self.pool.clone(),
+            read_pool: self.read_pool.clone(),
+            tenant: Some(tenant.into()),
+        }
+    }
+
+    /// Run `f` on a pooled connection in a [read_only_transaction], scoped to
+    /// the store's tenant. Query resolvers and loaders read through this
+    /// rather than the pool, and so read from the replica if there is one.
+    pub fn read_only<T, E>(
+        &self,
+        f: impl FnOnce(&mut DatabaseConnection) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<diesel::result::Error> + From<r2d2::Error>,
+    {
+        self.read_only_from(ReadFrom::Replica, f)
+    }
+
+    /// Run `f` as [Store::read_only] does, on a connection from the pool
+    /// `from`, for reads that must see Chronicle's latest writes
+    pub fn read_only_from<T, E>(
+        &self,
+        from: ReadFrom,
+        f: impl FnOnce(&mut DatabaseConnection) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<diesel::result::Error> + From<r2d2::Error>,
+    {
+        let pool = match (&self.read_pool, from) {
+            (Some(read_pool), ReadFrom::Replica) => read_pool,
+            _ => &self.pool,
+        };
+        read_only_transaction(&mut *pool.get()?, |connection| {
+            if let Some(tenant) = &self.tenant {
+                bind_tenant(connection, tenant)?;
+            }
+            f(connection)
+        })
+    }
+
+    /// Run `f` in a transaction on a connection from the primary pool, scoped
+    /// to the store's tenant, for the writes Chronicle keeps off the ledger
+    pub fn transaction<T, E>(
+        &self,
+        f: impl FnOnce(&mut DatabaseConnection) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<diesel::result::Error> + From<r2d2::Error>,
+    {
+        let mut connection = self.pool.get()?;
+        connection.transaction(|connection| {
+            if let Some(tenant) = &self.tenant {
+                bind_tenant(connection, tenant)?;
+            }
+            f(connection)
+        })
+    }
+
+    /// The persistence store over the same pool and tenant
+    fn persistence(&self) -> Result<crate::persistence::Store, StoreError> {
+        Ok(crate::persistence::Store::new(self.pool.clone())?
+            .with_read_pool(self.read_pool.clone())
+            .with_tenant(self.tenant.clone()))
     }
 }
 
@@ -303,11 +495,50 @@ impl From<SignedIdentity> for CommitIdentity {
     }
 }
 
+/// A single contradicted value, the `committed` value is the one already on
+/// the ledger and `attempted` the one the transaction tried to record
+#[derive(SimpleObject)]
+pub struct ContradictedValue {
+    pub field: String,
+    pub committed: String,
+    pub attempted: String,
+}
+
+/// The structured detail of a transaction that contradicted existing
+/// provenance
+#[derive(SimpleObject)]
+pub struct CommitContradiction {
+    pub id: String,
+    pub namespace: String,
+    pub operation: Option<String>,
+    pub values: Vec<ContradictedValue>,
+}
+
+impl From<&Contradiction> for CommitContradiction {
+    fn from(contradiction: &Contradiction) -> Self {
+        CommitContradiction {
+            id: contradiction.id().to_string(),
+            namespace: contradiction.namespace().to_string(),
+            operation: contradiction.operation().map(ToOwned::to_owned),
+            values: contradiction
+                .details()
+                .iter()
+                .map(|detail| ContradictedValue {
+                    field: detail.field().to_owned(),
+                    committed: detail.committed(),
+                    attempted: detail.attempted(),
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(SimpleObject)]
 pub struct CommitNotification {
     pub stage: Stage,
     pub tx_id: String,
     pub error: Option<String>,
+    pub contradiction: Option<CommitContradiction>,
     pub delta: Option<Delta>,
     pub id: Option<CommitIdentity>,
 }
@@ -318,6 +549,7 @@ impl CommitNotification {
             stage: Stage::Submit,
             tx_id: tx_id.to_string(),
             error: None,
+            contradiction: None,
             delta: None,
             id: None,
         }
@@ -328,6 +560,7 @@ impl CommitNotification {
             stage: Stage::Submit,
             tx_id: e.tx_id().to_string(),
             error: Some(e.to_string()),
+            contradiction: None,
             delta: None,
             id: None,
         }
@@ -335,13 +568,14 @@ impl CommitNotification {
 
     pub fn from_contradiction(
         tx_id: &ChronicleTransactionId,
-        contradiction: &str,
+        contradiction: &Contradiction,
         id: SignedIdentity,
     ) -> Self {
         CommitNotification {
             stage: Stage::Commit,
             tx_id: tx_id.to_string(),
             error: Some(contradiction.to_string()),
+            contradiction: Some(contradiction.into()),
             delta: None,
             id: Some(id.into()),
         }
@@ -356,6 +590,7 @@ impl CommitNotification {
             stage: Stage::Commit,
             tx_id: tx_id.to_string(),
             error: None,
+            contradiction: None,
             delta: delta
                 .to_json()
                 .compact_stable_order()
@@ -367,6 +602,32 @@ impl CommitNotification {
             id: Some(id.into()),
         })
     }
+
+    /// A commit read back from the store, with the delta it committed as
+    /// compact JSON-LD
+    pub fn from_replayed(tx_id: &str, delta: &str) -> Result<Self, async_graphql::Error> {
+        Ok(CommitNotification {
+            stage: Stage::Commit,
+            tx_id: tx_id.to_owned(),
+            error: None,
+            contradiction: None,
+            delta: Some(Delta(async_graphql::Value::from_json(
+                serde_json::from_str(delta)?,
+            )?)),
+            id: None,
+        })
+    }
+}
+
+/// Whether `delta` records anything in the namespace with external id
+/// `namespace`
+fn touches_namespace(delta: &ProvModel, namespace: &str) -> bool {
+    let named = |id: &NamespaceId| id.external_id_part().as_str() == namespace;
+
+    delta.namespaces.keys().any(named)
+        || delta.agents.keys().any(|(id, _)| named(id))
+        || delta.activities.keys().any(|(id, _)| named(id))
+        || delta.entities.keys().any(|(id, _)| named(id))
 }
 
 pub struct Subscription;
@@ -377,17 +638,60 @@ pub struct Subscription;
 ///
 /// [^note](https://graphql.org/blog/subscriptions-in-graphql-and-relay/)
 impl Subscription {
+    /// Notifications of operations as they are submitted and committed. Only
+    /// commits and contradictions in `namespace`, or the identity's default
+    /// namespace, are notified, once the namespace policy permits it to read
+    /// there. Submissions carry no records and are notified whatever their
+    /// namespace. A client resuming a subscription can pass the last
+    /// transaction it saw as `after`, to first be sent the commits it missed
+    /// in the order they were committed. Replayed commits carry the delta they
+    /// committed, and no identity.
     async fn commit_notifications<'a>(
         &self,
         ctx: &Context<'a>,
-    ) -> impl Stream<Item = CommitNotification> {
+        namespace: Option<String>,
+        after: Option<String>,
+    ) -> async_graphql::Result<impl Stream<Item = CommitNotification>> {
+        let namespace: String = readable_namespace(ctx, namespace).await?;
         let api = ctx.data_unchecked::<ApiDispatch>().clone();
+        // Subscribe before reading what was missed, so that nothing committed
+        // in between is lost
         let mut rx = api.notify_commit.subscribe();
-        async_stream::stream! {
+
+        let mut missed = vec![];
+        if let Some(after) = after {
+            let store = ctx.data_unchecked::<Store>().persistence()?;
+            // Read from the primary, which has applied everything notified
+            missed = store
+                .read_only_from(ReadFrom::Primary, |connection| {
+                    store.synced_since(connection, &after, &namespace)
+                })?
+                .ok_or_else(|| {
+                    async_graphql::Error::new(format!(
+                        "Transaction {after} has not been synchronized"
+                    ))
+                })?;
+        }
+        let replayed = missed
+            .iter()
+            .map(|(tx_id, _)| tx_id.clone())
+            .collect::<HashSet<_>>();
+
+        Ok(async_stream::stream! {
+            for (tx_id, delta) in missed {
+                match CommitNotification::from_replayed(&tx_id, &delta) {
+                    Ok(notify) => yield notify,
+                    Err(e) => error!("Failed to convert replayed commit to notification: {:?}", e),
+                }
+            }
+
             loop {
                 match rx.recv().await {
                     Ok(SubmissionStage::Submitted(Ok(submission))) =>
                       yield CommitNotification::from_submission(&submission),
+                    Ok(SubmissionStage::Committed(commit, _))
+                        if replayed.contains(&commit.tx_id.to_string())
+                            || !touches_namespace(&commit.delta, &namespace) => {}
                     Ok(SubmissionStage::Committed(commit, id)) => {
                       let notify = CommitNotification::from_committed(&commit.tx_id, commit.delta, *id).await;
                       if let Ok(notify) = notify {
@@ -396,8 +700,10 @@ impl Subscription {
                         error!("Failed to convert commit to notification: {:?}", notify.err());
                       }
                     }
+                    Ok(SubmissionStage::NotCommitted((_, contradiction, _)))
+                        if contradiction.namespace().external_id_part().as_str() != namespace => {}
                     Ok(SubmissionStage::NotCommitted((commit,contradiction, id))) =>
-                      yield CommitNotification::from_contradiction(&commit, &contradiction.to_string(), *id),
+                      yield CommitNotification::from_contradiction(&commit, &contradiction, *id),
                     Ok(SubmissionStage::Submitted(Err(e))) => {
                       error!("Failed to submit: {:?}", e);
                       yield CommitNotification::from_submission_failed(&e);
@@ -407,7 +713,7 @@ impl Subscription {
                     Err(_) => break
                 }
             }
-        }
+        })
     }
 }
 
@@ -503,6 +809,11 @@ pub struct SecurityConf {
     jwt_must_claim: HashMap<String, String>,
     allow_anonymous: bool,
     opa: ExecutorContext,
+    default_namespaces: DefaultNamespaces,
+    tenant_isolation: bool,
+    role_permissions: Option<RolePermissions>,
+    agent_claim: Option<AgentClaim>,
+    rate_limits: Option<RateLimits>,
 }
 
 impl SecurityConf {
@@ -513,6 +824,7 @@ impl SecurityConf {
         jwt_must_claim: HashMap<String, String>,
         allow_anonymous: bool,
         opa: ExecutorContext,
+        default_namespaces: DefaultNamespaces,
     ) -> Self {
         Self {
             jwks_uri,
@@ -521,20 +833,73 @@ impl SecurityConf {
             jwt_must_claim,
             allow_anonymous,
             opa,
+            default_namespaces,
+            tenant_isolation: false,
+            role_permissions: None,
+            agent_claim: None,
+            rate_limits: None,
         }
     }
+
+    /// Scope each request's reads to the home namespace of its principal
+    pub fn with_tenant_isolation(mut self, tenant_isolation: bool) -> Self {
+        self.tenant_isolation = tenant_isolation;
+        self
+    }
+
+    /// Authorize requests by the permissions granted to their principal's roles
+    pub fn with_role_permissions(mut self, role_permissions: Option<RolePermissions>) -> Self {
+        self.role_permissions = role_permissions;
+        self
+    }
+
+    /// Associate activities that JWT identities record without naming an agent
+    /// with the agent given by their claim
+    pub fn with_agent_claim(mut self, agent_claim: Option<AgentClaim>) -> Self {
+        self.agent_claim = agent_claim;
+        self
+    }
+
+    /// Limit how often each principal may submit, by its identity or roles
+    pub fn with_rate_limits(mut self, rate_limits: Option<RateLimits>) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+}
+
+/// What the api server serves, and how
+pub struct ServerOptions {
+    /// Serve GraphQL at `/` and subscriptions at `/ws`
+    pub serve_graphql: bool,
+    /// Serve JSON-LD at `/data`
+    pub serve_data: bool,
+    /// Serve the REST facade at `/namespaces`
+    pub serve_rest: bool,
+    /// The domain's record types, that REST definitions are checked against
+    pub domain_types: DomainTypes,
+    pub exports: Option<ExportConf>,
+    pub explain: ExplainTemplates,
+    pub playground: Option<PlaygroundConf>,
+    pub server_info: ServerInfo,
+    pub federation: FederationConf,
+    pub search: Option<SearchConf>,
+    pub persisted_queries: Option<PersistedQueries>,
+    pub limits: QueryLimits,
+    pub query_cache: Option<QueryCache>,
+    /// How long to wait for in-flight requests once asked to stop
+    pub drain_timeout: Duration,
 }
 
 #[async_trait::async_trait]
 pub trait ChronicleApiServer {
     async fn serve_api(
         &self,
-        pool: Pool<ConnectionManager<PgConnection>>,
+        pool: Pool<ConnectionManager<DatabaseConnection>>,
+        read_pool: Option<Pool<ConnectionManager<DatabaseConnection>>>,
         api: ApiDispatch,
         addresses: Vec<SocketAddr>,
         security_conf: SecurityConf,
-        serve_graphql: bool,
-        serve_data: bool,
+        options: ServerOptions,
     ) -> Result<(), ApiError>;
 }
 
@@ -556,6 +921,49 @@ where
 
         schema.sdl()
     }
+
+    /// The SDL of the schema as served with `--federate`, with the fields and
+    /// directives a federation gateway composes it by
+    pub fn exportable_federated_schema(&self) -> String
+    where
+        Query: ObjectType + Copy,
+        Mutation: ObjectType + Copy,
+    {
+        let schema = Schema::build(self.query, self.mutation, Subscription)
+            .enable_federation()
+            .finish();
+
+        schema.sdl_with_options(SDLExportOptions::new().federation())
+    }
+}
+
+/// The namespace named by a request, or the calling identity's default namespace
+/// if the request omits one
+fn namespace_or_default<T: From<String>>(ctx: &Context<'_>, namespace: Option<T>) -> T {
+    namespace.unwrap_or_else(|| {
+        let identity = ctx.data_unchecked::<AuthId>();
+        match ctx.data_opt::<DefaultNamespaces>() {
+            Some(defaults) => defaults.for_identity(identity).into(),
+            None => DefaultNamespaces::default().for_identity(identity).into(),
+        }
+    })
+}
+
+/// The namespace named by a query, as [namespace_or_default] finds it, once
+/// the namespace policy, if configured, permits the calling identity to read it
+async fn readable_namespace<T: From<String> + Into<String> + Clone>(
+    ctx: &Context<'_>,
+    namespace: Option<T>,
+) -> async_graphql::Result<T> {
+    let namespace: T = namespace_or_default(ctx, namespace);
+    let name: String = namespace.clone().into();
+
+    ctx.data_unchecked::<ApiDispatch>()
+        .check_read_access(ctx.data_unchecked::<AuthId>(), ExternalId::from(name))
+        .await
+        .map_err(|e| GraphQlError::from(e).extend())?;
+
+    Ok(namespace)
 }
 
 fn check_required_claim(must_value: &str, actual_value: &serde_json::Value) -> bool {
@@ -674,9 +1082,121 @@ impl EndpointSecurityConfiguration {
     }
 }
 
+/// Quote a version token as a strong entity tag
+fn entity_tag(version: &str) -> String {
+    format!("\"{version}\"")
+}
+
+/// Query fields answered from state that changes without a namespace's
+/// version doing so: export jobs and receipts are kept apart from the ledger,
+/// and the audit trail is written after a commit is applied
+const UNVERSIONED_FIELDS: [&str; 3] = ["exportJob", "receipt", "auditTrail"];
+
+/// The fields of `selection_set`, with those of the fragments it spreads
+fn selected_fields<'a>(
+    selection_set: &'a async_graphql::parser::types::SelectionSet,
+    document: &'a async_graphql::parser::types::ExecutableDocument,
+    spread: &mut HashSet<&'a str>,
+    fields: &mut Vec<&'a async_graphql::parser::types::Field>,
+) {
+    use async_graphql::parser::types::Selection;
+
+    for selection in &selection_set.items {
+        match &selection.node {
+            Selection::Field(field) => fields.push(&field.node),
+            Selection::InlineFragment(fragment) => {
+                selected_fields(&fragment.node.selection_set.node, document, spread, fields)
+            }
+            Selection::FragmentSpread(fragment) => {
+                let name = fragment.node.fragment_name.node.as_str();
+                if let (true, Some(fragment)) = (spread.insert(name), document.fragments.get(name))
+                {
+                    selected_fields(&fragment.node.selection_set.node, document, spread, fields)
+                }
+            }
+        }
+    }
+}
+
+/// The namespace a query field names, or `None` if it names none, in which
+/// case it may read the caller's default namespace or any other
+fn field_namespace(
+    field: &async_graphql::parser::types::Field,
+    variables: &async_graphql::Variables,
+) -> Option<String> {
+    let namespace = field.get_argument("namespace")?.node.clone();
+    match namespace.into_const_with(|variable| variables.get(&variable).cloned().ok_or(())) {
+        Ok(async_graphql::Value::String(namespace)) => Some(namespace),
+        _ => None,
+    }
+}
+
+/// True if `If-None-Match` names `etag`, in which case the client's copy is current
+fn matches_if_none_match(if_none_match: Option<&str>, etag: &str) -> bool {
+    if_none_match
+        .map(|tags| {
+            tags.split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag == etag || tag.strip_prefix("W/") == Some(etag))
+        })
+        .unwrap_or(false)
+}
+
+fn not_modified(etag: &str) -> poem::Response {
+    poem::Response::builder()
+        .status(StatusCode::NOT_MODIFIED)
+        .header(ETAG, etag)
+        .finish()
+}
+
+/// The seconds to wait before retrying a request whose mutations were all
+/// refused as over their principal's quota, the longest any was told. Requests
+/// with any operation that was not refused get `None`, so that what succeeded
+/// is not reported as refused, and their refused mutations carry their own
+/// `retryAfter`.
+fn rate_limited(response: &async_graphql::BatchResponse) -> Option<u64> {
+    let responses = match response {
+        async_graphql::BatchResponse::Single(response) => std::slice::from_ref(response),
+        async_graphql::BatchResponse::Batch(responses) => responses.as_slice(),
+    };
+
+    let mut retry_after = None;
+    for response in responses {
+        let resolved = match &response.data {
+            async_graphql::Value::Object(fields) => fields
+                .values()
+                .any(|value| *value != async_graphql::Value::Null),
+            async_graphql::Value::Null => false,
+            _ => true,
+        };
+        if resolved || response.errors.is_empty() {
+            return None;
+        }
+
+        for error in &response.errors {
+            let extensions = error.extensions.as_ref();
+            if extensions.and_then(|extensions| extensions.get("code"))
+                != Some(&async_graphql::Value::from(RATE_LIMITED))
+            {
+                return None;
+            }
+
+            if let Some(async_graphql::Value::Number(seconds)) =
+                extensions.and_then(|extensions| extensions.get("retryAfter"))
+            {
+                retry_after = retry_after.max(seconds.as_u64());
+            }
+        }
+    }
+
+    retry_after
+}
+
 struct QueryEndpoint<Q, M, S> {
-    secconf: EndpointSecurityConfiguration,
+    secconf: Option<EndpointSecurityConfiguration>,
     schema: Schema<Q, M, S>,
+    store: super::persistence::Store,
+    cache: Option<QueryCache>,
 }
 
 impl<Q, M, S> QueryEndpoint<Q, M, S>
@@ -685,16 +1205,150 @@ where
     M: ObjectType + 'static,
     S: SubscriptionType + 'static,
 {
+    /// An entity tag for a batch made up only of queries, derived from the
+    /// versions of the namespaces they name, the requests and the caller's
+    /// claims. A field that names no namespace may read any, so is versioned by
+    /// the last synchronized transaction. Batches selecting any of
+    /// [UNVERSIONED_FIELDS] are not tagged.
+    fn entity_tag(
+        &self,
+        batch: &async_graphql::BatchRequest,
+        claims: Option<&JwtClaims>,
+    ) -> Option<String> {
+        use async_graphql::parser::{parse_query, types::OperationType};
+        use common::k256::sha2::{Digest, Sha256};
+
+        let requests = match batch {
+            async_graphql::BatchRequest::Single(request) => std::slice::from_ref(request),
+            async_graphql::BatchRequest::Batch(requests) => requests.as_slice(),
+        };
+
+        let mut namespaces = BTreeSet::new();
+        for request in requests {
+            let document = parse_query(&request.query).ok()?;
+            for (_, operation) in document.operations.iter() {
+                if operation.node.ty != OperationType::Query {
+                    return None;
+                }
+
+                let mut fields = vec![];
+                selected_fields(
+                    &operation.node.selection_set.node,
+                    &document,
+                    &mut HashSet::new(),
+                    &mut fields,
+                );
+                for field in fields {
+                    let name = field.name.node.as_str();
+                    if UNVERSIONED_FIELDS.contains(&name) {
+                        return None;
+                    }
+                    // Introspection does not read the store
+                    if !name.starts_with("__") {
+                        namespaces.insert(field_namespace(field, &request.variables));
+                    }
+                }
+            }
+        }
+
+        let mut hasher = Sha256::new();
+        for namespace in namespaces {
+            let version = match &namespace {
+                Some(namespace) => self.store.namespace_version(&ExternalId::from(namespace)),
+                None => self.store.ledger_version(),
+            };
+            match version {
+                Ok(version) => {
+                    hasher.update(namespace.unwrap_or_default());
+                    hasher.update([0]);
+                    hasher.update(version.unwrap_or_default());
+                    hasher.update([0]);
+                }
+                Err(error) => {
+                    warn!(%error, "Cannot determine version for entity tag");
+                    return None;
+                }
+            }
+        }
+        for request in requests {
+            hasher.update(request.query.as_bytes());
+            hasher.update(request.operation_name.as_deref().unwrap_or_default());
+            hasher.update(serde_json::to_vec(&request.variables).ok()?);
+        }
+        if let Some(claims) = claims {
+            hasher.update(serde_json::to_vec(claims).ok()?);
+        }
+
+        Some(entity_tag(&hex::encode(hasher.finalize())))
+    }
+
     #[instrument(level = "debug", skip_all, ret(Debug))]
     async fn respond(
         &self,
         req: poem::Request,
-        prepare_req: impl FnOnce(GraphQLBatchRequest) -> async_graphql::BatchRequest,
+        claims: Option<JwtClaims>,
     ) -> poem::Result<poem::Response> {
         use poem::{FromRequest, IntoResponse};
+        let if_none_match = req.header(IF_NONE_MATCH).map(str::to_owned);
+        let idempotency_key = req.header(IDEMPOTENCY_KEY).map(str::to_owned);
         let (req, mut body) = req.split();
-        let req = prepare_req(GraphQLBatchRequest::from_request(&req, &mut body).await?);
-        Ok(GraphQLBatchResponse(self.schema.execute_batch(req).await).into_response())
+        let batch = GraphQLBatchRequest::from_request(&req, &mut body).await?.0;
+
+        let etag = self.entity_tag(&batch, claims.as_ref());
+        if let Some(etag) = &etag {
+            if matches_if_none_match(if_none_match.as_deref(), etag) {
+                return Ok(not_modified(etag));
+            }
+            if let Some(response) = self.cache.as_ref().and_then(|cache| cache.get(etag)) {
+                return Ok(response);
+            }
+        }
+
+        let batch = if let Some(claims) = claims {
+            batch.data(claims)
+        } else {
+            batch
+        };
+
+        let batch = match (idempotency_key, batch) {
+            (Some(key), async_graphql::BatchRequest::Single(request)) => {
+                async_graphql::BatchRequest::Single(request.data(IdempotencyKey(key)))
+            }
+            // Requests in a batch may resolve the same fields, so are keyed apart
+            (Some(key), async_graphql::BatchRequest::Batch(requests)) => {
+                async_graphql::BatchRequest::Batch(
+                    requests
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, request)| {
+                            request.data(IdempotencyKey(format!("{key}/{index}")))
+                        })
+                        .collect(),
+                )
+            }
+            (None, batch) => batch,
+        };
+
+        let response = self.schema.execute_batch(batch).await;
+        let cacheable = response.is_ok();
+        let retry_after = rate_limited(&response);
+        let mut response = GraphQLBatchResponse(response).into_response();
+        if let Some(retry_after) = retry_after {
+            response.set_status(StatusCode::TOO_MANY_REQUESTS);
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after.into());
+        }
+        if let (Some(etag), true) = (etag, cacheable) {
+            if let Ok(value) = HeaderValue::from_str(&etag) {
+                response.headers_mut().insert(ETAG, value);
+            }
+            if let Some(cache) = &self.cache {
+                response = cache.insert(etag, response).await;
+            }
+        }
+
+        Ok(response)
     }
 }
 
@@ -708,15 +1362,12 @@ where
     type Output = poem::Response;
 
     async fn call(&self, req: poem::Request) -> poem::Result<Self::Output> {
-        let checked_claims = check_claims(&self.secconf, &req).await?;
-        self.respond(req, |api_req| {
-            if let Some(claims) = checked_claims {
-                api_req.0.data(claims)
-            } else {
-                api_req.0
-            }
-        })
-        .await
+        let checked_claims = if let Some(secconf) = &self.secconf {
+            check_claims(secconf, &req).await?
+        } else {
+            None
+        };
+        self.respond(req, checked_claims).await
     }
 }
 
@@ -783,21 +1434,71 @@ struct IriEndpoint {
     store: super::persistence::Store,
     opa_executor: ExecutorContext,
     claim_parser: Option<AuthFromJwt>,
+    role_permissions: Option<RolePermissions>,
+    tenant_isolation: Option<TenantIsolation>,
+    api: ApiDispatch,
 }
 
 impl IriEndpoint {
+    /// Whether the principal's roles grant `permission`, when roles are in use
+    fn permits(&self, claims: Option<&JwtClaims>, permission: Permission) -> bool {
+        self.role_permissions
+            .as_ref()
+            .map_or(true, |roles| roles.permits(claims, permission))
+    }
+
+    /// The identity of the principal, or anonymous if the claims name none
+    fn identity(&self, claims: Option<&JwtClaims>) -> AuthId {
+        match (claims, &self.claim_parser) {
+            (Some(claims), Some(parser)) => parser.identity(claims).unwrap_or(AuthId::anonymous()),
+            _ => AuthId::anonymous(),
+        }
+    }
+
+    /// The store to read records through, scoped to the principal's tenant
+    /// when tenants are isolated
+    fn store_for(&self, claims: Option<&JwtClaims>) -> super::persistence::Store {
+        self.store.clone().with_tenant(
+            self.tenant_isolation
+                .as_ref()
+                .map(|isolation| isolation.tenant(claims)),
+        )
+    }
+
     async fn response_for_query<ID: Display + ExternalIdPart, X: ToJson>(
         &self,
         claims: Option<&JwtClaims>,
+        if_none_match: Option<&str>,
         prov_type: &str,
         id: &ID,
         ns: &ExternalId,
-        retrieve: impl FnOnce(
-            PooledConnection<ConnectionManager<PgConnection>>,
-            &ID,
-            &ExternalId,
-        ) -> Result<X, StoreError>,
+        retrieve: impl FnOnce(&mut DatabaseConnection, &ID, &ExternalId) -> Result<X, StoreError>,
     ) -> poem::Result<poem::Response> {
+        if !self.permits(claims, Permission::Read) {
+            return Ok(poem::Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body("role does not permit reading records"));
+        }
+
+        match self
+            .api
+            .check_read_access(&self.identity(claims), ns.clone())
+            .await
+        {
+            Ok(()) => {}
+            Err(error @ ApiError::NamespaceAccessDenied { .. }) => {
+                return Ok(poem::Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .body(error.to_string()));
+            }
+            Err(error) => {
+                tracing::error!("failed to evaluate namespace policy: {error}");
+                return Ok(poem::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body("failed to evaluate namespace policy"));
+            }
+        }
+
         match execute_opa_check(&self.opa_executor, &self.claim_parser, claims, |identity| {
             OpaData::operation(
                 identity,
@@ -811,51 +1512,74 @@ impl IriEndpoint {
         })
         .await
         {
-            Ok(()) => match self.store.connection() {
-                Ok(connection) => match retrieve(connection, id, ns) {
-                    Ok(data) => match data.to_json().compact().await {
-                        Ok(mut json) => {
-                            use serde_json::Value;
-                            if let Value::Object(mut map) = json {
-                                map.insert(
-                                    "@context".to_string(),
-                                    Value::String("/context".to_string()),
-                                );
-                                json = Value::Object(map);
-                            }
-                            Ok(IntoResponse::into_response(poem::web::Json(json)))
-                        }
-                        Err(error) => {
-                            tracing::error!("JSON failed compaction: {error}");
-                            Ok(poem::Response::builder()
-                                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                .body("failed to compact JSON response"))
-                        }
-                    },
-                    Err(StoreError::Db(diesel::result::Error::NotFound))
-                    | Err(StoreError::RecordNotFound) => {
-                        tracing::debug!("not found: {prov_type} {} in {ns}", id.external_id_part());
-                        Ok(poem::Response::builder()
-                            .status(StatusCode::NOT_FOUND)
-                            .body(format!("the specified {prov_type} does not exist")))
-                    }
+            Ok(()) => {
+                let etag = match self.store.namespace_version(ns) {
+                    Ok(version) => version.as_deref().map(entity_tag),
                     Err(error) => {
-                        tracing::error!("failed to retrieve from database: {error}");
-                        Ok(poem::Response::builder()
-                            .status(StatusCode::INTERNAL_SERVER_ERROR)
-                            .body("failed to fetch from backend storage"))
+                        warn!(%error, "Cannot determine namespace version for entity tag");
+                        None
                     }
-                },
+                };
+                if let Some(etag) = &etag {
+                    if matches_if_none_match(if_none_match, etag) {
+                        return Ok(not_modified(etag));
+                    }
+                }
+                self.retrieve_response(&self.store_for(claims), prov_type, id, ns, etag, retrieve)
+                    .await
+            }
+            Err(_) => Ok(poem::Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body("violation of policy rules")),
+        }
+    }
+
+    async fn retrieve_response<ID: Display + ExternalIdPart, X: ToJson>(
+        &self,
+        store: &super::persistence::Store,
+        prov_type: &str,
+        id: &ID,
+        ns: &ExternalId,
+        etag: Option<String>,
+        retrieve: impl FnOnce(&mut DatabaseConnection, &ID, &ExternalId) -> Result<X, StoreError>,
+    ) -> poem::Result<poem::Response> {
+        match store.read_only(|connection| retrieve(connection, id, ns)) {
+            Ok(data) => match data.to_json().compact().await {
+                Ok(mut json) => {
+                    use serde_json::Value;
+                    if let Value::Object(mut map) = json {
+                        map.insert(
+                            "@context".to_string(),
+                            Value::String("/context".to_string()),
+                        );
+                        json = Value::Object(map);
+                    }
+                    let mut response = IntoResponse::into_response(poem::web::Json(json));
+                    if let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+                        response.headers_mut().insert(ETAG, etag);
+                    }
+                    Ok(response)
+                }
                 Err(error) => {
-                    tracing::error!("failed to connect to database: {error}");
+                    tracing::error!("JSON failed compaction: {error}");
                     Ok(poem::Response::builder()
                         .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body("failed to access backend storage"))
+                        .body("failed to compact JSON response"))
                 }
             },
-            Err(_) => Ok(poem::Response::builder()
-                .status(StatusCode::FORBIDDEN)
-                .body("violation of policy rules")),
+            Err(StoreError::Db(diesel::result::Error::NotFound))
+            | Err(StoreError::RecordNotFound) => {
+                tracing::debug!("not found: {prov_type} {} in {ns}", id.external_id_part());
+                Ok(poem::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(format!("the specified {prov_type} does not exist")))
+            }
+            Err(error) => {
+                tracing::error!("failed to retrieve from database: {error}");
+                Ok(poem::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body("failed to fetch from backend storage"))
+            }
         }
     }
 
@@ -912,23 +1636,35 @@ impl IriEndpoint {
         req: poem::Request,
         claims: Option<&JwtClaims>,
     ) -> poem::Result<poem::Response> {
+        let if_none_match = req.header(IF_NONE_MATCH).map(str::to_owned);
+        let if_none_match = if_none_match.as_deref();
         match self.parse_ns_iri_from_uri_path(req).await? {
             Ok((ns, ChronicleIri::Activity(id))) => {
-                self.response_for_query(claims, "activity", &id, &ns, |mut conn, id, ns| {
-                    self.store.prov_model_for_activity_id(&mut conn, id, ns)
-                })
+                self.response_for_query(
+                    claims,
+                    if_none_match,
+                    "activity",
+                    &id,
+                    &ns,
+                    |conn, id, ns| self.store.prov_model_for_activity_id(conn, id, ns),
+                )
                 .await
             }
             Ok((ns, ChronicleIri::Agent(id))) => {
-                self.response_for_query(claims, "agent", &id, &ns, |mut conn, id, ns| {
-                    self.store.prov_model_for_agent_id(&mut conn, id, ns)
+                self.response_for_query(claims, if_none_match, "agent", &id, &ns, |conn, id, ns| {
+                    self.store.prov_model_for_agent_id(conn, id, ns)
                 })
                 .await
             }
             Ok((ns, ChronicleIri::Entity(id))) => {
-                self.response_for_query(claims, "entity", &id, &ns, |mut conn, id, ns| {
-                    self.store.prov_model_for_entity_id(&mut conn, id, ns)
-                })
+                self.response_for_query(
+                    claims,
+                    if_none_match,
+                    "entity",
+                    &id,
+                    &ns,
+                    |conn, id, ns| self.store.prov_model_for_entity_id(conn, id, ns),
+                )
                 .await
             }
             Ok(_) => Ok(poem::Response::builder()
@@ -953,6 +1689,76 @@ impl Endpoint for IriEndpoint {
     }
 }
 
+/// Liveness probe, failing once the api task has stopped
+struct HealthEndpoint {
+    api: ApiDispatch,
+}
+
+#[poem::async_trait]
+impl Endpoint for HealthEndpoint {
+    type Output = poem::Response;
+
+    async fn call(&self, _req: poem::Request) -> poem::Result<Self::Output> {
+        if self.api.is_running() {
+            Ok(poem::Response::builder().status(StatusCode::OK).body("ok"))
+        } else {
+            Ok(poem::Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body("api stopped"))
+        }
+    }
+}
+
+/// Readiness probe, failing while the database is unreachable, the api is not
+/// subscribed to the ledger, or transactions it submitted have waited longer
+/// than [`MAX_SYNC_LAG`] to be seen there
+struct ReadinessEndpoint {
+    pool: Pool<ConnectionManager<DatabaseConnection>>,
+    api: ApiDispatch,
+}
+
+impl ReadinessEndpoint {
+    async fn database(&self) -> Result<(), String> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = pool.get().map_err(|e| e.to_string())?;
+            diesel::select(diesel::dsl::sql::<diesel::sql_types::Bool>("true"))
+                .execute(&mut connection)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+#[poem::async_trait]
+impl Endpoint for ReadinessEndpoint {
+    type Output = poem::Response;
+
+    async fn call(&self, _req: poem::Request) -> poem::Result<Self::Output> {
+        let database = self.database().await;
+        let ledger_connected = self.api.health.ledger_connected();
+        let sync_lag = self.api.health.sync_lag();
+
+        let ready = database.is_ok() && ledger_connected && sync_lag <= MAX_SYNC_LAG;
+        let status = if ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+
+        Ok(poem::web::Json(json!({
+            "ready": ready,
+            "database": database.err().unwrap_or_else(|| "ok".to_owned()),
+            "ledger": if ledger_connected { "ok" } else { "not subscribed to updates" },
+            "syncLagSeconds": sync_lag.as_secs_f64(),
+        }))
+        .with_status(status)
+        .into_response())
+    }
+}
+
 struct LdContextEndpoint;
 
 #[poem::async_trait]
@@ -1078,8 +1884,50 @@ fn trigger_shutdown() {
     SHUTDOWN_SIGNAL.add_permits(1);
 }
 
+/// Resolves when the server should stop, when triggered internally, on an
+/// interrupt or, on unix, on SIGTERM
 async fn await_shutdown() {
-    let _permit = SHUTDOWN_SIGNAL.acquire().await.unwrap();
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                warn!(?error, "Cannot listen for SIGTERM");
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        permit = SHUTDOWN_SIGNAL.acquire() => {
+            let _permit = permit.unwrap();
+        }
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+
+    info!("Shutting down, no longer accepting connections");
+}
+
+/// Once the server has stopped, try once more to submit commands held for
+/// the ledger, for up to `timeout`. Any still held stay in the outbox and are
+/// submitted after the api next starts.
+async fn flush_held_commands(api: &ApiDispatch, timeout: Duration) {
+    let flush = api.dispatch(
+        ApiCommand::Outbox(OutboxCommand::Flush),
+        AuthId::chronicle(),
+    );
+
+    match tokio::time::timeout(timeout, flush).await {
+        Ok(Ok(_)) => info!("Flushed held commands"),
+        Ok(Err(error)) => warn!(?error, "Cannot flush held commands"),
+        Err(_) => warn!("Timed out flushing held commands, they remain in the outbox"),
+    }
 }
 
 #[async_trait::async_trait]
@@ -1090,13 +1938,29 @@ where
 {
     async fn serve_api(
         &self,
-        pool: Pool<ConnectionManager<PgConnection>>,
+        pool: Pool<ConnectionManager<DatabaseConnection>>,
+        read_pool: Option<Pool<ConnectionManager<DatabaseConnection>>>,
         api: ApiDispatch,
         addresses: Vec<SocketAddr>,
         sec: SecurityConf,
-        serve_graphql: bool,
-        serve_data: bool,
+        options: ServerOptions,
     ) -> Result<(), ApiError> {
+        let ServerOptions {
+            serve_graphql,
+            serve_data,
+            serve_rest,
+            domain_types,
+            exports,
+            explain,
+            playground,
+            server_info,
+            federation,
+            search,
+            persisted_queries,
+            limits,
+            query_cache,
+            drain_timeout,
+        } = options;
         let claim_parser = sec.id_claims.map(|id_claims| AuthFromJwt {
             id_claims,
             allow_anonymous: sec.allow_anonymous,
@@ -1111,21 +1975,86 @@ where
         if let Some(claim_parser) = &claim_parser {
             schema = schema.extension(claim_parser.clone());
         }
+        let tenant_isolation = sec.tenant_isolation.then(|| TenantIsolation {
+            claim_parser: claim_parser.clone(),
+            default_namespaces: sec.default_namespaces.clone(),
+        });
+        if let Some(tenant_isolation) = &tenant_isolation {
+            if let Some(role) =
+                super::persistence::Store::new(pool.clone())?.role_bypassing_row_security()?
+            {
+                return Err(ApiError::TenantIsolationBypassed(role));
+            }
+            schema = schema.extension(tenant_isolation.clone());
+        }
+        if let Some(role_permissions) = &sec.role_permissions {
+            schema = schema.extension(role_permissions.clone());
+        }
+        if let Some(rate_limits) = &sec.rate_limits {
+            schema = schema.extension(rate_limits.clone());
+        }
+        schema = limits.apply(schema);
+        if let Some(persisted_queries) = persisted_queries {
+            schema = schema.extension(persisted_queries);
+        }
+        if federation.is_enabled() {
+            schema = schema.enable_federation();
+        }
+        let store = Store::new(pool.clone()).with_read_pool(read_pool.clone());
+        if let Some(exports) = exports {
+            export::resume_exports(store.clone(), exports.clone())?;
+            schema = schema.data(exports);
+        }
+        if let Some(search) = search {
+            search::spawn_indexer(&api, search.clone()).await?;
+            schema = schema.data(search);
+        }
+        if let Some(cache) = &query_cache {
+            cache.spawn_invalidation(&api);
+        }
+        if let Some(agent_claim) = &sec.agent_claim {
+            schema = schema.data(agent_claim.clone());
+        }
         let schema = schema
-            .data(Store::new(pool.clone()))
-            .data(api)
+            .data(store.clone())
+            .data(DataLoader::new(
+                RelationLoader::for_store(store),
+                tokio::spawn,
+            ))
+            .data(api.clone())
             .data(sec.opa.clone())
             .data(AuthId::anonymous())
+            .data(sec.default_namespaces.clone())
+            .data(server_info)
+            .data(federation)
+            .data(explain)
             .finish();
 
         let iri_endpoint = |secconf| IriEndpoint {
             secconf,
-            store: super::persistence::Store::new(pool.clone()).unwrap(),
+            store: super::persistence::Store::new(pool.clone())
+                .unwrap()
+                .with_read_pool(read_pool.clone()),
             opa_executor: sec.opa.clone(),
             claim_parser: claim_parser.clone(),
+            role_permissions: sec.role_permissions.clone(),
+            tenant_isolation: tenant_isolation.clone(),
+            api: api.clone(),
         };
 
-        let mut app = Route::new();
+        let mut app = Route::new()
+            .at("/healthz", get(HealthEndpoint { api: api.clone() }))
+            .at(
+                "/readyz",
+                get(ReadinessEndpoint {
+                    pool: pool.clone(),
+                    api: api.clone(),
+                }),
+            );
+
+        if let Some(playground) = playground.as_ref().filter(|_| serve_graphql) {
+            app = app.at("/playground", get(PlaygroundEndpoint::new(playground)));
+        }
 
         match (&sec.jwks_uri, &sec.userinfo_uri) {
             (None, None) => {
@@ -1133,7 +2062,15 @@ where
 
                 if serve_graphql {
                     app = app
-                        .at("/", get(gql_playground).post(GraphQL::new(schema.clone())))
+                        .at(
+                            "/",
+                            get(gql_playground).post(QueryEndpoint {
+                                secconf: None,
+                                schema: schema.clone(),
+                                store: super::persistence::Store::new(pool.clone())?,
+                                cache: query_cache.clone(),
+                            }),
+                        )
                         .at("/ws", get(GraphQLSubscription::new(schema)))
                 };
                 if serve_data {
@@ -1142,6 +2079,26 @@ where
                         .at("/data/:iri", get(iri_endpoint(None)))
                         .at("/data/:ns/:iri", get(iri_endpoint(None)))
                 };
+                if serve_rest {
+                    app = app
+                        .at(
+                            "/namespaces/:ns/:kind",
+                            post(DefineEndpoint {
+                                secconf: None,
+                                api: api.clone(),
+                                data: iri_endpoint(None),
+                                rate_limits: sec.rate_limits.clone(),
+                                domain_types: domain_types.clone(),
+                            }),
+                        )
+                        .at(
+                            "/namespaces/:ns/:kind/:id",
+                            get(RecordEndpoint {
+                                secconf: None,
+                                data: iri_endpoint(None),
+                            }),
+                        )
+                };
             }
             (jwks_uri, userinfo_uri) => {
                 const CACHE_EXPIRY_SECONDS: u32 = 100;
@@ -1171,8 +2128,10 @@ where
                         .at(
                             "/",
                             post(QueryEndpoint {
-                                secconf: secconf(),
+                                secconf: Some(secconf()),
                                 schema: schema.clone(),
+                                store: super::persistence::Store::new(pool.clone())?,
+                                cache: query_cache.clone(),
                             }),
                         )
                         .at(
@@ -1189,6 +2148,26 @@ where
                         .at("/data/:iri", get(iri_endpoint(Some(secconf()))))
                         .at("/data/:ns/:iri", get(iri_endpoint(Some(secconf()))))
                 };
+                if serve_rest {
+                    app = app
+                        .at(
+                            "/namespaces/:ns/:kind",
+                            post(DefineEndpoint {
+                                secconf: Some(secconf()),
+                                api: api.clone(),
+                                data: iri_endpoint(None),
+                                rate_limits: sec.rate_limits.clone(),
+                                domain_types: domain_types.clone(),
+                            }),
+                        )
+                        .at(
+                            "/namespaces/:ns/:kind/:id",
+                            get(RecordEndpoint {
+                                secconf: Some(secconf()),
+                                data: iri_endpoint(None),
+                            }),
+                        )
+                };
             }
         }
 
@@ -1198,10 +2177,648 @@ where
             .reduce(|listener_1, listener_2| listener_1.combine(listener_2).boxed())
             .unwrap();
 
+        // In-flight requests and subscriptions are given the drain timeout to
+        // finish once shutdown begins
         Server::new(listener)
-            .run_with_graceful_shutdown(app, await_shutdown(), None)
+            .run_with_graceful_shutdown(app, await_shutdown(), Some(drain_timeout))
             .await?;
 
+        flush_held_commands(&api, drain_timeout).await;
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use async_graphql::{
+        futures_util::{Stream, StreamExt},
+        Context, EmptySubscription, Object, Schema, ID,
+    };
+    use common::{
+        attributes::Attributes,
+        commands::{AgentCommand, ApiCommand},
+        database::TemporaryDatabase,
+        identity::AuthId,
+        ledger::SubmissionStage,
+        opa::{CliPolicyLoader, ExecutorContext},
+    };
+    use diesel::prelude::*;
+    use poem::{
+        http::{
+            header::{ETAG, IF_NONE_MATCH, RETRY_AFTER},
+            Method, StatusCode,
+        },
+        Endpoint, EndpointExt, Request, Route,
+    };
+    use serde_json::json;
+
+    use super::{
+        export::{self, ExportConf, ExportFormat, ExportJob},
+        query_cache::QueryCache,
+        rate_limits::RateLimits,
+        rest::{AttributeType, DefineEndpoint, DomainTypes, RecordEndpoint},
+        HealthEndpoint, IriEndpoint, QueryEndpoint, ReadinessEndpoint, Subscription,
+    };
+    use crate::{persistence::Store, test::test_api, ApiDispatch, ApiSendWithReply};
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn ping(&self) -> bool {
+            true
+        }
+
+        /// The number of agents in `namespace`, counting each time it is run
+        async fn agents(&self, ctx: &Context<'_>, namespace: String) -> async_graphql::Result<i64> {
+            use crate::persistence::schema::{agent, namespace};
+
+            ctx.data::<Arc<AtomicUsize>>()?
+                .fetch_add(1, Ordering::SeqCst);
+            Ok(agent::table
+                .inner_join(namespace::table)
+                .filter(namespace::external_id.eq(namespace))
+                .count()
+                .get_result(&mut ctx.data::<Store>()?.connection()?)?)
+        }
+
+        /// Stands in for the fields answered from outside the ledger
+        async fn receipt(&self) -> bool {
+            true
+        }
+
+        async fn export_job(
+            &self,
+            ctx: &Context<'_>,
+            id: ID,
+        ) -> async_graphql::Result<Option<ExportJob>> {
+            export::export_job(ctx, id).await
+        }
+    }
+
+    struct Mutation;
+
+    #[Object]
+    impl Mutation {
+        async fn submit(&self) -> bool {
+            true
+        }
+
+        async fn start_export(
+            &self,
+            ctx: &Context<'_>,
+            namespace: String,
+        ) -> async_graphql::Result<ExportJob> {
+            export::start_export(ctx, Some(namespace), ExportFormat::JsonLd, None, None).await
+        }
+
+        async fn cancel_export(
+            &self,
+            ctx: &Context<'_>,
+            id: ID,
+        ) -> async_graphql::Result<ExportJob> {
+            export::cancel_export(ctx, id).await
+        }
+    }
+
+    /// A dispatch to an api task that runs until the returned receiver of its
+    /// commands is dropped
+    fn dispatch(store: Store) -> (ApiDispatch, tokio::sync::mpsc::Receiver<ApiSendWithReply>) {
+        let (tx, commands) = tokio::sync::mpsc::channel(1);
+        let dispatch = ApiDispatch {
+            tx,
+            store,
+            store_and_forward: false,
+            held: Default::default(),
+            namespace_policy: None,
+            notify_commit: tokio::sync::broadcast::channel(1).0,
+            health: Default::default(),
+        };
+        (dispatch, commands)
+    }
+
+    async fn get(endpoint: &impl Endpoint<Output = poem::Response>) -> StatusCode {
+        endpoint
+            .call(Request::builder().method(Method::GET).finish())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    async fn get_path(endpoint: &impl Endpoint, path: &str) -> StatusCode {
+        endpoint
+            .get_response(
+                Request::builder()
+                    .method(Method::GET)
+                    .uri(path.parse().unwrap())
+                    .finish(),
+            )
+            .await
+            .status()
+    }
+
+    /// The `code` of each error in a GraphQL response
+    async fn error_codes(response: poem::Response) -> Vec<serde_json::Value> {
+        let body = response
+            .into_body()
+            .into_json::<serde_json::Value>()
+            .await
+            .unwrap();
+        body["errors"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|error| error["extensions"]["code"].clone())
+            .collect()
+    }
+
+    async fn post(
+        endpoint: &impl Endpoint<Output = poem::Response>,
+        query: &str,
+    ) -> poem::Response {
+        post_if_none_match(endpoint, query, None).await
+    }
+
+    async fn post_if_none_match(
+        endpoint: &impl Endpoint<Output = poem::Response>,
+        query: &str,
+        etag: Option<&str>,
+    ) -> poem::Response {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .content_type("application/json");
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        endpoint
+            .call(request.body(json!({ "query": query }).to_string()))
+            .await
+            .unwrap()
+    }
+
+    fn create_agent(external_id: &str, namespace: &str) -> ApiCommand {
+        ApiCommand::Agent(AgentCommand::Create {
+            external_id: external_id.into(),
+            namespace: namespace.into(),
+            attributes: Attributes::type_only(None),
+        })
+    }
+
+    /// The delta of the next commit notified on `notifications`
+    async fn next_commit(
+        notifications: &mut (impl Stream<Item = async_graphql::Response> + Unpin),
+    ) -> String {
+        loop {
+            let response = notifications
+                .next()
+                .await
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let notification = &response["commitNotifications"];
+            if notification["stage"] == "COMMIT" {
+                return notification["delta"].to_string();
+            }
+        }
+    }
+
+    fn policy(entrypoint: &str) -> ExecutorContext {
+        let loader =
+            CliPolicyLoader::from_embedded_policy("allow_transactions", entrypoint).unwrap();
+        ExecutorContext::from_loader(&loader).unwrap()
+    }
+
+    /// `api` with a namespace policy that denies reading or writing any
+    /// namespace
+    fn denied(api: &ApiDispatch) -> ApiDispatch {
+        ApiDispatch {
+            namespace_policy: Some(policy("allow_transactions.deny_all")),
+            ..api.clone()
+        }
+    }
+
+    /// The `/data` endpoint, reading through `api`
+    fn data_endpoint(api: &ApiDispatch) -> IriEndpoint {
+        IriEndpoint {
+            secconf: None,
+            store: api.store.clone(),
+            opa_executor: policy("allow_transactions.allowed_users"),
+            claim_parser: None,
+            role_permissions: None,
+            tenant_isolation: None,
+            api: api.clone(),
+        }
+    }
+
+    /// The `/data` and REST record endpoints, reading through `api`
+    fn record_routes(api: &ApiDispatch) -> Route {
+        Route::new()
+            .at("/data/:ns/:iri", poem::get(data_endpoint(api)))
+            .at(
+                "/namespaces/:ns/:kind/:id",
+                poem::get(RecordEndpoint {
+                    secconf: None,
+                    data: data_endpoint(api),
+                }),
+            )
+    }
+
+    /// The REST endpoints, defining records through `api` against a domain
+    /// with the agent type `ContractorAgent`
+    fn rest_routes(api: &ApiDispatch, rate_limits: Option<RateLimits>) -> Route {
+        let domain_types = DomainTypes::new().with_agent(
+            "ContractorAgent",
+            [AttributeType {
+                name: "LocationAttribute".to_owned(),
+                primitive_type: "String".to_owned(),
+                repeated: false,
+            }],
+        );
+        record_routes(api).at(
+            "/namespaces/:ns/:kind",
+            poem::post(DefineEndpoint {
+                secconf: None,
+                api: api.clone(),
+                data: data_endpoint(api),
+                rate_limits,
+                domain_types,
+            }),
+        )
+    }
+
+    /// POST `definition` to the `kind` of records of `testns`
+    async fn define(routes: &Route, kind: &str, definition: &serde_json::Value) -> poem::Response {
+        routes
+            .get_response(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/namespaces/testns/{kind}").parse().unwrap())
+                    .content_type("application/json")
+                    .body(definition.to_string()),
+            )
+            .await
+    }
+
+    /// An endpoint answering [Query], [Mutation] and [Subscription] through
+    /// `api`, with exports written to `exports`
+    fn export_endpoint(
+        api: &ApiDispatch,
+        pool: &diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::PgConnection>>,
+        exports: &std::path::Path,
+    ) -> QueryEndpoint<Query, Mutation, Subscription> {
+        QueryEndpoint {
+            secconf: None,
+            schema: Schema::build(Query, Mutation, Subscription)
+                .data(AuthId::anonymous())
+                .data(api.clone())
+                .data(super::Store::new(pool.clone()))
+                .data(ExportConf::new(exports))
+                .finish(),
+            store: api.store.clone(),
+            cache: None,
+        }
+    }
+
+    /// An endpoint answering [Query] from the store of `api`, with a count of
+    /// the times it has run `agents`
+    fn query_endpoint(
+        api: &ApiDispatch,
+        cache: Option<QueryCache>,
+    ) -> (
+        QueryEndpoint<Query, Mutation, EmptySubscription>,
+        Arc<AtomicUsize>,
+    ) {
+        let resolved = Arc::new(AtomicUsize::new(0));
+        let endpoint = QueryEndpoint {
+            secconf: None,
+            schema: Schema::build(Query, Mutation, EmptySubscription)
+                .data(AuthId::anonymous())
+                .data(api.store.clone())
+                .data(resolved.clone())
+                .finish(),
+            store: api.store.clone(),
+            cache,
+        };
+        (endpoint, resolved)
+    }
+
+    #[tokio::test]
+    async fn requests_are_refused_only_when_all_their_mutations_are_over_quota() {
+        let limits: RateLimits =
+            serde_json::from_value(json!({"default": {"perMinute": 1}})).unwrap();
+        let database = TemporaryDatabase::default();
+        let endpoint = QueryEndpoint {
+            secconf: None,
+            schema: Schema::build(Query, Mutation, EmptySubscription)
+                .extension(limits)
+                .data(AuthId::anonymous())
+                .finish(),
+            store: Store::new(database.connection_pool().unwrap()).unwrap(),
+            cache: None,
+        };
+
+        // The first mutation takes the only submission, the second is refused
+        let response = post(&endpoint, "mutation { first: submit second: submit }").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+
+        let response = post(&endpoint, "mutation { submit }").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "60");
+    }
+
+    #[tokio::test]
+    async fn liveness_fails_once_the_api_has_stopped() {
+        let database = TemporaryDatabase::default();
+        let (api, commands) = dispatch(Store::new(database.connection_pool().unwrap()).unwrap());
+        let endpoint = HealthEndpoint { api };
+
+        assert_eq!(get(&endpoint).await, StatusCode::OK);
+
+        drop(commands);
+        assert_eq!(get(&endpoint).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn readiness_follows_the_ledger_subscription_and_submissions_seen_there() {
+        let database = TemporaryDatabase::default();
+        let pool = database.connection_pool().unwrap();
+        let (api, _commands) = dispatch(Store::new(pool.clone()).unwrap());
+        let endpoint = ReadinessEndpoint {
+            pool,
+            api: api.clone(),
+        };
+
+        assert_eq!(get(&endpoint).await, StatusCode::SERVICE_UNAVAILABLE);
+
+        api.health.set_ledger_connected(true);
+        assert_eq!(get(&endpoint).await, StatusCode::OK);
+
+        // A submission is ready as soon as it is made, and stays so once seen
+        api.health.submitted("tx".into());
+        assert_eq!(get(&endpoint).await, StatusCode::OK);
+        api.health.synced(&"tx".into());
+        assert_eq!(get(&endpoint).await, StatusCode::OK);
+
+        api.health.set_ledger_connected(false);
+        assert_eq!(get(&endpoint).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn query_tags_change_only_with_the_namespaces_queried() {
+        let mut api = test_api().await;
+        api.dispatch(create_agent("first", "testns"), AuthId::chronicle())
+            .await
+            .unwrap();
+        let (endpoint, _) = query_endpoint(&api.api, None);
+        let query = r#"{ agents(namespace: "testns") }"#;
+
+        let response = post(&endpoint, query).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[ETAG].to_str().unwrap().to_owned();
+
+        let response = post_if_none_match(&endpoint, query, Some(&etag)).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+
+        // A commit to another namespace leaves the client's copy current
+        api.dispatch(create_agent("first", "otherns"), AuthId::chronicle())
+            .await
+            .unwrap();
+        let response = post_if_none_match(&endpoint, query, Some(&etag)).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        api.dispatch(create_agent("second", "testns"), AuthId::chronicle())
+            .await
+            .unwrap();
+        let response = post_if_none_match(&endpoint, query, Some(&etag)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[ETAG], etag.as_str());
+        assert_eq!(
+            response
+                .into_body()
+                .into_json::<serde_json::Value>()
+                .await
+                .unwrap(),
+            json!({"data": {"agents": 2}})
+        );
+
+        // Nor are fields answered from outside the ledger tagged
+        let response = post(&endpoint, "{ receipt }").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(ETAG).is_none());
+    }
+
+    #[tokio::test]
+    async fn cached_queries_are_run_again_once_a_commit_changes_their_results() {
+        let mut api = test_api().await;
+        api.dispatch(create_agent("first", "testns"), AuthId::chronicle())
+            .await
+            .unwrap();
+        let cache = QueryCache::new(10);
+        cache.spawn_invalidation(&api.api);
+        let (endpoint, resolved) = query_endpoint(&api.api, Some(cache));
+        let query = r#"{ agents(namespace: "testns") }"#;
+
+        for _ in 0..2 {
+            let response = post(&endpoint, query).await;
+            assert_eq!(
+                response
+                    .into_body()
+                    .into_json::<serde_json::Value>()
+                    .await
+                    .unwrap(),
+                json!({"data": {"agents": 1}})
+            );
+        }
+        // The second was answered from the cache
+        assert_eq!(resolved.load(Ordering::SeqCst), 1);
+
+        api.dispatch(create_agent("second", "testns"), AuthId::chronicle())
+            .await
+            .unwrap();
+        let response = post(&endpoint, query).await;
+        assert_eq!(
+            response
+                .into_body()
+                .into_json::<serde_json::Value>()
+                .await
+                .unwrap(),
+            json!({"data": {"agents": 2}})
+        );
+        assert_eq!(resolved.load(Ordering::SeqCst), 2);
+
+        // Fields answered from outside the ledger are run every time
+        for _ in 0..2 {
+            post(&endpoint, "{ receipt agents(namespace: \"testns\") }").await;
+        }
+        assert_eq!(resolved.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn records_read_over_http_are_subject_to_the_namespace_policy() {
+        let mut api = test_api().await;
+        api.dispatch(create_agent("first", "testns"), AuthId::chronicle())
+            .await
+            .unwrap();
+
+        for path in [
+            "/data/testns/chronicle:agent:first",
+            "/namespaces/testns/agents/first",
+        ] {
+            assert_eq!(
+                get_path(&record_routes(&api.api), path).await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                get_path(&record_routes(&denied(&api.api)), path).await,
+                StatusCode::FORBIDDEN
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn exports_and_commit_notifications_are_subject_to_the_namespace_policy() {
+        let mut api = test_api().await;
+        let (_, tx_id) = api
+            .dispatch(create_agent("first", "testns"), AuthId::chronicle())
+            .await
+            .unwrap()
+            .unwrap();
+        let exports = tempfile::tempdir().unwrap();
+        let pool = api.pool();
+        let allowed = export_endpoint(&api.api, &pool, exports.path());
+        let denied = export_endpoint(&denied(&api.api), &pool, exports.path());
+
+        let response = post(
+            &allowed,
+            r#"mutation { startExport(namespace: "testns") { id } }"#,
+        )
+        .await
+        .into_body()
+        .into_json::<serde_json::Value>()
+        .await
+        .unwrap();
+        let id = response["data"]["startExport"]["id"].as_str().unwrap();
+
+        for request in [
+            r#"mutation { startExport(namespace: "testns") { id } }"#.to_owned(),
+            format!(r#"{{ exportJob(id: "{id}") {{ id }} }}"#),
+            format!(r#"mutation {{ cancelExport(id: "{id}") {{ id }} }}"#),
+        ] {
+            let response = post(&denied, &request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(error_codes(response).await, vec![json!("FORBIDDEN")]);
+        }
+
+        let subscription = format!(
+            r#"subscription {{ commitNotifications(namespace: "testns", after: "{tx_id}") {{ txId }} }}"#
+        );
+        let response = denied
+            .schema
+            .execute_stream(subscription.as_str())
+            .next()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("FORBIDDEN"))
+        );
+
+        // Commits in other namespaces are neither replayed nor notified to a
+        // subscriber to `otherns`
+        for (agent, namespace) in [("later", "testns"), ("second", "otherns")] {
+            api.dispatch(create_agent(agent, namespace), AuthId::chronicle())
+                .await
+                .unwrap();
+        }
+        let mut notifications = allowed.schema.execute_stream(format!(
+            r#"subscription {{ commitNotifications(namespace: "otherns", after: "{tx_id}") {{ stage delta }} }}"#
+        ));
+        assert!(next_commit(&mut notifications)
+            .await
+            .contains("chronicle:agent:second"));
+
+        for (agent, namespace) in [("fourth", "testns"), ("fifth", "otherns")] {
+            api.dispatch(create_agent(agent, namespace), AuthId::chronicle())
+                .await
+                .unwrap();
+        }
+        assert!(next_commit(&mut notifications)
+            .await
+            .contains("chronicle:agent:fifth"));
+    }
+
+    #[tokio::test]
+    async fn rest_definitions_are_checked_against_the_domain_and_policies() {
+        let api = test_api().await;
+        let routes = rest_routes(&api.api, None);
+        let contractor = json!({
+            "externalId": "first",
+            "type": "ContractorAgent",
+            "attributes": {"LocationAttribute": "here"},
+        });
+
+        let mut commits = api.api.notify_commit.subscribe();
+        let response = define(&routes, "agents", &contractor).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        while !matches!(
+            commits.recv().await.unwrap(),
+            SubmissionStage::Committed(..)
+        ) {}
+
+        let response = define(&routes, "agents", &contractor).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            get_path(&routes, "/namespaces/testns/agents/first").await,
+            StatusCode::OK
+        );
+
+        for invalid in [
+            json!({"externalId": "second", "type": "ContractorActivity"}),
+            json!({"externalId": "second", "type": "ContractorAgent"}),
+            json!({
+                "externalId": "second",
+                "type": "ContractorAgent",
+                "attributes": {"LocationAttribute": "here", "NameAttribute": "second"},
+            }),
+            json!({
+                "externalId": "second",
+                "type": "ContractorAgent",
+                "attributes": {"LocationAttribute": 1},
+            }),
+            json!({"externalId": "second", "attributes": {"LocationAttribute": "here"}}),
+        ] {
+            let response = define(&routes, "agents", &invalid).await;
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        // Nor is an agent type one of entities
+        let response = define(&routes, "entities", &contractor).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let denied = rest_routes(&denied(&api.api), None);
+        let response = define(&denied, "agents", &contractor).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            get_path(&denied, "/namespaces/testns/agents/first").await,
+            StatusCode::FORBIDDEN
+        );
+
+        let limited = rest_routes(
+            &api.api,
+            Some(serde_json::from_value(json!({"default": {"perMinute": 1}})).unwrap()),
+        );
+        let response = define(&limited, "agents", &json!({"externalId": "third"})).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let response = define(&limited, "agents", &json!({"externalId": "fourth"})).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "60");
+    }
+}
@@ -1,28 +1,113 @@
 //! Primitive mutation operations that are not in terms of particular domain types
 
-use async_graphql::Context;
+use async_graphql::{Context, ErrorExtensions};
 use chrono::{DateTime, Utc};
 use common::{
-    attributes::Attributes,
+    attributes::{AttributeTypeError, Attributes},
     commands::{ActivityCommand, AgentCommand, ApiCommand, ApiResponse, EntityCommand},
-    identity::AuthId,
+    identity::{AgentClaim, AuthId},
     prov::{operations::DerivationType, ActivityId, AgentId, EntityId, Role},
 };
 
-use crate::ApiDispatch;
+use crate::{ApiDispatch, ApiError};
+
+use super::{namespace_or_default, Submission};
+
+/// The key a client sent in the `Idempotency-Key` header of a mutation request
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey(pub String);
+
+/// The idempotency key for the mutation being resolved, qualified by its field
+/// so that each mutation in a request is keyed separately
+fn idempotency_key(ctx: &Context<'_>) -> Option<String> {
+    let key = ctx.data_opt::<IdempotencyKey>()?;
+    let field = ctx
+        .path_node
+        .map(|path| path.to_string())
+        .unwrap_or_default();
+
+    Some(format!("{}/{field}", key.0))
+}
+
+/// The agent named by a mutation, otherwise the agent the calling identity's
+/// claim gives, if the server maps a claim to agents
+fn agent_or_claimed(ctx: &Context<'_>, agent: Option<AgentId>) -> Option<AgentId> {
+    agent.or_else(|| {
+        ctx.data_opt::<AgentClaim>()
+            .and_then(|claim| claim.agent_for(ctx.data_unchecked::<AuthId>()))
+    })
+}
+
+/// The responsible agent named by a mutation, otherwise the one the calling
+/// identity's claim gives, which a mutation must have one of
+fn responsible_or_claimed(
+    ctx: &Context<'_>,
+    responsible: Option<AgentId>,
+) -> async_graphql::Result<AgentId> {
+    agent_or_claimed(ctx, responsible).ok_or_else(|| {
+        async_graphql::Error::new("No responsible agent was given, and the identity claims none")
+    })
+}
+
+/// The error for a command that could not be submitted, with the `code`
+/// `TEMPORARILY_UNAVAILABLE` and `retryable` set if it can be retried
+fn submission_error(error: ApiError) -> async_graphql::Error {
+    let retryable = error.is_retryable();
+    async_graphql::Error::new(error.to_string()).extend_with(|_, e| {
+        if retryable {
+            e.set("code", "TEMPORARILY_UNAVAILABLE");
+            e.set("retryable", true);
+        }
+    })
+}
+
+/// The error for a mutation given an attribute value that is not of the
+/// attribute's type, with the `code` `INVALID_ATTRIBUTE_TYPE`, the `path` of
+/// the attribute in the mutation's input, the `expectedType` and the
+/// `providedValue` as JSON
+pub fn attribute_type_error(ctx: &Context<'_>, error: AttributeTypeError) -> async_graphql::Error {
+    let field = ctx
+        .path_node
+        .map(|path| path.field_name().to_owned())
+        .unwrap_or_default();
+
+    async_graphql::Error::new(error.to_string()).extend_with(|_, e| {
+        e.set("code", "INVALID_ATTRIBUTE_TYPE");
+        e.set(
+            "path",
+            vec![
+                field.clone(),
+                "attributes".to_owned(),
+                error.attribute.clone(),
+            ],
+        );
+        e.set("expectedType", error.expected.clone());
+        e.set("providedValue", error.value.to_string());
+    })
+}
 
-use super::Submission;
 async fn transaction_context<'a>(
-    res: ApiResponse,
+    res: Result<ApiResponse, ApiError>,
     _ctx: &Context<'a>,
 ) -> async_graphql::Result<Submission> {
-    match res {
-        ApiResponse::Submission { subject, tx_id, .. } => {
-            Ok(Submission::from_submission(&subject, &tx_id))
+    match res.map_err(submission_error)? {
+        ApiResponse::Submission {
+            subject,
+            tx_id,
+            operation_digest,
+            ..
+        } => Ok(Submission::from_submission(
+            &subject,
+            &tx_id,
+            Some(&operation_digest),
+        )),
+        ApiResponse::AlreadySubmitted { subject, tx_id } => {
+            Ok(Submission::from_submission(&subject, &tx_id, None))
         }
         ApiResponse::AlreadyRecorded { subject, .. } => {
             Ok(Submission::from_already_recorded(&subject))
         }
+        ApiResponse::Queued { id } => Ok(Submission::from_queued(&id)),
         _ => unreachable!(),
     }
 }
@@ -38,7 +123,7 @@ async fn derivation<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".into()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
 
     let res = api
         .dispatch(
@@ -48,10 +133,11 @@ async fn derivation<'a>(
                 activity: None,
                 used_entity,
                 derivation,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -66,7 +152,7 @@ pub async fn agent<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned());
+    let namespace = namespace_or_default(ctx, namespace);
 
     let res = api
         .dispatch(
@@ -74,10 +160,11 @@ pub async fn agent<'a>(
                 external_id: external_id.into(),
                 namespace: namespace.into(),
                 attributes,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -92,7 +179,7 @@ pub async fn activity<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned());
+    let namespace = namespace_or_default(ctx, namespace);
 
     let res = api
         .dispatch(
@@ -100,10 +187,11 @@ pub async fn activity<'a>(
                 external_id: external_id.into(),
                 namespace: namespace.into(),
                 attributes,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -118,7 +206,7 @@ pub async fn entity<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned());
+    let namespace = namespace_or_default(ctx, namespace);
 
     let res = api
         .dispatch(
@@ -126,14 +214,16 @@ pub async fn entity<'a>(
                 external_id: external_id.into(),
                 namespace: namespace.into(),
                 attributes,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn acted_on_behalf_of<'a>(
     ctx: &Context<'a>,
     namespace: Option<String>,
@@ -141,12 +231,14 @@ pub async fn acted_on_behalf_of<'a>(
     delegate_id: AgentId,
     activity_id: Option<ActivityId>,
     role: Option<Role>,
+    valid_from: Option<DateTime<Utc>>,
+    valid_until: Option<DateTime<Utc>>,
 ) -> async_graphql::Result<Submission> {
     let api = ctx.data_unchecked::<ApiDispatch>();
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
 
     let res = api
         .dispatch(
@@ -156,10 +248,13 @@ pub async fn acted_on_behalf_of<'a>(
                 activity: activity_id,
                 namespace,
                 role,
-            }),
+                valid_from,
+                valid_until,
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -237,7 +332,9 @@ pub async fn start_activity<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let agent = agent_or_claimed(ctx, agent);
 
     let res = api
         .dispatch(
@@ -246,10 +343,11 @@ pub async fn start_activity<'a>(
                 namespace,
                 time,
                 agent,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -265,7 +363,9 @@ pub async fn end_activity<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let agent = agent_or_claimed(ctx, agent);
 
     let res = api
         .dispatch(
@@ -274,10 +374,11 @@ pub async fn end_activity<'a>(
                 namespace,
                 time,
                 agent,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -293,7 +394,9 @@ pub async fn instant_activity<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let agent = agent_or_claimed(ctx, agent);
 
     let res = api
         .dispatch(
@@ -302,10 +405,11 @@ pub async fn instant_activity<'a>(
                 namespace,
                 time,
                 agent,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -313,7 +417,7 @@ pub async fn instant_activity<'a>(
 pub async fn was_associated_with<'a>(
     ctx: &Context<'a>,
     namespace: Option<String>,
-    responsible: AgentId,
+    responsible: Option<AgentId>,
     activity: ActivityId,
     role: Option<Role>,
 ) -> async_graphql::Result<Submission> {
@@ -321,7 +425,9 @@ pub async fn was_associated_with<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let responsible = responsible_or_claimed(ctx, responsible)?;
 
     let res = api
         .dispatch(
@@ -330,10 +436,11 @@ pub async fn was_associated_with<'a>(
                 responsible,
                 role,
                 namespace,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -341,7 +448,7 @@ pub async fn was_associated_with<'a>(
 pub async fn was_attributed_to<'a>(
     ctx: &Context<'a>,
     namespace: Option<String>,
-    responsible: AgentId,
+    responsible: Option<AgentId>,
     id: EntityId,
     role: Option<Role>,
 ) -> async_graphql::Result<Submission> {
@@ -349,7 +456,9 @@ pub async fn was_attributed_to<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let responsible = responsible_or_claimed(ctx, responsible)?;
 
     let res = api
         .dispatch(
@@ -358,10 +467,150 @@ pub async fn was_attributed_to<'a>(
                 namespace,
                 responsible,
                 role,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
+            identity,
+        )
+        .await;
+
+    transaction_context(res, ctx).await
+}
+
+pub async fn retract_association<'a>(
+    ctx: &Context<'a>,
+    namespace: Option<String>,
+    responsible: AgentId,
+    activity: ActivityId,
+    role: Option<Role>,
+) -> async_graphql::Result<Submission> {
+    let api = ctx.data_unchecked::<ApiDispatch>();
+
+    let identity = ctx.data_unchecked::<AuthId>().to_owned();
+
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let res = api
+        .dispatch(
+            ApiCommand::Activity(ActivityCommand::RetractAssociation {
+                id: activity,
+                responsible,
+                role,
+                namespace,
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
+            identity,
+        )
+        .await;
+
+    transaction_context(res, ctx).await
+}
+
+pub async fn retract_attribution<'a>(
+    ctx: &Context<'a>,
+    namespace: Option<String>,
+    responsible: AgentId,
+    id: EntityId,
+    role: Option<Role>,
+) -> async_graphql::Result<Submission> {
+    let api = ctx.data_unchecked::<ApiDispatch>();
+
+    let identity = ctx.data_unchecked::<AuthId>().to_owned();
+
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let res = api
+        .dispatch(
+            ApiCommand::Entity(EntityCommand::RetractAttribution {
+                id,
+                namespace,
+                responsible,
+                role,
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
+            identity,
+        )
+        .await;
+
+    transaction_context(res, ctx).await
+}
+
+pub async fn retract_agent_attribute<'a>(
+    ctx: &Context<'a>,
+    id: AgentId,
+    namespace: Option<String>,
+    attribute: String,
+) -> async_graphql::Result<Submission> {
+    let api = ctx.data_unchecked::<ApiDispatch>();
+
+    let identity = ctx.data_unchecked::<AuthId>().to_owned();
+
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let res = api
+        .dispatch(
+            ApiCommand::Agent(AgentCommand::RetractAttribute {
+                id,
+                namespace,
+                attribute,
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
+
+    transaction_context(res, ctx).await
+}
+
+pub async fn retract_activity_attribute<'a>(
+    ctx: &Context<'a>,
+    id: ActivityId,
+    namespace: Option<String>,
+    attribute: String,
+) -> async_graphql::Result<Submission> {
+    let api = ctx.data_unchecked::<ApiDispatch>();
+
+    let identity = ctx.data_unchecked::<AuthId>().to_owned();
+
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let res = api
+        .dispatch(
+            ApiCommand::Activity(ActivityCommand::RetractAttribute {
+                id,
+                namespace,
+                attribute,
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
+            identity,
+        )
+        .await;
+
+    transaction_context(res, ctx).await
+}
+
+pub async fn retract_entity_attribute<'a>(
+    ctx: &Context<'a>,
+    id: EntityId,
+    namespace: Option<String>,
+    attribute: String,
+) -> async_graphql::Result<Submission> {
+    let api = ctx.data_unchecked::<ApiDispatch>();
+
+    let identity = ctx.data_unchecked::<AuthId>().to_owned();
+
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let res = api
+        .dispatch(
+            ApiCommand::Entity(EntityCommand::RetractAttribute {
+                id,
+                namespace,
+                attribute,
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
+            identity,
+        )
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -376,7 +625,7 @@ pub async fn used<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
 
     let res = api
         .dispatch(
@@ -384,10 +633,11 @@ pub async fn used<'a>(
                 id: entity,
                 namespace,
                 activity,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -402,7 +652,7 @@ pub async fn was_informed_by<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
 
     let res = api
         .dispatch(
@@ -410,10 +660,11 @@ pub async fn was_informed_by<'a>(
                 id: activity,
                 namespace,
                 informing_activity,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
@@ -428,7 +679,7 @@ pub async fn was_generated_by<'a>(
 
     let identity = ctx.data_unchecked::<AuthId>().to_owned();
 
-    let namespace = namespace.unwrap_or_else(|| "default".to_owned()).into();
+    let namespace = namespace_or_default(ctx, namespace).into();
 
     let res = api
         .dispatch(
@@ -436,10 +687,49 @@ pub async fn was_generated_by<'a>(
                 id: entity,
                 namespace,
                 activity,
-            }),
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
+            identity,
+        )
+        .await;
+
+    transaction_context(res, ctx).await
+}
+
+/// Record an activity with the agents, used entities and generated entities of
+/// a domain's recipe in one transaction
+#[allow(clippy::too_many_arguments)]
+pub async fn recipe<'a>(
+    ctx: &Context<'a>,
+    external_id: String,
+    namespace: Option<String>,
+    attributes: Attributes,
+    time: Option<DateTime<Utc>>,
+    associations: Vec<(AgentId, Option<Role>)>,
+    used: Vec<EntityId>,
+    generated: Vec<EntityId>,
+) -> async_graphql::Result<Submission> {
+    let api = ctx.data_unchecked::<ApiDispatch>();
+
+    let identity = ctx.data_unchecked::<AuthId>().to_owned();
+
+    let namespace = namespace_or_default(ctx, namespace).into();
+
+    let res = api
+        .dispatch(
+            ApiCommand::Activity(ActivityCommand::Recipe {
+                external_id: external_id.into(),
+                namespace,
+                attributes,
+                time,
+                associations,
+                used,
+                generated,
+            })
+            .with_idempotency_key(idempotency_key(ctx)),
             identity,
         )
-        .await?;
+        .await;
 
     transaction_context(res, ctx).await
 }
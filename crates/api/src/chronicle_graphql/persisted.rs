@@ -0,0 +1,198 @@
+//! Persisted queries, registered with the api ahead of time so that clients
+//! can send the SHA-256 hash of a query in place of its text, as Apollo's
+//! automatic persisted queries do. Queries are registered from a directory of
+//! `.graphql` files, each under the hash of its contents. Ad-hoc queries, those
+//! that are not registered, can be refused, so that a production api only runs
+//! the queries its clients were built with.
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    parser::parse_query,
+    Request, ServerError, ServerResult, Value,
+};
+use common::k256::sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{debug, info};
+
+#[derive(Error, Debug)]
+pub enum PersistedQueryError {
+    #[error("Persisted queries not readable: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Persisted query {path} is not valid GraphQL: {message}")]
+    Invalid { path: String, message: String },
+}
+
+/// The queries registered with the api, keyed by the hex encoded SHA-256 hash
+/// of their text
+#[derive(Debug, Clone, Default)]
+pub struct PersistedQueries {
+    documents: Arc<HashMap<String, String>>,
+    allow_ad_hoc: bool,
+}
+
+impl PersistedQueries {
+    /// Register every `.graphql` file in `dir`, refusing queries that are not
+    /// registered unless `allow_ad_hoc`
+    pub fn from_dir(dir: &Path, allow_ad_hoc: bool) -> Result<Self, PersistedQueryError> {
+        let mut documents = HashMap::new();
+
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(true, |extension| extension != "graphql")
+            {
+                continue;
+            }
+
+            let query = std::fs::read_to_string(&path)?;
+            parse_query(&query).map_err(|e| PersistedQueryError::Invalid {
+                path: path.display().to_string(),
+                message: e.to_string(),
+            })?;
+            debug!(path = %path.display(), "Registered persisted query");
+            documents.insert(hash(&query), query);
+        }
+
+        info!(
+            registered = documents.len(),
+            allow_ad_hoc, "Serving persisted queries"
+        );
+
+        Ok(Self {
+            documents: Arc::new(documents),
+            allow_ad_hoc,
+        })
+    }
+
+    /// Replace the hash a request was sent with by the query registered under
+    /// it, and refuse the request if the query is not one that may be run
+    fn resolve(&self, request: &mut Request) -> Result<(), ServerError> {
+        let sent_hash = match request.extensions.get("persistedQuery") {
+            Some(Value::Object(persisted)) => match persisted.get("sha256Hash") {
+                Some(Value::String(hash)) => Some(hash.to_owned()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if request.query.trim().is_empty() {
+            let sent_hash = sent_hash.unwrap_or_default();
+            return match self.documents.get(&sent_hash) {
+                Some(query) => {
+                    request.query = query.clone();
+                    Ok(())
+                }
+                None => Err(ServerError::new("PersistedQueryNotFound", None)),
+            };
+        }
+
+        let query_hash = hash(&request.query);
+        if sent_hash.is_some_and(|sent_hash| sent_hash != query_hash) {
+            return Err(ServerError::new("provided sha does not match query", None));
+        }
+        if !self.allow_ad_hoc && !self.documents.contains_key(&query_hash) {
+            return Err(ServerError::new(
+                "Ad-hoc queries are disabled, send a registered query or its hash",
+                None,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn hash(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueries {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        self.resolve(&mut request)?;
+        next.run(ctx, request).await
+    }
+}
+
+impl ExtensionFactory for PersistedQueries {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_graphql::{Name, Request, Value};
+
+    use super::{hash, PersistedQueries};
+
+    const REGISTERED: &str = "query { agentById(id: {externalId: \"a\"}) { __typename } }";
+
+    fn persisted_queries(allow_ad_hoc: bool) -> (tempfile::TempDir, PersistedQueries) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("agent.graphql"), REGISTERED).unwrap();
+        std::fs::write(dir.path().join("README.md"), "not a query").unwrap();
+        let queries = PersistedQueries::from_dir(dir.path(), allow_ad_hoc).unwrap();
+        (dir, queries)
+    }
+
+    fn with_hash(query: &str, sha256: &str) -> Request {
+        let mut request = Request::new(query);
+        request.extensions.insert(
+            "persistedQuery".to_owned(),
+            Value::Object(
+                [
+                    (Name::new("version"), Value::from(1)),
+                    (Name::new("sha256Hash"), Value::from(sha256)),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        );
+        request
+    }
+
+    fn error(queries: &PersistedQueries, mut request: Request) -> Option<String> {
+        queries
+            .resolve(&mut request)
+            .err()
+            .map(|error| error.message)
+    }
+
+    #[test]
+    fn registered_queries_are_resolved_by_hash() {
+        let (_dir, queries) = persisted_queries(false);
+
+        let mut request = with_hash("", &hash(REGISTERED));
+        queries.resolve(&mut request).unwrap();
+        assert_eq!(request.query, REGISTERED);
+
+        assert_eq!(
+            error(&queries, with_hash("", &hash("query { other }"))).as_deref(),
+            Some("PersistedQueryNotFound")
+        );
+        assert_eq!(
+            error(&queries, with_hash(REGISTERED, &hash("query { other }"))).as_deref(),
+            Some("provided sha does not match query")
+        );
+    }
+
+    #[test]
+    fn ad_hoc_queries_run_only_when_allowed() {
+        let (_dir, strict) = persisted_queries(false);
+        assert!(error(&strict, Request::new(REGISTERED)).is_none());
+        assert!(error(&strict, Request::new("query { __typename }"))
+            .is_some_and(|message| message.starts_with("Ad-hoc queries are disabled")));
+
+        let (_dir, lenient) = persisted_queries(true);
+        assert!(error(&lenient, Request::new("query { __typename }")).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+//! The GraphQL Playground served at `/playground` when enabled, opening with a
+//! tab for each example query and mutation supplied for the domain. The page
+//! is static, so when authentication is required a token must be added to the
+//! playground's HTTP headers before any of the examples can be run.
+
+use poem::{web::Html, Endpoint, IntoResponse};
+use serde_json::json;
+
+/// An example to open in its own playground tab
+#[derive(Debug, Clone)]
+pub struct PlaygroundExample {
+    pub name: String,
+    pub query: String,
+}
+
+/// The examples the playground opens with, the playground is not served
+/// unless configured
+#[derive(Debug, Clone, Default)]
+pub struct PlaygroundConf {
+    pub examples: Vec<PlaygroundExample>,
+}
+
+impl PlaygroundConf {
+    pub fn new(examples: Vec<PlaygroundExample>) -> Self {
+        Self { examples }
+    }
+}
+
+pub(crate) struct PlaygroundEndpoint {
+    page: String,
+}
+
+impl PlaygroundEndpoint {
+    pub(crate) fn new(conf: &PlaygroundConf) -> Self {
+        let tabs: Vec<_> = conf
+            .examples
+            .iter()
+            .map(|example| {
+                json!({
+                    "endpoint": "/",
+                    "name": example.name,
+                    "query": example.query,
+                })
+            })
+            .collect();
+
+        let settings = json!({
+            "endpoint": "/",
+            "subscriptionEndpoint": "/ws",
+            "tabs": tabs,
+        })
+        .to_string()
+        // The settings are inlined in a script element, which must not end early
+        .replace("</", "<\\/");
+
+        Self {
+            page: PLAYGROUND_PAGE.replace("PLAYGROUND_SETTINGS", &settings),
+        }
+    }
+}
+
+#[poem::async_trait]
+impl Endpoint for PlaygroundEndpoint {
+    type Output = poem::Response;
+
+    async fn call(&self, _req: poem::Request) -> poem::Result<Self::Output> {
+        Ok(Html(self.page.clone()).into_response())
+    }
+}
+
+const PLAYGROUND_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8" />
+  <meta name="viewport" content="user-scalable=no, initial-scale=1.0, minimum-scale=1.0, maximum-scale=1.0, minimal-ui" />
+  <title>Chronicle Playground</title>
+  <link rel="stylesheet" href="//cdn.jsdelivr.net/npm/graphql-playground-react/build/static/css/index.css" />
+  <script src="//cdn.jsdelivr.net/npm/graphql-playground-react/build/static/js/middleware.js"></script>
+</head>
+<body>
+  <div id="root"></div>
+  <script>
+    window.addEventListener('load', function () {
+      GraphQLPlayground.init(document.getElementById('root'), PLAYGROUND_SETTINGS);
+    });
+  </script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod test {
+    use super::{PlaygroundConf, PlaygroundEndpoint, PlaygroundExample};
+
+    #[test]
+    fn examples_open_as_tabs() {
+        let endpoint = PlaygroundEndpoint::new(&PlaygroundConf::new(vec![PlaygroundExample {
+            name: "Define an agent".to_owned(),
+            query: "mutation { defineAgent(externalId: \"</script>\") { context } }".to_owned(),
+        }]));
+
+        assert!(endpoint.page.contains(r#""name":"Define an agent""#));
+        assert!(endpoint.page.contains(r#"<\/script>"#));
+        assert!(!endpoint.page.contains("PLAYGROUND_SETTINGS"));
+    }
+}
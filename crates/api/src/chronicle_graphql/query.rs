@@ -3,15 +3,18 @@ use async_graphql::{
     Context, ID,
 };
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
-use diesel::{debug_query, pg::Pg, prelude::*};
+use diesel::{debug_query, prelude::*};
 use tracing::{debug, instrument};
 
 use super::{
     cursor_query::{project_to_nodes, Cursorize},
-    Activity, Agent, Entity, GraphQlError, Store, TimelineOrder,
+    readable_namespace, Activity, Agent, Entity, GraphQlError, Store, TimelineOrder,
+};
+use crate::{persistence::schema::generation, DatabaseBackend};
+use common::prov::{
+    to_json_ld::ToJson, ActivityId, AgentId, ChronicleJSON, DomaintypeId, EntityId, ExternalId,
+    ExternalIdPart,
 };
-use crate::persistence::schema::generation;
-use common::prov::{ActivityId, AgentId, DomaintypeId, EntityId, ExternalIdPart};
 
 #[allow(clippy::too_many_arguments)]
 #[instrument(skip(ctx))]
@@ -36,8 +39,7 @@ pub async fn activity_timeline<'a>(
 
     let store = ctx.data_unchecked::<Store>();
 
-    let mut connection = store.pool.get()?;
-    let ns = namespace.unwrap_or_else(|| "default".into());
+    let ns = readable_namespace(ctx, namespace).await?;
 
     // Default from and to to the maximum possible time range
     let from = from.or_else(|| {
@@ -134,14 +136,16 @@ pub async fn activity_timeline<'a>(
         |after, before, first, last| async move {
             debug!(
                 "Cursor query {}",
-                debug_query::<Pg, _>(&sql_query).to_string()
+                debug_query::<DatabaseBackend, _>(&sql_query).to_string()
             );
             let rx = sql_query.cursor(after, before, first, last);
 
             let start = rx.start;
             let limit = rx.limit;
 
-            let rx = rx.load::<(Activity, i64)>(&mut connection)?;
+            let rx = store.read_only(|connection| {
+                Ok::<_, GraphQlError>(rx.load::<(Activity, i64)>(connection)?)
+            })?;
 
             Ok::<_, GraphQlError>(project_to_nodes(rx, start, limit))
         },
@@ -155,19 +159,20 @@ pub async fn entities_by_type<'a>(
     ctx: &Context<'a>,
     typ: Option<DomaintypeId>,
     namespace: Option<ID>,
+    modified_since: Option<DateTime<Utc>>,
+    include_expired: Option<bool>,
     after: Option<String>,
     before: Option<String>,
     first: Option<i32>,
     last: Option<i32>,
 ) -> async_graphql::Result<Connection<i32, Entity, EmptyFields, EmptyFields>> {
-    use crate::persistence::schema::{entity, namespace::dsl as nsdsl};
+    use crate::persistence::schema::{activity, entity, namespace::dsl as nsdsl, usage};
 
     let store = ctx.data_unchecked::<Store>();
 
-    let mut connection = store.pool.get()?;
-    let ns = namespace.unwrap_or_else(|| "default".into());
+    let ns = readable_namespace(ctx, namespace).await?;
 
-    let sql_query = entity::table
+    let mut sql_query = entity::table
         .inner_join(nsdsl::namespace)
         .filter(
             nsdsl::external_id
@@ -175,7 +180,23 @@ pub async fn entities_by_type<'a>(
                 .and(entity::domaintype.eq(typ.as_ref().map(|x| x.external_id_part().to_owned()))),
         )
         .select(Entity::as_select())
-        .order_by(entity::external_id.asc());
+        .order_by(entity::external_id.asc())
+        .into_boxed::<DatabaseBackend>();
+
+    if let Some(since) = modified_since {
+        sql_query = sql_query.filter(entity::updated_at.ge(since.naive_utc()));
+    }
+
+    if !include_expired.unwrap_or(false) {
+        sql_query = sql_query.filter(
+            entity::id.ne_all(
+                usage::table
+                    .inner_join(activity::table)
+                    .filter(activity::domaintype.eq(crate::expiry::EXPIRY_ACTIVITY_TYPE))
+                    .select(usage::entity_id),
+            ),
+        );
+    }
 
     query(
         after,
@@ -185,14 +206,16 @@ pub async fn entities_by_type<'a>(
         |after, before, first, last| async move {
             debug!(
                 "Cursor query {}",
-                debug_query::<Pg, _>(&sql_query).to_string()
+                debug_query::<DatabaseBackend, _>(&sql_query).to_string()
             );
             let rx = sql_query.cursor(after, before, first, last);
 
             let start = rx.start;
             let limit = rx.limit;
 
-            let rx = rx.load::<(Entity, i64)>(&mut connection)?;
+            let rx = store.read_only(|connection| {
+                Ok::<_, GraphQlError>(rx.load::<(Entity, i64)>(connection)?)
+            })?;
 
             Ok::<_, GraphQlError>(project_to_nodes(rx, start, limit))
         },
@@ -205,6 +228,7 @@ pub async fn activities_by_type<'a>(
     ctx: &Context<'a>,
     typ: Option<DomaintypeId>,
     namespace: Option<ID>,
+    modified_since: Option<DateTime<Utc>>,
     after: Option<String>,
     before: Option<String>,
     first: Option<i32>,
@@ -214,17 +238,21 @@ pub async fn activities_by_type<'a>(
 
     let store = ctx.data_unchecked::<Store>();
 
-    let mut connection = store.pool.get()?;
-    let ns = namespace.unwrap_or_else(|| "default".into());
+    let ns = readable_namespace(ctx, namespace).await?;
 
-    let sql_query =
+    let mut sql_query =
         activity::table
             .inner_join(nsdsl::namespace)
             .filter(nsdsl::external_id.eq(&**ns).and(
                 activity::domaintype.eq(typ.as_ref().map(|x| x.external_id_part().to_owned())),
             ))
             .select(Activity::as_select())
-            .order_by(activity::external_id.asc());
+            .order_by(activity::external_id.asc())
+            .into_boxed::<DatabaseBackend>();
+
+    if let Some(since) = modified_since {
+        sql_query = sql_query.filter(activity::updated_at.ge(since.naive_utc()));
+    }
 
     query(
         after,
@@ -234,14 +262,16 @@ pub async fn activities_by_type<'a>(
         |after, before, first, last| async move {
             debug!(
                 "Cursor query {}",
-                debug_query::<Pg, _>(&sql_query).to_string()
+                debug_query::<DatabaseBackend, _>(&sql_query).to_string()
             );
             let rx = sql_query.cursor(after, before, first, last);
 
             let start = rx.start;
             let limit = rx.limit;
 
-            let rx = rx.load::<(Activity, i64)>(&mut connection)?;
+            let rx = store.read_only(|connection| {
+                Ok::<_, GraphQlError>(rx.load::<(Activity, i64)>(connection)?)
+            })?;
 
             Ok::<_, GraphQlError>(project_to_nodes(rx, start, limit))
         },
@@ -254,6 +284,7 @@ pub async fn agents_by_type<'a>(
     ctx: &Context<'a>,
     typ: Option<DomaintypeId>,
     namespace: Option<ID>,
+    modified_since: Option<DateTime<Utc>>,
     after: Option<String>,
     before: Option<String>,
     first: Option<i32>,
@@ -263,10 +294,9 @@ pub async fn agents_by_type<'a>(
 
     let store = ctx.data_unchecked::<Store>();
 
-    let mut connection = store.pool.get()?;
-    let ns = namespace.unwrap_or_else(|| "default".into());
+    let ns = readable_namespace(ctx, namespace).await?;
 
-    let sql_query = agent::table
+    let mut sql_query = agent::table
         .inner_join(nsdsl::namespace)
         .filter(
             nsdsl::external_id
@@ -274,7 +304,12 @@ pub async fn agents_by_type<'a>(
                 .and(agent::domaintype.eq(typ.as_ref().map(|x| x.external_id_part().to_owned()))),
         )
         .select(Agent::as_select())
-        .order_by(agent::external_id.asc());
+        .order_by(agent::external_id.asc())
+        .into_boxed::<DatabaseBackend>();
+
+    if let Some(since) = modified_since {
+        sql_query = sql_query.filter(agent::updated_at.ge(since.naive_utc()));
+    }
 
     query(
         after,
@@ -284,14 +319,16 @@ pub async fn agents_by_type<'a>(
         |after, before, first, last| async move {
             debug!(
                 "Cursor query {}",
-                debug_query::<Pg, _>(&sql_query).to_string()
+                debug_query::<DatabaseBackend, _>(&sql_query).to_string()
             );
             let rx = sql_query.cursor(after, before, first, last);
 
             let start = rx.start;
             let limit = rx.limit;
 
-            let rx = rx.load::<(Agent, i64)>(&mut connection)?;
+            let rx = store.read_only(|connection| {
+                Ok::<_, GraphQlError>(rx.load::<(Agent, i64)>(connection)?)
+            })?;
 
             Ok::<_, GraphQlError>(project_to_nodes(rx, start, limit))
         },
@@ -311,19 +348,19 @@ pub async fn agent_by_id<'a>(
 
     let store = ctx.data_unchecked::<Store>();
 
-    let ns = namespace.unwrap_or_else(|| "default".into());
-    let mut connection = store.pool.get()?;
-
-    Ok(agent::table
-        .inner_join(nsdsl::namespace)
-        .filter(
-            dsl::external_id
-                .eq(id.external_id_part())
-                .and(nsdsl::external_id.eq(&ns)),
-        )
-        .select(Agent::as_select())
-        .first::<Agent>(&mut connection)
-        .optional()?)
+    let ns = readable_namespace(ctx, namespace).await?;
+    store.read_only(|connection| {
+        Ok(agent::table
+            .inner_join(nsdsl::namespace)
+            .filter(
+                dsl::external_id
+                    .eq(id.external_id_part())
+                    .and(nsdsl::external_id.eq(&ns)),
+            )
+            .select(Agent::as_select())
+            .first::<Agent>(connection)
+            .optional()?)
+    })
 }
 
 pub async fn activity_by_id<'a>(
@@ -338,19 +375,19 @@ pub async fn activity_by_id<'a>(
 
     let store = ctx.data_unchecked::<Store>();
 
-    let ns = namespace.unwrap_or_else(|| "default".into());
-    let mut connection = store.pool.get()?;
-
-    Ok(activity::table
-        .inner_join(nsdsl::namespace)
-        .filter(
-            dsl::external_id
-                .eq(id.external_id_part())
-                .and(nsdsl::external_id.eq(&ns)),
-        )
-        .select(Activity::as_select())
-        .first::<Activity>(&mut connection)
-        .optional()?)
+    let ns = readable_namespace(ctx, namespace).await?;
+    store.read_only(|connection| {
+        Ok(activity::table
+            .inner_join(nsdsl::namespace)
+            .filter(
+                dsl::external_id
+                    .eq(id.external_id_part())
+                    .and(nsdsl::external_id.eq(&ns)),
+            )
+            .select(Activity::as_select())
+            .first::<Activity>(connection)
+            .optional()?)
+    })
 }
 
 pub async fn entity_by_id<'a>(
@@ -364,17 +401,36 @@ pub async fn entity_by_id<'a>(
     };
 
     let store = ctx.data_unchecked::<Store>();
-    let ns = namespace.unwrap_or_else(|| "default".into());
-    let mut connection = store.pool.get()?;
+    let ns = readable_namespace(ctx, namespace).await?;
+    store.read_only(|connection| {
+        Ok(entity::table
+            .inner_join(nsdsl::namespace)
+            .filter(
+                dsl::external_id
+                    .eq(id.external_id_part())
+                    .and(nsdsl::external_id.eq(&ns)),
+            )
+            .select(Entity::as_select())
+            .first::<Entity>(connection)
+            .optional()?)
+    })
+}
 
-    Ok(entity::table
-        .inner_join(nsdsl::namespace)
-        .filter(
-            dsl::external_id
-                .eq(id.external_id_part())
-                .and(nsdsl::external_id.eq(&ns)),
-        )
-        .select(Entity::as_select())
-        .first::<Entity>(&mut connection)
-        .optional()?)
+/// The provenance upstream of an entity as compacted JSON-LD, following
+/// derivation, generation and usage relations for at most `depth` hops
+#[instrument(skip(ctx))]
+pub async fn lineage<'a>(
+    ctx: &Context<'a>,
+    id: EntityId,
+    depth: u32,
+    namespace: Option<String>,
+) -> async_graphql::Result<ChronicleJSON> {
+    let store = ctx.data_unchecked::<Store>().persistence()?;
+    let ns: String = readable_namespace(ctx, namespace).await?;
+
+    let model = store.read_only(|connection| {
+        store.prov_model_for_lineage(connection, &id, &ExternalId::from(ns), depth)
+    })?;
+
+    Ok(ChronicleJSON(model.to_json().compact().await?))
 }
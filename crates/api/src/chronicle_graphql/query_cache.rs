@@ -0,0 +1,144 @@
+//! An in-process cache of query responses, so that dashboards polling the same
+//! queries are answered without running them again. Responses are keyed by
+//! their entity tag, which covers the versions of the namespaces queried, the
+//! requests and the caller's claims, and the cache is emptied on each commit.
+//! Queries that are not tagged, as those selecting fields answered from
+//! outside the ledger are not, are never cached.
+
+use std::sync::{Arc, Mutex};
+
+use cached::{Cached, SizedCache};
+use common::ledger::SubmissionStage;
+use poem::http::{HeaderMap, StatusCode};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use crate::ApiDispatch;
+
+#[derive(Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+/// Responses to queries, holding up to a fixed number of the most recently
+/// used
+#[derive(Clone)]
+pub struct QueryCache {
+    capacity: usize,
+    responses: Arc<Mutex<SizedCache<String, CachedResponse>>>,
+}
+
+impl std::fmt::Debug for QueryCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryCache")
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl QueryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            responses: Arc::new(Mutex::new(SizedCache::with_size(capacity.max(1)))),
+        }
+    }
+
+    /// The response cached under `key`, if any
+    pub fn get(&self, key: &str) -> Option<poem::Response> {
+        let mut responses = self.responses.lock().unwrap();
+        let cached = responses.cache_get(&key.to_owned())?;
+
+        let mut response = poem::Response::builder()
+            .status(cached.status)
+            .body(cached.body.clone());
+        *response.headers_mut() = cached.headers.clone();
+
+        Some(response)
+    }
+
+    /// Cache `response` under `key`, returning it to be sent
+    pub async fn insert(&self, key: String, mut response: poem::Response) -> poem::Response {
+        let body = match response.take_body().into_vec().await {
+            Ok(body) => body,
+            Err(error) => {
+                warn!(%error, "Cannot read query response to cache it");
+                return poem::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .finish();
+            }
+        };
+
+        self.responses.lock().unwrap().cache_set(
+            key,
+            CachedResponse {
+                status: response.status(),
+                headers: response.headers().clone(),
+                body: body.clone(),
+            },
+        );
+
+        response.set_body(body);
+        response
+    }
+
+    pub fn clear(&self) {
+        self.responses.lock().unwrap().cache_clear();
+    }
+
+    /// Empty the cache whenever a transaction commits, until the api stops
+    pub fn spawn_invalidation(&self, api: &ApiDispatch) {
+        let cache = self.clone();
+        let mut commits = api.notify_commit.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match commits.recv().await {
+                    Ok(SubmissionStage::Committed(..)) => {
+                        debug!("Transaction committed, clearing query cache");
+                        cache.clear();
+                    }
+                    Ok(_) => continue,
+                    // Commits were missed, any of which may have changed results
+                    Err(RecvError::Lagged(_)) => cache.clear(),
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use poem::http::StatusCode;
+
+    use super::QueryCache;
+
+    #[tokio::test]
+    async fn cached_responses_are_served_until_cleared() {
+        let cache = QueryCache::new(10);
+        assert!(cache.get("\"tag\"").is_none());
+
+        let response = poem::Response::builder()
+            .content_type("application/json")
+            .body(r#"{"data":{}}"#);
+        let response = cache.insert("\"tag\"".to_owned(), response).await;
+        assert_eq!(
+            response.into_body().into_string().await.unwrap(),
+            r#"{"data":{}}"#
+        );
+
+        let cached = cache.get("\"tag\"").unwrap();
+        assert_eq!(cached.status(), StatusCode::OK);
+        assert_eq!(cached.content_type(), Some("application/json"));
+        assert_eq!(
+            cached.into_body().into_string().await.unwrap(),
+            r#"{"data":{}}"#
+        );
+
+        cache.clear();
+        assert!(cache.get("\"tag\"").is_none());
+    }
+}
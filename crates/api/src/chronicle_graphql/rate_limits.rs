@@ -0,0 +1,352 @@
+//! Quotas on how often each principal may submit, so that one client of a
+//! shared deployment cannot starve the others. Each principal has a token
+//! bucket that refills at its quota's rate per minute and holds up to its
+//! burst, and each top-level mutation or REST definition takes a token.
+//! Submissions with none left are refused, GraphQL mutations with an error
+//! whose `code` extension is `RATE_LIMITED` and REST definitions with
+//! `429 Too Many Requests`, both saying when to retry. Only the mutations over
+//! quota are refused, so a GraphQL request is answered `429` only if every
+//! mutation in it, or in each request of a batch, was refused.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo},
+    ServerError, ServerResult, Value,
+};
+use common::identity::{AuthId, JwtClaims};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// The code of errors refusing mutations over quota
+pub const RATE_LIMITED: &str = "RATE_LIMITED";
+
+#[derive(Error, Debug)]
+pub enum RateLimitsError {
+    #[error("Could not read rate limits {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Rate limits {path} are not valid: {source}")]
+    Invalid {
+        path: String,
+        source: serde_json::Error,
+    },
+}
+
+/// Submissions a principal may make each minute, and how many it may make at
+/// once, which is `perMinute` if not set. A `perMinute` of 0 refuses every
+/// submission.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Quota {
+    per_minute: u32,
+    burst: Option<u32>,
+}
+
+impl Quota {
+    fn capacity(&self) -> f64 {
+        self.burst.unwrap_or(self.per_minute).max(1) as f64
+    }
+
+    fn per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+/// How often buckets that have refilled are dropped
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A principal's bucket, for quotas that refill
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// When the bucket will have refilled
+    full_at: Instant,
+}
+
+impl Bucket {
+    fn new(quota: &Quota, now: Instant) -> Self {
+        Bucket {
+            tokens: quota.capacity(),
+            updated: now,
+            full_at: now,
+        }
+    }
+
+    /// Take a token, refilling the bucket for the time since it was last
+    /// updated, or the time until one will be available if none is
+    fn take(&mut self, quota: &Quota, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * quota.per_second()).min(quota.capacity());
+        self.updated = now;
+
+        let taken = if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / quota.per_second(),
+            ))
+        };
+
+        self.full_at =
+            now + Duration::from_secs_f64((quota.capacity() - self.tokens) / quota.per_second());
+
+        taken
+    }
+}
+
+/// The buckets of principals that have submitted, as of when buckets that
+/// had refilled were last dropped
+#[derive(Debug, Default)]
+struct Buckets {
+    by_principal: HashMap<String, Bucket>,
+    swept: Option<Instant>,
+}
+
+impl Buckets {
+    /// Drop the buckets that have refilled by `now`, at most once each
+    /// [SWEEP_INTERVAL]. A full bucket is the same as none, so this only
+    /// bounds the memory that principals who stop submitting hold.
+    fn sweep(&mut self, now: Instant) {
+        if let Some(swept) = self.swept {
+            if now.saturating_duration_since(swept) < SWEEP_INTERVAL {
+                return;
+            }
+        }
+
+        self.by_principal.retain(|_, bucket| bucket.full_at > now);
+        self.swept = Some(now);
+    }
+}
+
+fn default_claim() -> String {
+    "roles".to_owned()
+}
+
+/// The quotas of principals, read from a JSON file such as
+/// `{"claim": "roles", "default": {"perMinute": 60}, "roles": {"ingest": {"perMinute": 600, "burst": 100}}, "identities": {"alice": {"perMinute": 10}}}`.
+/// A principal's own quota applies in preference to those of its roles, the
+/// most generous of which applies in preference to the default. Principals
+/// with no quota are not limited.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimits {
+    /// The JWT claim listing the principal's roles
+    #[serde(default = "default_claim")]
+    claim: String,
+    default: Option<Quota>,
+    #[serde(default)]
+    roles: BTreeMap<String, Quota>,
+    /// Quotas keyed by identity, `Anonymous` or the external id of a JWT
+    /// identity
+    #[serde(default)]
+    identities: BTreeMap<String, Quota>,
+    #[serde(skip)]
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimits {
+    pub fn from_file(path: &Path) -> Result<Self, RateLimitsError> {
+        let json = std::fs::read_to_string(path).map_err(|source| RateLimitsError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let limits: Self =
+            serde_json::from_str(&json).map_err(|source| RateLimitsError::Invalid {
+                path: path.display().to_string(),
+                source,
+            })?;
+        info!(
+            roles = limits.roles.len(),
+            identities = limits.identities.len(),
+            "Limiting the rate of submissions"
+        );
+
+        Ok(limits)
+    }
+
+    /// The quota of `identity`, by its own, its roles' or the default
+    fn quota(&self, identity: &str, claims: Option<&JwtClaims>) -> Option<Quota> {
+        if let Some(quota) = self.identities.get(identity) {
+            return Some(*quota);
+        }
+
+        let roles = match claims.and_then(|claims| claims.0.get(&self.claim)) {
+            Some(serde_json::Value::String(role)) => vec![role.as_str()],
+            Some(serde_json::Value::Array(roles)) => {
+                roles.iter().filter_map(serde_json::Value::as_str).collect()
+            }
+            _ => vec![],
+        };
+
+        roles
+            .into_iter()
+            .filter_map(|role| self.roles.get(role))
+            .copied()
+            .max_by(|a, b| a.per_second().total_cmp(&b.per_second()))
+            .or(self.default)
+    }
+
+    /// Take one of the submissions `identity` may make as of `now`, or the
+    /// time until it may make another
+    fn take_at(
+        &self,
+        identity: &AuthId,
+        claims: Option<&JwtClaims>,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        // Chronicle's own submissions, such as held commands, are not limited
+        if *identity == AuthId::chronicle() {
+            return Ok(());
+        }
+
+        let key = identity.to_string();
+        let quota = match self.quota(&key, claims) {
+            Some(quota) => quota,
+            None => return Ok(()),
+        };
+
+        if quota.per_minute == 0 {
+            return Err(Duration::from_secs(60));
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.sweep(now);
+        buckets
+            .by_principal
+            .entry(key)
+            .or_insert_with(|| Bucket::new(&quota, now))
+            .take(&quota, now)
+    }
+
+    /// Take one of the submissions `identity` may make, or the time until it
+    /// may make another
+    pub fn take(&self, identity: &AuthId, claims: Option<&JwtClaims>) -> Result<(), Duration> {
+        self.take_at(identity, claims, Instant::now())
+    }
+}
+
+/// Whole seconds until a refused submission may be retried, as for a
+/// `Retry-After` header
+pub fn retry_after_seconds(retry_after: Duration) -> u64 {
+    retry_after.as_secs_f64().ceil().max(1.0) as u64
+}
+
+#[async_trait::async_trait]
+impl Extension for RateLimits {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        if info.path_node.parent.is_some() || info.parent_type != "Mutation" {
+            return next.run(ctx, info).await;
+        }
+
+        let identity = ctx.data_unchecked::<AuthId>();
+        match self.take(identity, ctx.data_opt::<JwtClaims>()) {
+            Ok(()) => next.run(ctx, info).await,
+            Err(retry_after) => {
+                debug!(%identity, field = info.name, "Submission over quota");
+                let retry_after = retry_after_seconds(retry_after);
+                let mut error = ServerError::new(
+                    format!("Rate limit exceeded, retry after {retry_after} seconds"),
+                    None,
+                );
+                let extensions = error.extensions.get_or_insert_with(Default::default);
+                extensions.set("code", RATE_LIMITED);
+                extensions.set("retryAfter", retry_after);
+                Err(error)
+            }
+        }
+    }
+}
+
+impl ExtensionFactory for RateLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use common::identity::{AuthId, JwtClaims};
+    use serde_json::json;
+
+    use super::RateLimits;
+
+    fn claims(value: serde_json::Value) -> JwtClaims {
+        JwtClaims(value.as_object().unwrap().clone())
+    }
+
+    #[test]
+    fn submissions_are_limited_by_identity_then_role_then_default() {
+        let limits: RateLimits = serde_json::from_value(json!({
+            "default": {"perMinute": 60, "burst": 1},
+            "roles": {"ingest": {"perMinute": 120, "burst": 2}},
+            "identities": {"Anonymous": {"perMinute": 0}}
+        }))
+        .unwrap();
+
+        let now = Instant::now();
+        let ingest = claims(json!({"roles": ["ingest"]}));
+        let identity = AuthId::anonymous();
+
+        // Anonymous principals have their own quota of nothing
+        assert!(limits.take_at(&identity, Some(&ingest), now).is_err());
+        assert!(limits.take_at(&AuthId::chronicle(), None, now).is_ok());
+
+        let limits: RateLimits = serde_json::from_value(json!({
+            "default": {"perMinute": 60, "burst": 1},
+            "roles": {"ingest": {"perMinute": 120, "burst": 2}}
+        }))
+        .unwrap();
+
+        assert!(limits.take_at(&identity, Some(&ingest), now).is_ok());
+        assert!(limits.take_at(&identity, Some(&ingest), now).is_ok());
+        assert_eq!(
+            limits.take_at(&identity, Some(&ingest), now),
+            Err(Duration::from_millis(500))
+        );
+        assert!(limits
+            .take_at(&identity, Some(&ingest), now + Duration::from_millis(500))
+            .is_ok());
+    }
+
+    #[test]
+    fn buckets_are_dropped_once_they_refill() {
+        let limits: RateLimits = serde_json::from_value(json!({
+            "default": {"perMinute": 60, "burst": 2},
+            "identities": {"Anonymous": {"perMinute": 0, "burst": 1}}
+        }))
+        .unwrap();
+
+        let now = Instant::now();
+        let alice =
+            AuthId::from_jwt_claims(&claims(json!({"sub": "alice"})), &["sub".to_owned()].into())
+                .unwrap();
+
+        assert!(limits.take_at(&alice, None, now).is_ok());
+        assert!(limits.take_at(&AuthId::anonymous(), None, now).is_err());
+        // A quota that never refills refuses without holding a bucket
+        assert_eq!(limits.buckets.lock().unwrap().by_principal.len(), 1);
+
+        let later = now + Duration::from_secs(61);
+        assert!(limits.take_at(&AuthId::anonymous(), None, later).is_err());
+        assert!(limits.buckets.lock().unwrap().by_principal.is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+//! Receipts for transactions this deployment submitted, signed with its
+//! chronicle key once they commit, so that a party holding one can later show
+//! that the operations it describes were included in the ledger at a block,
+//! without trusting whoever passed the receipt on.
+
+use async_graphql::{Context, SimpleObject};
+use chronicle_signing::{ChronicleKnownKeyNamesSigner, ChronicleSigning, SecretError};
+use common::k256::{
+    ecdsa::VerifyingKey,
+    pkcs8::EncodePublicKey,
+    sha2::{Digest, Sha256},
+};
+use serde::Serialize;
+
+use super::Store;
+
+/// Hex SHA-256 of the body a transaction was submitted with, its operations as
+/// compact JSON-LD, as the ledger holds it
+pub fn payload_digest(payload: &str) -> String {
+    hex::encode(Sha256::digest(payload.as_bytes()))
+}
+
+/// Hex SHA-256 of the DER encoding of a verifying key, identifying the key
+/// whether or not it has since been rotated
+pub fn key_id(key: &VerifyingKey) -> Result<String, SecretError> {
+    let der = key
+        .to_public_key_der()
+        .map_err(|_| SecretError::InvalidPublicKey)?;
+    Ok(hex::encode(Sha256::digest(der.as_bytes())))
+}
+
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Receipt {
+    /// The transaction the receipt is for
+    pub tx_id: String,
+    /// The block the transaction committed in
+    pub block_id: String,
+    /// Hex SHA-256 of the body of the transaction, its operations as compact
+    /// JSON-LD
+    pub operation_digest: String,
+    /// Hex SHA-256 of the DER encoded key that verifies `signature`
+    pub key_id: String,
+    /// Hex signature with the chronicle key of the other fields, as a JSON
+    /// object with their names as keys in the order they are listed here, that
+    /// `serverInfo.verifyingKey` verifies unless the key has been rotated
+    #[serde(skip)]
+    pub signature: String,
+}
+
+impl Receipt {
+    /// Sign a receipt for a transaction that committed in `block_id`
+    pub async fn sign(
+        tx_id: &str,
+        block_id: &str,
+        operation_digest: &str,
+        signing: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let mut receipt = Self {
+            tx_id: tx_id.to_owned(),
+            block_id: block_id.to_owned(),
+            operation_digest: operation_digest.to_owned(),
+            key_id: key_id(&signing.chronicle_verifying().await?)?,
+            signature: String::new(),
+        };
+
+        let signed = serde_json::to_vec(&receipt).expect("receipt serializes as JSON");
+        receipt.signature = hex::encode(signing.chronicle_sign(&signed).await?);
+
+        Ok(receipt)
+    }
+}
+
+/// The receipt of a transaction, once it has committed
+pub async fn receipt<'a>(
+    ctx: &Context<'a>,
+    tx_id: String,
+) -> async_graphql::Result<Option<Receipt>> {
+    let store = ctx.data_unchecked::<Store>().persistence()?;
+
+    let receipt = store.read_only(|connection| store.receipt(connection, &tx_id))?;
+
+    Ok(receipt.and_then(|receipt| {
+        Some(Receipt {
+            tx_id: receipt.tx_id,
+            block_id: receipt.block_id?,
+            operation_digest: receipt.operation_digest,
+            key_id: receipt.key_id?,
+            signature: receipt.signature?,
+        })
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use chronicle_protocol::messages::operations_payload;
+    use chronicle_signing::{
+        chronicle_secret_names, ChronicleKnownKeyNamesSigner, ChronicleSecretsOptions,
+        ChronicleSigning, BATCHER_NAMESPACE, CHRONICLE_NAMESPACE,
+    };
+    use common::prov::{
+        operations::{ChronicleOperation, CreateNamespace},
+        NamespaceId,
+    };
+    use uuid::Uuid;
+
+    use super::{key_id, payload_digest, Receipt};
+
+    #[tokio::test]
+    async fn signature_covers_receipt() {
+        let signing = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let operations = [ChronicleOperation::CreateNamespace(CreateNamespace::new(
+            NamespaceId::from_external_id("testns", Uuid::nil()),
+            "testns",
+            Uuid::nil(),
+        ))];
+        let digest = payload_digest(&operations_payload(&operations).await.unwrap());
+        assert_eq!(digest.len(), 64);
+
+        let receipt = Receipt::sign("tx1", "block1", &digest, &signing)
+            .await
+            .unwrap();
+        assert_eq!(
+            receipt.key_id,
+            key_id(&signing.chronicle_verifying().await.unwrap()).unwrap()
+        );
+
+        let signed = serde_json::to_vec(&receipt).unwrap();
+        let signature = hex::decode(&receipt.signature).unwrap();
+        assert!(signing.chronicle_verify(&signed, &signature).await.unwrap());
+
+        let mut changed = receipt.clone();
+        changed.block_id = "block2".to_owned();
+        let signed = serde_json::to_vec(&changed).unwrap();
+        assert!(!signing.chronicle_verify(&signed, &signature).await.unwrap());
+    }
+}
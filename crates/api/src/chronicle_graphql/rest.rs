@@ -0,0 +1,607 @@
+//! A REST facade for integrators that cannot use GraphQL. Agents, activities
+//! and entities are defined with `POST /namespaces/{ns}/{kind}` and read as
+//! JSON-LD with `GET /namespaces/{ns}/{kind}/{externalId}`, where `kind` is
+//! `agents`, `activities` or `entities`. Definitions are checked against the
+//! domain's types and policy as the GraphQL mutation they correspond to, and
+//! reads as the `/data` endpoints, so policies apply to both interfaces alike.
+//! Definitions count against the same rate limits as mutations.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use common::{
+    attributes::{Attribute, AttributeTypeError, Attributes},
+    commands::{ActivityCommand, AgentCommand, ApiCommand, ApiResponse, EntityCommand},
+    identity::{AuthId, JwtClaims, OpaData},
+    prov::{ActivityId, AgentId, ChronicleIri, DomaintypeId, EntityId},
+};
+use poem::{
+    http::{header::RETRY_AFTER, StatusCode},
+    web::{Json, Path},
+    Endpoint, FromRequest, IntoResponse,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tracing::{debug, instrument};
+
+use super::{
+    check_claims, execute_opa_check, retry_after_seconds, EndpointSecurityConfiguration,
+    IriEndpoint, Permission, RateLimits, IDEMPOTENCY_KEY,
+};
+use crate::{ApiDispatch, ApiError};
+
+/// The kinds of record addressed by the path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum RecordKind {
+    Agent,
+    Activity,
+    Entity,
+}
+
+impl RecordKind {
+    fn from_path(segment: &str) -> Option<Self> {
+        match segment {
+            "agents" => Some(RecordKind::Agent),
+            "activities" => Some(RecordKind::Activity),
+            "entities" => Some(RecordKind::Entity),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            RecordKind::Agent => "agent",
+            RecordKind::Activity => "activity",
+            RecordKind::Entity => "entity",
+        }
+    }
+
+    /// The GraphQL mutation defining a record of this kind and domain type
+    fn mutation(&self, domaintype: Option<&str>) -> String {
+        match domaintype {
+            Some(domaintype) => format!("define{domaintype}"),
+            None => match self {
+                RecordKind::Agent => "defineAgent".to_owned(),
+                RecordKind::Activity => "defineActivity".to_owned(),
+                RecordKind::Entity => "defineEntity".to_owned(),
+            },
+        }
+    }
+}
+
+/// An attribute a record type is defined with, as the domain declares it
+#[derive(Debug, Clone)]
+pub struct AttributeType {
+    /// The attribute's name in the domain, which it is recorded under
+    pub name: String,
+    /// The name of the attribute's primitive type, such as `String` or `Int`
+    pub primitive_type: String,
+    /// Whether the attribute holds a list of values of its primitive type
+    pub repeated: bool,
+}
+
+/// The agent, activity and entity types of the domain, each with the
+/// attributes it is defined with, that definitions are checked against as
+/// the GraphQL schema generated from the domain checks its mutations
+#[derive(Debug, Clone, Default)]
+pub struct DomainTypes {
+    types: BTreeMap<(RecordKind, String), Vec<AttributeType>>,
+}
+
+#[derive(Error, Debug)]
+enum DefinitionError {
+    #[error("{typ} is not a type of {kind} in the domain")]
+    UnknownType { kind: &'static str, typ: String },
+
+    #[error("Records without a type have no attributes")]
+    Untyped,
+
+    #[error("{typ} has no attribute {attribute}")]
+    UnknownAttribute { typ: String, attribute: String },
+
+    #[error("{typ} requires the attribute {attribute}")]
+    MissingAttribute { typ: String, attribute: String },
+
+    #[error("{0}")]
+    AttributeType(#[from] AttributeTypeError),
+}
+
+impl DomainTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define the agent type named `typ` by GraphQL with `attributes`
+    pub fn with_agent(
+        self,
+        typ: impl Into<String>,
+        attributes: impl IntoIterator<Item = AttributeType>,
+    ) -> Self {
+        self.with_type(RecordKind::Agent, typ, attributes)
+    }
+
+    /// Define the activity type named `typ` by GraphQL with `attributes`
+    pub fn with_activity(
+        self,
+        typ: impl Into<String>,
+        attributes: impl IntoIterator<Item = AttributeType>,
+    ) -> Self {
+        self.with_type(RecordKind::Activity, typ, attributes)
+    }
+
+    /// Define the entity type named `typ` by GraphQL with `attributes`
+    pub fn with_entity(
+        self,
+        typ: impl Into<String>,
+        attributes: impl IntoIterator<Item = AttributeType>,
+    ) -> Self {
+        self.with_type(RecordKind::Entity, typ, attributes)
+    }
+
+    fn with_type(
+        mut self,
+        kind: RecordKind,
+        typ: impl Into<String>,
+        attributes: impl IntoIterator<Item = AttributeType>,
+    ) -> Self {
+        self.types
+            .insert((kind, typ.into()), attributes.into_iter().collect());
+        self
+    }
+
+    /// The attributes of a definition of a `kind` of record, once checked
+    /// against its type. As with the GraphQL mutations, a typed record must
+    /// be given every attribute of its type, and an untyped one none.
+    fn attributes(
+        &self,
+        kind: RecordKind,
+        definition: &Definition,
+    ) -> Result<Attributes, DefinitionError> {
+        let typ = match &definition.domaintype {
+            Some(typ) => typ,
+            None if definition.attributes.is_empty() => return Ok(Attributes::type_only(None)),
+            None => return Err(DefinitionError::Untyped),
+        };
+        let declared =
+            self.types
+                .get(&(kind, typ.clone()))
+                .ok_or_else(|| DefinitionError::UnknownType {
+                    kind: kind.name(),
+                    typ: typ.clone(),
+                })?;
+
+        if let Some(attribute) = definition
+            .attributes
+            .keys()
+            .find(|name| !declared.iter().any(|declared| &declared.name == *name))
+        {
+            return Err(DefinitionError::UnknownAttribute {
+                typ: typ.clone(),
+                attribute: attribute.clone(),
+            });
+        }
+
+        let mut attributes = Attributes::type_only(Some(DomaintypeId::from_external_id(typ)));
+        for attribute in declared {
+            let value = definition
+                .attributes
+                .get(&attribute.name)
+                .cloned()
+                .ok_or_else(|| DefinitionError::MissingAttribute {
+                    typ: typ.clone(),
+                    attribute: attribute.name.clone(),
+                })?;
+            let value = if attribute.primitive_type == "DateTime" {
+                in_utc(value)
+            } else {
+                value
+            };
+            attributes.attributes.insert(
+                attribute.name.clone(),
+                Attribute::typed(
+                    &attribute.name,
+                    &attribute.primitive_type,
+                    attribute.repeated,
+                    value,
+                )?,
+            );
+        }
+
+        Ok(attributes)
+    }
+}
+
+/// Timestamps in `value` normalized to UTC, as the GraphQL mutations record
+/// them, leaving anything that is not a timestamp to fail its type check
+fn in_utc(value: Value) -> Value {
+    match value {
+        Value::String(timestamp) => match DateTime::parse_from_rfc3339(&timestamp) {
+            Ok(timestamp) => Value::String(timestamp.with_timezone(&Utc).to_rfc3339()),
+            Err(_) => Value::String(timestamp),
+        },
+        Value::Array(values) => Value::Array(values.into_iter().map(in_utc).collect()),
+        value => value,
+    }
+}
+
+/// The body of a definition, attributes are keyed by their name in the domain
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Definition {
+    external_id: String,
+    #[serde(rename = "type")]
+    domaintype: Option<String>,
+    #[serde(default)]
+    attributes: BTreeMap<String, Value>,
+}
+
+impl Definition {
+    fn command(self, kind: RecordKind, namespace: String, attributes: Attributes) -> ApiCommand {
+        let external_id = self.external_id.into();
+        let namespace = namespace.into();
+
+        match kind {
+            RecordKind::Agent => ApiCommand::Agent(AgentCommand::Create {
+                external_id,
+                namespace,
+                attributes,
+            }),
+            RecordKind::Activity => ApiCommand::Activity(ActivityCommand::Create {
+                external_id,
+                namespace,
+                attributes,
+            }),
+            RecordKind::Entity => ApiCommand::Entity(EntityCommand::Create {
+                external_id,
+                namespace,
+                attributes,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Collection {
+    ns: String,
+    kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Record {
+    ns: String,
+    kind: String,
+    id: String,
+}
+
+fn error_response(status: StatusCode, message: impl ToString) -> poem::Response {
+    poem::Response::builder()
+        .status(status)
+        .body(message.to_string())
+}
+
+fn submission_response(subject: &ChronicleIri, tx_id: Option<String>) -> poem::Response {
+    let (status, result) = match tx_id {
+        Some(_) => (StatusCode::ACCEPTED, "SUBMISSION"),
+        None => (StatusCode::OK, "ALREADY_RECORDED"),
+    };
+
+    Json(json!({
+        "context": subject.to_string(),
+        "submissionResult": result,
+        "txId": tx_id,
+    }))
+    .with_status(status)
+    .into_response()
+}
+
+/// Defines records from `POST /namespaces/:ns/:kind`
+pub(super) struct DefineEndpoint {
+    pub(super) secconf: Option<EndpointSecurityConfiguration>,
+    pub(super) api: ApiDispatch,
+    pub(super) data: IriEndpoint,
+    pub(super) rate_limits: Option<RateLimits>,
+    pub(super) domain_types: DomainTypes,
+}
+
+impl DefineEndpoint {
+    /// The identity to submit as, as the GraphQL api determines it
+    fn identity(&self, claims: Option<&JwtClaims>) -> Result<AuthId, poem::Response> {
+        match (claims, &self.data.claim_parser) {
+            (Some(claims), Some(parser)) => match parser.identity(claims) {
+                Ok(identity) => Ok(identity),
+                Err(error) if parser.allow_anonymous => {
+                    debug!("Identity could not be determined: {:?}", error);
+                    Ok(AuthId::anonymous())
+                }
+                Err(_) => Err(error_response(
+                    StatusCode::UNAUTHORIZED,
+                    "Authorization header present but identity could not be determined from bearer token",
+                )),
+            },
+            _ => Ok(AuthId::anonymous()),
+        }
+    }
+
+    #[instrument(level = "trace", skip(self, req, claims), ret(Debug))]
+    async fn respond(
+        &self,
+        req: poem::Request,
+        claims: Option<&JwtClaims>,
+    ) -> poem::Result<poem::Response> {
+        let idempotency_key = req.header(IDEMPOTENCY_KEY).map(str::to_owned);
+        let (req, mut body) = req.split();
+        let Path(Collection { ns, kind }) = FromRequest::from_request(&req, &mut body).await?;
+        let kind = match RecordKind::from_path(&kind) {
+            Some(kind) => kind,
+            None => {
+                return Ok(error_response(
+                    StatusCode::NOT_FOUND,
+                    "may define only: agents, activities, entities",
+                ))
+            }
+        };
+        let definition: Definition = match Json::from_request(&req, &mut body).await {
+            Ok(Json(definition)) => definition,
+            Err(error) => return Ok(error_response(StatusCode::BAD_REQUEST, error)),
+        };
+
+        let mutation = kind.mutation(definition.domaintype.as_deref());
+        if !self.data.permits(claims, Permission::Submit) {
+            return Ok(error_response(
+                StatusCode::FORBIDDEN,
+                "role does not permit submitting records",
+            ));
+        }
+        if execute_opa_check(
+            &self.data.opa_executor,
+            &self.data.claim_parser,
+            claims,
+            |identity| OpaData::graphql(identity, &json!("Mutation"), &json!([mutation])),
+        )
+        .await
+        .is_err()
+        {
+            return Ok(error_response(
+                StatusCode::FORBIDDEN,
+                "violation of policy rules",
+            ));
+        }
+
+        let identity = match self.identity(claims) {
+            Ok(identity) => identity,
+            Err(response) => return Ok(response),
+        };
+
+        match self
+            .api
+            .check_write_access(&identity, ns.clone().into())
+            .await
+        {
+            Ok(()) => {}
+            Err(error @ ApiError::NamespaceAccessDenied { .. }) => {
+                return Ok(error_response(StatusCode::FORBIDDEN, error))
+            }
+            Err(error) => {
+                tracing::error!(%error, "Failed to evaluate namespace policy");
+                return Ok(error_response(StatusCode::INTERNAL_SERVER_ERROR, error));
+            }
+        }
+
+        let attributes = match self.domain_types.attributes(kind, &definition) {
+            Ok(attributes) => attributes,
+            Err(error) => return Ok(error_response(StatusCode::UNPROCESSABLE_ENTITY, error)),
+        };
+
+        if let Some(Err(retry_after)) = self
+            .rate_limits
+            .as_ref()
+            .map(|rate_limits| rate_limits.take(&identity, claims))
+        {
+            let retry_after = retry_after_seconds(retry_after);
+            let mut response = error_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("rate limit exceeded, retry after {retry_after} seconds"),
+            );
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after.into());
+            return Ok(response);
+        }
+
+        let command = definition
+            .command(kind, ns, attributes)
+            .with_idempotency_key(idempotency_key.map(|key| format!("{key}/{mutation}")));
+
+        match self.api.dispatch(command, identity).await {
+            Ok(ApiResponse::Submission { subject, tx_id, .. })
+            | Ok(ApiResponse::AlreadySubmitted { subject, tx_id }) => {
+                Ok(submission_response(&subject, Some(tx_id.to_string())))
+            }
+            Ok(ApiResponse::AlreadyRecorded { subject, .. }) => {
+                Ok(submission_response(&subject, None))
+            }
+            Ok(ApiResponse::Queued { id }) => Ok(Json(json!({
+                "context": id,
+                "submissionResult": "QUEUED",
+                "txId": null,
+            }))
+            .with_status(StatusCode::ACCEPTED)
+            .into_response()),
+            Ok(_) => Ok(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "unexpected response from api",
+            )),
+            Err(error @ ApiError::NamespaceAccessDenied { .. }) => {
+                Ok(error_response(StatusCode::FORBIDDEN, error))
+            }
+            Err(error) if error.is_retryable() => {
+                Ok(error_response(StatusCode::SERVICE_UNAVAILABLE, error))
+            }
+            Err(error @ ApiError::IdempotencyKeyPending { .. }) => {
+                Ok(error_response(StatusCode::CONFLICT, error))
+            }
+            Err(
+                error @ (ApiError::Contradiction(_)
+                | ApiError::Validation(_)
+                | ApiError::IdempotencyKeyReused { .. }),
+            ) => Ok(error_response(StatusCode::UNPROCESSABLE_ENTITY, error)),
+            Err(error) => {
+                tracing::error!(%error, "Failed to define {}", kind.name());
+                Ok(error_response(StatusCode::INTERNAL_SERVER_ERROR, error))
+            }
+        }
+    }
+}
+
+#[poem::async_trait]
+impl Endpoint for DefineEndpoint {
+    type Output = poem::Response;
+
+    async fn call(&self, req: poem::Request) -> poem::Result<Self::Output> {
+        let checked_claims = if let Some(secconf) = &self.secconf {
+            check_claims(secconf, &req).await?
+        } else {
+            None
+        };
+        self.respond(req, checked_claims.as_ref()).await
+    }
+}
+
+/// Reads records from `GET /namespaces/:ns/:kind/:id`, as the `/data`
+/// endpoints do
+pub(super) struct RecordEndpoint {
+    pub(super) secconf: Option<EndpointSecurityConfiguration>,
+    pub(super) data: IriEndpoint,
+}
+
+impl RecordEndpoint {
+    #[instrument(level = "trace", skip(self, req, claims), ret(Debug))]
+    async fn respond(
+        &self,
+        req: poem::Request,
+        claims: Option<&JwtClaims>,
+    ) -> poem::Result<poem::Response> {
+        let if_none_match = req
+            .header(poem::http::header::IF_NONE_MATCH)
+            .map(str::to_owned);
+        let if_none_match = if_none_match.as_deref();
+        let (req, mut body) = req.split();
+        let Path(Record { ns, kind, id }) = FromRequest::from_request(&req, &mut body).await?;
+        let ns = ns.into();
+        let store = &self.data.store;
+
+        match RecordKind::from_path(&kind) {
+            Some(RecordKind::Agent) => {
+                self.data
+                    .response_for_query(
+                        claims,
+                        if_none_match,
+                        "agent",
+                        &AgentId::from_external_id(id),
+                        &ns,
+                        |conn, id, ns| store.prov_model_for_agent_id(conn, id, ns),
+                    )
+                    .await
+            }
+            Some(RecordKind::Activity) => {
+                self.data
+                    .response_for_query(
+                        claims,
+                        if_none_match,
+                        "activity",
+                        &ActivityId::from_external_id(id),
+                        &ns,
+                        |conn, id, ns| store.prov_model_for_activity_id(conn, id, ns),
+                    )
+                    .await
+            }
+            Some(RecordKind::Entity) => {
+                self.data
+                    .response_for_query(
+                        claims,
+                        if_none_match,
+                        "entity",
+                        &EntityId::from_external_id(id),
+                        &ns,
+                        |conn, id, ns| store.prov_model_for_entity_id(conn, id, ns),
+                    )
+                    .await
+            }
+            None => Ok(error_response(
+                StatusCode::NOT_FOUND,
+                "may query only: agents, activities, entities",
+            )),
+        }
+    }
+}
+
+#[poem::async_trait]
+impl Endpoint for RecordEndpoint {
+    type Output = poem::Response;
+
+    async fn call(&self, req: poem::Request) -> poem::Result<Self::Output> {
+        let checked_claims = if let Some(secconf) = &self.secconf {
+            check_claims(secconf, &req).await?
+        } else {
+            None
+        };
+        self.respond(req, checked_claims.as_ref()).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{
+        commands::{ApiCommand, EntityCommand},
+        prov::DomaintypeId,
+    };
+
+    use super::{AttributeType, Definition, DomainTypes, RecordKind};
+
+    #[test]
+    fn definitions_map_onto_commands() {
+        let definition: Definition = serde_json::from_value(serde_json::json!({
+            "externalId": "report",
+            "type": "ReportEntity",
+            "attributes": { "TitleAttribute": "Quarterly" },
+        }))
+        .unwrap();
+        assert_eq!(
+            RecordKind::Entity.mutation(definition.domaintype.as_deref()),
+            "defineReportEntity"
+        );
+
+        let domain_types = DomainTypes::new().with_entity(
+            "ReportEntity",
+            [AttributeType {
+                name: "TitleAttribute".to_owned(),
+                primitive_type: "String".to_owned(),
+                repeated: false,
+            }],
+        );
+        let attributes = domain_types
+            .attributes(RecordKind::Entity, &definition)
+            .unwrap();
+
+        match definition.command(RecordKind::Entity, "testns".to_owned(), attributes) {
+            ApiCommand::Entity(EntityCommand::Create {
+                external_id,
+                namespace,
+                attributes,
+            }) => {
+                assert_eq!(external_id.as_str(), "report");
+                assert_eq!(namespace.as_str(), "testns");
+                assert_eq!(
+                    attributes.typ,
+                    Some(DomaintypeId::from_external_id("ReportEntity"))
+                );
+                assert_eq!(
+                    attributes.attributes["TitleAttribute"].value,
+                    serde_json::json!("Quarterly")
+                );
+            }
+            command => panic!("unexpected command {command:?}"),
+        }
+    }
+}
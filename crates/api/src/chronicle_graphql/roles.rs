@@ -0,0 +1,206 @@
+//! A built-in authorization matrix for deployments that do not want to write
+//! Rego policies. Each role, named by a claim of the principal's JWT, is
+//! granted some of the permissions `read`, `submit` and `admin`, and each
+//! top-level GraphQL field, `/data` and REST endpoint requires one of them:
+//! queries and subscriptions require `read`, mutations `submit`, and the audit
+//! trail and exports `admin`, which implies the other two. The matrix is
+//! checked in addition to the OPA policy, not in place of it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::Arc,
+};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo},
+    ServerError, ServerResult, Value,
+};
+use common::identity::JwtClaims;
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// Top-level fields that expose more than the records of a namespace
+const ADMIN_FIELDS: &[&str] = &["auditTrail", "exportJob", "startExport", "cancelExport"];
+
+#[derive(Error, Debug)]
+pub enum RolePermissionsError {
+    #[error("Could not read role permissions {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Role permissions {path} are not valid: {source}")]
+    Invalid {
+        path: String,
+        source: serde_json::Error,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    Submit,
+    Admin,
+}
+
+impl Permission {
+    /// The permission required to resolve the top-level field `name` of the
+    /// operation type `parent_type`
+    fn for_field(parent_type: &str, name: &str) -> Self {
+        if ADMIN_FIELDS.contains(&name) {
+            Permission::Admin
+        } else if parent_type == "Mutation" {
+            Permission::Submit
+        } else {
+            Permission::Read
+        }
+    }
+}
+
+fn default_claim() -> String {
+    "roles".to_owned()
+}
+
+/// The permissions granted to each role, read from a JSON file such as
+/// `{"claim": "roles", "anonymous": ["read"], "roles": {"writer": ["read", "submit"]}}`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RolePermissions {
+    /// The JWT claim listing the principal's roles
+    #[serde(default = "default_claim")]
+    claim: String,
+    /// Permissions granted to every principal, including anonymous ones
+    #[serde(default)]
+    anonymous: BTreeSet<Permission>,
+    #[serde(default)]
+    roles: BTreeMap<String, BTreeSet<Permission>>,
+}
+
+impl RolePermissions {
+    pub fn from_file(path: &Path) -> Result<Self, RolePermissionsError> {
+        let json = std::fs::read_to_string(path).map_err(|source| RolePermissionsError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let permissions: Self =
+            serde_json::from_str(&json).map_err(|source| RolePermissionsError::Invalid {
+                path: path.display().to_string(),
+                source,
+            })?;
+        info!(
+            roles = permissions.roles.len(),
+            claim = %permissions.claim,
+            "Authorizing requests by role"
+        );
+
+        Ok(permissions)
+    }
+
+    /// The roles named by the role claim, which may be a single role or an
+    /// array of them
+    fn claimed_roles<'a>(&self, claims: Option<&'a JwtClaims>) -> Vec<&'a str> {
+        match claims.and_then(|claims| claims.0.get(&self.claim)) {
+            Some(serde_json::Value::String(role)) => vec![role.as_str()],
+            Some(serde_json::Value::Array(roles)) => {
+                roles.iter().filter_map(serde_json::Value::as_str).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Whether the principal with `claims` holds `permission`, by its roles or
+    /// as any principal may
+    pub fn permits(&self, claims: Option<&JwtClaims>, permission: Permission) -> bool {
+        self.claimed_roles(claims)
+            .into_iter()
+            .filter_map(|role| self.roles.get(role))
+            .chain(std::iter::once(&self.anonymous))
+            .flatten()
+            .any(|granted| *granted == permission || *granted == Permission::Admin)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for RolePermissions {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        // Nested fields are permitted by the top-level field they belong to
+        if info.path_node.parent.is_some() || info.is_for_introspection {
+            return next.run(ctx, info).await;
+        }
+
+        let permission = Permission::for_field(info.parent_type, info.name);
+        if self.permits(ctx.data_opt::<JwtClaims>(), permission) {
+            next.run(ctx, info).await
+        } else {
+            debug!(field = info.name, ?permission, "Role does not permit field");
+            let mut error = ServerError::new(
+                format!("{} requires the {permission:?} permission", info.name),
+                None,
+            );
+            error
+                .extensions
+                .get_or_insert_with(Default::default)
+                .set("code", "FORBIDDEN");
+            Err(error)
+        }
+    }
+}
+
+impl ExtensionFactory for RolePermissions {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::identity::JwtClaims;
+    use serde_json::json;
+
+    use super::{Permission, RolePermissions};
+
+    fn claims(value: serde_json::Value) -> JwtClaims {
+        JwtClaims(value.as_object().unwrap().clone())
+    }
+
+    #[test]
+    fn roles_grant_permissions_to_top_level_fields() {
+        let permissions: RolePermissions = serde_json::from_value(json!({
+            "claim": "groups",
+            "anonymous": ["read"],
+            "roles": {
+                "writer": ["submit"],
+                "operator": ["admin"]
+            }
+        }))
+        .unwrap();
+
+        let anonymous = None;
+        let writer = claims(json!({"sub": "alice", "groups": "writer"}));
+        let operator = claims(json!({"sub": "bob", "groups": ["viewer", "operator"]}));
+
+        for (claims, field, permitted) in [
+            (anonymous, ("Query", "entity"), true),
+            (anonymous, ("Mutation", "defineEntity"), false),
+            (Some(&writer), ("Mutation", "defineEntity"), true),
+            (Some(&writer), ("Query", "auditTrail"), false),
+            (Some(&operator), ("Mutation", "startExport"), true),
+            (Some(&operator), ("Mutation", "defineEntity"), true),
+        ] {
+            assert_eq!(
+                permissions.permits(claims, Permission::for_field(field.0, field.1)),
+                permitted,
+                "{field:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,499 @@
+//! Search over an OpenSearch or Elasticsearch index that mirrors committed
+//! agents, activities and entities with their attributes, for deployments
+//! that outgrow the database's text search. The index is written by a
+//! background task following commit notifications, and the `search` query
+//! is proxied to it. Without an index, `search` uses the database's text
+//! search over external ids and attribute values.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use async_graphql::{Context, SimpleObject};
+use common::{
+    ledger::SubmissionStage,
+    prov::{ActivityId, AgentId, EntityId, ExternalId, ExternalIdPart, ProvModel},
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, instrument, warn};
+use url::Url;
+
+use super::{readable_namespace, Store};
+use crate::ApiDispatch;
+
+/// The most documents written to the index in one bulk request
+const BATCH_SIZE: usize = 500;
+
+/// The longest to wait before retrying a bulk request the index refused
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Settings and mappings for an index that does not exist yet, unless
+/// configured otherwise. The fields that `search` filters on are keywords.
+const DEFAULT_MAPPING: &str = r#"{
+  "mappings": {
+    "properties": {
+      "id": { "type": "keyword" },
+      "kind": { "type": "keyword" },
+      "namespace": { "type": "keyword" },
+      "externalId": { "type": "text", "fields": { "keyword": { "type": "keyword" } } },
+      "domaintype": { "type": "keyword" },
+      "attributes": { "type": "object", "dynamic": true }
+    }
+  }
+}"#;
+
+#[derive(Error, Debug)]
+pub enum SearchError {
+    #[error("Search index request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Search index responded {status}: {body}")]
+    Rejected { status: u16, body: String },
+
+    #[error("Invalid search index url: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("Invalid JSON from or for the search index: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Where records are indexed, search is disabled unless configured
+#[derive(Debug, Clone)]
+pub struct SearchConf {
+    pub address: Url,
+    pub index: String,
+    /// Settings and mappings to create the index with if it does not exist
+    pub mapping: Option<Value>,
+}
+
+impl SearchConf {
+    pub fn new(address: Url, index: impl Into<String>, mapping: Option<Value>) -> Self {
+        Self {
+            address,
+            index: index.into(),
+            mapping,
+        }
+    }
+
+    /// The url of the index, or of `path` under it
+    fn index_url(&self, path: Option<&str>) -> Result<Url, SearchError> {
+        Ok(match path {
+            Some(path) => self.address.join(&format!("{}/{path}", self.index))?,
+            None => self.address.join(&self.index)?,
+        })
+    }
+}
+
+/// A record as it is indexed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchDocument {
+    id: String,
+    kind: &'static str,
+    namespace: String,
+    external_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    domaintype: Option<String>,
+    // Omitted rather than emptied, as commits that only relate a record to
+    // others carry none of its attributes
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<String, Value>,
+}
+
+/// The documents to upsert for the records a commit changed
+fn documents(delta: &ProvModel) -> Vec<(String, SearchDocument)> {
+    let attributes = |attributes: &BTreeMap<String, common::attributes::Attribute>| {
+        attributes
+            .iter()
+            .map(|(name, attribute)| (name.clone(), attribute.value.clone()))
+            .collect()
+    };
+
+    let agents = delta.agents.values().map(|agent| {
+        (
+            format!("{}/{}", agent.namespaceid, agent.id),
+            SearchDocument {
+                id: agent.id.to_string(),
+                kind: "agent",
+                namespace: agent.namespaceid.external_id_part().to_string(),
+                external_id: agent.external_id.to_string(),
+                domaintype: agent
+                    .domaintypeid
+                    .as_ref()
+                    .map(|typ| typ.external_id_part().to_string()),
+                attributes: attributes(&agent.attributes),
+            },
+        )
+    });
+    let activities = delta.activities.values().map(|activity| {
+        (
+            format!("{}/{}", activity.namespaceid, activity.id),
+            SearchDocument {
+                id: activity.id.to_string(),
+                kind: "activity",
+                namespace: activity.namespaceid.external_id_part().to_string(),
+                external_id: activity.external_id.to_string(),
+                domaintype: activity
+                    .domaintypeid
+                    .as_ref()
+                    .map(|typ| typ.external_id_part().to_string()),
+                attributes: attributes(&activity.attributes),
+            },
+        )
+    });
+    let entities = delta.entities.values().map(|entity| {
+        (
+            format!("{}/{}", entity.namespaceid, entity.id),
+            SearchDocument {
+                id: entity.id.to_string(),
+                kind: "entity",
+                namespace: entity.namespaceid.external_id_part().to_string(),
+                external_id: entity.external_id.to_string(),
+                domaintype: entity
+                    .domaintypeid
+                    .as_ref()
+                    .map(|typ| typ.external_id_part().to_string()),
+                attributes: attributes(&entity.attributes),
+            },
+        )
+    });
+
+    agents.chain(activities).chain(entities).collect()
+}
+
+/// A bulk request upserting `documents`, as newline delimited JSON
+fn bulk_body(index: &str, documents: &[(String, SearchDocument)]) -> Result<String, SearchError> {
+    let mut body = String::new();
+    for (id, document) in documents {
+        body.push_str(&serde_json::to_string(
+            &json!({ "update": { "_index": index, "_id": id } }),
+        )?);
+        body.push('\n');
+        body.push_str(&serde_json::to_string(
+            &json!({ "doc": document, "doc_as_upsert": true }),
+        )?);
+        body.push('\n');
+    }
+    Ok(body)
+}
+
+async fn rejected(response: reqwest::Response) -> SearchError {
+    SearchError::Rejected {
+        status: response.status().as_u16(),
+        body: response.text().await.unwrap_or_default(),
+    }
+}
+
+/// Create the index with the configured mapping, unless it already exists
+pub(crate) async fn ensure_index(
+    client: &reqwest::Client,
+    conf: &SearchConf,
+) -> Result<(), SearchError> {
+    let url = conf.index_url(None)?;
+    let exists = client.head(url.clone()).send().await?;
+    if exists.status().is_success() {
+        return Ok(());
+    }
+
+    let mapping = match &conf.mapping {
+        Some(mapping) => mapping.to_string(),
+        None => DEFAULT_MAPPING.to_owned(),
+    };
+    let response = client
+        .put(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(mapping)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(rejected(response).await);
+    }
+
+    info!(index = %conf.index, "Created search index");
+    Ok(())
+}
+
+/// Write `documents` to the index, waiting and retrying while the index is
+/// overloaded or unavailable
+#[instrument(skip(client, conf, documents), fields(documents = documents.len()))]
+async fn write(
+    client: &reqwest::Client,
+    conf: &SearchConf,
+    documents: &[(String, SearchDocument)],
+) -> Result<(), SearchError> {
+    let body = bulk_body(&conf.index, documents)?;
+
+    let mut backoff = Duration::from_millis(500);
+    loop {
+        let response = conf.index_url(Some("_bulk")).map(|url| {
+            client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+                .body(body.clone())
+                .send()
+        });
+        let result = match response {
+            Ok(response) => response.await.map_err(SearchError::from),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(response) if response.status().is_success() => {
+                let outcome = response.text().await.unwrap_or_default();
+                let failed = serde_json::from_str::<Value>(&outcome)
+                    .ok()
+                    .filter(|outcome| outcome["errors"] == Value::Bool(true))
+                    .and_then(|outcome| outcome["items"].as_array().cloned())
+                    .map(|items| {
+                        items
+                            .iter()
+                            .filter(|item| item["update"]["error"].is_object())
+                            .count()
+                    })
+                    .unwrap_or_default();
+                if failed > 0 {
+                    warn!(failed, "Search index rejected some documents");
+                }
+                debug!("Indexed documents");
+                return Ok(());
+            }
+            Ok(response)
+                if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || response.status().is_server_error() =>
+            {
+                warn!(status = %response.status(), ?backoff, "Search index is unavailable, retrying");
+            }
+            Ok(response) => return Err(rejected(response).await),
+            Err(e) => {
+                warn!(?e, ?backoff, "Could not reach search index, retrying");
+            }
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Write the records in `model` to the index, returning how many were written
+pub(crate) async fn index_model(
+    client: &reqwest::Client,
+    conf: &SearchConf,
+    model: &ProvModel,
+) -> Result<usize, SearchError> {
+    let documents = documents(model);
+    for batch in documents.chunks(BATCH_SIZE) {
+        write(client, conf, batch).await?;
+    }
+
+    Ok(documents.len())
+}
+
+/// Create the index if needed, then mirror each commit into it until the api
+/// stops. Commits are batched while the index is busy, up to `BATCH_SIZE`
+/// documents per request.
+pub async fn spawn_indexer(api: &ApiDispatch, conf: SearchConf) -> Result<(), SearchError> {
+    let client = reqwest::Client::new();
+    ensure_index(&client, &conf).await?;
+
+    let mut commits = api.notify_commit.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let mut documents = vec![];
+            match commits.recv().await {
+                Ok(SubmissionStage::Committed(commit, _)) => {
+                    documents.extend(documents(&commit.delta))
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(
+                        missed,
+                        "Search indexing fell behind, commits were not indexed"
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            }
+
+            // Take what else has been committed while the last batch was written
+            while documents.len() < BATCH_SIZE {
+                match commits.try_recv() {
+                    Ok(SubmissionStage::Committed(commit, _)) => {
+                        documents.extend(documents(&commit.delta))
+                    }
+                    Ok(_) => continue,
+                    Err(_) => break,
+                }
+            }
+
+            for batch in documents.chunks(BATCH_SIZE) {
+                if let Err(e) = write(&client, &conf, batch).await {
+                    error!(%e, "Search index refused documents");
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// A record matching a search, which can be fetched by its id
+#[derive(Debug, Clone, SimpleObject)]
+pub struct SearchHit {
+    pub id: String,
+    /// `agent`, `activity` or `entity`
+    pub kind: String,
+    pub namespace: String,
+    pub external_id: String,
+    pub domaintype: Option<String>,
+    pub score: f64,
+}
+
+/// Records in the namespace matching `query` by the database's text search
+async fn search_database<'a>(
+    ctx: &Context<'a>,
+    query: String,
+    namespace: String,
+    first: i64,
+) -> async_graphql::Result<Vec<SearchHit>> {
+    let store = ctx.data_unchecked::<Store>().persistence()?;
+    let ns = ExternalId::from(namespace.as_str());
+
+    let hits = store.read_only(|connection| store.text_search(connection, &ns, &query, first))?;
+
+    Ok(hits
+        .into_iter()
+        .map(|hit| SearchHit {
+            id: match hit.kind.as_str() {
+                "agent" => AgentId::from_external_id(&hit.external_id).to_string(),
+                "activity" => ActivityId::from_external_id(&hit.external_id).to_string(),
+                _ => EntityId::from_external_id(&hit.external_id).to_string(),
+            },
+            kind: hit.kind,
+            namespace: namespace.clone(),
+            external_id: hit.external_id,
+            domaintype: hit.domaintype,
+            score: hit.score,
+        })
+        .collect())
+}
+
+/// Records in the namespace matching `query`, best matches first. With an
+/// index `query` is in its query string syntax, otherwise records match
+/// words beginning with any of its terms.
+pub async fn search<'a>(
+    ctx: &Context<'a>,
+    query: String,
+    namespace: Option<String>,
+    first: Option<i32>,
+) -> async_graphql::Result<Vec<SearchHit>> {
+    let namespace: String = readable_namespace(ctx, namespace).await?;
+    let first = first.unwrap_or(10).clamp(0, 1000);
+
+    let conf = match ctx.data_opt::<SearchConf>() {
+        Some(conf) => conf,
+        None => return search_database(ctx, query, namespace, first.into()).await,
+    };
+
+    let request = json!({
+        "size": first,
+        "query": {
+            "bool": {
+                "must": { "simple_query_string": { "query": query } },
+                "filter": { "term": { "namespace": namespace } }
+            }
+        }
+    });
+
+    let response = reqwest::Client::new()
+        .post(conf.index_url(Some("_search"))?)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(request.to_string())
+        .send()
+        .await
+        .map_err(SearchError::from)?;
+    if !response.status().is_success() {
+        return Err(rejected(response).await.into());
+    }
+
+    let results: Value = serde_json::from_str(&response.text().await.map_err(SearchError::from)?)
+        .map_err(SearchError::from)?;
+
+    Ok(results["hits"]["hits"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|hit| {
+            let source = &hit["_source"];
+            let field = |name: &str| source[name].as_str().unwrap_or_default().to_owned();
+            SearchHit {
+                id: field("id"),
+                kind: field("kind"),
+                namespace: field("namespace"),
+                external_id: field("externalId"),
+                domaintype: source["domaintype"].as_str().map(ToOwned::to_owned),
+                score: hit["_score"].as_f64().unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use common::{
+        attributes::{Attribute, Attributes},
+        prov::{
+            operations::{ChronicleOperation, CreateNamespace, SetAttributes},
+            AgentId, DomaintypeId, NamespaceId, ProvModel,
+        },
+    };
+    use uuid::Uuid;
+
+    use super::{bulk_body, documents};
+
+    #[test]
+    fn commits_upsert_their_records() {
+        let namespace = NamespaceId::from_external_id("testns", Uuid::nil());
+        let id = AgentId::from_external_id("testagent");
+        let delta = ProvModel::from_tx(&[
+            ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                namespace.clone(),
+                "testns",
+                Uuid::nil(),
+            )),
+            ChronicleOperation::SetAttributes(SetAttributes::Agent {
+                namespace,
+                id,
+                attributes: Attributes {
+                    typ: Some(DomaintypeId::from_external_id("Person")),
+                    attributes: [(
+                        "name".to_owned(),
+                        Attribute {
+                            typ: "name".to_owned(),
+                            value: serde_json::json!("Alice"),
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                },
+            }),
+        ])
+        .unwrap();
+
+        let documents = documents(&delta);
+        let body = bulk_body("chronicle", &documents).unwrap();
+        let lines: Vec<serde_json::Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["update"]["_index"], "chronicle");
+        assert_eq!(lines[1]["doc_as_upsert"], true);
+        assert_eq!(lines[1]["doc"]["id"], "chronicle:agent:testagent");
+        assert_eq!(lines[1]["doc"]["kind"], "agent");
+        assert_eq!(lines[1]["doc"]["namespace"], "testns");
+        assert_eq!(lines[1]["doc"]["domaintype"], "Person");
+        assert_eq!(lines[1]["doc"]["attributes"]["name"], "Alice");
+    }
+}
@@ -0,0 +1,112 @@
+//! A manifest of what this instance of Chronicle is running, signed with its
+//! chronicle key, so that clients can pin a deployment and notice when the
+//! software, domain or schema behind it changes.
+
+use async_graphql::{Context, SimpleObject};
+use chronicle_signing::{ChronicleKnownKeyNamesSigner, ChronicleSigning, SecretError};
+use common::k256::{
+    pkcs8::{EncodePublicKey, LineEnding},
+    sha2::{Digest, Sha256},
+};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    /// The version of Chronicle, with the ledger it was built for
+    pub chronicle_version: String,
+    /// The version of the protocol that transactions are submitted in
+    pub protocol_version: String,
+    /// The version of the chronicle transaction family
+    pub family_version: String,
+    /// Hex SHA-256 of the domain definition Chronicle was built with
+    pub domain_hash: String,
+    /// Hex SHA-256 of the GraphQL schema in SDL
+    pub schema_hash: String,
+    /// The PEM encoded key that verifies `signature`
+    pub verifying_key: String,
+    /// Hex signature with the chronicle key of the other fields, as a JSON
+    /// object with their names as keys in the order they are listed here
+    #[serde(skip)]
+    pub signature: String,
+}
+
+impl ServerInfo {
+    /// Describe this instance, hashing the domain definition and schema, and
+    /// sign the result
+    pub async fn new(
+        chronicle_version: &str,
+        protocol_version: &str,
+        family_version: &str,
+        domain: &[u8],
+        schema_sdl: &str,
+        signing: &ChronicleSigning,
+    ) -> Result<Self, SecretError> {
+        let verifying_key = signing
+            .chronicle_verifying()
+            .await?
+            .to_public_key_pem(LineEnding::LF)
+            .map_err(|_| SecretError::InvalidPublicKey)?;
+
+        let mut info = Self {
+            chronicle_version: chronicle_version.to_owned(),
+            protocol_version: protocol_version.to_owned(),
+            family_version: family_version.to_owned(),
+            domain_hash: hex::encode(Sha256::digest(domain)),
+            schema_hash: hex::encode(Sha256::digest(schema_sdl.as_bytes())),
+            verifying_key,
+            signature: String::new(),
+        };
+
+        let signed = serde_json::to_vec(&info).expect("server info serializes as JSON");
+        info.signature = hex::encode(signing.chronicle_sign(&signed).await?);
+
+        Ok(info)
+    }
+}
+
+pub async fn server_info<'a>(ctx: &Context<'a>) -> async_graphql::Result<ServerInfo> {
+    Ok(ctx.data::<ServerInfo>()?.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use chronicle_signing::{
+        chronicle_secret_names, ChronicleKnownKeyNamesSigner, ChronicleSecretsOptions,
+        ChronicleSigning, BATCHER_NAMESPACE, CHRONICLE_NAMESPACE,
+    };
+
+    use super::ServerInfo;
+
+    #[tokio::test]
+    async fn signature_covers_manifest() {
+        let signing = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let info = ServerInfo::new("0.7.5", "2", "1.0", b"name: test", "type Query", &signing)
+            .await
+            .unwrap();
+
+        let signed = serde_json::to_vec(&info).unwrap();
+        let signature = hex::decode(&info.signature).unwrap();
+        assert!(signing.chronicle_verify(&signed, &signature).await.unwrap());
+
+        let mut changed = info.clone();
+        changed.schema_hash = "0".repeat(64);
+        let signed = serde_json::to_vec(&changed).unwrap();
+        assert!(!signing.chronicle_verify(&signed, &signature).await.unwrap());
+    }
+}
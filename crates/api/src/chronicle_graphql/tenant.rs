@@ -0,0 +1,106 @@
+//! Tenant isolation scopes the reads made for each request to the home
+//! namespace of its principal, by giving the request a [Store] and loader that
+//! set the tenant of each transaction they run. On Postgres, the row-level
+//! security policies of the schema then hide the records of other namespaces,
+//! so that a resolver that mislays its namespace filter still cannot return
+//! another tenant's records.
+
+use std::sync::Arc;
+
+use async_graphql::{
+    dataloader::DataLoader,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    Request, ServerResult,
+};
+use common::identity::{AuthId, DefaultNamespaces, JwtClaims};
+use tracing::trace;
+
+use super::{loader::RelationLoader, AuthFromJwt, Store};
+
+#[derive(Clone, Debug)]
+pub struct TenantIsolation {
+    pub claim_parser: Option<AuthFromJwt>,
+    pub default_namespaces: DefaultNamespaces,
+}
+
+impl TenantIsolation {
+    /// The namespace the request's reads are scoped to
+    pub(super) fn tenant(&self, claims: Option<&JwtClaims>) -> String {
+        let identity = match (&self.claim_parser, claims) {
+            (Some(claim_parser), Some(claims)) => claim_parser
+                .identity(claims)
+                .unwrap_or_else(|_| AuthId::anonymous()),
+            _ => AuthId::anonymous(),
+        };
+        self.default_namespaces.for_identity(&identity)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for TenantIsolation {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let tenant = self.tenant(ctx.data_opt::<JwtClaims>());
+        trace!(%tenant, "Scoping request to tenant");
+
+        let store = ctx.data_unchecked::<Store>().for_tenant(tenant);
+        let request = request
+            .data(DataLoader::new(
+                RelationLoader::for_store(store.clone()),
+                tokio::spawn,
+            ))
+            .data(store);
+        next.run(ctx, request).await
+    }
+}
+
+impl ExtensionFactory for TenantIsolation {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use common::identity::{DefaultNamespaces, JwtClaims};
+    use serde_json::json;
+
+    use super::{AuthFromJwt, TenantIsolation};
+
+    fn claims(value: serde_json::Value) -> JwtClaims {
+        JwtClaims(value.as_object().unwrap().clone())
+    }
+
+    #[test]
+    fn requests_are_scoped_to_the_home_namespace_of_their_principal() {
+        let isolation = TenantIsolation {
+            claim_parser: Some(AuthFromJwt {
+                id_claims: BTreeSet::from(["sub".to_owned()]),
+                allow_anonymous: true,
+            }),
+            default_namespaces: DefaultNamespaces::default()
+                .with_principal("anonymous", "public")
+                .with_claim("tenant"),
+        };
+
+        assert_eq!(
+            isolation.tenant(Some(&claims(json!({"sub": "alice", "tenant": "acme"})))),
+            "acme"
+        );
+        assert_eq!(
+            isolation.tenant(Some(&claims(json!({"sub": "bob"})))),
+            "default"
+        );
+        assert_eq!(
+            isolation.tenant(Some(&claims(json!({"tenant": "acme"})))),
+            "public"
+        );
+        assert_eq!(isolation.tenant(None), "public");
+    }
+}
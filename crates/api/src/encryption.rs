@@ -0,0 +1,225 @@
+//! Encryption of outgoing payloads to their recipients' public keys, so that
+//! provenance deltas can pass through shared infrastructure without exposing
+//! attribute values. Payloads are encrypted as a JWE in its general JSON
+//! serialization, with a random AES-256-GCM content key wrapped for each
+//! recipient's RSA key with RSA-OAEP-256, so any of them can decrypt it with a
+//! standard JOSE library. Recipients are identified by the `kid` of their
+//! entry, the hex SHA-256 of their DER encoded public key.
+
+use std::path::Path;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use openssl::{
+    encrypt::Encrypter,
+    error::ErrorStack,
+    hash::{hash, MessageDigest},
+    pkey::{Id, PKey, Public},
+    rsa::Padding,
+    symm::{encrypt_aead, Cipher},
+};
+use serde_json::json;
+use thiserror::Error;
+
+/// The mime type of an encrypted payload
+pub const JOSE_JSON: &str = "application/jose+json";
+
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("Could not read recipient key {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Recipient key {0} is not an RSA public key in PEM")]
+    UnsupportedKey(String),
+
+    #[error("Encryption failed: {0}")]
+    OpenSsl(#[from] ErrorStack),
+}
+
+#[derive(Clone)]
+struct Recipient {
+    kid: String,
+    key: PKey<Public>,
+}
+
+impl std::fmt::Debug for Recipient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recipient").field("kid", &self.kid).finish()
+    }
+}
+
+/// The public keys to encrypt payloads to
+#[derive(Debug, Clone)]
+pub struct PayloadEncryption {
+    recipients: Vec<Recipient>,
+}
+
+impl PayloadEncryption {
+    /// Read the PEM encoded RSA public key of each recipient
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> Result<Self, EncryptionError> {
+        let recipients = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let pem = std::fs::read(path).map_err(|source| EncryptionError::Io {
+                    path: path.display().to_string(),
+                    source,
+                })?;
+                let key = PKey::public_key_from_pem(&pem)
+                    .ok()
+                    .filter(|key| key.id() == Id::RSA)
+                    .ok_or_else(|| EncryptionError::UnsupportedKey(path.display().to_string()))?;
+                Ok(Recipient {
+                    kid: hex::encode(hash(MessageDigest::sha256(), &key.public_key_to_der()?)?),
+                    key,
+                })
+            })
+            .collect::<Result<_, EncryptionError>>()?;
+
+        Ok(Self { recipients })
+    }
+
+    /// The key ids of the recipients, in the order they were given
+    pub fn kids(&self) -> impl Iterator<Item = &str> {
+        self.recipients
+            .iter()
+            .map(|recipient| recipient.kid.as_str())
+    }
+
+    fn wrap_key(recipient: &Recipient, cek: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+        let mut encrypter = Encrypter::new(&recipient.key)?;
+        encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+        encrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+        encrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+
+        let mut wrapped = vec![0; encrypter.encrypt_len(cek)?];
+        let len = encrypter.encrypt(cek, &mut wrapped)?;
+        wrapped.truncate(len);
+
+        Ok(wrapped)
+    }
+
+    /// Encrypt `plaintext` to every recipient, returning the JWE as JSON
+    pub fn encrypt(&self, plaintext: &[u8], content_type: &str) -> Result<String, EncryptionError> {
+        let mut cek = [0; 32];
+        openssl::rand::rand_bytes(&mut cek)?;
+        let mut iv = [0; 12];
+        openssl::rand::rand_bytes(&mut iv)?;
+
+        let protected = URL_SAFE_NO_PAD.encode(
+            json!({
+                "enc": "A256GCM",
+                "cty": content_type,
+            })
+            .to_string(),
+        );
+
+        let mut tag = [0; 16];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &cek,
+            Some(&iv),
+            protected.as_bytes(),
+            plaintext,
+            &mut tag,
+        )?;
+
+        let recipients = self
+            .recipients
+            .iter()
+            .map(|recipient| {
+                Ok(json!({
+                    "header": { "alg": "RSA-OAEP-256", "kid": recipient.kid },
+                    "encrypted_key": URL_SAFE_NO_PAD.encode(Self::wrap_key(recipient, &cek)?),
+                }))
+            })
+            .collect::<Result<Vec<_>, ErrorStack>>()?;
+
+        Ok(json!({
+            "protected": protected,
+            "recipients": recipients,
+            "iv": URL_SAFE_NO_PAD.encode(iv),
+            "ciphertext": URL_SAFE_NO_PAD.encode(ciphertext),
+            "tag": URL_SAFE_NO_PAD.encode(tag),
+        })
+        .to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use openssl::{
+        encrypt::Decrypter,
+        hash::MessageDigest,
+        pkey::PKey,
+        rsa::{Padding, Rsa},
+        symm::{decrypt_aead, Cipher},
+    };
+    use serde_json::Value;
+
+    use super::PayloadEncryption;
+
+    #[test]
+    fn each_recipient_can_decrypt() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys: Vec<_> = (0..2)
+            .map(|_| PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap())
+            .collect();
+        let paths: Vec<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let path = dir.path().join(format!("recipient-{i}.pem"));
+                std::fs::write(&path, key.public_key_to_pem().unwrap()).unwrap();
+                path
+            })
+            .collect();
+
+        let encryption = PayloadEncryption::from_files(&paths).unwrap();
+        let jwe: Value = serde_json::from_str(
+            &encryption
+                .encrypt(
+                    br#"{"@id":"chronicle:entity:report"}"#,
+                    "application/ld+json",
+                )
+                .unwrap(),
+        )
+        .unwrap();
+        let decode = |field: &Value| URL_SAFE_NO_PAD.decode(field.as_str().unwrap()).unwrap();
+
+        for (key, recipient) in keys.iter().zip(jwe["recipients"].as_array().unwrap()) {
+            let mut decrypter = Decrypter::new(key).unwrap();
+            decrypter.set_rsa_padding(Padding::PKCS1_OAEP).unwrap();
+            decrypter.set_rsa_oaep_md(MessageDigest::sha256()).unwrap();
+            decrypter.set_rsa_mgf1_md(MessageDigest::sha256()).unwrap();
+            let wrapped = decode(&recipient["encrypted_key"]);
+            let mut cek = vec![0; decrypter.decrypt_len(&wrapped).unwrap()];
+            let len = decrypter.decrypt(&wrapped, &mut cek).unwrap();
+            cek.truncate(len);
+
+            let plaintext = decrypt_aead(
+                Cipher::aes_256_gcm(),
+                &cek,
+                Some(&decode(&jwe["iv"])),
+                jwe["protected"].as_str().unwrap().as_bytes(),
+                &decode(&jwe["ciphertext"]),
+                &decode(&jwe["tag"]),
+            )
+            .unwrap();
+            assert_eq!(plaintext, br#"{"@id":"chronicle:entity:report"}"#);
+        }
+
+        assert_eq!(
+            encryption.kids().collect::<Vec<_>>(),
+            jwe["recipients"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|recipient| recipient["header"]["kid"].as_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+}
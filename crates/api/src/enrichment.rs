@@ -0,0 +1,175 @@
+use std::{fmt::Debug, sync::Arc};
+
+use common::{identity::AuthId, prov::operations::ChronicleOperation};
+use thiserror::Error;
+use tracing::{instrument, trace};
+
+#[derive(Error, Debug)]
+#[error("Enricher {enricher}: {message}")]
+pub struct EnrichmentError {
+    pub enricher: String,
+    pub message: String,
+}
+
+impl EnrichmentError {
+    pub fn new(enricher: impl ToString, message: impl ToString) -> Self {
+        Self {
+            enricher: enricher.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// An enricher can rewrite, remove or append to the operations produced by an
+/// api command before they are signed and submitted to the ledger. Enrichers
+/// run after the effect check, so operations they append will always be
+/// submitted, even if the command itself was otherwise already recorded.
+pub trait OperationEnricher: Debug + Send + Sync {
+    /// A stable name for this enricher, used in tracing and error reporting
+    fn name(&self) -> &str;
+
+    fn enrich(
+        &self,
+        identity: &AuthId,
+        operations: Vec<ChronicleOperation>,
+    ) -> Result<Vec<ChronicleOperation>, EnrichmentError>;
+}
+
+/// An ordered chain of [OperationEnricher]s, configured per deployment and
+/// applied to every operation set the api submits on behalf of a command.
+/// Imports and depth charges are submitted as supplied.
+#[derive(Debug, Clone, Default)]
+pub struct OperationEnrichment {
+    enrichers: Vec<Arc<dyn OperationEnricher>>,
+}
+
+impl OperationEnrichment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an enricher, it will run after all those already added
+    pub fn with_enricher(mut self, enricher: impl OperationEnricher + 'static) -> Self {
+        self.enrichers.push(Arc::new(enricher));
+        self
+    }
+
+    #[instrument(level = "trace", skip(self, operations), ret(Debug))]
+    pub fn enrich(
+        &self,
+        identity: &AuthId,
+        operations: Vec<ChronicleOperation>,
+    ) -> Result<Vec<ChronicleOperation>, EnrichmentError> {
+        self.enrichers
+            .iter()
+            .try_fold(operations, |operations, enricher| {
+                trace!(enricher = enricher.name(), "Applying operation enricher");
+                enricher.enrich(identity, operations)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{
+        identity::AuthId,
+        prov::{
+            operations::{ActivityExists, ChronicleOperation, EntityExists},
+            NamespaceId,
+        },
+    };
+    use uuid::Uuid;
+
+    use super::{EnrichmentError, OperationEnricher, OperationEnrichment};
+
+    fn namespace() -> NamespaceId {
+        NamespaceId::from_external_id(
+            "testns",
+            Uuid::parse_str("5a0ab5b8-eeb7-4812-9fe3-6dd69bd20cea").unwrap(),
+        )
+    }
+
+    #[derive(Debug)]
+    struct AppendEntity(&'static str);
+
+    impl OperationEnricher for AppendEntity {
+        fn name(&self) -> &str {
+            "append-entity"
+        }
+
+        fn enrich(
+            &self,
+            _identity: &AuthId,
+            mut operations: Vec<ChronicleOperation>,
+        ) -> Result<Vec<ChronicleOperation>, EnrichmentError> {
+            operations.push(ChronicleOperation::EntityExists(EntityExists {
+                namespace: namespace(),
+                external_id: self.0.into(),
+            }));
+            Ok(operations)
+        }
+    }
+
+    #[derive(Debug)]
+    struct Reject;
+
+    impl OperationEnricher for Reject {
+        fn name(&self) -> &str {
+            "reject"
+        }
+
+        fn enrich(
+            &self,
+            _identity: &AuthId,
+            _operations: Vec<ChronicleOperation>,
+        ) -> Result<Vec<ChronicleOperation>, EnrichmentError> {
+            Err(EnrichmentError::new(self.name(), "rejected"))
+        }
+    }
+
+    fn activity() -> ChronicleOperation {
+        ChronicleOperation::ActivityExists(ActivityExists {
+            namespace: namespace(),
+            external_id: "activity".into(),
+        })
+    }
+
+    #[test]
+    fn enrichers_apply_in_order() {
+        let enrichment = OperationEnrichment::new()
+            .with_enricher(AppendEntity("first"))
+            .with_enricher(AppendEntity("second"));
+
+        let enriched = enrichment
+            .enrich(&AuthId::chronicle(), vec![activity()])
+            .unwrap();
+
+        assert_eq!(
+            enriched,
+            vec![
+                activity(),
+                ChronicleOperation::EntityExists(EntityExists {
+                    namespace: namespace(),
+                    external_id: "first".into(),
+                }),
+                ChronicleOperation::EntityExists(EntityExists {
+                    namespace: namespace(),
+                    external_id: "second".into(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn enricher_error_stops_chain() {
+        let enrichment = OperationEnrichment::new()
+            .with_enricher(Reject)
+            .with_enricher(AppendEntity("unreachable"));
+
+        let err = enrichment
+            .enrich(&AuthId::chronicle(), vec![activity()])
+            .unwrap_err();
+
+        assert_eq!(err.enricher, "reject");
+    }
+}
@@ -0,0 +1,453 @@
+//! An event sink publishes each committed delta to a message broker, so that
+//! stream processors can consume provenance changes without polling the api.
+//! Deltas are published to a Kafka topic through a Kafka REST proxy, or to a
+//! NATS subject over the NATS client protocol. Each message holds the id of
+//! the transaction and block that committed the delta, and the delta as
+//! JSON-LD, and may be encrypted to a set of recipients. Commits are published
+//! in order, with messages keyed by namespace on Kafka so that each
+//! namespace's changes stay in order within a partition, and are retried while
+//! the broker is unavailable. Namespaces may be published under pseudonyms,
+//! for consumers that should not learn their names.
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    ledger::{Commit, SubmissionStage},
+    prov::{to_json_ld::ToJson, CompactionError, ExternalIdPart, ProvModel},
+};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::broadcast::error::RecvError,
+};
+use tracing::{debug, error, info, instrument, warn};
+use url::Url;
+
+use crate::{
+    encryption::{EncryptionError, PayloadEncryption},
+    pseudonym::{NamespacePseudonyms, PseudonymError},
+    ApiDispatch,
+};
+
+/// The most commits published in one request or flush
+const BATCH_SIZE: usize = 100;
+
+/// The longest to wait before publishing again after a failure
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long to wait for a NATS server to acknowledge a flush
+const NATS_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub enum EventSinkError {
+    #[error("Event sink request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Event sink responded {status}: {body}")]
+    Rejected { status: u16, body: String },
+
+    #[error("Event sink connection failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("NATS server refused: {0}")]
+    Nats(String),
+
+    #[error("Invalid event sink url: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("Could not compact delta: {0}")]
+    Compaction(#[from] CompactionError),
+
+    #[error("Could not serialize delta: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Could not encrypt delta: {0}")]
+    Encryption(#[from] EncryptionError),
+
+    #[error("Could not pseudonymize delta: {0}")]
+    Pseudonym(#[from] PseudonymError),
+}
+
+/// How deltas are serialized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSinkFormat {
+    /// Compacted JSON-LD, as served by the `/data` endpoints
+    JsonLd,
+    /// Expanded JSON-LD
+    ExpandedJsonLd,
+}
+
+impl EventSinkFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "json-ld" => Some(EventSinkFormat::JsonLd),
+            "expanded-json-ld" => Some(EventSinkFormat::ExpandedJsonLd),
+            _ => None,
+        }
+    }
+}
+
+/// The broker to publish to
+#[derive(Debug, Clone)]
+pub enum EventSinkTarget {
+    /// A topic, published to through the Kafka REST proxy at `proxy`
+    Kafka { proxy: Url, topic: String },
+    /// A subject on the NATS server at `server`, with any credentials given
+    /// as the url's user and password
+    Nats { server: Url, subject: String },
+}
+
+/// Where and how to publish deltas, the sink is disabled unless configured
+#[derive(Debug, Clone)]
+pub struct EventSinkConf {
+    pub target: EventSinkTarget,
+    pub format: EventSinkFormat,
+    pub encryption: Option<PayloadEncryption>,
+    pub pseudonyms: Option<NamespacePseudonyms>,
+}
+
+/// A delta ready to publish
+#[derive(Debug, Clone)]
+struct Message {
+    key: String,
+    payload: Vec<u8>,
+}
+
+/// The serialized, and if configured encrypted, delta of a commit
+async fn payload(
+    conf: &EventSinkConf,
+    tx_id: &str,
+    block_id: &str,
+    delta: &ProvModel,
+) -> Result<Vec<u8>, EventSinkError> {
+    let mut json = match conf.format {
+        EventSinkFormat::JsonLd => delta.to_json().compact().await?,
+        EventSinkFormat::ExpandedJsonLd => delta.to_json().0,
+    };
+    if let Some(pseudonyms) = &conf.pseudonyms {
+        pseudonyms.apply(delta, &mut json)?;
+    }
+    let payload = serde_json::to_vec(&json!({
+        "txId": tx_id,
+        "blockId": block_id,
+        "delta": json,
+    }))?;
+
+    match &conf.encryption {
+        Some(encryption) => Ok(encryption
+            .encrypt(&payload, "application/json")?
+            .into_bytes()),
+        None => Ok(payload),
+    }
+}
+
+/// Deltas normally touch a single namespace, and are keyed by it, or its
+/// pseudonym
+fn key(conf: &EventSinkConf, tx_id: &str, delta: &ProvModel) -> Result<String, EventSinkError> {
+    let namespace = match (delta.namespaces.keys().next(), &conf.pseudonyms) {
+        (Some(namespace), Some(pseudonyms)) => pseudonyms.pseudonym(namespace)?,
+        (Some(namespace), None) => namespace.clone(),
+        (None, _) => return Ok(tx_id.to_owned()),
+    };
+
+    Ok(namespace.external_id_part().to_string())
+}
+
+async fn message(conf: &EventSinkConf, commit: &Commit) -> Result<Message, EventSinkError> {
+    let tx_id = commit.tx_id.to_string();
+
+    Ok(Message {
+        key: key(conf, &tx_id, &commit.delta)?,
+        payload: payload(conf, &tx_id, &commit.block_id.to_string(), &commit.delta).await?,
+    })
+}
+
+/// Publish records through the Kafka REST proxy's v2 api
+async fn publish_kafka(
+    client: &reqwest::Client,
+    proxy: &Url,
+    topic: &str,
+    messages: &[Message],
+) -> Result<(), EventSinkError> {
+    let records: Vec<Value> = messages
+        .iter()
+        .map(|message| {
+            json!({
+                "key": STANDARD.encode(&message.key),
+                "value": STANDARD.encode(&message.payload),
+            })
+        })
+        .collect();
+
+    let response = client
+        .post(proxy.join(&format!("topics/{topic}"))?)
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            "application/vnd.kafka.binary.v2+json",
+        )
+        .header(reqwest::header::ACCEPT, "application/vnd.kafka.v2+json")
+        .json(&json!({ "records": records }))
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(EventSinkError::Rejected {
+            status: status.as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        });
+    }
+
+    // The proxy reports failures of individual records alongside their offsets
+    let body: Value = response.json().await?;
+    let failed = body["offsets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|offset| !offset["error"].is_null());
+    match failed {
+        Some(offset) => Err(EventSinkError::Rejected {
+            status: status.as_u16(),
+            body: offset["error"].to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// A connection to a NATS server, publishing with `PUB` and confirming
+/// publications with `PING`
+struct NatsConnection {
+    reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
+    writer: tokio::net::tcp::OwnedWriteHalf,
+}
+
+impl NatsConnection {
+    #[instrument(skip(server), fields(server = %server.host_str().unwrap_or_default()))]
+    async fn connect(server: &Url) -> Result<Self, EventSinkError> {
+        let host = server.host_str().unwrap_or("localhost");
+        let port = server.port().unwrap_or(4222);
+        let (reader, writer) = TcpStream::connect((host, port)).await?.into_split();
+        let mut connection = Self {
+            reader: BufReader::new(reader),
+            writer,
+        };
+
+        let info = connection.read_line().await?;
+        let info: Value = serde_json::from_str(info.strip_prefix("INFO ").unwrap_or("{}"))?;
+        if info["tls_required"].as_bool().unwrap_or(false) {
+            return Err(EventSinkError::Nats("TLS is required".to_owned()));
+        }
+
+        let mut options = json!({
+            "verbose": false,
+            "pedantic": false,
+            "headers": false,
+            "lang": "rust",
+            "name": "chronicle",
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        if !server.username().is_empty() {
+            options["user"] = server.username().into();
+            options["pass"] = server.password().unwrap_or_default().into();
+        }
+        connection
+            .writer
+            .write_all(format!("CONNECT {options}\r\n").as_bytes())
+            .await?;
+        connection.flush().await?;
+        debug!("Connected to NATS server");
+
+        Ok(connection)
+    }
+
+    async fn read_line(&mut self) -> Result<String, EventSinkError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Err(EventSinkError::Nats("connection closed".to_owned()));
+        }
+
+        Ok(line.trim_end().to_owned())
+    }
+
+    /// Wait for the server to answer a `PING`, which it does only after
+    /// processing everything sent before it
+    async fn flush(&mut self) -> Result<(), EventSinkError> {
+        self.writer.write_all(b"PING\r\n").await?;
+        tokio::time::timeout(NATS_TIMEOUT, async {
+            loop {
+                let line = self.read_line().await?;
+                match line.as_str() {
+                    "PONG" => return Ok(()),
+                    "PING" => self.writer.write_all(b"PONG\r\n").await?,
+                    _ if line.starts_with("-ERR") => {
+                        return Err(EventSinkError::Nats(line[4..].trim().to_owned()))
+                    }
+                    _ => continue,
+                }
+            }
+        })
+        .await
+        .map_err(|_| EventSinkError::Nats("timed out waiting for the server".to_owned()))?
+    }
+
+    async fn publish(&mut self, subject: &str, messages: &[Message]) -> Result<(), EventSinkError> {
+        for message in messages {
+            self.writer
+                .write_all(format!("PUB {subject} {}\r\n", message.payload.len()).as_bytes())
+                .await?;
+            self.writer.write_all(&message.payload).await?;
+            self.writer.write_all(b"\r\n").await?;
+        }
+
+        self.flush().await
+    }
+}
+
+/// Publishes batches of messages to the configured broker, reconnecting as
+/// needed
+struct Publisher {
+    target: EventSinkTarget,
+    client: reqwest::Client,
+    nats: Option<NatsConnection>,
+}
+
+impl Publisher {
+    async fn publish(&mut self, messages: &[Message]) -> Result<(), EventSinkError> {
+        match &self.target {
+            EventSinkTarget::Kafka { proxy, topic } => {
+                publish_kafka(&self.client, proxy, topic, messages).await
+            }
+            EventSinkTarget::Nats { server, subject } => {
+                let mut connection = match self.nats.take() {
+                    Some(connection) => connection,
+                    None => NatsConnection::connect(server).await?,
+                };
+                connection.publish(subject, messages).await?;
+                // A connection that failed is dropped, and replaced on the next attempt
+                self.nats = Some(connection);
+                Ok(())
+            }
+        }
+    }
+
+    /// Publish `messages`, retrying with increasing delays until the broker
+    /// accepts them
+    async fn publish_with_retry(&mut self, messages: &[Message]) {
+        let mut backoff = Duration::from_millis(500);
+        loop {
+            match self.publish(messages).await {
+                Ok(()) => return,
+                Err(e) => {
+                    warn!(%e, ?backoff, "Event sink unavailable, retrying");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+}
+
+/// Publish each commit to the event sink until the api stops. Commits are
+/// batched while the broker is busy.
+pub async fn spawn_event_sink(
+    api: &ApiDispatch,
+    conf: EventSinkConf,
+) -> Result<(), EventSinkError> {
+    let mut publisher = Publisher {
+        target: conf.target.clone(),
+        client: reqwest::Client::new(),
+        nats: None,
+    };
+    if let EventSinkTarget::Nats { server, .. } = &conf.target {
+        publisher.nats = Some(NatsConnection::connect(server).await?);
+    }
+    match &conf.target {
+        EventSinkTarget::Kafka { proxy, topic } => {
+            info!(%proxy, %topic, "Publishing commits to Kafka")
+        }
+        EventSinkTarget::Nats { server, subject } => info!(
+            server = server.host_str().unwrap_or_default(),
+            %subject,
+            "Publishing commits to NATS"
+        ),
+    }
+
+    let mut commits = api.notify_commit.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let mut pending = vec![];
+            match commits.recv().await {
+                Ok(SubmissionStage::Committed(commit, _)) => pending.push(commit),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(missed, "Event sink fell behind, commits were not published");
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            }
+
+            // Take what else has been committed while the last batch was published
+            while pending.len() < BATCH_SIZE {
+                match commits.try_recv() {
+                    Ok(SubmissionStage::Committed(commit, _)) => pending.push(commit),
+                    Ok(_) => continue,
+                    Err(_) => break,
+                }
+            }
+
+            let mut messages = Vec::with_capacity(pending.len());
+            for commit in &pending {
+                match message(&conf, commit).await {
+                    Ok(message) => messages.push(message),
+                    Err(e) => {
+                        error!(tx_id = %commit.tx_id, %e, "Could not serialize commit for event sink")
+                    }
+                }
+            }
+            publisher.publish_with_retry(&messages).await;
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use common::prov::{
+        operations::{ChronicleOperation, CreateNamespace},
+        NamespaceId, ProvModel,
+    };
+    use uuid::Uuid;
+
+    use super::{key, payload, EventSinkConf, EventSinkFormat, EventSinkTarget};
+
+    #[tokio::test]
+    async fn deltas_are_keyed_by_namespace() {
+        let namespace = NamespaceId::from_external_id("testns", Uuid::nil());
+        let delta = ProvModel::from_tx(&[ChronicleOperation::CreateNamespace(
+            CreateNamespace::new(namespace, "testns", Uuid::nil()),
+        )])
+        .unwrap();
+        let conf = EventSinkConf {
+            target: EventSinkTarget::Nats {
+                server: "nats://localhost:4222".parse().unwrap(),
+                subject: "chronicle.commits".to_owned(),
+            },
+            format: EventSinkFormat::JsonLd,
+            encryption: None,
+            pseudonyms: None,
+        };
+
+        assert_eq!(key(&conf, "tx", &delta).unwrap(), "testns");
+        assert_eq!(key(&conf, "tx", &ProvModel::default()).unwrap(), "tx");
+
+        let payload: serde_json::Value =
+            serde_json::from_slice(&payload(&conf, "tx", "block", &delta).await.unwrap()).unwrap();
+        assert_eq!(payload["txId"], "tx");
+        assert_eq!(payload["blockId"], "block");
+        assert!(payload["delta"].is_object());
+    }
+}
@@ -0,0 +1,136 @@
+//! Expiry ends the lifetime of entities whose type the domain gives a TTL.
+//! Once an entity has been stored for longer than its type's TTL, an activity
+//! of type `ChronicleExpiry` that uses it is recorded, starting and ending at
+//! the moment it expired. Expired entities are left out of `entitiesByType`
+//! unless they are asked for. Expiry activities are identified by the entity
+//! they expire, so every replica running the scheduler records the same one.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use common::{
+    attributes::Attributes,
+    identity::AuthId,
+    prov::{
+        operations::{
+            ActivityExists, ActivityUses, ChronicleOperation, EndActivity, SetAttributes,
+            StartActivity,
+        },
+        ActivityId, DomaintypeId, EntityId, ExternalIdPart, NamespaceId,
+    },
+};
+use tracing::{debug, error, info, instrument};
+
+use crate::{ApiDispatch, ApiError};
+
+/// The domain type of the activities that expire entities
+pub const EXPIRY_ACTIVITY_TYPE: &str = "ChronicleExpiry";
+
+/// The most entities of each type expired in one pass
+const BATCH_SIZE: i64 = 100;
+
+#[derive(Debug, Clone)]
+pub struct ExpiryConf {
+    /// TTLs keyed by entity domain type
+    pub ttls: BTreeMap<String, Duration>,
+    /// How often to look for expired entities
+    pub interval: Duration,
+}
+
+/// The operations recording the expiry of `entity` at `expired_at`
+pub fn expiry_operations(
+    namespace: &NamespaceId,
+    entity: &EntityId,
+    expired_at: DateTime<Utc>,
+) -> Vec<ChronicleOperation> {
+    let external_id = format!("{}-expiry", entity.external_id_part());
+    let activity = ActivityId::from_external_id(&external_id);
+
+    vec![
+        ChronicleOperation::ActivityExists(ActivityExists {
+            namespace: namespace.clone(),
+            external_id: external_id.into(),
+        }),
+        ChronicleOperation::SetAttributes(SetAttributes::Activity {
+            namespace: namespace.clone(),
+            id: activity.clone(),
+            attributes: Attributes::type_only(Some(DomaintypeId::from_external_id(
+                EXPIRY_ACTIVITY_TYPE,
+            ))),
+        }),
+        ChronicleOperation::StartActivity(StartActivity {
+            namespace: namespace.clone(),
+            id: activity.clone(),
+            time: expired_at,
+        }),
+        ChronicleOperation::EndActivity(EndActivity {
+            namespace: namespace.clone(),
+            id: activity.clone(),
+            time: expired_at,
+        }),
+        ChronicleOperation::ActivityUses(ActivityUses {
+            namespace: namespace.clone(),
+            id: entity.clone(),
+            activity,
+        }),
+    ]
+}
+
+/// Record the expiry of entities that have outlived their type's TTL at
+/// `now`, returning how many were expired
+#[instrument(skip(api, ttls))]
+pub(crate) async fn expire_entities(
+    api: &ApiDispatch,
+    ttls: &BTreeMap<String, Duration>,
+    now: NaiveDateTime,
+) -> Result<usize, ApiError> {
+    let mut expired = 0;
+
+    for (domaintype, ttl) in ttls {
+        let ttl = match chrono::Duration::from_std(*ttl) {
+            Ok(ttl) => ttl,
+            Err(_) => continue,
+        };
+        let created_before = match now.checked_sub_signed(ttl) {
+            Some(created_before) => created_before,
+            None => continue,
+        };
+
+        let store = api.store.clone();
+        let entities = store.read_only(|connection| {
+            store.unexpired_entities(connection, domaintype, created_before, BATCH_SIZE)
+        })?;
+
+        for (namespace, entity, created_at) in entities {
+            let expired_at = DateTime::from_naive_utc_and_offset(created_at + ttl, Utc);
+            debug!(%entity, %expired_at, "Expiring entity");
+            api.handle_import_command(
+                AuthId::chronicle(),
+                namespace.clone(),
+                expiry_operations(&namespace, &entity, expired_at),
+            )
+            .await?;
+            expired += 1;
+        }
+    }
+
+    Ok(expired)
+}
+
+/// Look for expired entities every `conf.interval` until the api stops
+pub fn spawn_expiry(api: &ApiDispatch, conf: ExpiryConf) {
+    info!(types = ?conf.ttls.keys().collect::<Vec<_>>(), "Expiring entities");
+
+    let api = api.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(conf.interval);
+        while api.is_running() {
+            interval.tick().await;
+            match expire_entities(&api, &conf.ttls, Utc::now().naive_utc()).await {
+                Ok(0) => {}
+                Ok(expired) => info!(expired, "Expired entities"),
+                Err(e) => error!(%e, "Failed to expire entities"),
+            }
+        }
+    });
+}
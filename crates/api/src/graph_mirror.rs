@@ -0,0 +1,560 @@
+//! A mirror of the provenance graph in Neo4j, for analytics such as
+//! centrality or community detection that are impractical in SQL. Agents,
+//! activities and entities become nodes labelled with their kind and domain
+//! type, and relations between them become relationships. The mirror follows
+//! commit notifications, writing each commit with `MERGE` statements through
+//! Neo4j's HTTP transaction API, so applying a commit twice changes nothing.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use common::{
+    attributes::Attribute,
+    ledger::SubmissionStage,
+    prov::{operations::DerivationType, ExternalIdPart, NamespaceId, ProvModel, Role},
+};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, instrument, warn};
+use url::Url;
+
+use crate::ApiDispatch;
+
+/// The most statements sent to the graph database in one transaction
+const BATCH_SIZE: usize = 1000;
+
+/// The longest to wait before retrying a transaction that failed transiently
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum GraphMirrorError {
+    #[error("Graph database request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Graph database responded {status}: {body}")]
+    Rejected { status: u16, body: String },
+
+    #[error("Graph database could not run statements: {0}")]
+    Cypher(String),
+
+    #[error("Invalid graph database url: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("Invalid JSON from the graph database: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The Neo4j database to mirror into, the mirror is disabled unless
+/// configured
+#[derive(Debug, Clone)]
+pub struct GraphMirrorConf {
+    pub address: Url,
+    pub database: String,
+    pub credentials: Option<(String, String)>,
+}
+
+impl GraphMirrorConf {
+    pub fn new(
+        address: Url,
+        database: impl Into<String>,
+        credentials: Option<(String, String)>,
+    ) -> Self {
+        Self {
+            address,
+            database: database.into(),
+            credentials,
+        }
+    }
+}
+
+/// A Cypher statement with its parameters
+fn statement(cypher: impl Into<String>, parameters: Value) -> Value {
+    json!({ "statement": cypher.into(), "parameters": parameters })
+}
+
+/// Quote a label or property name for Cypher
+fn quoted(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// Attribute values as node properties, which can only be scalars or lists of
+/// scalars, so other values are stored as JSON strings
+fn properties(attributes: &BTreeMap<String, Attribute>) -> Value {
+    let scalar = |value: &Value| !(value.is_object() || value.is_array() || value.is_null());
+
+    attributes
+        .iter()
+        .map(|(name, attribute)| {
+            let value = match &attribute.value {
+                Value::Array(items) if items.iter().all(scalar) => attribute.value.clone(),
+                Value::Object(_) | Value::Array(_) => Value::String(attribute.value.to_string()),
+                value => value.clone(),
+            };
+            (name.clone(), value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn node(
+    kind: &str,
+    namespace: &NamespaceId,
+    id: String,
+    external_id: &str,
+    domaintype: Option<&str>,
+    attributes: &BTreeMap<String, Attribute>,
+) -> Value {
+    // Labels cannot be parameters, domain types are quoted instead
+    let label = domaintype
+        .map(|domaintype| format!(" SET n:{}", quoted(domaintype)))
+        .unwrap_or_default();
+
+    statement(
+        format!(
+            "MERGE (n:{kind} {{id: $id, namespace: $namespace}}) \
+             SET n += $attributes SET n.externalId = $externalId{label}"
+        ),
+        json!({
+            "id": id,
+            "namespace": namespace.external_id_part().to_string(),
+            "externalId": external_id,
+            "attributes": properties(attributes),
+        }),
+    )
+}
+
+/// Merge a relationship between two nodes. Relationships are distinguished by
+/// their properties, none of which may be null.
+fn relationship(
+    namespace: &NamespaceId,
+    (from_kind, from): (&str, String),
+    typ: &str,
+    (to_kind, to): (&str, String),
+    properties: Value,
+) -> Value {
+    statement(
+        format!(
+            "MERGE (a:{from_kind} {{id: $from, namespace: $namespace}}) \
+             MERGE (b:{to_kind} {{id: $to, namespace: $namespace}}) \
+             MERGE (a)-[r:{typ} $properties]->(b)"
+        ),
+        json!({
+            "namespace": namespace.external_id_part().to_string(),
+            "from": from,
+            "to": to,
+            "properties": properties,
+        }),
+    )
+}
+
+fn retract_relationship(
+    namespace: &NamespaceId,
+    (from_kind, from): (&str, String),
+    typ: &str,
+    (to_kind, to): (&str, String),
+    properties: Value,
+) -> Value {
+    statement(
+        format!(
+            "MATCH (a:{from_kind} {{id: $from, namespace: $namespace}})\
+             -[r:{typ} $properties]->(b:{to_kind} {{id: $to, namespace: $namespace}}) \
+             DELETE r"
+        ),
+        json!({
+            "namespace": namespace.external_id_part().to_string(),
+            "from": from,
+            "to": to,
+            "properties": properties,
+        }),
+    )
+}
+
+fn role(role: &Option<Role>) -> String {
+    role.as_ref().map(Role::to_string).unwrap_or_default()
+}
+
+/// The statements that apply a commit to the mirror, nodes before the
+/// relationships between them
+fn statements(delta: &ProvModel) -> Vec<Value> {
+    let mut statements = vec![];
+
+    for agent in delta.agents.values() {
+        statements.push(node(
+            "Agent",
+            &agent.namespaceid,
+            agent.id.to_string(),
+            &agent.external_id,
+            agent
+                .domaintypeid
+                .as_ref()
+                .map(|typ| typ.external_id_part().as_str()),
+            &agent.attributes,
+        ));
+    }
+    for activity in delta.activities.values() {
+        statements.push(node(
+            "Activity",
+            &activity.namespaceid,
+            activity.id.to_string(),
+            &activity.external_id,
+            activity
+                .domaintypeid
+                .as_ref()
+                .map(|typ| typ.external_id_part().as_str()),
+            &activity.attributes,
+        ));
+    }
+    for entity in delta.entities.values() {
+        statements.push(node(
+            "Entity",
+            &entity.namespaceid,
+            entity.id.to_string(),
+            &entity.external_id,
+            entity
+                .domaintypeid
+                .as_ref()
+                .map(|typ| typ.external_id_part().as_str()),
+            &entity.attributes,
+        ));
+    }
+
+    for ((namespace, _), associations) in &delta.association {
+        for association in associations {
+            statements.push(relationship(
+                namespace,
+                ("Activity", association.activity_id.to_string()),
+                "WAS_ASSOCIATED_WITH",
+                ("Agent", association.agent_id.to_string()),
+                json!({ "role": role(&association.role) }),
+            ));
+        }
+    }
+    for ((namespace, _), delegations) in &delta.delegation {
+        for delegation in delegations {
+            statements.push(relationship(
+                namespace,
+                ("Agent", delegation.delegate_id.to_string()),
+                "ACTED_ON_BEHALF_OF",
+                ("Agent", delegation.responsible_id.to_string()),
+                json!({
+                    "role": role(&delegation.role),
+                    "activity": delegation
+                        .activity_id
+                        .as_ref()
+                        .map(ToString::to_string)
+                        .unwrap_or_default(),
+                }),
+            ));
+        }
+    }
+    for ((namespace, _), derivations) in &delta.derivation {
+        for derivation in derivations {
+            let typ = match derivation.typ {
+                DerivationType::None => "",
+                DerivationType::Revision => "Revision",
+                DerivationType::Quotation => "Quotation",
+                DerivationType::PrimarySource => "PrimarySource",
+            };
+            statements.push(relationship(
+                namespace,
+                ("Entity", derivation.generated_id.to_string()),
+                "WAS_DERIVED_FROM",
+                ("Entity", derivation.used_id.to_string()),
+                json!({ "type": typ }),
+            ));
+        }
+    }
+    for ((namespace, _), generations) in &delta.generation {
+        for generation in generations {
+            statements.push(relationship(
+                namespace,
+                ("Entity", generation.generated_id.to_string()),
+                "WAS_GENERATED_BY",
+                ("Activity", generation.activity_id.to_string()),
+                json!({}),
+            ));
+        }
+    }
+    for ((namespace, _), usages) in &delta.usage {
+        for usage in usages {
+            statements.push(relationship(
+                namespace,
+                ("Activity", usage.activity_id.to_string()),
+                "USED",
+                ("Entity", usage.entity_id.to_string()),
+                json!({}),
+            ));
+        }
+    }
+    for ((namespace, informed), informants) in &delta.was_informed_by {
+        for (_, informant) in informants {
+            statements.push(relationship(
+                namespace,
+                ("Activity", informed.to_string()),
+                "WAS_INFORMED_BY",
+                ("Activity", informant.to_string()),
+                json!({}),
+            ));
+        }
+    }
+    for ((namespace, _), attributions) in &delta.attribution {
+        for attribution in attributions {
+            statements.push(relationship(
+                namespace,
+                ("Entity", attribution.entity_id.to_string()),
+                "WAS_ATTRIBUTED_TO",
+                ("Agent", attribution.agent_id.to_string()),
+                json!({ "role": role(&attribution.role) }),
+            ));
+        }
+    }
+
+    for ((namespace, _), associations) in &delta.retracted_association {
+        for association in associations {
+            statements.push(retract_relationship(
+                namespace,
+                ("Activity", association.activity_id.to_string()),
+                "WAS_ASSOCIATED_WITH",
+                ("Agent", association.agent_id.to_string()),
+                json!({ "role": role(&association.role) }),
+            ));
+        }
+    }
+    for ((namespace, _), attributions) in &delta.retracted_attribution {
+        for attribution in attributions {
+            statements.push(retract_relationship(
+                namespace,
+                ("Entity", attribution.entity_id.to_string()),
+                "WAS_ATTRIBUTED_TO",
+                ("Agent", attribution.agent_id.to_string()),
+                json!({ "role": role(&attribution.role) }),
+            ));
+        }
+    }
+    for ((namespace, id), names) in &delta.retracted_attributes {
+        for name in names {
+            statements.push(statement(
+                format!(
+                    "MATCH (n {{id: $id, namespace: $namespace}}) REMOVE n.{}",
+                    quoted(name)
+                ),
+                json!({
+                    "id": id.to_string(),
+                    "namespace": namespace.external_id_part().to_string(),
+                }),
+            ));
+        }
+    }
+
+    statements
+}
+
+/// Run `statements` in one transaction
+async fn run(
+    client: &reqwest::Client,
+    conf: &GraphMirrorConf,
+    statements: &[Value],
+) -> Result<(), GraphMirrorError> {
+    let url = conf
+        .address
+        .join(&format!("db/{}/tx/commit", conf.database))?;
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(json!({ "statements": statements }).to_string());
+    if let Some((user, password)) = &conf.credentials {
+        request = request.basic_auth(user, Some(password));
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(GraphMirrorError::Rejected {
+            status: status.as_u16(),
+            body,
+        });
+    }
+
+    let outcome: Value = serde_json::from_str(&body)?;
+    match outcome["errors"].as_array() {
+        Some(errors) if !errors.is_empty() => Err(GraphMirrorError::Cypher(
+            Value::from(errors.clone()).to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Neo4j reports errors that may succeed on retry with codes in this class
+fn is_transient(e: &GraphMirrorError) -> bool {
+    match e {
+        GraphMirrorError::Request(_) => true,
+        GraphMirrorError::Rejected { status, .. } => *status == 429 || *status >= 500,
+        GraphMirrorError::Cypher(errors) => errors.contains("Neo.TransientError"),
+        _ => false,
+    }
+}
+
+/// Apply `statements`, waiting and retrying while the database is unavailable
+#[instrument(skip(client, conf, statements), fields(statements = statements.len()))]
+async fn write(
+    client: &reqwest::Client,
+    conf: &GraphMirrorConf,
+    statements: &[Value],
+) -> Result<(), GraphMirrorError> {
+    let mut backoff = Duration::from_millis(500);
+    loop {
+        match run(client, conf, statements).await {
+            Ok(()) => {
+                debug!("Mirrored statements");
+                return Ok(());
+            }
+            Err(e) if is_transient(&e) => {
+                warn!(%e, ?backoff, "Graph database is unavailable, retrying");
+            }
+            Err(e) => return Err(e),
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Index the properties nodes are merged on, unless they are already indexed
+pub(crate) async fn ensure_indexes(
+    client: &reqwest::Client,
+    conf: &GraphMirrorConf,
+) -> Result<(), GraphMirrorError> {
+    let indexes: Vec<_> = ["Agent", "Activity", "Entity"]
+        .into_iter()
+        .map(|kind| {
+            statement(
+                format!("CREATE INDEX IF NOT EXISTS FOR (n:{kind}) ON (n.id, n.namespace)"),
+                json!({}),
+            )
+        })
+        .collect();
+
+    run(client, conf, &indexes).await
+}
+
+/// Merge the records in `model` and their relations into the graph
+pub(crate) async fn mirror_model(
+    client: &reqwest::Client,
+    conf: &GraphMirrorConf,
+    model: &ProvModel,
+) -> Result<(), GraphMirrorError> {
+    for batch in statements(model).chunks(BATCH_SIZE) {
+        write(client, conf, batch).await?;
+    }
+
+    Ok(())
+}
+
+/// Index the properties nodes are merged on, then mirror each commit until the
+/// api stops. Commits are batched while the database is busy.
+pub async fn spawn_mirror(
+    api: &ApiDispatch,
+    conf: GraphMirrorConf,
+) -> Result<(), GraphMirrorError> {
+    let client = reqwest::Client::new();
+    ensure_indexes(&client, &conf).await?;
+    info!(address = %conf.address, database = %conf.database, "Mirroring provenance to graph database");
+
+    let mut commits = api.notify_commit.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let mut pending = vec![];
+            match commits.recv().await {
+                Ok(SubmissionStage::Committed(commit, _)) => {
+                    pending.extend(statements(&commit.delta))
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(
+                        missed,
+                        "Graph mirror fell behind, commits were not mirrored"
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            }
+
+            // Take what else has been committed while the last batch was written
+            while pending.len() < BATCH_SIZE {
+                match commits.try_recv() {
+                    Ok(SubmissionStage::Committed(commit, _)) => {
+                        pending.extend(statements(&commit.delta))
+                    }
+                    Ok(_) => continue,
+                    Err(_) => break,
+                }
+            }
+
+            for batch in pending.chunks(BATCH_SIZE) {
+                if let Err(e) = write(&client, &conf, batch).await {
+                    error!(%e, "Graph database refused commits");
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use common::{
+        attributes::Attributes,
+        prov::{
+            operations::{ActivityUses, ChronicleOperation, CreateNamespace, SetAttributes},
+            ActivityId, DomaintypeId, EntityId, NamespaceId, ProvModel,
+        },
+    };
+    use uuid::Uuid;
+
+    use super::statements;
+
+    #[test]
+    fn nodes_are_merged_before_relationships() {
+        let namespace = NamespaceId::from_external_id("testns", Uuid::nil());
+        let delta = ProvModel::from_tx(&[
+            ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                namespace.clone(),
+                "testns",
+                Uuid::nil(),
+            )),
+            ChronicleOperation::SetAttributes(SetAttributes::Entity {
+                namespace: namespace.clone(),
+                id: EntityId::from_external_id("report"),
+                attributes: Attributes::type_only(Some(DomaintypeId::from_external_id(
+                    "ReportEntity",
+                ))),
+            }),
+            ChronicleOperation::ActivityUses(ActivityUses {
+                namespace,
+                id: EntityId::from_external_id("report"),
+                activity: ActivityId::from_external_id("review"),
+            }),
+        ])
+        .unwrap();
+
+        let statements = statements(&delta);
+        let cypher: Vec<_> = statements
+            .iter()
+            .map(|statement| statement["statement"].as_str().unwrap())
+            .collect();
+
+        assert_eq!(cypher.len(), 3);
+        assert!(cypher[0].starts_with("MERGE (n:Activity"));
+        assert!(cypher[1].starts_with("MERGE (n:Entity"));
+        assert!(cypher[1].ends_with("SET n:`ReportEntity`"));
+        assert!(cypher[2].ends_with("MERGE (a)-[r:USED $properties]->(b)"));
+        assert_eq!(
+            statements[2]["parameters"]["from"],
+            "chronicle:activity:review"
+        );
+        assert_eq!(statements[2]["parameters"]["to"], "chronicle:entity:report");
+        assert_eq!(statements[2]["parameters"]["namespace"], "testns");
+    }
+}
@@ -0,0 +1,134 @@
+//! State the api shares with readiness probes: whether it is subscribed to
+//! ledger updates, and how long its oldest submitted transaction has waited
+//! to be seen there.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use common::prov::ChronicleTransactionId;
+use tracing::warn;
+
+/// The longest a submitted transaction may wait to be seen on the ledger
+/// before the api is reported as not ready
+pub const MAX_SYNC_LAG: Duration = Duration::from_secs(60);
+
+/// How long a submitted transaction is waited for before it is taken to have
+/// been dropped, as the validator does with transactions it rejects outright,
+/// so that it no longer holds the api out of readiness
+pub const UNSYNCED_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    ledger_connected: Arc<AtomicBool>,
+    unsynced: Arc<Mutex<HashMap<ChronicleTransactionId, Instant>>>,
+}
+
+impl Health {
+    pub(crate) fn set_ledger_connected(&self, connected: bool) {
+        self.ledger_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub(crate) fn submitted(&self, tx_id: ChronicleTransactionId) {
+        self.submitted_at(tx_id, Instant::now());
+    }
+
+    fn submitted_at(&self, tx_id: ChronicleTransactionId, now: Instant) {
+        let mut unsynced = self.unsynced.lock().unwrap();
+        Self::expire(&mut unsynced, now);
+        unsynced.insert(tx_id, now);
+    }
+
+    /// Stop waiting for transactions submitted longer than [UNSYNCED_EXPIRY]
+    /// before `now`
+    fn expire(unsynced: &mut HashMap<ChronicleTransactionId, Instant>, now: Instant) {
+        unsynced.retain(|tx_id, submitted| {
+            let waiting = now.saturating_duration_since(*submitted) < UNSYNCED_EXPIRY;
+            if !waiting {
+                warn!(%tx_id, "Submitted transaction was never seen on the ledger");
+            }
+            waiting
+        });
+    }
+
+    pub(crate) fn synced(&self, tx_id: &ChronicleTransactionId) {
+        self.unsynced.lock().unwrap().remove(tx_id);
+    }
+
+    /// True while the api holds a subscription to ledger updates
+    pub fn ledger_connected(&self) -> bool {
+        self.ledger_connected.load(Ordering::Relaxed)
+    }
+
+    /// How long the oldest transaction submitted by this api has waited to be
+    /// seen on the ledger, zero if none are waiting. Transactions waited for
+    /// longer than [UNSYNCED_EXPIRY] are no longer counted
+    pub fn sync_lag(&self) -> Duration {
+        self.sync_lag_at(Instant::now())
+    }
+
+    fn sync_lag_at(&self, now: Instant) -> Duration {
+        let mut unsynced = self.unsynced.lock().unwrap();
+        Self::expire(&mut unsynced, now);
+        unsynced
+            .values()
+            .min()
+            .map(|submitted| now.saturating_duration_since(*submitted))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use common::prov::ChronicleTransactionId;
+
+    use super::{Health, MAX_SYNC_LAG, UNSYNCED_EXPIRY};
+
+    #[test]
+    fn sync_lag_tracks_oldest_unsynced_transaction() {
+        let health = Health::default();
+        assert_eq!(health.sync_lag(), Duration::ZERO);
+
+        let first = ChronicleTransactionId::from("first");
+        let second = ChronicleTransactionId::from("second");
+        health.submitted(first.clone());
+        std::thread::sleep(Duration::from_millis(10));
+        health.submitted(second.clone());
+
+        let lag = health.sync_lag();
+        assert!(lag >= Duration::from_millis(10));
+
+        health.synced(&first);
+        assert!(health.sync_lag() < lag);
+
+        health.synced(&second);
+        assert_eq!(health.sync_lag(), Duration::ZERO);
+    }
+
+    #[test]
+    fn transactions_never_seen_on_the_ledger_stop_counting_after_expiry() {
+        let health = Health::default();
+        let submitted = Instant::now();
+        let expired = submitted + UNSYNCED_EXPIRY + Duration::from_secs(1);
+
+        health.submitted_at(ChronicleTransactionId::from("dropped"), submitted);
+        assert!(health.sync_lag_at(expired - Duration::from_secs(2)) > MAX_SYNC_LAG);
+        assert_eq!(health.sync_lag_at(expired), Duration::ZERO);
+        assert!(health.unsynced.lock().unwrap().is_empty());
+
+        // Expired as others are submitted, without the lag being read
+        health.submitted_at(ChronicleTransactionId::from("dropped"), submitted);
+        health.submitted_at(ChronicleTransactionId::from("waiting"), expired);
+        assert_eq!(
+            health.unsynced.lock().unwrap().keys().collect::<Vec<_>>(),
+            vec![&ChronicleTransactionId::from("waiting")]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use common::{
+    attributes::Attributes,
+    prov::{ExternalId, ExternalIdPart},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Crockford's base 32 alphabet, which ULIDs are written in
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// How the api names new agents, activities and entities of a domain type
+/// that are created without an external id. Those created with one keep it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdStrategy {
+    /// Records are named by their creator
+    #[default]
+    Supplied,
+    /// A random UUID
+    Uuid,
+    /// A ULID, which sorts by the time the record was created
+    Ulid,
+    /// The SHA-256 of the record's domain type and attributes, so recording
+    /// the same attributes twice names the same record
+    HashOfAttributes,
+}
+
+/// A ULID for the moment `at`, with 80 bits of `randomness`
+fn ulid(at: DateTime<Utc>, randomness: u128) -> String {
+    let millis = u128::try_from(at.timestamp_millis()).unwrap_or_default() & ((1 << 48) - 1);
+    let mut value = (millis << 80) | (randomness & ((1 << 80) - 1));
+
+    let mut encoded = [0u8; 26];
+    for digit in encoded.iter_mut().rev() {
+        *digit = CROCKFORD[(value & 31) as usize];
+        value >>= 5;
+    }
+
+    String::from_utf8_lossy(&encoded).into_owned()
+}
+
+/// The hex SHA-256 of the domain type and attributes, which are ordered by
+/// name so the digest does not depend on the order they were given in
+fn hash_of_attributes(attributes: &Attributes) -> String {
+    let domaintype = attributes
+        .typ
+        .as_ref()
+        .map(|typ| typ.external_id_part().to_string());
+    let values: BTreeMap<&String, &serde_json::Value> = attributes
+        .attributes
+        .iter()
+        .map(|(name, attribute)| (name, &attribute.value))
+        .collect();
+    let canonical = serde_json::json!({ "type": domaintype, "attributes": values });
+
+    hex::encode(openssl::sha::sha256(canonical.to_string().as_bytes()))
+}
+
+impl IdStrategy {
+    /// An external id for a new record with `attributes`, where `uuid` is used
+    /// as the source of randomness
+    pub fn generate(&self, attributes: &Attributes, uuid: Uuid) -> Option<ExternalId> {
+        match self {
+            IdStrategy::Supplied => None,
+            IdStrategy::Uuid => Some(uuid.to_string().into()),
+            IdStrategy::Ulid => Some(ulid(Utc::now(), uuid.as_u128()).into()),
+            IdStrategy::HashOfAttributes => Some(hash_of_attributes(attributes).into()),
+        }
+    }
+}
+
+/// The [IdStrategy] of each domain type that has one, keyed by the name its
+/// records are recorded under
+#[derive(Debug, Clone, Default)]
+pub struct IdStrategies {
+    strategies: BTreeMap<String, IdStrategy>,
+}
+
+impl IdStrategies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_strategy(mut self, domaintype: impl Into<String>, strategy: IdStrategy) -> Self {
+        self.strategies.insert(domaintype.into(), strategy);
+        self
+    }
+
+    /// `external_id` if one was given, otherwise one generated by the
+    /// strategy of the domain type in `attributes`
+    pub fn external_id(
+        &self,
+        external_id: ExternalId,
+        attributes: &Attributes,
+        uuid: impl FnOnce() -> Uuid,
+    ) -> ExternalId {
+        if !external_id.as_str().is_empty() {
+            return external_id;
+        }
+
+        attributes
+            .typ
+            .as_ref()
+            .and_then(|typ| self.strategies.get(typ.external_id_part().as_str()))
+            .and_then(|strategy| strategy.generate(attributes, uuid()))
+            .unwrap_or(external_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use common::{
+        attributes::{Attribute, Attributes},
+        prov::DomaintypeId,
+    };
+    use uuid::Uuid;
+
+    use super::{ulid, IdStrategies, IdStrategy};
+
+    #[test]
+    fn records_without_an_external_id_are_named_by_their_type() {
+        let attributes = |values: &[(&str, serde_json::Value)]| Attributes {
+            typ: Some(DomaintypeId::from_external_id("Sample")),
+            attributes: values
+                .iter()
+                .map(|(name, value)| (name.to_string(), Attribute::new(*name, value.clone())))
+                .collect(),
+        };
+        let sample = attributes(&[("site", "north".into()), ("depth", 12.into())]);
+        let uuid = || Uuid::from_u128(0x6803790d58914dfaa77341827d2c630b);
+
+        let supplied = IdStrategies::new().with_strategy("Sample", IdStrategy::Supplied);
+        let uuids = IdStrategies::new().with_strategy("Sample", IdStrategy::Uuid);
+        let hashes = IdStrategies::new().with_strategy("Sample", IdStrategy::HashOfAttributes);
+
+        assert_eq!(
+            uuids.external_id("sample-1".into(), &sample, uuid),
+            "sample-1".into()
+        );
+        assert_eq!(supplied.external_id("".into(), &sample, uuid), "".into());
+        assert_eq!(
+            uuids.external_id("".into(), &sample, uuid),
+            "6803790d-5891-4dfa-a773-41827d2c630b".into()
+        );
+
+        let hashed = hashes.external_id("".into(), &sample, uuid);
+        assert_eq!(hashed.as_str().len(), 64);
+        assert_eq!(
+            hashes.external_id(
+                "".into(),
+                &attributes(&[("depth", 12.into()), ("site", "north".into())]),
+                uuid
+            ),
+            hashed
+        );
+        assert_ne!(
+            hashes.external_id(
+                "".into(),
+                &attributes(&[("site", "south".into()), ("depth", 12.into())]),
+                uuid
+            ),
+            hashed
+        );
+
+        assert_eq!(
+            ulid(Utc.timestamp_millis_opt(1469918176385).unwrap(), 0),
+            "01ARYZ6S410000000000000000"
+        );
+    }
+}
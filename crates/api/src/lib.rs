@@ -1,49 +1,74 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
+pub mod backfill;
 pub mod chronicle_graphql;
+pub mod encryption;
+pub mod enrichment;
+pub mod event_sink;
+pub mod expiry;
+pub mod graph_mirror;
+pub mod health;
+pub mod id_strategy;
 pub mod inmem;
 mod persistence;
+pub mod policy_watcher;
+pub mod prometheus;
+pub mod pseudonym;
+pub mod validation;
+pub mod webhooks;
 
 use async_stl_client::{
     error::SawtoothCommunicationError,
     ledger::{BlockId, BlockingLedgerWriter, FromBlock},
 };
+use chronicle_graphql::receipt::{payload_digest, Receipt};
 use chronicle_protocol::{
     async_stl_client::ledger::{LedgerReader, LedgerWriter},
     messages::ChronicleSubmitTransaction,
-    protocol::ChronicleOperationEvent,
+    protocol::{ChronicleOperationEvent, ProtocolError},
+};
+use chronicle_signing::{
+    ChronicleSigning, NewKey, PendingKeyRotation, SecretError, CHRONICLE_NAMESPACE, CHRONICLE_PK,
 };
-use chronicle_signing::{ChronicleSigning, SecretError};
 use chrono::{DateTime, Utc};
 
-use diesel::{r2d2::ConnectionManager, PgConnection};
+use diesel::r2d2::ConnectionManager;
 use diesel_migrations::MigrationHarness;
+use enrichment::{EnrichmentError, OperationEnrichment};
 use futures::{select, FutureExt, StreamExt};
+use health::Health;
+use id_strategy::IdStrategies;
+use validation::{AttributeValidation, ValidationError};
 
 use common::{
-    attributes::Attributes,
+    attributes::{Attribute, Attributes},
     commands::*,
-    identity::{AuthId, IdentityError},
+    identity::{AuthId, IdentityError, OpaData, SignedIdentity},
     ledger::{Commit, SubmissionError, SubmissionStage, SubscriptionError},
+    opa::{ExecutorContext, OpaExecutorError},
     prov::{
         operations::{
             ActivityExists, ActivityUses, ActsOnBehalfOf, AgentExists, ChronicleOperation,
             CreateNamespace, DerivationType, EndActivity, EntityDerive, EntityExists, RegisterKey,
-            SetAttributes, StartActivity, WasAssociatedWith, WasAttributedTo, WasGeneratedBy,
-            WasInformedBy,
+            RetractAssociation, RetractAttribute, RetractAttribution, RotateKey, SetAttributes,
+            StartActivity, WasAssociatedWith, WasAttributedTo, WasGeneratedBy, WasInformedBy,
         },
         to_json_ld::ToJson,
         ActivityId, AgentId, ChronicleIri, ChronicleTransaction, ChronicleTransactionId,
-        Contradiction, EntityId, ExternalId, ExternalIdPart, NamespaceId, ProcessorError,
-        ProvModel, Role, UuidPart, SYSTEM_ID, SYSTEM_UUID,
+        Contradiction, DomaintypeId, EntityId, ExternalId, ExternalIdPart, NamespaceId,
+        ProcessorError, ProvModel, Role, RoleConstraints, UuidPart, SYSTEM_ID, SYSTEM_UUID,
     },
 };
 
 use metrics::histogram;
-use metrics_exporter_prometheus::PrometheusBuilder;
 pub use persistence::StoreError;
-use persistence::{Store, MIGRATIONS};
+pub use persistence::{
+    bind_tenant, pending_migrations, read_only_transaction, DatabaseBackend, DatabaseConnection,
+    ReadFrom,
+};
+use persistence::{IdempotencyKeyHolder, Store, SyncLeadership, SyncedDelta, MIGRATIONS};
 use r2d2::Pool;
 use std::{
+    collections::VecDeque,
     convert::Infallible,
     marker::PhantomData,
     net::AddrParseError,
@@ -58,9 +83,12 @@ use tokio::{
 
 use tracing::{debug, error, info, info_span, instrument, trace, warn, Instrument};
 
+#[cfg(feature = "sqlite")]
+pub use persistence::sqlite_pool;
 pub use persistence::ConnectionOptions;
 use user_error::UFE;
 use uuid::Uuid;
+use webhooks::WebhookConf;
 
 #[derive(Error, Debug)]
 pub enum ApiError {
@@ -126,6 +154,76 @@ pub enum ApiError {
 
     #[error("Authentication endpoint error: {0}")]
     AuthenticationEndpoint(#[from] chronicle_graphql::AuthorizationError),
+
+    #[error("Operation enrichment: {0}")]
+    Enrichment(#[from] EnrichmentError),
+
+    #[error("Validation: {0}")]
+    Validation(#[from] ValidationError),
+
+    #[error("Identity {identity} denied {access} access to namespace {namespace}")]
+    NamespaceAccessDenied {
+        identity: String,
+        namespace: ExternalId,
+        access: &'static str,
+    },
+
+    #[error(
+        "Tenant isolation cannot be enforced while connected to the database as {0}, \
+         a superuser or role with BYPASSRLS that row-level security does not apply to"
+    )]
+    TenantIsolationBypassed(String),
+
+    #[error("Policy evaluation: {0}")]
+    OpaExecutor(#[from] OpaExecutorError),
+
+    #[error("Explanation templates: {0}")]
+    Explain(#[from] chronicle_graphql::explain::ExplainError),
+
+    #[error("Export: {0}")]
+    Export(#[from] chronicle_graphql::export::ExportError),
+
+    #[error("Search: {0}")]
+    Search(#[from] chronicle_graphql::search::SearchError),
+
+    #[error("Persisted queries: {0}")]
+    PersistedQuery(#[from] chronicle_graphql::persisted::PersistedQueryError),
+
+    #[error("Role permissions: {0}")]
+    RolePermissions(#[from] chronicle_graphql::roles::RolePermissionsError),
+
+    #[error("Rate limits: {0}")]
+    RateLimits(#[from] chronicle_graphql::rate_limits::RateLimitsError),
+
+    #[error("Protocol: {0}")]
+    Protocol(#[from] ProtocolError),
+
+    #[error("Graph mirror: {0}")]
+    GraphMirror(#[from] graph_mirror::GraphMirrorError),
+
+    #[error("Webhooks: {0}")]
+    Webhook(#[from] webhooks::WebhookError),
+
+    #[error("Event sink: {0}")]
+    EventSink(#[from] event_sink::EventSinkError),
+
+    #[error("Policy watcher: {0}")]
+    PolicyWatcher(#[from] policy_watcher::PolicyWatcherError),
+
+    #[error("Backfill: {0}")]
+    Backfill(#[from] backfill::BackfillError),
+
+    #[error("Lineage cannot be queried as of a block")]
+    LineageAsOfBlock,
+
+    #[error("Idempotency key {key} was already used for a different command")]
+    IdempotencyKeyReused { key: String },
+
+    #[error("Idempotency key {key} is held by the same command, still being submitted")]
+    IdempotencyKeyPending { key: String },
+
+    #[error("JSON: {0}")]
+    Json(#[from] serde_json::Error),
 }
 
 /// Ugly but we need this until ! is stable, see <https://github.com/rust-lang/rust/issues/64715>
@@ -135,6 +233,27 @@ impl From<Infallible> for ApiError {
     }
 }
 
+impl ApiError {
+    /// Whether the command failed for a reason that may clear up, so that
+    /// submitting it again later can succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Signing(SecretError::TemporarilyUnavailable(_))
+        )
+    }
+
+    /// Whether the command failed because the ledger could not be reached,
+    /// so that it can be held and submitted once it can
+    pub fn is_ledger_unreachable(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Ledger(SubmissionError::Communication { .. })
+                | ApiError::SawtoothCommunicationError(_)
+        )
+    }
+}
+
 impl UFE for ApiError {}
 
 type LedgerSendWithReply = (
@@ -142,7 +261,50 @@ type LedgerSendWithReply = (
     Sender<Result<ChronicleTransactionId, SubmissionError>>,
 );
 
-type ApiSendWithReply = ((ApiCommand, AuthId), Sender<Result<ApiResponse, ApiError>>);
+/// A command, the id of its outbox entry if it was queued durably, and where
+/// to reply
+type ApiSendWithReply = (
+    (ApiCommand, AuthId),
+    Option<String>,
+    Sender<Result<ApiResponse, ApiError>>,
+);
+
+/// Commands held in the outbox while the ledger is unreachable, by the id of
+/// their entry, in the order they are to be submitted
+type HeldCommands = Arc<tokio::sync::Mutex<VecDeque<(String, (ApiCommand, AuthId))>>>;
+
+/// The domain type of the entities in the system namespace that record
+/// checkpoints of derived state
+const CHECKPOINT_DOMAINTYPE: &str = "ChronicleCheckpoint";
+
+/// The domain type of the entities in the system namespace that register the
+/// UUID of a namespace's external id, so that every node uses the same one
+const NAMESPACE_REGISTRATION_DOMAINTYPE: &str = "ChronicleNamespaceRegistration";
+
+/// How often superseded attribute values older than the configured retention
+/// are pruned from attribute_history
+const ATTRIBUTE_HISTORY_COMPACTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often a replica confirms it still leads the sync loop, or contends for
+/// leadership if it does not
+const SYNC_LEADERSHIP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a replica that does not lead waits for the leader to apply a
+/// commit before notifying subscribers of it regardless
+const FOLLOWER_NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most notifications a replica that does not lead holds while waiting for
+/// the leader, before it stops reading ledger updates until the leader catches
+/// up
+const FOLLOWER_NOTIFICATION_BACKLOG: usize = 1000;
+
+/// The most commits the sync loop applies to the store in one transaction if
+/// not configured
+const DEFAULT_SYNC_BATCH_SIZE: usize = 100;
+
+/// How often commands held while the ledger was unreachable are submitted
+/// again, in store and forward mode
+const STORE_AND_FORWARD_INTERVAL: Duration = Duration::from_secs(5);
 
 pub trait UuidGen {
     fn uuid() -> Uuid {
@@ -150,6 +312,152 @@ pub trait UuidGen {
     }
 }
 
+/// The UUIDv5 of a namespace named `external_id` in a deployment seeded with
+/// `seed`, so that nodes sharing a seed create the same namespace for a name
+pub fn namespace_uuid(seed: &Uuid, external_id: &ExternalId) -> Uuid {
+    Uuid::new_v5(seed, external_id.as_str().as_bytes())
+}
+
+/// The operations registering `namespace` in the system namespace `system`.
+/// A registration cannot be changed once committed, so the ledger rejects a
+/// transaction registering an external id already registered to another UUID
+fn namespace_registration(
+    system: &NamespaceId,
+    namespace: &NamespaceId,
+) -> Vec<ChronicleOperation> {
+    let external_id = ExternalId::from(format!("namespace-{}", namespace.external_id_part()));
+    let attribute =
+        |name: &str, value: String| (name.to_owned(), Attribute::new(name, value.into()));
+
+    vec![
+        ChronicleOperation::EntityExists(EntityExists {
+            namespace: system.clone(),
+            external_id: external_id.clone(),
+        }),
+        ChronicleOperation::SetAttributes(SetAttributes::Entity {
+            namespace: system.clone(),
+            id: EntityId::from_external_id(&external_id),
+            attributes: Attributes {
+                typ: Some(DomaintypeId::from_external_id(
+                    NAMESPACE_REGISTRATION_DOMAINTYPE,
+                )),
+                attributes: [
+                    attribute("namespace", namespace.external_id_part().to_string()),
+                    attribute("namespaceUuid", namespace.uuid_part().to_string()),
+                ]
+                .into_iter()
+                .collect(),
+            },
+        }),
+    ]
+}
+
+/// The key a delta records for the chronicle agent in the system namespace
+fn chronicle_key(delta: &ProvModel) -> Option<&str> {
+    let chronicle = AgentId::from_external_id(CHRONICLE_PK);
+
+    delta
+        .has_identity
+        .iter()
+        .find(|((namespace, agent), _)| {
+            agent == &chronicle && namespace.external_id_part().as_str() == SYSTEM_ID
+        })
+        .and_then(|(_, identity)| delta.identities.get(identity))
+        .map(|identity| identity.public_key.as_str())
+}
+
+/// The namespaces registered by a committed delta
+fn namespace_registrations(delta: &ProvModel) -> Vec<NamespaceId> {
+    let registration_type = DomaintypeId::from_external_id(NAMESPACE_REGISTRATION_DOMAINTYPE);
+
+    delta
+        .entities
+        .values()
+        .filter(|entity| {
+            entity.namespaceid.external_id_part().as_str() == SYSTEM_ID
+                && entity.domaintypeid.as_ref() == Some(&registration_type)
+        })
+        .filter_map(|entity| {
+            let attribute = |name: &str| {
+                entity
+                    .attributes
+                    .get(name)
+                    .and_then(|attribute| attribute.value.as_str())
+                    .map(|value| value.to_owned())
+            };
+
+            let uuid = Uuid::parse_str(&attribute("namespaceUuid")?).ok()?;
+            Some(NamespaceId::from_external_id(attribute("namespace")?, uuid))
+        })
+        .collect()
+}
+
+/// Merge the compact JSON-LD deltas recorded in the history of `namespace`,
+/// in order, into its provenance
+async fn replay_history(
+    namespace: &NamespaceId,
+    deltas: Vec<String>,
+) -> Result<ProvModel, ProcessorError> {
+    let mut model = ProvModel::default();
+    model.namespace_context(namespace);
+    for delta in deltas {
+        let mut applied = ProvModel::default();
+        applied.apply_json_ld_str(&delta).await?;
+        model.merge_delta(applied);
+    }
+
+    Ok(model)
+}
+
+/// Notifies subscribers of the ledger updates a replica that does not lead the
+/// sync loop follows. A single task sends them in the order they were
+/// committed, each once the leader has applied it to the store, or once it has
+/// waited [FOLLOWER_NOTIFICATION_TIMEOUT] from being followed.
+#[derive(Clone)]
+struct FollowerNotifier {
+    queue: Sender<(SubmissionStage, Instant)>,
+}
+
+impl FollowerNotifier {
+    fn spawn(store: Store, submit_tx: tokio::sync::broadcast::Sender<SubmissionStage>) -> Self {
+        let (queue, mut followed) =
+            mpsc::channel::<(SubmissionStage, Instant)>(FOLLOWER_NOTIFICATION_BACKLOG);
+
+        tokio::task::spawn(async move {
+            while let Some((stage, followed_at)) = followed.recv().await {
+                if let SubmissionStage::Committed(commit, _) = &stage {
+                    let deadline = followed_at + FOLLOWER_NOTIFICATION_TIMEOUT;
+                    loop {
+                        let store = store.clone();
+                        let tx_id = commit.tx_id.clone();
+                        let synced = tokio::task::spawn_blocking(move || store.is_synced(&tx_id))
+                            .await
+                            .map(|synced| synced.unwrap_or(false))
+                            .unwrap_or(false);
+                        if synced {
+                            break;
+                        }
+                        if Instant::now() > deadline {
+                            warn!(tx_id = %commit.tx_id, "Commit not yet applied by sync leader");
+                            break;
+                        }
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                }
+
+                submit_tx.send(stage).ok();
+            }
+        });
+
+        Self { queue }
+    }
+
+    /// Queue a notification behind those already followed
+    async fn notify(&self, stage: SubmissionStage) {
+        self.queue.send((stage, Instant::now())).await.ok();
+    }
+}
+
 #[derive(Clone)]
 pub struct Api<
     U: UuidGen + Send + Sync + Clone,
@@ -163,19 +471,118 @@ pub struct Api<
     submit_tx: tokio::sync::broadcast::Sender<SubmissionStage>,
     signing: ChronicleSigning,
     ledger_writer: Arc<BlockingLedgerWriter<W>>,
+    ledger_reader: W,
     store: persistence::Store,
     uuid_source: PhantomData<U>,
+    namespace_seed: Option<Uuid>,
+    register_namespaces: bool,
     policy_name: Option<String>,
+    namespace_policy: Option<ExecutorContext>,
+    enrichment: OperationEnrichment,
+    validation: AttributeValidation,
+    id_strategies: IdStrategies,
+    role_constraints: RoleConstraints,
+    webhooks: Option<WebhookConf>,
+    store_and_forward: bool,
+    held: HeldCommands,
+    health: Health,
+    pending_rotation:
+        Arc<tokio::sync::Mutex<Option<(Option<ChronicleTransactionId>, PendingKeyRotation)>>>,
+    follower_notifier: FollowerNotifier,
 }
 
 #[derive(Debug, Clone)]
 /// A clonable api handle
 pub struct ApiDispatch {
     tx: Sender<ApiSendWithReply>,
+    store: persistence::Store,
+    store_and_forward: bool,
+    held: HeldCommands,
+    namespace_policy: Option<ExecutorContext>,
     pub notify_commit: tokio::sync::broadcast::Sender<SubmissionStage>,
+    pub health: Health,
+}
+
+/// Evaluate the namespace policy `opa` for `identity`'s `access` to
+/// `namespace`. The policy sees a `NamespaceAccess` operation with the
+/// namespace and either `read` or `write` access as its state
+async fn evaluate_namespace_access(
+    opa: &ExecutorContext,
+    identity: &AuthId,
+    namespace: ExternalId,
+    access: &'static str,
+) -> Result<(), ApiError> {
+    let data = OpaData::operation(
+        identity,
+        &serde_json::json!("NamespaceAccess"),
+        &serde_json::json!({
+            "namespace": namespace,
+            "access": access,
+        }),
+    );
+
+    match opa.evaluate(identity, &data).await {
+        Ok(()) => Ok(()),
+        Err(OpaExecutorError::AccessDenied) => Err(ApiError::NamespaceAccessDenied {
+            identity: identity.to_string(),
+            namespace,
+            access,
+        }),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Commands that submit provenance, which are queued in the outbox until they
+/// are handled. Key rotations, checkpoints and depth charges are not, as they
+/// are not repeated safely or are repeated anyway.
+fn is_durable(command: &ApiCommand) -> bool {
+    match command {
+        ApiCommand::Idempotent(IdempotentCommand { command, .. }) => is_durable(command),
+        command => matches!(
+            command,
+            ApiCommand::NameSpace(_)
+                | ApiCommand::Agent(_)
+                | ApiCommand::Activity(_)
+                | ApiCommand::Entity(_)
+                | ApiCommand::Import(_)
+        ),
+    }
 }
 
 impl ApiDispatch {
+    /// True until the api task has stopped accepting commands
+    pub fn is_running(&self) -> bool {
+        !self.tx.is_closed()
+    }
+
+    /// Evaluate the namespace policy, if configured, for `identity` reading
+    /// `namespace` other than through a command, as GraphQL queries do
+    #[instrument(skip(self))]
+    pub async fn check_read_access(
+        &self,
+        identity: &AuthId,
+        namespace: ExternalId,
+    ) -> Result<(), ApiError> {
+        match &self.namespace_policy {
+            Some(opa) => evaluate_namespace_access(opa, identity, namespace, "read").await,
+            None => Ok(()),
+        }
+    }
+
+    /// Evaluate the namespace policy, if configured, for `identity` writing to
+    /// `namespace`, ahead of a command that may be held before it is handled
+    #[instrument(skip(self))]
+    pub async fn check_write_access(
+        &self,
+        identity: &AuthId,
+        namespace: ExternalId,
+    ) -> Result<(), ApiError> {
+        match &self.namespace_policy {
+            Some(opa) => evaluate_namespace_access(opa, identity, namespace, "write").await,
+            None => Ok(()),
+        }
+    }
+
     #[instrument]
     pub async fn dispatch(
         &self,
@@ -184,9 +591,40 @@ impl ApiDispatch {
     ) -> Result<ApiResponse, ApiError> {
         let (reply_tx, mut reply_rx) = mpsc::channel(1);
         trace!(?command, "Dispatch command to api");
+
+        // Queue commands that submit provenance before they are handled, so
+        // they survive the api stopping
+        let queued = if is_durable(&command) {
+            let store = self.store.clone();
+            let (command, identity) = (command.clone(), identity.clone());
+            Some(
+                tokio::task::spawn_blocking(move || store.enqueue_command(&command, &identity))
+                    .await??,
+            )
+        } else {
+            None
+        };
+
+        // While the ledger is unreachable, or commands held earlier wait for
+        // it, commands are held behind them rather than handled, so that they
+        // reach the ledger in the order they were made
+        if let Some(id) = queued.as_ref().filter(|_| self.store_and_forward) {
+            let mut held = self.held.lock().await;
+            if !held.is_empty() || !self.health.ledger_connected() {
+                held.push_back((id.clone(), (command, identity)));
+                return Ok(ApiResponse::Queued { id: id.clone() });
+            }
+        }
+
+        // The outbox is read from the store, so it can be inspected while the
+        // ledger is unreachable
+        if let ApiCommand::Outbox(OutboxCommand::List) = command {
+            return outbox(&self.store).await;
+        }
+
         self.tx
             .clone()
-            .send(((command, identity), reply_tx))
+            .send(((command, identity), queued, reply_tx))
             .await?;
 
         let reply = reply_rx.recv().await;
@@ -253,24 +691,54 @@ impl ApiDispatch {
     }
 }
 
-fn install_prometheus_metrics_exporter() {
-    let metrics_endpoint = "127.0.0.1:9000";
-    let metrics_listen_socket = match metrics_endpoint.parse::<std::net::SocketAddrV4>() {
-        Ok(addr) => addr,
-        Err(e) => {
-            error!("Unable to parse metrics listen socket address: {e:?}");
-            return;
-        }
-    };
+/// The commands in the outbox of `store`
+async fn outbox(store: &persistence::Store) -> Result<ApiResponse, ApiError> {
+    let store = store.clone();
+    let commands = tokio::task::spawn_blocking(move || store.queued_commands()).await??;
 
-    if let Err(e) = PrometheusBuilder::new()
-        .with_http_listener(metrics_listen_socket)
-        .install()
-    {
-        error!("Prometheus exporter installation for liveness check metrics failed: {e:?}");
-    } else {
-        debug!("Liveness check metrics Prometheus exporter installed with endpoint on {metrics_endpoint}/metrics");
-    }
+    Ok(ApiResponse::Outbox {
+        commands: commands
+            .into_iter()
+            .map(|(id, command, identity, queued_at)| QueuedCommand {
+                id,
+                kind: prometheus::command_label(&command).to_owned(),
+                identity: identity.to_string(),
+                queued_at,
+            })
+            .collect(),
+    })
+}
+
+/// How an [Api] is configured, beyond the store, ledger and keys it uses.
+/// Every option is off or empty by default.
+#[derive(Debug, Default)]
+pub struct ApiConfig {
+    /// Namespaces bound to configured UUIDs
+    pub namespace_bindings: Vec<NamespaceId>,
+    /// The seed new namespaces' UUIDs are derived from, rather than generated
+    pub namespace_seed: Option<Uuid>,
+    /// The name of the on-chain policy to check requests against
+    pub policy_name: Option<String>,
+    /// The policy checking access to each namespace a command touches
+    pub namespace_policy: Option<ExecutorContext>,
+    /// Seconds between depth charges checking the ledger is live
+    pub liveness_check_interval: Option<u64>,
+    /// Seconds between checkpoints of namespace digests
+    pub checkpoint_interval: Option<u64>,
+    /// Seconds superseded attribute values are kept for
+    pub attribute_history_retention: Option<u64>,
+    /// The most ledger commits applied in one transaction when catching up
+    pub sync_batch_size: Option<usize>,
+    pub enrichment: OperationEnrichment,
+    pub validation: AttributeValidation,
+    pub id_strategies: IdStrategies,
+    /// Hold commands in the outbox while the ledger is unreachable
+    pub store_and_forward: bool,
+    /// Register namespace bindings on the ledger for peers to adopt
+    pub register_namespaces: bool,
+    pub role_constraints: RoleConstraints,
+    /// The webhooks to queue deliveries of commits for as they are synced
+    pub webhooks: Option<WebhookConf>,
 }
 
 impl<U, LEDGER> Api<U, LEDGER>
@@ -285,42 +753,88 @@ where
 {
     #[instrument(skip(ledger))]
     pub async fn new(
-        pool: Pool<ConnectionManager<PgConnection>>,
+        pool: Pool<ConnectionManager<DatabaseConnection>>,
         ledger: LEDGER,
         uuidgen: U,
         signing: ChronicleSigning,
-        namespace_bindings: Vec<NamespaceId>,
-        policy_name: Option<String>,
-        liveness_check_interval: Option<u64>,
+        config: ApiConfig,
     ) -> Result<ApiDispatch, ApiError> {
+        let ApiConfig {
+            namespace_bindings,
+            namespace_seed,
+            policy_name,
+            namespace_policy,
+            liveness_check_interval,
+            checkpoint_interval,
+            attribute_history_retention,
+            sync_batch_size,
+            enrichment,
+            validation,
+            id_strategies,
+            store_and_forward,
+            register_namespaces,
+            role_constraints,
+            webhooks,
+        } = config;
+
         let (commit_tx, mut commit_rx) = mpsc::channel::<ApiSendWithReply>(10);
 
         let (commit_notify_tx, _) = tokio::sync::broadcast::channel(20);
+        let health = Health::default();
+        let store = Store::new(pool.clone())?;
+        let held = HeldCommands::default();
+
         let dispatch = ApiDispatch {
             tx: commit_tx.clone(),
+            store: store.clone(),
+            store_and_forward,
+            held: held.clone(),
+            namespace_policy: namespace_policy.clone(),
             notify_commit: commit_notify_tx.clone(),
+            health: health.clone(),
         };
 
-        let store = Store::new(pool.clone())?;
+        prometheus::sample_pool_utilization(pool.clone());
 
         pool.get()?
             .build_transaction()
             .run(|connection| connection.run_pending_migrations(MIGRATIONS).map(|_| ()))
             .map_err(StoreError::DbMigration)?;
 
+        let mut outbox = store.queued_commands()?;
+
+        // In store and forward mode, what was left in the outbox is held to
+        // be submitted in order once the ledger can be reached
+        if store_and_forward {
+            let mut held = held.lock().await;
+            for (id, command, identity, _) in outbox.drain(..) {
+                held.push_back((id, (command, identity)));
+            }
+        }
+
         let system_namespace_uuid = (SYSTEM_ID, Uuid::try_from(SYSTEM_UUID).unwrap());
 
         // Append namespace bindings and system namespace
         store.namespace_binding(system_namespace_uuid.0, system_namespace_uuid.1)?;
         for ns in namespace_bindings {
-            store.namespace_binding(ns.external_id_part().as_str(), ns.uuid_part().to_owned())?
+            // Bindings registered on the ledger take precedence
+            match store.registered_namespace(ns.external_id_part())? {
+                Some(registered) if registered != *ns.uuid_part() => warn!(
+                    namespace = %ns.external_id_part(),
+                    configured = %ns.uuid_part(),
+                    %registered,
+                    "Ignoring namespace binding that contradicts the ledger's registry"
+                ),
+                _ => store
+                    .namespace_binding(ns.external_id_part().as_str(), ns.uuid_part().to_owned())?,
+            }
         }
 
         let reuse_reader = ledger.clone();
 
         let last_seen_block = store.get_last_block_id();
 
-        let start_from_block = if let Ok(Some(start_from_block)) = last_seen_block {
+        let mut start_from_block = if let Ok(Some(start_from_block)) = last_seen_block {
             FromBlock::BlockId(start_from_block)
         } else {
             FromBlock::First //Full catch up, as we have no last seen block
@@ -328,17 +842,98 @@ where
 
         debug!(start_from_block = ?start_from_block, "Starting from block");
 
+        let sync_batch_size = sync_batch_size.unwrap_or(DEFAULT_SYNC_BATCH_SIZE).max(1);
+
+        // Replicas sharing a store elect one of their number to apply ledger
+        // updates to it. The others follow the ledger only to notify their
+        // own subscribers, and take over if the leader goes away.
+        let mut leadership = SyncLeadership::new(pool.clone());
+        let (leader_tx, leader) = tokio::sync::watch::channel(leadership.try_lead());
+        let mut leadership_changes =
+            tokio_stream::wrappers::WatchStream::from_changes(leader.clone()).fuse();
+
+        tokio::task::spawn(async move {
+            loop {
+                tokio::time::sleep(SYNC_LEADERSHIP_INTERVAL).await;
+
+                match tokio::task::spawn_blocking(move || {
+                    let leading = leadership.try_lead();
+                    (leadership, leading)
+                })
+                .await
+                {
+                    Ok((contender, leading)) => {
+                        leadership = contender;
+                        leader_tx.send_if_modified(|leader| {
+                            std::mem::replace(leader, leading) != leading
+                        });
+                    }
+                    Err(e) => {
+                        error!(?e, "Sync leadership task");
+                        break;
+                    }
+                }
+            }
+        });
+
+        if let Some(retention) = attribute_history_retention {
+            debug!(retention, "Starting attribute history compaction task");
+
+            let compaction_store = store.clone();
+
+            tokio::task::spawn(async move {
+                loop {
+                    tokio::time::sleep(ATTRIBUTE_HISTORY_COMPACTION_INTERVAL).await;
+
+                    let store = compaction_store.clone();
+                    let older_than = Utc::now() - chrono::Duration::seconds(retention as i64);
+
+                    match tokio::task::spawn_blocking(move || {
+                        store.compact_attribute_history(older_than)
+                    })
+                    .await
+                    {
+                        Ok(Ok(pruned)) => {
+                            debug!(pruned, %older_than, "Compacted attribute history")
+                        }
+                        Ok(Err(e)) => error!(?e, "Attribute history compaction"),
+                        Err(e) => error!(?e, "Attribute history compaction task"),
+                    }
+                }
+            });
+        }
+
+        let outbox_tx = commit_tx.clone();
+
         tokio::task::spawn(async move {
             let mut api = Api::<U, LEDGER> {
                 _reply_tx: commit_tx.clone(),
                 submit_tx: commit_notify_tx.clone(),
                 signing,
                 ledger_writer: Arc::new(BlockingLedgerWriter::new(ledger)),
+                ledger_reader: reuse_reader.clone(),
                 store: store.clone(),
                 uuid_source: PhantomData,
+                namespace_seed,
+                register_namespaces,
                 policy_name,
+                namespace_policy,
+                enrichment,
+                validation,
+                id_strategies,
+                role_constraints,
+                webhooks,
+                store_and_forward,
+                held,
+                health: health.clone(),
+                pending_rotation: Arc::new(tokio::sync::Mutex::new(None)),
+                follower_notifier: FollowerNotifier::spawn(store.clone(), commit_notify_tx.clone()),
             };
 
+            api.recover_key_rotation().await;
+
+            let mut forward_interval = tokio::time::interval(STORE_AND_FORWARD_INTERVAL);
+
             loop {
                 let state_updates = reuse_reader.clone();
 
@@ -348,55 +943,93 @@ where
 
                 if let Err(e) = state_updates {
                     error!(subscribe_to_events = ?e);
+                    health.set_ledger_connected(false);
                     tokio::time::sleep(Duration::from_secs(2)).await;
                     continue;
                 }
 
-                let mut state_updates = state_updates.unwrap();
+                // Updates already waiting on the stream, as when catching up
+                // after a restart, are applied in batches
+                let mut state_updates = state_updates.unwrap().ready_chunks(sync_batch_size);
+                health.set_ledger_connected(true);
 
                 loop {
                     select! {
-                            state = state_updates.next().fuse() =>{
+                            updates = state_updates.next().fuse() =>{
 
-                                match state {
-                                  None => {
-                                    debug!("Ledger reader stream ended");
+                                // Resubscribe from the last block processed, which
+                                // the ledger client may serve from another validator
+                                if updates.is_none() {
+                                    debug!(start_from_block = ?start_from_block, "Ledger reader stream ended");
+                                    health.set_ledger_connected(false);
                                     break;
-                                  }
+                                }
+
+                                let mut batch = vec![];
+                                for update in updates.into_iter().flatten() {
+                                  match update {
                                   // Ledger contradicted or error, so nothing to
                                   // apply, but forward notification
-                                  Some((ChronicleOperationEvent(Err(e), id),tx,_block_id,_position, _span)) => {
-                                    commit_notify_tx.send(SubmissionStage::not_committed(
+                                  (ChronicleOperationEvent(Err(e), id, _),tx,block_id,_position, _span) => {
+                                    // Apply the commits before it first, so that
+                                    // notifications keep to ledger order
+                                    api.sync(std::mem::take(&mut batch)).await;
+
+                                    start_from_block = FromBlock::BlockId(block_id);
+                                    health.synced(&ChronicleTransactionId::from(tx.as_str()));
+                                    api.resolve_key_rotation(&ChronicleTransactionId::from(tx.as_str()), None).await;
+                                    let not_committed = SubmissionStage::not_committed(
                                       ChronicleTransactionId::from(tx.as_str()),e.clone(), id
-                                    )).ok();
+                                    );
+                                    // A follower queues it behind the commits
+                                    // it is waiting for the leader to apply
+                                    if *leader.borrow() {
+                                        commit_notify_tx.send(not_committed).ok();
+                                    } else {
+                                        api.follower_notifier.notify(not_committed).await;
+                                    }
                                   },
                                   // Successfully committed to ledger, so apply
                                   // to db and broadcast notification to
                                   // subscription subscribers
-                                  Some((ChronicleOperationEvent(Ok(ref commit), id,),tx,block_id,_position,_span )) => {
+                                  (ChronicleOperationEvent(Ok(commit), id, operations),tx,block_id,_position,_span ) => {
+
+                                        start_from_block = FromBlock::BlockId(block_id);
+                                        health.synced(&ChronicleTransactionId::from(tx.as_str()));
 
                                         debug!(committed = ?tx);
                                         debug!(delta = %serde_json::to_string_pretty(&commit.to_json().compact().await.unwrap()).unwrap());
 
-                                        api.sync( commit.clone().into(), &block_id,ChronicleTransactionId::from(tx.as_str()))
-                                            .instrument(info_span!("Incoming confirmation", offset = ?block_id, tx_id = %tx))
-                                            .await
-                                            .map_err(|e| {
-                                                error!(?e, "Api sync to confirmed commit");
-                                            }).map(|_| commit_notify_tx.send(SubmissionStage::committed(Commit::new(
-                                               ChronicleTransactionId::from(tx.as_str()),block_id, Box::new(commit.clone())
-                                            ), id )).ok())
-                                            .ok();
+                                        api.resolve_key_rotation(&ChronicleTransactionId::from(tx.as_str()), Some(&commit)).await;
+
+                                        let commit = Commit::new(
+                                           ChronicleTransactionId::from(tx.as_str()),block_id, Box::new(commit), operations
+                                        );
+
+                                        if *leader.borrow() {
+                                            batch.push((commit, id));
+                                        } else {
+                                            api.verify_checkpoints(&commit.delta).await;
+                                            api.follower_notifier.notify(SubmissionStage::committed(commit, id)).await;
+                                        }
                                   },
+                                  }
                                 }
+
+                                api.sync(batch).await;
                             },
                             cmd = commit_rx.recv().fuse() => {
-                                if let Some((command, reply)) = cmd {
+                                if let Some((command, queued, reply)) = cmd {
+
+                                let started = Instant::now();
+                                let command_kind = prometheus::command_label(&command.0);
 
                                 let result = api
-                                    .dispatch(command)
+                                    .handle(command, queued)
                                     .await;
 
+                                prometheus::record_command(command_kind, started.elapsed(), result.is_ok());
+
                                 reply
                                     .send(result)
                                     .await
@@ -405,6 +1038,22 @@ where
                                     })
                                     .ok();
                                 }
+                        },
+                        _ = forward_interval.tick().fuse() => {
+                            if api.store_and_forward {
+                                api.forward_held().await;
+                            }
+                        },
+                        leading = leadership_changes.next() => {
+                            // Resume from the store's position, so commits
+                            // the previous leader did not apply are replayed
+                            if let Some(true) = leading {
+                                if let Ok(Some(block)) = api.store.get_last_block_id() {
+                                    start_from_block = FromBlock::BlockId(block);
+                                }
+                                debug!(start_from_block = ?start_from_block, "Leading sync, resubscribing");
+                                break;
+                            }
                         }
                         complete => break
                     }
@@ -412,14 +1061,65 @@ where
             }
         });
 
+        if !outbox.is_empty() {
+            info!(
+                commands = outbox.len(),
+                "Resubmitting commands queued before the api stopped"
+            );
+
+            tokio::task::spawn(async move {
+                for (id, command, identity, _) in outbox {
+                    let (reply_tx, mut reply_rx) = mpsc::channel(1);
+                    if outbox_tx
+                        .send(((command, identity), Some(id), reply_tx))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+
+                    match reply_rx.recv().await {
+                        Some(Ok(response)) => debug!(?response, "Resubmitted queued command"),
+                        Some(Err(e)) => warn!(?e, "Queued command failed on resubmission"),
+                        None => return,
+                    }
+                }
+            });
+        }
+
+        if let Some(interval) = checkpoint_interval {
+            debug!(interval, "Starting checkpoint task");
+
+            let checkpoint_api = dispatch.clone();
+
+            tokio::task::spawn(async move {
+                loop {
+                    tokio::time::sleep(Duration::from_secs(interval)).await;
+
+                    if let Err(e) = checkpoint_api
+                        .dispatch(
+                            ApiCommand::Checkpoint(CheckpointCommand),
+                            AuthId::chronicle(),
+                        )
+                        .await
+                    {
+                        error!(?e, "Submitting checkpoint");
+                    }
+                }
+            });
+        }
+
         if let Some(interval) = liveness_check_interval {
             debug!("Starting liveness depth charge task");
 
             let depth_charge_api = dispatch.clone();
 
             tokio::task::spawn(async move {
-                // Configure and install Prometheus exporter
-                install_prometheus_metrics_exporter();
+                // Serve liveness check metrics on the default address, unless
+                // an exporter has already been installed
+                if !prometheus::exporter_installed() {
+                    prometheus::install_prometheus_metrics_exporter(([127, 0, 0, 1], 9000).into());
+                }
 
                 loop {
                     tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
@@ -502,22 +1202,32 @@ where
     /// Notify after a successful submission, for now this makes little
     /// difference, but with the future introduction of a submission queue,
     /// submission notifications will be decoupled from api invocation.
-    /// This is a measure to keep the api interface stable once this is introduced
+    /// This is a measure to keep the api interface stable once this is introduced.
+    /// Returns the transaction id with the digest its receipt will carry
     fn submit_blocking(
         &mut self,
         tx: &ChronicleTransaction,
-    ) -> Result<ChronicleTransactionId, ApiError> {
-        let res = self.ledger_writer.submit(&ChronicleSubmitTransaction {
-            tx: tx.clone(),
-            signer: self.signing.clone(),
-            policy_name: self.policy_name.clone(),
-        });
+    ) -> Result<(ChronicleTransactionId, String), ApiError> {
+        let started = Instant::now();
+
+        let submission = ChronicleSubmitTransaction::new(
+            tx.clone(),
+            self.signing.clone(),
+            self.policy_name.clone(),
+        );
+        let res = self.ledger_writer.submit(&submission);
+
+        prometheus::record_submission(started.elapsed(), res.is_ok());
 
         match res {
             Ok(tx_id) => {
                 let tx_id = ChronicleTransactionId::from(tx_id.as_str());
+                // The digest of the body the ledger writer compacted and sent
+                let operation_digest = submission.payload().map(payload_digest).unwrap_or_default();
+                self.record_receipt(&tx_id, &operation_digest);
+                self.health.submitted(tx_id.clone());
                 self.submit_tx.send(SubmissionStage::submitted(&tx_id)).ok();
-                Ok(tx_id)
+                Ok((tx_id, operation_digest))
             }
             Err((Some(tx_id), e)) => {
                 // We need the cloneable SubmissionError wrapper here
@@ -534,18 +1244,48 @@ where
         }
     }
 
+    /// Append a committed transaction to the audit log. It is already on the
+    /// ledger, so a failure to record it is logged, not returned
+    fn audit(
+        &self,
+        tx_id: &ChronicleTransactionId,
+        identity: &SignedIdentity,
+        operations: &[ChronicleOperation],
+    ) {
+        let identity = AuthId::try_from(identity)
+            .map(|identity| identity.to_string())
+            .unwrap_or_default();
+
+        if let Err(e) = self.store.record_audit(tx_id, &identity, operations) {
+            error!(?e, %tx_id, "Recording transaction in the audit log");
+        }
+    }
+
+    /// Record the digest of a submitted transaction's operations, to sign a
+    /// receipt for once it commits. Like the audit log, a failure to record it
+    /// is logged, not returned
+    fn record_receipt(&self, tx_id: &ChronicleTransactionId, operation_digest: &str) {
+        if let Err(e) = self.store.record_receipt(tx_id, operation_digest) {
+            error!(?e, %tx_id, "Recording transaction receipt");
+        }
+    }
+
     /// Generate and submit the signed identity to send to the Transaction Processor along with the transactions to be applied
+    ///
+    /// Operations are passed through the configured [OperationEnrichment] first
     fn submit(
         &mut self,
         id: impl Into<ChronicleIri>,
         identity: AuthId,
         to_apply: Vec<ChronicleOperation>,
     ) -> Result<ApiResponse, ApiError> {
+        let to_apply = self.enrichment.enrich(&identity, to_apply)?;
         let identity = identity.signed_identity(&self.signing)?;
         let model = ProvModel::from_tx(&to_apply)?;
-        let tx_id = self.submit_blocking(&ChronicleTransaction::new(to_apply, identity))?;
+        let (tx_id, operation_digest) =
+            self.submit_blocking(&ChronicleTransaction::new(to_apply, identity))?;
 
-        Ok(ApiResponse::submission(id, model, tx_id))
+        Ok(ApiResponse::submission(id, model, tx_id, operation_digest))
     }
 
     /// Checks if ChronicleOperations resulting from Chronicle API calls will result in any changes in state
@@ -556,7 +1296,7 @@ where
     #[instrument(skip(self, connection))]
     fn check_for_effects(
         &mut self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         to_apply: &Vec<ChronicleOperation>,
     ) -> Result<Option<Vec<ChronicleOperation>>, ApiError> {
         let mut model = ProvModel::default();
@@ -820,32 +1560,126 @@ where
                         namespace.external_id_part(),
                     )?
                 }
-            };
-            let state = applied_model.clone();
-            applied_model.apply(op)?;
-            if state != applied_model {
-                transactions.push(op.clone());
-            }
+                ChronicleOperation::RetractAssociation(RetractAssociation {
+                    namespace,
+                    activity_id,
+                    agent_id,
+                    ..
+                }) => {
+                    model.namespace_context(namespace);
+                    let model = self.store.apply_prov_model_for_activity_id(
+                        connection,
+                        model,
+                        activity_id,
+                        namespace.external_id_part(),
+                    )?;
 
-            model = applied_model;
-        }
+                    self.store.apply_prov_model_for_agent_id(
+                        connection,
+                        model,
+                        agent_id,
+                        namespace.external_id_part(),
+                    )?
+                }
+                ChronicleOperation::RetractAttribution(RetractAttribution {
+                    namespace,
+                    entity_id,
+                    agent_id,
+                    ..
+                }) => {
+                    model.namespace_context(namespace);
+                    let model = self.store.apply_prov_model_for_entity_id(
+                        connection,
+                        model,
+                        entity_id,
+                        namespace.external_id_part(),
+                    )?;
 
-        if transactions.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(transactions))
-        }
-    }
+                    self.store.apply_prov_model_for_agent_id(
+                        connection,
+                        model,
+                        agent_id,
+                        namespace.external_id_part(),
+                    )?
+                }
+                ChronicleOperation::RetractAttribute(ref o) => match o {
+                    RetractAttribute::Activity { namespace, id, .. } => {
+                        model.namespace_context(namespace);
+                        self.store.apply_prov_model_for_activity_id(
+                            connection,
+                            model,
+                            id,
+                            namespace.external_id_part(),
+                        )?
+                    }
+                    RetractAttribute::Agent { namespace, id, .. } => {
+                        model.namespace_context(namespace);
+                        self.store.apply_prov_model_for_agent_id(
+                            connection,
+                            model,
+                            id,
+                            namespace.external_id_part(),
+                        )?
+                    }
+                    RetractAttribute::Entity { namespace, id, .. } => {
+                        model.namespace_context(namespace);
+                        self.store.apply_prov_model_for_entity_id(
+                            connection,
+                            model,
+                            id,
+                            namespace.external_id_part(),
+                        )?
+                    }
+                },
+                ChronicleOperation::RotateKey(RotateKey { namespace, id, .. }) => {
+                    model.namespace_context(namespace);
+                    self.store.apply_prov_model_for_agent_id(
+                        connection,
+                        model,
+                        id,
+                        namespace.external_id_part(),
+                    )?
+                }
+            };
+            self.role_constraints.check(&applied_model, op)?;
+            let state = applied_model.clone();
+            applied_model.apply(op)?;
+            if state != applied_model {
+                transactions.push(op.clone());
+            }
+
+            model = applied_model;
+        }
+
+        if transactions.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(transactions))
+        }
+    }
+
+    /// Checks the roles given by operations in a namespace this call creates,
+    /// where every agent they can give a role to is created by the call too
+    fn check_role_constraints(&self, to_apply: &[ChronicleOperation]) -> Result<(), ApiError> {
+        let mut model = ProvModel::default();
+        for op in to_apply {
+            self.role_constraints.check(&model, op)?;
+            model.apply(op)?;
+        }
+
+        Ok(())
+    }
 
     fn apply_effects_and_submit(
         &mut self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         id: impl Into<ChronicleIri>,
         identity: AuthId,
         to_apply: Vec<ChronicleOperation>,
         applying_new_namespace: bool,
     ) -> Result<ApiResponse, ApiError> {
         if applying_new_namespace {
+            self.check_role_constraints(&to_apply)?;
             self.submit(id, identity, to_apply)
         } else if let Some(to_apply) = self.check_for_effects(connection, &to_apply)? {
             self.submit(id, identity, to_apply)
@@ -860,13 +1694,14 @@ where
     ///
     /// A namespace uri is of the form chronicle:ns:{external_id}:{uuid}
     /// Namespaces must be globally unique, so are disambiguated by uuid but are locally referred to by external_id only
-    /// For coordination between chronicle nodes we also need a namespace binding operation to tie the UUID from another instance to a external_id
+    /// For coordination between chronicle nodes we also need a namespace binding operation to tie the UUID from another instance to a external_id,
+    /// or a namespace seed shared between the nodes, from which the UUID is derived
     /// # Arguments
     /// * `external_id` - an arbitrary namespace identifier
     #[instrument(skip(self, connection))]
     fn ensure_namespace(
         &mut self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         external_id: &ExternalId,
     ) -> Result<(NamespaceId, Vec<ChronicleOperation>), ApiError> {
         let ns = self.store.namespace_by_external_id(connection, external_id);
@@ -874,16 +1709,25 @@ where
         if ns.is_err() {
             debug!(?ns, "Namespace does not exist, creating");
 
-            let uuid = U::uuid();
+            let uuid = self
+                .namespace_seed
+                .map(|seed| namespace_uuid(&seed, external_id))
+                .unwrap_or_else(U::uuid);
             let id: NamespaceId = NamespaceId::from_external_id(external_id, uuid);
-            Ok((
+            let mut to_apply = vec![ChronicleOperation::CreateNamespace(CreateNamespace::new(
                 id.clone(),
-                vec![ChronicleOperation::CreateNamespace(CreateNamespace::new(
-                    id,
-                    external_id,
-                    uuid,
-                ))],
-            ))
+                external_id,
+                uuid,
+            ))];
+
+            if self.register_namespaces && external_id.as_str() != SYSTEM_ID {
+                let (system, create_system) =
+                    self.ensure_namespace(connection, &ExternalId::from(SYSTEM_ID))?;
+                to_apply.extend(create_system);
+                to_apply.extend(namespace_registration(&system, &id));
+            }
+
+            Ok((id, to_apply))
         } else {
             Ok((ns?.0, vec![]))
         }
@@ -1027,6 +1871,11 @@ where
         attributes: Attributes,
         identity: AuthId,
     ) -> Result<ApiResponse, ApiError> {
+        self.validation.validate(&attributes)?;
+        let external_id = self
+            .id_strategies
+            .external_id(external_id, &attributes, U::uuid);
+
         let mut api = self.clone();
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
@@ -1076,6 +1925,11 @@ where
         attributes: Attributes,
         identity: AuthId,
     ) -> Result<ApiResponse, ApiError> {
+        self.validation.validate(&attributes)?;
+        let external_id = self
+            .id_strategies
+            .external_id(external_id, &attributes, U::uuid);
+
         let mut api = self.clone();
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
@@ -1113,6 +1967,98 @@ where
         .await?
     }
 
+    /// Submits the operations a recipe records in one transaction:
+    /// [`ActivityExists`] and [`SetAttributes::Activity`] for the activity,
+    /// [`StartActivity`] and [`EndActivity`] at `time`, then
+    /// [`WasAssociatedWith`] for each agent, [`ActivityUses`] for each entity
+    /// used and [`WasGeneratedBy`] for each entity generated
+    #[instrument(skip(self))]
+    #[allow(clippy::too_many_arguments)]
+    async fn recipe(
+        &self,
+        external_id: ExternalId,
+        namespace: ExternalId,
+        attributes: Attributes,
+        time: Option<DateTime<Utc>>,
+        associations: Vec<(AgentId, Option<Role>)>,
+        used: Vec<EntityId>,
+        generated: Vec<EntityId>,
+        identity: AuthId,
+    ) -> Result<ApiResponse, ApiError> {
+        self.validation.validate(&attributes)?;
+        let external_id = self
+            .id_strategies
+            .external_id(external_id, &attributes, U::uuid);
+        let time = time.unwrap_or_else(Utc::now);
+
+        let mut api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+
+            connection.build_transaction().run(|connection| {
+                let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
+
+                let applying_new_namespace = !to_apply.is_empty();
+
+                let id = ActivityId::from_external_id(&external_id);
+
+                to_apply.push(ChronicleOperation::ActivityExists(ActivityExists {
+                    namespace: namespace.clone(),
+                    external_id,
+                }));
+
+                to_apply.push(ChronicleOperation::SetAttributes(SetAttributes::Activity {
+                    namespace: namespace.clone(),
+                    id: id.clone(),
+                    attributes,
+                }));
+
+                to_apply.push(ChronicleOperation::StartActivity(StartActivity {
+                    namespace: namespace.clone(),
+                    id: id.clone(),
+                    time,
+                }));
+
+                to_apply.push(ChronicleOperation::EndActivity(EndActivity {
+                    namespace: namespace.clone(),
+                    id: id.clone(),
+                    time,
+                }));
+
+                for (agent, role) in associations {
+                    to_apply.push(ChronicleOperation::WasAssociatedWith(
+                        WasAssociatedWith::new(&namespace, &id, &agent, role),
+                    ));
+                }
+
+                for entity in used {
+                    to_apply.push(ChronicleOperation::ActivityUses(ActivityUses {
+                        namespace: namespace.clone(),
+                        id: entity,
+                        activity: id.clone(),
+                    }));
+                }
+
+                for entity in generated {
+                    to_apply.push(ChronicleOperation::WasGeneratedBy(WasGeneratedBy {
+                        namespace: namespace.clone(),
+                        id: entity,
+                        activity: id.clone(),
+                    }));
+                }
+
+                api.apply_effects_and_submit(
+                    connection,
+                    id,
+                    identity,
+                    to_apply,
+                    applying_new_namespace,
+                )
+            })
+        })
+        .await?
+    }
+
     /// Submits operations [`CreateAgent`], and [`SetAttributes::Agent`]
     ///
     /// We use our local store to see if the agent already exists, disambiguating the URI if so
@@ -1124,6 +2070,11 @@ where
         attributes: Attributes,
         identity: AuthId,
     ) -> Result<ApiResponse, ApiError> {
+        self.validation.validate(&attributes)?;
+        let external_id = self
+            .id_strategies
+            .external_id(external_id, &attributes, U::uuid);
+
         let mut api = self.clone();
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
@@ -1180,6 +2131,324 @@ where
         .await?
     }
 
+    /// Rotate the chronicle signing key. The outgoing key signs the transition,
+    /// which is recorded against the chronicle agent in the system namespace.
+    /// The new key is held in the secret store under a pending name before the
+    /// transition is submitted, and only replaces the current key once the
+    /// ledger records it, see [Api::resolve_key_rotation], so a rejected
+    /// rotation leaves the current key in place and one interrupted by a
+    /// restart can still be completed.
+    #[instrument(skip(self, new_key))]
+    async fn rotate_key(&self, new_key: NewKey, identity: AuthId) -> Result<ApiResponse, ApiError> {
+        let rotation = self
+            .signing
+            .prepare_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK, new_key)
+            .await?;
+
+        let id = AgentId::from_external_id(CHRONICLE_PK);
+        let previous_key = hex::encode(rotation.previous.to_bytes());
+        let publickey = hex::encode(rotation.next.to_bytes());
+        let signature = hex::encode(&rotation.signature);
+
+        let mut api = self.clone();
+        let response = tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+            connection.build_transaction().run(|connection| {
+                let (namespace, mut to_apply) =
+                    api.ensure_namespace(connection, &ExternalId::from(SYSTEM_ID))?;
+
+                to_apply.push(ChronicleOperation::RotateKey(RotateKey {
+                    namespace,
+                    id: id.clone(),
+                    previous_key,
+                    publickey,
+                    signature,
+                }));
+
+                api.submit(id, identity, to_apply)
+            })
+        })
+        .await?;
+
+        // Commits are processed by the same task as dispatch, so this cannot
+        // race the commit of the transition. A transition that failed to
+        // submit may still have reached the ledger, so its key is kept pending
+        // until the ledger records a key for the chronicle agent.
+        let tx_id = match &response {
+            Ok(ApiResponse::Submission { tx_id, .. }) => Some(tx_id.clone()),
+            _ => None,
+        };
+        *self.pending_rotation.lock().await = Some((tx_id, rotation));
+
+        response
+    }
+
+    /// Complete or discard the pending key rotation once the ledger settles
+    /// it, by recording its key for the chronicle agent, by recording another
+    /// key, or by rejecting the transaction that submitted it. `delta` is the
+    /// state committed by `tx_id`, or `None` if the ledger rejected it.
+    async fn resolve_key_rotation(
+        &self,
+        tx_id: &ChronicleTransactionId,
+        delta: Option<&ProvModel>,
+    ) {
+        let mut pending = self.pending_rotation.lock().await;
+
+        let recorded = match (&*pending, delta.and_then(chronicle_key)) {
+            (Some((_, rotation)), Some(key)) if key == hex::encode(rotation.next.to_bytes()) => {
+                true
+            }
+            (Some((_, rotation)), Some(key))
+                if key != hex::encode(rotation.previous.to_bytes()) =>
+            {
+                false
+            }
+            (Some((Some(pending_tx_id), _)), None) if delta.is_none() && pending_tx_id == tx_id => {
+                false
+            }
+            _ => return,
+        };
+
+        if let Some((_, rotation)) = pending.take() {
+            self.settle_key_rotation(rotation, recorded).await;
+        }
+    }
+
+    /// Resolve a key rotation left pending by an earlier run, from the key the
+    /// ledger has recorded for the chronicle agent. A rotation whose key is not
+    /// yet recorded stays pending, to be resolved as the ledger is synced.
+    async fn recover_key_rotation(&self) {
+        let rotation = match self
+            .signing
+            .pending_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+            .await
+        {
+            Ok(Some(rotation)) => rotation,
+            Ok(None) => return,
+            Err(e) => {
+                error!(?e, "Reading pending key rotation");
+                return;
+            }
+        };
+
+        let store = self.store.clone();
+        let recorded = tokio::task::spawn_blocking(move || {
+            store.read_only_from(ReadFrom::Primary, |connection| {
+                store.apply_prov_model_for_agent_id(
+                    connection,
+                    ProvModel::default(),
+                    &AgentId::from_external_id(CHRONICLE_PK),
+                    &ExternalId::from(SYSTEM_ID),
+                )
+            })
+        })
+        .await;
+
+        let recorded = match recorded {
+            Ok(Ok(model)) => chronicle_key(&model).map(str::to_owned),
+            Ok(Err(e)) => {
+                error!(?e, "Reading the chronicle key recorded by the ledger");
+                None
+            }
+            Err(e) => {
+                error!(?e, "Reading the chronicle key recorded by the ledger");
+                None
+            }
+        };
+
+        match recorded {
+            Some(key) if key == hex::encode(rotation.next.to_bytes()) => {
+                self.settle_key_rotation(rotation, true).await
+            }
+            Some(key) if key != hex::encode(rotation.previous.to_bytes()) => {
+                self.settle_key_rotation(rotation, false).await
+            }
+            _ => {
+                info!(next = ?rotation.next, "Key rotation pending from an earlier run");
+                *self.pending_rotation.lock().await = Some((None, rotation));
+            }
+        }
+    }
+
+    /// Complete a rotation whose key the ledger has recorded, or discard one
+    /// it has not
+    async fn settle_key_rotation(&self, rotation: PendingKeyRotation, recorded: bool) {
+        if !recorded {
+            warn!("Key rotation not recorded by the ledger, keeping the current key");
+            if let Err(e) = self.signing.discard_key_rotation(rotation).await {
+                error!(?e, "Discarding key rotation");
+            }
+        } else if let Err(e) = self.signing.complete_key_rotation(rotation).await {
+            error!(?e, "Completing key rotation");
+        } else {
+            info!("Chronicle key rotated");
+        }
+    }
+
+    /// Record the digest of each namespace's derived state, as of the last
+    /// transaction to affect it, as a checkpoint entity in the system
+    /// namespace. Nodes syncing the same ledger compare checkpoints with their
+    /// own state in [Api::verify_checkpoints]. Checkpoint ids are derived from
+    /// the namespace and its version, so unchanged namespaces are skipped.
+    #[instrument(skip(self))]
+    async fn checkpoint(&self, identity: AuthId) -> Result<ApiResponse, ApiError> {
+        let mut api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+            let (system, create_system) =
+                api.ensure_namespace(&mut connection, &ExternalId::from(SYSTEM_ID))?;
+
+            let mut to_apply = vec![];
+            for namespace in api
+                .store
+                .read_only(|connection| api.store.namespaces(connection))?
+            {
+                if namespace.external_id_part().as_str() == SYSTEM_ID {
+                    continue;
+                }
+
+                let version = match api.store.namespace_version(namespace.external_id_part())? {
+                    Some(version) => version,
+                    None => continue,
+                };
+
+                let external_id = ExternalId::from(format!(
+                    "checkpoint-{}-{}",
+                    namespace.uuid_part(),
+                    version.replace(':', "-")
+                ));
+
+                if api
+                    .store
+                    .entity_by_entity_external_id_and_namespace(
+                        &mut connection,
+                        &external_id,
+                        &system,
+                    )
+                    .is_ok()
+                {
+                    continue;
+                }
+
+                let digest = api
+                    .store
+                    .read_only(|connection| {
+                        api.store.prov_model_for_namespace(connection, &namespace)
+                    })?
+                    .namespace_digest(&namespace);
+
+                let attribute = |name: &str, value: String| {
+                    (name.to_owned(), Attribute::new(name, value.into()))
+                };
+
+                to_apply.push(ChronicleOperation::EntityExists(EntityExists {
+                    namespace: system.clone(),
+                    external_id: external_id.clone(),
+                }));
+                to_apply.push(ChronicleOperation::SetAttributes(SetAttributes::Entity {
+                    namespace: system.clone(),
+                    id: EntityId::from_external_id(&external_id),
+                    attributes: Attributes {
+                        typ: Some(DomaintypeId::from_external_id(CHECKPOINT_DOMAINTYPE)),
+                        attributes: [
+                            attribute("namespace", namespace.external_id_part().to_string()),
+                            attribute("namespaceUuid", namespace.uuid_part().to_string()),
+                            attribute("version", version),
+                            attribute("digest", digest),
+                        ]
+                        .into_iter()
+                        .collect(),
+                    },
+                }));
+            }
+
+            if to_apply.is_empty() {
+                debug!("No namespaces changed since their last checkpoint");
+                return Ok(ApiResponse::Unit);
+            }
+
+            let to_apply = create_system.into_iter().chain(to_apply).collect();
+            let identity = identity.signed_identity(&api.signing)?;
+            let (tx_id, _) = api.submit_blocking(&ChronicleTransaction::new(to_apply, identity))?;
+            info!(%tx_id, "Submitted checkpoint");
+
+            Ok(ApiResponse::Unit)
+        })
+        .await?
+    }
+
+    /// Compare the checkpoints in a committed delta with the digests of this
+    /// node's own state. A checkpoint can only be compared while its namespace
+    /// is still at the version the checkpoint was taken at, later checkpoints
+    /// cover any changes made since.
+    async fn verify_checkpoints(&self, delta: &ProvModel) {
+        let checkpoint_type = DomaintypeId::from_external_id(CHECKPOINT_DOMAINTYPE);
+
+        let checkpoints = delta
+            .entities
+            .values()
+            .filter(|entity| {
+                entity.namespaceid.external_id_part().as_str() == SYSTEM_ID
+                    && entity.domaintypeid.as_ref() == Some(&checkpoint_type)
+            })
+            .filter_map(|entity| {
+                let attribute = |name: &str| {
+                    entity
+                        .attributes
+                        .get(name)
+                        .and_then(|attribute| attribute.value.as_str())
+                        .map(|value| value.to_owned())
+                };
+
+                let uuid = Uuid::parse_str(&attribute("namespaceUuid")?).ok()?;
+                Some((
+                    NamespaceId::from_external_id(attribute("namespace")?, uuid),
+                    attribute("version")?,
+                    attribute("digest")?,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        if checkpoints.is_empty() {
+            return;
+        }
+
+        let store = self.store.clone();
+        let verified = tokio::task::spawn_blocking(move || {
+            for (namespace, version, digest) in checkpoints {
+                if store.namespace_version(namespace.external_id_part())?.as_ref() != Some(&version) {
+                    debug!(%namespace, %version, "Namespace has changed since checkpoint, skipping");
+                    continue;
+                }
+
+                let local = store
+                    .read_only(|connection| store.prov_model_for_namespace(connection, &namespace))?
+                    .namespace_digest(&namespace);
+
+                if local == digest {
+                    debug!(%namespace, %version, %digest, "Checkpoint verified");
+                } else {
+                    error!(
+                        %namespace,
+                        %version,
+                        checkpoint = %digest,
+                        %local,
+                        "Derived state diverges from checkpoint"
+                    );
+                }
+            }
+
+            Ok::<_, StoreError>(())
+        })
+        .await;
+
+        match verified {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!(?e, "Verifying checkpoints"),
+            Err(e) => error!(?e, "Verifying checkpoints task"),
+        }
+    }
+
     #[instrument(skip(self))]
     async fn depth_charge(
         &self,
@@ -1212,29 +2481,219 @@ where
         to_apply: Vec<ChronicleOperation>,
     ) -> Result<ApiResponse, ApiError> {
         let identity = identity.signed_identity(&self.signing)?;
-        let tx_id = self.submit_blocking(&ChronicleTransaction::new(to_apply, identity))?;
+        let (tx_id, _) = self.submit_blocking(&ChronicleTransaction::new(to_apply, identity))?;
         Ok(ApiResponse::depth_charge_submission(tx_id))
     }
 
+    /// Evaluate the namespace policy, if configured, for the namespace the
+    /// command targets
     #[instrument(skip(self))]
-    async fn dispatch(&mut self, command: (ApiCommand, AuthId)) -> Result<ApiResponse, ApiError> {
-        match command {
-            (ApiCommand::DepthCharge(DepthChargeCommand { namespace }), identity) => {
-                self.depth_charge(namespace, identity).await
-            }
-            (
-                ApiCommand::Import(ImportCommand {
-                    namespace,
-                    operations,
-                }),
-                identity,
-            ) => {
+    async fn check_namespace_access(
+        &self,
+        command: &ApiCommand,
+        identity: &AuthId,
+    ) -> Result<(), ApiError> {
+        if let Some(opa) = &self.namespace_policy {
+            let access = if command.is_query() { "read" } else { "write" };
+            evaluate_namespace_access(opa, identity, command.namespace(), access).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Handle a command sent to the api, removing it from the outbox entry
+    /// `queued` once handled. The caller is told of failures, so handled
+    /// commands leave the outbox either way, unless in store and forward mode
+    /// the ledger could not be reached, when the command is held there
+    #[instrument(skip(self))]
+    async fn handle(
+        &mut self,
+        command: (ApiCommand, AuthId),
+        queued: Option<String>,
+    ) -> Result<ApiResponse, ApiError> {
+        if let ApiCommand::Outbox(OutboxCommand::Flush) = command.0 {
+            self.forward_held().await;
+        }
+
+        let id = match queued {
+            Some(id) => id,
+            None => return self.dispatch(command).await,
+        };
+
+        if !self.store_and_forward {
+            let result = self.dispatch(command).await;
+            self.dequeue(&id);
+            return result;
+        }
+
+        // Commands sent before one was held follow it, to keep their order
+        let mut held = self.held.lock().await;
+        if !held.is_empty() {
+            held.push_back((id.clone(), command));
+            return Ok(ApiResponse::Queued { id });
+        }
+        drop(held);
+
+        let result = self.dispatch(command.clone()).await;
+        if result.as_ref().is_err_and(ApiError::is_ledger_unreachable) {
+            warn!(%id, "Ledger unreachable, holding command in the outbox");
+            self.held.lock().await.push_back((id.clone(), command));
+            return Ok(ApiResponse::Queued { id });
+        }
+
+        self.dequeue(&id);
+        result
+    }
+
+    /// Submit the commands held while the ledger was unreachable, in the order
+    /// they were made, stopping at the first that finds it unreachable still.
+    /// Their callers have been answered already, so failures are logged
+    #[instrument(skip(self))]
+    async fn forward_held(&mut self) {
+        loop {
+            let next = self.held.lock().await.front().cloned();
+            let (id, command) = match next {
+                Some(next) => next,
+                None => return,
+            };
+
+            let result = self.dispatch(command).await;
+            if result.as_ref().is_err_and(ApiError::is_ledger_unreachable) {
+                let remaining = self.held.lock().await.len();
+                debug!(remaining, "Ledger still unreachable, commands remain held");
+                return;
+            }
+
+            match result {
+                Ok(response) => debug!(%id, ?response, "Forwarded held command"),
+                Err(e) => warn!(?e, %id, "Held command failed when forwarded"),
+            }
+
+            self.held.lock().await.pop_front();
+            self.dequeue(&id);
+        }
+    }
+
+    /// Remove a handled command from the outbox
+    fn dequeue(&self, id: &str) {
+        if let Err(e) = self.store.dequeue_command(id) {
+            error!(?e, %id, "Remove command from outbox");
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn dispatch(&mut self, command: (ApiCommand, AuthId)) -> Result<ApiResponse, ApiError> {
+        self.check_namespace_access(&command.0, &command.1).await?;
+
+        let (command, identity) = command;
+        match command.split_idempotency_key() {
+            (Some(key), command) => self.dispatch_idempotent(key, command, identity).await,
+            (None, command) => self.execute(command, identity).await,
+        }
+    }
+
+    /// Execute a command sent with an idempotency key, unless a command was
+    /// submitted with the same key by the same identity, in which case the
+    /// transaction it was submitted in is returned. The key is reserved before
+    /// the command is executed, so that the same command sent twice at once is
+    /// submitted once, and released if the command submits nothing.
+    #[instrument(skip(self))]
+    async fn dispatch_idempotent(
+        &mut self,
+        key: String,
+        command: ApiCommand,
+        identity: AuthId,
+    ) -> Result<ApiResponse, ApiError> {
+        let command_digest = command.digest()?;
+        let owner = identity.to_string();
+
+        let store = self.store.clone();
+        let (reserve_owner, reserve_key, reserve_digest) =
+            (owner.clone(), key.clone(), command_digest.clone());
+        let holder = tokio::task::spawn_blocking(move || {
+            store.reserve_idempotency_key(&reserve_owner, &reserve_key, &reserve_digest)
+        })
+        .await??;
+
+        match holder {
+            IdempotencyKeyHolder::Reserved => {}
+            IdempotencyKeyHolder::Pending {
+                command_digest: held,
+            }
+            | IdempotencyKeyHolder::Submitted {
+                command_digest: held,
+                ..
+            } if held != command_digest => {
+                return Err(ApiError::IdempotencyKeyReused { key });
+            }
+            IdempotencyKeyHolder::Pending { .. } => {
+                return Err(ApiError::IdempotencyKeyPending { key });
+            }
+            IdempotencyKeyHolder::Submitted { subject, tx_id, .. } => {
+                debug!(%key, %tx_id, "Command already submitted");
+                return Ok(ApiResponse::AlreadySubmitted { subject, tx_id });
+            }
+        }
+
+        let response = self.execute(command, identity).await;
+
+        // Only commands that submit a transaction hold their key, others have
+        // no effect to repeat
+        let store = self.store.clone();
+        let submitted = match &response {
+            Ok(ApiResponse::Submission { subject, tx_id, .. }) => {
+                Some((subject.clone(), tx_id.clone()))
+            }
+            _ => None,
+        };
+        tokio::task::spawn_blocking(move || match submitted {
+            Some((subject, tx_id)) => {
+                store.complete_idempotency_key(&owner, &key, &subject, &tx_id)
+            }
+            None => store.release_idempotency_key(&owner, &key),
+        })
+        .await??;
+
+        response
+    }
+
+    async fn execute(
+        &mut self,
+        command: ApiCommand,
+        identity: AuthId,
+    ) -> Result<ApiResponse, ApiError> {
+        match (command, identity) {
+            (ApiCommand::DepthCharge(DepthChargeCommand { namespace }), identity) => {
+                self.depth_charge(namespace, identity).await
+            }
+            (
+                ApiCommand::Import(ImportCommand {
+                    namespace,
+                    operations,
+                }),
+                identity,
+            ) => {
                 self.submit_import_operations(identity, namespace, operations)
                     .await
             }
             (ApiCommand::NameSpace(NamespaceCommand::Create { external_id }), identity) => {
                 self.create_namespace(&external_id, identity).await
             }
+            (ApiCommand::RotateKey(RotateKeyCommand { import }), identity) => {
+                let new_key = match import {
+                    Some(path) => NewKey::Import(std::fs::read_to_string(path)?),
+                    None => NewKey::Generate,
+                };
+                self.rotate_key(new_key, identity).await
+            }
+            (ApiCommand::Verify(VerifyCommand { namespace }), _identity) => {
+                self.verify(namespace).await
+            }
+            (ApiCommand::Checkpoint(CheckpointCommand), identity) => {
+                self.checkpoint(identity).await
+            }
+            // Flushing has submitted what it could before reaching here
+            (ApiCommand::Outbox(_), _identity) => outbox(&self.store).await,
             (
                 ApiCommand::Agent(AgentCommand::Create {
                     external_id,
@@ -1256,10 +2715,32 @@ where
                     activity,
                     namespace,
                     role,
+                    valid_from,
+                    valid_until,
+                }),
+                identity,
+            ) => {
+                self.delegate(
+                    namespace,
+                    id,
+                    delegate,
+                    activity,
+                    role,
+                    valid_from,
+                    valid_until,
+                    identity,
+                )
+                .await
+            }
+            (
+                ApiCommand::Agent(AgentCommand::RetractAttribute {
+                    id,
+                    namespace,
+                    attribute,
                 }),
                 identity,
             ) => {
-                self.delegate(namespace, id, delegate, activity, role, identity)
+                self.retract_attribute(namespace, id.into(), attribute, identity)
                     .await
             }
             (
@@ -1337,6 +2818,53 @@ where
                 self.associate(namespace, responsible, id, role, identity)
                     .await
             }
+            (
+                ApiCommand::Activity(ActivityCommand::RetractAssociation {
+                    id,
+                    namespace,
+                    responsible,
+                    role,
+                }),
+                identity,
+            ) => {
+                self.retract_association(namespace, responsible, id, role, identity)
+                    .await
+            }
+            (
+                ApiCommand::Activity(ActivityCommand::RetractAttribute {
+                    id,
+                    namespace,
+                    attribute,
+                }),
+                identity,
+            ) => {
+                self.retract_attribute(namespace, id.into(), attribute, identity)
+                    .await
+            }
+            (
+                ApiCommand::Activity(ActivityCommand::Recipe {
+                    external_id,
+                    namespace,
+                    attributes,
+                    time,
+                    associations,
+                    used,
+                    generated,
+                }),
+                identity,
+            ) => {
+                self.recipe(
+                    external_id,
+                    namespace,
+                    attributes,
+                    time,
+                    associations,
+                    used,
+                    generated,
+                    identity,
+                )
+                .await
+            }
             (
                 ApiCommand::Entity(EntityCommand::Attribute {
                     id,
@@ -1349,6 +2877,29 @@ where
                 self.attribute(namespace, responsible, id, role, identity)
                     .await
             }
+            (
+                ApiCommand::Entity(EntityCommand::RetractAttribution {
+                    id,
+                    namespace,
+                    responsible,
+                    role,
+                }),
+                identity,
+            ) => {
+                self.retract_attribution(namespace, responsible, id, role, identity)
+                    .await
+            }
+            (
+                ApiCommand::Entity(EntityCommand::RetractAttribute {
+                    id,
+                    namespace,
+                    attribute,
+                }),
+                identity,
+            ) => {
+                self.retract_attribute(namespace, id.into(), attribute, identity)
+                    .await
+            }
             (
                 ApiCommand::Entity(EntityCommand::Create {
                     external_id,
@@ -1384,10 +2935,27 @@ where
                 self.entity_derive(id, namespace, activity, used_entity, derivation, identity)
                     .await
             }
+            (
+                ApiCommand::Entity(EntityCommand::Revise {
+                    id,
+                    namespace,
+                    external_id,
+                    attributes,
+                    expire,
+                }),
+                identity,
+            ) => {
+                self.revise_entity(id, namespace, external_id, attributes, expire, identity)
+                    .await
+            }
             (ApiCommand::Query(query), _identity) => self.query(query).await,
+            (ApiCommand::Idempotent(_), _identity) => {
+                unreachable!("idempotency keys are split from commands before execution")
+            }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip(self))]
     async fn delegate(
         &self,
@@ -1396,6 +2964,8 @@ where
         delegate_id: AgentId,
         activity_id: Option<ActivityId>,
         role: Option<Role>,
+        valid_from: Option<DateTime<Utc>>,
+        valid_until: Option<DateTime<Utc>>,
         identity: AuthId,
     ) -> Result<ApiResponse, ApiError> {
         let mut api = self.clone();
@@ -1408,13 +2978,16 @@ where
 
                 let applying_new_namespace = !to_apply.is_empty();
 
-                let tx = ChronicleOperation::AgentActsOnBehalfOf(ActsOnBehalfOf::new(
-                    &namespace,
-                    &responsible_id,
-                    &delegate_id,
-                    activity_id.as_ref(),
-                    role,
-                ));
+                let tx = ChronicleOperation::AgentActsOnBehalfOf(
+                    ActsOnBehalfOf::new(
+                        &namespace,
+                        &responsible_id,
+                        &delegate_id,
+                        activity_id.as_ref(),
+                        role,
+                    )
+                    .with_validity(valid_from, valid_until),
+                );
 
                 to_apply.push(tx);
 
@@ -1511,13 +3084,12 @@ where
     }
 
     #[instrument(skip(self))]
-    async fn entity_derive(
+    async fn retract_association(
         &self,
-        id: EntityId,
         namespace: ExternalId,
-        activity_id: Option<ActivityId>,
-        used_id: EntityId,
-        typ: DerivationType,
+        responsible_id: AgentId,
+        activity_id: ActivityId,
+        role: Option<Role>,
         identity: AuthId,
     ) -> Result<ApiResponse, ApiError> {
         let mut api = self.clone();
@@ -1530,19 +3102,18 @@ where
 
                 let applying_new_namespace = !to_apply.is_empty();
 
-                let tx = ChronicleOperation::EntityDerive(EntityDerive {
-                    namespace,
-                    id: id.clone(),
-                    used_id: used_id.clone(),
-                    activity_id: activity_id.clone(),
-                    typ,
-                });
+                let tx = ChronicleOperation::RetractAssociation(RetractAssociation::new(
+                    &namespace,
+                    &activity_id,
+                    &responsible_id,
+                    role,
+                ));
 
                 to_apply.push(tx);
 
                 api.apply_effects_and_submit(
                     connection,
-                    id,
+                    activity_id,
                     identity,
                     to_apply,
                     applying_new_namespace,
@@ -1552,91 +3123,555 @@ where
         .await?
     }
 
-    async fn query(&self, query: QueryCommand) -> Result<ApiResponse, ApiError> {
-        let api = self.clone();
-        tokio::task::spawn_blocking(move || {
-            let mut connection = api.store.connection()?;
-
-            let (id, _) = api
-                .store
-                .namespace_by_external_id(&mut connection, &ExternalId::from(&query.namespace))?;
-            Ok(ApiResponse::query_reply(
-                api.store.prov_model_for_namespace(&mut connection, &id)?,
-            ))
-        })
-        .await?
-    }
-
-    async fn submit_import_operations(
+    #[instrument(skip(self))]
+    async fn retract_attribution(
         &self,
+        namespace: ExternalId,
+        responsible_id: AgentId,
+        entity_id: EntityId,
+        role: Option<Role>,
         identity: AuthId,
-        namespace: NamespaceId,
-        operations: Vec<ChronicleOperation>,
     ) -> Result<ApiResponse, ApiError> {
         let mut api = self.clone();
-        let identity = identity.signed_identity(&self.signing)?;
-        let model = ProvModel::from_tx(&operations)?;
+
         tokio::task::spawn_blocking(move || {
-            // Check here to ensure that import operations result in data changes
             let mut connection = api.store.connection()?;
+
             connection.build_transaction().run(|connection| {
-                if let Some(operations_to_apply) = api.check_for_effects(connection, &operations)? {
-                    info!("Submitting import operations to ledger");
-                    let tx_id = api.submit_blocking(&ChronicleTransaction::new(
-                        operations_to_apply,
-                        identity,
-                    ))?;
-                    Ok(ApiResponse::import_submitted(model, tx_id))
-                } else {
-                    info!("Import will not result in any data changes");
-                    let model = ProvModel::from_tx(&operations)?;
-                    Ok(ApiResponse::already_recorded(namespace, model))
-                }
-            })
-        })
-        .await?
-    }
+                let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
-    #[instrument(level = "debug", skip(self), ret(Debug))]
-    async fn sync(
-        &self,
-        prov: Box<ProvModel>,
-        block_id: &BlockId,
-        tx_id: ChronicleTransactionId,
-    ) -> Result<ApiResponse, ApiError> {
-        let api = self.clone();
-        let block_id = *block_id;
-        tokio::task::spawn_blocking(move || {
-            api.store.apply_prov(&prov)?;
-            api.store.set_last_block_id(&block_id, tx_id)?;
+                let applying_new_namespace = !to_apply.is_empty();
 
-            Ok(ApiResponse::Unit)
+                let tx = ChronicleOperation::RetractAttribution(RetractAttribution::new(
+                    &namespace,
+                    &entity_id,
+                    &responsible_id,
+                    role,
+                ));
+
+                to_apply.push(tx);
+
+                api.apply_effects_and_submit(
+                    connection,
+                    entity_id,
+                    identity,
+                    to_apply,
+                    applying_new_namespace,
+                )
+            })
         })
         .await?
     }
 
-    /// Creates and submits a (ChronicleTransaction::StartActivity) determining the appropriate agent by external_id, or via [use_agent] context
+    /// Retract a single attribute of an agent, activity or entity
     #[instrument(skip(self))]
-    async fn instant(
+    async fn retract_attribute(
         &self,
-        id: ActivityId,
         namespace: ExternalId,
-        time: Option<DateTime<Utc>>,
-        agent: Option<AgentId>,
+        id: ChronicleIri,
+        typename: String,
         identity: AuthId,
     ) -> Result<ApiResponse, ApiError> {
         let mut api = self.clone();
+
         tokio::task::spawn_blocking(move || {
             let mut connection = api.store.connection()?;
+
             connection.build_transaction().run(|connection| {
                 let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
 
                 let applying_new_namespace = !to_apply.is_empty();
 
-                let agent_id = {
-                    if let Some(agent) = agent {
-                        Some(agent)
-                    } else {
+                let retraction = match id.clone() {
+                    ChronicleIri::Agent(id) => RetractAttribute::Agent {
+                        namespace,
+                        id,
+                        typename,
+                    },
+                    ChronicleIri::Activity(id) => RetractAttribute::Activity {
+                        namespace,
+                        id,
+                        typename,
+                    },
+                    ChronicleIri::Entity(id) => RetractAttribute::Entity {
+                        namespace,
+                        id,
+                        typename,
+                    },
+                    _ => {
+                        unreachable!("attributes are only held by agents, activities and entities")
+                    }
+                };
+
+                to_apply.push(ChronicleOperation::RetractAttribute(retraction));
+
+                api.apply_effects_and_submit(
+                    connection,
+                    id,
+                    identity,
+                    to_apply,
+                    applying_new_namespace,
+                )
+            })
+        })
+        .await?
+    }
+
+    #[instrument(skip(self))]
+    async fn entity_derive(
+        &self,
+        id: EntityId,
+        namespace: ExternalId,
+        activity_id: Option<ActivityId>,
+        used_id: EntityId,
+        typ: DerivationType,
+        identity: AuthId,
+    ) -> Result<ApiResponse, ApiError> {
+        let mut api = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+
+            connection.build_transaction().run(|connection| {
+                let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
+
+                let applying_new_namespace = !to_apply.is_empty();
+
+                let tx = ChronicleOperation::EntityDerive(EntityDerive {
+                    namespace,
+                    id: id.clone(),
+                    used_id: used_id.clone(),
+                    activity_id: activity_id.clone(),
+                    typ,
+                });
+
+                to_apply.push(tx);
+
+                api.apply_effects_and_submit(
+                    connection,
+                    id,
+                    identity,
+                    to_apply,
+                    applying_new_namespace,
+                )
+            })
+        })
+        .await?
+    }
+
+    /// Submits the operations defining `external_id` as a revision of `id` in
+    /// one transaction: [`EntityExists`], [`SetAttributes::Entity`] with the
+    /// attributes of `id` overridden by `attributes`, [`EntityDerive`] as a
+    /// revision and, if `expire` is set, the expiry of `id`
+    #[instrument(skip(self))]
+    async fn revise_entity(
+        &self,
+        id: EntityId,
+        namespace: ExternalId,
+        external_id: ExternalId,
+        attributes: Attributes,
+        expire: bool,
+        identity: AuthId,
+    ) -> Result<ApiResponse, ApiError> {
+        let mut api = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+
+            connection.build_transaction().run(|connection| {
+                let revised = api
+                    .store
+                    .prov_model_for_entity_id(connection, &id, &namespace)?
+                    .entities
+                    .into_values()
+                    .find(|entity| entity.id == id);
+
+                let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
+
+                let applying_new_namespace = !to_apply.is_empty();
+
+                let mut revision_attributes = Attributes {
+                    typ: attributes
+                        .typ
+                        .or_else(|| revised.as_ref().and_then(|e| e.domaintypeid.clone())),
+                    attributes: revised.map(|e| e.attributes).unwrap_or_default(),
+                };
+                revision_attributes.attributes.extend(attributes.attributes);
+                api.validation.validate(&revision_attributes)?;
+
+                let revision = EntityId::from_external_id(&external_id);
+
+                to_apply.push(ChronicleOperation::EntityExists(EntityExists {
+                    namespace: namespace.clone(),
+                    external_id,
+                }));
+
+                to_apply.push(ChronicleOperation::SetAttributes(SetAttributes::Entity {
+                    namespace: namespace.clone(),
+                    id: revision.clone(),
+                    attributes: revision_attributes,
+                }));
+
+                to_apply.push(ChronicleOperation::EntityDerive(EntityDerive {
+                    namespace: namespace.clone(),
+                    id: revision.clone(),
+                    used_id: id.clone(),
+                    activity_id: None,
+                    typ: DerivationType::Revision,
+                }));
+
+                if expire {
+                    to_apply.extend(expiry::expiry_operations(&namespace, &id, Utc::now()));
+                }
+
+                api.apply_effects_and_submit(
+                    connection,
+                    revision,
+                    identity,
+                    to_apply,
+                    applying_new_namespace,
+                )
+            })
+        })
+        .await?
+    }
+
+    async fn query(&self, query: QueryCommand) -> Result<ApiResponse, ApiError> {
+        if let Some(block_id) = query.as_of_block {
+            if query.lineage.is_some() {
+                return Err(ApiError::LineageAsOfBlock);
+            }
+            return self.query_as_of_block(query.namespace, block_id).await;
+        }
+
+        let api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let namespace = ExternalId::from(&query.namespace);
+
+            let model = api.store.read_only(|connection| {
+                if let Some(lineage) = query.lineage {
+                    return api.store.prov_model_for_lineage(
+                        connection,
+                        &lineage.entity,
+                        &namespace,
+                        lineage.depth,
+                    );
+                }
+
+                let (id, _) = api.store.namespace_by_external_id(connection, &namespace)?;
+                api.store.prov_model_for_namespace(connection, &id)
+            })?;
+
+            Ok(ApiResponse::query_reply(model))
+        })
+        .await?
+    }
+
+    /// Rebuild the provenance of a namespace as it stood once `block_id` was
+    /// synced, by merging the deltas recorded in its history up to that block
+    async fn query_as_of_block(
+        &self,
+        namespace: String,
+        block_id: String,
+    ) -> Result<ApiResponse, ApiError> {
+        let store = self.store.clone();
+        let (namespace, deltas) = tokio::task::spawn_blocking(move || {
+            store.read_only(|connection| {
+                store.history_as_of_block(connection, &ExternalId::from(namespace), &block_id)
+            })
+        })
+        .await??;
+
+        Ok(ApiResponse::query_reply(
+            replay_history(&namespace, deltas).await?,
+        ))
+    }
+
+    /// Replay the ledger up to the last transaction synchronized to the store,
+    /// then compare the provenance it produces with the store's, per namespace
+    #[instrument(skip(self))]
+    async fn verify(&self, namespace: Option<ExternalId>) -> Result<ApiResponse, ApiError> {
+        let store = self.store.clone();
+        let last_tx_id = tokio::task::spawn_blocking(move || store.get_last_tx_id()).await??;
+
+        let mut ledger = ProvModel::default();
+        let mut ledger_transactions = vec![];
+
+        if let Some(last_tx_id) = last_tx_id {
+            let mut state_updates = self
+                .ledger_reader
+                .clone()
+                .state_updates("chronicle/prov-update", FromBlock::First, None)
+                .await?;
+
+            while let Some((ChronicleOperationEvent(delta, _, _), tx, ..)) =
+                state_updates.next().await
+            {
+                let tx_id = ChronicleTransactionId::from(tx.as_str());
+
+                // Contradicted transactions have no effect on either side
+                if let Ok(delta) = delta {
+                    let namespaces = delta.namespaces.keys().cloned().collect::<Vec<_>>();
+                    ledger_transactions.push((tx_id.clone(), namespaces));
+                    ledger.merge_delta(delta);
+                }
+
+                if tx_id == last_tx_id {
+                    break;
+                }
+            }
+        }
+
+        let api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let namespaces = api.store.read_only(|connection| {
+                let synced = api.store.synced_transactions(connection)?;
+                let mut namespaces = api.store.namespaces(connection)?;
+                for namespace in ledger.namespaces.keys() {
+                    if !namespaces.contains(namespace) {
+                        namespaces.push(namespace.clone());
+                    }
+                }
+
+                namespaces
+                    .into_iter()
+                    .filter(|ns| {
+                        namespace
+                            .as_ref()
+                            .map_or(true, |n| ns.external_id_part() == n)
+                    })
+                    .map(|ns| {
+                        let local = match api.store.prov_model_for_namespace(connection, &ns) {
+                            Ok(local) => local,
+                            Err(StoreError::RecordNotFound {}) => ProvModel::default(),
+                            Err(e) => return Err(e),
+                        };
+                        let unsynced = ledger_transactions
+                            .iter()
+                            .filter(|(tx_id, namespaces)| {
+                                namespaces.contains(&ns) && !synced.contains(tx_id)
+                            })
+                            .map(|(tx_id, _)| tx_id.clone())
+                            .collect();
+                        Ok(NamespaceVerification::new(ns, &local, &ledger, unsynced))
+                    })
+                    .collect::<Result<Vec<_>, StoreError>>()
+            })?;
+
+            Ok(ApiResponse::Verified { namespaces })
+        })
+        .await?
+    }
+
+    async fn submit_import_operations(
+        &self,
+        identity: AuthId,
+        namespace: NamespaceId,
+        operations: Vec<ChronicleOperation>,
+    ) -> Result<ApiResponse, ApiError> {
+        let mut api = self.clone();
+        let identity = identity.signed_identity(&self.signing)?;
+        let model = ProvModel::from_tx(&operations)?;
+        tokio::task::spawn_blocking(move || {
+            // Check here to ensure that import operations result in data changes
+            let mut connection = api.store.connection()?;
+            connection.build_transaction().run(|connection| {
+                if let Some(operations_to_apply) = api.check_for_effects(connection, &operations)? {
+                    info!("Submitting import operations to ledger");
+                    let (tx_id, _) = api.submit_blocking(&ChronicleTransaction::new(
+                        operations_to_apply,
+                        identity,
+                    ))?;
+                    Ok(ApiResponse::import_submitted(model, tx_id))
+                } else {
+                    info!("Import will not result in any data changes");
+                    let model = ProvModel::from_tx(&operations)?;
+                    Ok(ApiResponse::already_recorded(namespace, model))
+                }
+            })
+        })
+        .await?
+    }
+
+    /// Apply commits confirmed by the ledger to the store, then notify
+    /// subscribers of them and verify any checkpoints they record. Several
+    /// commits are applied in a single transaction, or one at a time if that
+    /// fails, so that a commit the store rejects does not hold back the rest.
+    async fn sync(&self, commits: Vec<(Commit, SignedIdentity)>) {
+        let batched = commits.len() > 1
+            && match self
+                .apply_commits(commits.iter().map(|(commit, _)| commit))
+                .await
+            {
+                Ok(()) => {
+                    info!(
+                        commits = commits.len(),
+                        offset = ?commits[commits.len() - 1].0.block_id,
+                        "Applied batch of confirmed commits"
+                    );
+                    true
+                }
+                Err(e) => {
+                    warn!(
+                        ?e,
+                        commits = commits.len(),
+                        "Api sync to batch of confirmed commits, applying them singly"
+                    );
+                    false
+                }
+            };
+
+        for (commit, id) in commits {
+            let applied = batched
+                || self
+                    .apply_commits([&commit])
+                    .instrument(info_span!("Incoming confirmation", offset = ?commit.block_id, tx_id = %commit.tx_id))
+                    .await
+                    .map_err(|e| {
+                        error!(?e, "Api sync to confirmed commit");
+                    })
+                    .is_ok();
+
+            self.verify_checkpoints(&commit.delta).await;
+
+            if applied {
+                // Every transaction is audited from its commit, whichever
+                // deployment submitted it
+                self.audit(&commit.tx_id, &id, &commit.operations);
+                self.adopt_namespace_registrations(&commit.delta);
+                self.sign_receipt(&commit).await;
+                self.submit_tx
+                    .send(SubmissionStage::committed(commit, id))
+                    .ok();
+            }
+        }
+    }
+
+    /// Bind the namespaces registered by a committed delta to their registered
+    /// UUIDs, replacing any binding of this node's that contradicts them
+    fn adopt_namespace_registrations(&self, delta: &ProvModel) {
+        for namespace in namespace_registrations(delta) {
+            let external_id = namespace.external_id_part();
+            let bound = self
+                .store
+                .connection()
+                .and_then(|mut connection| {
+                    self.store
+                        .namespace_by_external_id(&mut connection, external_id)
+                })
+                .ok()
+                .map(|(bound, _)| bound);
+
+            if bound.as_ref() == Some(&namespace) {
+                continue;
+            }
+
+            if let Some(bound) = bound {
+                warn!(%bound, registered = %namespace, "Rebinding namespace to its registered UUID");
+            }
+
+            if let Err(e) = self
+                .store
+                .namespace_binding(external_id.as_str(), namespace.uuid_part().to_owned())
+            {
+                error!(?e, %namespace, "Binding registered namespace");
+            }
+        }
+    }
+
+    /// Sign the receipt of a committed transaction, if this deployment
+    /// submitted it. A failure is logged, as the commit has been applied
+    /// regardless, and the receipt stays unsigned.
+    async fn sign_receipt(&self, commit: &Commit) {
+        let operation_digest = match self.store.unsigned_receipt(&commit.tx_id) {
+            Ok(Some(operation_digest)) => operation_digest,
+            Ok(None) => return,
+            Err(e) => {
+                error!(?e, tx_id = %commit.tx_id, "Reading transaction receipt");
+                return;
+            }
+        };
+
+        let signed = Receipt::sign(
+            &commit.tx_id.to_string(),
+            &commit.block_id.to_string(),
+            &operation_digest,
+            &self.signing,
+        )
+        .await
+        .map_err(ApiError::from)
+        .and_then(|receipt| {
+            Ok(self.store.sign_receipt(
+                &commit.tx_id,
+                &commit.block_id,
+                &receipt.key_id,
+                &receipt.signature,
+            )?)
+        });
+
+        if let Err(e) = signed {
+            error!(?e, tx_id = %commit.tx_id, "Signing transaction receipt");
+        }
+    }
+
+    async fn apply_commits<'a>(
+        &self,
+        commits: impl IntoIterator<Item = &'a Commit>,
+    ) -> Result<(), ApiError> {
+        let mut deltas = vec![];
+        for commit in commits {
+            deltas.push(SyncedDelta {
+                prov: commit.delta.clone(),
+                block_id: commit.block_id,
+                tx_id: commit.tx_id.clone(),
+                delta: commit
+                    .delta
+                    .to_json()
+                    .compact_stable_order()
+                    .await
+                    .map_err(ProcessorError::from)?
+                    .to_string(),
+                deliveries: match &self.webhooks {
+                    Some(webhooks) => webhooks.deliveries(&self.signing, commit).await,
+                    None => vec![],
+                },
+            });
+        }
+
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || {
+            let started = Instant::now();
+
+            let synced = store.apply_prov(&deltas);
+
+            prometheus::record_sync(started.elapsed(), deltas.len(), synced.is_ok());
+            Ok(synced?)
+        })
+        .await?
+    }
+
+    /// Creates and submits a (ChronicleTransaction::StartActivity) determining the appropriate agent by external_id, or via [use_agent] context
+    #[instrument(skip(self))]
+    async fn instant(
+        &self,
+        id: ActivityId,
+        namespace: ExternalId,
+        time: Option<DateTime<Utc>>,
+        agent: Option<AgentId>,
+        identity: AuthId,
+    ) -> Result<ApiResponse, ApiError> {
+        let mut api = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = api.store.connection()?;
+            connection.build_transaction().run(|connection| {
+                let (namespace, mut to_apply) = api.ensure_namespace(connection, &namespace)?;
+
+                let applying_new_namespace = !to_apply.is_empty();
+
+                let agent_id = {
+                    if let Some(agent) = agent {
+                        Some(agent)
+                    } else {
                         api.store
                             .get_current_agent(connection)
                             .ok()
@@ -1802,29 +3837,33 @@ where
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
 
-    use crate::{inmem::EmbeddedChronicleTp, Api, ApiDispatch, ApiError, UuidGen};
+    use crate::{
+        inmem::EmbeddedChronicleTp, Api, ApiConfig, ApiDispatch, ApiError, FollowerNotifier,
+        UuidGen,
+    };
 
     use chronicle_signing::{
-        chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, BATCHER_NAMESPACE,
-        CHRONICLE_NAMESPACE,
+        chronicle_secret_names, ChronicleKnownKeyNamesSigner, ChronicleSecretsOptions,
+        ChronicleSigning, NewKey, BATCHER_NAMESPACE, CHRONICLE_NAMESPACE, CHRONICLE_PK,
     };
     use chrono::{TimeZone, Utc};
     use common::{
         attributes::{Attribute, Attributes},
         commands::{
             ActivityCommand, AgentCommand, ApiCommand, ApiResponse, EntityCommand, ImportCommand,
-            NamespaceCommand,
+            NamespaceCommand, OutboxCommand, RotateKeyCommand,
         },
         database::TemporaryDatabase,
         identity::AuthId,
         k256::sha2::{Digest, Sha256},
+        opa::{CliPolicyLoader, ExecutorContext},
         prov::{
             operations::{ChronicleOperation, DerivationType},
             to_json_ld::ToJson,
             ActivityId, AgentId, ChronicleTransactionId, DomaintypeId, EntityId, NamespaceId,
-            ProvModel,
+            ProvModel, Role, SYSTEM_ID, SYSTEM_UUID,
         },
     };
     use opa_tp_protocol::state::{policy_address, policy_meta_address, PolicyMeta};
@@ -1833,13 +3872,20 @@ mod test {
 
     use uuid::Uuid;
 
-    struct TestDispatch<'a> {
-        api: ApiDispatch,
-        _db: TemporaryDatabase<'a>, // share lifetime
+    pub(crate) struct TestDispatch<'a> {
+        pub(crate) api: ApiDispatch,
+        db: TemporaryDatabase<'a>, // share lifetime
         _tp: EmbeddedChronicleTp,
     }
 
     impl<'a> TestDispatch<'a> {
+        /// A new pool of connections to the api's database
+        pub(crate) fn pool(
+            &self,
+        ) -> diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::PgConnection>> {
+            self.db.connection_pool().unwrap()
+        }
+
         pub async fn dispatch(
             &mut self,
             command: ApiCommand,
@@ -1941,7 +3987,38 @@ mod test {
         .unwrap()
     }
 
-    async fn test_api<'a>() -> TestDispatch<'a> {
+    pub(crate) async fn test_api<'a>() -> TestDispatch<'a> {
+        test_api_with_namespace_policy(None).await
+    }
+
+    pub(crate) async fn test_api_with_namespace_policy<'a>(
+        namespace_policy: Option<ExecutorContext>,
+    ) -> TestDispatch<'a> {
+        test_api_with(
+            ApiConfig {
+                namespace_policy,
+                ..test_config()
+            },
+            |_| {},
+        )
+        .await
+    }
+
+    pub(crate) fn test_config() -> ApiConfig {
+        ApiConfig {
+            policy_name: Some("allow_transactions".into()),
+            ..Default::default()
+        }
+    }
+
+    /// An api configured by `config`, started once `prepare` has written to
+    /// its store, as it would find the store after a restart
+    pub(crate) async fn test_api_with<'a>(
+        config: ApiConfig,
+        prepare: impl FnOnce(&crate::persistence::Store),
+    ) -> TestDispatch<'a> {
+        use diesel_migrations::MigrationHarness;
+
         chronicle_telemetry::telemetry(None, chronicle_telemetry::ConsoleLogging::Pretty);
 
         let secrets = ChronicleSigning::new(
@@ -1963,27 +4040,72 @@ mod test {
         let database = TemporaryDatabase::default();
         let pool = database.connection_pool().unwrap();
 
-        let liveness_check_interval = None;
+        pool.get()
+            .unwrap()
+            .run_pending_migrations(crate::persistence::MIGRATIONS)
+            .unwrap();
+        prepare(&crate::persistence::Store::new(pool.clone()).unwrap());
 
-        let dispatch = Api::new(
-            pool,
-            embed_tp.ledger.clone(),
-            SameUuid,
-            secrets,
-            vec![],
-            Some("allow_transactions".into()),
-            liveness_check_interval,
-        )
-        .await
-        .unwrap();
+        let dispatch = Api::new(pool, embed_tp.ledger.clone(), SameUuid, secrets, config)
+            .await
+            .unwrap();
 
         TestDispatch {
             api: dispatch,
-            _db: database, // share the lifetime
+            db: database, // share the lifetime
             _tp: embed_tp,
         }
     }
 
+    /// An api over the store and ledger of `dispatch`, to drive its handling
+    /// of ledger updates directly
+    async fn api_over(dispatch: &TestDispatch<'_>) -> Api<SameUuid, crate::inmem::InMemLedger> {
+        use async_stl_client::ledger::BlockingLedgerWriter;
+        use std::{marker::PhantomData, sync::Arc};
+
+        let (reply_tx, _) = tokio::sync::mpsc::channel(1);
+        Api {
+            _reply_tx: reply_tx,
+            submit_tx: dispatch.api.notify_commit.clone(),
+            signing: ChronicleSigning::new(
+                chronicle_secret_names(),
+                vec![
+                    (
+                        CHRONICLE_NAMESPACE.to_string(),
+                        ChronicleSecretsOptions::generate_in_memory(),
+                    ),
+                    (
+                        BATCHER_NAMESPACE.to_string(),
+                        ChronicleSecretsOptions::generate_in_memory(),
+                    ),
+                ],
+            )
+            .await
+            .unwrap(),
+            ledger_writer: Arc::new(BlockingLedgerWriter::new(dispatch._tp.ledger.clone())),
+            ledger_reader: dispatch._tp.ledger.clone(),
+            store: dispatch.api.store.clone(),
+            uuid_source: PhantomData,
+            namespace_seed: None,
+            register_namespaces: false,
+            policy_name: None,
+            namespace_policy: None,
+            enrichment: Default::default(),
+            validation: Default::default(),
+            id_strategies: Default::default(),
+            role_constraints: Default::default(),
+            webhooks: None,
+            store_and_forward: false,
+            held: Default::default(),
+            health: dispatch.api.health.clone(),
+            pending_rotation: Default::default(),
+            follower_notifier: FollowerNotifier::spawn(
+                dispatch.api.store.clone(),
+                dispatch.api.notify_commit.clone(),
+            ),
+        }
+    }
+
     // Creates a mock file containing JSON-LD of the ChronicleOperations
     // that would be created by the given command, although not in any particular order.
     fn test_create_agent_operations_import() -> assert_fs::NamedTempFile {
@@ -2160,6 +4282,51 @@ mod test {
         "###);
     }
 
+    #[tokio::test]
+    async fn namespace_access_denied() {
+        let loader = CliPolicyLoader::from_embedded_policy(
+            "allow_transactions",
+            "allow_transactions.deny_all",
+        )
+        .unwrap();
+        let opa = ExecutorContext::from_loader(&loader).unwrap();
+
+        let mut api = test_api_with_namespace_policy(Some(opa)).await;
+
+        let res = api
+            .dispatch(
+                ApiCommand::NameSpace(NamespaceCommand::Create {
+                    external_id: "testns".into(),
+                }),
+                AuthId::chronicle(),
+            )
+            .await;
+
+        insta::assert_snapshot!(res.unwrap_err().to_string(), @"Identity Chronicle denied write access to namespace testns");
+    }
+
+    #[tokio::test]
+    async fn namespace_access_allowed() {
+        let loader = CliPolicyLoader::from_embedded_policy(
+            "allow_transactions",
+            "allow_transactions.allowed_users",
+        )
+        .unwrap();
+        let opa = ExecutorContext::from_loader(&loader).unwrap();
+
+        let mut api = test_api_with_namespace_policy(Some(opa)).await;
+
+        assert!(api
+            .dispatch(
+                ApiCommand::NameSpace(NamespaceCommand::Create {
+                    external_id: "testns".into(),
+                }),
+                AuthId::chronicle(),
+            )
+            .await
+            .is_ok());
+    }
+
     #[tokio::test]
     async fn create_agent() {
         let mut api = test_api().await;
@@ -2215,6 +4382,46 @@ mod test {
         "###);
     }
 
+    #[tokio::test]
+    async fn idempotency_key_submits_once() {
+        let mut api = test_api().await;
+
+        let create = |external_id: &str| {
+            ApiCommand::Agent(AgentCommand::Create {
+                external_id: external_id.into(),
+                namespace: "testns".into(),
+                attributes: Attributes::default(),
+            })
+            .with_idempotency_key(Some("create-agent".to_owned()))
+        };
+
+        let (_, tx_id) = api
+            .dispatch(create("testagent"), AuthId::chronicle())
+            .await
+            .unwrap()
+            .unwrap();
+
+        match api
+            .api
+            .dispatch(create("testagent"), AuthId::chronicle())
+            .await
+            .unwrap()
+        {
+            ApiResponse::AlreadySubmitted {
+                tx_id: retried_tx_id,
+                ..
+            } => assert_eq!(retried_tx_id, tx_id),
+            other => panic!("expected the first submission, got {other:?}"),
+        }
+
+        let reused = api
+            .api
+            .dispatch(create("otheragent"), AuthId::chronicle())
+            .await;
+
+        insta::assert_snapshot!(reused.unwrap_err().to_string(), @"Idempotency key create-agent was already used for a different command");
+    }
+
     #[tokio::test]
     async fn create_system_activity() {
         let mut api = test_api().await;
@@ -2518,7 +4725,240 @@ mod test {
             )
             .await;
 
-        insta::assert_snapshot!(res.err().unwrap().to_string(), @r###"Contradiction: Contradiction { attribute value change: test Attribute { typ: "test", value: String("test2") } Attribute { typ: "test", value: String("test") } }"###);
+        insta::assert_snapshot!(res.err().unwrap().to_string(), @r###"Contradiction: Contradiction { SetAttributes: attribute value change: test Attribute { typ: "test", value: String("test") } Attribute { typ: "test", value: String("test2") } }"###);
+    }
+
+    #[tokio::test]
+    async fn retract_attribute_allows_new_value() {
+        let mut api = test_api().await;
+
+        let identity = AuthId::chronicle();
+
+        let agent = |value: &str| {
+            ApiCommand::Agent(AgentCommand::Create {
+                external_id: "testagent".into(),
+                namespace: "testns".into(),
+                attributes: Attributes {
+                    typ: Some(DomaintypeId::from_external_id("test")),
+                    attributes: [(
+                        "test".to_owned(),
+                        Attribute {
+                            typ: "test".to_owned(),
+                            value: serde_json::Value::String(value.to_owned()),
+                        },
+                    )]
+                    .into_iter()
+                    .collect(),
+                },
+            })
+        };
+
+        api.dispatch(agent("test"), identity.clone()).await.unwrap();
+
+        let retract = || {
+            ApiCommand::Agent(AgentCommand::RetractAttribute {
+                id: AgentId::from_external_id("testagent"),
+                namespace: "testns".into(),
+                attribute: "test".to_owned(),
+            })
+        };
+
+        let (delta, tx_id) = api
+            .dispatch(retract(), identity.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_ne!(tx_id, ChronicleTransactionId::from("null"));
+        let (_, agent_after) = delta.agents.iter().next().unwrap();
+        assert!(agent_after.attributes.is_empty());
+        assert_eq!(delta.retracted_attributes.len(), 1);
+
+        // Retracting an attribute that is no longer present does nothing
+        let (_, tx_id) = api
+            .dispatch(retract(), identity.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(tx_id, ChronicleTransactionId::from("null"));
+
+        // The attribute can now be set to a different value
+        let (delta, _) = api
+            .dispatch(agent("test2"), identity)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (_, agent_after) = delta.agents.iter().next().unwrap();
+        assert_eq!(
+            agent_after.attributes.get("test").unwrap().value,
+            serde_json::Value::String("test2".to_owned())
+        );
+        assert!(delta.retracted_attributes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn retract_association() {
+        let mut api = test_api().await;
+
+        let identity = AuthId::chronicle();
+
+        api.dispatch(
+            ApiCommand::Activity(ActivityCommand::Associate {
+                id: ActivityId::from_external_id("testactivity"),
+                namespace: "testns".into(),
+                responsible: AgentId::from_external_id("testagent"),
+                role: None,
+            }),
+            identity.clone(),
+        )
+        .await
+        .unwrap();
+
+        let retract = || {
+            ApiCommand::Activity(ActivityCommand::RetractAssociation {
+                id: ActivityId::from_external_id("testactivity"),
+                namespace: "testns".into(),
+                responsible: AgentId::from_external_id("testagent"),
+                role: None,
+            })
+        };
+
+        let (delta, tx_id) = api
+            .dispatch(retract(), identity.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_ne!(tx_id, ChronicleTransactionId::from("null"));
+        assert!(delta.association.is_empty());
+        assert_eq!(delta.retracted_association.len(), 1);
+
+        let (_, tx_id) = api.dispatch(retract(), identity).await.unwrap().unwrap();
+
+        assert_eq!(tx_id, ChronicleTransactionId::from("null"));
+    }
+
+    #[tokio::test]
+    async fn rotate_key() {
+        let mut api = test_api().await;
+
+        let identity = AuthId::chronicle();
+
+        let (prov, _) = api
+            .dispatch(
+                ApiCommand::RotateKey(RotateKeyCommand { import: None }),
+                identity,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let agent = (
+            NamespaceId::from_external_id(SYSTEM_ID, Uuid::parse_str(SYSTEM_UUID).unwrap()),
+            AgentId::from_external_id(CHRONICLE_PK),
+        );
+
+        // The outgoing key is retained as a past identity of the chronicle agent
+        let (_, current) = prov.has_identity.get(&agent).unwrap();
+        let past = prov.had_identity.get(&agent).unwrap();
+
+        assert_eq!(past.len(), 1);
+        assert!(past.iter().all(|(_, identity)| identity != current));
+
+        // The new key is in place once the transition is committed, so it
+        // can authorise a further rotation
+        let (prov, _) = api
+            .dispatch(
+                ApiCommand::RotateKey(RotateKeyCommand { import: None }),
+                AuthId::chronicle(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(prov.had_identity.get(&agent).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn key_rotations_the_ledger_does_not_record_are_discarded() {
+        let mut api = test_api().await;
+
+        // The ledger records a key for the chronicle agent other than the one
+        // held by the api below
+        api.dispatch(
+            ApiCommand::RotateKey(RotateKeyCommand { import: None }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let mut rotating = api_over(&api).await;
+        rotating.policy_name = Some("allow_transactions".into());
+        let current = rotating.signing.chronicle_verifying().await.unwrap();
+        async fn is_pending(signing: &ChronicleSigning) -> bool {
+            signing
+                .pending_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+                .await
+                .unwrap()
+                .is_some()
+        }
+
+        // The transition from a key the ledger does not record is rejected
+        let mut notified = api.api.notify_commit.subscribe();
+        let tx_id = match rotating
+            .rotate_key(NewKey::Generate, AuthId::chronicle())
+            .await
+            .unwrap()
+        {
+            ApiResponse::Submission { tx_id, .. } => tx_id,
+            response => panic!("unexpected response {response:?}"),
+        };
+        assert!(is_pending(&rotating.signing).await);
+
+        loop {
+            match tokio::time::timeout(std::time::Duration::from_secs(5), notified.recv())
+                .await
+                .unwrap()
+                .unwrap()
+            {
+                common::ledger::SubmissionStage::NotCommitted((rejected, ..))
+                    if rejected == tx_id =>
+                {
+                    break
+                }
+                common::ledger::SubmissionStage::Committed(commit, _) => {
+                    assert_ne!(commit.tx_id, tx_id, "the transition was committed")
+                }
+                _ => {}
+            }
+        }
+        rotating.resolve_key_rotation(&tx_id, None).await;
+
+        assert!(rotating.pending_rotation.lock().await.is_none());
+        assert!(!is_pending(&rotating.signing).await);
+        assert_eq!(
+            rotating.signing.chronicle_verifying().await.unwrap(),
+            current
+        );
+
+        // A rotation left pending by an earlier run is discarded on startup,
+        // as the ledger has recorded another key
+        rotating
+            .signing
+            .prepare_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK, NewKey::Generate)
+            .await
+            .unwrap();
+        rotating.recover_key_rotation().await;
+
+        assert!(rotating.pending_rotation.lock().await.is_none());
+        assert!(!is_pending(&rotating.signing).await);
+        assert_eq!(
+            rotating.signing.chronicle_verifying().await.unwrap(),
+            current
+        );
     }
 
     #[tokio::test]
@@ -3456,4 +5896,909 @@ mod test {
         }
         "###);
     }
+
+    #[tokio::test]
+    async fn commands_queued_before_a_restart_are_resubmitted() {
+        use common::prov::ExternalIdPart;
+
+        // A command accepted by an api that stopped before handling it
+        let api = test_api_with(test_config(), |store| {
+            store
+                .enqueue_command(
+                    &ApiCommand::NameSpace(NamespaceCommand::Create {
+                        external_id: "testns".into(),
+                    }),
+                    &AuthId::chronicle(),
+                )
+                .unwrap();
+        })
+        .await;
+        let store = api.api.store.clone();
+
+        let mut commits = api.api.notify_commit.subscribe();
+        loop {
+            if let common::ledger::SubmissionStage::Committed(commit, _) =
+                commits.recv().await.unwrap()
+            {
+                assert!(commit
+                    .delta
+                    .namespaces
+                    .keys()
+                    .any(|namespace| namespace.external_id_part().as_str() == "testns"));
+                break;
+            }
+        }
+
+        assert!(store.queued_commands().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn commands_held_in_store_and_forward_mode_are_forwarded_in_order() {
+        use common::prov::ExternalIdPart;
+
+        // Commands held while the ledger was unreachable, before a restart
+        let api = test_api_with(
+            ApiConfig {
+                store_and_forward: true,
+                ..test_config()
+            },
+            |store| {
+                for external_id in ["first", "second"] {
+                    store
+                        .enqueue_command(
+                            &ApiCommand::Agent(AgentCommand::Create {
+                                external_id: external_id.into(),
+                                namespace: "testns".into(),
+                                attributes: Attributes::type_only(None),
+                            }),
+                            &AuthId::chronicle(),
+                        )
+                        .unwrap();
+                }
+            },
+        )
+        .await;
+
+        let mut commits = api.api.notify_commit.subscribe();
+        let mut agents = vec![];
+        while agents.len() < 2 {
+            if let common::ledger::SubmissionStage::Committed(commit, _) =
+                commits.recv().await.unwrap()
+            {
+                agents.extend(
+                    commit
+                        .delta
+                        .agents
+                        .keys()
+                        .map(|(_, id)| id.external_id_part().to_string()),
+                );
+            }
+        }
+
+        assert_eq!(agents, ["first", "second"]);
+
+        let outbox = api
+            .api
+            .dispatch(ApiCommand::Outbox(OutboxCommand::List), AuthId::chronicle())
+            .await
+            .unwrap();
+        assert!(matches!(outbox, ApiResponse::Outbox { commands } if commands.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn namespaces_are_registered_in_the_system_namespace() {
+        use common::prov::UuidPart;
+
+        let api = test_api_with(
+            ApiConfig {
+                register_namespaces: true,
+                ..test_config()
+            },
+            |_| {},
+        )
+        .await;
+        let store = api.api.store.clone();
+
+        let mut commits = api.api.notify_commit.subscribe();
+        api.api
+            .dispatch(
+                ApiCommand::Agent(AgentCommand::Create {
+                    external_id: "testagent".into(),
+                    namespace: "testns".into(),
+                    attributes: Attributes::type_only(None),
+                }),
+                AuthId::chronicle(),
+            )
+            .await
+            .unwrap();
+
+        let registered = loop {
+            if let common::ledger::SubmissionStage::Committed(commit, _) =
+                commits.recv().await.unwrap()
+            {
+                break crate::namespace_registrations(&commit.delta);
+            }
+        };
+
+        let bound = store
+            .namespace_by_external_id(&mut store.connection().unwrap(), &"testns".into())
+            .unwrap()
+            .0;
+        assert_eq!(registered, [bound.clone()]);
+        assert_eq!(
+            store.registered_namespace(&"testns".into()).unwrap(),
+            Some(*bound.uuid_part())
+        );
+    }
+
+    #[test]
+    fn read_only_transaction_rejects_writes() {
+        use diesel::{sql_query, RunQueryDsl};
+
+        let database = TemporaryDatabase::default();
+        let pool = database.connection_pool().unwrap();
+
+        let written = crate::read_only_transaction(&mut pool.get().unwrap(), |connection| {
+            sql_query("CREATE TABLE read_only_probe (id INTEGER)").execute(connection)
+        });
+
+        assert!(written.is_err());
+    }
+
+    #[tokio::test]
+    async fn committed_operations_are_audited_with_their_identity() {
+        let mut api = test_api().await;
+
+        api.dispatch(
+            ApiCommand::Agent(AgentCommand::Create {
+                external_id: "testagent".into(),
+                namespace: "testns".into(),
+                attributes: Attributes::type_only(None),
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+
+        let store = api.api.store.clone();
+        let subjects = [AgentId::from_external_id("testagent").to_string()];
+        let trail = store
+            .read_only(|connection| store.audit_trail(connection, "testns", &subjects, 10))
+            .unwrap();
+
+        assert!(!trail.is_empty());
+        assert!(trail.iter().all(|record| record.identity == "Chronicle"
+            && record.subject == subjects[0]
+            && record.block_id.is_some()));
+        // Logged from its commit, once
+        assert_eq!(
+            trail
+                .iter()
+                .filter(|record| record.operation == "AgentExists")
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn operations_submitted_elsewhere_are_audited_when_synced() {
+        use async_stl_client::ledger::BlockingLedgerWriter;
+        use chronicle_protocol::messages::ChronicleSubmitTransaction;
+        use common::prov::{
+            operations::{AgentExists, CreateNamespace},
+            ChronicleTransaction,
+        };
+
+        let api = test_api().await;
+
+        // Another deployment sharing the ledger, with its own keys
+        let signing = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::generate_in_memory(),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        let namespace = NamespaceId::from_external_id("testns", SameUuid::uuid());
+        let tx = ChronicleTransaction::new(
+            vec![
+                ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                    namespace.clone(),
+                    "testns",
+                    SameUuid::uuid(),
+                )),
+                ChronicleOperation::AgentExists(AgentExists::new(namespace, "testagent")),
+            ],
+            AuthId::anonymous().signed_identity(&signing).unwrap(),
+        );
+
+        let mut commits = api.api.notify_commit.subscribe();
+        let writer = BlockingLedgerWriter::new(api._tp.ledger.clone());
+        let tx_id = tokio::task::spawn_blocking(move || {
+            writer
+                .submit(&ChronicleSubmitTransaction::new(
+                    tx,
+                    signing,
+                    Some("allow_transactions".to_owned()),
+                ))
+                .map_err(|(_, e)| e)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        loop {
+            if let common::ledger::SubmissionStage::Committed(commit, _) =
+                commits.recv().await.unwrap()
+            {
+                if commit.tx_id.to_string() == tx_id.as_str() {
+                    break;
+                }
+            }
+        }
+
+        let store = api.api.store.clone();
+        let subjects = [AgentId::from_external_id("testagent").to_string()];
+        let trail = store
+            .read_only(|connection| store.audit_trail(connection, "testns", &subjects, 10))
+            .unwrap();
+
+        assert_eq!(trail.len(), 1);
+        assert_eq!(trail[0].operation, "AgentExists");
+        assert_eq!(trail[0].identity, "Anonymous");
+        assert_eq!(trail[0].tx_id, tx_id.as_str());
+        assert!(trail[0].block_id.is_some());
+    }
+
+    #[tokio::test]
+    async fn commits_synced_since_a_transaction_are_replayed() {
+        let mut api = test_api().await;
+
+        for (external_id, namespace) in [
+            ("testagent", "testns"),
+            ("otheragent", "testns"),
+            ("elsewhere", "otherns"),
+            ("thirdagent", "testns"),
+        ] {
+            api.dispatch(
+                ApiCommand::Agent(AgentCommand::Create {
+                    external_id: external_id.into(),
+                    namespace: namespace.into(),
+                    attributes: Attributes::type_only(None),
+                }),
+                AuthId::chronicle(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let store = api.api.store.clone();
+        let subjects = [AgentId::from_external_id("testagent").to_string()];
+        let first = store
+            .read_only(|connection| store.audit_trail(connection, "testns", &subjects, 10))
+            .unwrap()
+            .remove(0)
+            .tx_id;
+
+        let missed = store
+            .read_only(|connection| store.synced_since(connection, &first, "testns"))
+            .unwrap()
+            .unwrap();
+        // Replayed in ledger order, each with the delta it committed, leaving
+        // out those of other namespaces
+        assert_eq!(missed.len(), 2);
+        assert!(missed[0].1.contains("otheragent"));
+        assert!(missed[1].1.contains("thirdagent"));
+        assert_eq!(
+            store.get_last_tx_id().unwrap().unwrap().to_string(),
+            missed[1].0
+        );
+
+        let elsewhere = store
+            .read_only(|connection| store.synced_since(connection, &first, "otherns"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(elsewhere.len(), 1);
+        assert!(elsewhere[0].1.contains("elsewhere"));
+
+        assert!(store
+            .read_only(|connection| store.synced_since(connection, "unknown", "testns"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn large_imports_are_applied_in_bulk() {
+        use crate::persistence::schema::{entity, entity_attribute, usage};
+        use common::prov::operations::{
+            ActivityExists, ActivityUses, CreateNamespace, EntityExists, SetAttributes,
+        };
+        use diesel::prelude::*;
+
+        let mut api = test_api().await;
+
+        let uuid = Uuid::parse_str("6803790d-5891-4dfa-b773-41827d2c630b").unwrap();
+        let namespace = NamespaceId::from_external_id("testns", uuid);
+        let ingest = ActivityId::from_external_id("ingest");
+
+        let mut operations = vec![
+            ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                namespace.clone(),
+                "testns",
+                uuid,
+            )),
+            ChronicleOperation::ActivityExists(ActivityExists {
+                namespace: namespace.clone(),
+                external_id: "ingest".into(),
+            }),
+        ];
+        for i in 0..150 {
+            let id = EntityId::from_external_id(format!("reading-{i}"));
+            operations.extend([
+                ChronicleOperation::EntityExists(EntityExists {
+                    namespace: namespace.clone(),
+                    external_id: format!("reading-{i}").into(),
+                }),
+                ChronicleOperation::SetAttributes(SetAttributes::Entity {
+                    namespace: namespace.clone(),
+                    id: id.clone(),
+                    attributes: Attributes {
+                        typ: Some(DomaintypeId::from_external_id("Reading")),
+                        attributes: [(
+                            "value".to_owned(),
+                            Attribute::new("value", serde_json::json!(i)),
+                        )]
+                        .into_iter()
+                        .collect(),
+                    },
+                }),
+                ChronicleOperation::ActivityUses(ActivityUses {
+                    namespace: namespace.clone(),
+                    id,
+                    activity: ingest.clone(),
+                }),
+            ]);
+        }
+
+        api.dispatch(
+            ApiCommand::Import(ImportCommand {
+                namespace,
+                operations,
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+
+        let mut connection = api.api.store.connection().unwrap();
+        let readings = entity::table
+            .filter(entity::domaintype.eq("Reading"))
+            .count()
+            .get_result::<i64>(&mut connection)
+            .unwrap();
+        let values = entity_attribute::table
+            .filter(entity_attribute::typename.eq("value"))
+            .count()
+            .get_result::<i64>(&mut connection)
+            .unwrap();
+        let usages = usage::table
+            .count()
+            .get_result::<i64>(&mut connection)
+            .unwrap();
+
+        assert_eq!((readings, values, usages), (150, 150, 150));
+    }
+
+    #[tokio::test]
+    async fn text_search_ranks_records_by_matching_terms() {
+        let mut api = test_api().await;
+
+        api.dispatch(
+            ApiCommand::Agent(AgentCommand::Create {
+                external_id: "operator-7".into(),
+                namespace: "testns".into(),
+                attributes: Attributes {
+                    typ: Some(DomaintypeId::from_external_id("Person")),
+                    attributes: [
+                        (
+                            "name".to_owned(),
+                            Attribute::new("name", serde_json::json!("Alice Moreau")),
+                        ),
+                        (
+                            "email".to_owned(),
+                            Attribute::new("email", serde_json::json!("alice.moreau@example.com")),
+                        ),
+                    ]
+                    .into_iter()
+                    .collect(),
+                },
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+        api.dispatch(
+            ApiCommand::Entity(EntityCommand::Create {
+                external_id: "alice-handover-notes".into(),
+                namespace: "testns".into(),
+                attributes: Attributes::type_only(None),
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+
+        let store = &api.api.store;
+        let hits = store
+            .text_search(
+                &mut store.connection().unwrap(),
+                &"testns".into(),
+                "ali* & moreau!",
+                10,
+            )
+            .unwrap();
+
+        let found: Vec<_> = hits
+            .iter()
+            .map(|hit| (hit.kind.as_str(), hit.external_id.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("agent", "operator-7"), ("entity", "alice-handover-notes")]
+        );
+        assert!(hits[0].score > hits[1].score);
+        assert!(store
+            .text_search(&mut store.connection().unwrap(), &"testns".into(), "&!", 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn entities_outliving_their_ttl_are_expired() {
+        let mut api = test_api().await;
+
+        api.dispatch(
+            ApiCommand::Entity(EntityCommand::Create {
+                external_id: "credential".into(),
+                namespace: "testns".into(),
+                attributes: Attributes::type_only(Some(DomaintypeId::from_external_id(
+                    "Credential",
+                ))),
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+
+        let ttls = std::collections::BTreeMap::from([(
+            "Credential".to_owned(),
+            std::time::Duration::from_secs(60),
+        )]);
+        let now = Utc::now().naive_utc();
+        let later = now + chrono::Duration::seconds(120);
+
+        assert_eq!(
+            crate::expiry::expire_entities(&api.api, &ttls, now)
+                .await
+                .unwrap(),
+            0
+        );
+
+        let mut commits = api.api.notify_commit.subscribe();
+        assert_eq!(
+            crate::expiry::expire_entities(&api.api, &ttls, later)
+                .await
+                .unwrap(),
+            1
+        );
+        loop {
+            match commits.recv().await.unwrap() {
+                common::ledger::SubmissionStage::Committed(..) => break,
+                _ => continue,
+            }
+        }
+
+        let store = api.api.store.clone();
+        let unexpired = store
+            .read_only(|connection| store.unexpired_entities(connection, "Credential", later, 10))
+            .unwrap();
+        assert!(unexpired.is_empty());
+        assert_eq!(
+            crate::expiry::expire_entities(&api.api, &ttls, later)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn revisions_carry_forward_the_attributes_they_do_not_override() {
+        let mut api = test_api().await;
+
+        api.dispatch(
+            ApiCommand::Entity(EntityCommand::Create {
+                external_id: "report-v1".into(),
+                namespace: "testns".into(),
+                attributes: Attributes {
+                    typ: Some(DomaintypeId::from_external_id("Report")),
+                    attributes: [
+                        ("title", serde_json::json!("Draft")),
+                        ("pages", serde_json::json!(10)),
+                    ]
+                    .into_iter()
+                    .map(|(name, value)| (name.to_owned(), Attribute::new(name, value)))
+                    .collect(),
+                },
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+
+        api.dispatch(
+            ApiCommand::Entity(EntityCommand::Revise {
+                id: EntityId::from_external_id("report-v1"),
+                namespace: "testns".into(),
+                external_id: "report-v2".into(),
+                attributes: Attributes {
+                    typ: None,
+                    attributes: [(
+                        "title".to_owned(),
+                        Attribute::new("title", serde_json::json!("Final")),
+                    )]
+                    .into_iter()
+                    .collect(),
+                },
+                expire: true,
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+
+        let store = api.api.store.clone();
+        let model = store
+            .read_only(|connection| {
+                store.prov_model_for_entity_id(
+                    connection,
+                    &EntityId::from_external_id("report-v2"),
+                    &"testns".into(),
+                )
+            })
+            .unwrap();
+
+        let revision = model.entities.values().next().unwrap();
+        assert_eq!(
+            revision.domaintypeid,
+            Some(DomaintypeId::from_external_id("Report"))
+        );
+        assert_eq!(
+            revision.attributes["title"].value,
+            serde_json::json!("Final")
+        );
+        assert_eq!(revision.attributes["pages"].value, serde_json::json!(10));
+
+        let derivation = model.derivation.values().flatten().next().unwrap();
+        assert_eq!(derivation.typ, DerivationType::Revision);
+        assert_eq!(derivation.used_id, EntityId::from_external_id("report-v1"));
+
+        let expired = store
+            .read_only(|connection| {
+                store.prov_model_for_activity_id(
+                    connection,
+                    &ActivityId::from_external_id("report-v1-expiry"),
+                    &"testns".into(),
+                )
+            })
+            .unwrap();
+        assert_eq!(expired.usage.values().flatten().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn recipes_record_their_operations_together() {
+        let mut api = test_api().await;
+
+        api.dispatch(
+            ApiCommand::Activity(ActivityCommand::Recipe {
+                external_id: "certification".into(),
+                namespace: "testns".into(),
+                attributes: Attributes::type_only(Some(DomaintypeId::from_external_id(
+                    "ItemCertified",
+                ))),
+                time: None,
+                associations: vec![(
+                    AgentId::from_external_id("inspector"),
+                    Some(Role::from("CERTIFIER")),
+                )],
+                used: vec![EntityId::from_external_id("widget")],
+                generated: vec![EntityId::from_external_id("widget-certificate")],
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+
+        let store = api.api.store.clone();
+        let model = store
+            .read_only(|connection| {
+                store.prov_model_for_activity_id(
+                    connection,
+                    &ActivityId::from_external_id("certification"),
+                    &"testns".into(),
+                )
+            })
+            .unwrap();
+
+        let activity = model.activities.values().next().unwrap();
+        assert_eq!(
+            activity.domaintypeid,
+            Some(DomaintypeId::from_external_id("ItemCertified"))
+        );
+        assert!(activity.started.is_some());
+        assert_eq!(activity.started, activity.ended);
+
+        let association = model.association.values().flatten().next().unwrap();
+        assert_eq!(association.agent_id, AgentId::from_external_id("inspector"));
+        assert_eq!(association.role, Some(Role::from("CERTIFIER")));
+
+        let usage = model.usage.values().flatten().next().unwrap();
+        assert_eq!(usage.entity_id, EntityId::from_external_id("widget"));
+
+        let generation = model.generation.values().flatten().next().unwrap();
+        assert_eq!(
+            generation.generated_id,
+            EntityId::from_external_id("widget-certificate")
+        );
+    }
+
+    #[tokio::test]
+    async fn history_is_replayed_up_to_a_block() {
+        use common::prov::operations::{AgentExists, CreateNamespace};
+        use diesel_migrations::MigrationHarness;
+
+        let database = TemporaryDatabase::default();
+        let pool = database.connection_pool().unwrap();
+        pool.get()
+            .unwrap()
+            .run_pending_migrations(crate::persistence::MIGRATIONS)
+            .unwrap();
+        let store = crate::persistence::Store::new(pool).unwrap();
+
+        let namespace = NamespaceId::from_external_id("testns", SameUuid::uuid());
+        store.namespace_binding("testns", SameUuid::uuid()).unwrap();
+
+        for (block_id, agent) in [("block-1", "first"), ("block-2", "second")] {
+            let delta = ProvModel::from_tx(&[
+                ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                    namespace.clone(),
+                    "testns",
+                    SameUuid::uuid(),
+                )),
+                ChronicleOperation::AgentExists(AgentExists::new(namespace.clone(), agent)),
+            ])
+            .unwrap();
+            let json = delta
+                .to_json()
+                .compact_stable_order()
+                .await
+                .unwrap()
+                .to_string();
+            store
+                .record_history(
+                    &mut store.connection().unwrap(),
+                    &delta,
+                    block_id,
+                    &ChronicleTransactionId::from(agent),
+                    &json,
+                )
+                .unwrap();
+        }
+
+        let (id, deltas) = store
+            .read_only(|connection| {
+                store.history_as_of_block(connection, &"testns".into(), "block-1")
+            })
+            .unwrap();
+        let model = crate::replay_history(&id, deltas).await.unwrap();
+
+        assert!(model
+            .agents
+            .contains_key(&(namespace.clone(), AgentId::from_external_id("first"))));
+        assert!(!model
+            .agents
+            .contains_key(&(namespace.clone(), AgentId::from_external_id("second"))));
+
+        assert!(store
+            .read_only(|connection| {
+                store.history_as_of_block(connection, &"testns".into(), "block-3")
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn seeded_namespace_uuids_are_deterministic() {
+        let seed = Uuid::parse_str("6ba7b810-9dad-11d1-80b4-00c04fd430c8").unwrap();
+        let other_seed = Uuid::new_v4();
+
+        let uuid = crate::namespace_uuid(&seed, &"clinical".into());
+
+        assert_eq!(uuid.get_version_num(), 5);
+        assert_eq!(
+            uuid,
+            Uuid::parse_str("241ad53a-0094-521e-84a7-bb0bde6e8064").unwrap()
+        );
+        assert_ne!(uuid, crate::namespace_uuid(&seed, &"finance".into()));
+        assert_ne!(uuid, crate::namespace_uuid(&other_seed, &"clinical".into()));
+    }
+
+    #[tokio::test]
+    async fn commits_after_one_the_store_rejects_are_applied_and_notified_in_order() {
+        use common::{
+            ledger::{Commit, SubmissionStage},
+            prov::operations::{AgentExists, CreateNamespace},
+        };
+
+        let mut api = test_api().await;
+
+        // A block the ledger committed, for the commits synced below to claim
+        let mut commits = api.api.notify_commit.subscribe();
+        api.dispatch(
+            ApiCommand::NameSpace(NamespaceCommand::Create {
+                external_id: "testns".into(),
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+        let block_id = loop {
+            if let SubmissionStage::Committed(commit, _) = commits.recv().await.unwrap() {
+                break commit.block_id;
+            }
+        };
+
+        let namespace = NamespaceId::from_external_id("batchns", SameUuid::uuid());
+        let agent = |external_id: &str| {
+            ProvModel::from_tx(&[
+                ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                    namespace.clone(),
+                    "batchns",
+                    SameUuid::uuid(),
+                )),
+                ChronicleOperation::AgentExists(AgentExists::new(namespace.clone(), external_id)),
+            ])
+            .unwrap()
+        };
+        // The store refuses records in a namespace the delta does not carry
+        let mut rejected = agent("rejected");
+        rejected.namespaces.clear();
+
+        let sync = api_over(&api).await;
+        let identity = AuthId::chronicle().signed_identity(&sync.signing).unwrap();
+        let mut notified = api.api.notify_commit.subscribe();
+
+        sync.sync(
+            [
+                ("tx-first", agent("first")),
+                ("tx-rejected", rejected),
+                ("tx-last", agent("last")),
+            ]
+            .into_iter()
+            .map(|(tx_id, delta)| {
+                (
+                    Commit::new(tx_id.into(), block_id, Box::new(delta), vec![]),
+                    identity.clone(),
+                )
+            })
+            .collect(),
+        )
+        .await;
+
+        let mut synced = vec![];
+        while let Ok(stage) = notified.try_recv() {
+            if let SubmissionStage::Committed(commit, _) = stage {
+                synced.push(commit.tx_id.to_string());
+            }
+        }
+        assert_eq!(synced, ["tx-first", "tx-last"]);
+
+        let store = &api.api.store;
+        let mut connection = store.connection().unwrap();
+        for (external_id, applied) in [("first", true), ("rejected", false), ("last", true)] {
+            assert_eq!(
+                store
+                    .agent_by_agent_external_id_and_namespace(
+                        &mut connection,
+                        &external_id.into(),
+                        &namespace,
+                    )
+                    .is_ok(),
+                applied
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn followers_notify_commits_in_ledger_order_once_the_leader_applies_them() {
+        use common::{
+            ledger::{Commit, SubmissionStage},
+            prov::operations::{AgentExists, CreateNamespace},
+        };
+        use std::time::Duration;
+
+        let mut api = test_api().await;
+
+        // A block the ledger committed, for the commits followed below to claim
+        let mut commits = api.api.notify_commit.subscribe();
+        api.dispatch(
+            ApiCommand::NameSpace(NamespaceCommand::Create {
+                external_id: "testns".into(),
+            }),
+            AuthId::chronicle(),
+        )
+        .await
+        .unwrap();
+        let block_id = loop {
+            if let SubmissionStage::Committed(commit, _) = commits.recv().await.unwrap() {
+                break commit.block_id;
+            }
+        };
+
+        let namespace = NamespaceId::from_external_id("followns", SameUuid::uuid());
+        let commit = |tx_id: &str| {
+            let delta = ProvModel::from_tx(&[
+                ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                    namespace.clone(),
+                    "followns",
+                    SameUuid::uuid(),
+                )),
+                ChronicleOperation::AgentExists(AgentExists::new(namespace.clone(), tx_id)),
+            ])
+            .unwrap();
+            Commit::new(tx_id.into(), block_id, Box::new(delta), vec![])
+        };
+
+        let leader = api_over(&api).await;
+        let identity = AuthId::chronicle()
+            .signed_identity(&leader.signing)
+            .unwrap();
+        let (notify, mut notified) = tokio::sync::broadcast::channel(10);
+        let follower = FollowerNotifier::spawn(api.api.store.clone(), notify);
+
+        follower
+            .notify(SubmissionStage::committed(
+                commit("first"),
+                identity.clone(),
+            ))
+            .await;
+        follower
+            .notify(SubmissionStage::committed(commit("second"), identity))
+            .await;
+
+        // The second commit is applied, but not notified ahead of the first
+        leader.apply_commits([&commit("second")]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(notified.try_recv().is_err());
+
+        leader.apply_commits([&commit("first")]).await.unwrap();
+        let mut followed = vec![];
+        for _ in 0..2 {
+            if let SubmissionStage::Committed(commit, _) =
+                tokio::time::timeout(Duration::from_secs(5), notified.recv())
+                    .await
+                    .unwrap()
+                    .unwrap()
+            {
+                followed.push(commit.tx_id.to_string());
+            }
+        }
+        assert_eq!(followed, ["first", "second"]);
+    }
 }
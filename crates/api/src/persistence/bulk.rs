@@ -0,0 +1,556 @@
+//! Bulk application of large deltas, such as those of an import. Rather than
+//! upserting each record with its own statements, the agents, activities and
+//! entities of the delta are upserted in multi-row batches and their ids read
+//! back with a query per batch and namespace, then their attributes, usages
+//! and generations are inserted in multi-row batches. Conflicts are resolved
+//! as they are one record at a time: records keep their domain type and times
+//! unless the delta sets them, attributes are overwritten once their
+//! superseded values are kept in attribute_history, and relations that already
+//! exist are left as they are. Only Postgres applies deltas in bulk.
+
+#![cfg_attr(feature = "sqlite", allow(dead_code))]
+
+use std::collections::{BTreeMap, HashMap};
+
+use common::{
+    attributes::Attribute,
+    prov::{ActivityId, EntityId, ExternalIdPart, NamespaceId, ProvModel},
+};
+use diesel::{
+    prelude::*,
+    sql_types::{Nullable, SingleValue, SqlType},
+    upsert::excluded,
+};
+
+use super::{query, schema, DatabaseConnection, Store, StoreError};
+
+/// Deltas with at least this many agents, activities and entities between
+/// them are applied in bulk
+const BULK_THRESHOLD: usize = 100;
+
+/// The most rows inserted by one statement, well within the limit on bind
+/// parameters
+const BATCH_ROWS: usize = 1000;
+
+diesel::sql_function!(fn coalesce<T: SqlType + SingleValue>(x: Nullable<T>, y: Nullable<T>) -> Nullable<T>);
+
+/// The ids of records applied in bulk, by namespace and external id
+#[derive(Debug)]
+pub(super) struct BulkIds {
+    activities: HashMap<(NamespaceId, String), i32>,
+    entities: HashMap<(NamespaceId, String), i32>,
+}
+
+impl Store {
+    /// Apply the agents, activities and entities of `model` in bulk if it has
+    /// enough of them, returning their ids for its relations to be applied in
+    /// bulk too
+    #[cfg(not(feature = "sqlite"))]
+    pub(super) fn apply_records_in_bulk(
+        &self,
+        connection: &mut DatabaseConnection,
+        model: &ProvModel,
+    ) -> Result<Option<BulkIds>, StoreError> {
+        if model.agents.len() + model.activities.len() + model.entities.len() < BULK_THRESHOLD {
+            return Ok(None);
+        }
+
+        let namespaces = model
+            .namespaces
+            .keys()
+            .map(|namespace| {
+                let (_, nsid) =
+                    self.namespace_by_external_id(connection, namespace.external_id_part())?;
+                Ok((namespace.clone(), nsid))
+            })
+            .collect::<Result<HashMap<_, _>, StoreError>>()?;
+
+        self.bulk_apply_agents(connection, model, &namespaces)?;
+
+        Ok(Some(BulkIds {
+            activities: self.bulk_apply_activities(connection, model, &namespaces)?,
+            entities: self.bulk_apply_entities(connection, model, &namespaces)?,
+        }))
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub(super) fn apply_records_in_bulk(
+        &self,
+        _connection: &mut DatabaseConnection,
+        _model: &ProvModel,
+    ) -> Result<Option<BulkIds>, StoreError> {
+        Ok(None)
+    }
+
+    fn bulk_apply_agents(
+        &self,
+        connection: &mut DatabaseConnection,
+        model: &ProvModel,
+        namespaces: &HashMap<NamespaceId, i32>,
+    ) -> Result<(), StoreError> {
+        use schema::{agent::dsl, agent_attribute as attribute};
+
+        let agents = model.agents.values().collect::<Vec<_>>();
+        for batch in agents.chunks(BATCH_ROWS) {
+            let rows = batch
+                .iter()
+                .map(|agent| {
+                    Ok((
+                        dsl::external_id.eq(agent.external_id.as_str()),
+                        dsl::namespace_id.eq(namespace_id(namespaces, &agent.namespaceid)?),
+                        dsl::current.eq(0),
+                        dsl::domaintype.eq(agent
+                            .domaintypeid
+                            .as_ref()
+                            .map(|x| x.external_id_part().as_str())),
+                    ))
+                })
+                .collect::<Result<Vec<_>, StoreError>>()?;
+
+            diesel::insert_into(dsl::agent)
+                .values(rows)
+                .on_conflict((dsl::namespace_id, dsl::external_id))
+                .do_update()
+                .set(dsl::domaintype.eq(coalesce(excluded(dsl::domaintype), dsl::domaintype)))
+                .execute(connection)?;
+
+            let mut ids = HashMap::new();
+            for (namespace, nsid) in namespaces {
+                let external_ids = batch
+                    .iter()
+                    .filter(|agent| &agent.namespaceid == namespace)
+                    .map(|agent| agent.external_id.as_str())
+                    .collect::<Vec<_>>();
+                if external_ids.is_empty() {
+                    continue;
+                }
+
+                let stored = dsl::agent
+                    .filter(dsl::namespace_id.eq(nsid))
+                    .filter(dsl::external_id.eq_any(external_ids))
+                    .select((dsl::id, dsl::external_id))
+                    .load::<(i32, String)>(connection)?;
+                ids.extend(
+                    stored
+                        .into_iter()
+                        .map(|(id, external_id)| ((namespace.clone(), external_id), id)),
+                );
+            }
+
+            let records = batch
+                .iter()
+                .map(|agent| {
+                    Ok((
+                        record_id(&ids, &agent.namespaceid, agent.external_id.as_str())?,
+                        &agent.attributes,
+                    ))
+                })
+                .collect::<Result<Vec<_>, StoreError>>()?;
+
+            let current = attribute::table
+                .filter(attribute::agent_id.eq_any(records.iter().map(|(id, _)| *id)))
+                .select((attribute::agent_id, attribute::typename, attribute::value))
+                .load::<(i32, String, String)>(connection)?;
+            self.supersede_in_bulk(connection, "agent", current, &records)?;
+
+            let values = records
+                .iter()
+                .flat_map(|(id, attributes)| {
+                    attributes
+                        .values()
+                        .map(|Attribute { typ, value, .. }| query::AgentAttribute {
+                            agent_id: *id,
+                            typename: typ.to_owned(),
+                            value: value.to_string(),
+                        })
+                })
+                .collect::<Vec<_>>();
+            for values in values.chunks(BATCH_ROWS) {
+                diesel::insert_into(attribute::table)
+                    .values(values)
+                    .on_conflict((attribute::agent_id, attribute::typename))
+                    .do_update()
+                    .set(attribute::value.eq(excluded(attribute::value)))
+                    .execute(connection)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn bulk_apply_activities(
+        &self,
+        connection: &mut DatabaseConnection,
+        model: &ProvModel,
+        namespaces: &HashMap<NamespaceId, i32>,
+    ) -> Result<HashMap<(NamespaceId, String), i32>, StoreError> {
+        use schema::{activity::dsl, activity_attribute as attribute};
+
+        let mut ids = HashMap::new();
+        let activities = model.activities.values().collect::<Vec<_>>();
+        for batch in activities.chunks(BATCH_ROWS) {
+            let rows = batch
+                .iter()
+                .map(|activity| {
+                    Ok((
+                        dsl::external_id.eq(activity.external_id.as_str()),
+                        dsl::namespace_id.eq(namespace_id(namespaces, &activity.namespaceid)?),
+                        dsl::started.eq(activity.started.map(|t| t.naive_utc())),
+                        dsl::ended.eq(activity.ended.map(|t| t.naive_utc())),
+                        dsl::domaintype.eq(activity
+                            .domaintypeid
+                            .as_ref()
+                            .map(|x| x.external_id_part().as_str())),
+                    ))
+                })
+                .collect::<Result<Vec<_>, StoreError>>()?;
+
+            diesel::insert_into(dsl::activity)
+                .values(rows)
+                .on_conflict((dsl::external_id, dsl::namespace_id))
+                .do_update()
+                .set((
+                    dsl::domaintype.eq(coalesce(excluded(dsl::domaintype), dsl::domaintype)),
+                    dsl::started.eq(coalesce(excluded(dsl::started), dsl::started)),
+                    dsl::ended.eq(coalesce(excluded(dsl::ended), dsl::ended)),
+                ))
+                .execute(connection)?;
+
+            for (namespace, nsid) in namespaces {
+                let external_ids = batch
+                    .iter()
+                    .filter(|activity| &activity.namespaceid == namespace)
+                    .map(|activity| activity.external_id.as_str())
+                    .collect::<Vec<_>>();
+                if external_ids.is_empty() {
+                    continue;
+                }
+
+                let stored = dsl::activity
+                    .filter(dsl::namespace_id.eq(nsid))
+                    .filter(dsl::external_id.eq_any(external_ids))
+                    .select((dsl::id, dsl::external_id))
+                    .load::<(i32, String)>(connection)?;
+                ids.extend(
+                    stored
+                        .into_iter()
+                        .map(|(id, external_id)| ((namespace.clone(), external_id), id)),
+                );
+            }
+
+            let records = batch
+                .iter()
+                .map(|activity| {
+                    Ok((
+                        record_id(&ids, &activity.namespaceid, activity.external_id.as_str())?,
+                        &activity.attributes,
+                    ))
+                })
+                .collect::<Result<Vec<_>, StoreError>>()?;
+
+            let current = attribute::table
+                .filter(attribute::activity_id.eq_any(records.iter().map(|(id, _)| *id)))
+                .select((
+                    attribute::activity_id,
+                    attribute::typename,
+                    attribute::value,
+                ))
+                .load::<(i32, String, String)>(connection)?;
+            self.supersede_in_bulk(connection, "activity", current, &records)?;
+
+            let values = records
+                .iter()
+                .flat_map(|(id, attributes)| {
+                    attributes.values().map(|Attribute { typ, value, .. }| {
+                        query::ActivityAttribute {
+                            activity_id: *id,
+                            typename: typ.to_owned(),
+                            value: value.to_string(),
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for values in values.chunks(BATCH_ROWS) {
+                diesel::insert_into(attribute::table)
+                    .values(values)
+                    .on_conflict((attribute::activity_id, attribute::typename))
+                    .do_update()
+                    .set(attribute::value.eq(excluded(attribute::value)))
+                    .execute(connection)?;
+            }
+        }
+
+        Ok(ids)
+    }
+
+    fn bulk_apply_entities(
+        &self,
+        connection: &mut DatabaseConnection,
+        model: &ProvModel,
+        namespaces: &HashMap<NamespaceId, i32>,
+    ) -> Result<HashMap<(NamespaceId, String), i32>, StoreError> {
+        use schema::{entity::dsl, entity_attribute as attribute};
+
+        let mut ids = HashMap::new();
+        let entities = model.entities.values().collect::<Vec<_>>();
+        for batch in entities.chunks(BATCH_ROWS) {
+            let rows = batch
+                .iter()
+                .map(|entity| {
+                    Ok((
+                        dsl::external_id.eq(entity.external_id.as_str()),
+                        dsl::namespace_id.eq(namespace_id(namespaces, &entity.namespaceid)?),
+                        dsl::domaintype.eq(entity
+                            .domaintypeid
+                            .as_ref()
+                            .map(|x| x.external_id_part().as_str())),
+                    ))
+                })
+                .collect::<Result<Vec<_>, StoreError>>()?;
+
+            diesel::insert_into(dsl::entity)
+                .values(rows)
+                .on_conflict((dsl::namespace_id, dsl::external_id))
+                .do_update()
+                .set(dsl::domaintype.eq(coalesce(excluded(dsl::domaintype), dsl::domaintype)))
+                .execute(connection)?;
+
+            for (namespace, nsid) in namespaces {
+                let external_ids = batch
+                    .iter()
+                    .filter(|entity| &entity.namespaceid == namespace)
+                    .map(|entity| entity.external_id.as_str())
+                    .collect::<Vec<_>>();
+                if external_ids.is_empty() {
+                    continue;
+                }
+
+                let stored = dsl::entity
+                    .filter(dsl::namespace_id.eq(nsid))
+                    .filter(dsl::external_id.eq_any(external_ids))
+                    .select((dsl::id, dsl::external_id))
+                    .load::<(i32, String)>(connection)?;
+                ids.extend(
+                    stored
+                        .into_iter()
+                        .map(|(id, external_id)| ((namespace.clone(), external_id), id)),
+                );
+            }
+
+            let records = batch
+                .iter()
+                .map(|entity| {
+                    Ok((
+                        record_id(&ids, &entity.namespaceid, entity.external_id.as_str())?,
+                        &entity.attributes,
+                    ))
+                })
+                .collect::<Result<Vec<_>, StoreError>>()?;
+
+            let current = attribute::table
+                .filter(attribute::entity_id.eq_any(records.iter().map(|(id, _)| *id)))
+                .select((attribute::entity_id, attribute::typename, attribute::value))
+                .load::<(i32, String, String)>(connection)?;
+            self.supersede_in_bulk(connection, "entity", current, &records)?;
+
+            let values = records
+                .iter()
+                .flat_map(|(id, attributes)| {
+                    attributes
+                        .values()
+                        .map(|Attribute { typ, value, .. }| query::EntityAttribute {
+                            entity_id: *id,
+                            typename: typ.to_owned(),
+                            value: value.to_string(),
+                        })
+                })
+                .collect::<Vec<_>>();
+            for values in values.chunks(BATCH_ROWS) {
+                diesel::insert_into(attribute::table)
+                    .values(values)
+                    .on_conflict((attribute::entity_id, attribute::typename))
+                    .do_update()
+                    .set(attribute::value.eq(excluded(attribute::value)))
+                    .execute(connection)?;
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Keep the values of attributes about to be overwritten in
+    /// attribute_history, given the current attributes of the records
+    fn supersede_in_bulk(
+        &self,
+        connection: &mut DatabaseConnection,
+        record_type: &str,
+        current: Vec<(i32, String, String)>,
+        incoming: &[(i32, &BTreeMap<String, Attribute>)],
+    ) -> Result<(), StoreError> {
+        let mut current_by_record = HashMap::<i32, Vec<(String, String)>>::new();
+        for (id, typename, value) in current {
+            current_by_record
+                .entry(id)
+                .or_default()
+                .push((typename, value));
+        }
+
+        for (id, attributes) in incoming {
+            if let Some(current) = current_by_record.remove(id) {
+                self.record_superseded_attributes(
+                    connection,
+                    record_type,
+                    *id,
+                    current,
+                    attributes,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The id of an activity applied in bulk, or already stored
+    fn bulk_activity_id(
+        &self,
+        connection: &mut DatabaseConnection,
+        ids: &BulkIds,
+        namespace: &NamespaceId,
+        activity: &ActivityId,
+    ) -> Result<i32, StoreError> {
+        match ids
+            .activities
+            .get(&(namespace.clone(), activity.external_id_part().to_string()))
+        {
+            Some(id) => Ok(*id),
+            None => Ok(self
+                .activity_by_activity_external_id_and_namespace(
+                    connection,
+                    activity.external_id_part(),
+                    namespace,
+                )?
+                .id),
+        }
+    }
+
+    /// The id of an entity applied in bulk, or already stored
+    fn bulk_entity_id(
+        &self,
+        connection: &mut DatabaseConnection,
+        ids: &BulkIds,
+        namespace: &NamespaceId,
+        entity: &EntityId,
+    ) -> Result<i32, StoreError> {
+        match ids
+            .entities
+            .get(&(namespace.clone(), entity.external_id_part().to_string()))
+        {
+            Some(id) => Ok(*id),
+            None => Ok(self
+                .entity_by_entity_external_id_and_namespace(
+                    connection,
+                    entity.external_id_part(),
+                    namespace,
+                )?
+                .id),
+        }
+    }
+
+    pub(super) fn bulk_apply_usage(
+        &self,
+        connection: &mut DatabaseConnection,
+        model: &ProvModel,
+        ids: &BulkIds,
+    ) -> Result<(), StoreError> {
+        use schema::usage::dsl as link;
+
+        let mut links = vec![];
+        for ((namespace, _), usages) in model.usage.iter() {
+            for usage in usages {
+                links.push((
+                    self.bulk_activity_id(connection, ids, namespace, &usage.activity_id)?,
+                    self.bulk_entity_id(connection, ids, namespace, &usage.entity_id)?,
+                ));
+            }
+        }
+
+        for links in links.chunks(BATCH_ROWS) {
+            diesel::insert_into(schema::usage::table)
+                .values(
+                    links
+                        .iter()
+                        .map(|(activity_id, entity_id)| {
+                            (
+                                link::activity_id.eq(*activity_id),
+                                link::entity_id.eq(*entity_id),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .on_conflict_do_nothing()
+                .execute(connection)?;
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn bulk_apply_generation(
+        &self,
+        connection: &mut DatabaseConnection,
+        model: &ProvModel,
+        ids: &BulkIds,
+    ) -> Result<(), StoreError> {
+        use schema::generation::dsl as link;
+
+        let mut links = vec![];
+        for ((namespace, _), generations) in model.generation.iter() {
+            for generation in generations {
+                links.push((
+                    self.bulk_activity_id(connection, ids, namespace, &generation.activity_id)?,
+                    self.bulk_entity_id(connection, ids, namespace, &generation.generated_id)?,
+                ));
+            }
+        }
+
+        for links in links.chunks(BATCH_ROWS) {
+            diesel::insert_into(schema::generation::table)
+                .values(
+                    links
+                        .iter()
+                        .map(|(activity_id, entity_id)| {
+                            (
+                                link::activity_id.eq(*activity_id),
+                                link::generated_entity_id.eq(*entity_id),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .on_conflict_do_nothing()
+                .execute(connection)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The id of a namespace of the delta, which must describe the namespaces of
+/// its records
+fn namespace_id(
+    namespaces: &HashMap<NamespaceId, i32>,
+    namespace: &NamespaceId,
+) -> Result<i32, StoreError> {
+    namespaces
+        .get(namespace)
+        .copied()
+        .ok_or(StoreError::InvalidNamespace {})
+}
+
+fn record_id(
+    ids: &HashMap<(NamespaceId, String), i32>,
+    namespace: &NamespaceId,
+    external_id: &str,
+) -> Result<i32, StoreError> {
+    ids.get(&(namespace.clone(), external_id.to_owned()))
+        .copied()
+        .ok_or(StoreError::RecordNotFound {})
+}
@@ -0,0 +1,120 @@
+//! Election of the single replica that applies ledger updates to a shared
+//! store. Replicas contend for a Postgres session-level advisory lock, held on
+//! a connection taken from the pool for as long as the replica leads. If the
+//! leader exits or loses its connection, Postgres releases the lock and the
+//! next replica to try takes over. A leader that is dropped releases the lock
+//! itself, as its connection returns to the pool rather than closing.
+
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+#[cfg(not(feature = "sqlite"))]
+use tracing::{info, warn};
+
+use super::DatabaseConnection;
+
+/// Advisory lock key for leadership of the sync loop, the bytes of "chronicl"
+#[cfg(not(feature = "sqlite"))]
+const SYNC_LEADER_LOCK_KEY: i64 = 0x6368_726f_6e69_636c;
+
+#[cfg(not(feature = "sqlite"))]
+diesel::sql_function!(fn pg_try_advisory_lock(key: diesel::sql_types::BigInt) -> diesel::sql_types::Bool);
+
+#[cfg(not(feature = "sqlite"))]
+diesel::sql_function!(fn pg_advisory_unlock(key: diesel::sql_types::BigInt) -> diesel::sql_types::Bool);
+
+#[cfg_attr(feature = "sqlite", allow(dead_code))]
+pub(crate) struct SyncLeadership {
+    pool: Pool<ConnectionManager<DatabaseConnection>>,
+    lock: Option<PooledConnection<ConnectionManager<DatabaseConnection>>>,
+}
+
+impl SyncLeadership {
+    pub(crate) fn new(pool: Pool<ConnectionManager<DatabaseConnection>>) -> Self {
+        Self { pool, lock: None }
+    }
+
+    /// Confirm that this replica still holds leadership, or try to acquire it
+    /// if not. Returns whether this replica now leads.
+    #[cfg(not(feature = "sqlite"))]
+    pub(crate) fn try_lead(&mut self) -> bool {
+        use diesel::{dsl::sql, sql_types::Bool, RunQueryDsl};
+
+        if let Some(connection) = self.lock.as_mut() {
+            if diesel::select(sql::<Bool>("true"))
+                .execute(&mut **connection)
+                .is_ok()
+            {
+                return true;
+            }
+
+            warn!("Lost connection holding sync leadership");
+            self.lock = None;
+        }
+
+        let mut connection = match self.pool.get() {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!(?e, "Cannot contend for sync leadership");
+                return false;
+            }
+        };
+
+        match diesel::select(pg_try_advisory_lock(SYNC_LEADER_LOCK_KEY))
+            .get_result::<bool>(&mut *connection)
+        {
+            Ok(true) => {
+                info!("Acquired sync leadership");
+                self.lock = Some(connection);
+                true
+            }
+            Ok(false) => false,
+            Err(e) => {
+                warn!(?e, "Cannot contend for sync leadership");
+                false
+            }
+        }
+    }
+
+    /// An SQLite store belongs to a single process, which always leads
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn try_lead(&mut self) -> bool {
+        true
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+impl Drop for SyncLeadership {
+    fn drop(&mut self) {
+        use diesel::RunQueryDsl;
+
+        if let Some(mut connection) = self.lock.take() {
+            if let Err(e) =
+                diesel::select(pg_advisory_unlock(SYNC_LEADER_LOCK_KEY)).execute(&mut *connection)
+            {
+                warn!(?e, "Cannot release sync leadership");
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "sqlite")))]
+mod test {
+    use common::database::TemporaryDatabase;
+
+    use super::SyncLeadership;
+
+    #[test]
+    fn one_replica_leads_until_it_is_dropped() {
+        let database = TemporaryDatabase::default();
+        let mut first = SyncLeadership::new(database.connection_pool().unwrap());
+        let mut second = SyncLeadership::new(database.connection_pool().unwrap());
+
+        assert!(first.try_lead());
+        assert!(!second.try_lead());
+        // Leadership is kept by confirming it
+        assert!(first.try_lead());
+        assert!(!second.try_lead());
+
+        drop(first);
+        assert!(second.try_lead());
+    }
+}
@@ -1,16 +1,23 @@
-use std::{collections::BTreeMap, str::FromStr, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    str::FromStr,
+    time::Duration,
+};
 
 use async_stl_client::ledger::{BlockId, BlockIdError};
-use chrono::DateTime;
+use chrono::{DateTime, NaiveDateTime};
 
 use chrono::Utc;
 use common::{
     attributes::Attribute,
+    commands::ApiCommand,
+    identity::AuthId,
     prov::{
-        operations::DerivationType, Activity, ActivityId, Agent, AgentId, Association, Attribution,
+        operations::{ChronicleOperation, DerivationType},
+        Activity, ActivityId, Agent, AgentId, Association, Attribution, ChronicleIri,
         ChronicleTransactionId, ChronicleTransactionIdError, Delegation, Derivation, DomaintypeId,
         Entity, EntityId, ExternalId, ExternalIdPart, Generation, Identity, IdentityId, Namespace,
-        NamespaceId, ProvModel, PublicKeyPart, Role, Usage,
+        NamespaceId, ParseIriError, ProvModel, PublicKeyPart, Role, Usage, SYSTEM_ID,
     },
 };
 use derivative::*;
@@ -18,16 +25,114 @@ use derivative::*;
 use diesel::{
     prelude::*,
     r2d2::{ConnectionManager, Pool, PooledConnection},
-    PgConnection,
+    upsert::excluded,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations};
 use thiserror::Error;
 use tracing::{debug, instrument, warn};
 use uuid::Uuid;
 
+use crate::webhooks::{queue_deliveries, PreparedDelivery};
+
+mod bulk;
+mod leader;
 mod query;
 pub(crate) mod schema;
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+pub(crate) use leader::SyncLeadership;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "sqlite")] {
+        /// Embedded SQLite, for standalone and development deployments
+        pub type DatabaseConnection = diesel::SqliteConnection;
+        pub type DatabaseBackend = diesel::sqlite::Sqlite;
+        pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations-sqlite");
+    } else {
+        pub type DatabaseConnection = diesel::PgConnection;
+        pub type DatabaseBackend = diesel::pg::Pg;
+        pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+    }
+}
+
+/// Walks upstream from an entity along derivation, generation and usage edges,
+/// stopping after a number of hops. Bounding the depth also bounds the walk
+/// when the provenance graph contains cycles.
+macro_rules! lineage_sql {
+    ($root:literal, $depth:literal) => {
+        concat!(
+            "WITH RECURSIVE edge(from_kind, from_id, to_kind, to_id) AS (",
+            "SELECT CAST('entity' AS TEXT), generated_entity_id, CAST('entity' AS TEXT), used_entity_id FROM derivation ",
+            "UNION ALL ",
+            "SELECT CAST('entity' AS TEXT), generated_entity_id, CAST('activity' AS TEXT), activity_id FROM generation ",
+            "UNION ALL ",
+            "SELECT CAST('activity' AS TEXT), activity_id, CAST('entity' AS TEXT), entity_id FROM usage",
+            "), lineage(kind, id, depth) AS (",
+            "SELECT CAST('entity' AS TEXT), CAST(", $root, " AS INTEGER), 0 ",
+            "UNION ",
+            "SELECT edge.to_kind, edge.to_id, lineage.depth + 1 FROM edge ",
+            "INNER JOIN lineage ON edge.from_kind = lineage.kind AND edge.from_id = lineage.id ",
+            "WHERE lineage.depth < ", $depth,
+            ") SELECT DISTINCT kind, id FROM lineage"
+        )
+    };
+}
+
+#[cfg(feature = "sqlite")]
+const LINEAGE_SQL: &str = lineage_sql!("?", "?");
+#[cfg(not(feature = "sqlite"))]
+const LINEAGE_SQL: &str = lineage_sql!("$1", "$2");
+
+/// The external ids and attribute values of the agents, activities and
+/// entities in a namespace, one searchable document each
+macro_rules! text_search_documents {
+    ($namespace:literal) => {
+        concat!(
+            "WITH document(kind, record_id, external_id, domaintype, text) AS (",
+            "SELECT CAST('agent' AS TEXT), agent.id, agent.external_id, agent.domaintype, agent.external_id FROM agent ",
+            "INNER JOIN namespace ON namespace.id = agent.namespace_id WHERE namespace.external_id = ", $namespace, " ",
+            "UNION ALL ",
+            "SELECT CAST('agent' AS TEXT), agent.id, agent.external_id, agent.domaintype, agent_attribute.value FROM agent ",
+            "INNER JOIN agent_attribute ON agent_attribute.agent_id = agent.id ",
+            "INNER JOIN namespace ON namespace.id = agent.namespace_id WHERE namespace.external_id = ", $namespace, " ",
+            "UNION ALL ",
+            "SELECT CAST('activity' AS TEXT), activity.id, activity.external_id, activity.domaintype, activity.external_id FROM activity ",
+            "INNER JOIN namespace ON namespace.id = activity.namespace_id WHERE namespace.external_id = ", $namespace, " ",
+            "UNION ALL ",
+            "SELECT CAST('activity' AS TEXT), activity.id, activity.external_id, activity.domaintype, activity_attribute.value FROM activity ",
+            "INNER JOIN activity_attribute ON activity_attribute.activity_id = activity.id ",
+            "INNER JOIN namespace ON namespace.id = activity.namespace_id WHERE namespace.external_id = ", $namespace, " ",
+            "UNION ALL ",
+            "SELECT CAST('entity' AS TEXT), entity.id, entity.external_id, entity.domaintype, entity.external_id FROM entity ",
+            "INNER JOIN namespace ON namespace.id = entity.namespace_id WHERE namespace.external_id = ", $namespace, " ",
+            "UNION ALL ",
+            "SELECT CAST('entity' AS TEXT), entity.id, entity.external_id, entity.domaintype, entity_attribute.value FROM entity ",
+            "INNER JOIN entity_attribute ON entity_attribute.entity_id = entity.id ",
+            "INNER JOIN namespace ON namespace.id = entity.namespace_id WHERE namespace.external_id = ", $namespace,
+            ") "
+        )
+    };
+}
+
+/// Ranks records by how well their documents match a prefix query, using the
+/// text search indexes over external ids and attribute values
+#[cfg(not(feature = "sqlite"))]
+const TEXT_SEARCH_SQL: &str = concat!(
+    text_search_documents!("$1"),
+    "SELECT kind, external_id, domaintype, ",
+    "CAST(SUM(ts_rank(to_tsvector('simple', text), to_tsquery('simple', $2))) AS DOUBLE PRECISION) AS score ",
+    "FROM document WHERE to_tsvector('simple', text) @@ to_tsquery('simple', $2) ",
+    "GROUP BY kind, record_id, external_id, domaintype ",
+    "ORDER BY score DESC, external_id LIMIT $3"
+);
+
+/// Terms of a search query, lowercased, ignoring punctuation and operators
+fn search_terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
 
 #[derive(Error, Debug)]
 pub enum StoreError {
@@ -68,6 +173,57 @@ pub enum StoreError {
 
     #[error("Invalid UUID: {0}")]
     Uuid(#[from] uuid::Error),
+
+    #[error("Invalid IRI: {0}")]
+    Iri(#[from] ParseIriError),
+}
+
+/// How long an idempotency key is remembered after the command sent with it
+/// is submitted
+pub const IDEMPOTENCY_KEY_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Who holds an idempotency key after an attempt to reserve it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum IdempotencyKeyHolder {
+    /// The key was free and is now reserved for the command
+    Reserved,
+    /// A command with `command_digest` reserved the key and has not finished
+    /// submitting
+    Pending { command_digest: String },
+    /// A command with `command_digest` was submitted with the key
+    Submitted {
+        command_digest: String,
+        subject: ChronicleIri,
+        tx_id: ChronicleTransactionId,
+    },
+}
+
+/// The kinds of record with a row of their own, as named in
+/// `attribute_history` and `backfill_progress`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RecordType {
+    Agent,
+    Activity,
+    Entity,
+}
+
+impl RecordType {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::Agent => "agent",
+            RecordType::Activity => "activity",
+            RecordType::Entity => "entity",
+        }
+    }
+}
+
+/// A chunk of records read in row order, with their relations
+#[derive(Debug)]
+pub(crate) struct RecordChunk {
+    pub model: ProvModel,
+    pub records: usize,
+    /// The row id of the last record in the chunk
+    pub last_id: i32,
 }
 
 #[derive(Debug)]
@@ -77,6 +233,54 @@ pub struct ConnectionOptions {
     pub busy_timeout: Option<Duration>,
 }
 
+#[cfg(feature = "sqlite")]
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            enable_wal: true,
+            enable_foreign_keys: true,
+            busy_timeout: Some(Duration::from_secs(2)),
+        }
+    }
+}
+
+/// SQLite connections need their pragmas applied per connection, so we apply
+/// them as the pool opens each one
+#[cfg(feature = "sqlite")]
+impl diesel::r2d2::CustomizeConnection<DatabaseConnection, diesel::r2d2::Error>
+    for ConnectionOptions
+{
+    fn on_acquire(&self, conn: &mut DatabaseConnection) -> Result<(), diesel::r2d2::Error> {
+        use diesel::connection::SimpleConnection;
+
+        let mut pragmas = String::new();
+        if self.enable_wal {
+            pragmas.push_str("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;");
+        }
+        if self.enable_foreign_keys {
+            pragmas.push_str("PRAGMA foreign_keys = ON;");
+        }
+        if let Some(d) = self.busy_timeout {
+            pragmas.push_str(&format!("PRAGMA busy_timeout = {};", d.as_millis()));
+        }
+
+        conn.batch_execute(&pragmas)
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// Names of the embedded migrations not yet applied to the database
+pub fn pending_migrations(connection: &mut DatabaseConnection) -> Result<Vec<String>, StoreError> {
+    use diesel_migrations::MigrationHarness;
+
+    Ok(connection
+        .pending_migrations(MIGRATIONS)
+        .map_err(StoreError::DbMigration)?
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect())
+}
+
 #[instrument]
 fn sleeper(attempts: i32) -> bool {
     warn!(attempts, "SQLITE_BUSY, retrying");
@@ -84,11 +288,111 @@ fn sleeper(attempts: i32) -> bool {
     true
 }
 
+/// Open a pool against an SQLite database file, creating it if necessary
+#[cfg(feature = "sqlite")]
+pub fn sqlite_pool(
+    path: &str,
+    options: ConnectionOptions,
+) -> Result<Pool<ConnectionManager<DatabaseConnection>>, StoreError> {
+    Ok(Pool::builder()
+        .connection_customizer(Box::new(options))
+        .build(ConnectionManager::<DatabaseConnection>::new(path))?)
+}
+
+/// Run `f` in a read-only, repeatable-read transaction. Postgres rejects any
+/// write attempted within it, so query paths can be pointed at a replica, and
+/// the statements `f` issues all see the same snapshot.
+#[cfg(not(feature = "sqlite"))]
+pub fn read_only_transaction<T, E>(
+    connection: &mut DatabaseConnection,
+    f: impl FnOnce(&mut DatabaseConnection) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: From<diesel::result::Error>,
+{
+    connection
+        .build_transaction()
+        .read_only()
+        .repeatable_read()
+        .run(f)
+}
+
+/// Run `f` in a transaction with the connection set to `query_only`, SQLite's
+/// closest equivalent to a read-only transaction. SQLite transactions already
+/// read from a single snapshot.
+#[cfg(feature = "sqlite")]
+pub fn read_only_transaction<T, E>(
+    connection: &mut DatabaseConnection,
+    f: impl FnOnce(&mut DatabaseConnection) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: From<diesel::result::Error>,
+{
+    use diesel::connection::SimpleConnection;
+
+    connection.transaction(|connection| {
+        connection.batch_execute("PRAGMA query_only = ON")?;
+        let result = f(connection);
+        connection.batch_execute("PRAGMA query_only = OFF")?;
+        result
+    })
+}
+
+/// Scope the rest of the transaction on `connection` to the records of the
+/// namespace with external id `tenant`, as enforced by the row-level security
+/// policies of the Postgres schema.
+#[cfg(not(feature = "sqlite"))]
+pub fn bind_tenant(
+    connection: &mut DatabaseConnection,
+    tenant: &str,
+) -> Result<(), diesel::result::Error> {
+    diesel::sql_query("select set_config('chronicle.tenant', $1, true)")
+        .bind::<diesel::sql_types::Text, _>(tenant)
+        .execute(connection)?;
+    Ok(())
+}
+
+/// SQLite has no row-level security, so records cannot be scoped to a tenant
+#[cfg(feature = "sqlite")]
+pub fn bind_tenant(
+    _connection: &mut DatabaseConnection,
+    _tenant: &str,
+) -> Result<(), diesel::result::Error> {
+    Ok(())
+}
+
+/// A delta committed to the ledger, with the block and transaction that
+/// carried it and its compact JSON-LD, to be kept as history, and its
+/// deliveries to webhooks, to be queued
+#[derive(Debug)]
+pub(crate) struct SyncedDelta {
+    pub prov: Box<ProvModel>,
+    pub block_id: BlockId,
+    pub tx_id: ChronicleTransactionId,
+    pub delta: String,
+    pub deliveries: Vec<PreparedDelivery>,
+}
+
+/// The pool a read is made from, where reads can be directed at a replica
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadFrom {
+    /// The database Chronicle writes to, for reads that must see its latest
+    /// writes
+    Primary,
+    /// The read replica, if one is configured, and otherwise the primary
+    Replica,
+}
+
 #[derive(Derivative)]
 #[derivative(Debug, Clone)]
 pub struct Store {
     #[derivative(Debug = "ignore")]
-    pool: Pool<ConnectionManager<PgConnection>>,
+    pool: Pool<ConnectionManager<DatabaseConnection>>,
+    /// A pool of connections to a read replica of the database, if any
+    #[derivative(Debug = "ignore")]
+    read_pool: Option<Pool<ConnectionManager<DatabaseConnection>>>,
+    /// The namespace reads are scoped to, if any
+    tenant: Option<String>,
 }
 
 impl Store {
@@ -113,10 +417,36 @@ impl Store {
         Ok(())
     }
 
+    /// The UUID registered on the ledger for the namespace `external_id`, as
+    /// synced from its registration entity in the system namespace
+    #[instrument(skip(self))]
+    pub(crate) fn registered_namespace(
+        &self,
+        external_id: &ExternalId,
+    ) -> Result<Option<Uuid>, StoreError> {
+        let registered = schema::entity::table
+            .inner_join(schema::namespace::table)
+            .inner_join(schema::entity_attribute::table)
+            .filter(schema::namespace::external_id.eq(SYSTEM_ID))
+            .filter(schema::entity::external_id.eq(format!("namespace-{external_id}")))
+            .filter(schema::entity_attribute::typename.eq("namespaceUuid"))
+            .select(schema::entity_attribute::value)
+            .first::<String>(&mut self.connection()?)
+            .optional()?;
+
+        registered
+            .map(|value| {
+                let value: serde_json::Value = serde_json::from_str(&value)?;
+                Ok(value.as_str().and_then(|uuid| Uuid::parse_str(uuid).ok()))
+            })
+            .transpose()
+            .map(Option::flatten)
+    }
+
     /// Fetch the activity record for the IRI
     fn activity_by_activity_external_id_and_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         external_id: &ExternalId,
         namespaceid: &NamespaceId,
     ) -> Result<query::Activity, StoreError> {
@@ -134,9 +464,9 @@ impl Store {
     }
 
     /// Fetch the entity record for the IRI
-    fn entity_by_entity_external_id_and_namespace(
+    pub(crate) fn entity_by_entity_external_id_and_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         external_id: &ExternalId,
         namespace_id: &NamespaceId,
     ) -> Result<query::Entity, StoreError> {
@@ -156,7 +486,7 @@ impl Store {
     /// Fetch the agent record for the IRI
     pub(crate) fn agent_by_agent_external_id_and_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         external_id: &ExternalId,
         namespaceid: &NamespaceId,
     ) -> Result<query::Agent, StoreError> {
@@ -177,7 +507,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_activity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         Activity {
             ref external_id,
             namespaceid,
@@ -238,6 +568,15 @@ impl Store {
             namespaceid,
         )?;
 
+        let current = schema::activity_attribute::table
+            .filter(schema::activity_attribute::activity_id.eq(id))
+            .select((
+                schema::activity_attribute::typename,
+                schema::activity_attribute::value,
+            ))
+            .load::<(String, String)>(connection)?;
+        self.record_superseded_attributes(connection, "activity", id, current, attributes)?;
+
         diesel::insert_into(schema::activity_attribute::table)
             .values(
                 attributes
@@ -251,7 +590,12 @@ impl Store {
                     )
                     .collect::<Vec<_>>(),
             )
-            .on_conflict_do_nothing()
+            .on_conflict((
+                schema::activity_attribute::activity_id,
+                schema::activity_attribute::typename,
+            ))
+            .do_update()
+            .set(schema::activity_attribute::value.eq(excluded(schema::activity_attribute::value)))
             .execute(connection)?;
 
         Ok(())
@@ -262,7 +606,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_agent(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         Agent {
             ref external_id,
             namespaceid,
@@ -305,6 +649,15 @@ impl Store {
         let query::Agent { id, .. } =
             self.agent_by_agent_external_id_and_namespace(connection, external_id, namespaceid)?;
 
+        let current = schema::agent_attribute::table
+            .filter(schema::agent_attribute::agent_id.eq(id))
+            .select((
+                schema::agent_attribute::typename,
+                schema::agent_attribute::value,
+            ))
+            .load::<(String, String)>(connection)?;
+        self.record_superseded_attributes(connection, "agent", id, current, attributes)?;
+
         diesel::insert_into(schema::agent_attribute::table)
             .values(
                 attributes
@@ -316,7 +669,12 @@ impl Store {
                     })
                     .collect::<Vec<_>>(),
             )
-            .on_conflict_do_nothing()
+            .on_conflict((
+                schema::agent_attribute::agent_id,
+                schema::agent_attribute::typename,
+            ))
+            .do_update()
+            .set(schema::agent_attribute::value.eq(excluded(schema::agent_attribute::value)))
             .execute(connection)?;
 
         Ok(())
@@ -325,7 +683,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_entity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         Entity {
             namespaceid,
             id,
@@ -367,6 +725,15 @@ impl Store {
         let query::Entity { id, .. } =
             self.entity_by_entity_external_id_and_namespace(connection, external_id, namespaceid)?;
 
+        let current = schema::entity_attribute::table
+            .filter(schema::entity_attribute::entity_id.eq(id))
+            .select((
+                schema::entity_attribute::typename,
+                schema::entity_attribute::value,
+            ))
+            .load::<(String, String)>(connection)?;
+        self.record_superseded_attributes(connection, "entity", id, current, attributes)?;
+
         diesel::insert_into(schema::entity_attribute::table)
             .values(
                 attributes
@@ -378,16 +745,178 @@ impl Store {
                     })
                     .collect::<Vec<_>>(),
             )
-            .on_conflict_do_nothing()
+            .on_conflict((
+                schema::entity_attribute::entity_id,
+                schema::entity_attribute::typename,
+            ))
+            .do_update()
+            .set(schema::entity_attribute::value.eq(excluded(schema::entity_attribute::value)))
+            .execute(connection)?;
+
+        Ok(())
+    }
+
+    /// Move attribute values that are about to be overwritten into
+    /// attribute_history, so the attribute tables only hold current values
+    #[instrument(level = "trace", skip(self, connection), ret(Debug))]
+    fn record_superseded_attributes(
+        &self,
+        connection: &mut DatabaseConnection,
+        record_type: &str,
+        record_id: i32,
+        current: Vec<(String, String)>,
+        incoming: &BTreeMap<String, Attribute>,
+    ) -> Result<(), StoreError> {
+        let superseded = current
+            .into_iter()
+            .filter(|(typename, value)| {
+                incoming.values().any(
+                    |Attribute {
+                         typ, value: new, ..
+                     }| { typ == typename && &new.to_string() != value },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        self.insert_attribute_history(connection, record_type, record_id, superseded)
+    }
+
+    fn insert_attribute_history(
+        &self,
+        connection: &mut DatabaseConnection,
+        record_type: &str,
+        record_id: i32,
+        values: Vec<(String, String)>,
+    ) -> Result<(), StoreError> {
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let superseded_at = Utc::now().naive_utc();
+        diesel::insert_into(schema::attribute_history::table)
+            .values(
+                values
+                    .into_iter()
+                    .map(|(typename, value)| query::NewAttributeHistory {
+                        record_type,
+                        record_id,
+                        typename,
+                        value,
+                        superseded_at,
+                    })
+                    .collect::<Vec<_>>(),
+            )
             .execute(connection)?;
 
         Ok(())
     }
 
+    /// Remove retracted attributes from the attribute tables, keeping their
+    /// last values in attribute_history
+    #[instrument(level = "trace", skip(self, connection), ret(Debug))]
+    fn apply_retracted_attributes(
+        &self,
+        connection: &mut DatabaseConnection,
+        namespaceid: &NamespaceId,
+        id: &ChronicleIri,
+        typenames: &BTreeSet<String>,
+    ) -> Result<(), StoreError> {
+        match id {
+            ChronicleIri::Agent(id) => {
+                use schema::agent_attribute::dsl;
+                let agent = self
+                    .agent_by_agent_external_id_and_namespace(
+                        connection,
+                        id.external_id_part(),
+                        namespaceid,
+                    )
+                    .ok();
+
+                if let Some(agent) = agent {
+                    let retracted = dsl::agent_attribute.filter(
+                        dsl::agent_id
+                            .eq(agent.id)
+                            .and(dsl::typename.eq_any(typenames)),
+                    );
+                    let removed = retracted
+                        .select((dsl::typename, dsl::value))
+                        .load::<(String, String)>(connection)?;
+                    self.insert_attribute_history(connection, "agent", agent.id, removed)?;
+                    diesel::delete(retracted).execute(connection)?;
+                }
+            }
+            ChronicleIri::Activity(id) => {
+                use schema::activity_attribute::dsl;
+                let activity = self
+                    .activity_by_activity_external_id_and_namespace(
+                        connection,
+                        id.external_id_part(),
+                        namespaceid,
+                    )
+                    .ok();
+
+                if let Some(activity) = activity {
+                    let retracted = dsl::activity_attribute.filter(
+                        dsl::activity_id
+                            .eq(activity.id)
+                            .and(dsl::typename.eq_any(typenames)),
+                    );
+                    let removed = retracted
+                        .select((dsl::typename, dsl::value))
+                        .load::<(String, String)>(connection)?;
+                    self.insert_attribute_history(connection, "activity", activity.id, removed)?;
+                    diesel::delete(retracted).execute(connection)?;
+                }
+            }
+            ChronicleIri::Entity(id) => {
+                use schema::entity_attribute::dsl;
+                let entity = self
+                    .entity_by_entity_external_id_and_namespace(
+                        connection,
+                        id.external_id_part(),
+                        namespaceid,
+                    )
+                    .ok();
+
+                if let Some(entity) = entity {
+                    let retracted = dsl::entity_attribute.filter(
+                        dsl::entity_id
+                            .eq(entity.id)
+                            .and(dsl::typename.eq_any(typenames)),
+                    );
+                    let removed = retracted
+                        .select((dsl::typename, dsl::value))
+                        .load::<(String, String)>(connection)?;
+                    self.insert_attribute_history(connection, "entity", entity.id, removed)?;
+                    diesel::delete(retracted).execute(connection)?;
+                }
+            }
+            _ => warn!(%id, "Attributes retracted from a record that cannot hold them"),
+        }
+
+        Ok(())
+    }
+
+    /// Remove superseded attribute values recorded before `older_than`,
+    /// returning the number of rows removed
+    #[instrument(skip(self))]
+    pub(crate) fn compact_attribute_history(
+        &self,
+        older_than: DateTime<Utc>,
+    ) -> Result<usize, StoreError> {
+        use schema::attribute_history::dsl;
+
+        Ok(self.connection()?.build_transaction().run(|connection| {
+            diesel::delete(dsl::attribute_history)
+                .filter(dsl::superseded_at.lt(older_than.naive_utc()))
+                .execute(connection)
+        })?)
+    }
+
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_has_identity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         model: &ProvModel,
         namespaceid: &NamespaceId,
         agent: &AgentId,
@@ -413,7 +942,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_had_identity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         model: &ProvModel,
         namespaceid: &NamespaceId,
         agent: &AgentId,
@@ -438,7 +967,7 @@ impl Store {
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_identity(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         Identity {
             id,
             namespaceid,
@@ -462,20 +991,25 @@ impl Store {
 
     fn apply_model(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         model: &ProvModel,
     ) -> Result<(), StoreError> {
         for (_, ns) in model.namespaces.iter() {
             self.apply_namespace(connection, ns)?
         }
-        for (_, agent) in model.agents.iter() {
-            self.apply_agent(connection, agent, &model.namespaces)?
-        }
-        for (_, activity) in model.activities.iter() {
-            self.apply_activity(connection, activity, &model.namespaces)?
-        }
-        for (_, entity) in model.entities.iter() {
-            self.apply_entity(connection, entity, &model.namespaces)?
+
+        // Large deltas apply their records, usages and generations in bulk
+        let bulk = self.apply_records_in_bulk(connection, model)?;
+        if bulk.is_none() {
+            for (_, agent) in model.agents.iter() {
+                self.apply_agent(connection, agent, &model.namespaces)?
+            }
+            for (_, activity) in model.activities.iter() {
+                self.apply_activity(connection, activity, &model.namespaces)?
+            }
+            for (_, entity) in model.entities.iter() {
+                self.apply_entity(connection, entity, &model.namespaces)?
+            }
         }
         for (_, identity) in model.identities.iter() {
             self.apply_identity(connection, identity, &model.namespaces)?
@@ -497,9 +1031,13 @@ impl Store {
             }
         }
 
-        for ((namespaceid, _), usage) in model.usage.iter() {
-            for usage in usage.iter() {
-                self.apply_used(connection, namespaceid, usage)?;
+        if let Some(ids) = &bulk {
+            self.bulk_apply_usage(connection, model, ids)?;
+        } else {
+            for ((namespaceid, _), usage) in model.usage.iter() {
+                for usage in usage.iter() {
+                    self.apply_used(connection, namespaceid, usage)?;
+                }
             }
         }
 
@@ -514,9 +1052,13 @@ impl Store {
             }
         }
 
-        for ((namespaceid, _), generation) in model.generation.iter() {
-            for generation in generation.iter() {
-                self.apply_was_generated_by(connection, namespaceid, generation)?;
+        if let Some(ids) = &bulk {
+            self.bulk_apply_generation(connection, model, ids)?;
+        } else {
+            for ((namespaceid, _), generation) in model.generation.iter() {
+                for generation in generation.iter() {
+                    self.apply_was_generated_by(connection, namespaceid, generation)?;
+                }
             }
         }
 
@@ -538,13 +1080,29 @@ impl Store {
             }
         }
 
+        for ((namespaceid, _), association) in model.retracted_association.iter() {
+            for association in association.iter() {
+                self.apply_retracted_association(connection, namespaceid, association)?;
+            }
+        }
+
+        for ((namespace_id, _), attribution) in model.retracted_attribution.iter() {
+            for attribution in attribution.iter() {
+                self.apply_retracted_attribution(connection, namespace_id, attribution)?;
+            }
+        }
+
+        for ((namespaceid, id), typenames) in model.retracted_attributes.iter() {
+            self.apply_retracted_attributes(connection, namespaceid, id, typenames)?;
+        }
+
         Ok(())
     }
 
     #[instrument(level = "trace", skip(self, connection), ret(Debug))]
     fn apply_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         Namespace {
             ref external_id,
             ref uuid,
@@ -563,10 +1121,160 @@ impl Store {
         Ok(())
     }
 
-    pub(crate) fn apply_prov(&self, prov: &ProvModel) -> Result<(), StoreError> {
-        self.connection()?
-            .build_transaction()
-            .run(|connection| self.apply_model(connection, prov))?;
+    /// Apply committed deltas to the store in a single transaction, recording
+    /// each in the history of the namespaces it touches, queueing its webhook
+    /// deliveries and advancing the synchronized offsets past it
+    pub(crate) fn apply_prov(&self, deltas: &[SyncedDelta]) -> Result<(), StoreError> {
+        self.connection()?.build_transaction().run(|connection| {
+            let synced_at = Utc::now().naive_utc();
+            for synced in deltas {
+                let block_id = synced.block_id.to_string();
+                self.apply_model(connection, &synced.prov)?;
+                self.stamp_records(connection, &synced.prov, &block_id)?;
+                self.record_history(
+                    connection,
+                    &synced.prov,
+                    &block_id,
+                    &synced.tx_id,
+                    &synced.delta,
+                )?;
+                queue_deliveries(connection, &synced.tx_id, &synced.deliveries)?;
+                self.record_last_block_id(connection, &synced.block_id, &synced.tx_id, synced_at)?;
+                self.record_namespace_block_id(
+                    connection,
+                    synced.prov.namespaces.keys(),
+                    &synced.block_id,
+                    &synced.tx_id,
+                )?;
+            }
+
+            Ok::<_, StoreError>(())
+        })
+    }
+
+    #[instrument(skip(self, connection, model, delta))]
+    pub(crate) fn record_history(
+        &self,
+        connection: &mut DatabaseConnection,
+        model: &ProvModel,
+        block_id: &str,
+        tx_id: &ChronicleTransactionId,
+        delta: &str,
+    ) -> Result<(), StoreError> {
+        use schema::prov_history::dsl;
+
+        let now = Utc::now().naive_utc();
+        for namespace in model.namespaces.keys() {
+            let (_, nsid) =
+                self.namespace_by_external_id(connection, namespace.external_id_part())?;
+            diesel::insert_into(dsl::prov_history)
+                .values((
+                    dsl::namespace_id.eq(nsid),
+                    dsl::block_id.eq(block_id),
+                    dsl::tx_id.eq(tx_id.to_string()),
+                    dsl::delta.eq(delta),
+                    dsl::recorded_at.eq(now),
+                ))
+                .execute(connection)?;
+        }
+
+        Ok(())
+    }
+
+    /// The deltas applied to `namespace` up to and including those carried by
+    /// `block_id`, in the order they were applied, as compact JSON-LD. Blocks
+    /// are ordered by when they were synced, so `block_id` need not have
+    /// touched the namespace, but must have been synced since history was kept.
+    /// Only reads, so it can be run within a [read_only_transaction]
+    #[instrument(skip(self, connection))]
+    pub(crate) fn history_as_of_block(
+        &self,
+        connection: &mut DatabaseConnection,
+        namespace: &ExternalId,
+        block_id: &str,
+    ) -> Result<(NamespaceId, Vec<String>), StoreError> {
+        use schema::prov_history::dsl;
+
+        let (namespaceid, nsid) = self.namespace_by_external_id(connection, namespace)?;
+        let last = dsl::prov_history
+            .filter(dsl::block_id.eq(block_id))
+            .select(diesel::dsl::max(dsl::id))
+            .first::<Option<i32>>(connection)?
+            .ok_or(StoreError::RecordNotFound {})?;
+
+        let deltas = dsl::prov_history
+            .filter(dsl::namespace_id.eq(nsid).and(dsl::id.le(last)))
+            .order(dsl::id.asc())
+            .select(dsl::delta)
+            .load::<String>(connection)?;
+
+        Ok((namespaceid, deltas))
+    }
+
+    /// Stamp records and relations first stored by this transaction with the
+    /// time and block of their creation, and any records it touched with the
+    /// time and block of their last update
+    #[instrument(skip(self, connection, model))]
+    fn stamp_records(
+        &self,
+        connection: &mut DatabaseConnection,
+        model: &ProvModel,
+        block_id: &str,
+    ) -> Result<(), StoreError> {
+        let now = Utc::now().naive_utc();
+
+        macro_rules! stamp_created {
+            ($($table:ident),*) => {
+                $(
+                    diesel::update(schema::$table::table.filter(schema::$table::created_at.is_null()))
+                        .set((
+                            schema::$table::created_at.eq(now),
+                            schema::$table::created_block.eq(block_id),
+                            schema::$table::updated_at.eq(now),
+                            schema::$table::updated_block.eq(block_id),
+                        ))
+                        .execute(connection)?;
+                )*
+            };
+        }
+
+        macro_rules! stamp_updated {
+            ($table:ident, $records:expr) => {
+                for (namespaceid, id) in $records {
+                    let (_, nsid) =
+                        self.namespace_by_external_id(connection, namespaceid.external_id_part())?;
+                    diesel::update(
+                        schema::$table::table.filter(
+                            schema::$table::external_id
+                                .eq(id.external_id_part())
+                                .and(schema::$table::namespace_id.eq(nsid)),
+                        ),
+                    )
+                    .set((
+                        schema::$table::updated_at.eq(now),
+                        schema::$table::updated_block.eq(block_id),
+                    ))
+                    .execute(connection)?;
+                }
+            };
+        }
+
+        stamp_created!(
+            agent,
+            activity,
+            entity,
+            association,
+            attribution,
+            delegation,
+            derivation,
+            generation,
+            usage,
+            wasinformedby
+        );
+
+        stamp_updated!(agent, model.agents.keys());
+        stamp_updated!(activity, model.activities.keys());
+        stamp_updated!(entity, model.entities.keys());
 
         Ok(())
     }
@@ -574,7 +1282,7 @@ impl Store {
     #[instrument(skip(connection))]
     fn apply_used(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         namespace: &NamespaceId,
         usage: &Usage,
     ) -> Result<(), StoreError> {
@@ -605,7 +1313,7 @@ impl Store {
     #[instrument(skip(connection))]
     fn apply_was_informed_by(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         namespace: &NamespaceId,
         activity_id: &ActivityId,
         informing_activity_id: &ActivityId,
@@ -637,7 +1345,7 @@ impl Store {
     #[instrument(skip(self, connection))]
     fn apply_was_associated_with(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         namespaceid: &common::prov::NamespaceId,
         association: &Association,
     ) -> Result<(), StoreError> {
@@ -667,10 +1375,51 @@ impl Store {
         Ok(())
     }
 
+    /// Remove a retracted association, if both parties are known to the store
+    #[instrument(level = "trace", skip(self, connection), ret(Debug))]
+    fn apply_retracted_association(
+        &self,
+        connection: &mut DatabaseConnection,
+        namespaceid: &common::prov::NamespaceId,
+        association: &Association,
+    ) -> Result<(), StoreError> {
+        let storedactivity = self
+            .activity_by_activity_external_id_and_namespace(
+                connection,
+                association.activity_id.external_id_part(),
+                namespaceid,
+            )
+            .ok();
+
+        let storedagent = self
+            .agent_by_agent_external_id_and_namespace(
+                connection,
+                association.agent_id.external_id_part(),
+                namespaceid,
+            )
+            .ok();
+
+        if let (Some(storedactivity), Some(storedagent)) = (storedactivity, storedagent) {
+            use schema::association::dsl as asoc;
+            let no_role = common::prov::Role("".to_string());
+            diesel::delete(
+                asoc::association.filter(
+                    asoc::activity_id
+                        .eq(storedactivity.id)
+                        .and(asoc::agent_id.eq(storedagent.id))
+                        .and(asoc::role.eq(association.role.as_ref().unwrap_or(&no_role))),
+                ),
+            )
+            .execute(connection)?;
+        }
+
+        Ok(())
+    }
+
     #[instrument(skip(self, connection, namespace))]
     fn apply_delegation(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         namespace: &common::prov::NamespaceId,
         delegation: &Delegation,
     ) -> Result<(), StoreError> {
@@ -709,8 +1458,20 @@ impl Store {
                 &link::delegate_id.eq(delegate.id),
                 &link::activity_id.eq(activity.unwrap_or(-1)),
                 &link::role.eq(delegation.role.as_ref().unwrap_or(&no_role)),
+                &link::valid_from.eq(delegation.valid_from.map(|time| time.naive_utc())),
+                &link::valid_until.eq(delegation.valid_until.map(|time| time.naive_utc())),
+            ))
+            .on_conflict((
+                link::responsible_id,
+                link::delegate_id,
+                link::activity_id,
+                link::role,
+            ))
+            .do_update()
+            .set((
+                link::valid_from.eq(excluded(link::valid_from)),
+                link::valid_until.eq(excluded(link::valid_until)),
             ))
-            .on_conflict_do_nothing()
             .execute(connection)?;
 
         Ok(())
@@ -719,7 +1480,7 @@ impl Store {
     #[instrument(skip(self, connection, namespace))]
     fn apply_derivation(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         namespace: &common::prov::NamespaceId,
         derivation: &Derivation,
     ) -> Result<(), StoreError> {
@@ -764,7 +1525,7 @@ impl Store {
     #[instrument(skip(connection))]
     fn apply_was_generated_by(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         namespace: &common::prov::NamespaceId,
         generation: &Generation,
     ) -> Result<(), StoreError> {
@@ -795,7 +1556,7 @@ impl Store {
     #[instrument(skip(self, connection))]
     fn apply_was_attributed_to(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         namespace_id: &common::prov::NamespaceId,
         attribution: &Attribution,
     ) -> Result<(), StoreError> {
@@ -825,16 +1586,93 @@ impl Store {
         Ok(())
     }
 
+    /// Remove a retracted attribution, if both parties are known to the store
+    #[instrument(level = "trace", skip(self, connection), ret(Debug))]
+    fn apply_retracted_attribution(
+        &self,
+        connection: &mut DatabaseConnection,
+        namespace_id: &common::prov::NamespaceId,
+        attribution: &Attribution,
+    ) -> Result<(), StoreError> {
+        let stored_entity = self
+            .entity_by_entity_external_id_and_namespace(
+                connection,
+                attribution.entity_id.external_id_part(),
+                namespace_id,
+            )
+            .ok();
+
+        let stored_agent = self
+            .agent_by_agent_external_id_and_namespace(
+                connection,
+                attribution.agent_id.external_id_part(),
+                namespace_id,
+            )
+            .ok();
+
+        if let (Some(stored_entity), Some(stored_agent)) = (stored_entity, stored_agent) {
+            use schema::attribution::dsl as attr;
+            let no_role = common::prov::Role("".to_string());
+            diesel::delete(
+                attr::attribution.filter(
+                    attr::entity_id
+                        .eq(stored_entity.id)
+                        .and(attr::agent_id.eq(stored_agent.id))
+                        .and(attr::role.eq(attribution.role.as_ref().unwrap_or(&no_role))),
+                ),
+            )
+            .execute(connection)?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn connection(
         &self,
-    ) -> Result<PooledConnection<ConnectionManager<PgConnection>>, StoreError> {
+    ) -> Result<PooledConnection<ConnectionManager<DatabaseConnection>>, StoreError> {
         Ok(self.pool.get()?)
     }
 
+    /// A connection from the read replica, if one is configured, and
+    /// otherwise the primary
+    pub(crate) fn read_connection(
+        &self,
+        from: ReadFrom,
+    ) -> Result<PooledConnection<ConnectionManager<DatabaseConnection>>, StoreError> {
+        match (&self.read_pool, from) {
+            (Some(read_pool), ReadFrom::Replica) => Ok(read_pool.get()?),
+            _ => self.connection(),
+        }
+    }
+
+    /// Run `f` on a pooled connection in a [read_only_transaction]. The
+    /// `prov_model_for_*` reads should be called this way when serving queries.
+    pub(crate) fn read_only<T>(
+        &self,
+        f: impl FnOnce(&mut DatabaseConnection) -> Result<T, StoreError>,
+    ) -> Result<T, StoreError> {
+        self.read_only_from(ReadFrom::Replica, f)
+    }
+
+    /// Run `f` as [Store::read_only] does, on a connection from the pool
+    /// `from`
+    pub(crate) fn read_only_from<T>(
+        &self,
+        from: ReadFrom,
+        f: impl FnOnce(&mut DatabaseConnection) -> Result<T, StoreError>,
+    ) -> Result<T, StoreError> {
+        read_only_transaction(&mut *self.read_connection(from)?, |connection| {
+            if let Some(tenant) = &self.tenant {
+                bind_tenant(connection, tenant)?;
+            }
+            f(connection)
+        })
+    }
+
     #[instrument(skip(connection))]
     pub(crate) fn get_current_agent(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
     ) -> Result<query::Agent, StoreError> {
         use schema::agent::dsl;
         Ok(schema::agent::table
@@ -848,7 +1686,7 @@ impl Store {
         use schema::ledgersync::dsl;
         self.connection()?.build_transaction().run(|connection| {
             let block_id_and_tx = schema::ledgersync::table
-                .order_by(dsl::sync_time)
+                .order_by(dsl::ledger_sequence.desc())
                 .select((dsl::bc_offset, dsl::tx_id))
                 .first::<(Option<String>, String)>(connection)
                 .map_err(StoreError::from)?;
@@ -861,11 +1699,74 @@ impl Store {
         })
     }
 
-    #[instrument(skip(connection))]
-    pub(crate) fn namespace_by_external_id(
-        &self,
-        connection: &mut PgConnection,
-        namespace: &ExternalId,
+    /// The most recently synchronized transaction, if any
+    #[instrument]
+    pub(crate) fn get_last_tx_id(&self) -> Result<Option<ChronicleTransactionId>, StoreError> {
+        use schema::ledgersync::dsl;
+
+        Ok(schema::ledgersync::table
+            .order_by(dsl::ledger_sequence.desc())
+            .select(dsl::tx_id)
+            .first::<String>(&mut self.connection()?)
+            .optional()?
+            .map(|tx_id| ChronicleTransactionId::from(tx_id.as_str())))
+    }
+
+    /// True if the store has applied the transaction, whichever replica
+    /// synchronized it
+    #[instrument]
+    pub(crate) fn is_synced(&self, tx_id: &ChronicleTransactionId) -> Result<bool, StoreError> {
+        use schema::ledgersync::dsl;
+
+        Ok(schema::ledgersync::table
+            .filter(dsl::tx_id.eq(tx_id.to_string()))
+            .count()
+            .get_result::<i64>(&mut self.connection()?)?
+            > 0)
+    }
+
+    /// Every transaction the store has applied
+    #[instrument(skip(connection))]
+    pub(crate) fn synced_transactions(
+        &self,
+        connection: &mut DatabaseConnection,
+    ) -> Result<HashSet<ChronicleTransactionId>, StoreError> {
+        use schema::ledgersync::dsl;
+
+        Ok(dsl::ledgersync
+            .select(dsl::tx_id)
+            .load::<String>(connection)?
+            .into_iter()
+            .map(|tx_id| ChronicleTransactionId::from(tx_id.as_str()))
+            .collect())
+    }
+
+    /// All namespaces known to the store
+    #[instrument(skip(connection))]
+    pub(crate) fn namespaces(
+        &self,
+        connection: &mut DatabaseConnection,
+    ) -> Result<Vec<NamespaceId>, StoreError> {
+        use self::schema::namespace::dsl;
+
+        dsl::namespace
+            .select((dsl::external_id, dsl::uuid))
+            .load::<(String, String)>(connection)?
+            .into_iter()
+            .map(|(external_id, uuid)| {
+                Ok(NamespaceId::from_external_id(
+                    external_id,
+                    Uuid::from_str(&uuid)?,
+                ))
+            })
+            .collect()
+    }
+
+    #[instrument(skip(connection))]
+    pub(crate) fn namespace_by_external_id(
+        &self,
+        connection: &mut DatabaseConnection,
+        namespace: &ExternalId,
     ) -> Result<(NamespaceId, i32), StoreError> {
         use self::schema::namespace::dsl;
 
@@ -885,7 +1786,7 @@ impl Store {
     #[instrument(skip(connection))]
     pub(crate) fn identity_by(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         namespaceid: &NamespaceId,
         identity: &IdentityId,
     ) -> Result<query::Identity, StoreError> {
@@ -904,8 +1805,51 @@ impl Store {
     }
 
     #[instrument]
-    pub(crate) fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Result<Self, StoreError> {
-        Ok(Store { pool })
+    pub(crate) fn new(
+        pool: Pool<ConnectionManager<DatabaseConnection>>,
+    ) -> Result<Self, StoreError> {
+        Ok(Store {
+            pool,
+            read_pool: None,
+            tenant: None,
+        })
+    }
+
+    /// Direct the reads made through [Store::read_only] to `read_pool`, a
+    /// pool of connections to a read replica, leaving writes on the primary
+    pub(crate) fn with_read_pool(
+        mut self,
+        read_pool: Option<Pool<ConnectionManager<DatabaseConnection>>>,
+    ) -> Self {
+        self.read_pool = read_pool;
+        self
+    }
+
+    /// Scope the reads made through [Store::read_only] to the namespace with
+    /// external id `tenant`
+    pub(crate) fn with_tenant(mut self, tenant: Option<String>) -> Self {
+        self.tenant = tenant;
+        self
+    }
+
+    /// The role the store connects as, if row-level security does not apply
+    /// to it. Superusers and roles with `BYPASSRLS` see every tenant's
+    /// records, even on tables that force row-level security.
+    #[cfg(not(feature = "sqlite"))]
+    pub(crate) fn role_bypassing_row_security(&self) -> Result<Option<String>, StoreError> {
+        let role = diesel::sql_query(
+            "select rolname as name, rolsuper or rolbypassrls as bypasses_row_security \
+             from pg_roles where rolname = current_user",
+        )
+        .get_result::<query::DatabaseRole>(&mut *self.connection()?)?;
+
+        Ok(role.bypasses_row_security.then_some(role.name))
+    }
+
+    /// SQLite has no roles, nor row-level security for them to bypass
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn role_bypassing_row_security(&self) -> Result<Option<String>, StoreError> {
+        Ok(None)
     }
 
     pub(crate) fn prov_model_for_agent(
@@ -913,7 +1857,7 @@ impl Store {
         agent: query::Agent,
         namespaceid: &NamespaceId,
         model: &mut ProvModel,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
     ) -> Result<(), StoreError> {
         debug!(?agent, "Map agent to prov");
 
@@ -946,7 +1890,7 @@ impl Store {
             },
         );
 
-        for (responsible, activity, role) in schema::delegation::table
+        for (responsible, activity, role, valid_from, valid_until) in schema::delegation::table
             .filter(schema::delegation::delegate_id.eq(agent.id))
             .inner_join(
                 schema::agent::table.on(schema::delegation::responsible_id.eq(schema::agent::id)),
@@ -960,30 +1904,74 @@ impl Store {
                 schema::agent::external_id,
                 schema::activity::external_id,
                 schema::delegation::role,
+                schema::delegation::valid_from,
+                schema::delegation::valid_until,
             ))
-            .load::<(String, String, String)>(connection)?
+            .load::<(
+                String,
+                String,
+                String,
+                Option<NaiveDateTime>,
+                Option<NaiveDateTime>,
+            )>(connection)?
         {
-            model.qualified_delegation(
-                namespaceid,
-                &AgentId::from_external_id(responsible),
-                &AgentId::from_external_id(&agent.external_id),
-                {
-                    if activity.contains("hidden entry for Option None") {
-                        None
-                    } else {
-                        Some(ActivityId::from_external_id(activity))
-                    }
-                },
-                {
-                    if role.is_empty() {
-                        None
-                    } else {
-                        Some(Role(role))
-                    }
-                },
+            let activity = if activity.contains("hidden entry for Option None") {
+                None
+            } else {
+                Some(ActivityId::from_external_id(activity))
+            };
+            let role = if role.is_empty() {
+                None
+            } else {
+                Some(Role(role))
+            };
+
+            model.add_delegation(
+                Delegation::new(
+                    namespaceid,
+                    &AgentId::from_external_id(&agent.external_id),
+                    &AgentId::from_external_id(responsible),
+                    activity.as_ref(),
+                    role,
+                )
+                .with_validity(
+                    valid_from.map(|time| DateTime::from_naive_utc_and_offset(time, Utc)),
+                    valid_until.map(|time| DateTime::from_naive_utc_and_offset(time, Utc)),
+                ),
             );
         }
 
+        let current = agent
+            .identity_id
+            .map(|identity_id| {
+                schema::identity::table
+                    .find(identity_id)
+                    .select(schema::identity::public_key)
+                    .first::<String>(connection)
+            })
+            .transpose()?;
+        let past = schema::hadidentity::table
+            .filter(schema::hadidentity::agent_id.eq(agent.id))
+            .inner_join(schema::identity::table)
+            .select(schema::identity::public_key)
+            .load::<String>(connection)?;
+
+        let agentid = AgentId::from_external_id(&agent.external_id);
+        for public_key in &past {
+            let identity = Identity::new(namespaceid, &agentid, public_key);
+            model.had_identity(namespaceid.clone(), &agentid, &identity.id);
+            model
+                .identities
+                .insert((namespaceid.clone(), identity.id.clone()), identity);
+        }
+        if let Some(public_key) = current {
+            let identity = Identity::new(namespaceid, &agentid, &public_key);
+            model.has_identity(namespaceid.clone(), &agentid, &identity.id);
+            model
+                .identities
+                .insert((namespaceid.clone(), identity.id.clone()), identity);
+        }
+
         Ok(())
     }
 
@@ -992,7 +1980,7 @@ impl Store {
         activity: query::Activity,
         namespaceid: &NamespaceId,
         model: &mut ProvModel,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
     ) -> Result<(), StoreError> {
         debug!(?activity, "Map activity to prov");
 
@@ -1095,15 +2083,15 @@ impl Store {
         entity: query::Entity,
         namespace_id: &NamespaceId,
         model: &mut ProvModel,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
     ) -> Result<(), StoreError> {
         debug!(?entity, "Map entity to prov");
 
         let query::Entity {
             id,
-            namespace_id: _,
             domaintype,
             external_id,
+            ..
         } = entity;
 
         let entity_id = EntityId::from_external_id(&external_id);
@@ -1195,35 +2183,60 @@ impl Store {
         Ok(())
     }
 
+    /// The provenance of a namespace. Only reads, so it can be run within a
+    /// [read_only_transaction]
     #[instrument(skip(connection))]
     pub(crate) fn prov_model_for_namespace(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
+        namespace: &NamespaceId,
+    ) -> Result<ProvModel, StoreError> {
+        self.prov_model_for_namespace_since(connection, namespace, None)
+    }
+
+    /// The provenance of a namespace, limited to the agents, activities and
+    /// entities updated at or after `since` if it is supplied
+    #[instrument(skip(connection))]
+    pub(crate) fn prov_model_for_namespace_since(
+        &self,
+        connection: &mut DatabaseConnection,
         namespace: &NamespaceId,
+        since: Option<NaiveDateTime>,
     ) -> Result<ProvModel, StoreError> {
         let mut model = ProvModel::default();
         let (namespaceid, nsid) =
             self.namespace_by_external_id(connection, namespace.external_id_part())?;
 
-        let agents = schema::agent::table
+        let mut agents = schema::agent::table
             .filter(schema::agent::namespace_id.eq(&nsid))
-            .load::<query::Agent>(connection)?;
+            .into_boxed::<DatabaseBackend>();
+        if let Some(since) = since {
+            agents = agents.filter(schema::agent::updated_at.ge(since));
+        }
 
-        for agent in agents {
+        for agent in agents.load::<query::Agent>(connection)? {
             self.prov_model_for_agent(agent, &namespaceid, &mut model, connection)?;
         }
 
-        let activities = schema::activity::table
+        let mut activities = schema::activity::table
             .filter(schema::activity::namespace_id.eq(nsid))
-            .load::<query::Activity>(connection)?;
+            .into_boxed::<DatabaseBackend>();
+        if let Some(since) = since {
+            activities = activities.filter(schema::activity::updated_at.ge(since));
+        }
 
-        for activity in activities {
+        for activity in activities.load::<query::Activity>(connection)? {
             self.prov_model_for_activity(activity, &namespaceid, &mut model, connection)?;
         }
 
-        let entities = schema::entity::table
+        let mut entities = schema::entity::table
             .filter(schema::entity::namespace_id.eq(nsid))
-            .load::<query::Entity>(connection)?;
+            .into_boxed::<DatabaseBackend>();
+        if let Some(since) = since {
+            entities = entities.filter(schema::entity::updated_at.ge(since));
+        }
+
+        let entities = entities.load::<query::Entity>(connection)?;
 
         for entity in entities {
             self.prov_model_for_entity(entity, &namespaceid, &mut model, connection)?;
@@ -1232,34 +2245,691 @@ impl Store {
         Ok(model)
     }
 
-    /// Set the last fully synchronized offset
-    #[instrument]
-    pub(crate) fn set_last_block_id(
+    fn namespace_by_id(
         &self,
-        block_id: &BlockId,
-        tx_id: ChronicleTransactionId,
+        connection: &mut DatabaseConnection,
+        namespaces: &mut BTreeMap<i32, NamespaceId>,
+        nsid: i32,
+    ) -> Result<NamespaceId, StoreError> {
+        use self::schema::namespace::dsl;
+
+        if let Some(namespace) = namespaces.get(&nsid) {
+            return Ok(namespace.clone());
+        }
+
+        let (external_id, uuid) = dsl::namespace
+            .find(nsid)
+            .select((dsl::external_id, dsl::uuid))
+            .first::<(String, String)>(connection)?;
+        let namespace = NamespaceId::from_external_id(external_id, Uuid::from_str(&uuid)?);
+        namespaces.insert(nsid, namespace.clone());
+
+        Ok(namespace)
+    }
+
+    /// The provenance of up to `limit` records of `record_type`, in any
+    /// namespace, whose row ids follow `after`, or `None` if there are no more.
+    /// Only reads, so it can be run within a [read_only_transaction]
+    #[instrument(skip(connection))]
+    pub(crate) fn prov_model_for_records_after(
+        &self,
+        connection: &mut DatabaseConnection,
+        record_type: RecordType,
+        after: i32,
+        limit: i64,
+    ) -> Result<Option<RecordChunk>, StoreError> {
+        let mut model = ProvModel::default();
+        let mut namespaces = BTreeMap::new();
+        let mut ids = vec![];
+
+        match record_type {
+            RecordType::Agent => {
+                for agent in schema::agent::table
+                    .filter(schema::agent::id.gt(after))
+                    .order(schema::agent::id)
+                    .limit(limit)
+                    .load::<query::Agent>(connection)?
+                {
+                    ids.push(agent.id);
+                    let namespaceid =
+                        self.namespace_by_id(connection, &mut namespaces, agent.namespace_id)?;
+                    self.prov_model_for_agent(agent, &namespaceid, &mut model, connection)?;
+                }
+            }
+            RecordType::Activity => {
+                for activity in schema::activity::table
+                    .filter(schema::activity::id.gt(after))
+                    .order(schema::activity::id)
+                    .limit(limit)
+                    .load::<query::Activity>(connection)?
+                {
+                    ids.push(activity.id);
+                    let namespaceid =
+                        self.namespace_by_id(connection, &mut namespaces, activity.namespace_id)?;
+                    self.prov_model_for_activity(activity, &namespaceid, &mut model, connection)?;
+                }
+            }
+            RecordType::Entity => {
+                for entity in schema::entity::table
+                    .filter(schema::entity::id.gt(after))
+                    .order(schema::entity::id)
+                    .limit(limit)
+                    .load::<query::Entity>(connection)?
+                {
+                    ids.push(entity.id);
+                    let namespaceid =
+                        self.namespace_by_id(connection, &mut namespaces, entity.namespace_id)?;
+                    self.prov_model_for_entity(entity, &namespaceid, &mut model, connection)?;
+                }
+            }
+        }
+
+        Ok(ids.last().map(|&last_id| RecordChunk {
+            model,
+            records: ids.len(),
+            last_id,
+        }))
+    }
+
+    /// The number of records of `record_type` with row ids after `after`
+    #[instrument(skip(self))]
+    pub(crate) fn count_records_after(
+        &self,
+        record_type: RecordType,
+        after: i32,
+    ) -> Result<i64, StoreError> {
+        let connection = &mut self.connection()?;
+        Ok(match record_type {
+            RecordType::Agent => schema::agent::table
+                .filter(schema::agent::id.gt(after))
+                .count()
+                .get_result(connection)?,
+            RecordType::Activity => schema::activity::table
+                .filter(schema::activity::id.gt(after))
+                .count()
+                .get_result(connection)?,
+            RecordType::Entity => schema::entity::table
+                .filter(schema::entity::id.gt(after))
+                .count()
+                .get_result(connection)?,
+        })
+    }
+
+    /// The last row id of `record_type` that `task` has processed, and how many
+    /// records it has processed, if it has started
+    #[instrument(skip(self))]
+    pub(crate) fn backfill_progress(
+        &self,
+        task: &str,
+        record_type: RecordType,
+    ) -> Result<Option<(i32, i64)>, StoreError> {
+        use schema::backfill_progress::dsl;
+
+        Ok(dsl::backfill_progress
+            .filter(
+                dsl::task
+                    .eq(task)
+                    .and(dsl::record_type.eq(record_type.as_str())),
+            )
+            .select((dsl::last_id, dsl::processed))
+            .first::<(i32, i64)>(&mut self.connection()?)
+            .optional()?)
+    }
+
+    #[instrument(skip(self))]
+    pub(crate) fn set_backfill_progress(
+        &self,
+        task: &str,
+        record_type: RecordType,
+        last_id: i32,
+        processed: i64,
     ) -> Result<(), StoreError> {
+        use schema::backfill_progress::dsl;
+
+        let updated_at = Utc::now().naive_utc();
+        diesel::insert_into(dsl::backfill_progress)
+            .values((
+                dsl::task.eq(task),
+                dsl::record_type.eq(record_type.as_str()),
+                dsl::last_id.eq(last_id),
+                dsl::processed.eq(processed),
+                dsl::updated_at.eq(updated_at),
+            ))
+            .on_conflict((dsl::task, dsl::record_type))
+            .do_update()
+            .set((
+                dsl::last_id.eq(last_id),
+                dsl::processed.eq(processed),
+                dsl::updated_at.eq(updated_at),
+            ))
+            .execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// Forget the progress of `task`, so it starts again from the first record
+    #[instrument(skip(self))]
+    pub(crate) fn reset_backfill_progress(&self, task: &str) -> Result<(), StoreError> {
+        use schema::backfill_progress::dsl;
+
+        diesel::delete(dsl::backfill_progress.filter(dsl::task.eq(task)))
+            .execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// Set the last fully synchronized offset, giving the transaction the
+    /// next place in the ledger sequence. Deltas are applied in the order they
+    /// were committed, so the sequence follows the ledger's, by block and then
+    /// position in the block. A transaction applied again keeps its place.
+    fn record_last_block_id(
+        &self,
+        connection: &mut DatabaseConnection,
+        block_id: &BlockId,
+        tx_id: &ChronicleTransactionId,
+        synced_at: NaiveDateTime,
+    ) -> Result<(), diesel::result::Error> {
         use schema::ledgersync as dsl;
 
-        Ok(self.connection()?.build_transaction().run(|connection| {
+        let sequence = dsl::table
+            .select(diesel::dsl::max(dsl::ledger_sequence))
+            .first::<Option<i64>>(connection)?
+            .unwrap_or_default()
+            + 1;
+
+        diesel::insert_into(dsl::table)
+            .values((
+                dsl::bc_offset.eq(block_id.to_string()),
+                dsl::tx_id.eq(&*tx_id.to_string()),
+                dsl::sync_time.eq(synced_at),
+                dsl::ledger_sequence.eq(sequence),
+            ))
+            .on_conflict(dsl::tx_id)
+            .do_nothing()
+            .execute(connection)
+            .map(|_| ())
+    }
+
+    /// Record `block_id` as the last to affect each of `namespaces`
+    fn record_namespace_block_id<'a>(
+        &self,
+        connection: &mut DatabaseConnection,
+        namespaces: impl IntoIterator<Item = &'a NamespaceId>,
+        block_id: &BlockId,
+        tx_id: &ChronicleTransactionId,
+    ) -> Result<(), StoreError> {
+        use schema::{namespace, namespace_sync as dsl};
+
+        for ns in namespaces {
+            let namespace_id = namespace::table
+                .filter(namespace::external_id.eq(ns.external_id_part()))
+                .select(namespace::id)
+                .first::<i32>(connection)?;
+
+            let now = Utc::now().naive_utc();
             diesel::insert_into(dsl::table)
                 .values((
+                    dsl::namespace_id.eq(namespace_id),
                     dsl::bc_offset.eq(block_id.to_string()),
-                    dsl::tx_id.eq(&*tx_id.to_string()),
-                    (dsl::sync_time.eq(Utc::now().naive_utc())),
+                    dsl::tx_id.eq(tx_id.to_string()),
+                    dsl::sync_time.eq(now),
                 ))
-                .on_conflict(dsl::tx_id)
+                .on_conflict(dsl::namespace_id)
                 .do_update()
-                .set(dsl::sync_time.eq(Utc::now().naive_utc()))
-                .execute(connection)
-                .map(|_| ())
-        })?)
+                .set((
+                    dsl::bc_offset.eq(excluded(dsl::bc_offset)),
+                    dsl::tx_id.eq(excluded(dsl::tx_id)),
+                    dsl::sync_time.eq(now),
+                ))
+                .execute(connection)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reserve the idempotency key `key` of `identity` for a command with
+    /// `command_digest` before it is submitted, unless a command reserved it
+    /// within [IDEMPOTENCY_KEY_RETENTION], pruning keys older than that. Only
+    /// one of several commands sent with the same key at once can reserve it.
+    #[instrument(skip(self))]
+    pub(crate) fn reserve_idempotency_key(
+        &self,
+        identity: &str,
+        key: &str,
+        command_digest: &str,
+    ) -> Result<IdempotencyKeyHolder, StoreError> {
+        use schema::idempotency_key::dsl;
+
+        let now = Utc::now().naive_utc();
+        let retained_since = now - chrono::Duration::from_std(IDEMPOTENCY_KEY_RETENTION).unwrap();
+
+        self.connection()?.build_transaction().run(|connection| {
+            diesel::delete(dsl::idempotency_key.filter(dsl::created_at.lt(retained_since)))
+                .execute(connection)?;
+
+            let reserved = diesel::insert_into(dsl::idempotency_key)
+                .values((
+                    dsl::identity.eq(identity),
+                    dsl::key.eq(key),
+                    dsl::command_digest.eq(command_digest),
+                    dsl::created_at.eq(now),
+                ))
+                .on_conflict((dsl::identity, dsl::key))
+                .do_nothing()
+                .execute(connection)?;
+
+            if reserved == 1 {
+                return Ok(IdempotencyKeyHolder::Reserved);
+            }
+
+            let (command_digest, subject, tx_id) = dsl::idempotency_key
+                .filter(dsl::identity.eq(identity).and(dsl::key.eq(key)))
+                .select((dsl::command_digest, dsl::subject, dsl::tx_id))
+                .first::<(String, Option<String>, Option<String>)>(connection)?;
+
+            Ok(match (subject, tx_id) {
+                (Some(subject), Some(tx_id)) => IdempotencyKeyHolder::Submitted {
+                    command_digest,
+                    subject: ChronicleIri::from_str(&subject)?,
+                    tx_id: ChronicleTransactionId::from(tx_id.as_str()),
+                },
+                _ => IdempotencyKeyHolder::Pending { command_digest },
+            })
+        })
+    }
+
+    /// Record the transaction the command holding an idempotency key was
+    /// submitted in
+    #[instrument(skip(self))]
+    pub(crate) fn complete_idempotency_key(
+        &self,
+        identity: &str,
+        key: &str,
+        subject: &ChronicleIri,
+        tx_id: &ChronicleTransactionId,
+    ) -> Result<(), StoreError> {
+        use schema::idempotency_key::dsl;
+
+        diesel::update(
+            dsl::idempotency_key.filter(dsl::identity.eq(identity).and(dsl::key.eq(key))),
+        )
+        .set((
+            dsl::subject.eq(subject.to_string()),
+            dsl::tx_id.eq(tx_id.to_string()),
+        ))
+        .execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// Free an idempotency key reserved by a command that submitted nothing,
+    /// so that it can be retried
+    #[instrument(skip(self))]
+    pub(crate) fn release_idempotency_key(
+        &self,
+        identity: &str,
+        key: &str,
+    ) -> Result<(), StoreError> {
+        use schema::idempotency_key::dsl;
+
+        diesel::delete(
+            dsl::idempotency_key.filter(
+                dsl::identity
+                    .eq(identity)
+                    .and(dsl::key.eq(key))
+                    .and(dsl::tx_id.is_null()),
+            ),
+        )
+        .execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// Queue a command in the outbox until the api has handled it, returning
+    /// the id of its entry
+    #[instrument(skip(self, command))]
+    pub(crate) fn enqueue_command(
+        &self,
+        command: &ApiCommand,
+        identity: &AuthId,
+    ) -> Result<String, StoreError> {
+        use schema::outbox::dsl;
+
+        let id = Uuid::new_v4().to_string();
+        diesel::insert_into(dsl::outbox)
+            .values((
+                dsl::id.eq(&id),
+                dsl::command.eq(serde_json::to_string(command)?),
+                dsl::identity.eq(serde_json::to_string(identity)?),
+                dsl::created_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(&mut self.connection()?)?;
+
+        Ok(id)
+    }
+
+    /// Remove a handled command from the outbox
+    #[instrument(skip(self))]
+    pub(crate) fn dequeue_command(&self, id: &str) -> Result<(), StoreError> {
+        use schema::outbox::dsl;
+
+        diesel::delete(dsl::outbox.filter(dsl::id.eq(id))).execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// Commands in the outbox, in the order they were queued, with when they
+    /// were queued
+    #[instrument(skip(self))]
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn queued_commands(
+        &self,
+    ) -> Result<Vec<(String, ApiCommand, AuthId, DateTime<Utc>)>, StoreError> {
+        use schema::outbox::dsl;
+
+        dsl::outbox
+            .order(dsl::created_at)
+            .select((dsl::id, dsl::command, dsl::identity, dsl::created_at))
+            .load::<(String, String, String, NaiveDateTime)>(&mut self.connection()?)?
+            .into_iter()
+            .map(|(id, command, identity, created_at)| {
+                Ok((
+                    id,
+                    serde_json::from_str(&command)?,
+                    serde_json::from_str(&identity)?,
+                    DateTime::from_naive_utc_and_offset(created_at, Utc),
+                ))
+            })
+            .collect()
+    }
+
+    /// Append the operations of a committed transaction to the audit log,
+    /// one row for each record an operation applies to. Rows already logged
+    /// for the same operation and record are left as they are, so syncing a
+    /// transaction again does not log it twice
+    #[instrument(skip(self, operations))]
+    pub(crate) fn record_audit(
+        &self,
+        tx_id: &ChronicleTransactionId,
+        identity: &str,
+        operations: &[ChronicleOperation],
+    ) -> Result<(), StoreError> {
+        use schema::audit_log::dsl;
+
+        let now = Utc::now().naive_utc();
+        let rows = operations
+            .iter()
+            .enumerate()
+            .flat_map(|(index, operation)| {
+                operation
+                    .subjects()
+                    .into_iter()
+                    .map(move |subject| (index, operation, subject))
+            })
+            .map(|(index, operation, subject)| {
+                Ok((
+                    dsl::tx_id.eq(tx_id.to_string()),
+                    dsl::namespace.eq(operation.namespace().external_id_part().to_string()),
+                    dsl::subject.eq(subject.to_string()),
+                    dsl::operation.eq(operation.name()),
+                    dsl::detail.eq(serde_json::to_string(operation)?),
+                    dsl::identity.eq(identity),
+                    dsl::submitted_at.eq(now),
+                    dsl::operation_index.eq(index as i32),
+                ))
+            })
+            .collect::<Result<Vec<_>, StoreError>>()?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        self.connection()?.build_transaction().run(|connection| {
+            for row in rows {
+                diesel::insert_into(dsl::audit_log)
+                    .values(row)
+                    .on_conflict((dsl::tx_id, dsl::subject, dsl::operation_index))
+                    .do_nothing()
+                    .execute(connection)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Record the digest of the operations in a transaction this deployment
+    /// submitted, for a receipt to be signed once it commits
+    pub(crate) fn record_receipt(
+        &self,
+        tx_id: &ChronicleTransactionId,
+        operation_digest: &str,
+    ) -> Result<(), StoreError> {
+        use schema::receipt::dsl;
+
+        diesel::insert_into(dsl::receipt)
+            .values((
+                dsl::tx_id.eq(tx_id.to_string()),
+                dsl::operation_digest.eq(operation_digest),
+            ))
+            .on_conflict_do_nothing()
+            .execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// The operation digest of a transaction this deployment submitted that
+    /// has no signed receipt yet
+    pub(crate) fn unsigned_receipt(
+        &self,
+        tx_id: &ChronicleTransactionId,
+    ) -> Result<Option<String>, StoreError> {
+        use schema::receipt::dsl;
+
+        Ok(dsl::receipt
+            .filter(dsl::tx_id.eq(tx_id.to_string()))
+            .filter(dsl::signature.is_null())
+            .select(dsl::operation_digest)
+            .first(&mut self.connection()?)
+            .optional()?)
+    }
+
+    /// Complete the receipt of a committed transaction with its signature and
+    /// the id of the key that made it
+    pub(crate) fn sign_receipt(
+        &self,
+        tx_id: &ChronicleTransactionId,
+        block_id: &BlockId,
+        key_id: &str,
+        signature: &str,
+    ) -> Result<(), StoreError> {
+        use schema::receipt::dsl;
+
+        diesel::update(dsl::receipt.filter(dsl::tx_id.eq(tx_id.to_string())))
+            .set((
+                dsl::block_id.eq(block_id.to_string()),
+                dsl::key_id.eq(key_id),
+                dsl::signature.eq(signature),
+            ))
+            .execute(&mut self.connection()?)?;
+
+        Ok(())
+    }
+
+    /// The receipt of a transaction this deployment submitted. Only reads, so
+    /// it can be run within a [read_only_transaction]
+    pub(crate) fn receipt(
+        &self,
+        connection: &mut DatabaseConnection,
+        tx_id: &str,
+    ) -> Result<Option<query::Receipt>, StoreError> {
+        use schema::receipt::dsl;
+
+        Ok(dsl::receipt
+            .filter(dsl::tx_id.eq(tx_id))
+            .first(connection)
+            .optional()?)
+    }
+
+    /// The audited operations in `namespace`, most recent first, with the
+    /// block they committed in once they have been synced from the ledger,
+    /// limited to those applied to one of `subjects` unless it is empty. Only
+    /// reads, so it can be run within a [read_only_transaction]
+    #[instrument(skip(self, connection))]
+    pub(crate) fn audit_trail(
+        &self,
+        connection: &mut DatabaseConnection,
+        namespace: &str,
+        subjects: &[String],
+        limit: i64,
+    ) -> Result<Vec<query::AuditRecord>, StoreError> {
+        use schema::{audit_log::dsl, ledgersync};
+
+        let mut records = dsl::audit_log
+            .left_join(ledgersync::table.on(ledgersync::tx_id.eq(dsl::tx_id)))
+            .filter(dsl::namespace.eq(namespace))
+            .into_boxed::<DatabaseBackend>();
+        if !subjects.is_empty() {
+            records = records.filter(dsl::subject.eq_any(subjects));
+        }
+
+        Ok(records
+            .order(dsl::id.desc())
+            .limit(limit)
+            .select((
+                dsl::id,
+                dsl::tx_id,
+                dsl::namespace,
+                dsl::subject,
+                dsl::operation,
+                dsl::detail,
+                dsl::identity,
+                dsl::submitted_at,
+                ledgersync::bc_offset.nullable(),
+                ledgersync::sync_time.nullable(),
+            ))
+            .load(connection)?)
+    }
+
+    /// The transactions in `namespace` synchronized after `tx_id` in ledger
+    /// order, each with the delta it committed as compact JSON-LD, as kept in
+    /// the namespace history. Transactions synchronized before history was
+    /// kept are left out. `None` if `tx_id` has not been synchronized. Only reads, so it can
+    /// be run within a [read_only_transaction]
+    #[instrument(skip(self, connection))]
+    pub(crate) fn synced_since(
+        &self,
+        connection: &mut DatabaseConnection,
+        tx_id: &str,
+        namespace: &str,
+    ) -> Result<Option<Vec<(String, String)>>, StoreError> {
+        use schema::{ledgersync::dsl, namespace, prov_history};
+
+        let after = match dsl::ledgersync
+            .filter(dsl::tx_id.eq(tx_id))
+            .select(dsl::ledger_sequence)
+            .first::<i64>(connection)
+            .optional()?
+        {
+            Some(after) => after,
+            None => return Ok(None),
+        };
+
+        let mut synced = dsl::ledgersync
+            .inner_join(prov_history::table.on(prov_history::tx_id.eq(dsl::tx_id)))
+            .filter(dsl::ledger_sequence.gt(after))
+            .filter(
+                prov_history::namespace_id.eq_any(
+                    namespace::table
+                        .filter(namespace::external_id.eq(namespace))
+                        .select(namespace::id),
+                ),
+            )
+            .order((dsl::ledger_sequence.asc(), prov_history::id.asc()))
+            .select((dsl::tx_id, prov_history::delta))
+            .load::<(String, String)>(connection)?;
+        // A delta is kept once for each namespace it touches
+        synced.dedup_by(|later, earlier| later.0 == earlier.0);
+
+        Ok(Some(synced))
+    }
+
+    /// Entities of `domaintype` first stored before `created_before` that no
+    /// expiry activity has used, oldest first, with the time they were stored
+    #[instrument(skip(self, connection))]
+    pub(crate) fn unexpired_entities(
+        &self,
+        connection: &mut DatabaseConnection,
+        domaintype: &str,
+        created_before: NaiveDateTime,
+        limit: i64,
+    ) -> Result<Vec<(NamespaceId, EntityId, NaiveDateTime)>, StoreError> {
+        use schema::{activity, entity, namespace, usage};
+
+        let expired = usage::table
+            .inner_join(activity::table)
+            .filter(activity::domaintype.eq(crate::expiry::EXPIRY_ACTIVITY_TYPE))
+            .select(usage::entity_id);
+
+        entity::table
+            .inner_join(namespace::table)
+            .filter(entity::domaintype.eq(domaintype))
+            .filter(entity::created_at.lt(created_before))
+            .filter(entity::id.ne_all(expired))
+            .order(entity::created_at.asc())
+            .limit(limit)
+            .select((
+                namespace::external_id,
+                namespace::uuid,
+                entity::external_id,
+                entity::created_at.assume_not_null(),
+            ))
+            .load::<(String, String, String, NaiveDateTime)>(connection)?
+            .into_iter()
+            .map(|(namespace, uuid, external_id, created_at)| {
+                Ok::<_, StoreError>((
+                    NamespaceId::from_external_id(namespace, Uuid::from_str(&uuid)?),
+                    EntityId::from_external_id(external_id),
+                    created_at,
+                ))
+            })
+            .collect()
+    }
+
+    /// A token that changes whenever a transaction affecting `namespace` is
+    /// synchronized, or `None` if none has been
+    #[instrument(skip(self))]
+    pub(crate) fn namespace_version(
+        &self,
+        namespace: &ExternalId,
+    ) -> Result<Option<String>, StoreError> {
+        use schema::{namespace, namespace_sync as dsl};
+
+        Ok(dsl::table
+            .inner_join(namespace::table)
+            .filter(namespace::external_id.eq(namespace))
+            .select((dsl::bc_offset, dsl::tx_id))
+            .first::<(String, String)>(&mut self.connection()?)
+            .optional()?
+            .map(|(block_id, tx_id)| format!("{block_id}:{tx_id}")))
+    }
+
+    /// A token that changes whenever any transaction is synchronized, or `None`
+    /// if none has been
+    #[instrument(skip(self))]
+    pub(crate) fn ledger_version(&self) -> Result<Option<String>, StoreError> {
+        use schema::ledgersync::dsl;
+
+        Ok(schema::ledgersync::table
+            .order_by(dsl::ledger_sequence.desc())
+            .select((dsl::bc_offset, dsl::tx_id))
+            .first::<(Option<String>, String)>(&mut self.connection()?)
+            .optional()?
+            .map(|(block_id, tx_id)| format!("{}:{tx_id}", block_id.unwrap_or_default())))
     }
 
     #[instrument(skip(connection))]
     pub(crate) fn use_agent(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         external_id: &ExternalId,
         namespace: &ExternalId,
     ) -> Result<(), StoreError> {
@@ -1283,10 +2953,11 @@ impl Store {
         Ok(())
     }
 
+    /// The provenance of a single agent and its relations, reading only
     #[instrument(level = "debug", skip(connection))]
     pub fn prov_model_for_agent_id(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         id: &AgentId,
         ns: &ExternalId,
     ) -> Result<ProvModel, StoreError> {
@@ -1307,7 +2978,7 @@ impl Store {
     #[instrument(level = "debug", skip(connection))]
     pub fn apply_prov_model_for_agent_id(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         mut model: ProvModel,
         id: &AgentId,
         ns: &ExternalId,
@@ -1326,10 +2997,11 @@ impl Store {
         Ok(model)
     }
 
+    /// The provenance of a single activity and its relations, reading only
     #[instrument(level = "debug", skip(connection))]
     pub fn prov_model_for_activity_id(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         id: &ActivityId,
         ns: &ExternalId,
     ) -> Result<ProvModel, StoreError> {
@@ -1350,7 +3022,7 @@ impl Store {
     #[instrument(level = "debug", skip(connection))]
     pub fn apply_prov_model_for_activity_id(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         mut model: ProvModel,
         id: &ActivityId,
         ns: &ExternalId,
@@ -1369,10 +3041,11 @@ impl Store {
         Ok(model)
     }
 
+    /// The provenance of a single entity and its relations, reading only
     #[instrument(level = "debug", skip(connection))]
     pub fn prov_model_for_entity_id(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         id: &EntityId,
         ns: &ExternalId,
     ) -> Result<ProvModel, StoreError> {
@@ -1393,7 +3066,7 @@ impl Store {
     #[instrument(level = "debug", skip(connection))]
     pub fn apply_prov_model_for_entity_id(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         mut model: ProvModel,
         id: &EntityId,
         ns: &ExternalId,
@@ -1412,9 +3085,176 @@ impl Store {
         Ok(model)
     }
 
+    /// The upstream provenance of an entity, following derivation, generation
+    /// and usage relations for at most `depth` hops in a single recursive query.
+    /// Relations of the records at the limit of the walk are included, but the
+    /// records at their far end are not.
+    #[instrument(level = "debug", skip(connection))]
+    pub fn prov_model_for_lineage(
+        &self,
+        connection: &mut DatabaseConnection,
+        id: &EntityId,
+        ns: &ExternalId,
+        depth: u32,
+    ) -> Result<ProvModel, StoreError> {
+        use diesel::sql_types::Integer;
+
+        let (namespace, nsid) = self.namespace_by_external_id(connection, ns)?;
+
+        let root = schema::entity::table
+            .filter(schema::entity::external_id.eq(id.external_id_part()))
+            .filter(schema::entity::namespace_id.eq(nsid))
+            .select(schema::entity::id)
+            .first::<i32>(connection)?;
+
+        let nodes = diesel::sql_query(LINEAGE_SQL)
+            .bind::<Integer, _>(root)
+            .bind::<Integer, _>(i32::try_from(depth).unwrap_or(i32::MAX))
+            .load::<query::LineageNode>(connection)?;
+
+        let (entity_ids, activity_ids): (Vec<_>, Vec<_>) =
+            nodes.into_iter().partition(|node| node.kind == "entity");
+
+        let mut model = ProvModel::default();
+
+        for entity in schema::entity::table
+            .filter(schema::entity::id.eq_any(entity_ids.into_iter().map(|node| node.id)))
+            .load::<query::Entity>(connection)?
+        {
+            self.prov_model_for_entity(entity, &namespace, &mut model, connection)?;
+        }
+
+        for activity in schema::activity::table
+            .filter(schema::activity::id.eq_any(activity_ids.into_iter().map(|node| node.id)))
+            .load::<query::Activity>(connection)?
+        {
+            self.prov_model_for_activity(activity, &namespace, &mut model, connection)?;
+        }
+
+        Ok(model)
+    }
+
+    /// Agents, activities and entities in namespace `ns` whose external id or
+    /// attribute values contain words beginning with any of the terms of
+    /// `query`, best matches first. Records matching more terms, or matching
+    /// in more of their attributes, rank higher.
+    #[instrument(level = "debug", skip(connection))]
+    pub fn text_search(
+        &self,
+        connection: &mut DatabaseConnection,
+        ns: &ExternalId,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<query::TextSearchHit>, StoreError> {
+        use diesel::sql_types::{BigInt, Text};
+
+        let terms = search_terms(query);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+
+        #[cfg(not(feature = "sqlite"))]
+        let hits = diesel::sql_query(TEXT_SEARCH_SQL)
+            .bind::<Text, _>(ns.as_str())
+            .bind::<Text, _>(
+                terms
+                    .iter()
+                    .map(|term| format!("{term}:*"))
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+            )
+            .bind::<BigInt, _>(limit)
+            .load::<query::TextSearchHit>(connection)?;
+
+        // SQLite has no text search indexes without an extension, so documents
+        // are scanned for each term, scoring one for each term they contain
+        #[cfg(feature = "sqlite")]
+        let hits = {
+            let score = vec!["(instr(lower(text), ?) > 0)"; terms.len()].join(" + ");
+            let sql = format!(
+                "{}SELECT kind, external_id, domaintype, CAST(SUM({score}) AS DOUBLE) AS score \
+                 FROM document GROUP BY kind, record_id, external_id, domaintype \
+                 HAVING score > 0 ORDER BY score DESC, external_id LIMIT ?",
+                text_search_documents!("?"),
+            );
+            let mut search = diesel::sql_query(sql).into_boxed::<DatabaseBackend>();
+            for _ in 0..6 {
+                search = search.bind::<Text, _>(ns.as_str().to_owned());
+            }
+            for term in terms {
+                search = search.bind::<Text, _>(term);
+            }
+            search
+                .bind::<BigInt, _>(limit)
+                .load::<query::TextSearchHit>(connection)?
+        };
+
+        Ok(hits)
+    }
+
+    /// The records an explanation of `subject` describes: the lineage of an
+    /// entity for at most `depth` hops, an activity and the entities it used,
+    /// or an agent, with the agents responsible for each of them
+    pub fn prov_model_for_explanation(
+        &self,
+        connection: &mut DatabaseConnection,
+        subject: &ChronicleIri,
+        ns: &ExternalId,
+        depth: u32,
+    ) -> Result<ProvModel, StoreError> {
+        let mut model = match subject {
+            ChronicleIri::Entity(id) => self.prov_model_for_lineage(connection, id, ns, depth)?,
+            ChronicleIri::Activity(id) => {
+                let mut model = self.prov_model_for_activity_id(connection, id, ns)?;
+                let used = model
+                    .usage
+                    .values()
+                    .flatten()
+                    .map(|usage| usage.entity_id.clone())
+                    .collect::<BTreeSet<_>>();
+                for entity in used {
+                    model = self.apply_prov_model_for_entity_id(connection, model, &entity, ns)?;
+                }
+                model
+            }
+            ChronicleIri::Agent(id) => self.prov_model_for_agent_id(connection, id, ns)?,
+            _ => ProvModel::default(),
+        };
+
+        // Agents of associations and attributions, then those they acted for
+        for _ in 0..2 {
+            let agents = model
+                .association
+                .values()
+                .flatten()
+                .map(|association| association.agent_id.clone())
+                .chain(
+                    model
+                        .attribution
+                        .values()
+                        .flatten()
+                        .map(|attribution| attribution.agent_id.clone()),
+                )
+                .chain(
+                    model
+                        .acted_on_behalf_of
+                        .values()
+                        .flatten()
+                        .map(|delegation| delegation.responsible_id.clone()),
+                )
+                .filter(|agent| !model.agents.keys().any(|(_, id)| id == agent))
+                .collect::<BTreeSet<_>>();
+            for agent in agents {
+                model = self.apply_prov_model_for_agent_id(connection, model, &agent, ns)?;
+            }
+        }
+
+        Ok(model)
+    }
+
     pub(crate) fn prov_model_for_usage(
         &self,
-        connection: &mut PgConnection,
+        connection: &mut DatabaseConnection,
         mut model: ProvModel,
         id: &EntityId,
         activity_id: &ActivityId,
@@ -1457,3 +3297,323 @@ impl Store {
         Ok(model)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+    use common::{
+        attributes::{Attribute, Attributes},
+        database::TemporaryDatabase,
+        prov::{
+            operations::{ChronicleOperation, CreateNamespace, EntityExists, SetAttributes},
+            ChronicleIri, EntityId, NamespaceId, ProvModel,
+        },
+    };
+    use diesel::prelude::*;
+    use diesel_migrations::MigrationHarness;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::{schema, IdempotencyKeyHolder, Store, MIGRATIONS};
+
+    fn store(database: &TemporaryDatabase) -> Store {
+        let pool = database.connection_pool().unwrap();
+        pool.get()
+            .unwrap()
+            .run_pending_migrations(MIGRATIONS)
+            .unwrap();
+        Store::new(pool).unwrap()
+    }
+
+    /// The entity `testentity` with its `name` attribute set to `name`
+    fn named(name: &str) -> ProvModel {
+        let namespace = NamespaceId::from_external_id("testns", Uuid::nil());
+        ProvModel::from_tx(&[
+            ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                namespace.clone(),
+                "testns",
+                Uuid::nil(),
+            )),
+            ChronicleOperation::EntityExists(EntityExists {
+                namespace: namespace.clone(),
+                external_id: "testentity".into(),
+            }),
+            ChronicleOperation::SetAttributes(SetAttributes::Entity {
+                namespace,
+                id: EntityId::from_external_id("testentity"),
+                attributes: Attributes {
+                    typ: None,
+                    attributes: [("name".to_owned(), Attribute::new("name", json!(name)))]
+                        .into_iter()
+                        .collect(),
+                },
+            }),
+        ])
+        .unwrap()
+    }
+
+    fn history(store: &Store) -> Vec<(String, String, String)> {
+        use schema::attribute_history::dsl;
+
+        dsl::attribute_history
+            .order(dsl::id)
+            .select((dsl::record_type, dsl::typename, dsl::value))
+            .load(&mut store.connection().unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn overwritten_attributes_are_kept_in_history() {
+        let database = TemporaryDatabase::default();
+        let store = store(&database);
+        let mut connection = store.connection().unwrap();
+
+        store.apply_model(&mut connection, &named("first")).unwrap();
+        // Setting the same value again supersedes nothing
+        store.apply_model(&mut connection, &named("first")).unwrap();
+        assert!(history(&store).is_empty());
+
+        store
+            .apply_model(&mut connection, &named("second"))
+            .unwrap();
+        assert_eq!(
+            history(&store),
+            vec![(
+                "entity".to_owned(),
+                "name".to_owned(),
+                json!("first").to_string()
+            )]
+        );
+
+        let current = schema::entity_attribute::table
+            .select(schema::entity_attribute::value)
+            .load::<String>(&mut connection)
+            .unwrap();
+        assert_eq!(current, vec![json!("second").to_string()]);
+    }
+
+    #[test]
+    fn compaction_removes_only_history_older_than_retention() {
+        use schema::attribute_history::dsl;
+
+        let database = TemporaryDatabase::default();
+        let store = store(&database);
+        let mut connection = store.connection().unwrap();
+
+        for name in ["first", "second", "third"] {
+            store.apply_model(&mut connection, &named(name)).unwrap();
+        }
+
+        // The first value was superseded two days ago
+        diesel::update(dsl::attribute_history.filter(dsl::value.eq(json!("first").to_string())))
+            .set(dsl::superseded_at.eq((Utc::now() - Duration::days(2)).naive_utc()))
+            .execute(&mut connection)
+            .unwrap();
+
+        let removed = store
+            .compact_attribute_history(Utc::now() - Duration::days(1))
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(
+            history(&store),
+            vec![(
+                "entity".to_owned(),
+                "name".to_owned(),
+                json!("second").to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn idempotency_keys_are_reserved_before_submission() {
+        let database = TemporaryDatabase::default();
+        let store = store(&database);
+
+        assert_eq!(
+            store
+                .reserve_idempotency_key("alice", "key", "digest")
+                .unwrap(),
+            IdempotencyKeyHolder::Reserved
+        );
+        // The same command sent again while the first is submitting
+        assert_eq!(
+            store
+                .reserve_idempotency_key("alice", "key", "digest")
+                .unwrap(),
+            IdempotencyKeyHolder::Pending {
+                command_digest: "digest".to_owned()
+            }
+        );
+        // Keys are scoped to their identity
+        assert_eq!(
+            store
+                .reserve_idempotency_key("bob", "key", "digest")
+                .unwrap(),
+            IdempotencyKeyHolder::Reserved
+        );
+
+        // A command that submitted nothing frees its key
+        store.release_idempotency_key("bob", "key").unwrap();
+        assert_eq!(
+            store
+                .reserve_idempotency_key("bob", "key", "other")
+                .unwrap(),
+            IdempotencyKeyHolder::Reserved
+        );
+
+        let subject = ChronicleIri::from(EntityId::from_external_id("testentity"));
+        store
+            .complete_idempotency_key("alice", "key", &subject, &"tx".into())
+            .unwrap();
+        // A submitted key is not released
+        store.release_idempotency_key("alice", "key").unwrap();
+        assert_eq!(
+            store
+                .reserve_idempotency_key("alice", "key", "digest")
+                .unwrap(),
+            IdempotencyKeyHolder::Submitted {
+                command_digest: "digest".to_owned(),
+                subject,
+                tx_id: "tx".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn operations_are_audited_once_however_often_they_are_synced() {
+        use schema::audit_log::dsl;
+
+        let database = TemporaryDatabase::default();
+        let store = store(&database);
+
+        let namespace = NamespaceId::from_external_id("testns", Uuid::nil());
+        let entity = EntityId::from_external_id("testentity");
+        // Two operations on the same entity, logged as two rows for it
+        let operations = [
+            ChronicleOperation::EntityExists(EntityExists {
+                namespace: namespace.clone(),
+                external_id: "testentity".into(),
+            }),
+            ChronicleOperation::SetAttributes(SetAttributes::Entity {
+                namespace,
+                id: entity.clone(),
+                attributes: Attributes::type_only(None),
+            }),
+        ];
+        let logged = || {
+            dsl::audit_log
+                .filter(dsl::subject.eq(entity.to_string()))
+                .order(dsl::operation_index)
+                .select((dsl::operation, dsl::operation_index))
+                .load::<(String, i32)>(&mut store.connection().unwrap())
+                .unwrap()
+        };
+
+        store
+            .record_audit(&"tx".into(), "Chronicle", &operations)
+            .unwrap();
+        let once = logged();
+        assert_eq!(
+            once,
+            vec![
+                ("EntityExists".to_owned(), 0),
+                ("SetAttributes".to_owned(), 1)
+            ]
+        );
+
+        store
+            .record_audit(&"tx".into(), "Chronicle", &operations)
+            .unwrap();
+        assert_eq!(logged(), once);
+    }
+
+    /// The entity `testentity` in the namespace `namespace`
+    #[cfg(not(feature = "sqlite"))]
+    fn entity_in(namespace: &str) -> ProvModel {
+        let namespace_id = NamespaceId::from_external_id(namespace, Uuid::nil());
+        ProvModel::from_tx(&[
+            ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                namespace_id.clone(),
+                namespace,
+                Uuid::nil(),
+            )),
+            ChronicleOperation::EntityExists(EntityExists {
+                namespace: namespace_id,
+                external_id: "testentity".into(),
+            }),
+        ])
+        .unwrap()
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[test]
+    fn tenants_see_only_their_own_records_unless_their_role_bypasses_row_security() {
+        let database = TemporaryDatabase::default();
+        let store = store(&database);
+        let mut connection = store.connection().unwrap();
+
+        let now = Utc::now().naive_utc();
+        for namespace in ["acme", "other"] {
+            store
+                .apply_model(&mut connection, &entity_in(namespace))
+                .unwrap();
+            let namespace_id = schema::namespace::table
+                .filter(schema::namespace::external_id.eq(namespace))
+                .select(schema::namespace::id)
+                .first::<i32>(&mut connection)
+                .unwrap();
+            diesel::insert_into(schema::export_job::table)
+                .values((
+                    schema::export_job::id.eq(namespace),
+                    schema::export_job::namespace_id.eq(namespace_id),
+                    schema::export_job::format.eq("json-ld"),
+                    schema::export_job::status.eq("queued"),
+                    schema::export_job::created_at.eq(now),
+                    schema::export_job::updated_at.eq(now),
+                ))
+                .execute(&mut connection)
+                .unwrap();
+        }
+
+        diesel::sql_query("create role tenant_reader nologin")
+            .execute(&mut connection)
+            .unwrap();
+        diesel::sql_query("grant select on all tables in schema public to tenant_reader")
+            .execute(&mut connection)
+            .unwrap();
+
+        // The namespaces of the entities and export jobs read as `role`, or as
+        // the connecting role if none
+        let visible = |tenant: Option<&str>, role: Option<&str>| {
+            store
+                .clone()
+                .with_tenant(tenant.map(str::to_owned))
+                .read_only(|connection| {
+                    if let Some(role) = role {
+                        diesel::sql_query(format!("set local role {role}")).execute(connection)?;
+                    }
+                    let entities = schema::entity::table
+                        .inner_join(schema::namespace::table)
+                        .order(schema::namespace::external_id)
+                        .select(schema::namespace::external_id)
+                        .load::<String>(connection)?;
+                    let export_jobs = schema::export_job::table
+                        .order(schema::export_job::id)
+                        .select(schema::export_job::id)
+                        .load::<String>(connection)?;
+                    Ok((entities, export_jobs))
+                })
+                .unwrap()
+        };
+        let both = || vec!["acme".to_owned(), "other".to_owned()];
+
+        assert_eq!(
+            visible(Some("acme"), Some("tenant_reader")),
+            (vec!["acme".to_owned()], vec!["acme".to_owned()])
+        );
+        assert_eq!(visible(None, Some("tenant_reader")), (both(), both()));
+        // The test database connects as a superuser, which sees every tenant
+        assert!(store.role_bypassing_row_security().unwrap().is_some());
+        assert_eq!(visible(Some("acme"), None), (both(), both()));
+    }
+}
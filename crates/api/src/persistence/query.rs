@@ -14,6 +14,32 @@ pub struct LedgerSync {
     pub sync_time: Option<NaiveDateTime>,
 }
 
+/// An operation from the audit log, with the block and time it was synced
+#[derive(Queryable)]
+pub struct AuditRecord {
+    pub id: i32,
+    pub tx_id: String,
+    pub namespace: String,
+    pub subject: String,
+    pub operation: String,
+    pub detail: String,
+    pub identity: String,
+    pub submitted_at: NaiveDateTime,
+    pub block_id: Option<String>,
+    pub synced_at: Option<NaiveDateTime>,
+}
+
+/// The receipt of a submitted transaction, with the block it committed in and
+/// its signature and signing key once it has
+#[derive(Queryable)]
+pub struct Receipt {
+    pub tx_id: String,
+    pub operation_digest: String,
+    pub block_id: Option<String>,
+    pub key_id: Option<String>,
+    pub signature: Option<String>,
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = namespace)]
 pub struct NewNamespace<'a> {
@@ -52,6 +78,16 @@ pub struct AgentAttribute {
     pub value: String,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = attribute_history)]
+pub struct NewAttributeHistory<'a> {
+    pub record_type: &'a str,
+    pub record_id: i32,
+    pub typename: String,
+    pub value: String,
+    pub superseded_at: NaiveDateTime,
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = activity)]
 pub struct NewActivity<'a> {
@@ -71,6 +107,10 @@ pub struct Agent {
     pub domaintype: Option<String>,
     pub current: i32,
     pub identity_id: Option<i32>,
+    pub created_at: Option<NaiveDateTime>,
+    pub created_block: Option<String>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub updated_block: Option<String>,
 }
 
 #[derive(Debug, Queryable)]
@@ -89,6 +129,10 @@ pub struct Activity {
     pub domaintype: Option<String>,
     pub started: Option<NaiveDateTime>,
     pub ended: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+    pub created_block: Option<String>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub updated_block: Option<String>,
 }
 
 #[derive(Debug, Queryable, Selectable)]
@@ -98,6 +142,10 @@ pub struct Entity {
     pub external_id: String,
     pub namespace_id: i32,
     pub domaintype: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub created_block: Option<String>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub updated_block: Option<String>,
 }
 
 #[derive(Insertable, Queryable, Selectable)]
@@ -108,3 +156,35 @@ pub struct NewAgent<'a> {
     pub current: i32,
     pub domaintype: Option<&'a str>,
 }
+
+/// A record reached by a lineage walk, `kind` is either "entity" or "activity"
+#[derive(Debug, QueryableByName)]
+pub struct LineageNode {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub kind: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub id: i32,
+}
+
+/// A record matching a text search, `kind` is "agent", "activity" or "entity"
+#[derive(Debug, QueryableByName)]
+pub struct TextSearchHit {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub kind: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub external_id: String,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Text>)]
+    pub domaintype: Option<String>,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub score: f64,
+}
+
+/// The role a connection is made as, `bypasses_row_security` if it is a
+/// superuser or has `BYPASSRLS`
+#[derive(Debug, QueryableByName)]
+pub struct DatabaseRole {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub name: String,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub bypasses_row_security: bool,
+}
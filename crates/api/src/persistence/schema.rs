@@ -8,6 +8,10 @@ diesel::table! {
         domaintype -> Nullable<Text>,
         started -> Nullable<Timestamp>,
         ended -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
     }
 }
 
@@ -27,6 +31,10 @@ diesel::table! {
         domaintype -> Nullable<Text>,
         current -> Int4,
         identity_id -> Nullable<Int4>,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
     }
 }
 
@@ -43,6 +51,21 @@ diesel::table! {
         agent_id -> Int4,
         activity_id -> Int4,
         role -> Text,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    attribute_history (id) {
+        id -> Int4,
+        record_type -> Text,
+        record_id -> Int4,
+        typename -> Text,
+        value -> Text,
+        superseded_at -> Timestamp,
     }
 }
 
@@ -51,6 +74,34 @@ diesel::table! {
         agent_id -> Int4,
         entity_id -> Int4,
         role -> Text,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Int4,
+        tx_id -> Text,
+        namespace -> Text,
+        subject -> Text,
+        operation -> Text,
+        detail -> Text,
+        identity -> Text,
+        submitted_at -> Timestamp,
+        operation_index -> Int4,
+    }
+}
+
+diesel::table! {
+    backfill_progress (task, record_type) {
+        task -> Text,
+        record_type -> Text,
+        last_id -> Int4,
+        processed -> Int8,
+        updated_at -> Timestamp,
     }
 }
 
@@ -60,6 +111,12 @@ diesel::table! {
         responsible_id -> Int4,
         activity_id -> Int4,
         role -> Text,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
+        valid_from -> Nullable<Timestamp>,
+        valid_until -> Nullable<Timestamp>,
     }
 }
 
@@ -69,6 +126,10 @@ diesel::table! {
         generated_entity_id -> Int4,
         used_entity_id -> Int4,
         typ -> Int4,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
     }
 }
 
@@ -78,6 +139,10 @@ diesel::table! {
         external_id -> Text,
         namespace_id -> Int4,
         domaintype -> Nullable<Text>,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
     }
 }
 
@@ -89,10 +154,29 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    export_job (id) {
+        id -> Text,
+        namespace_id -> Int4,
+        format -> Text,
+        status -> Text,
+        modified_since -> Nullable<Timestamp>,
+        callback -> Nullable<Text>,
+        artifact -> Nullable<Text>,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     generation (activity_id, generated_entity_id) {
         activity_id -> Int4,
         generated_entity_id -> Int4,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
     }
 }
 
@@ -111,11 +195,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    idempotency_key (identity, key) {
+        identity -> Text,
+        key -> Text,
+        command_digest -> Text,
+        subject -> Nullable<Text>,
+        tx_id -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     ledgersync (tx_id) {
         tx_id -> Text,
         bc_offset -> Nullable<Text>,
         sync_time -> Nullable<Timestamp>,
+        ledger_sequence -> Int8,
     }
 }
 
@@ -127,10 +223,53 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    namespace_sync (namespace_id) {
+        namespace_id -> Int4,
+        bc_offset -> Text,
+        tx_id -> Text,
+        sync_time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    outbox (id) {
+        id -> Text,
+        command -> Text,
+        identity -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    prov_history (id) {
+        id -> Int4,
+        namespace_id -> Int4,
+        block_id -> Text,
+        tx_id -> Text,
+        delta -> Text,
+        recorded_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    receipt (tx_id) {
+        tx_id -> Text,
+        operation_digest -> Text,
+        block_id -> Nullable<Text>,
+        key_id -> Nullable<Text>,
+        signature -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     usage (activity_id, entity_id) {
         activity_id -> Int4,
         entity_id -> Int4,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
     }
 }
 
@@ -138,6 +277,27 @@ diesel::table! {
     wasinformedby (activity_id, informing_activity_id) {
         activity_id -> Int4,
         informing_activity_id -> Int4,
+        created_at -> Nullable<Timestamp>,
+        created_block -> Nullable<Text>,
+        updated_at -> Nullable<Timestamp>,
+        updated_block -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    webhook_delivery (id) {
+        id -> Text,
+        url -> Text,
+        tx_id -> Text,
+        payload -> Text,
+        signature -> Text,
+        status -> Text,
+        attempts -> Int4,
+        response_status -> Nullable<Int4>,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        next_attempt_at -> Timestamp,
     }
 }
 
@@ -154,11 +314,14 @@ diesel::joinable!(delegation -> activity (activity_id));
 diesel::joinable!(derivation -> activity (activity_id));
 diesel::joinable!(entity -> namespace (namespace_id));
 diesel::joinable!(entity_attribute -> entity (entity_id));
+diesel::joinable!(export_job -> namespace (namespace_id));
 diesel::joinable!(generation -> activity (activity_id));
 diesel::joinable!(generation -> entity (generated_entity_id));
 diesel::joinable!(hadidentity -> agent (agent_id));
 diesel::joinable!(hadidentity -> identity (identity_id));
 diesel::joinable!(identity -> namespace (namespace_id));
+diesel::joinable!(namespace_sync -> namespace (namespace_id));
+diesel::joinable!(prov_history -> namespace (namespace_id));
 diesel::joinable!(usage -> activity (activity_id));
 diesel::joinable!(usage -> entity (entity_id));
 
@@ -168,16 +331,26 @@ diesel::allow_tables_to_appear_in_same_query!(
     agent,
     agent_attribute,
     association,
+    attribute_history,
     attribution,
+    audit_log,
+    backfill_progress,
     delegation,
     derivation,
     entity,
     entity_attribute,
+    export_job,
     generation,
     hadidentity,
     identity,
+    idempotency_key,
     ledgersync,
     namespace,
+    namespace_sync,
+    outbox,
+    prov_history,
+    receipt,
     usage,
     wasinformedby,
+    webhook_delivery,
 );
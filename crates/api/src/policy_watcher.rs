@@ -0,0 +1,146 @@
+//! The policy watcher keeps the OPA policy Chronicle enforces in step with the
+//! one set on chain. The OPA transaction processor emits an event for each
+//! policy it sets, and when one replaces the policy this deployment was
+//! started with, the policy is loaded again and swapped into the running
+//! executor, so the new rules apply without a restart.
+
+use std::{net::SocketAddr, time::Duration};
+
+use async_stl_client::{
+    error::SawtoothCommunicationError,
+    ledger::{FromBlock, LedgerReader},
+    zmq_client::{HighestBlockValidatorSelector, ZmqRequestResponseSawtoothChannel},
+};
+use common::opa::{
+    ExecutorContext, OpaExecutorError, PolicyLoader, PolicyLoaderError, SawtoothPolicyLoader,
+};
+use futures::StreamExt;
+use opa_tp_protocol::{
+    address::{FAMILY, VERSION},
+    state::OpaOperationEvent,
+    OpaLedger,
+};
+use thiserror::Error;
+use tracing::{debug, error, info};
+
+/// How long to wait before subscribing again after the validator is lost
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error)]
+pub enum PolicyWatcherError {
+    #[error("Could not connect to the validator: {0}")]
+    Ledger(#[from] SawtoothCommunicationError),
+
+    #[error("Could not load OPA policy: {0}")]
+    Loader(#[from] PolicyLoaderError),
+
+    #[error("Could not build OPA policy: {0}")]
+    Executor(#[from] OpaExecutorError),
+}
+
+/// The on-chain policy enforced by a deployment
+#[derive(Debug, Clone)]
+pub struct PolicyWatcherConf {
+    pub validator: SocketAddr,
+    pub policy_name: String,
+    pub entrypoint: String,
+}
+
+/// Whether `event` sets the policy named `policy_name`
+fn replaces_policy(event: &OpaOperationEvent, policy_name: &str) -> bool {
+    matches!(event, OpaOperationEvent::PolicyUpdate(meta) if meta.id == policy_name)
+}
+
+/// Load the policy from chain and swap it into `context`
+async fn reload(
+    context: &ExecutorContext,
+    conf: &PolicyWatcherConf,
+) -> Result<(), PolicyWatcherError> {
+    let mut loader =
+        SawtoothPolicyLoader::new(&conf.validator, &conf.policy_name, &conf.entrypoint)?;
+    loader.load_policy().await?;
+    context.replace(&loader).await?;
+    Ok(())
+}
+
+/// Watch the OPA transaction processor's events from the current block, and
+/// reload the policy in `context` whenever it is set again on chain
+pub fn spawn_policy_watcher(
+    context: ExecutorContext,
+    conf: PolicyWatcherConf,
+) -> Result<(), PolicyWatcherError> {
+    let reader = OpaLedger::new(
+        ZmqRequestResponseSawtoothChannel::new(
+            "policy_watcher",
+            &[conf.validator],
+            HighestBlockValidatorSelector,
+        )?
+        .retrying(),
+        FAMILY,
+        VERSION,
+    );
+    info!(policy = %conf.policy_name, "Watching for OPA policy updates");
+
+    tokio::spawn(async move {
+        loop {
+            let mut updates = match reader
+                .state_updates("opa/operation", FromBlock::Head, None)
+                .await
+            {
+                Ok(updates) => updates,
+                Err(e) => {
+                    error!(subscribe_to_policy_updates = ?e);
+                    tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                    continue;
+                }
+            };
+
+            while let Some((event, tx, block_id, _position, _span)) = updates.next().await {
+                if !replaces_policy(&event, &conf.policy_name) {
+                    continue;
+                }
+
+                debug!(%tx, %block_id, "OPA policy updated on chain");
+                if let Err(e) = reload(&context, &conf).await {
+                    error!(%e, policy = %conf.policy_name, "Failed to reload OPA policy");
+                }
+            }
+
+            debug!("Policy update stream ended");
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use opa_tp_protocol::state::{OpaOperationEvent, PolicyMeta};
+
+    use super::replaces_policy;
+
+    fn policy_update(id: &str) -> OpaOperationEvent {
+        OpaOperationEvent::PolicyUpdate(PolicyMeta {
+            id: id.to_owned(),
+            hash: "hash".to_owned(),
+            policy_address: "address".to_owned(),
+        })
+    }
+
+    #[test]
+    fn only_updates_to_the_enforced_policy_reload_it() {
+        assert!(replaces_policy(
+            &policy_update("allow_transactions"),
+            "allow_transactions"
+        ));
+        assert!(!replaces_policy(
+            &policy_update("another_policy"),
+            "allow_transactions"
+        ));
+        assert!(!replaces_policy(
+            &OpaOperationEvent::Error("denied".to_owned()),
+            "allow_transactions"
+        ));
+    }
+}
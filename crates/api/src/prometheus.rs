@@ -0,0 +1,134 @@
+//! Prometheus metrics for the api: commands dispatched, ledger submissions,
+//! transactions synchronized into the store, database pool utilization, and
+//! the progress of backfills.
+//! The macros record nothing until an exporter is installed.
+
+use std::{net::SocketAddr, time::Duration};
+
+use common::commands::{ApiCommand, IdempotentCommand};
+use diesel::r2d2::{ConnectionManager, Pool};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use tracing::{debug, error};
+
+use crate::DatabaseConnection;
+
+/// How often the database pool's utilization is sampled
+const POOL_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Serve metrics for scraping at `/metrics` on `address`
+pub fn install_prometheus_metrics_exporter(address: SocketAddr) {
+    if let Err(e) = PrometheusBuilder::new()
+        .with_http_listener(address)
+        .install()
+    {
+        error!("Prometheus exporter installation failed: {e:?}");
+    } else {
+        debug!("Prometheus exporter installed with endpoint on {address}/metrics");
+    }
+}
+
+/// True once a metrics exporter has been installed for the process
+pub(crate) fn exporter_installed() -> bool {
+    metrics::try_recorder().is_some()
+}
+
+pub(crate) fn command_label(command: &ApiCommand) -> &'static str {
+    match command {
+        ApiCommand::NameSpace(_) => "namespace",
+        ApiCommand::Agent(_) => "agent",
+        ApiCommand::Activity(_) => "activity",
+        ApiCommand::Entity(_) => "entity",
+        ApiCommand::Query(_) => "query",
+        ApiCommand::DepthCharge(_) => "depth_charge",
+        ApiCommand::Import(_) => "import",
+        ApiCommand::RotateKey(_) => "rotate_key",
+        ApiCommand::Verify(_) => "verify",
+        ApiCommand::Checkpoint(_) => "checkpoint",
+        ApiCommand::Outbox(_) => "outbox",
+        ApiCommand::Idempotent(IdempotentCommand { command, .. }) => command_label(command),
+    }
+}
+
+fn outcome_label(succeeded: bool) -> &'static str {
+    if succeeded {
+        "ok"
+    } else {
+        "error"
+    }
+}
+
+pub(crate) fn record_command(command: &'static str, elapsed: Duration, succeeded: bool) {
+    counter!(
+        "chronicle_api_commands_total",
+        1,
+        "command" => command,
+        "outcome" => outcome_label(succeeded)
+    );
+    histogram!(
+        "chronicle_api_command_duration_seconds",
+        elapsed.as_secs_f64(),
+        "command" => command
+    );
+}
+
+pub(crate) fn record_submission(elapsed: Duration, succeeded: bool) {
+    counter!(
+        "chronicle_ledger_submissions_total",
+        1,
+        "outcome" => outcome_label(succeeded)
+    );
+    histogram!(
+        "chronicle_ledger_submission_duration_seconds",
+        elapsed.as_secs_f64()
+    );
+}
+
+pub(crate) fn record_sync(elapsed: Duration, transactions: usize, succeeded: bool) {
+    counter!(
+        "chronicle_ledger_transactions_synced_total",
+        transactions as u64,
+        "outcome" => outcome_label(succeeded)
+    );
+    histogram!(
+        "chronicle_store_sync_duration_seconds",
+        elapsed.as_secs_f64()
+    );
+}
+
+pub(crate) fn record_backfill(
+    task: &'static str,
+    record_type: &'static str,
+    records: u64,
+    remaining: i64,
+) {
+    counter!(
+        "chronicle_backfill_records_total",
+        records,
+        "task" => task,
+        "record_type" => record_type
+    );
+    gauge!(
+        "chronicle_backfill_remaining_records",
+        remaining as f64,
+        "task" => task,
+        "record_type" => record_type
+    );
+}
+
+/// Sample the pool's open and idle connections for as long as the process runs
+pub(crate) fn sample_pool_utilization(pool: Pool<ConnectionManager<DatabaseConnection>>) {
+    tokio::task::spawn(async move {
+        loop {
+            let state = pool.state();
+            gauge!("chronicle_db_pool_connections", state.connections as f64);
+            gauge!(
+                "chronicle_db_pool_idle_connections",
+                state.idle_connections as f64
+            );
+            gauge!("chronicle_db_pool_max_connections", pool.max_size() as f64);
+
+            tokio::time::sleep(POOL_SAMPLE_INTERVAL).await;
+        }
+    });
+}
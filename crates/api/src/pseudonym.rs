@@ -0,0 +1,198 @@
+//! Pseudonyms for namespaces, for deployments where the names of namespaces
+//! are themselves sensitive. Deltas published outside Chronicle, through the
+//! event sink and webhooks, can name each namespace by a pseudonym derived
+//! from an HMAC-SHA256 of its uuid under a key held by the deployment. The
+//! same namespace always has the same pseudonym, so consumers can still tell
+//! namespaces apart, but cannot recover their names without the key. Chronicle
+//! itself, and its api, continue to use the real namespace.
+
+use std::path::Path;
+
+use common::prov::{AsCompact, ExternalIdPart, FromCompact, NamespaceId, ProvModel, UuidPart};
+use openssl::{
+    error::ErrorStack,
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    sign::Signer,
+};
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PseudonymError {
+    #[error("Could not read pseudonym key {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Pseudonym key {0} is empty")]
+    EmptyKey(String),
+
+    #[error("Could not derive pseudonym: {0}")]
+    OpenSsl(#[from] ErrorStack),
+}
+
+/// The key namespace pseudonyms are derived with
+#[derive(Clone)]
+pub struct NamespacePseudonyms {
+    key: PKey<Private>,
+}
+
+impl std::fmt::Debug for NamespacePseudonyms {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NamespacePseudonyms")
+            .finish_non_exhaustive()
+    }
+}
+
+impl NamespacePseudonyms {
+    pub fn new(key: &[u8]) -> Result<Self, PseudonymError> {
+        Ok(Self {
+            key: PKey::hmac(key)?,
+        })
+    }
+
+    /// Read the key from a file, ignoring surrounding whitespace
+    pub fn from_file(path: &Path) -> Result<Self, PseudonymError> {
+        let key = std::fs::read_to_string(path).map_err(|source| PseudonymError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        if key.trim().is_empty() {
+            return Err(PseudonymError::EmptyKey(path.display().to_string()));
+        }
+
+        Self::new(key.trim().as_bytes())
+    }
+
+    /// The namespace `namespace` is published as, named by the hex of the
+    /// first half of its HMAC, with a uuid made from the second half
+    pub fn pseudonym(&self, namespace: &NamespaceId) -> Result<NamespaceId, PseudonymError> {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.update(namespace.uuid_part().as_bytes())?;
+        let hmac = signer.sign_to_vec()?;
+
+        let mut uuid = [0u8; 16];
+        uuid.copy_from_slice(&hmac[16..32]);
+
+        Ok(NamespaceId::from_external_id(
+            hex::encode(&hmac[..16]),
+            uuid::Builder::from_random_bytes(uuid).into_uuid(),
+        ))
+    }
+
+    /// Replace each namespace of `delta` in `json`, its JSON-LD in compact or
+    /// expanded form, with its pseudonym
+    pub fn apply(&self, delta: &ProvModel, json: &mut Value) -> Result<(), PseudonymError> {
+        for namespace in delta.namespaces.keys() {
+            let pseudonym = self.pseudonym(namespace)?;
+            replace(
+                json,
+                &[
+                    (namespace.compact(), pseudonym.compact()),
+                    (namespace.de_compact(), pseudonym.de_compact()),
+                ],
+                &(
+                    namespace.external_id_part().to_string(),
+                    pseudonym.external_id_part().to_string(),
+                ),
+                false,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Replace strings that are one of the `iris` of a namespace, and its
+/// `external_id` within the node describing it
+fn replace(
+    json: &mut Value,
+    iris: &[(String, String)],
+    external_id: &(String, String),
+    in_namespace: bool,
+) {
+    match json {
+        Value::String(s) => {
+            if let Some((_, pseudonym)) = iris.iter().find(|(iri, _)| iri == s) {
+                *s = pseudonym.clone();
+            } else if in_namespace && *s == external_id.0 {
+                *s = external_id.1.clone();
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                replace(value, iris, external_id, in_namespace);
+            }
+        }
+        Value::Object(fields) => {
+            let describes_namespace = in_namespace
+                || fields
+                    .get("@id")
+                    .and_then(Value::as_str)
+                    .is_some_and(|id| iris.iter().any(|(iri, _)| iri == id));
+            for (key, value) in fields.iter_mut() {
+                // References to the namespace from other nodes are only its iri
+                let in_namespace = describes_namespace && key != "@id";
+                replace(value, iris, external_id, in_namespace);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::prov::{
+        operations::{ActivityExists, ChronicleOperation, CreateNamespace},
+        to_json_ld::ToJson,
+        ExternalIdPart, NamespaceId, ProvModel, UuidPart,
+    };
+    use uuid::Uuid;
+
+    use super::NamespacePseudonyms;
+
+    #[tokio::test]
+    async fn namespaces_are_replaced_by_stable_pseudonyms() {
+        let namespace = NamespaceId::from_external_id("payroll", Uuid::new_v4());
+        let delta = ProvModel::from_tx(&[
+            ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                namespace.clone(),
+                "payroll",
+                *namespace.uuid_part(),
+            )),
+            ChronicleOperation::ActivityExists(ActivityExists {
+                namespace: namespace.clone(),
+                external_id: "run".into(),
+            }),
+        ])
+        .unwrap();
+
+        let pseudonyms = NamespacePseudonyms::new(b"secret").unwrap();
+        let pseudonym = pseudonyms.pseudonym(&namespace).unwrap();
+        assert_eq!(pseudonym, pseudonyms.pseudonym(&namespace).unwrap());
+        assert_ne!(
+            pseudonym,
+            NamespacePseudonyms::new(b"other")
+                .unwrap()
+                .pseudonym(&namespace)
+                .unwrap()
+        );
+
+        let mut compact = delta.to_json().compact().await.unwrap();
+        pseudonyms.apply(&delta, &mut compact).unwrap();
+        let mut expanded = delta.to_json().0;
+        pseudonyms.apply(&delta, &mut expanded).unwrap();
+
+        for json in [compact, expanded] {
+            let json = json.to_string();
+            assert!(!json.contains("payroll"), "{json}");
+            assert!(!json.contains(&namespace.uuid_part().to_string()), "{json}");
+            assert!(
+                json.contains(pseudonym.external_id_part().as_str()),
+                "{json}"
+            );
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use common::attributes::{AttributeConstraints, Attributes};
+use regex::Regex;
+use serde_json::Value;
+use thiserror::Error;
+
+/// The constraint an attribute value failed
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Violation {
+    #[error("does not match pattern {pattern}")]
+    Pattern { pattern: String },
+
+    #[error("is less than the minimum {minimum}")]
+    BelowMinimum { minimum: serde_json::Number },
+
+    #[error("is greater than the maximum {maximum}")]
+    AboveMaximum { maximum: serde_json::Number },
+
+    #[error("is not one of {}", Value::from(allowed.clone()))]
+    NotAllowed { allowed: Vec<Value> },
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Attribute {attribute} value {value} {violation}")]
+pub struct ValidationError {
+    pub attribute: String,
+    pub value: Value,
+    pub violation: Violation,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledConstraints {
+    constraints: AttributeConstraints,
+    pattern: Option<Regex>,
+}
+
+/// Constraints on attribute values, keyed by the name attributes are recorded
+/// under, checked by the api before it submits new agents, activities and
+/// entities. Attributes without constraints accept any value.
+#[derive(Debug, Clone, Default)]
+pub struct AttributeValidation {
+    constraints: BTreeMap<String, CompiledConstraints>,
+}
+
+impl AttributeValidation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constrain values of the attribute recorded as `attribute`, failing if
+    /// the pattern is not a valid regular expression
+    pub fn with_constraints(
+        mut self,
+        attribute: impl Into<String>,
+        constraints: AttributeConstraints,
+    ) -> Result<Self, regex::Error> {
+        let pattern = constraints.pattern.as_deref().map(Regex::new).transpose()?;
+
+        self.constraints.insert(
+            attribute.into(),
+            CompiledConstraints {
+                constraints,
+                pattern,
+            },
+        );
+
+        Ok(self)
+    }
+
+    pub fn validate(&self, attributes: &Attributes) -> Result<(), ValidationError> {
+        for (name, attribute) in &attributes.attributes {
+            if let Some(constraints) = self.constraints.get(name) {
+                match &attribute.value {
+                    Value::Array(values) => {
+                        for value in values {
+                            constraints.check(name, value)?;
+                        }
+                    }
+                    value => constraints.check(name, value)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CompiledConstraints {
+    fn check(&self, attribute: &str, value: &Value) -> Result<(), ValidationError> {
+        let violated = |violation| ValidationError {
+            attribute: attribute.to_owned(),
+            value: value.clone(),
+            violation,
+        };
+
+        if let (Some(pattern), Some(text)) = (&self.pattern, value.as_str()) {
+            if !pattern.is_match(text) {
+                return Err(violated(Violation::Pattern {
+                    pattern: pattern.to_string(),
+                }));
+            }
+        }
+
+        // Decimals are recorded as strings, so compare those numerically too
+        let number = value
+            .as_f64()
+            .or_else(|| value.as_str().and_then(|text| text.parse::<f64>().ok()));
+
+        if let Some(number) = number {
+            if let Some(minimum) = &self.constraints.minimum {
+                if minimum.as_f64().map_or(false, |minimum| number < minimum) {
+                    return Err(violated(Violation::BelowMinimum {
+                        minimum: minimum.clone(),
+                    }));
+                }
+            }
+            if let Some(maximum) = &self.constraints.maximum {
+                if maximum.as_f64().map_or(false, |maximum| number > maximum) {
+                    return Err(violated(Violation::AboveMaximum {
+                        maximum: maximum.clone(),
+                    }));
+                }
+            }
+        }
+
+        if let Some(allowed) = &self.constraints.allowed {
+            if !allowed.contains(value) {
+                return Err(violated(Violation::NotAllowed {
+                    allowed: allowed.clone(),
+                }));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::attributes::{Attribute, AttributeConstraints, Attributes};
+    use serde_json::{json, Value};
+
+    use super::{AttributeValidation, Violation};
+
+    fn attributes(name: &str, value: Value) -> Attributes {
+        Attributes {
+            typ: None,
+            attributes: [(name.to_owned(), Attribute::new(name, value))]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn validation() -> AttributeValidation {
+        AttributeValidation::new()
+            .with_constraints(
+                "Code",
+                AttributeConstraints {
+                    pattern: Some("^[A-Z]{3}$".to_owned()),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .with_constraints(
+                "Rating",
+                AttributeConstraints {
+                    minimum: Some(1.into()),
+                    maximum: Some(5.into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .with_constraints(
+                "Status",
+                AttributeConstraints {
+                    allowed: Some(vec![json!("draft"), json!("published")]),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn valid_values_are_accepted() {
+        let validation = validation();
+
+        assert!(validation
+            .validate(&attributes("Code", json!("ABC")))
+            .is_ok());
+        assert!(validation.validate(&attributes("Rating", json!(5))).is_ok());
+        assert!(validation
+            .validate(&attributes("Status", json!(["draft", "published"])))
+            .is_ok());
+        assert!(validation
+            .validate(&attributes("Unconstrained", json!("anything")))
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let validation = validation();
+
+        let err = validation
+            .validate(&attributes("Code", json!("abc")))
+            .unwrap_err();
+        assert_eq!(err.attribute, "Code");
+        assert_eq!(
+            err.violation,
+            Violation::Pattern {
+                pattern: "^[A-Z]{3}$".to_owned()
+            }
+        );
+
+        let err = validation
+            .validate(&attributes("Rating", json!("0.5")))
+            .unwrap_err();
+        assert_eq!(err.violation, Violation::BelowMinimum { minimum: 1.into() });
+
+        let err = validation
+            .validate(&attributes("Status", json!(["draft", "retracted"])))
+            .unwrap_err();
+        assert_eq!(err.value, json!("retracted"));
+        assert_eq!(
+            err.to_string(),
+            r#"Attribute Status value "retracted" is not one of ["draft","published"]"#
+        );
+    }
+
+    #[test]
+    fn invalid_pattern_is_an_error() {
+        assert!(AttributeValidation::new()
+            .with_constraints(
+                "Code",
+                AttributeConstraints {
+                    pattern: Some("[".to_owned()),
+                    ..Default::default()
+                },
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,764 @@
+//! Webhooks notify other systems of commits as they happen. Each webhook is a
+//! url with optional filters on the namespace, term and domain type of the
+//! records a commit touches. Matching commits are POSTed to the url as their
+//! compacted JSON-LD delta, signed with the chronicle key so receivers can
+//! check that they came from this Chronicle. A webhook may list public keys to
+//! encrypt its deliveries to, for receivers behind shared infrastructure, and
+//! namespaces may be delivered under pseudonyms.
+//! Deliveries are queued in the store by the sync leader as part of applying
+//! each commit, so every commit is queued once whichever replicas are running.
+//! Each replica runs a worker per webhook that claims due deliveries from the
+//! queue, so a delivery is attempted by one replica at a time. Deliveries are
+//! retried with increasing delays while the receiver is unavailable, without
+//! holding up the commits after them, and resumed when the api restarts.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chronicle_signing::{ChronicleKnownKeyNamesSigner, ChronicleSigning, SecretError};
+use chrono::{NaiveDateTime, Utc};
+use common::{
+    ledger::Commit,
+    prov::{
+        to_json_ld::ToJson, ChronicleTransactionId, CompactionError, DomaintypeId, ExternalIdPart,
+        NamespaceId, ProvModel,
+    },
+};
+use diesel::{
+    prelude::*,
+    r2d2::{ConnectionManager, Pool},
+};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, instrument, warn};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    encryption::{EncryptionError, PayloadEncryption, JOSE_JSON},
+    persistence::schema::webhook_delivery,
+    pseudonym::{NamespacePseudonyms, PseudonymError},
+    ApiDispatch, DatabaseConnection,
+};
+
+/// The most times a delivery is attempted before it is marked failed
+const MAX_ATTEMPTS: i32 = 10;
+
+/// The longest to wait before attempting a delivery again
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// The longest a receiver is given to answer a delivery
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a claimed delivery is left to the worker that claimed it, longer
+/// than an attempt can take, before another worker may claim it
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often a worker looks for due deliveries when no commit wakes it
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The header carrying the hex signature of the body with the chronicle key
+pub const SIGNATURE_HEADER: &str = "Chronicle-Signature";
+
+/// The header carrying the id of the transaction that was committed
+pub const TRANSACTION_HEADER: &str = "Chronicle-Transaction-Id";
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Could not read webhooks: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid webhooks: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Could not compact delta: {0}")]
+    Compaction(#[from] CompactionError),
+
+    #[error("Could not encrypt delivery: {0}")]
+    Encryption(#[from] EncryptionError),
+
+    #[error("Could not pseudonymize delivery: {0}")]
+    Pseudonym(#[from] PseudonymError),
+
+    #[error("Could not sign delivery: {0}")]
+    Signing(#[from] SecretError),
+
+    #[error("Webhook request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Webhook responded {status}: {body}")]
+    Rejected { status: u16, body: String },
+
+    #[error("Database operation failed: {0}")]
+    Db(#[from] diesel::result::Error),
+
+    #[error("Connection pool error: {0}")]
+    DbPool(#[from] r2d2::Error),
+}
+
+/// The kinds of record a webhook can be filtered to
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Term {
+    Agent,
+    Activity,
+    Entity,
+}
+
+/// A url to notify of commits, and the records it is interested in. A commit
+/// is delivered if it touches any record matching every filter given, empty
+/// filters match all records.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Webhook {
+    pub url: Url,
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    #[serde(default)]
+    pub terms: Vec<Term>,
+    #[serde(default)]
+    pub domaintypes: Vec<String>,
+    /// PEM encoded RSA public keys to encrypt deliveries to, relative to the
+    /// webhooks file
+    #[serde(default)]
+    pub encrypt_to: Vec<PathBuf>,
+    #[serde(skip)]
+    encryption: Option<PayloadEncryption>,
+}
+
+impl Webhook {
+    fn matches_record(
+        &self,
+        namespace: &NamespaceId,
+        term: Term,
+        domaintype: Option<&DomaintypeId>,
+    ) -> bool {
+        (self.namespaces.is_empty()
+            || self
+                .namespaces
+                .iter()
+                .any(|name| name == namespace.external_id_part().as_str()))
+            && (self.terms.is_empty() || self.terms.contains(&term))
+            && (self.domaintypes.is_empty()
+                || domaintype.is_some_and(|domaintype| {
+                    self.domaintypes
+                        .iter()
+                        .any(|name| name == domaintype.external_id_part().as_str())
+                }))
+    }
+
+    fn content_type(&self) -> &'static str {
+        if self.encryption.is_some() {
+            JOSE_JSON
+        } else {
+            "application/ld+json"
+        }
+    }
+
+    /// If the commit of `delta` should be delivered to this webhook
+    fn matches(&self, delta: &ProvModel) -> bool {
+        delta.agents.values().any(|agent| {
+            self.matches_record(&agent.namespaceid, Term::Agent, agent.domaintypeid.as_ref())
+        }) || delta.activities.values().any(|activity| {
+            self.matches_record(
+                &activity.namespaceid,
+                Term::Activity,
+                activity.domaintypeid.as_ref(),
+            )
+        }) || delta.entities.values().any(|entity| {
+            self.matches_record(
+                &entity.namespaceid,
+                Term::Entity,
+                entity.domaintypeid.as_ref(),
+            )
+        })
+    }
+}
+
+/// The webhooks to deliver commits to, webhooks are disabled unless configured
+#[derive(Debug, Clone)]
+pub struct WebhookConf {
+    pub webhooks: Vec<Webhook>,
+    pub pseudonyms: Option<NamespacePseudonyms>,
+}
+
+impl WebhookConf {
+    /// Read webhooks from a JSON file holding an array of them, and the keys
+    /// they encrypt to
+    pub fn from_file(path: &Path) -> Result<Self, WebhookError> {
+        let mut webhooks: Vec<Webhook> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        for webhook in webhooks.iter_mut() {
+            if !webhook.encrypt_to.is_empty() {
+                let keys: Vec<_> = webhook.encrypt_to.iter().map(|key| dir.join(key)).collect();
+                webhook.encryption = Some(PayloadEncryption::from_files(&keys)?);
+            }
+        }
+
+        Ok(Self {
+            webhooks,
+            pseudonyms: None,
+        })
+    }
+
+    /// Deliver namespaces under their pseudonyms
+    pub fn with_pseudonyms(mut self, pseudonyms: Option<NamespacePseudonyms>) -> Self {
+        self.pseudonyms = pseudonyms;
+        self
+    }
+
+    /// The deliveries of `commit` to the webhooks it matches, to be queued as
+    /// it is applied. A delivery that cannot be prepared is logged and left
+    /// out, as the commit is applied regardless.
+    pub(crate) async fn deliveries(
+        &self,
+        signing: &ChronicleSigning,
+        commit: &Commit,
+    ) -> Vec<PreparedDelivery> {
+        let mut deliveries = vec![];
+        for webhook in self
+            .webhooks
+            .iter()
+            .filter(|webhook| webhook.matches(&commit.delta))
+        {
+            match payload(signing, self.pseudonyms.as_ref(), webhook, commit).await {
+                Ok((payload, signature)) => deliveries.push(PreparedDelivery {
+                    url: webhook.url.clone(),
+                    payload,
+                    signature,
+                }),
+                Err(e) => {
+                    error!(url = %webhook.url, tx_id = %commit.tx_id, %e, "Could not prepare webhook delivery")
+                }
+            }
+        }
+
+        deliveries
+    }
+}
+
+/// The signed body of a commit to deliver to a webhook
+#[derive(Debug, Clone)]
+pub(crate) struct PreparedDelivery {
+    url: Url,
+    payload: String,
+    signature: String,
+}
+
+/// Queue the deliveries of the commit of `tx_id`, in the transaction that
+/// applies it
+pub(crate) fn queue_deliveries(
+    connection: &mut DatabaseConnection,
+    tx_id: &ChronicleTransactionId,
+    deliveries: &[PreparedDelivery],
+) -> Result<(), diesel::result::Error> {
+    for delivery in deliveries {
+        let now = Utc::now().naive_utc();
+
+        diesel::insert_into(webhook_delivery::table)
+            .values((
+                webhook_delivery::id.eq(Uuid::new_v4().to_string()),
+                webhook_delivery::url.eq(delivery.url.as_str()),
+                webhook_delivery::tx_id.eq(tx_id.to_string()),
+                webhook_delivery::payload.eq(&delivery.payload),
+                webhook_delivery::signature.eq(&delivery.signature),
+                webhook_delivery::status.eq(DeliveryStatus::Pending.as_str()),
+                webhook_delivery::attempts.eq(0),
+                webhook_delivery::created_at.eq(now),
+                webhook_delivery::updated_at.eq(now),
+                webhook_delivery::next_attempt_at.eq(now),
+            ))
+            .execute(connection)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl DeliveryStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Queryable, Debug)]
+struct Delivery {
+    id: String,
+    tx_id: String,
+    payload: String,
+    signature: String,
+    attempts: i32,
+}
+
+/// Records deliveries and their outcome
+#[derive(Clone)]
+struct Deliveries {
+    pool: Pool<ConnectionManager<DatabaseConnection>>,
+}
+
+/// A time `delay` after `now`
+fn after(now: NaiveDateTime, delay: Duration) -> NaiveDateTime {
+    now + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+impl Deliveries {
+    /// Claim the earliest queued delivery to `url` that is due, if there is
+    /// one. It is not due again until the claim times out, so other workers
+    /// leave it alone while it is attempted, and skip it while it is claimed.
+    fn claim(&self, url: &Url) -> Result<Option<Delivery>, WebhookError> {
+        self.pool.get()?.build_transaction().run(|connection| {
+            let now = Utc::now().naive_utc();
+            let due = webhook_delivery::table
+                .filter(webhook_delivery::url.eq(url.as_str()))
+                .filter(webhook_delivery::status.eq(DeliveryStatus::Pending.as_str()))
+                .filter(webhook_delivery::next_attempt_at.le(now))
+                .order((webhook_delivery::created_at, webhook_delivery::id))
+                .select((
+                    webhook_delivery::id,
+                    webhook_delivery::tx_id,
+                    webhook_delivery::payload,
+                    webhook_delivery::signature,
+                    webhook_delivery::attempts,
+                ));
+
+            #[cfg(not(feature = "sqlite"))]
+            let due = due.for_update().skip_locked();
+
+            let delivery: Option<Delivery> = due.first(connection).optional()?;
+            if let Some(delivery) = &delivery {
+                diesel::update(
+                    webhook_delivery::table.filter(webhook_delivery::id.eq(&delivery.id)),
+                )
+                .set(webhook_delivery::next_attempt_at.eq(after(now, CLAIM_TIMEOUT)))
+                .execute(connection)?;
+            }
+
+            Ok(delivery)
+        })
+    }
+
+    fn record_attempt(
+        &self,
+        id: &str,
+        status: DeliveryStatus,
+        attempts: i32,
+        response_status: Option<i32>,
+        error: Option<&str>,
+        next_attempt_at: NaiveDateTime,
+    ) -> Result<(), WebhookError> {
+        diesel::update(webhook_delivery::table.filter(webhook_delivery::id.eq(id)))
+            .set((
+                webhook_delivery::status.eq(status.as_str()),
+                webhook_delivery::attempts.eq(attempts),
+                webhook_delivery::response_status.eq(response_status),
+                webhook_delivery::error.eq(error),
+                webhook_delivery::updated_at.eq(Utc::now().naive_utc()),
+                webhook_delivery::next_attempt_at.eq(next_attempt_at),
+            ))
+            .execute(&mut self.pool.get()?)?;
+
+        Ok(())
+    }
+}
+
+async fn post(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    delivery: &Delivery,
+) -> Result<(), WebhookError> {
+    let response = client
+        .post(webhook.url.clone())
+        .header(reqwest::header::CONTENT_TYPE, webhook.content_type())
+        .header(SIGNATURE_HEADER, &delivery.signature)
+        .header(TRANSACTION_HEADER, &delivery.tx_id)
+        .body(delivery.payload.clone())
+        .send()
+        .await?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(WebhookError::Rejected {
+            status: status.as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        })
+    }
+}
+
+/// Receivers that are unavailable, overloaded or erroring may accept the
+/// delivery later, other rejections are final
+fn is_transient(e: &WebhookError) -> bool {
+    match e {
+        WebhookError::Request(_) => true,
+        WebhookError::Rejected { status, .. } => *status == 429 || *status >= 500,
+        _ => false,
+    }
+}
+
+fn backoff(attempts: i32) -> Duration {
+    Duration::from_secs(1 << attempts.clamp(0, 16)).min(MAX_BACKOFF)
+}
+
+/// Attempt a claimed delivery once, recording the outcome. A delivery the
+/// receiver may accept later is left queued, due again after a delay that
+/// grows with each attempt, until it runs out of attempts.
+#[instrument(skip(client, deliveries, webhook, delivery), fields(url = %webhook.url, id = %delivery.id, tx_id = %delivery.tx_id))]
+async fn attempt(
+    client: &reqwest::Client,
+    deliveries: &Deliveries,
+    webhook: &Webhook,
+    mut delivery: Delivery,
+) -> Result<(), WebhookError> {
+    let result = post(client, webhook, &delivery).await;
+    delivery.attempts += 1;
+    let now = Utc::now().naive_utc();
+
+    match result {
+        Ok(()) => {
+            debug!(attempts = delivery.attempts, "Delivered commit");
+            deliveries.record_attempt(
+                &delivery.id,
+                DeliveryStatus::Delivered,
+                delivery.attempts,
+                None,
+                None,
+                now,
+            )
+        }
+        Err(e) => {
+            let response_status = match &e {
+                WebhookError::Rejected { status, .. } => Some(*status as i32),
+                _ => None,
+            };
+            let status = if is_transient(&e) && delivery.attempts < MAX_ATTEMPTS {
+                DeliveryStatus::Pending
+            } else {
+                DeliveryStatus::Failed
+            };
+            let delay = backoff(delivery.attempts);
+            deliveries.record_attempt(
+                &delivery.id,
+                status,
+                delivery.attempts,
+                response_status,
+                Some(&e.to_string()),
+                after(now, delay),
+            )?;
+
+            if status == DeliveryStatus::Failed {
+                warn!(%e, attempts = delivery.attempts, "Webhook delivery failed");
+            } else {
+                debug!(%e, ?delay, "Webhook unavailable, retrying");
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// The body of a delivery, encrypted if the webhook has recipients, with its
+/// signature
+async fn payload(
+    signing: &ChronicleSigning,
+    pseudonyms: Option<&NamespacePseudonyms>,
+    webhook: &Webhook,
+    commit: &Commit,
+) -> Result<(String, String), WebhookError> {
+    let mut json = commit.delta.to_json().compact().await?;
+    if let Some(pseudonyms) = pseudonyms {
+        pseudonyms.apply(&commit.delta, &mut json)?;
+    }
+    let mut payload = serde_json::to_string(&json)?;
+    if let Some(encryption) = &webhook.encryption {
+        payload = encryption.encrypt(payload.as_bytes(), "application/ld+json")?;
+    }
+    let signature = hex::encode(signing.chronicle_sign(payload.as_bytes()).await?);
+
+    Ok((payload, signature))
+}
+
+/// Attempt the deliveries queued for `webhook` as they fall due, looking for
+/// more as each commit is notified or a retry may have fallen due
+fn run_webhook(
+    api: &ApiDispatch,
+    client: reqwest::Client,
+    deliveries: Deliveries,
+    webhook: Webhook,
+) {
+    let mut commits = api.notify_commit.subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            match deliveries.claim(&webhook.url) {
+                Ok(Some(delivery)) => {
+                    if let Err(e) = attempt(&client, &deliveries, &webhook, delivery).await {
+                        error!(url = %webhook.url, %e, "Could not record webhook delivery");
+                    }
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => error!(url = %webhook.url, %e, "Could not claim webhook delivery"),
+            }
+
+            // Notifications only prompt a look at the queue, so one missed
+            // while lagging is made up for by the next poll
+            tokio::select! {
+                notified = commits.recv() => {
+                    if let Err(RecvError::Closed) = notified {
+                        return;
+                    }
+                }
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+/// Deliver the commits queued for each webhook until the api stops. Each
+/// webhook is delivered to independently, so a slow receiver does not hold up
+/// others. Commits are queued as they are applied, see [WebhookConf::deliveries].
+pub async fn spawn_webhooks(
+    api: &ApiDispatch,
+    pool: Pool<ConnectionManager<DatabaseConnection>>,
+    conf: WebhookConf,
+) -> Result<(), WebhookError> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let deliveries = Deliveries { pool };
+    for webhook in conf.webhooks {
+        info!(url = %webhook.url, "Delivering commits to webhook");
+        run_webhook(api, client.clone(), deliveries.clone(), webhook);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use common::{
+        attributes::Attributes,
+        commands::{ApiCommand, NamespaceCommand},
+        identity::AuthId,
+        prov::{
+            operations::{ChronicleOperation, CreateNamespace, SetAttributes},
+            DomaintypeId, EntityId, NamespaceId, ProvModel,
+        },
+    };
+    use diesel::prelude::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use uuid::Uuid;
+
+    use super::{spawn_webhooks, Term, Webhook, WebhookConf, TRANSACTION_HEADER};
+    use crate::{
+        persistence::schema::webhook_delivery,
+        test::{test_api_with, test_config},
+        ApiConfig,
+    };
+
+    fn webhook(filters: serde_json::Value) -> Webhook {
+        let mut webhook = serde_json::json!({ "url": "http://receiver/hook" });
+        webhook
+            .as_object_mut()
+            .unwrap()
+            .extend(filters.as_object().unwrap().clone());
+        serde_json::from_value(webhook).unwrap()
+    }
+
+    #[test]
+    fn commits_are_matched_by_their_records() {
+        let namespace = NamespaceId::from_external_id("testns", Uuid::nil());
+        let delta = ProvModel::from_tx(&[
+            ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                namespace.clone(),
+                "testns",
+                Uuid::nil(),
+            )),
+            ChronicleOperation::SetAttributes(SetAttributes::Entity {
+                namespace,
+                id: EntityId::from_external_id("report"),
+                attributes: Attributes::type_only(Some(DomaintypeId::from_external_id(
+                    "ReportEntity",
+                ))),
+            }),
+        ])
+        .unwrap();
+
+        assert!(webhook(serde_json::json!({})).matches(&delta));
+        assert!(webhook(serde_json::json!({
+            "namespaces": ["testns"],
+            "terms": ["entity"],
+            "domaintypes": ["ReportEntity"],
+        }))
+        .matches(&delta));
+        assert!(!webhook(serde_json::json!({ "namespaces": ["otherns"] })).matches(&delta));
+        assert!(!webhook(serde_json::json!({ "terms": ["agent", "activity"] })).matches(&delta));
+        assert!(!webhook(serde_json::json!({ "domaintypes": ["InvoiceEntity"] })).matches(&delta));
+        assert_eq!(
+            webhook(serde_json::json!({ "terms": ["agent"] })).terms,
+            vec![Term::Agent]
+        );
+    }
+
+    /// A receiver that is unavailable the first time each transaction is
+    /// delivered to it and accepts it after that, recording the transaction
+    /// of each request it receives
+    async fn flaky_receiver(received: Arc<Mutex<Vec<String>>>) -> url::Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0u8; 4096];
+                let mut head: Option<(usize, usize, String)> = None;
+                loop {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..read]);
+
+                    if head.is_none() {
+                        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            let headers = String::from_utf8_lossy(&request[..end]).to_string();
+                            let header = |name: &str| {
+                                headers.lines().find_map(|line| {
+                                    let (key, value) = line.split_once(':')?;
+                                    key.trim()
+                                        .eq_ignore_ascii_case(name)
+                                        .then(|| value.trim().to_owned())
+                                })
+                            };
+                            let length = header("content-length")
+                                .and_then(|length| length.parse().ok())
+                                .unwrap_or(0);
+                            head = Some((end + 4, length, header(TRANSACTION_HEADER).unwrap()));
+                        }
+                    }
+                    if let Some((start, length, _)) = &head {
+                        if request.len() >= start + length {
+                            break;
+                        }
+                    }
+                }
+
+                let tx_id = head.unwrap().2;
+                let status = {
+                    let mut received = received.lock().unwrap();
+                    let status = if received.contains(&tx_id) {
+                        "200 OK"
+                    } else {
+                        "503 Service Unavailable"
+                    };
+                    received.push(tx_id);
+                    status
+                };
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_queued_with_the_commit_and_retried_once_by_any_replica() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let url = flaky_receiver(received.clone()).await;
+        let conf = WebhookConf {
+            webhooks: vec![serde_json::from_value(serde_json::json!({ "url": url })).unwrap()],
+            pseudonyms: None,
+        };
+
+        let mut api = test_api_with(
+            ApiConfig {
+                webhooks: Some(conf.clone()),
+                ..test_config()
+            },
+            |_| {},
+        )
+        .await;
+
+        let (_, tx_id) = api
+            .dispatch(
+                ApiCommand::NameSpace(NamespaceCommand::Create {
+                    external_id: "testns".into(),
+                }),
+                AuthId::chronicle(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let tx_id = tx_id.to_string();
+
+        let pool = api.pool();
+        let delivery = || -> (String, i32) {
+            webhook_delivery::table
+                .filter(webhook_delivery::tx_id.eq(&tx_id))
+                .select((webhook_delivery::status, webhook_delivery::attempts))
+                .first(&mut pool.get().unwrap())
+                .unwrap()
+        };
+
+        // Queued as the commit was applied, before anything delivers it
+        assert_eq!(delivery(), ("pending".to_owned(), 0));
+
+        // Two replicas deliver from the same queue
+        spawn_webhooks(&api.api, api.pool(), conf.clone())
+            .await
+            .unwrap();
+        spawn_webhooks(&api.api, api.pool(), conf).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while delivery().0 != "delivered" {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(delivery(), ("delivered".to_owned(), 2));
+        assert_eq!(
+            received
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|received| **received == tx_id)
+                .count(),
+            2
+        );
+    }
+}
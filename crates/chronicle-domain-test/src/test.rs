@@ -66,11 +66,16 @@ mod test {
     use async_stl_client::prost::Message;
     use chronicle::{
         api::{
-            chronicle_graphql::{OpaCheck, Store, Subscription},
+            chronicle_graphql::{
+                export::ExportConf,
+                federation::{FederatedKind, FederationConf},
+                loader::RelationLoader,
+                OpaCheck, Store, Subscription,
+            },
             inmem::EmbeddedChronicleTp,
-            Api, UuidGen,
+            Api, ApiConfig, UuidGen,
         },
-        async_graphql::{Request, Response, Schema},
+        async_graphql::{dataloader::DataLoader, Request, Response, Schema},
         chrono::{DateTime, NaiveDate, Utc},
         common::{
             database::TemporaryDatabase,
@@ -123,6 +128,13 @@ mod test {
 
     async fn test_schema_with_opa<'a>(
         opa_executor: ExecutorContext,
+    ) -> (Schema<Query, Mutation, Subscription>, TemporaryDatabase<'a>) {
+        test_schema_with_config(opa_executor, ApiConfig::default()).await
+    }
+
+    async fn test_schema_with_config<'a>(
+        opa_executor: ExecutorContext,
+        config: ApiConfig,
     ) -> (Schema<Query, Mutation, Subscription>, TemporaryDatabase<'a>) {
         chronicle_telemetry::telemetry(None, chronicle_telemetry::ConsoleLogging::Pretty);
 
@@ -195,23 +207,19 @@ mod test {
 
         let database = TemporaryDatabase::default();
         let pool = database.connection_pool().unwrap();
-        let liveness_check_interval = None;
-
-        let dispatch = Api::new(
-            pool.clone(),
-            ledger,
-            SameUuid,
-            signing,
-            vec![],
-            None,
-            liveness_check_interval,
-        )
-        .await
-        .unwrap();
+
+        let dispatch = Api::new(pool.clone(), ledger, SameUuid, signing, config)
+            .await
+            .unwrap();
 
         let schema = Schema::build(Query, Mutation, Subscription)
             .extension(OpaCheck { claim_parser: None })
-            .data(Store::new(pool))
+            .data(Store::new(pool.clone()))
+            .data(DataLoader::new(RelationLoader::new(pool), tokio::spawn))
+            .data(ExportConf::new(
+                std::env::temp_dir().join("chronicle-test-exports"),
+            ))
+            .data(FederationConf::new([FederatedKind::Agent]))
             .data(dispatch)
             .data(AuthId::chronicle())
             .data(opa_executor)
@@ -220,6 +228,47 @@ mod test {
         (schema, database)
     }
 
+    #[tokio::test]
+    async fn queries_are_subject_to_namespace_access() {
+        let loader = CliPolicyLoader::from_embedded_policy(
+            "allow_transactions",
+            "allow_transactions.allowed_users",
+        )
+        .unwrap();
+        let opa_executor = ExecutorContext::from_loader(&loader).unwrap();
+        let loader = CliPolicyLoader::from_embedded_policy(
+            "allow_transactions",
+            "allow_transactions.deny_all",
+        )
+        .unwrap();
+        let namespace_policy = ExecutorContext::from_loader(&loader).unwrap();
+
+        let (schema, _database) = test_schema_with_config(
+            opa_executor,
+            ApiConfig {
+                namespace_policy: Some(namespace_policy),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let response = schema
+            .execute(Request::new(
+                r#"
+          query {
+              agentById(id: { id: "chronicle:agent:testagent" }) {
+                  ... on ProvAgent {
+                      id
+                  }
+              }
+          }
+      "#,
+            ))
+            .await;
+
+        insta::assert_snapshot!(response.errors[0].message, @"API: Identity Chronicle denied read access to namespace default");
+    }
+
     #[tokio::test]
     async fn accept_long_form_including_original_name_iris() {
         let (schema, _database) = test_schema().await;
@@ -2039,6 +2088,310 @@ mod test {
         "###);
     }
 
+    #[tokio::test]
+    async fn derivations_of_several_entities() {
+        let (schema, _database) = test_schema().await;
+
+        insta::assert_toml_snapshot!(schema
+          .execute(Request::new(
+              r#"
+          mutation {
+              wasRevisionOf(generatedEntity: {id: "chronicle:entity:testentity1" },
+                          usedEntity: {id: "chronicle:entity:testentity2" }) {
+                  context
+              }
+          }
+      "#,
+          ))
+          .await, @r###"
+        [data.wasRevisionOf]
+        context = 'chronicle:entity:testentity1'
+        "###);
+
+        insta::assert_toml_snapshot!(schema
+          .execute(Request::new(
+              r#"
+          mutation {
+              wasQuotedFrom(generatedEntity: {id: "chronicle:entity:testentity3" },
+                          usedEntity: {id: "chronicle:entity:testentity2" }) {
+                  context
+              }
+          }
+      "#,
+          ))
+          .await, @r###"
+        [data.wasQuotedFrom]
+        context = 'chronicle:entity:testentity3'
+        "###);
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        insta::assert_toml_snapshot!(schema
+          .execute(Request::new(
+              r#"
+          query {
+              revised: entityById(id: {id: "chronicle:entity:testentity1" }) {
+                  ... on ProvEntity {
+                      id
+                      wasDerivedFrom {
+                          ... on ProvEntity {
+                              id
+                          }
+                      }
+                      wasRevisionOf {
+                          ... on ProvEntity {
+                              id
+                          }
+                      }
+                  }
+              }
+              quoted: entityById(id: {id: "chronicle:entity:testentity3" }) {
+                  ... on ProvEntity {
+                      id
+                      wasDerivedFrom {
+                          ... on ProvEntity {
+                              id
+                          }
+                      }
+                      wasQuotedFrom {
+                          ... on ProvEntity {
+                              id
+                          }
+                      }
+                  }
+              }
+          }
+      "#,
+          ))
+          .await, @r###"
+        [data.revised]
+        id = 'chronicle:entity:testentity1'
+
+        [[data.revised.wasDerivedFrom]]
+        id = 'chronicle:entity:testentity2'
+
+        [[data.revised.wasRevisionOf]]
+        id = 'chronicle:entity:testentity2'
+
+        [data.quoted]
+        id = 'chronicle:entity:testentity3'
+
+        [[data.quoted.wasDerivedFrom]]
+        id = 'chronicle:entity:testentity2'
+
+        [[data.quoted.wasQuotedFrom]]
+        id = 'chronicle:entity:testentity2'
+        "###);
+    }
+
+    fn lineage_ids(response: Response) -> Vec<String> {
+        let data = response.data.into_json().unwrap();
+        let mut ids = data["lineage"]["@graph"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|node| node["@id"].as_str())
+            .filter(|id| id.contains(":entity:"))
+            .map(|id| id.to_owned())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn lineage_is_limited_by_depth() {
+        let (schema, _database) = test_schema().await;
+
+        for (generated, used) in [
+            ("testentity1", "testentity2"),
+            ("testentity2", "testentity3"),
+        ] {
+            let response = schema
+                .execute(Request::new(format!(
+                    r#"
+          mutation {{
+              wasRevisionOf(generatedEntity: {{id: "chronicle:entity:{generated}" }},
+                          usedEntity: {{id: "chronicle:entity:{used}" }}) {{
+                  context
+              }}
+          }}
+      "#
+                )))
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+        }
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let lineage = |depth: u32| {
+            Request::new(format!(
+                r#"
+          query {{
+              lineage(id: {{id: "chronicle:entity:testentity1" }}, depth: {depth})
+          }}
+      "#
+            ))
+        };
+
+        assert_eq!(
+            lineage_ids(schema.execute(lineage(1)).await),
+            vec![
+                "chronicle:entity:testentity1",
+                "chronicle:entity:testentity2"
+            ]
+        );
+
+        assert_eq!(
+            lineage_ids(schema.execute(lineage(2)).await),
+            vec![
+                "chronicle:entity:testentity1",
+                "chronicle:entity:testentity2",
+                "chronicle:entity:testentity3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn federated_entities_resolve_by_key() {
+        let (schema, _database) = test_schema().await;
+
+        let response = schema
+            .execute(Request::new(
+                r#"
+          mutation {
+              defineContractorAgent(
+                externalId: "testagent"
+                attributes: { locationAttribute: "location" }
+              ) {
+                context
+              }
+          }
+      "#,
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let response = schema
+            .execute(Request::new(
+                r#"
+          query {
+              _entities(representations: [
+                  { __typename: "ContractorAgent", externalId: "testagent", namespace: { externalId: "default" } }
+              ]) {
+                  ... on ContractorAgent {
+                      id
+                      locationAttribute
+                  }
+              }
+          }
+      "#,
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["_entities"][0]["id"], "chronicle:agent:testagent");
+        assert_eq!(data["_entities"][0]["locationAttribute"], "location");
+
+        // Only agents are federated by the test schema
+        let response = schema
+            .execute(Request::new(
+                r#"
+          query {
+              _entities(representations: [
+                  { __typename: "ProvEntity", externalId: "testentity", namespace: { externalId: "default" } }
+              ]) {
+                  __typename
+              }
+          }
+      "#,
+            ))
+            .await;
+        assert!(!response.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn export_runs_in_the_background() {
+        let (schema, _database) = test_schema().await;
+
+        let response = schema
+            .execute(Request::new(
+                r#"
+          mutation {
+              wasRevisionOf(generatedEntity: {id: "chronicle:entity:testentity1" },
+                          usedEntity: {id: "chronicle:entity:testentity2" }) {
+                  context
+              }
+          }
+      "#,
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let response = schema
+            .execute(Request::new(
+                r#"
+          mutation {
+              startExport(format: JSON_LD) {
+                  id
+              }
+          }
+      "#,
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let data = response.data.into_json().unwrap();
+        let id = data["startExport"]["id"].as_str().unwrap().to_owned();
+
+        let mut job = serde_json::Value::Null;
+        for _ in 0..50 {
+            let response = schema
+                .execute(Request::new(format!(
+                    r#"
+          query {{
+              exportJob(id: "{id}") {{
+                  status
+                  artifact
+              }}
+          }}
+      "#
+                )))
+                .await;
+            job = response.data.into_json().unwrap()["exportJob"].clone();
+            if job["status"] == "COMPLETE" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        assert_eq!(job["status"], "COMPLETE");
+
+        let artifact = job["artifact"].as_str().unwrap();
+        let exported = std::fs::read_to_string(artifact).unwrap();
+        std::fs::remove_file(artifact).unwrap();
+
+        assert!(exported.contains("chronicle:entity:testentity1"));
+        assert!(exported.contains("chronicle:entity:testentity2"));
+
+        let response = schema
+            .execute(Request::new(format!(
+                r#"
+          mutation {{
+              cancelExport(id: "{id}") {{
+                  status
+              }}
+          }}
+      "#
+            )))
+            .await;
+        assert!(!response.errors.is_empty());
+    }
+
     #[tokio::test]
     async fn agent_can_be_created() {
         let (schema, _database) = test_schema().await;
@@ -2880,6 +3233,116 @@ mod test {
         "###);
     }
 
+    #[tokio::test]
+    async fn unnamed_responsible_agents_are_taken_from_the_agent_claim() {
+        use chronicle::common::identity::{AgentClaim, JwtClaims};
+
+        let (schema, _database) = test_schema().await;
+
+        let identity = AuthId::from_jwt_claims(
+            &JwtClaims(
+                serde_json::json!({"sub": "jdoe-subject", "preferred_username": "jdoe"})
+                    .as_object()
+                    .unwrap()
+                    .to_owned(),
+            ),
+            &["sub".to_owned()].into(),
+        )
+        .unwrap();
+        let claimed = |query: &str| {
+            Request::new(query)
+                .data(identity.clone())
+                .data(AgentClaim::new("preferred_username"))
+        };
+
+        let response = schema
+            .execute(claimed(
+                r#"
+                mutation {
+                    defineContractorAgent(externalId: "jdoe", attributes: { locationAttribute: "Here" }) {
+                        context
+                    }
+                    defineCertificateEntity(externalId: "certificate", attributes: { certIdAttribute: "1" }) {
+                        context
+                    }
+                }
+            "#,
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        insta::assert_json_snapshot!(schema
+            .execute(claimed(
+                r#"
+                mutation {
+                    wasAttributedTo(role: CERTIFIER, entity: { id: "chronicle:entity:certificate" }) {
+                        context
+                    }
+                }
+            "#,
+            ))
+            .await, @r###"
+        {
+          "data": {
+            "wasAttributedTo": {
+              "context": "chronicle:agent:jdoe"
+            }
+          }
+        }
+        "###);
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        insta::assert_toml_snapshot!(schema
+            .execute(Request::new(
+                r#"
+                query {
+                    entityById(id: { id: "chronicle:entity:certificate" }) {
+                        ... on CertificateEntity {
+                            wasAttributedTo {
+                                responsible {
+                                    role
+                                    agent {
+                                        ... on ContractorAgent {
+                                            externalId
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            "#,
+            ))
+            .await, @r###"
+        [[data.entityById.wasAttributedTo]]
+        [data.entityById.wasAttributedTo.responsible]
+        role = 'CERTIFIER'
+
+        [data.entityById.wasAttributedTo.responsible.agent]
+        externalId = 'jdoe'
+        "###);
+
+        // Without the claim, an agent must be named
+        let response = schema
+            .execute(Request::new(
+                r#"
+                mutation {
+                    wasAttributedTo(role: CERTIFIER, entity: { id: "chronicle:entity:certificate" }) {
+                        context
+                    }
+                }
+            "#,
+            ))
+            .await;
+        assert_eq!(
+            response.errors[0].message,
+            "No responsible agent was given, and the identity claims none"
+        );
+    }
+
     #[tokio::test]
     async fn generated() {
         let (schema, _database) = test_schema().await;
@@ -4903,6 +5366,88 @@ mod test {
         "###);
     }
 
+    #[tokio::test]
+    async fn resumed_subscriptions_replay_missed_commits_then_go_live() {
+        use chronicle::async_graphql::{
+            futures_util::{Stream, StreamExt},
+            Variables,
+        };
+
+        async fn define(schema: &Schema<Query, Mutation, Subscription>, agent: &str) -> String {
+            let res = schema
+                .execute(Request::new(format!(
+                    r#"
+                mutation {{
+                  defineContractorAgent(
+                    externalId: "{agent}"
+                    attributes: {{ locationAttribute: "location" }}
+                  ) {{
+                    txId
+                  }}
+                }}
+                "#
+                )))
+                .await;
+            assert!(res.errors.is_empty(), "{:?}", res.errors);
+            res.data.into_json().unwrap()["defineContractorAgent"]["txId"]
+                .as_str()
+                .unwrap()
+                .to_owned()
+        }
+
+        // The transaction id and delta of the next commit notification
+        async fn next_commit(
+            stream: &mut (impl Stream<Item = Response> + Unpin),
+        ) -> (String, String) {
+            loop {
+                let res = stream.next().await.unwrap().data.into_json().unwrap();
+                let notification = &res["commitNotifications"];
+                if notification["stage"] == "COMMIT" {
+                    return (
+                        notification["txId"].as_str().unwrap().to_owned(),
+                        notification["delta"].to_string(),
+                    );
+                }
+            }
+        }
+
+        let (schema, _database) = test_schema().await;
+        let subscription = r#"
+          subscription($after: String) {
+            commitNotifications(after: $after) {
+              stage
+              txId
+              delta
+            }
+          }
+          "#;
+
+        let mut live = schema.execute_stream(Request::new(subscription));
+        let mut committed = vec![];
+        for agent in ["first", "second", "third"] {
+            let tx_id = define(&schema, agent).await;
+            assert_eq!(next_commit(&mut live).await.0, tx_id);
+            committed.push(tx_id);
+        }
+
+        // A client that saw only the first commit resumes from it
+        let mut resumed = schema.execute_stream(Request::new(subscription).variables(
+            Variables::from_json(serde_json::json!({ "after": committed[0] })),
+        ));
+
+        let (tx_id, delta) = next_commit(&mut resumed).await;
+        assert_eq!(tx_id, committed[1]);
+        assert!(delta.contains("chronicle:agent:second"));
+        let (tx_id, delta) = next_commit(&mut resumed).await;
+        assert_eq!(tx_id, committed[2]);
+        assert!(delta.contains("chronicle:agent:third"));
+
+        // Then commits are delivered as they happen, without replaying again
+        let tx_id = define(&schema, "fourth").await;
+        let (live_tx_id, delta) = next_commit(&mut resumed).await;
+        assert_eq!(live_tx_id, tx_id);
+        assert!(delta.contains("chronicle:agent:fourth"));
+    }
     async fn subscription_response(
         schema: &Schema<Query, Mutation, Subscription>,
         subscription: &str,
@@ -12,7 +12,7 @@ pub mod settings;
 pub use async_stl_client;
 use protocol::ChronicleOperationEvent;
 
-static PROTOCOL_VERSION: &str = "2";
+pub static PROTOCOL_VERSION: &str = "2";
 const SUBMISSION_BODY_VERSION: u16 = 1;
 
 pub type ChronicleLedger = SawtoothLedger<
@@ -1,10 +1,11 @@
 use std::sync::Arc;
 
 use chronicle_signing::{BatcherKnownKeyNamesSigner, ChronicleSigning, SecretError};
-use common::prov::{to_json_ld::ToJson, ChronicleTransaction};
+use common::prov::{operations::ChronicleOperation, to_json_ld::ToJson, ChronicleTransaction};
 use k256::ecdsa::VerifyingKey;
 use opa_tp_protocol::state::{policy_address, policy_meta_address};
 use serde_json::json;
+use tokio::sync::OnceCell;
 
 use crate::{
     address::SawtoothAddress,
@@ -26,6 +27,58 @@ pub struct ChronicleSubmitTransaction {
     pub tx: ChronicleTransaction,
     pub signer: ChronicleSigning,
     pub policy_name: Option<String>,
+    /// The operations as compact JSON-LD, compacted once when first submitted
+    payload: Arc<OnceCell<String>>,
+}
+
+/// The body of a submission, the operations as compact JSON-LD
+pub async fn operations_payload(ops: &[ChronicleOperation]) -> Result<String, ProtocolError> {
+    let mut compact_ops = Vec::with_capacity(ops.len());
+    for op in ops {
+        let op_json = op.to_json();
+        let compact_json = op_json.compact_stable_order().await?;
+        compact_ops.push(compact_json);
+    }
+
+    Ok(serde_json::to_string(
+        &json!({"version": SUBMISSION_BODY_VERSION, "ops": compact_ops}),
+    )?)
+}
+
+/// The ledger state addresses that applying `ops` reads and writes
+fn operation_addresses(ops: &[ChronicleOperation]) -> Vec<String> {
+    ops.iter()
+        .flat_map(|op| op.dependencies())
+        .map(|dep| SawtoothAddress::from(&dep).to_string())
+        .collect::<std::collections::HashSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// What submitting a batch of operations in one transaction would cost. The
+/// ledger charges no fees, so this is the size of the transaction and the
+/// state the transaction processor must read and write to apply it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmissionEstimate {
+    pub operations: usize,
+    /// Bytes of operations in the transaction payload, the signed identity
+    /// submitting them is not included
+    pub payload_bytes: usize,
+    /// State addresses the operations depend on, not including those of
+    /// settings and policy that every transaction reads
+    pub addresses: usize,
+}
+
+/// Estimate the cost of submitting `ops` in one transaction, without signing
+/// or submitting it
+pub async fn estimate_submission(
+    ops: &[ChronicleOperation],
+) -> Result<SubmissionEstimate, ProtocolError> {
+    Ok(SubmissionEstimate {
+        operations: ops.len(),
+        payload_bytes: operations_payload(ops).await?.len(),
+        addresses: operation_addresses(ops).len(),
+    })
 }
 
 #[async_trait::async_trait]
@@ -41,19 +94,16 @@ impl TransactionPayload for ChronicleSubmitTransaction {
             ..Default::default()
         };
 
-        let mut ops = Vec::with_capacity(self.tx.tx.len());
-        for op in &self.tx.tx {
-            let op_json = op.to_json();
-            let compact_json = op_json.compact_stable_order().await?;
-            ops.push(compact_json);
-        }
-
-        let ops_json =
-            serde_json::to_string(&json!({"version": SUBMISSION_BODY_VERSION, "ops": ops}))?;
+        let ops_json = self
+            .payload
+            .get_or_try_init(|| operations_payload(&self.tx.tx))
+            .await?;
         let identity_json = serde_json::to_string(&self.tx.identity)?;
         tracing::debug!(ops_json = %ops_json, identity_json = %identity_json);
 
-        submission.body_variant = Some(BodyVariant::Body(BodyMessageV1 { payload: ops_json }));
+        submission.body_variant = Some(BodyVariant::Body(BodyMessageV1 {
+            payload: ops_json.clone(),
+        }));
         submission.identity_variant = Some(IdentityVariant::Identity(IdentityMessageV1 {
             payload: identity_json,
         }));
@@ -71,8 +121,14 @@ impl ChronicleSubmitTransaction {
             tx,
             signer,
             policy_name,
+            payload: Arc::default(),
         }
     }
+
+    /// The body the transaction was submitted with, once it has been
+    pub fn payload(&self) -> Option<&str> {
+        self.payload.get().map(String::as_str)
+    }
 }
 
 #[async_trait::async_trait]
@@ -88,14 +144,7 @@ impl LedgerTransaction for ChronicleSubmitTransaction {
     }
 
     fn addresses(&self) -> Vec<String> {
-        self.tx
-            .tx
-            .iter()
-            .flat_map(|op| op.dependencies())
-            .map(|dep| SawtoothAddress::from(&dep).to_string())
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .collect()
+        operation_addresses(&self.tx.tx)
     }
 
     async fn as_sawtooth_tx(
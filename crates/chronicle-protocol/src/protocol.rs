@@ -16,10 +16,19 @@ use tracing::span;
 
 use thiserror::Error;
 
+use crate::messages::operations_payload;
+
 use self::messages::event::OptionContradiction;
 
+/// The outcome of a transaction, the identity that submitted it, and its
+/// operations, which are empty for events from transaction processors that do
+/// not record them
 #[derive(Debug)]
-pub struct ChronicleOperationEvent(pub Result<ProvModel, Contradiction>, pub SignedIdentity);
+pub struct ChronicleOperationEvent(
+    pub Result<ProvModel, Contradiction>,
+    pub SignedIdentity,
+    pub Vec<ChronicleOperation>,
+);
 
 impl From<ChronicleOperationEvent> for Result<ProvModel, Contradiction> {
     fn from(val: ChronicleOperationEvent) -> Self {
@@ -70,7 +79,19 @@ impl LedgerEvent for ChronicleOperationEvent {
                 })?
             }
         };
-        Ok((Self(model, identity), Span::Span(span_id.into_u64())))
+
+        let operations = if event.operations.is_empty() {
+            vec![]
+        } else {
+            chronicle_operations_from_submission_v2(event.operations)
+                .await
+                .map_err(|e| SawtoothCommunicationError::LedgerEventParse { source: e.into() })?
+        };
+
+        Ok((
+            Self(model, identity, operations),
+            Span::Span(span_id.into_u64()),
+        ))
     }
 }
 
@@ -116,6 +137,7 @@ pub mod messages {
 pub async fn chronicle_committed(
     span: u64,
     delta: ProvModel,
+    operations: &[ChronicleOperation],
     identity: &SignedIdentity,
 ) -> Result<messages::Event, ProtocolError> {
     Ok(messages::Event {
@@ -123,6 +145,7 @@ pub async fn chronicle_committed(
         delta: serde_json::to_string(&delta.to_json().compact_stable_order().await?)?,
         span_id: span,
         identity: serde_json::to_string(identity)?,
+        operations: operations_payload(operations).await?,
         ..Default::default()
     })
 }
@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use aws_sdk_kms::{
+    primitives::Blob,
+    types::{MessageType, SigningAlgorithmSpec},
+    Client,
+};
+use k256::{ecdsa::VerifyingKey, pkcs8::DecodePublicKey};
+use tracing::error;
+
+use crate::{remote_signer::KmsClient, SecretError};
+
+/// An `ECC_SECG_P256K1` key held in AWS KMS. Credentials are resolved from
+/// the environment in the same way as the AWS CLI.
+#[derive(Debug, Clone)]
+pub struct AwsKmsSigner {
+    client: Client,
+    key_id: String,
+}
+
+impl AwsKmsSigner {
+    pub async fn new(key_id: &str, region: Option<&str>) -> Self {
+        let mut config = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = region {
+            config = config.region(aws_config::Region::new(region.to_owned()));
+        }
+
+        Self {
+            client: Client::new(&config.load().await),
+            key_id: key_id.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl KmsClient for AwsKmsSigner {
+    fn name(&self) -> &str {
+        "AwsKms"
+    }
+
+    async fn sign_digest(&self, digest: &[u8]) -> Result<Vec<u8>, SecretError> {
+        let signed = self
+            .client
+            .sign()
+            .key_id(&self.key_id)
+            .message(Blob::new(digest))
+            .message_type(MessageType::Digest)
+            .signing_algorithm(SigningAlgorithmSpec::EcdsaSha256)
+            .send()
+            .await
+            .map_err(|e| {
+                error!(key_id = %self.key_id, ?e, "AWS KMS sign");
+                SecretError::Kms(e.to_string())
+            })?;
+
+        signed
+            .signature()
+            .map(|signature| signature.as_ref().to_vec())
+            .ok_or_else(|| SecretError::Kms("AWS KMS returned no signature".to_owned()))
+    }
+
+    async fn public_key(&self) -> Result<VerifyingKey, SecretError> {
+        let public_key = self
+            .client
+            .get_public_key()
+            .key_id(&self.key_id)
+            .send()
+            .await
+            .map_err(|e| {
+                error!(key_id = %self.key_id, ?e, "AWS KMS get public key");
+                SecretError::Kms(e.to_string())
+            })?;
+
+        // Returned as a DER encoded SubjectPublicKeyInfo
+        public_key
+            .public_key()
+            .ok_or(SecretError::NoPublicKeyFound)
+            .and_then(|der| {
+                VerifyingKey::from_public_key_der(der.as_ref())
+                    .map_err(|_| SecretError::InvalidPublicKey)
+            })
+    }
+}
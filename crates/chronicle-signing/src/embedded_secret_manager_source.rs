@@ -10,8 +10,9 @@ use std::{collections::HashMap, sync::Arc};
 use tokio::sync::Mutex;
 use tracing::debug;
 
-use crate::SecretError;
+use crate::{KeyWriter, SecretError};
 
+#[derive(Clone)]
 pub struct EmbeddedSecretManagerSource {
     secrets: Arc<Mutex<HashMap<SecretVaultRef, Vec<u8>>>>,
     deterministic: bool,
@@ -76,3 +77,46 @@ impl SecretsSource for EmbeddedSecretManagerSource {
         Ok(result_map)
     }
 }
+
+#[async_trait]
+impl KeyWriter for EmbeddedSecretManagerSource {
+    async fn replace_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+        secret: &[u8],
+        retained_ref: &SecretVaultRef,
+        expired: &[u8],
+    ) -> Result<(), SecretError> {
+        let mut secrets = self.secrets.lock().await;
+        secrets.insert(retained_ref.clone(), expired.to_vec());
+        secrets.insert(secret_ref.clone(), secret.to_vec());
+
+        Ok(())
+    }
+
+    async fn write_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+        secret: &[u8],
+    ) -> Result<(), SecretError> {
+        self.secrets
+            .lock()
+            .await
+            .insert(secret_ref.clone(), secret.to_vec());
+
+        Ok(())
+    }
+
+    async fn read_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+    ) -> Result<Option<Vec<u8>>, SecretError> {
+        Ok(self.secrets.lock().await.get(secret_ref).cloned())
+    }
+
+    async fn remove_secret(&self, secret_ref: &SecretVaultRef) -> Result<(), SecretError> {
+        self.secrets.lock().await.remove(secret_ref);
+
+        Ok(())
+    }
+}
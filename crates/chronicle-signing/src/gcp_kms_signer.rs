@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use k256::{ecdsa::VerifyingKey, pkcs8::DecodePublicKey};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use serde_json::json;
+use tracing::error;
+use url::Url;
+
+use crate::{remote_signer::KmsClient, SecretError};
+
+static CLOUD_KMS_API: &str = "https://cloudkms.googleapis.com/v1/";
+
+/// An `EC_SIGN_SECP256K1_SHA256` key version held in Google Cloud KMS,
+/// addressed by its full resource name, `projects/*/locations/*/keyRings/*/cryptoKeys/*/cryptoKeyVersions/*`
+#[derive(Debug, Clone)]
+pub struct GcpKmsSigner {
+    client: reqwest::Client,
+    key_version: String,
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct AsymmetricSignResponse {
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct PublicKeyResponse {
+    pem: String,
+}
+
+impl GcpKmsSigner {
+    pub fn new(key_version: &str, access_token: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            key_version: key_version.to_owned(),
+            access_token: access_token.to_owned(),
+        }
+    }
+
+    fn url(&self, method: &str) -> Result<Url, SecretError> {
+        Url::parse(CLOUD_KMS_API)
+            .and_then(|api| api.join(&format!("{}{method}", self.key_version)))
+            .map_err(|e| SecretError::Kms(e.to_string()))
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, SecretError> {
+        request
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                error!(key_version = %self.key_version, ?e, "Google Cloud KMS request");
+                SecretError::Kms(e.to_string())
+            })?
+            .json()
+            .await
+            .map_err(|e| SecretError::Kms(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl KmsClient for GcpKmsSigner {
+    fn name(&self) -> &str {
+        "GcpKms"
+    }
+
+    async fn sign_digest(&self, digest: &[u8]) -> Result<Vec<u8>, SecretError> {
+        let signed: AsymmetricSignResponse = self
+            .send(
+                self.client
+                    .post(self.url(":asymmetricSign")?)
+                    .json(&json!({ "digest": { "sha256": STANDARD.encode(digest) } })),
+            )
+            .await?;
+
+        STANDARD
+            .decode(signed.signature)
+            .map_err(|e| SecretError::Kms(e.to_string()))
+    }
+
+    async fn public_key(&self) -> Result<VerifyingKey, SecretError> {
+        let public_key: PublicKeyResponse =
+            self.send(self.client.get(self.url("/publicKey")?)).await?;
+
+        VerifyingKey::from_public_key_pem(&public_key.pem)
+            .map_err(|_| SecretError::InvalidPublicKey)
+    }
+}
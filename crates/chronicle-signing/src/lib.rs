@@ -3,8 +3,10 @@ use k256::{
         signature::{Signer, Verifier},
         Signature, SigningKey, VerifyingKey,
     },
-    pkcs8::DecodePrivateKey,
+    pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding},
+    SecretKey,
 };
+use rand::{rngs::StdRng, SeedableRng};
 use secret_vault::{
     errors::SecretVaultError, FilesSource, FilesSourceOptions, MultipleSecretsSources, SecretName,
     SecretNamespace, SecretVaultBuilder, SecretVaultRef, SecretVaultView,
@@ -12,13 +14,27 @@ use secret_vault::{
 use std::{
     path::{Path, PathBuf},
     sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
-use tracing::instrument;
+use tracing::{info, instrument};
 use url::Url;
+#[cfg(feature = "aws-kms")]
+mod aws_kms_signer;
 mod embedded_secret_manager_source;
+#[cfg(feature = "gcp-kms")]
+mod gcp_kms_signer;
+mod remote_signer;
 mod vault_secret_manager_source;
 
+#[cfg(feature = "aws-kms")]
+pub use aws_kms_signer::AwsKmsSigner;
+use embedded_secret_manager_source::EmbeddedSecretManagerSource;
+#[cfg(feature = "gcp-kms")]
+pub use gcp_kms_signer::GcpKmsSigner;
+pub use remote_signer::{KmsClient, RemoteSigner};
+use vault_secret_manager_source::VaultSecretManagerSource;
+
 pub static CHRONICLE_NAMESPACE: &str = "chronicle";
 pub static BATCHER_NAMESPACE: &str = "batcher";
 pub static OPA_NAMESPACE: &str = "opa";
@@ -37,11 +53,31 @@ pub enum SecretError {
     #[error("No private key found")]
     NoPrivateKeyFound,
 
+    #[error("Key rotation failed: {0}")]
+    Rotation(String),
+
+    #[error("Keys in namespace {0} are held remotely and cannot be exported")]
+    KeyNotExportable(String),
+
+    #[error("Key management service: {0}")]
+    Kms(String),
+
+    /// The secret store could not be reached, the operation can be retried
+    /// once it recovers
+    #[error("Secret store temporarily unavailable: {0}")]
+    TemporarilyUnavailable(String),
+
     #[error("Vault {source}")]
     SecretVault {
         #[from]
         source: SecretVaultError,
     },
+
+    #[error("IO error {source}")]
+    Io {
+        #[from]
+        source: std::io::Error,
+    },
 }
 
 pub enum ChronicleSecretsOptions {
@@ -53,6 +89,8 @@ pub enum ChronicleSecretsOptions {
     Test,
     //Filesystem based keys
     Filesystem(PathBuf),
+    // Delegate signing to a key management service, the key never leaves it
+    Kms(Arc<dyn KmsClient>),
 }
 
 impl ChronicleSecretsOptions {
@@ -85,11 +123,264 @@ impl ChronicleSecretsOptions {
     pub fn test_keys() -> ChronicleSecretsOptions {
         ChronicleSecretsOptions::Test
     }
+
+    // Sign with a key held by a key management service
+    pub fn signed_by_kms(client: impl KmsClient + 'static) -> ChronicleSecretsOptions {
+        ChronicleSecretsOptions::Kms(Arc::new(client))
+    }
+}
+
+/// A secret source that can persist a replacement for one of its secrets
+#[async_trait::async_trait]
+pub(crate) trait KeyWriter {
+    /// Store `secret` under `secret_ref`, keeping the key it replaces as
+    /// `expired` under `retained_ref`
+    async fn replace_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+        secret: &[u8],
+        retained_ref: &SecretVaultRef,
+        expired: &[u8],
+    ) -> Result<(), SecretError>;
+
+    /// Store `secret` under `secret_ref`
+    async fn write_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+        secret: &[u8],
+    ) -> Result<(), SecretError>;
+
+    /// The secret stored under `secret_ref`, if there is one
+    async fn read_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+    ) -> Result<Option<Vec<u8>>, SecretError>;
+
+    /// Remove the secret stored under `secret_ref`, if there is one
+    async fn remove_secret(&self, secret_ref: &SecretVaultRef) -> Result<(), SecretError>;
+}
+
+// The configured sources, retained so that the vault view can be rebuilt
+// once a key has been rotated
+#[derive(Clone)]
+enum KeySource {
+    Embedded(EmbeddedSecretManagerSource),
+    Vault(VaultSecretManagerSource),
+    Filesystem(PathBuf),
+}
+
+impl KeySource {
+    /// Why the source cannot be read from, if it is suffering an outage
+    fn outage(&self) -> Option<String> {
+        match self {
+            KeySource::Vault(source) => source.outage(),
+            KeySource::Embedded(_) | KeySource::Filesystem(_) => None,
+        }
+    }
+
+    fn add_to(
+        &self,
+        namespace: &str,
+        multi_source: MultipleSecretsSources,
+    ) -> MultipleSecretsSources {
+        let namespace = SecretNamespace::new(namespace.to_owned());
+        match self {
+            KeySource::Embedded(source) => multi_source.add_source(&namespace, source.clone()),
+            KeySource::Vault(source) => multi_source.add_source(&namespace, source.clone()),
+            KeySource::Filesystem(path) => multi_source.add_source(
+                &namespace,
+                FilesSource::with_options(FilesSourceOptions {
+                    root_path: Some(path.clone().into_boxed_path()),
+                }),
+            ),
+        }
+    }
+
+    async fn replace_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+        secret: &[u8],
+        retained_ref: &SecretVaultRef,
+        expired: &[u8],
+    ) -> Result<(), SecretError> {
+        match self {
+            KeySource::Embedded(source) => {
+                source
+                    .replace_secret(secret_ref, secret, retained_ref, expired)
+                    .await
+            }
+            KeySource::Vault(source) => {
+                source
+                    .replace_secret(secret_ref, secret, retained_ref, expired)
+                    .await
+            }
+            // Write the new key beside the current one and rename it into
+            // place, so a reader never observes a partially written key
+            KeySource::Filesystem(path) => {
+                let target = path.join(secret_ref.key.secret_name.as_ref());
+                let staged = path.join(format!("{}.rotating", secret_ref.key.secret_name.as_ref()));
+                std::fs::write(path.join(retained_ref.key.secret_name.as_ref()), expired)?;
+                std::fs::write(&staged, secret)?;
+                std::fs::rename(&staged, &target)?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn write_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+        secret: &[u8],
+    ) -> Result<(), SecretError> {
+        match self {
+            KeySource::Embedded(source) => source.write_secret(secret_ref, secret).await,
+            KeySource::Vault(source) => source.write_secret(secret_ref, secret).await,
+            KeySource::Filesystem(path) => {
+                let target = path.join(secret_ref.key.secret_name.as_ref());
+                let staged = path.join(format!("{}.writing", secret_ref.key.secret_name.as_ref()));
+                std::fs::write(&staged, secret)?;
+                std::fs::rename(&staged, &target)?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn read_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+    ) -> Result<Option<Vec<u8>>, SecretError> {
+        match self {
+            KeySource::Embedded(source) => source.read_secret(secret_ref).await,
+            KeySource::Vault(source) => source.read_secret(secret_ref).await,
+            KeySource::Filesystem(path) => {
+                match std::fs::read(path.join(secret_ref.key.secret_name.as_ref())) {
+                    Ok(secret) => Ok(Some(secret)),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    async fn remove_secret(&self, secret_ref: &SecretVaultRef) -> Result<(), SecretError> {
+        match self {
+            KeySource::Embedded(source) => source.remove_secret(secret_ref).await,
+            KeySource::Vault(source) => source.remove_secret(secret_ref).await,
+            KeySource::Filesystem(path) => {
+                match std::fs::remove_file(path.join(secret_ref.key.secret_name.as_ref())) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Report a failure to read from the secret store as temporary if one of the
+/// sources is suffering an outage
+fn unavailable_or(sources: &[(String, KeySource)], error: SecretVaultError) -> SecretError {
+    match sources.iter().find_map(|(_, source)| source.outage()) {
+        Some(outage) => SecretError::TemporarilyUnavailable(outage),
+        None => SecretError::from(error),
+    }
+}
+
+async fn build_vault(
+    secret_refs: &[SecretVaultRef],
+    sources: &[(String, KeySource)],
+) -> Result<Box<dyn SecretVaultView + Send + Sync>, SecretError> {
+    let multi_source = sources.iter().fold(
+        MultipleSecretsSources::new(),
+        |multi_source, (namespace, source)| source.add_to(namespace, multi_source),
+    );
+
+    let vault = SecretVaultBuilder::with_source(multi_source)
+        .with_secret_refs(secret_refs.iter().collect())
+        .build()?;
+
+    vault
+        .refresh()
+        .await
+        .map_err(|e| unavailable_or(sources, e))?;
+
+    info!(
+        secrets = ?secret_refs
+            .iter()
+            .map(|secret_ref| secret_ref.key.secret_name.as_ref().to_owned())
+            .collect::<Vec<_>>(),
+        "Loaded secrets"
+    );
+
+    Ok(Box::new(vault.viewer()))
+}
+
+static KEY_TRANSITION_PREFIX: &[u8] = b"chronicle-key-transition:";
+
+/// The statement an outgoing key signs to authorise its successor, the
+/// compressed SEC1 encodings of both keys behind a fixed prefix
+pub fn key_transition_statement(previous: &VerifyingKey, next: &VerifyingKey) -> Vec<u8> {
+    [
+        KEY_TRANSITION_PREFIX,
+        previous.to_bytes().as_slice(),
+        next.to_bytes().as_slice(),
+    ]
+    .concat()
+}
+
+/// Check that `signature` is the outgoing key's signature over the transition
+/// statement for `previous` to `next`
+pub fn verify_key_transition(
+    previous: &VerifyingKey,
+    next: &VerifyingKey,
+    signature: &[u8],
+) -> bool {
+    <Signature as k256::ecdsa::signature::Signature>::from_bytes(signature)
+        .map(|signature| {
+            previous
+                .verify(&key_transition_statement(previous, next), &signature)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// The replacement for a signing key
+pub enum NewKey {
+    /// Generate a new random key
+    Generate,
+    /// Import an existing PKCS8 PEM encoded key
+    Import(String),
+}
+
+/// A key rotation that has been signed by the outgoing key, with the new key
+/// held in the secret store under a pending name until the rotation is
+/// completed or discarded. The transition should be recorded before the
+/// rotation is completed, if that fails the rotation should be discarded and
+/// the current key remains in use.
+pub struct PendingKeyRotation {
+    pub previous: VerifyingKey,
+    pub next: VerifyingKey,
+    pub signature: Vec<u8>,
+    secret_namespace: String,
+    secret_ref: SecretVaultRef,
+    pending_ref: SecretVaultRef,
+    secret: String,
+}
+
+impl std::fmt::Debug for PendingKeyRotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingKeyRotation")
+            .field("previous", &self.previous)
+            .field("next", &self.next)
+            .finish()
+    }
 }
 
 #[derive(Clone)]
 pub struct ChronicleSigning {
     vault: Arc<tokio::sync::Mutex<Box<dyn SecretVaultView + Send + Sync>>>,
+    secret_refs: Arc<Vec<SecretVaultRef>>,
+    sources: Arc<Vec<(String, KeySource)>>,
+    remote: RemoteSigner,
 }
 
 impl std::fmt::Debug for ChronicleSigning {
@@ -105,54 +396,271 @@ impl ChronicleSigning {
         // Secret stores are namespaced
         options: Vec<(String, ChronicleSecretsOptions)>,
     ) -> Result<Self, SecretError> {
-        let mut multi_source = MultipleSecretsSources::new();
+        let mut sources = vec![];
+        let mut remote = vec![];
+        for (namespace, options) in options {
+            let source = match options {
+                ChronicleSecretsOptions::Kms(client) => {
+                    remote.push((namespace, client));
+                    continue;
+                }
+                ChronicleSecretsOptions::Embedded => {
+                    KeySource::Embedded(EmbeddedSecretManagerSource::new())
+                }
+                ChronicleSecretsOptions::Test => {
+                    KeySource::Embedded(EmbeddedSecretManagerSource::new_deterministic())
+                }
+                ChronicleSecretsOptions::Vault(options) => {
+                    KeySource::Vault(VaultSecretManagerSource::with_options(options).await?)
+                }
+                ChronicleSecretsOptions::Filesystem(path) => KeySource::Filesystem(path),
+            };
+            sources.push((namespace, source));
+        }
+
+        let remote = RemoteSigner::new(remote);
+
+        // Remotely held keys are not loaded into the vault
         let required_secret_refs: Vec<_> = required_secret_names
             .into_iter()
+            .filter(|(namespace, _)| !remote.holds(namespace))
             .map(|(namespace, name)| {
                 SecretVaultRef::new(SecretName::new(name))
                     .with_namespace(SecretNamespace::new(namespace))
             })
             .collect();
 
-        for options in options {
-            match options {
-                (namespace, ChronicleSecretsOptions::Embedded) => {
-                    let source = embedded_secret_manager_source::EmbeddedSecretManagerSource::new();
-                    multi_source =
-                        multi_source.add_source(&SecretNamespace::new(namespace), source);
-                }
-                (namespace, ChronicleSecretsOptions::Test) => {
-                    let source = embedded_secret_manager_source::EmbeddedSecretManagerSource::new_deterministic();
-                    multi_source =
-                        multi_source.add_source(&SecretNamespace::new(namespace), source);
-                }
-                (namespace, ChronicleSecretsOptions::Vault(options)) => {
-                    let source =
-                        vault_secret_manager_source::VaultSecretManagerSource::with_options(
-                            options,
-                        )
-                        .await?;
-                    multi_source =
-                        multi_source.add_source(&SecretNamespace::new(namespace), source);
-                }
-                (namespace, ChronicleSecretsOptions::Filesystem(path)) => {
-                    let source = FilesSource::with_options(FilesSourceOptions {
-                        root_path: Some(path.into_boxed_path()),
-                    });
-                    multi_source =
-                        multi_source.add_source(&SecretNamespace::new(namespace), source);
-                }
+        let vault = build_vault(&required_secret_refs, &sources).await?;
+
+        Ok(Self {
+            vault: Arc::new(tokio::sync::Mutex::new(vault)),
+            secret_refs: Arc::new(required_secret_refs),
+            sources: Arc::new(sources),
+            remote,
+        })
+    }
+
+    /// Read a secret from the vault, failing as temporarily unavailable if the
+    /// secret store is suffering an outage
+    async fn require_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+    ) -> Result<secret_vault::Secret, SecretError> {
+        self.vault
+            .lock()
+            .await
+            .require_secret_by_ref(secret_ref)
+            .await
+            .map_err(|e| unavailable_or(&self.sources, e))
+    }
+
+    /// The secret source for keys in `namespace`
+    fn source(&self, namespace: &str) -> Result<&KeySource, SecretError> {
+        self.sources
+            .iter()
+            .find(|(source_namespace, _)| source_namespace == namespace)
+            .map(|(_, source)| source)
+            .ok_or_else(|| {
+                SecretError::Rotation(format!("no secret source for namespace {namespace}"))
+            })
+    }
+
+    /// The name a new key is held under until its rotation is completed
+    fn pending_ref(secret_namespace: &str, secret_name: &str) -> SecretVaultRef {
+        SecretVaultRef::new(SecretName::new(format!("{secret_name}-pending")))
+            .with_namespace(secret_namespace.into())
+    }
+
+    /// A rotation to `secret` from the current key, signed by the current key
+    async fn rotation_to(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+        secret: String,
+    ) -> Result<PendingKeyRotation, SecretError> {
+        let next = SigningKey::from_pkcs8_pem(&secret)
+            .map_err(|_| SecretError::InvalidPrivateKey)?
+            .verifying_key();
+
+        let previous = self.verifying_key(secret_namespace, secret_name).await?;
+
+        let signature = self
+            .sign(
+                secret_namespace,
+                secret_name,
+                &key_transition_statement(&previous, &next),
+            )
+            .await?
+            .to_vec();
+
+        Ok(PendingKeyRotation {
+            previous,
+            next,
+            signature,
+            secret_namespace: secret_namespace.to_owned(),
+            secret_ref: SecretVaultRef::new(SecretName::new(secret_name.to_owned()))
+                .with_namespace(secret_namespace.into()),
+            pending_ref: Self::pending_ref(secret_namespace, secret_name),
+            secret,
+        })
+    }
+
+    /// Sign a transition from the current key to a new one. The new key is
+    /// written to the secret store under a pending name, so that a rotation
+    /// interrupted by a restart can be found with
+    /// [ChronicleSigning::pending_key_rotation], but the current key stays in
+    /// use until the returned rotation is passed to
+    /// [ChronicleSigning::complete_key_rotation]
+    #[instrument(skip(self, new_key), level = "debug")]
+    pub async fn prepare_key_rotation(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+        new_key: NewKey,
+    ) -> Result<PendingKeyRotation, SecretError> {
+        if self.remote.holds(secret_namespace) {
+            return Err(SecretError::Rotation(format!(
+                "keys in namespace {secret_namespace} are held remotely, rotate them with the key management service"
+            )));
+        }
+
+        let secret = match new_key {
+            NewKey::Generate => SecretKey::random(StdRng::from_entropy())
+                .to_pkcs8_pem(LineEnding::CRLF)
+                .map_err(|_| SecretError::InvalidPrivateKey)?
+                .to_string(),
+            NewKey::Import(pem) => pem,
+        };
+
+        let rotation = self
+            .rotation_to(secret_namespace, secret_name, secret)
+            .await?;
+
+        if rotation.previous == rotation.next {
+            return Err(SecretError::Rotation(
+                "the new key is the same as the current key".to_owned(),
+            ));
+        }
+
+        self.source(secret_namespace)?
+            .write_secret(&rotation.pending_ref, rotation.secret.as_bytes())
+            .await?;
+
+        Ok(rotation)
+    }
+
+    /// The rotation prepared for a key but neither completed nor discarded,
+    /// as when the process preparing it stopped before it was resolved. A
+    /// pending key that has already replaced the current one is removed.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn pending_key_rotation(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+    ) -> Result<Option<PendingKeyRotation>, SecretError> {
+        if self.remote.holds(secret_namespace) {
+            return Ok(None);
+        }
+
+        let source = self.source(secret_namespace)?;
+        let pending_ref = Self::pending_ref(secret_namespace, secret_name);
+        let secret = match source.read_secret(&pending_ref).await? {
+            Some(secret) => {
+                String::from_utf8(secret).map_err(|_| SecretError::InvalidPrivateKey)?
             }
+            None => return Ok(None),
+        };
+
+        let rotation = self
+            .rotation_to(secret_namespace, secret_name, secret)
+            .await?;
+
+        if rotation.previous == rotation.next {
+            source.remove_secret(&pending_ref).await?;
+            return Ok(None);
         }
 
-        let vault = SecretVaultBuilder::with_source(multi_source)
-            .with_secret_refs(required_secret_refs.iter().collect())
-            .build()?;
+        Ok(Some(rotation))
+    }
 
-        vault.refresh().await?;
-        Ok(Self {
-            vault: Arc::new(tokio::sync::Mutex::new(Box::new(vault.viewer()))),
-        })
+    /// Remove the new key of a rotation that will not be completed, leaving
+    /// the current key in use
+    #[instrument(skip(self), level = "debug")]
+    pub async fn discard_key_rotation(
+        &self,
+        rotation: PendingKeyRotation,
+    ) -> Result<(), SecretError> {
+        self.source(&rotation.secret_namespace)?
+            .remove_secret(&rotation.pending_ref)
+            .await
+    }
+
+    /// Replace the outgoing key with its successor. The outgoing key is kept
+    /// in the same store, named for the time it expired. Signing is blocked
+    /// until the store has been updated and reloaded.
+    #[instrument(skip(self), level = "debug")]
+    pub async fn complete_key_rotation(
+        &self,
+        rotation: PendingKeyRotation,
+    ) -> Result<(), SecretError> {
+        let mut vault = self.vault.lock().await;
+
+        let current = vault
+            .require_secret_by_ref(&rotation.secret_ref)
+            .await
+            .map_err(|e| unavailable_or(&self.sources, e))?;
+        let (current_key, expired) = current.value.exposed_in_as_str(|secret| {
+            (
+                (
+                    SigningKey::from_pkcs8_pem(&secret)
+                        .map_err(|_| SecretError::InvalidPrivateKey)
+                        .map(|signing_key| signing_key.verifying_key()),
+                    secret.clone(),
+                ),
+                secret,
+            )
+        });
+
+        if current_key? != rotation.previous {
+            return Err(SecretError::Rotation(
+                "the key was changed after the rotation was prepared".to_owned(),
+            ));
+        }
+
+        let namespace = &rotation.secret_namespace;
+        let source = self.source(namespace)?;
+
+        let expired_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+        let retained_ref = SecretVaultRef::new(SecretName::new(format!(
+            "{}-expired-{}",
+            rotation.secret_ref.key.secret_name.as_ref(),
+            expired_at
+        )))
+        .with_namespace(namespace.as_str().into());
+
+        source
+            .replace_secret(
+                &rotation.secret_ref,
+                rotation.secret.as_bytes(),
+                &retained_ref,
+                expired.as_bytes(),
+            )
+            .await?;
+
+        *vault = build_vault(&self.secret_refs, &self.sources).await?;
+
+        source.remove_secret(&rotation.pending_ref).await?;
+
+        info!(
+            namespace = %namespace,
+            retained = %retained_ref.key.secret_name.as_ref(),
+            "Rotated signing key"
+        );
+
+        Ok(())
     }
 }
 
@@ -199,14 +707,16 @@ impl WithSecret for ChronicleSigning {
         F: Send,
         T: Send,
     {
+        if self.remote.holds(secret_namespace) {
+            return self
+                .remote
+                .with_signing_key(secret_namespace, secret_name, f)
+                .await;
+        }
+
         let secret_ref = SecretVaultRef::new(SecretName::new(secret_name.to_owned()))
             .with_namespace(secret_namespace.into());
-        let secret = self
-            .vault
-            .lock()
-            .await
-            .require_secret_by_ref(&secret_ref)
-            .await?;
+        let secret = self.require_secret(&secret_ref).await?;
 
         let signing_result = secret.value.exposed_in_as_str(|secret| {
             (
@@ -232,14 +742,16 @@ impl WithSecret for ChronicleSigning {
         F: Send,
         T: Send,
     {
+        if self.remote.holds(secret_namespace) {
+            return self
+                .remote
+                .with_verifying_key(secret_namespace, secret_name, f)
+                .await;
+        }
+
         let secret_ref = SecretVaultRef::new(SecretName::new(secret_name.to_owned()))
             .with_namespace(secret_namespace.into());
-        let secret = self
-            .vault
-            .lock()
-            .await
-            .require_secret_by_ref(&secret_ref)
-            .await?;
+        let secret = self.require_secret(&secret_ref).await?;
 
         let signing_result = secret.value.exposed_in_as_str(|secret| {
             (
@@ -258,14 +770,16 @@ impl WithSecret for ChronicleSigning {
         secret_namespace: &str,
         secret_name: &str,
     ) -> Result<VerifyingKey, SecretError> {
+        if self.remote.holds(secret_namespace) {
+            return self
+                .remote
+                .verifying_key(secret_namespace, secret_name)
+                .await;
+        }
+
         let secret_ref = SecretVaultRef::new(SecretName::new(secret_name.to_owned()))
             .with_namespace(secret_namespace.into());
-        let secret = self
-            .vault
-            .lock()
-            .await
-            .require_secret_by_ref(&secret_ref)
-            .await?;
+        let secret = self.require_secret(&secret_ref).await?;
 
         let key = secret.value.exposed_in_as_str(|secret| {
             (
@@ -302,14 +816,19 @@ pub trait ChronicleSigner {
 }
 
 #[async_trait::async_trait]
-impl<T: WithSecret + Send + Sync> ChronicleSigner for T {
-    /// Sign data with the chronicle key and return a signature
+impl ChronicleSigner for ChronicleSigning {
+    /// Sign data with the chronicle key and return a signature, remotely held
+    /// keys sign through their key management service
     async fn sign(
         &self,
         secret_namespace: &str,
         secret_name: &str,
         data: &[u8],
     ) -> Result<Signature, SecretError> {
+        if self.remote.holds(secret_namespace) {
+            return self.remote.sign(secret_namespace, secret_name, data).await;
+        }
+
         self.with_signing_key(secret_namespace, secret_name, |signing_key| {
             let s: Signature = signing_key.sign(data);
             s
@@ -590,4 +1109,215 @@ mod tests {
             .await
             .unwrap());
     }
+
+    #[tokio::test]
+    async fn rotate_embedded_key() {
+        let secrets = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![(
+                CHRONICLE_NAMESPACE.to_string(),
+                ChronicleSecretsOptions::Embedded,
+            )],
+        )
+        .await
+        .unwrap();
+
+        let previous = secrets.chronicle_verifying().await.unwrap();
+
+        let rotation = secrets
+            .prepare_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK, NewKey::Generate)
+            .await
+            .unwrap();
+
+        assert_eq!(rotation.previous, previous);
+        assert!(verify_key_transition(
+            &rotation.previous,
+            &rotation.next,
+            &rotation.signature
+        ));
+        assert!(!verify_key_transition(
+            &rotation.next,
+            &rotation.previous,
+            &rotation.signature
+        ));
+
+        // Preparing a rotation leaves the current key in place, with the new
+        // key held as pending
+        assert_eq!(secrets.chronicle_verifying().await.unwrap(), previous);
+        let pending = secrets
+            .pending_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pending.next, rotation.next);
+
+        let next = rotation.next;
+        secrets.complete_key_rotation(rotation).await.unwrap();
+
+        assert_eq!(secrets.chronicle_verifying().await.unwrap(), next);
+        assert!(secrets
+            .pending_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+            .await
+            .unwrap()
+            .is_none());
+
+        let sig = secrets.chronicle_sign(b"hello world").await.unwrap();
+        let sig = k256::ecdsa::Signature::try_from(sig.as_slice()).unwrap();
+        assert!(next.verify(b"hello world", &sig).is_ok());
+    }
+
+    #[tokio::test]
+    async fn discard_embedded_key_rotation() {
+        let secrets = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![(
+                CHRONICLE_NAMESPACE.to_string(),
+                ChronicleSecretsOptions::Embedded,
+            )],
+        )
+        .await
+        .unwrap();
+
+        let previous = secrets.chronicle_verifying().await.unwrap();
+
+        let rotation = secrets
+            .prepare_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK, NewKey::Generate)
+            .await
+            .unwrap();
+        secrets.discard_key_rotation(rotation).await.unwrap();
+
+        assert_eq!(secrets.chronicle_verifying().await.unwrap(), previous);
+        assert!(secrets
+            .pending_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn pending_key_rotation_survives_a_restart() {
+        let path = std::env::temp_dir().join(format!(
+            "chronicle-signing-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(
+            path.join(CHRONICLE_PK),
+            SecretKey::random(StdRng::from_entropy())
+                .to_pkcs8_pem(LineEnding::CRLF)
+                .unwrap()
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let start = || {
+            ChronicleSigning::new(
+                vec![(CHRONICLE_NAMESPACE.to_string(), CHRONICLE_PK.to_string())],
+                vec![(
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::stored_at_path(&path),
+                )],
+            )
+        };
+
+        let secrets = start().await.unwrap();
+        let previous = secrets.chronicle_verifying().await.unwrap();
+        let next = secrets
+            .prepare_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK, NewKey::Generate)
+            .await
+            .unwrap()
+            .next;
+        drop(secrets);
+
+        // The prepared key is found again after a restart, as a transition
+        // from the current key
+        let secrets = start().await.unwrap();
+        let rotation = secrets
+            .pending_key_rotation(CHRONICLE_NAMESPACE, CHRONICLE_PK)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rotation.previous, previous);
+        assert_eq!(rotation.next, next);
+        assert!(verify_key_transition(
+            &rotation.previous,
+            &rotation.next,
+            &rotation.signature
+        ));
+
+        secrets.complete_key_rotation(rotation).await.unwrap();
+        assert_eq!(secrets.chronicle_verifying().await.unwrap(), next);
+        assert!(!path.join(format!("{CHRONICLE_PK}-pending")).exists());
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    struct LocalKms(SigningKey);
+
+    #[async_trait::async_trait]
+    impl KmsClient for LocalKms {
+        fn name(&self) -> &str {
+            "LocalKms"
+        }
+
+        async fn sign_digest(&self, digest: &[u8]) -> Result<Vec<u8>, SecretError> {
+            use k256::ecdsa::signature::hazmat::PrehashSigner;
+
+            let signature: k256::ecdsa::Signature = self
+                .0
+                .sign_prehash(digest)
+                .map_err(|e| SecretError::Kms(e.to_string()))?;
+
+            Ok(signature.to_der().as_bytes().to_vec())
+        }
+
+        async fn public_key(&self) -> Result<VerifyingKey, SecretError> {
+            Ok(self.0.verifying_key())
+        }
+    }
+
+    #[tokio::test]
+    async fn kms_keys() {
+        let key = SigningKey::random(StdRng::from_entropy());
+        let verifying_key = key.verifying_key();
+
+        let secrets = ChronicleSigning::new(
+            chronicle_secret_names(),
+            vec![
+                (
+                    CHRONICLE_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::signed_by_kms(LocalKms(key)),
+                ),
+                (
+                    BATCHER_NAMESPACE.to_string(),
+                    ChronicleSecretsOptions::Embedded,
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(secrets.chronicle_verifying().await.unwrap(), verifying_key);
+
+        let sig = secrets.chronicle_sign(b"hello world").await.unwrap();
+        assert!(secrets
+            .chronicle_verify(b"hello world", &sig)
+            .await
+            .unwrap());
+        assert!(!secrets.chronicle_verify(b"boom", &sig).await.unwrap());
+
+        assert!(matches!(
+            secrets
+                .with_signing_key(CHRONICLE_NAMESPACE, CHRONICLE_PK, |_| ())
+                .await,
+            Err(SecretError::KeyNotExportable(_))
+        ));
+
+        // Keys held elsewhere are unaffected
+        assert!(secrets.batcher_sign(b"hello world").await.is_ok());
+    }
 }
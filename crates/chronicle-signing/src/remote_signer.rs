@@ -0,0 +1,154 @@
+use std::{collections::HashMap, sync::Arc};
+
+use k256::{
+    ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    sha2::{Digest, Sha256},
+};
+use tokio::sync::OnceCell;
+use tracing::instrument;
+
+use crate::{ChronicleSigner, SecretError, WithSecret};
+
+/// A secp256k1 key held by a key management service. The private key is never
+/// exported, signing requests are made against the service's sign API.
+#[async_trait::async_trait]
+pub trait KmsClient: Send + Sync {
+    /// A short name for the service, used in tracing
+    fn name(&self) -> &str;
+
+    /// Sign a SHA-256 digest, returning a DER encoded ECDSA signature
+    async fn sign_digest(&self, digest: &[u8]) -> Result<Vec<u8>, SecretError>;
+
+    /// The verifying key for the held key
+    async fn public_key(&self) -> Result<VerifyingKey, SecretError>;
+}
+
+struct RemoteKey {
+    client: Arc<dyn KmsClient>,
+    public_key: OnceCell<VerifyingKey>,
+}
+
+/// Signs with keys held by key management services, one per secret namespace.
+/// The secret name is ignored, as each namespace maps to a single remote key.
+/// Verifying keys are fetched once and then cached.
+#[derive(Clone, Default)]
+pub struct RemoteSigner {
+    keys: Arc<HashMap<String, RemoteKey>>,
+}
+
+impl std::fmt::Debug for RemoteSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteSigner")
+            .field("namespaces", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl RemoteSigner {
+    pub fn new(keys: Vec<(String, Arc<dyn KmsClient>)>) -> Self {
+        Self {
+            keys: Arc::new(
+                keys.into_iter()
+                    .map(|(namespace, client)| {
+                        (
+                            namespace,
+                            RemoteKey {
+                                client,
+                                public_key: OnceCell::new(),
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// True if keys in `secret_namespace` are held remotely
+    pub fn holds(&self, secret_namespace: &str) -> bool {
+        self.keys.contains_key(secret_namespace)
+    }
+
+    fn key(&self, secret_namespace: &str) -> Result<&RemoteKey, SecretError> {
+        self.keys
+            .get(secret_namespace)
+            .ok_or(SecretError::NoPublicKeyFound)
+    }
+}
+
+#[async_trait::async_trait]
+impl WithSecret for RemoteSigner {
+    /// Remote keys cannot be exported, use [ChronicleSigner::sign] instead
+    async fn with_signing_key<T, F>(
+        &self,
+        secret_namespace: &str,
+        _secret_name: &str,
+        _f: F,
+    ) -> Result<T, SecretError>
+    where
+        F: Fn(k256::ecdsa::SigningKey) -> T,
+        F: Send,
+        T: Send,
+    {
+        Err(SecretError::KeyNotExportable(secret_namespace.to_owned()))
+    }
+
+    async fn with_verifying_key<T, F>(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+        f: F,
+    ) -> Result<T, SecretError>
+    where
+        F: Fn(VerifyingKey) -> T,
+        F: Send,
+        T: Send,
+    {
+        Ok(f(self.verifying_key(secret_namespace, secret_name).await?))
+    }
+
+    async fn verifying_key(
+        &self,
+        secret_namespace: &str,
+        _secret_name: &str,
+    ) -> Result<VerifyingKey, SecretError> {
+        let key = self.key(secret_namespace)?;
+
+        key.public_key
+            .get_or_try_init(|| key.client.public_key())
+            .await
+            .cloned()
+    }
+}
+
+#[async_trait::async_trait]
+impl ChronicleSigner for RemoteSigner {
+    #[instrument(skip(self, data), level = "trace")]
+    async fn sign(
+        &self,
+        secret_namespace: &str,
+        _secret_name: &str,
+        data: &[u8],
+    ) -> Result<Signature, SecretError> {
+        let key = self.key(secret_namespace)?;
+        let digest = Sha256::digest(data);
+
+        let signature = key.client.sign_digest(&digest).await?;
+
+        Signature::from_der(&signature)
+            .map_err(|e| SecretError::Kms(format!("{}: {e}", key.client.name())))
+    }
+
+    async fn verify(
+        &self,
+        secret_namespace: &str,
+        secret_name: &str,
+        data: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, SecretError> {
+        let verifying_key = self.verifying_key(secret_namespace, secret_name).await?;
+        let signature: Signature = k256::ecdsa::signature::Signature::from_bytes(signature)
+            .map_err(|_| SecretError::InvalidPublicKey)?;
+
+        Ok(verifying_key.verify(data, &signature).is_ok())
+    }
+}
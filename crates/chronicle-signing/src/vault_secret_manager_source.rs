@@ -1,4 +1,8 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_trait::*;
 use secret_vault::{
@@ -8,12 +12,70 @@ use secret_vault::{
 use secret_vault_value::SecretValue;
 use tokio::sync::Mutex;
 use tracing::*;
+
+use crate::{KeyWriter, SecretError};
 use url::Url;
 use vaultrs::{
     client::{VaultClient, VaultClientSettingsBuilder},
+    error::ClientError,
     kv2,
 };
 
+/// How many times a read that fails transiently is attempted
+const READ_ATTEMPTS: u32 = 4;
+/// The delay before the first retry of a read, doubled for each retry after
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+/// How many reads in a row may fail transiently before Vault is left alone
+const BREAKER_THRESHOLD: u32 = 5;
+/// How long Vault is left alone once the breaker has opened
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Whether a Vault error may clear up without intervention, as when Vault is
+/// unreachable, sealed, or overloaded
+fn is_transient(error: &ClientError) -> bool {
+    match error {
+        ClientError::APIError { code, .. } => *code == 429 || *code >= 500,
+        ClientError::RestClientError { .. } => true,
+        _ => false,
+    }
+}
+
+/// Stops reads from Vault for a while after it has failed repeatedly, so that
+/// an outage fails requests quickly rather than stalling them in retries
+#[derive(Debug, Default)]
+struct CircuitBreaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    last_failure: Option<String>,
+}
+
+impl CircuitBreaker {
+    /// The reason Vault is being left alone, if it is
+    fn check(&self, now: Instant) -> Result<(), String> {
+        match (&self.open_until, &self.last_failure) {
+            (Some(open_until), Some(reason)) if now < *open_until => Err(reason.clone()),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_success(&mut self) {
+        *self = Self::default();
+    }
+
+    fn record_failure(&mut self, reason: String, now: Instant) {
+        self.consecutive_failures += 1;
+        self.last_failure = Some(reason);
+        if self.consecutive_failures >= BREAKER_THRESHOLD {
+            self.open_until = Some(now + BREAKER_COOLDOWN);
+        }
+    }
+
+    /// The last transient failure, if Vault has not been read from since
+    fn outage(&self) -> Option<String> {
+        self.last_failure.clone()
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct VaultSecretManagerSourceOptions {
     pub vault_url: Url,
@@ -35,6 +97,7 @@ impl VaultSecretManagerSourceOptions {
 pub struct VaultSecretManagerSource {
     options: VaultSecretManagerSourceOptions,
     client: Arc<Mutex<VaultClient>>,
+    breaker: Arc<std::sync::Mutex<CircuitBreaker>>,
 }
 
 impl VaultSecretManagerSource {
@@ -59,8 +122,43 @@ impl VaultSecretManagerSource {
                     )
                 })?,
             )),
+            breaker: Arc::new(std::sync::Mutex::new(CircuitBreaker::default())),
         })
     }
+
+    /// Why Vault is unavailable, if its last read failed transiently
+    pub(crate) fn outage(&self) -> Option<String> {
+        self.breaker.lock().unwrap().outage()
+    }
+
+    /// Read a secret, retrying with increasing delays while Vault fails
+    /// transiently
+    async fn read_with_retry(
+        &self,
+        client: &VaultClient,
+        secret_name: &str,
+    ) -> Result<Vec<u8>, ClientError> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match kv2::read(client, &self.options.mount_path, secret_name).await {
+                Err(e) if is_transient(&e) && attempt < READ_ATTEMPTS => {
+                    warn!(secret_name, attempt, %e, "Vault read failed, retrying");
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn source_error(message: String) -> SecretVaultError {
+    SecretVaultError::SecretsSourceError(SecretsSourceError::new(
+        SecretVaultErrorPublicGenericDetails::new(message.clone()),
+        message,
+    ))
 }
 
 #[async_trait]
@@ -73,26 +171,26 @@ impl SecretsSource for VaultSecretManagerSource {
         &self,
         references: &[SecretVaultRef],
     ) -> SecretVaultResult<HashMap<SecretVaultRef, Secret>> {
+        if let Err(reason) = self.breaker.lock().unwrap().check(Instant::now()) {
+            return Err(source_error(format!(
+                "Vault at {} is temporarily unavailable: {}",
+                self.options.vault_url, reason
+            )));
+        }
+
         let mut result_map: HashMap<SecretVaultRef, Secret> = HashMap::new();
+        let mut failures = vec![];
         let client = &*self.client.lock().await;
 
-        let mut results = vec![];
         for secret_ref in references {
-            results.push((
-                secret_ref.clone(),
-                kv2::read(
-                    client,
-                    &self.options.mount_path,
-                    secret_ref.key.secret_name.as_ref(),
-                )
-                .await,
-            ));
-        }
+            let result = self
+                .read_with_retry(client, secret_ref.key.secret_name.as_ref())
+                .await;
 
-        for (secret_ref, result) in results {
             match result {
                 Ok(vault_secret) => {
-                    let metadata = SecretMetadata::create_from_ref(&secret_ref);
+                    self.breaker.lock().unwrap().record_success();
+                    let metadata = SecretMetadata::create_from_ref(secret_ref);
                     result_map.insert(
                         secret_ref.clone(),
                         Secret::new(SecretValue::new(vault_secret), metadata),
@@ -106,22 +204,156 @@ impl SecretsSource for VaultSecretManagerSource {
                         &secret_ref.key.secret_version,
                         err
                     );
-                    return Err(SecretVaultError::SecretsSourceError(
-                        SecretsSourceError::new(
-                            SecretVaultErrorPublicGenericDetails::new(format!(
-                                "Unable to read secret or secret version {}/{:?}: {}.",
-                                self.options.mount_path, &secret_ref.key.secret_name, err
-                            )),
-                            format!(
-                                "Unable to read secret or secret version {}/{:?}: {}.",
-                                self.options.mount_path, &secret_ref.key.secret_name, err
-                            ),
-                        ),
+                    if is_transient(&err) {
+                        self.breaker
+                            .lock()
+                            .unwrap()
+                            .record_failure(err.to_string(), Instant::now());
+                    } else {
+                        self.breaker.lock().unwrap().record_success();
+                    }
+                    failures.push(format!(
+                        "{}/{}: {}",
+                        self.options.mount_path, &secret_ref.key.secret_name, err
                     ));
                 }
             }
         }
 
+        if !failures.is_empty() {
+            return Err(source_error(format!(
+                "Unable to read secrets from Vault at {}: {}",
+                self.options.vault_url,
+                failures.join(", ")
+            )));
+        }
+
         Ok(result_map)
     }
 }
+
+#[async_trait]
+impl KeyWriter for VaultSecretManagerSource {
+    // Vault cannot update two secrets in one request, so the expired key is
+    // written first, a failure then leaves the current key untouched
+    async fn replace_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+        secret: &[u8],
+        retained_ref: &SecretVaultRef,
+        expired: &[u8],
+    ) -> Result<(), SecretError> {
+        let client = &*self.client.lock().await;
+
+        for (secret_ref, value) in [(retained_ref, expired), (secret_ref, secret)] {
+            kv2::set(
+                client,
+                &self.options.mount_path,
+                secret_ref.key.secret_name.as_ref(),
+                &value.to_vec(),
+            )
+            .await
+            .map_err(|e| {
+                error!(
+                    "Unable to write secret {}/{}: {}",
+                    self.options.mount_path, &secret_ref.key.secret_name, e
+                );
+                SecretError::Rotation(e.to_string())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn write_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+        secret: &[u8],
+    ) -> Result<(), SecretError> {
+        let client = &*self.client.lock().await;
+
+        kv2::set(
+            client,
+            &self.options.mount_path,
+            secret_ref.key.secret_name.as_ref(),
+            &secret.to_vec(),
+        )
+        .await
+        .map_err(|e| {
+            error!(
+                "Unable to write secret {}/{}: {}",
+                self.options.mount_path, &secret_ref.key.secret_name, e
+            );
+            SecretError::Rotation(e.to_string())
+        })?;
+
+        Ok(())
+    }
+
+    async fn read_secret(
+        &self,
+        secret_ref: &SecretVaultRef,
+    ) -> Result<Option<Vec<u8>>, SecretError> {
+        let client = &*self.client.lock().await;
+
+        match self
+            .read_with_retry(client, secret_ref.key.secret_name.as_ref())
+            .await
+        {
+            Ok(secret) => Ok(Some(secret)),
+            Err(ClientError::APIError { code: 404, .. }) => Ok(None),
+            Err(e) if is_transient(&e) => Err(SecretError::TemporarilyUnavailable(e.to_string())),
+            Err(e) => Err(SecretError::Rotation(e.to_string())),
+        }
+    }
+
+    async fn remove_secret(&self, secret_ref: &SecretVaultRef) -> Result<(), SecretError> {
+        let client = &*self.client.lock().await;
+
+        match kv2::delete_metadata(
+            client,
+            &self.options.mount_path,
+            secret_ref.key.secret_name.as_ref(),
+        )
+        .await
+        {
+            Ok(()) | Err(ClientError::APIError { code: 404, .. }) => Ok(()),
+            Err(e) => {
+                error!(
+                    "Unable to remove secret {}/{}: {}",
+                    self.options.mount_path, &secret_ref.key.secret_name, e
+                );
+                Err(SecretError::Rotation(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{CircuitBreaker, BREAKER_COOLDOWN, BREAKER_THRESHOLD};
+
+    #[test]
+    fn breaker_opens_after_repeated_failures_until_cooldown() {
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+
+        for _ in 1..BREAKER_THRESHOLD {
+            breaker.record_failure("sealed".to_owned(), now);
+        }
+        assert!(breaker.check(now).is_ok());
+        assert_eq!(breaker.outage().as_deref(), Some("sealed"));
+
+        breaker.record_failure("sealed".to_owned(), now);
+        assert_eq!(breaker.check(now), Err("sealed".to_owned()));
+        assert!(breaker
+            .check(now + BREAKER_COOLDOWN + Duration::from_secs(1))
+            .is_ok());
+
+        breaker.record_success();
+        assert!(breaker.check(now).is_ok());
+        assert_eq!(breaker.outage(), None);
+    }
+}
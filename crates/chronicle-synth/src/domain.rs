@@ -136,12 +136,19 @@ impl ParsedDomainType {
     }
 }
 
+/// A role, written as its name or as its name and the agents that may hold it
 #[derive(Debug, Deserialize)]
-struct Role(String);
+#[serde(untagged)]
+enum Role {
+    Name(String),
+    Held { name: String },
+}
 
 impl Role {
     fn as_str(&self) -> &str {
-        &self.0
+        match self {
+            Role::Name(name) | Role::Held { name } => name,
+        }
     }
 }
 
@@ -150,16 +157,32 @@ enum SynthType {
     String,
     Object,
     Number,
+    Float,
     Bool,
+    DateTime,
+    Decimal,
+    Bytes,
+    /// A list of values of the contained type
+    Array(Box<SynthType>),
 }
 
 impl From<&ChroniclePrimitive> for SynthType {
     fn from(value: &ChroniclePrimitive) -> Self {
-        match value.r#type {
+        let r#type = match value.r#type {
             PrimitiveType::String => SynthType::String,
             PrimitiveType::JSON => SynthType::Object,
             PrimitiveType::Int => SynthType::Number,
             PrimitiveType::Bool => SynthType::Bool,
+            PrimitiveType::DateTime => SynthType::DateTime,
+            PrimitiveType::Float => SynthType::Float,
+            PrimitiveType::Decimal => SynthType::Decimal,
+            PrimitiveType::Bytes => SynthType::Bytes,
+        };
+
+        if value.repeated {
+            SynthType::Array(Box::new(r#type))
+        } else {
+            r#type
         }
     }
 }
@@ -170,6 +193,8 @@ struct ChroniclePrimitive {
     _doc: Option<String>,
     #[serde(rename = "type")]
     r#type: PrimitiveType,
+    #[serde(default)]
+    repeated: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -272,6 +297,79 @@ fn set_attributes(type_name_lower: &str) -> Collection {
     Collection::Operation(Operation::DomainCollection(domain_collection))
 }
 
+fn synth_variant(r#type: &SynthType) -> serde_json::Value {
+    match r#type {
+        SynthType::String => {
+            json!({
+                "type": "string",
+                "faker": {
+                    "generator": "bs_noun"
+                }
+            })
+        }
+        SynthType::Number => {
+            json!({
+                "type": "number",
+                "subtype": "u32"
+            })
+        }
+        SynthType::Float => {
+            json!({
+                "type": "number",
+                "subtype": "f64"
+            })
+        }
+        SynthType::Bool => {
+            json!({
+                "type": "bool",
+                "frequency": 0.5
+            })
+        }
+        SynthType::DateTime => {
+            json!({
+                "type": "date_time",
+                "format": "%Y-%m-%dT%H:%M:%S%:z",
+                "subtype": "date_time"
+            })
+        }
+        // Decimals and bytes are both stored as strings
+        SynthType::Decimal => {
+            json!({
+                "type": "string",
+                "pattern": "[1-9][0-9]{0,5}\\.[0-9]{2}"
+            })
+        }
+        SynthType::Bytes => {
+            json!({
+                "type": "string",
+                "pattern": "[A-Za-z0-9+/]{16}"
+            })
+        }
+        // Arrays hold between one and five values
+        SynthType::Array(content) => {
+            json!({
+                "type": "array",
+                "length": {
+                    "type": "number",
+                    "range": {
+                        "low": 1,
+                        "high": 6,
+                        "step": 1
+                    }
+                },
+                "content": synth_variant(content)
+            })
+        }
+        // Object will be an empty object.
+        // This is something that could be tweaked on a case by case basis given some domain knowledge
+        SynthType::Object => {
+            json!({
+                "type": "object",
+            })
+        }
+    }
+}
+
 fn type_attribute_variants(
     type_name_lower: &str,
     attributes: &BTreeMap<AttributeType, SynthType>,
@@ -281,35 +379,7 @@ fn type_attribute_variants(
     };
 
     for (AttributeType(attribute), r#type) in attributes {
-        let type_attribute_variant = match r#type {
-            SynthType::String => {
-                json!({
-                    "type": "string",
-                    "faker": {
-                        "generator": "bs_noun"
-                    }
-                })
-            }
-            SynthType::Number => {
-                json!({
-                    "type": "number",
-                    "subtype": "u32"
-                })
-            }
-            SynthType::Bool => {
-                json!({
-                    "type": "bool",
-                    "frequency": 0.5
-                })
-            }
-            // Object will be an empty object.
-            // This is something that could be tweaked on a case by case basis given some domain knowledge
-            SynthType::Object => {
-                json!({
-                    "type": "object",
-                })
-            }
-        };
+        let type_attribute_variant = synth_variant(r#type);
 
         type_attribute_variants.insert(attribute.clone(), type_attribute_variant);
     }
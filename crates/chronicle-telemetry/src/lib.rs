@@ -1,3 +1,5 @@
+mod otlp;
 pub mod telemetry;
 
+pub use crate::otlp::{OtlpConfig, OtlpError};
 pub use crate::telemetry::*;
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use opentelemetry::{
+    metrics::MetricsError,
+    runtime,
+    sdk::{
+        trace::{self, Sampler, Tracer},
+        Resource,
+    },
+    trace::TraceError,
+    KeyValue,
+};
+use opentelemetry_otlp::{TonicExporterBuilder, WithExportConfig};
+use thiserror::Error;
+use tonic::metadata::{
+    errors::{InvalidMetadataKey, InvalidMetadataValue},
+    MetadataKey, MetadataMap,
+};
+use url::Url;
+
+#[derive(Error, Debug)]
+pub enum OtlpError {
+    #[error("Invalid OTLP header name: {0}")]
+    HeaderName(#[from] InvalidMetadataKey),
+
+    #[error("Invalid OTLP header value: {0}")]
+    HeaderValue(#[from] InvalidMetadataValue),
+
+    #[error("OTLP trace pipeline: {0}")]
+    Trace(#[from] TraceError),
+
+    #[error("OTLP metrics pipeline: {0}")]
+    Metrics(#[from] MetricsError),
+}
+
+/// Where and how to export traces and metrics over OTLP, for collectors such
+/// as the OpenTelemetry Collector, Jaeger or Tempo
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    /// The collector's OTLP gRPC endpoint
+    pub endpoint: Url,
+    /// Sent with every export, typically to authenticate with the collector
+    pub headers: Vec<(String, String)>,
+    /// The fraction of new traces to sample, from 0.0 to 1.0. Spans in a
+    /// trace propagated from a caller follow the caller's sampling decision.
+    pub sampling_ratio: f64,
+    /// How often metrics are exported
+    pub metrics_interval: Duration,
+}
+
+impl OtlpConfig {
+    pub fn new(endpoint: Url) -> Self {
+        Self {
+            endpoint,
+            headers: vec![],
+            sampling_ratio: 1.0,
+            metrics_interval: Duration::from_secs(60),
+        }
+    }
+
+    fn exporter(&self) -> Result<TonicExporterBuilder, OtlpError> {
+        let mut metadata = MetadataMap::new();
+        for (name, value) in &self.headers {
+            metadata.insert(MetadataKey::from_bytes(name.as_bytes())?, value.parse()?);
+        }
+
+        Ok(opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(self.endpoint.as_str())
+            .with_metadata(metadata))
+    }
+}
+
+fn resource() -> Resource {
+    Resource::new(vec![KeyValue::new("service.name", "chronicle")])
+}
+
+/// Install the global tracer and meter providers, exporting to the configured
+/// collector, and return a tracer for spans recorded through `tracing`
+pub(crate) fn install(config: &OtlpConfig) -> Result<Tracer, OtlpError> {
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(config.exporter()?)
+        .with_trace_config(
+            trace::config()
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                    config.sampling_ratio,
+                ))))
+                .with_resource(resource()),
+        )
+        .install_batch(runtime::Tokio)?;
+
+    let meter_provider = opentelemetry_otlp::new_pipeline()
+        .metrics(runtime::Tokio)
+        .with_exporter(config.exporter()?)
+        .with_resource(resource())
+        .with_period(config.metrics_interval)
+        .build()?;
+
+    opentelemetry::global::set_meter_provider(meter_provider);
+
+    Ok(tracer)
+}
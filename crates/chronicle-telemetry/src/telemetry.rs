@@ -4,6 +4,8 @@ use tracing_log::{log::LevelFilter, LogTracer};
 use tracing_subscriber::{prelude::*, EnvFilter, Registry};
 use url::Url;
 
+use crate::otlp::{self, OtlpConfig, OtlpError};
+
 #[derive(Debug, Clone, Copy)]
 pub enum ConsoleLogging {
     Off,
@@ -40,15 +42,36 @@ macro_rules! apm_layer {
     };
 }
 
+macro_rules! otlp_layer {
+    ( $tracer: expr ) => {
+        $tracer
+            .clone()
+            .map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer))
+    };
+}
+
 pub fn telemetry(collector_endpoint: Option<Url>, console_logging: ConsoleLogging) {
+    telemetry_with_otlp(collector_endpoint, None, console_logging).ok();
+}
+
+/// As [telemetry], additionally exporting traces and metrics over OTLP if
+/// `otlp` is configured
+pub fn telemetry_with_otlp(
+    collector_endpoint: Option<Url>,
+    otlp: Option<OtlpConfig>,
+    console_logging: ConsoleLogging,
+) -> Result<(), OtlpError> {
     LogTracer::init_with_filter(LevelFilter::Trace).ok();
 
+    let tracer = otlp.as_ref().map(otlp::install).transpose()?;
+
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error"));
     match (collector_endpoint, console_logging) {
         (Some(otel), ConsoleLogging::Json) => {
             set_global_default(
                 Registry::default()
                     .with(env_filter)
+                    .with(otlp_layer!(tracer))
                     .with(apm_layer!(otel))
                     .with(stdio_layer!().json()),
             )
@@ -58,6 +81,7 @@ pub fn telemetry(collector_endpoint: Option<Url>, console_logging: ConsoleLoggin
             set_global_default(
                 Registry::default()
                     .with(env_filter)
+                    .with(otlp_layer!(tracer))
                     .with(apm_layer!(otel.as_str()))
                     .with(stdio_layer!().pretty()),
             )
@@ -67,6 +91,7 @@ pub fn telemetry(collector_endpoint: Option<Url>, console_logging: ConsoleLoggin
             set_global_default(
                 Registry::default()
                     .with(env_filter)
+                    .with(otlp_layer!(tracer))
                     .with(apm_layer!(otel.as_str())),
             )
             .ok();
@@ -75,6 +100,7 @@ pub fn telemetry(collector_endpoint: Option<Url>, console_logging: ConsoleLoggin
             set_global_default(
                 Registry::default()
                     .with(env_filter)
+                    .with(otlp_layer!(tracer))
                     .with(stdio_layer!().json()),
             )
             .ok();
@@ -84,6 +110,7 @@ pub fn telemetry(collector_endpoint: Option<Url>, console_logging: ConsoleLoggin
               if #[cfg(feature = "tokio-tracing")] {
                 let layers = Registry::default()
                   .with(env_filter)
+                  .with(otlp_layer!(tracer))
                   .with(stdio_layer!().pretty())
                   .with(console_layer!());
 
@@ -92,11 +119,23 @@ pub fn telemetry(collector_endpoint: Option<Url>, console_logging: ConsoleLoggin
               } else {
                 let layers = Registry::default()
                   .with(env_filter)
+                  .with(otlp_layer!(tracer))
                   .with(stdio_layer!().pretty());
                 set_global_default(layers).ok();
               }
             }
         }
-        _ => (),
+        (None, ConsoleLogging::Off) => {
+            if tracer.is_some() {
+                set_global_default(
+                    Registry::default()
+                        .with(env_filter)
+                        .with(otlp_layer!(tracer)),
+                )
+                .ok();
+            }
+        }
     }
+
+    Ok(())
 }
@@ -0,0 +1,385 @@
+//! The configuration Chronicle runs with, read from command line flags and
+//! their environment variables. Every invalid value is collected, so that all
+//! of them can be reported together with the flag and variable that set each
+//! one, rather than failing on the first.
+
+use std::{
+    fmt,
+    net::{SocketAddr, ToSocketAddrs},
+    str::FromStr,
+};
+
+use chronicle_telemetry::OtlpConfig;
+use clap::{ArgMatches, Command};
+use thiserror::Error;
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub arg: String,
+    pub env: Option<String>,
+    pub expected: String,
+    pub got: Option<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "--{}", self.arg)?;
+        if let Some(env) = &self.env {
+            write!(f, " (or {env})")?;
+        }
+        match &self.got {
+            Some(got) => write!(f, ": expected {}, got {got:?}", self.expected),
+            None => write!(f, ": missing, expected {}", self.expected),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in &self.0 {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for `serve-api`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ServeApiConfig {
+    pub interface: Vec<SocketAddr>,
+    pub liveness_check_interval: Option<u64>,
+    pub checkpoint_interval: Option<u64>,
+    pub expiry_interval: Option<u64>,
+    pub drain_timeout: Option<u64>,
+    pub attribute_history_retention: Option<u64>,
+    pub sync_batch_size: Option<usize>,
+    pub metrics_address: Option<SocketAddr>,
+    pub jwks_uri: Option<Url>,
+    pub userinfo_uri: Option<Url>,
+    pub search_address: Option<Url>,
+    pub graph_mirror_address: Option<Url>,
+    pub event_sink_kafka: Option<Url>,
+    pub event_sink_nats: Option<Url>,
+}
+
+/// Options for `backfill`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BackfillConfig {
+    pub chunk_size: Option<i64>,
+    pub rate: Option<f64>,
+    pub metrics_address: Option<SocketAddr>,
+    pub search_address: Option<Url>,
+    pub graph_mirror_address: Option<Url>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Config {
+    pub instrument: Option<Url>,
+    pub otlp: Option<OtlpConfig>,
+    #[cfg(not(feature = "inmem"))]
+    pub sawtooth: Vec<SocketAddr>,
+    pub serve_api: Option<ServeApiConfig>,
+    pub backfill: Option<BackfillConfig>,
+}
+
+impl Config {
+    /// Read and validate the configuration, `cmd` being the command `matches`
+    /// were parsed by
+    pub(crate) fn from_matches(cmd: &Command, matches: &ArgMatches) -> Result<Self, ConfigErrors> {
+        let mut validator = Validator {
+            cmd,
+            errors: vec![],
+        };
+
+        let config = Config {
+            instrument: validator.parse_url(matches, "instrument"),
+            otlp: otlp(&mut validator, matches),
+            #[cfg(not(feature = "inmem"))]
+            sawtooth: sawtooth(&mut validator, matches),
+            serve_api: matches
+                .subcommand_matches("serve-api")
+                .map(|matches| serve_api(&mut validator, matches)),
+            backfill: matches
+                .subcommand_matches("backfill")
+                .map(|matches| backfill(&mut validator, matches)),
+        };
+
+        if validator.errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigErrors(validator.errors))
+        }
+    }
+
+    pub(crate) fn serve_api(&self) -> ServeApiConfig {
+        self.serve_api.clone().unwrap_or_default()
+    }
+
+    pub(crate) fn backfill(&self) -> BackfillConfig {
+        self.backfill.clone().unwrap_or_default()
+    }
+}
+
+struct Validator<'a> {
+    cmd: &'a Command<'a>,
+    errors: Vec<ConfigError>,
+}
+
+impl Validator<'_> {
+    fn invalid(&mut self, arg: &str, expected: &str, got: Option<&str>) {
+        self.errors.push(ConfigError {
+            arg: arg.to_owned(),
+            env: env_var(self.cmd, arg),
+            expected: expected.to_owned(),
+            got: got.map(ToOwned::to_owned),
+        });
+    }
+
+    fn parse<T: FromStr>(&mut self, matches: &ArgMatches, arg: &str, expected: &str) -> Option<T> {
+        let value = matches.value_of(arg)?;
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            self.invalid(arg, expected, Some(value));
+        }
+        parsed
+    }
+
+    fn parse_url(&mut self, matches: &ArgMatches, arg: &str) -> Option<Url> {
+        self.parse(matches, arg, "a URL")
+    }
+
+    fn parse_socket_addrs(&mut self, arg: &str, value: &str) -> Vec<SocketAddr> {
+        match value.to_socket_addrs() {
+            Ok(addresses) => addresses.collect(),
+            Err(_) => {
+                self.invalid(arg, "a host:port pair, such as 0.0.0.0:9982", Some(value));
+                vec![]
+            }
+        }
+    }
+}
+
+/// The environment variable that sets `arg`, on `cmd` or any subcommand
+fn env_var(cmd: &Command, arg: &str) -> Option<String> {
+    cmd.get_arguments()
+        .find(|candidate| candidate.get_id() == arg)
+        .and_then(|candidate| candidate.get_env())
+        .map(|env| env.to_string_lossy().into_owned())
+        .or_else(|| {
+            cmd.get_subcommands()
+                .find_map(|subcommand| env_var(subcommand, arg))
+        })
+}
+
+fn otlp(validator: &mut Validator, matches: &ArgMatches) -> Option<OtlpConfig> {
+    let endpoint = validator.parse_url(matches, "otlp-endpoint");
+
+    let mut headers = vec![];
+    for header in matches
+        .get_many::<String>("otlp-header")
+        .into_iter()
+        .flatten()
+    {
+        match header.split_once('=') {
+            Some((name, value)) => headers.push((name.trim().to_owned(), value.trim().to_owned())),
+            None => validator.invalid("otlp-header", "name=value", Some(header)),
+        }
+    }
+
+    let sampling_ratio = matches.value_of("otlp-sampling-ratio").and_then(|ratio| {
+        let parsed = ratio
+            .parse::<f64>()
+            .ok()
+            .filter(|ratio| (0.0..=1.0).contains(ratio));
+        if parsed.is_none() {
+            validator.invalid(
+                "otlp-sampling-ratio",
+                "a number from 0.0 to 1.0",
+                Some(ratio),
+            );
+        }
+        parsed
+    });
+
+    endpoint.map(|endpoint| {
+        let mut otlp = OtlpConfig::new(endpoint);
+        otlp.headers = headers;
+        if let Some(sampling_ratio) = sampling_ratio {
+            otlp.sampling_ratio = sampling_ratio;
+        }
+        otlp
+    })
+}
+
+#[cfg(not(feature = "inmem"))]
+fn sawtooth(validator: &mut Validator, matches: &ArgMatches) -> Vec<SocketAddr> {
+    const EXPECTED: &str = "a validator URL, such as tcp://validator:4004";
+
+    let addresses = match matches.get_many::<String>("sawtooth") {
+        Some(addresses) => addresses,
+        None => {
+            validator.invalid("sawtooth", EXPECTED, None);
+            return vec![];
+        }
+    };
+
+    let mut socket_addrs = vec![];
+    for address in addresses {
+        match Url::parse(address).map(|url| url.socket_addrs(|| Some(4004))) {
+            Ok(Ok(addresses)) => socket_addrs.extend(addresses),
+            _ => validator.invalid("sawtooth", EXPECTED, Some(address)),
+        }
+    }
+
+    socket_addrs
+}
+
+fn serve_api(validator: &mut Validator, matches: &ArgMatches) -> ServeApiConfig {
+    const SECONDS: &str = "a number of seconds";
+
+    let interface = matches
+        .get_many::<String>("interface")
+        .into_iter()
+        .flatten()
+        .flat_map(|interface| validator.parse_socket_addrs("interface", interface))
+        .collect();
+
+    let metrics_address = matches.value_of("metrics-address").and_then(|address| {
+        validator
+            .parse_socket_addrs("metrics-address", address)
+            .into_iter()
+            .next()
+    });
+
+    ServeApiConfig {
+        interface,
+        liveness_check_interval: validator.parse(matches, "liveness-check", SECONDS),
+        checkpoint_interval: validator.parse(matches, "checkpoint-interval", SECONDS),
+        expiry_interval: validator.parse(matches, "expiry-interval", SECONDS),
+        drain_timeout: validator.parse(matches, "drain-timeout", SECONDS),
+        attribute_history_retention: validator.parse(
+            matches,
+            "attribute-history-retention",
+            SECONDS,
+        ),
+        sync_batch_size: validator.parse(matches, "sync-batch-size", "a number of commits"),
+        metrics_address,
+        jwks_uri: validator.parse_url(matches, "jwks-address"),
+        userinfo_uri: validator.parse_url(matches, "userinfo-address"),
+        search_address: validator.parse_url(matches, "search-address"),
+        graph_mirror_address: validator.parse_url(matches, "graph-mirror-address"),
+        event_sink_kafka: validator.parse_url(matches, "event-sink-kafka"),
+        event_sink_nats: validator.parse_url(matches, "event-sink-nats"),
+    }
+}
+
+fn backfill(validator: &mut Validator, matches: &ArgMatches) -> BackfillConfig {
+    let metrics_address = matches.value_of("metrics-address").and_then(|address| {
+        validator
+            .parse_socket_addrs("metrics-address", address)
+            .into_iter()
+            .next()
+    });
+
+    // The address of the store being backfilled is required
+    let required = match matches.value_of("task") {
+        Some("graph-mirror") => "graph-mirror-address",
+        _ => "search-address",
+    };
+    if matches.value_of(required).is_none() {
+        validator.invalid(required, "a URL", None);
+    }
+
+    BackfillConfig {
+        chunk_size: validator.parse(matches, "chunk-size", "a number of records"),
+        rate: validator.parse(matches, "rate", "a number of records per second"),
+        metrics_address,
+        search_address: validator.parse_url(matches, "search-address"),
+        graph_mirror_address: validator.parse_url(matches, "graph-mirror-address"),
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "inmem"))]
+mod test {
+    use crate::{
+        bootstrap::{cli, SubCommand},
+        codegen::ChronicleDomainDef,
+    };
+
+    use super::Config;
+
+    #[test]
+    fn reports_every_invalid_value() {
+        let cmd = cli(ChronicleDomainDef::build("test").build()).as_cmd();
+        let matches = cmd.clone().get_matches_from(
+            "chronicle --sawtooth tcp://localhost:4004 --otlp-endpoint collector \
+             --otlp-sampling-ratio 2 serve-api --checkpoint-interval hourly \
+             --metrics-address nowhere"
+                .split_whitespace(),
+        );
+
+        let errors = Config::from_matches(&cmd, &matches).unwrap_err();
+        let args: Vec<_> = errors.0.iter().map(|error| error.arg.as_str()).collect();
+
+        assert_eq!(
+            args,
+            [
+                "otlp-endpoint",
+                "otlp-sampling-ratio",
+                "checkpoint-interval",
+                "metrics-address"
+            ]
+        );
+        assert_eq!(
+            errors.0[2].to_string(),
+            r#"--checkpoint-interval (or CHECKPOINT_INTERVAL): expected a number of seconds, got "hourly""#
+        );
+    }
+
+    #[test]
+    fn valid_configuration() {
+        let cmd = cli(ChronicleDomainDef::build("test").build()).as_cmd();
+        let matches = cmd.clone().get_matches_from(
+            "chronicle --sawtooth tcp://localhost:4004 serve-api --interface 127.0.0.1:9982 \
+             --checkpoint-interval 60 --sync-batch-size 250"
+                .split_whitespace(),
+        );
+
+        let config = Config::from_matches(&cmd, &matches).unwrap();
+        let serve_api = config.serve_api();
+
+        assert_eq!(serve_api.interface, vec!["127.0.0.1:9982".parse().unwrap()]);
+        assert_eq!(serve_api.checkpoint_interval, Some(60));
+        assert_eq!(serve_api.sync_batch_size, Some(250));
+        assert!(config.otlp.is_none());
+    }
+
+    #[test]
+    fn backfill_requires_the_address_of_its_task() {
+        let cmd = cli(ChronicleDomainDef::build("test").build()).as_cmd();
+        let matches = cmd.clone().get_matches_from(
+            "chronicle --sawtooth tcp://localhost:4004 backfill graph-mirror \
+             --search-address http://opensearch:9200 --rate 50"
+                .split_whitespace(),
+        );
+
+        let errors = Config::from_matches(&cmd, &matches).unwrap_err();
+        let args: Vec<_> = errors.0.iter().map(|error| error.arg.as_str()).collect();
+        assert_eq!(args, ["graph-mirror-address"]);
+
+        let matches = cmd.clone().get_matches_from(
+            "chronicle --sawtooth tcp://localhost:4004 backfill search \
+             --search-address http://opensearch:9200 --rate 50"
+                .split_whitespace(),
+        );
+
+        let backfill = Config::from_matches(&cmd, &matches).unwrap().backfill();
+        assert_eq!(backfill.rate, Some(50.0));
+        assert_eq!(backfill.chunk_size, Some(500));
+    }
+}
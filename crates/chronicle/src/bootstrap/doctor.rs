@@ -0,0 +1,299 @@
+//! Pre-flight checks of the environment Chronicle is configured to run in,
+//! for `chronicle doctor`. Each check that fails suggests a fix.
+
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use chronicle_signing::{BatcherKnownKeyNamesSigner, ChronicleKnownKeyNamesSigner};
+use clap::{ArgMatches, Command};
+use diesel::Connection;
+
+use api::DatabaseConnection;
+
+use super::config::Config;
+
+/// How long to wait for a ledger endpoint to accept a connection
+#[cfg(not(feature = "inmem"))]
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// The largest difference from the database server's clock that is not
+/// reported
+#[cfg(not(feature = "sqlite"))]
+const MAX_CLOCK_SKEW: std::time::Duration = std::time::Duration::from_secs(5);
+
+enum Outcome {
+    Pass(String),
+    Warn { detail: String, fix: String },
+    Fail { detail: String, fix: String },
+}
+
+struct Check {
+    name: &'static str,
+    outcome: Outcome,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Pass(detail.into()),
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Warn {
+                detail: detail.into(),
+                fix: fix.into(),
+            },
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Fail {
+                detail: detail.into(),
+                fix: fix.into(),
+            },
+        }
+    }
+}
+
+/// Run every check, printing the outcomes, and return the number that failed
+pub(crate) async fn doctor(cmd: &Command<'_>, matches: &ArgMatches, doctor: &ArgMatches) -> usize {
+    let config = Config::from_matches(cmd, matches);
+
+    let mut checks = configuration(&config);
+    checks.extend(database(matches));
+    #[cfg(not(feature = "inmem"))]
+    if let Ok(config) = &config {
+        checks.extend(ledger(config));
+    }
+    checks.push(secrets(matches).await);
+    checks.extend(ports(doctor));
+
+    let mut failed = 0;
+    for check in checks {
+        match check.outcome {
+            Outcome::Pass(detail) => println!("[ ok ] {}: {detail}", check.name),
+            Outcome::Warn { detail, fix } => {
+                println!("[warn] {}: {detail}", check.name);
+                println!("       fix: {fix}");
+            }
+            Outcome::Fail { detail, fix } => {
+                failed += 1;
+                println!("[fail] {}: {detail}", check.name);
+                println!("       fix: {fix}");
+            }
+        }
+    }
+
+    failed
+}
+
+fn configuration(config: &Result<Config, super::config::ConfigErrors>) -> Vec<Check> {
+    match config {
+        Ok(_) => vec![Check::pass("Configuration", "all options are valid")],
+        Err(errors) => errors
+            .0
+            .iter()
+            .map(|error| {
+                Check::fail(
+                    "Configuration",
+                    error.to_string(),
+                    format!("Set --{} to {}", error.arg, error.expected),
+                )
+            })
+            .collect(),
+    }
+}
+
+fn migrations(connection: &mut DatabaseConnection) -> Check {
+    match api::pending_migrations(connection) {
+        Ok(pending) if pending.is_empty() => Check::pass("Migrations", "schema is up to date"),
+        Ok(pending) => Check::warn(
+            "Migrations",
+            format!(
+                "{} migration(s) pending: {}",
+                pending.len(),
+                pending.join(", ")
+            ),
+            "Chronicle applies pending migrations when it starts, back up the database first",
+        ),
+        Err(e) => Check::fail(
+            "Migrations",
+            format!("cannot read migration status: {e}"),
+            "Grant the database user access to the __diesel_schema_migrations table",
+        ),
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn database(matches: &ArgMatches) -> Vec<Check> {
+    use chrono::{DateTime, Utc};
+    use diesel::{dsl::sql, sql_types::Timestamptz, RunQueryDsl};
+
+    let server = format!(
+        "{}:{}/{}",
+        matches.value_of("database-host").unwrap_or_default(),
+        matches.value_of("database-port").unwrap_or_default(),
+        matches.value_of("database-name").unwrap_or_default()
+    );
+
+    let mut connection = match DatabaseConnection::establish(&super::construct_db_uri(matches)) {
+        Ok(connection) => connection,
+        Err(e) => {
+            return vec![Check::fail(
+                "Database",
+                format!("cannot connect to {server}: {e}"),
+                "Check --database-host, --database-port, --database-name and \
+                 --database-username, and set PGPASSWORD if the server requires a password",
+            )]
+        }
+    };
+
+    let mut checks = vec![
+        Check::pass("Database", format!("connected to {server}")),
+        migrations(&mut connection),
+    ];
+
+    checks.push(
+        match diesel::select(sql::<Timestamptz>("now()"))
+            .get_result::<DateTime<Utc>>(&mut connection)
+        {
+            Ok(server_time) => {
+                let skew = (Utc::now() - server_time).num_milliseconds().unsigned_abs();
+                if skew > MAX_CLOCK_SKEW.as_millis() as u64 {
+                    Check::warn(
+                        "Clock",
+                        format!("local clock differs from the database server's by {skew}ms"),
+                        "Synchronize both hosts with NTP, as Chronicle timestamps activities \
+                         and synchronization with the local clock",
+                    )
+                } else {
+                    Check::pass("Clock", format!("within {skew}ms of the database server"))
+                }
+            }
+            Err(e) => Check::warn(
+                "Clock",
+                format!("cannot read the database server's clock: {e}"),
+                "Check that the database user may run queries",
+            ),
+        },
+    );
+
+    checks
+}
+
+#[cfg(feature = "sqlite")]
+fn database(matches: &ArgMatches) -> Vec<Check> {
+    let path = matches.value_of("database-path").unwrap_or_default();
+
+    match DatabaseConnection::establish(path) {
+        Ok(mut connection) => vec![
+            Check::pass("Database", format!("opened {path}")),
+            migrations(&mut connection),
+        ],
+        Err(e) => vec![Check::fail(
+            "Database",
+            format!("cannot open {path}: {e}"),
+            "Set --database-path to a file in a writable directory",
+        )],
+    }
+}
+
+#[cfg(not(feature = "inmem"))]
+fn ledger(config: &Config) -> Vec<Check> {
+    use chronicle_protocol::address::{FAMILY, VERSION};
+
+    let mut checks = vec![];
+    for address in &config.sawtooth {
+        let connection = TcpStream::connect_timeout(address, CONNECT_TIMEOUT);
+
+        checks.push(match connection {
+            Ok(_) => Check::pass(
+                "Ledger",
+                format!(
+                    "validator {address} is reachable, transaction processors must support \
+                     family {FAMILY} version {VERSION}"
+                ),
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => Check::fail(
+                "Ledger",
+                format!(
+                    "validator {address} did not accept a connection within {}s",
+                    CONNECT_TIMEOUT.as_secs()
+                ),
+                "Check firewall rules and network policies between this host and the validator",
+            ),
+            Err(e) => Check::fail(
+                "Ledger",
+                format!("cannot connect to validator {address}: {e}"),
+                "Check that the validator is running and that its component endpoint is \
+                 reachable from this host",
+            ),
+        });
+    }
+
+    checks
+}
+
+async fn secrets(matches: &ArgMatches) -> Check {
+    let signing = match super::chronicle_signing(matches).await {
+        Ok(signing) => signing,
+        Err(e) => {
+            return Check::fail(
+                "Secrets",
+                format!("cannot configure key storage: {e}"),
+                "Check the --chronicle-key-* and --batcher-key-* options, and for Vault its \
+                 address, token and mount path",
+            )
+        }
+    };
+
+    match (
+        signing.chronicle_verifying().await,
+        signing.batcher_verifying().await,
+    ) {
+        (Ok(_), Ok(_)) => Check::pass("Secrets", "chronicle and batcher keys resolved"),
+        (Err(e), _) | (_, Err(e)) => Check::fail(
+            "Secrets",
+            format!("cannot resolve signing keys: {e}"),
+            "Check that the keys exist at the configured path or Vault mount, or run \
+             `chronicle generate-key` to create one",
+        ),
+    }
+}
+
+fn ports(doctor: &ArgMatches) -> Vec<Check> {
+    let mut checks = vec![];
+
+    for interface in doctor.get_many::<String>("interface").into_iter().flatten() {
+        let addresses = match interface.to_socket_addrs() {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                checks.push(Check::fail(
+                    "Ports",
+                    format!("cannot resolve {interface}: {e}"),
+                    "Set --interface to a host:port pair, such as 0.0.0.0:9982",
+                ));
+                continue;
+            }
+        };
+
+        for address in addresses {
+            checks.push(match TcpListener::bind(address) {
+                Ok(_) => Check::pass("Ports", format!("{address} is available")),
+                Err(e) => Check::fail(
+                    "Ports",
+                    format!("cannot listen on {address}: {e}"),
+                    "Stop the process using the port, or serve the API on another with \
+                     serve-api --interface",
+                ),
+            });
+        }
+    }
+
+    checks
+}
@@ -1,13 +1,41 @@
 mod cli;
+mod config;
+mod doctor;
 mod opa;
 
 #[cfg(feature = "inmem")]
 use api::inmem::EmbeddedChronicleTp;
 use api::{
-    chronicle_graphql::{ChronicleApiServer, ChronicleGraphQl, JwksUri, SecurityConf, UserInfoUri},
-    Api, ApiDispatch, ApiError, StoreError, UuidGen,
+    backfill::{BackfillConf, BackfillTask},
+    chronicle_graphql::{
+        explain::ExplainTemplates,
+        export::ExportConf,
+        federation::{FederatedKind, FederationConf},
+        limits::QueryLimits,
+        persisted::PersistedQueries,
+        playground::{PlaygroundConf, PlaygroundExample},
+        query_cache::QueryCache,
+        rate_limits::RateLimits,
+        rest::{AttributeType, DomainTypes},
+        roles::RolePermissions,
+        search::SearchConf,
+        server_info::ServerInfo,
+        ChronicleApiServer, ChronicleGraphQl, JwksUri, SecurityConf, ServerOptions, UserInfoUri,
+    },
+    encryption::PayloadEncryption,
+    enrichment::OperationEnrichment,
+    event_sink::{self, EventSinkConf, EventSinkError, EventSinkFormat, EventSinkTarget},
+    expiry::{self, ExpiryConf},
+    graph_mirror::{self, GraphMirrorConf},
+    id_strategy::IdStrategies,
+    policy_watcher::{self, PolicyWatcherConf},
+    pseudonym::NamespacePseudonyms,
+    validation::AttributeValidation,
+    webhooks::{self, WebhookConf},
+    Api, ApiConfig, ApiDispatch, ApiError, DatabaseConnection, StoreError, UuidGen,
 };
 use async_graphql::{async_trait, ObjectType};
+use chronicle_protocol::messages::estimate_submission;
 #[cfg(not(feature = "inmem"))]
 use chronicle_protocol::{
     address::{FAMILY, VERSION},
@@ -20,62 +48,54 @@ use chronicle_signing::{
 use clap::{ArgMatches, Command};
 use clap_complete::{generate, Generator, Shell};
 pub use cli::*;
+#[cfg(not(feature = "sqlite"))]
+use common::database::{get_connection_with_retry, DatabaseConnector};
 use common::{
-    commands::ApiResponse,
-    database::{get_connection_with_retry, DatabaseConnector},
-    identity::AuthId,
+    commands::{ApiCommand, ApiResponse, OutboxCommand, RotateKeyCommand, VerifyCommand},
+    identity::{AgentClaim, AuthId},
     import::{load_bytes_from_stdin, load_bytes_from_url},
     k256::{
         pkcs8::{EncodePrivateKey, LineEnding},
         SecretKey,
     },
     ledger::SubmissionStage,
-    opa::ExecutorContext,
-    prov::{operations::ChronicleOperation, to_json_ld::ToJson, NamespaceId},
+    opa::{DecisionLog, DecisionLogSink, ExecutorContext},
+    prov::{
+        operations::ChronicleOperation, to_json_ld::ToJson, DomaintypeId, ExternalId, NamespaceId,
+        ProvModel, RoleConstraints,
+    },
 };
 use rand::rngs::StdRng;
 use rand_core::SeedableRng;
 use std::io::IsTerminal;
-use tracing::{debug, error, info, instrument, warn};
+use tracing::{debug, error, info, instrument};
 use user_error::UFE;
 
-use diesel::{
-    r2d2::{ConnectionManager, Pool},
-    PgConnection,
-};
+use diesel::r2d2::{ConnectionManager, Pool};
 
 use chronicle_telemetry::{self, ConsoleLogging};
 use url::Url;
 
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     fs::File,
     io::{self, Write},
-    net::{SocketAddr, ToSocketAddrs},
+    net::SocketAddr,
     path::PathBuf,
-    str::FromStr,
 };
 
-use crate::codegen::ChronicleDomainDef;
+use crate::codegen::{
+    evolution::{self, DomainChange},
+    examples, linter, AttributeDef, ChronicleDomainDef,
+};
 
-use self::opa::opa_executor_from_embedded_policy;
-
-#[cfg(not(feature = "inmem"))]
-fn sawtooth_address(options: &ArgMatches) -> Result<Vec<SocketAddr>, CliError> {
-    Ok(options
-        .value_of("sawtooth")
-        .map(str::to_string)
-        .ok_or(CliError::MissingArgument {
-            arg: "sawtooth".to_owned(),
-        })
-        .and_then(|s| Url::parse(&s).map_err(CliError::from))
-        .map(|u| u.socket_addrs(|| Some(4004)))
-        .map_err(CliError::from)??)
-}
+use self::{config::Config, opa::opa_executor_from_embedded_policy};
 
 #[allow(dead_code)]
 #[cfg(not(feature = "inmem"))]
-fn ledger(options: &ArgMatches) -> Result<ChronicleLedger, CliError> {
+/// A client of the configured validators, which selects the one with the
+/// highest block
+fn ledger(config: &Config) -> Result<ChronicleLedger, CliError> {
     use async_stl_client::zmq_client::{
         HighestBlockValidatorSelector, ZmqRequestResponseSawtoothChannel,
     };
@@ -83,7 +103,7 @@ fn ledger(options: &ArgMatches) -> Result<ChronicleLedger, CliError> {
     Ok(ChronicleLedger::new(
         ZmqRequestResponseSawtoothChannel::new(
             "inmem",
-            &sawtooth_address(options)?,
+            &config.sawtooth,
             HighestBlockValidatorSelector,
         )?
         .retrying(),
@@ -110,20 +130,22 @@ struct UniqueUuid;
 
 impl UuidGen for UniqueUuid {}
 
-type ConnectionPool = Pool<ConnectionManager<PgConnection>>;
+type ConnectionPool = Pool<ConnectionManager<DatabaseConnection>>;
 
+#[cfg(not(feature = "sqlite"))]
 struct RemoteDatabaseConnector {
     db_uri: String,
 }
 
+#[cfg(not(feature = "sqlite"))]
 #[async_trait::async_trait]
 impl DatabaseConnector<(), StoreError> for RemoteDatabaseConnector {
-    async fn try_connect(&self) -> Result<((), Pool<ConnectionManager<PgConnection>>), StoreError> {
+    async fn try_connect(&self) -> Result<((), ConnectionPool), StoreError> {
         use diesel::Connection;
-        PgConnection::establish(&self.db_uri)?;
+        DatabaseConnection::establish(&self.db_uri)?;
         Ok((
             (),
-            Pool::builder().build(ConnectionManager::<PgConnection>::new(&self.db_uri))?,
+            Pool::builder().build(ConnectionManager::<DatabaseConnection>::new(&self.db_uri))?,
         ))
     }
 
@@ -135,6 +157,7 @@ impl DatabaseConnector<(), StoreError> for RemoteDatabaseConnector {
     }
 }
 
+#[cfg(not(feature = "sqlite"))]
 #[instrument(skip(db_uri))] //Do not log db_uri, as it can contain passwords
 async fn pool_remote(db_uri: impl ToString) -> Result<ConnectionPool, ApiError> {
     let (_, pool) = get_connection_with_retry(RemoteDatabaseConnector {
@@ -144,14 +167,20 @@ async fn pool_remote(db_uri: impl ToString) -> Result<ConnectionPool, ApiError>
     Ok(pool)
 }
 
+#[cfg(feature = "sqlite")]
+#[instrument]
+async fn pool_embedded(path: &str) -> Result<ConnectionPool, ApiError> {
+    Ok(api::sqlite_pool(path, api::ConnectionOptions::default())?)
+}
+
 pub async fn api_server<Query, Mutation>(
     api: &ApiDispatch,
     pool: &ConnectionPool,
+    read_pool: Option<ConnectionPool>,
     gql: ChronicleGraphQl<Query, Mutation>,
     interface: Option<Vec<SocketAddr>>,
     security_conf: SecurityConf,
-    serve_graphql: bool,
-    serve_data: bool,
+    options: ServerOptions,
 ) -> Result<(), ApiError>
 where
     Query: ObjectType + Copy,
@@ -160,11 +189,11 @@ where
     if let Some(addresses) = interface {
         gql.serve_api(
             pool.clone(),
+            read_pool,
             api.clone(),
             addresses,
             security_conf,
-            serve_graphql,
-            serve_data,
+            options,
         )
         .await?
     }
@@ -189,6 +218,21 @@ fn namespace_bindings(options: &ArgMatches) -> Vec<NamespaceId> {
         .unwrap_or_default()
 }
 
+/// The seed from which implicitly created namespaces derive their UUIDs, if
+/// they are not to be random
+fn namespace_seed(options: &ArgMatches) -> Result<Option<uuid::Uuid>, CliError> {
+    options
+        .get_one::<String>("namespace-seed")
+        .map(|seed| {
+            uuid::Uuid::parse_str(seed).map_err(|_| CliError::InvalidArgument {
+                arg: "namespace-seed".to_owned(),
+                expected: "a UUID".to_owned(),
+                got: seed.to_owned(),
+            })
+        })
+        .transpose()
+}
+
 fn vault_secrets_options(options: &ArgMatches) -> Result<ChronicleSecretsOptions, CliError> {
     let vault_url = options
         .value_of("vault-url")
@@ -206,28 +250,62 @@ fn vault_secrets_options(options: &ArgMatches) -> Result<ChronicleSecretsOptions
     ))
 }
 
+// Keys held in a key management service, for the key named by `key`
+#[allow(unused_variables)]
+async fn kms_secrets_options(
+    options: &ArgMatches,
+    key: &str,
+) -> Result<Option<ChronicleSecretsOptions>, CliError> {
+    #[cfg(feature = "aws-kms")]
+    if let Some(key_id) = options.value_of(&format!("{key}-key-from-aws-kms")) {
+        return Ok(Some(ChronicleSecretsOptions::signed_by_kms(
+            chronicle_signing::AwsKmsSigner::new(key_id, options.value_of("aws-kms-region")).await,
+        )));
+    }
+
+    #[cfg(feature = "gcp-kms")]
+    if let Some(key_version) = options.value_of(&format!("{key}-key-from-gcp-kms")) {
+        let access_token = options
+            .value_of("gcp-access-token")
+            .ok_or_else(|| CliError::missing_argument("gcp-access-token"))?;
+        return Ok(Some(ChronicleSecretsOptions::signed_by_kms(
+            chronicle_signing::GcpKmsSigner::new(key_version, access_token),
+        )));
+    }
+
+    Ok(None)
+}
+
 async fn chronicle_signing(options: &ArgMatches) -> Result<ChronicleSigning, CliError> {
     // Determine batcher configuration
-    let batcher_options = match (
-        options.get_one::<PathBuf>("batcher-key-from-path"),
-        options.get_flag("batcher-key-from-vault"),
-        options.get_flag("batcher-key-generated"),
-    ) {
-        (Some(path), _, _) => ChronicleSecretsOptions::stored_at_path(path),
-        (_, true, _) => vault_secrets_options(options)?,
-        (_, _, true) => ChronicleSecretsOptions::generate_in_memory(),
-        _ => unreachable!("CLI should always set batcher key"),
+    let batcher_options = if let Some(kms) = kms_secrets_options(options, "batcher").await? {
+        kms
+    } else {
+        match (
+            options.get_one::<PathBuf>("batcher-key-from-path"),
+            options.get_flag("batcher-key-from-vault"),
+            options.get_flag("batcher-key-generated"),
+        ) {
+            (Some(path), _, _) => ChronicleSecretsOptions::stored_at_path(path),
+            (_, true, _) => vault_secrets_options(options)?,
+            (_, _, true) => ChronicleSecretsOptions::generate_in_memory(),
+            _ => unreachable!("CLI should always set batcher key"),
+        }
     };
 
-    let chronicle_options = match (
-        options.get_one::<PathBuf>("chronicle-key-from-path"),
-        options.get_flag("chronicle-key-from-vault"),
-        options.get_flag("chronicle-key-generated"),
-    ) {
-        (Some(path), _, _) => ChronicleSecretsOptions::stored_at_path(path),
-        (_, true, _) => vault_secrets_options(options)?,
-        (_, _, true) => ChronicleSecretsOptions::generate_in_memory(),
-        _ => unreachable!("CLI should always set chronicle key"),
+    let chronicle_options = if let Some(kms) = kms_secrets_options(options, "chronicle").await? {
+        kms
+    } else {
+        match (
+            options.get_one::<PathBuf>("chronicle-key-from-path"),
+            options.get_flag("chronicle-key-from-vault"),
+            options.get_flag("chronicle-key-generated"),
+        ) {
+            (Some(path), _, _) => ChronicleSecretsOptions::stored_at_path(path),
+            (_, true, _) => vault_secrets_options(options)?,
+            (_, _, true) => ChronicleSecretsOptions::generate_in_memory(),
+            _ => unreachable!("CLI should always set chronicle key"),
+        }
     };
 
     Ok(ChronicleSigning::new(
@@ -244,19 +322,28 @@ async fn chronicle_signing(options: &ArgMatches) -> Result<ChronicleSigning, Cli
 pub async fn api(
     pool: &ConnectionPool,
     options: &ArgMatches,
-    policy_name: Option<String>,
-    liveness_check_interval: Option<u64>,
+    signing: ChronicleSigning,
+    config: &Config,
+    api_config: ApiConfig,
 ) -> Result<ApiDispatch, CliError> {
-    let ledger = ledger(options)?;
+    let ledger = ledger(config)?;
 
     Ok(Api::new(
         pool.clone(),
         ledger,
         UniqueUuid,
-        chronicle_signing(options).await?,
-        namespace_bindings(options),
-        policy_name,
-        liveness_check_interval,
+        signing,
+        ApiConfig {
+            namespace_bindings: namespace_bindings(options),
+            namespace_seed: namespace_seed(options)?,
+            liveness_check_interval: config.serve_api().liveness_check_interval,
+            checkpoint_interval: config.serve_api().checkpoint_interval,
+            attribute_history_retention: config.serve_api().attribute_history_retention,
+            sync_batch_size: config.serve_api().sync_batch_size,
+            store_and_forward: options.is_present("store-and-forward"),
+            register_namespaces: options.is_present("register-namespaces"),
+            ..api_config
+        },
     )
     .await?)
 }
@@ -265,8 +352,9 @@ pub async fn api(
 pub async fn api(
     pool: &ConnectionPool,
     options: &ArgMatches,
-    remote_opa: Option<String>,
-    liveness_check_interval: Option<u64>,
+    signing: ChronicleSigning,
+    config: &Config,
+    api_config: ApiConfig,
 ) -> Result<api::ApiDispatch, CliError> {
     let embedded_tp = in_mem_ledger(options)?;
 
@@ -274,15 +362,36 @@ pub async fn api(
         pool.clone(),
         embedded_tp.ledger,
         UniqueUuid,
-        chronicle_signing(options).await?,
-        vec![],
-        remote_opa,
-        liveness_check_interval,
+        signing,
+        ApiConfig {
+            namespace_bindings: vec![],
+            namespace_seed: namespace_seed(options)?,
+            liveness_check_interval: config.serve_api().liveness_check_interval,
+            checkpoint_interval: config.serve_api().checkpoint_interval,
+            attribute_history_retention: config.serve_api().attribute_history_retention,
+            sync_batch_size: config.serve_api().sync_batch_size,
+            store_and_forward: options.is_present("store-and-forward"),
+            register_namespaces: options.is_present("register-namespaces"),
+            ..api_config
+        },
     )
     .await?)
 }
 
+#[cfg(not(feature = "sqlite"))]
 fn construct_db_uri(matches: &ArgMatches) -> String {
+    construct_db_uri_for_host(
+        matches,
+        matches
+            .value_of("database-host")
+            .expect("CLI should always set database host"),
+    )
+}
+
+/// The uri of the database on `host`, with the port, user and name configured
+/// for the primary
+#[cfg(not(feature = "sqlite"))]
+fn construct_db_uri_for_host(matches: &ArgMatches, host: &str) -> String {
     fn encode(string: &str) -> String {
         use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
         utf8_percent_encode(string, NON_ALPHANUMERIC).to_string()
@@ -307,11 +416,7 @@ fn construct_db_uri(matches: &ArgMatches) -> String {
                 .expect("CLI should always set database user")
         ),
         password,
-        encode(
-            matches
-                .value_of("database-host")
-                .expect("CLI should always set database host")
-        ),
+        encode(host),
         encode(
             matches
                 .value_of("database-port")
@@ -341,6 +446,21 @@ impl ConfiguredOpa {
         }
     }
 
+    /// Log the decisions of the configured policy to `decision_log`
+    pub fn with_decision_log(self, decision_log: DecisionLog) -> Self {
+        match self {
+            ConfiguredOpa::Embedded(context) => {
+                ConfiguredOpa::Embedded(context.with_decision_log(decision_log))
+            }
+            ConfiguredOpa::Remote(context, settings) => {
+                ConfiguredOpa::Remote(context.with_decision_log(decision_log), settings)
+            }
+            ConfiguredOpa::Url(context) => {
+                ConfiguredOpa::Url(context.with_decision_log(decision_log))
+            }
+        }
+    }
+
     pub fn remote_settings(&self) -> Option<String> {
         match self {
             ConfiguredOpa::Embedded(_) => None,
@@ -355,7 +475,7 @@ impl ConfiguredOpa {
 /// then always use embedded policy
 #[cfg(feature = "inmem")]
 #[allow(unused_variables)]
-async fn configure_opa(options: &ArgMatches) -> Result<ConfiguredOpa, CliError> {
+async fn configure_opa(options: &ArgMatches, config: &Config) -> Result<ConfiguredOpa, CliError> {
     let (default_policy_name, entrypoint) =
         ("allow_transactions", "allow_transactions.allowed_users");
     let opa = opa_executor_from_embedded_policy(default_policy_name, entrypoint).await?;
@@ -363,8 +483,8 @@ async fn configure_opa(options: &ArgMatches) -> Result<ConfiguredOpa, CliError>
 }
 
 #[cfg(not(feature = "inmem"))]
-#[instrument(skip(options))]
-async fn configure_opa(options: &ArgMatches) -> Result<ConfiguredOpa, CliError> {
+#[instrument(skip(options, config))]
+async fn configure_opa(options: &ArgMatches, config: &Config) -> Result<ConfiguredOpa, CliError> {
     if options.is_present("embedded-opa-policy") {
         let (default_policy_name, entrypoint) =
             ("allow_transactions", "allow_transactions.allowed_users");
@@ -384,86 +504,246 @@ async fn configure_opa(options: &ArgMatches) -> Result<ConfiguredOpa, CliError>
         Ok(ConfiguredOpa::Url(opa))
     } else {
         let (opa, settings) =
-            self::opa::opa_executor_from_sawtooth_settings(&sawtooth_address(options)?).await?;
+            self::opa::opa_executor_from_sawtooth_settings(&config.sawtooth).await?;
         tracing::info!(use_on_chain_opa= ?settings, "Chronicle operating in secure mode with on chain OPA policy");
 
         Ok(ConfiguredOpa::Remote(opa, settings))
     }
 }
 
-/// If `--liveness-check` is set, we use either the interval in seconds provided or the default of 1800.
-/// Otherwise, we use `None` to disable the depth charge.
-fn configure_depth_charge(matches: &ArgMatches) -> Option<u64> {
-    if let Some(serve_api_matches) = matches.subcommand_matches("serve-api") {
-        if let Some(interval) = serve_api_matches.value_of("liveness-check") {
-            let parsed_interval = interval.parse::<u64>().unwrap_or_else(|e| {
-                warn!("Failed to parse '--liveness-check' value: {e}");
-                1800
+/// TTLs of the entity types the domain definition gives one, keyed by both the
+/// name the command line records entities under and the one GraphQL uses
+fn configure_expiry(domain: &ChronicleDomainDef) -> BTreeMap<String, std::time::Duration> {
+    let mut ttls = BTreeMap::new();
+
+    for entity in &domain.entities {
+        if let Some(ttl) = entity.ttl() {
+            ttls.insert(entity.external_id.clone(), ttl);
+            ttls.insert(entity.as_type_name(), ttl);
+        }
+    }
+
+    ttls
+}
+
+/// Id strategies of the types the domain definition gives one, keyed by both
+/// the name the command line records types under and the one GraphQL uses
+fn configure_id_strategies(domain: &ChronicleDomainDef) -> IdStrategies {
+    let mut strategies = IdStrategies::new();
+
+    let agents = domain.agents.iter().map(|agent| {
+        (
+            agent.id_strategy,
+            agent.external_id.clone(),
+            agent.as_type_name(),
+        )
+    });
+    let activities = domain.activities.iter().map(|activity| {
+        (
+            activity.id_strategy,
+            activity.external_id.clone(),
+            activity.as_type_name(),
+        )
+    });
+    let entities = domain.entities.iter().map(|entity| {
+        (
+            entity.id_strategy,
+            entity.external_id.clone(),
+            entity.as_type_name(),
+        )
+    });
+
+    for (strategy, external_id, type_name) in agents.chain(activities).chain(entities) {
+        if let Some(strategy) = strategy {
+            strategies = strategies
+                .with_strategy(external_id, strategy)
+                .with_strategy(type_name, strategy);
+        }
+    }
+
+    strategies
+}
+
+/// The agent types that may hold each role the domain definition constrains,
+/// named both as the command line records agents and as GraphQL does
+fn configure_role_constraints(domain: &ChronicleDomainDef) -> RoleConstraints {
+    let mut constraints = RoleConstraints::new();
+
+    for role in &domain.roles {
+        if role.agents.is_empty() {
+            continue;
+        }
+
+        let holders = domain
+            .agents
+            .iter()
+            .filter(|agent| role.agents.contains(&agent.external_id))
+            .flat_map(|agent| {
+                [
+                    DomaintypeId::from_external_id(&agent.external_id),
+                    DomaintypeId::from_external_id(agent.as_type_name()),
+                ]
             });
+        constraints = constraints.with_holders(role.preserve_inflection(), holders);
+    }
 
-            if parsed_interval == 1800 {
-                debug!("Using default liveness health check interval value: 1800");
-            } else {
-                debug!("Using custom liveness health check interval value: {parsed_interval}");
-            }
-            return Some(parsed_interval);
+    constraints
+}
+
+/// The record types of the domain definition with their attributes, named as
+/// GraphQL names them, for definitions made through the REST endpoints
+fn configure_domain_types(domain: &ChronicleDomainDef) -> DomainTypes {
+    let attribute_types = |attributes: &[AttributeDef]| {
+        attributes
+            .iter()
+            .map(|attribute| AttributeType {
+                name: attribute.preserve_inflection(),
+                primitive_type: format!("{:?}", attribute.primitive_type),
+                repeated: attribute.repeated,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut types = DomainTypes::new();
+    for agent in &domain.agents {
+        types = types.with_agent(agent.as_type_name(), attribute_types(&agent.attributes));
+    }
+    for activity in &domain.activities {
+        types = types.with_activity(
+            activity.as_type_name(),
+            attribute_types(&activity.attributes),
+        );
+    }
+    for entity in &domain.entities {
+        types = types.with_entity(entity.as_type_name(), attribute_types(&entity.attributes));
+    }
+
+    types
+}
+
+/// Attribute constraints from the domain definition, keyed by both the name
+/// the command line records attributes under and the one GraphQL uses
+fn configure_validation(domain: &ChronicleDomainDef) -> Result<AttributeValidation, CliError> {
+    let mut validation = AttributeValidation::new();
+
+    for attribute in &domain.attributes {
+        if attribute.constraints.is_empty() {
+            continue;
+        }
+
+        for name in [attribute.as_type_name(), attribute.preserve_inflection()] {
+            validation = validation
+                .with_constraints(name, attribute.constraints.clone())
+                .map_err(|source| CliError::InvalidAttributePattern {
+                    attribute: attribute.as_type_name(),
+                    source,
+                })?;
         }
     }
-    debug!("Liveness health check disabled");
-    None
+
+    Ok(validation)
 }
 
-#[instrument(skip(gql, cli))]
+#[instrument(skip(gql, cli, enrichment))]
 async fn execute_subcommand<Query, Mutation>(
     gql: ChronicleGraphQl<Query, Mutation>,
     cli: CliModel,
+    enrichment: OperationEnrichment,
+    config: Config,
 ) -> Result<(ApiResponse, ApiDispatch), CliError>
 where
     Query: ObjectType + Copy,
     Mutation: ObjectType + Copy,
 {
-    dotenvy::dotenv().ok();
-
     let matches = cli.as_cmd().get_matches();
 
+    #[cfg(not(feature = "sqlite"))]
     let pool = pool_remote(&construct_db_uri(&matches)).await?;
+    #[cfg(not(feature = "sqlite"))]
+    let read_pool = match matches.value_of("database-replica-host") {
+        Some(host) => Some(pool_remote(&construct_db_uri_for_host(&matches, host)).await?),
+        None => None,
+    };
+    #[cfg(feature = "sqlite")]
+    let pool = pool_embedded(
+        matches
+            .value_of("database-path")
+            .expect("CLI should always set database path"),
+    )
+    .await?;
+    #[cfg(feature = "sqlite")]
+    let read_pool = None;
 
-    let opa = configure_opa(&matches).await?;
+    let opa = configure_opa(&matches, &config).await?;
+    let opa = match matches.value_of("opa-decision-log") {
+        Some(target) => opa.with_decision_log(DecisionLog::spawn(DecisionLogSink::parse(target))),
+        None => opa,
+    };
+
+    if let Some(address) = config
+        .serve_api()
+        .metrics_address
+        .or(config.backfill().metrics_address)
+    {
+        api::prometheus::install_prometheus_metrics_exporter(address);
+    }
 
-    let liveness_check_interval = configure_depth_charge(&matches);
+    let namespace_policy = if matches.is_present("enforce-namespace-access") {
+        Some(opa.context().clone())
+    } else {
+        None
+    };
+
+    let validation = configure_validation(&cli.domain)?;
+    let id_strategies = configure_id_strategies(&cli.domain);
+    let role_constraints = configure_role_constraints(&cli.domain);
+
+    // Kept for the server manifest, which is signed with the same keys as the api
+    let signing = chronicle_signing(&matches).await?;
+
+    let pseudonyms = matches
+        .subcommand_matches("serve-api")
+        .and_then(|matches| matches.get_one::<PathBuf>("pseudonymize-namespaces"))
+        .map(|path| NamespacePseudonyms::from_file(path))
+        .transpose()?;
+
+    // Deliveries are queued as the sync leader applies each commit, so the
+    // webhooks are part of the api's configuration
+    let webhook_conf = matches
+        .subcommand_matches("serve-api")
+        .and_then(|matches| matches.get_one::<PathBuf>("webhooks"))
+        .map(|path| WebhookConf::from_file(path))
+        .transpose()
+        .map_err(ApiError::from)?
+        .map(|conf| conf.with_pseudonyms(pseudonyms.clone()));
 
     let api = api(
         &pool,
         &matches,
-        opa.remote_settings(),
-        liveness_check_interval,
+        signing.clone(),
+        &config,
+        ApiConfig {
+            policy_name: opa.remote_settings(),
+            namespace_policy,
+            enrichment,
+            validation,
+            id_strategies,
+            role_constraints,
+            webhooks: webhook_conf.clone(),
+            ..Default::default()
+        },
     )
     .await?;
     let ret_api = api.clone();
 
     if let Some(matches) = matches.subcommand_matches("serve-api") {
-        let interface = match matches.get_many::<String>("interface") {
-            Some(interface_args) => {
-                let mut addrs = Vec::new();
-                for interface_arg in interface_args {
-                    addrs.extend(interface_arg.to_socket_addrs()?);
-                }
-                Some(addrs)
-            }
-            None => None,
-        };
+        let serve_api = config.serve_api();
 
-        let jwks_uri = if let Some(uri) = matches.value_of("jwks-address") {
-            Some(JwksUri::new(Url::from_str(uri)?))
-        } else {
-            None
-        };
+        let interface = Some(serve_api.interface);
 
-        let userinfo_uri = if let Some(uri) = matches.value_of("userinfo-address") {
-            Some(UserInfoUri::new(Url::from_str(uri)?))
-        } else {
-            None
-        };
+        let jwks_uri = serve_api.jwks_uri.map(JwksUri::new);
+
+        let userinfo_uri = serve_api.userinfo_uri.map(UserInfoUri::new);
 
         let allow_anonymous = !matches.is_present("require-auth");
 
@@ -493,9 +773,118 @@ where
             .map(String::clone)
             .collect();
 
+        let search = match serve_api.search_address {
+            Some(address) => Some(search_conf(matches, address)?),
+            None => None,
+        };
+
+        if let ConfiguredOpa::Remote(context, settings) = &opa {
+            policy_watcher::spawn_policy_watcher(
+                context.clone(),
+                PolicyWatcherConf {
+                    validator: config.sawtooth[0],
+                    policy_name: settings.policy_name.clone(),
+                    entrypoint: settings.entrypoint.clone(),
+                },
+            )
+            .map_err(ApiError::from)?;
+        }
+
+        let persisted_queries = matches
+            .get_one::<PathBuf>("persisted-queries")
+            .map(|dir| {
+                PersistedQueries::from_dir(dir, !matches.is_present("disable-ad-hoc-queries"))
+            })
+            .transpose()
+            .map_err(ApiError::from)?;
+
+        let explain = matches
+            .get_one::<PathBuf>("explain-templates")
+            .map(|path| ExplainTemplates::from_file(path))
+            .transpose()
+            .map_err(ApiError::from)?
+            .unwrap_or_default();
+
+        let role_permissions = matches
+            .get_one::<PathBuf>("role-permissions")
+            .map(|path| RolePermissions::from_file(path))
+            .transpose()
+            .map_err(ApiError::from)?;
+
+        let rate_limits = matches
+            .get_one::<PathBuf>("rate-limits")
+            .map(|path| RateLimits::from_file(path))
+            .transpose()
+            .map_err(ApiError::from)?;
+
+        let ttls = configure_expiry(&cli.domain);
+        if !ttls.is_empty() {
+            expiry::spawn_expiry(
+                &api,
+                ExpiryConf {
+                    ttls,
+                    interval: std::time::Duration::from_secs(
+                        serve_api.expiry_interval.unwrap_or(60),
+                    ),
+                },
+            );
+        }
+
+        if let Some(address) = serve_api.graph_mirror_address {
+            graph_mirror::spawn_mirror(&api, graph_mirror_conf(matches, address))
+                .await
+                .map_err(ApiError::from)?;
+        }
+
+        if let Some(conf) = webhook_conf {
+            webhooks::spawn_webhooks(&api, pool.clone(), conf)
+                .await
+                .map_err(ApiError::from)?;
+        }
+
+        if let Some(target) = event_sink_target(
+            matches,
+            serve_api.event_sink_kafka,
+            serve_api.event_sink_nats,
+        ) {
+            let encryption = match matches.get_many::<PathBuf>("event-sink-encrypt-to") {
+                Some(paths) => Some(
+                    PayloadEncryption::from_files(&paths.collect::<Vec<_>>())
+                        .map_err(EventSinkError::from)
+                        .map_err(ApiError::from)?,
+                ),
+                None => None,
+            };
+            event_sink::spawn_event_sink(
+                &api,
+                EventSinkConf {
+                    target,
+                    format: matches
+                        .value_of("event-sink-format")
+                        .and_then(EventSinkFormat::parse)
+                        .unwrap_or(EventSinkFormat::JsonLd),
+                    encryption,
+                    pseudonyms,
+                },
+            )
+            .await
+            .map_err(ApiError::from)?;
+        }
+
+        let server_info = ServerInfo::new(
+            LONG_VERSION,
+            chronicle_protocol::PROTOCOL_VERSION,
+            chronicle_protocol::address::VERSION,
+            &serde_json::to_vec(&cli.domain)?,
+            &gql.exportable_schema(),
+            &signing,
+        )
+        .await?;
+
         api_server(
             &api,
             &pool,
+            read_pool,
             gql,
             interface,
             SecurityConf::new(
@@ -505,9 +894,49 @@ where
                 jwt_must_claim,
                 allow_anonymous,
                 opa.context().clone(),
+                default_namespaces(matches),
+            )
+            .with_tenant_isolation(matches.is_present("tenant-isolation"))
+            .with_role_permissions(role_permissions)
+            .with_rate_limits(rate_limits)
+            .with_agent_claim(
+                matches
+                    .get_one::<String>("agent-claim")
+                    .map(AgentClaim::new),
             ),
-            endpoints.contains(&"graphql".to_string()),
-            endpoints.contains(&"data".to_string()),
+            ServerOptions {
+                serve_graphql: endpoints.contains(&"graphql".to_string()),
+                serve_data: endpoints.contains(&"data".to_string()),
+                serve_rest: endpoints.contains(&"rest".to_string()),
+                domain_types: configure_domain_types(&cli.domain),
+                exports: matches
+                    .get_one::<PathBuf>("export-dir")
+                    .map(ExportConf::new),
+                explain,
+                playground: matches
+                    .is_present("playground-examples")
+                    .then(|| playground_conf(&cli.domain)),
+                server_info,
+                federation: FederationConf::new(
+                    matches
+                        .get_many::<String>("federate")
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|kind| kind.parse::<FederatedKind>().ok()),
+                ),
+                search,
+                persisted_queries,
+                limits: QueryLimits {
+                    max_depth: matches.get_one::<usize>("max-query-depth").copied(),
+                    max_complexity: matches.get_one::<usize>("max-query-complexity").copied(),
+                },
+                query_cache: matches
+                    .get_one::<usize>("query-cache")
+                    .map(|capacity| QueryCache::new(*capacity)),
+                drain_timeout: std::time::Duration::from_secs(
+                    serve_api.drain_timeout.unwrap_or(30),
+                ),
+            },
         )
         .await?;
 
@@ -538,18 +967,57 @@ where
 
         let json_array = serde_json::from_str::<Vec<serde_json::Value>>(data)?;
 
+        let total = json_array.len();
+        let show_progress = std::io::stderr().is_terminal();
+
         let mut operations = Vec::new();
-        for value in json_array.into_iter() {
+        let mut skipped = 0;
+        for (index, value) in json_array.into_iter().enumerate() {
             let op = ChronicleOperation::from_json(&value)
                 .await
-                .expect("Failed to parse imported JSON-LD to ChronicleOperation");
+                .map_err(|source| CliError::InvalidImportOperation { index, source })?;
             // Only import operations for the specified namespace
             if op.namespace() == &namespace {
                 operations.push(op);
+            } else {
+                skipped += 1;
             }
+
+            if show_progress {
+                eprint!("\rParsed {}/{total} operations", index + 1);
+            }
+        }
+        if show_progress {
+            eprintln!();
         }
 
         info!("Loading import data complete");
+        eprintln!(
+            "{} operation(s) to import into {namespace}, {skipped} in other namespaces skipped",
+            operations.len()
+        );
+
+        if matches.is_present("dry-run") {
+            // Operations that contradict one another would be rejected
+            ProvModel::from_tx(&operations).map_err(ApiError::from)?;
+
+            let mut kinds = BTreeMap::<_, usize>::new();
+            for op in &operations {
+                *kinds.entry(op.name()).or_default() += 1;
+            }
+            for (kind, count) in kinds {
+                eprintln!("  {kind}: {count}");
+            }
+
+            let estimate = estimate_submission(&operations).await?;
+            eprintln!(
+                "Submitted as one transaction: {} byte payload, {} state address(es) read and written",
+                estimate.payload_bytes, estimate.addresses
+            );
+            eprintln!("Dry run, nothing submitted");
+
+            return Ok((ApiResponse::Unit, ret_api));
+        }
 
         let identity = AuthId::chronicle();
         info!("Importing data as root to Chronicle namespace: {namespace}");
@@ -558,6 +1026,75 @@ where
             .handle_import_command(identity, namespace, operations)
             .await?;
 
+        Ok((response, ret_api))
+    } else if let Some(matches) = matches.subcommand_matches("verify") {
+        let namespace = matches.value_of("namespace").map(ExternalId::from);
+
+        info!("Verifying local store against the ledger");
+
+        let response = api
+            .dispatch(
+                ApiCommand::Verify(VerifyCommand { namespace }),
+                AuthId::chronicle(),
+            )
+            .await?;
+
+        Ok((response, ret_api))
+    } else if let Some(matches) = matches.subcommand_matches("outbox") {
+        let command = if matches.is_present("flush") {
+            OutboxCommand::Flush
+        } else {
+            OutboxCommand::List
+        };
+
+        let response = api
+            .dispatch(ApiCommand::Outbox(command), AuthId::chronicle())
+            .await?;
+
+        Ok((response, ret_api))
+    } else if let Some(matches) = matches.subcommand_matches("backfill") {
+        let backfill = config.backfill();
+        let task = match (
+            matches.value_of("task"),
+            backfill.search_address,
+            backfill.graph_mirror_address,
+        ) {
+            (Some("search"), Some(address), _) => {
+                BackfillTask::Search(search_conf(matches, address)?)
+            }
+            (Some("graph-mirror"), _, Some(address)) => {
+                BackfillTask::GraphMirror(graph_mirror_conf(matches, address))
+            }
+            _ => return Err(CliError::missing_argument("task")),
+        };
+
+        let processed = api::backfill::backfill(
+            pool.clone(),
+            task,
+            BackfillConf {
+                chunk_size: backfill.chunk_size.unwrap_or(500),
+                rate: backfill.rate,
+                restart: matches.is_present("restart"),
+            },
+        )
+        .await
+        .map_err(ApiError::from)?;
+
+        println!("Backfilled {processed} records");
+
+        Ok((ApiResponse::Unit, ret_api))
+    } else if let Some(matches) = matches.subcommand_matches("rotate-key") {
+        let import = matches.value_of("import").map(PathBuf::from);
+
+        info!("Rotating chronicle signing key");
+
+        let response = api
+            .dispatch(
+                ApiCommand::RotateKey(RotateKeyCommand { import }),
+                AuthId::chronicle(),
+            )
+            .await?;
+
         Ok((response, ret_api))
     } else if let Some(cmd) = cli.matches(&matches)? {
         let identity = AuthId::chronicle();
@@ -567,6 +1104,55 @@ where
     }
 }
 
+/// The search index configured by the `serve-api` or `backfill` matches
+fn search_conf(matches: &ArgMatches, address: Url) -> Result<SearchConf, CliError> {
+    let mapping = match matches.get_one::<PathBuf>("search-mapping") {
+        Some(path) => Some(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+        None => None,
+    };
+
+    Ok(SearchConf::new(
+        address,
+        matches.value_of("search-index").unwrap_or("chronicle"),
+        mapping,
+    ))
+}
+
+/// The broker to publish committed deltas to, if one is configured
+fn event_sink_target(
+    matches: &ArgMatches,
+    kafka: Option<Url>,
+    nats: Option<Url>,
+) -> Option<EventSinkTarget> {
+    let topic = matches
+        .value_of("event-sink-topic")
+        .unwrap_or("chronicle.commits")
+        .to_owned();
+
+    match (kafka, nats) {
+        (Some(proxy), _) => Some(EventSinkTarget::Kafka { proxy, topic }),
+        (_, Some(server)) => Some(EventSinkTarget::Nats {
+            server,
+            subject: topic,
+        }),
+        _ => None,
+    }
+}
+
+/// The graph mirror configured by the `serve-api` or `backfill` matches
+fn graph_mirror_conf(matches: &ArgMatches, address: Url) -> GraphMirrorConf {
+    let credentials = matches
+        .value_of("graph-mirror-user")
+        .zip(matches.value_of("graph-mirror-password"))
+        .map(|(user, password)| (user.to_owned(), password.to_owned()));
+
+    GraphMirrorConf::new(
+        address,
+        matches.value_of("graph-mirror-database").unwrap_or("neo4j"),
+        credentials,
+    )
+}
+
 fn get_namespace(matches: &ArgMatches) -> NamespaceId {
     let namespace_id = matches.value_of("namespace-id").unwrap();
     let namespace_uuid = matches.value_of("namespace-uuid").unwrap();
@@ -578,6 +1164,8 @@ fn get_namespace(matches: &ArgMatches) -> NamespaceId {
 async fn config_and_exec<Query, Mutation>(
     gql: ChronicleGraphQl<Query, Mutation>,
     model: CliModel,
+    enrichment: OperationEnrichment,
+    config: Config,
 ) -> Result<(), CliError>
 where
     Query: ObjectType + Copy,
@@ -585,15 +1173,11 @@ where
 {
     use colored_json::prelude::*;
 
-    let response = execute_subcommand(gql, model).await?;
+    let response = execute_subcommand(gql, model, enrichment, config).await?;
 
     match response {
         (
-            ApiResponse::Submission {
-                subject,
-                prov: _,
-                tx_id,
-            },
+            ApiResponse::Submission { subject, tx_id, .. },
             api,
         ) => {
             // For commands that have initiated a ledger operation, wait for the matching result
@@ -701,6 +1285,55 @@ where
                 }
             }
         }
+        (ApiResponse::Verified { namespaces }, _) => {
+            let mut diverged = 0;
+            for verification in namespaces {
+                if verification.is_faithful() {
+                    println!(
+                        "{}: faithful {}",
+                        verification.namespace, verification.local_digest
+                    );
+                } else {
+                    diverged += 1;
+                    println!(
+                        "{}: diverged, local {} ledger {}",
+                        verification.namespace,
+                        verification.local_digest,
+                        verification.ledger_digest
+                    );
+                    for fact in verification.local_only {
+                        println!("  - {fact}");
+                    }
+                    for fact in verification.ledger_only {
+                        println!("  + {fact}");
+                    }
+                    for tx_id in verification.unsynced {
+                        println!("  ! {tx_id} not applied to the store");
+                    }
+                }
+            }
+
+            if diverged > 0 {
+                return Err(CliError::Diverged { count: diverged });
+            }
+        }
+        (ApiResponse::AlreadySubmitted { subject, tx_id }, _) => {
+            println!("Transaction already submitted with this idempotency key: {tx_id} {subject}");
+        }
+        (ApiResponse::Queued { id }, _) => {
+            println!("Ledger unreachable, command held in the outbox: {id}");
+        }
+        (ApiResponse::Outbox { commands }, _) => {
+            for command in commands {
+                println!(
+                    "{} {} {} {}",
+                    command.queued_at.to_rfc3339(),
+                    command.id,
+                    command.kind,
+                    command.identity
+                );
+            }
+        }
         (ApiResponse::DepthChargeSubmitted { tx_id }, _) => error!(
             "DepthChargeSubmitted is an unexpected API response for transaction: {tx_id}. Depth charge not implemented."
         ),
@@ -718,6 +1351,91 @@ pub async fn bootstrap<Query, Mutation>(
 ) where
     Query: ObjectType + 'static + Copy,
     Mutation: ObjectType + 'static + Copy,
+{
+    bootstrap_with_enrichment(domain, gql, OperationEnrichment::default()).await
+}
+
+/// Open the playground with the domain's queries, then its mutations
+fn playground_conf(domain: &ChronicleDomainDef) -> PlaygroundConf {
+    PlaygroundConf::new(
+        examples::graphql_queries(domain)
+            .into_iter()
+            .chain(examples::graphql_mutations(domain))
+            .map(|example| PlaygroundExample {
+                name: example.name,
+                query: example.document,
+            })
+            .collect(),
+    )
+}
+
+fn print_domain_examples(domain: &ChronicleDomainDef, matches: &ArgMatches) {
+    if matches.value_of("format") == Some("json-ld") {
+        let namespace_uuid = match matches.value_of("namespace-uuid").unwrap().parse() {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                eprintln!("Invalid --namespace-uuid: {e}");
+                std::process::exit(1);
+            }
+        };
+        let namespace = NamespaceId::from_external_id(
+            matches.value_of("namespace-id").unwrap(),
+            namespace_uuid,
+        );
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&examples::json_ld_examples(domain, &namespace)).unwrap()
+        );
+    } else {
+        print!("{}", examples::graphql_examples(domain));
+    }
+}
+
+/// Print the changes from the domain this Chronicle was built from, or the one
+/// named by `--from`, to the one named, with the migration each needs.
+/// Returns true if any change is breaking.
+fn print_domain_diff(domain: &ChronicleDomainDef, matches: &ArgMatches) -> bool {
+    let read = |path: &str| match ChronicleDomainDef::from_file(path) {
+        Ok(domain) => domain,
+        Err(e) => {
+            eprintln!("Cannot read domain {path}: {e}");
+            std::process::exit(2);
+        }
+    };
+    let old = matches
+        .value_of("from")
+        .map(read)
+        .unwrap_or_else(|| domain.clone());
+    let new = read(matches.value_of("domain").unwrap());
+
+    let changes = evolution::diff(&old, &new);
+    if changes.is_empty() {
+        println!("no changes");
+    }
+    for change in &changes {
+        let kind = if change.is_breaking() {
+            "breaking"
+        } else {
+            "compatible"
+        };
+        println!("{kind}: {change}");
+        println!("  migration: {}", change.migration());
+    }
+
+    changes.iter().any(DomainChange::is_breaking)
+}
+
+/// As [bootstrap], but submitted operations are first passed through the
+/// supplied chain of enrichers, allowing a deployment to stamp or append
+/// operations without modifying the api
+pub async fn bootstrap_with_enrichment<Query, Mutation>(
+    domain: ChronicleDomainDef,
+    gql: ChronicleGraphQl<Query, Mutation>,
+    enrichment: OperationEnrichment,
+) where
+    Query: ObjectType + 'static + Copy,
+    Mutation: ObjectType + 'static + Copy,
 {
     let matches = cli(domain.clone()).as_cmd().get_matches();
 
@@ -731,29 +1449,39 @@ pub async fn bootstrap<Query, Mutation>(
         std::process::exit(0);
     }
 
-    if matches.subcommand_matches("export-schema").is_some() {
-        print!("{}", gql.exportable_schema());
+    if let Some(export) = matches.subcommand_matches("export-schema") {
+        if export.is_present("federation") {
+            print!("{}", gql.exportable_federated_schema());
+        } else {
+            print!("{}", gql.exportable_schema());
+        }
         std::process::exit(0);
     }
-    chronicle_telemetry::telemetry(
-        matches
-            .get_one::<String>("instrument")
-            .map(|s| Url::parse(s).expect("cannot parse instrument as URI: {s}")),
-        if matches.contains_id("console-logging") {
-            match matches.get_one::<String>("console-logging") {
-                Some(level) => match level.as_str() {
-                    "pretty" => ConsoleLogging::Pretty,
-                    "json" => ConsoleLogging::Json,
-                    _ => ConsoleLogging::Off,
-                },
-                _ => ConsoleLogging::Off,
-            }
-        } else if matches.subcommand_name() == Some("serve-api") {
-            ConsoleLogging::Pretty
-        } else {
-            ConsoleLogging::Off
-        },
-    );
+
+    if let Some(examples) = matches
+        .subcommand_matches("domain")
+        .and_then(|domain| domain.subcommand_matches("examples"))
+    {
+        print_domain_examples(&domain, examples);
+        std::process::exit(0);
+    }
+
+    if let Some(lint) = matches
+        .subcommand_matches("domain")
+        .and_then(|domain| domain.subcommand_matches("lint"))
+    {
+        linter::check_files(lint.values_of("filenames").unwrap().collect());
+        println!("successful: no domain definition errors detected");
+        std::process::exit(0);
+    }
+
+    if let Some(diff) = matches
+        .subcommand_matches("domain")
+        .and_then(|domain| domain.subcommand_matches("diff"))
+    {
+        let breaking = print_domain_diff(&domain, diff);
+        std::process::exit(if breaking { 1 } else { 0 });
+    }
 
     if matches.subcommand_matches("generate-key").is_some() {
         let key = SecretKey::random(StdRng::from_entropy());
@@ -770,7 +1498,47 @@ pub async fn bootstrap<Query, Mutation>(
         std::process::exit(0);
     }
 
-    config_and_exec(gql, domain.into())
+    dotenvy::dotenv().ok();
+
+    let cmd = cli(domain.clone()).as_cmd();
+    let matches = cmd.clone().get_matches();
+
+    if let Some(doctor) = matches.subcommand_matches("doctor") {
+        let failed = doctor::doctor(&cmd, &matches, doctor).await;
+
+        std::process::exit(if failed == 0 { 0 } else { 1 });
+    }
+
+    let config = match Config::from_matches(&cmd, &matches) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration:{e}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = chronicle_telemetry::telemetry_with_otlp(
+        config.instrument.clone(),
+        config.otlp.clone(),
+        if matches.contains_id("console-logging") {
+            match matches.get_one::<String>("console-logging") {
+                Some(level) => match level.as_str() {
+                    "pretty" => ConsoleLogging::Pretty,
+                    "json" => ConsoleLogging::Json,
+                    _ => ConsoleLogging::Off,
+                },
+                _ => ConsoleLogging::Off,
+            }
+        } else if matches.subcommand_name() == Some("serve-api") {
+            ConsoleLogging::Pretty
+        } else {
+            ConsoleLogging::Off
+        },
+    ) {
+        eprintln!("Cannot export telemetry: {e}");
+    }
+
+    config_and_exec(gql, domain.into(), enrichment, config)
         .await
         .map_err(|e| {
             error!(?e, "Api error");
@@ -786,7 +1554,7 @@ pub async fn bootstrap<Query, Mutation>(
 /// configuration + server execution would get a little tricky in the context of a unit test.
 #[cfg(test)]
 pub mod test {
-    use api::{inmem::EmbeddedChronicleTp, Api, ApiDispatch, ApiError, UuidGen};
+    use api::{inmem::EmbeddedChronicleTp, Api, ApiConfig, ApiDispatch, ApiError, UuidGen};
     use async_stl_client::prost::Message;
     use chronicle_signing::{
         chronicle_secret_names, ChronicleSecretsOptions, ChronicleSigning, BATCHER_NAMESPACE,
@@ -924,16 +1692,15 @@ pub mod test {
         let database = TemporaryDatabase::default();
         let pool = database.connection_pool().unwrap();
 
-        let liveness_check_interval = None;
-
         let dispatch = Api::new(
             pool,
             embedded_tp.ledger.clone(),
             SameUuid,
             secrets,
-            vec![],
-            Some("allow_transactions".to_owned()),
-            liveness_check_interval,
+            ApiConfig {
+                policy_name: Some("allow_transactions".to_owned()),
+                ..Default::default()
+            },
         )
         .await
         .unwrap();
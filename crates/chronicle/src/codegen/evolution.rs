@@ -0,0 +1,413 @@
+//! The changes between two versions of a domain, for `chronicle domain diff`.
+//! Each change is breaking if clients of the API generated from the earlier
+//! version, or provenance already recorded, no longer fit the later one, and
+//! carries the step a migration to the later version takes in the GraphQL
+//! schema.
+
+use std::{collections::BTreeMap, fmt};
+
+use super::model::{AttributeDef, ChronicleDomainDef, RecipeDef, TypeName};
+
+/// A change from one version of a domain to the next, naming types, fields,
+/// roles and mutations as they appear in the GraphQL schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomainChange {
+    TypeAdded {
+        typ: String,
+    },
+    TypeRemoved {
+        typ: String,
+        untyped: &'static str,
+    },
+    AttributeAdded {
+        typ: String,
+        attribute: String,
+    },
+    AttributeRemoved {
+        typ: String,
+        attribute: String,
+    },
+    AttributeTypeChanged {
+        attribute: String,
+        from: String,
+        to: String,
+    },
+    AttributeConstraintsChanged {
+        attribute: String,
+    },
+    RoleAdded {
+        role: String,
+    },
+    RoleRemoved {
+        role: String,
+    },
+    /// The agents that may hold a role changed, restricting it if an agent
+    /// type that could hold it no longer can
+    RoleHoldersChanged {
+        role: String,
+        restricted: bool,
+    },
+    RecipeAdded {
+        mutation: String,
+    },
+    RecipeRemoved {
+        mutation: String,
+    },
+    RecipeChanged {
+        mutation: String,
+    },
+}
+
+impl DomainChange {
+    /// True if clients of the earlier API or recorded provenance no longer fit
+    pub fn is_breaking(&self) -> bool {
+        match self {
+            DomainChange::TypeAdded { .. }
+            | DomainChange::RoleAdded { .. }
+            | DomainChange::RecipeAdded { .. } => false,
+            DomainChange::RoleHoldersChanged { restricted, .. } => *restricted,
+            _ => true,
+        }
+    }
+
+    /// The change a migration to the later version makes to the GraphQL
+    /// schema, and what it requires of clients and recorded provenance
+    pub fn migration(&self) -> String {
+        match self {
+            DomainChange::TypeAdded { typ } => {
+                format!("Add type `{typ}` and mutation `define{typ}`.")
+            }
+            DomainChange::TypeRemoved { typ, untyped } => format!(
+                "Remove type `{typ}` and mutation `define{typ}`. Records of the type are \
+                 returned as `{untyped}`, without their attributes."
+            ),
+            DomainChange::AttributeAdded { typ, attribute } => format!(
+                "Add field `{attribute}` to `{typ}` and its attributes input. Callers of \
+                 `define{typ}` must supply it."
+            ),
+            DomainChange::AttributeRemoved { typ, attribute } => format!(
+                "Remove field `{attribute}` from `{typ}` and its attributes input. Queries \
+                 selecting it must be updated, and values already recorded are no longer \
+                 returned."
+            ),
+            DomainChange::AttributeTypeChanged {
+                attribute,
+                from,
+                to,
+            } => format!(
+                "Change field `{attribute}` from `{from}` to `{to}` wherever it appears. \
+                 Values already recorded are not converted."
+            ),
+            DomainChange::AttributeConstraintsChanged { attribute } => format!(
+                "Validate `{attribute}` against its new constraints. Values already recorded \
+                 may not satisfy them."
+            ),
+            DomainChange::RoleAdded { role } => format!("Add `{role}` to `RoleType`."),
+            DomainChange::RoleRemoved { role } => format!(
+                "Remove `{role}` from `RoleType`. Relations already recorded in the role are \
+                 returned with the role `Unspecified`."
+            ),
+            DomainChange::RoleHoldersChanged {
+                role,
+                restricted: true,
+            } => format!(
+                "Reject relations giving `{role}` to agents of types that may no longer hold it."
+            ),
+            DomainChange::RoleHoldersChanged { role, .. } => {
+                format!("Accept relations giving `{role}` to agents of the added types.")
+            }
+            DomainChange::RecipeAdded { mutation } => format!("Add mutation `{mutation}`."),
+            DomainChange::RecipeRemoved { mutation } => format!(
+                "Remove mutation `{mutation}`. Callers must record its operations separately."
+            ),
+            DomainChange::RecipeChanged { mutation } => {
+                format!("Change the arguments of mutation `{mutation}`. Callers must be updated.")
+            }
+        }
+    }
+}
+
+impl fmt::Display for DomainChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainChange::TypeAdded { typ } => write!(f, "added type {typ}"),
+            DomainChange::TypeRemoved { typ, .. } => write!(f, "removed type {typ}"),
+            DomainChange::AttributeAdded { typ, attribute } => {
+                write!(f, "added attribute {attribute} to {typ}")
+            }
+            DomainChange::AttributeRemoved { typ, attribute } => {
+                write!(f, "removed attribute {attribute} from {typ}")
+            }
+            DomainChange::AttributeTypeChanged {
+                attribute,
+                from,
+                to,
+            } => write!(f, "changed attribute {attribute} from {from} to {to}"),
+            DomainChange::AttributeConstraintsChanged { attribute } => {
+                write!(f, "changed the constraints on attribute {attribute}")
+            }
+            DomainChange::RoleAdded { role } => write!(f, "added role {role}"),
+            DomainChange::RoleRemoved { role } => write!(f, "removed role {role}"),
+            DomainChange::RoleHoldersChanged { role, .. } => {
+                write!(f, "changed the agents that may hold role {role}")
+            }
+            DomainChange::RecipeAdded { mutation } => write!(f, "added recipe {mutation}"),
+            DomainChange::RecipeRemoved { mutation } => write!(f, "removed recipe {mutation}"),
+            DomainChange::RecipeChanged { mutation } => write!(f, "changed recipe {mutation}"),
+        }
+    }
+}
+
+/// The agent, entity and activity types of a domain by GraphQL type name, with
+/// the untyped GraphQL type their records fall back to and their attributes
+fn types(domain: &ChronicleDomainDef) -> BTreeMap<String, (&'static str, &[AttributeDef])> {
+    domain
+        .agents
+        .iter()
+        .map(|agent| {
+            (
+                agent.as_type_name(),
+                ("ProvAgent", agent.attributes.as_slice()),
+            )
+        })
+        .chain(domain.entities.iter().map(|entity| {
+            (
+                entity.as_type_name(),
+                ("ProvEntity", entity.attributes.as_slice()),
+            )
+        }))
+        .chain(domain.activities.iter().map(|activity| {
+            (
+                activity.as_type_name(),
+                ("ProvActivity", activity.attributes.as_slice()),
+            )
+        }))
+        .collect()
+}
+
+/// The GraphQL type of an attribute's values
+fn attribute_type(attribute: &AttributeDef) -> String {
+    if attribute.repeated {
+        format!("[{:?}]", attribute.primitive_type)
+    } else {
+        format!("{:?}", attribute.primitive_type)
+    }
+}
+
+fn recipes(domain: &ChronicleDomainDef) -> BTreeMap<String, &RecipeDef> {
+    domain
+        .recipes
+        .iter()
+        .map(|recipe| (recipe.as_method_name(), recipe))
+        .collect()
+}
+
+/// The changes from `old` to `new`: those to types and their attributes, then
+/// to the attributes themselves, then to roles and recipes
+pub fn diff(old: &ChronicleDomainDef, new: &ChronicleDomainDef) -> Vec<DomainChange> {
+    let mut changes = vec![];
+
+    let (old_types, new_types) = (types(old), types(new));
+    for (typ, (untyped, _)) in &old_types {
+        if !new_types.contains_key(typ) {
+            changes.push(DomainChange::TypeRemoved {
+                typ: typ.clone(),
+                untyped: *untyped,
+            });
+        }
+    }
+    for (typ, (_, attributes)) in &new_types {
+        let old_attributes = match old_types.get(typ) {
+            Some((_, old_attributes)) => *old_attributes,
+            None => {
+                changes.push(DomainChange::TypeAdded { typ: typ.clone() });
+                continue;
+            }
+        };
+        let fields = |attributes: &[AttributeDef]| {
+            attributes
+                .iter()
+                .map(|attribute| attribute.preserve_inflection())
+                .collect::<Vec<_>>()
+        };
+        let (old_fields, fields) = (fields(old_attributes), fields(attributes));
+        for attribute in old_fields.iter().filter(|field| !fields.contains(field)) {
+            changes.push(DomainChange::AttributeRemoved {
+                typ: typ.clone(),
+                attribute: attribute.clone(),
+            });
+        }
+        for attribute in fields.iter().filter(|field| !old_fields.contains(field)) {
+            changes.push(DomainChange::AttributeAdded {
+                typ: typ.clone(),
+                attribute: attribute.clone(),
+            });
+        }
+    }
+
+    for attribute in &new.attributes {
+        let field = attribute.preserve_inflection();
+        let old_attribute = match old
+            .attributes
+            .iter()
+            .find(|old_attribute| old_attribute.preserve_inflection() == field)
+        {
+            Some(old_attribute) => old_attribute,
+            None => continue,
+        };
+        let (from, to) = (attribute_type(old_attribute), attribute_type(attribute));
+        if from != to {
+            changes.push(DomainChange::AttributeTypeChanged {
+                attribute: field,
+                from,
+                to,
+            });
+        } else if old_attribute.constraints != attribute.constraints {
+            changes.push(DomainChange::AttributeConstraintsChanged { attribute: field });
+        }
+    }
+
+    for role in &old.roles {
+        if !new
+            .roles
+            .iter()
+            .any(|new_role| new_role.external_id == role.external_id)
+        {
+            changes.push(DomainChange::RoleRemoved {
+                role: role.external_id.clone(),
+            });
+        }
+    }
+    for role in &new.roles {
+        match old
+            .roles
+            .iter()
+            .find(|old_role| old_role.external_id == role.external_id)
+        {
+            None => changes.push(DomainChange::RoleAdded {
+                role: role.external_id.clone(),
+            }),
+            Some(old_role) => {
+                let holders = |agents: &[String]| {
+                    let mut agents = agents.to_vec();
+                    agents.sort();
+                    agents.dedup();
+                    agents
+                };
+                let (old_holders, holders) = (holders(&old_role.agents), holders(&role.agents));
+                if old_holders != holders {
+                    let restricted = !holders.is_empty()
+                        && (old_holders.is_empty()
+                            || old_holders.iter().any(|agent| !holders.contains(agent)));
+                    changes.push(DomainChange::RoleHoldersChanged {
+                        role: role.external_id.clone(),
+                        restricted,
+                    });
+                }
+            }
+        }
+    }
+
+    let (old_recipes, new_recipes) = (recipes(old), recipes(new));
+    for mutation in old_recipes.keys() {
+        if !new_recipes.contains_key(mutation) {
+            changes.push(DomainChange::RecipeRemoved {
+                mutation: mutation.clone(),
+            });
+        }
+    }
+    for (mutation, recipe) in &new_recipes {
+        match old_recipes.get(mutation) {
+            None => changes.push(DomainChange::RecipeAdded {
+                mutation: mutation.clone(),
+            }),
+            Some(old_recipe)
+                if (
+                    &old_recipe.activity,
+                    &old_recipe.roles,
+                    &old_recipe.uses,
+                    &old_recipe.generates,
+                ) != (
+                    &recipe.activity,
+                    &recipe.roles,
+                    &recipe.uses,
+                    &recipe.generates,
+                ) =>
+            {
+                changes.push(DomainChange::RecipeChanged {
+                    mutation: mutation.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::{diff, DomainChange};
+    use crate::codegen::{model::RoleDef, ChronicleDomainDef};
+
+    fn domain(question: &str, content: &str) -> ChronicleDomainDef {
+        ChronicleDomainDef::from_str(&format!(
+            r#"
+            name: evidence
+            attributes:
+              Content:
+                type: {content}
+              Title:
+                type: String
+            agents:
+              Person:
+                attributes: []
+            entities:
+              Question:
+                attributes: {question}
+            activities: {{}}
+            roles:
+              - AUTHOR
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn removals_and_type_changes_are_breaking() {
+        let old = domain("[Content, Title]", "String");
+
+        assert!(diff(&old, &old).is_empty());
+
+        let changes = diff(&old, &domain("[Content]", "JSON"));
+        assert_eq!(
+            changes,
+            [
+                DomainChange::AttributeRemoved {
+                    typ: "QuestionEntity".to_owned(),
+                    attribute: "titleAttribute".to_owned(),
+                },
+                DomainChange::AttributeTypeChanged {
+                    attribute: "contentAttribute".to_owned(),
+                    from: "String".to_owned(),
+                    to: "JSON".to_owned(),
+                },
+            ]
+        );
+        assert!(changes.iter().all(DomainChange::is_breaking));
+
+        let mut added = domain("[Content, Title]", "String");
+        added.roles.push(RoleDef::new("EDITOR"));
+        let changes = diff(&old, &added);
+        assert_eq!(
+            changes,
+            [DomainChange::RoleAdded {
+                role: "EDITOR".to_owned()
+            }]
+        );
+        assert!(!changes[0].is_breaking());
+        assert_eq!(changes[0].migration(), "Add `EDITOR` to `RoleType`.");
+    }
+}
@@ -0,0 +1,556 @@
+//! Example provenance for a domain, for `chronicle domain examples` and the
+//! API's playground: a definition of each agent, activity and entity type with
+//! values for all of its attributes, followed by one of each relationship
+//! between them, and queries for the records of each type. Values satisfy the
+//! constraints declared in the domain, except for patterns, which an example
+//! value cannot be derived from and are noted instead.
+
+use common::{
+    attributes::{Attribute, Attributes},
+    prov::{
+        operations::{
+            ActivityExists, ActivityUses, ActsOnBehalfOf, AgentExists, ChronicleOperation,
+            DerivationType, EntityDerive, EntityExists, SetAttributes, WasAssociatedWith,
+            WasAttributedTo, WasGeneratedBy, WasInformedBy,
+        },
+        to_json_ld::ToJson,
+        ActivityId, AgentId, DomaintypeId, EntityId, NamespaceId, Role,
+    },
+};
+use serde_json::{json, Value};
+
+use super::{AttributeDef, ChronicleDomainDef, CliName, PrimitiveType, TypeName};
+
+/// A value of `attribute`'s type that satisfies its constraints, other than a
+/// pattern
+pub fn example_value(attribute: &AttributeDef) -> Value {
+    let constraints = &attribute.constraints;
+    let bound = constraints
+        .minimum
+        .clone()
+        .or_else(|| constraints.maximum.clone());
+
+    let value = match constraints
+        .allowed
+        .as_ref()
+        .and_then(|allowed| allowed.first())
+    {
+        Some(allowed) => allowed.clone(),
+        None => match attribute.primitive_type {
+            PrimitiveType::String => json!("example"),
+            PrimitiveType::Bool => json!(true),
+            PrimitiveType::Int => bound.map(Value::Number).unwrap_or_else(|| json!(1)),
+            PrimitiveType::Float => bound.map(Value::Number).unwrap_or_else(|| json!(1.5)),
+            PrimitiveType::Decimal => json!(bound
+                .map(|bound| bound.to_string())
+                .unwrap_or_else(|| "1.50".to_owned())),
+            PrimitiveType::DateTime => json!("2023-01-01T00:00:00Z"),
+            PrimitiveType::Bytes => json!("ZXhhbXBsZQ=="),
+            PrimitiveType::JSON => json!({ "example": true }),
+        },
+    };
+
+    if attribute.repeated {
+        Value::Array(vec![value])
+    } else {
+        value
+    }
+}
+
+/// The external ids the examples give to the subjects of relationships, the
+/// first type of each kind in the domain standing in for all of them
+struct Subjects {
+    agent: String,
+    delegate: String,
+    activity: String,
+    informing_activity: String,
+    entity: String,
+    used_entity: String,
+    role: Option<String>,
+}
+
+impl Subjects {
+    fn new(domain: &ChronicleDomainDef) -> Self {
+        let agent = domain
+            .agents
+            .first()
+            .map(|agent| example_id(&agent.as_cli_name()))
+            .unwrap_or_else(|| example_id("agent"));
+        let activity = domain
+            .activities
+            .first()
+            .map(|activity| example_id(&activity.as_cli_name()))
+            .unwrap_or_else(|| example_id("activity"));
+        let entity = domain
+            .entities
+            .first()
+            .map(|entity| example_id(&entity.as_cli_name()))
+            .unwrap_or_else(|| example_id("entity"));
+
+        Self {
+            delegate: format!("{agent}-delegate"),
+            informing_activity: format!("{activity}-informant"),
+            used_entity: format!("{entity}-source"),
+            agent,
+            activity,
+            entity,
+            role: domain.roles.first().map(|role| role.preserve_inflection()),
+        }
+    }
+}
+
+fn example_id(type_cli_name: &str) -> String {
+    format!("example-{type_cli_name}")
+}
+
+/// Render a JSON value as a GraphQL input value literal
+fn graphql_literal(value: &Value) -> String {
+    match value {
+        Value::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(graphql_literal)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Object(fields) => format!(
+            "{{ {} }}",
+            fields
+                .iter()
+                .map(|(name, value)| format!("{name}: {}", graphql_literal(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        other => other.to_string(),
+    }
+}
+
+fn graphql_id(kind: &str, external_id: &str) -> String {
+    format!("{{ id: \"chronicle:{kind}:{external_id}\" }}")
+}
+
+/// A GraphQL operation, its name describing what it records or queries
+#[derive(Debug, Clone)]
+pub struct GraphQlExample {
+    pub name: String,
+    pub document: String,
+}
+
+fn graphql_mutation(name: &str, field: &str, arguments: &[String]) -> GraphQlExample {
+    let mut document = format!("# {name}\nmutation {{\n  {field}(\n");
+    for argument in arguments {
+        for line in argument.lines() {
+            document.push_str(&format!("    {line}\n"));
+        }
+    }
+    document.push_str("  ) {\n    context\n    txId\n  }\n}\n");
+
+    GraphQlExample {
+        name: name.to_owned(),
+        document,
+    }
+}
+
+fn graphql_query(
+    kind: &str,
+    field: &str,
+    type_argument: &str,
+    type_name: &str,
+    attributes: &[AttributeDef],
+) -> GraphQlExample {
+    let name = format!("Query {kind} of type {type_name}");
+    let mut document = format!(
+        "# {name}\nquery {{\n  {field}({type_argument}: {type_name}, first: 10) {{\n    \
+         nodes {{\n      ... on {type_name} {{\n        id\n        externalId\n"
+    );
+    for attribute in attributes {
+        document.push_str(&format!("        {}\n", attribute.preserve_inflection()));
+    }
+    document.push_str("      }\n    }\n  }\n}\n");
+
+    GraphQlExample { name, document }
+}
+
+fn graphql_define(
+    kind: &str,
+    method_name: &str,
+    type_name: &str,
+    cli_name: &str,
+    attributes: &[AttributeDef],
+) -> GraphQlExample {
+    let mut arguments = vec![format!("externalId: \"{}\"", example_id(cli_name))];
+
+    if !attributes.is_empty() {
+        let mut input = "attributes: {\n".to_owned();
+        for attribute in attributes {
+            input.push_str(&format!(
+                "  {}: {}",
+                attribute.preserve_inflection(),
+                graphql_literal(&example_value(attribute))
+            ));
+            if let Some(pattern) = &attribute.constraints.pattern {
+                input.push_str(&format!(" # must match {pattern}"));
+            }
+            input.push('\n');
+        }
+        input.push('}');
+        arguments.push(input);
+    }
+
+    graphql_mutation(
+        &format!("Define {kind} of type {type_name}"),
+        method_name,
+        &arguments,
+    )
+}
+
+/// An example GraphQL mutation for each type and relationship in the domain
+pub fn graphql_examples(domain: &ChronicleDomainDef) -> String {
+    graphql_mutations(domain)
+        .into_iter()
+        .map(|example| example.document)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A query for the records of each type in the domain, selecting all of
+/// their attributes
+pub fn graphql_queries(domain: &ChronicleDomainDef) -> Vec<GraphQlExample> {
+    let mut examples = vec![];
+
+    for agent in &domain.agents {
+        examples.push(graphql_query(
+            "agents",
+            "agentsByType",
+            "agentType",
+            &agent.as_type_name(),
+            &agent.attributes,
+        ));
+    }
+    for activity in &domain.activities {
+        examples.push(graphql_query(
+            "activities",
+            "activitiesByType",
+            "activityType",
+            &activity.as_type_name(),
+            &activity.attributes,
+        ));
+    }
+    for entity in &domain.entities {
+        examples.push(graphql_query(
+            "entities",
+            "entitiesByType",
+            "entityType",
+            &entity.as_type_name(),
+            &entity.attributes,
+        ));
+    }
+
+    examples
+}
+
+/// The mutations behind [graphql_examples], one for each type and
+/// relationship in the domain
+pub fn graphql_mutations(domain: &ChronicleDomainDef) -> Vec<GraphQlExample> {
+    let mut examples = vec![];
+
+    for agent in &domain.agents {
+        examples.push(graphql_define(
+            "an agent",
+            &agent.as_method_name(),
+            &agent.as_type_name(),
+            &agent.as_cli_name(),
+            &agent.attributes,
+        ));
+    }
+    for activity in &domain.activities {
+        examples.push(graphql_define(
+            "an activity",
+            &activity.as_method_name(),
+            &activity.as_type_name(),
+            &activity.as_cli_name(),
+            &activity.attributes,
+        ));
+    }
+    for entity in &domain.entities {
+        examples.push(graphql_define(
+            "an entity",
+            &entity.as_method_name(),
+            &entity.as_type_name(),
+            &entity.as_cli_name(),
+            &entity.attributes,
+        ));
+    }
+
+    let subjects = Subjects::new(domain);
+    let role = format!(
+        "role: {}",
+        subjects.role.as_deref().unwrap_or("UNSPECIFIED")
+    );
+    let agent = graphql_id("agent", &subjects.agent);
+    let activity = graphql_id("activity", &subjects.activity);
+    let entity = graphql_id("entity", &subjects.entity);
+
+    examples.extend([
+        graphql_mutation(
+            "An agent takes part in an activity",
+            "wasAssociatedWith",
+            &[
+                format!("responsible: {agent}"),
+                format!("activity: {activity}"),
+                role.clone(),
+            ],
+        ),
+        graphql_mutation(
+            "An entity is attributed to an agent",
+            "wasAttributedTo",
+            &[
+                format!("responsible: {agent}"),
+                format!("entity: {entity}"),
+                role.clone(),
+            ],
+        ),
+        graphql_mutation(
+            "An agent acts on behalf of another in an activity",
+            "actedOnBehalfOf",
+            &[
+                format!("responsible: {agent}"),
+                format!("delegate: {}", graphql_id("agent", &subjects.delegate)),
+                format!("activity: {activity}"),
+                role,
+            ],
+        ),
+        graphql_mutation(
+            "An activity uses an entity",
+            "used",
+            &[format!("activity: {activity}"), format!("id: {entity}")],
+        ),
+        graphql_mutation(
+            "An activity generates an entity",
+            "wasGeneratedBy",
+            &[format!("activity: {activity}"), format!("id: {entity}")],
+        ),
+        graphql_mutation(
+            "An activity is informed by another",
+            "wasInformedBy",
+            &[
+                format!("activity: {activity}"),
+                format!(
+                    "informingActivity: {}",
+                    graphql_id("activity", &subjects.informing_activity)
+                ),
+            ],
+        ),
+        graphql_mutation(
+            "An entity is derived from another",
+            "wasDerivedFrom",
+            &[
+                format!("generatedEntity: {entity}"),
+                format!(
+                    "usedEntity: {}",
+                    graphql_id("entity", &subjects.used_entity)
+                ),
+            ],
+        ),
+    ]);
+
+    examples
+}
+
+fn example_attributes(type_name: &str, attributes: &[AttributeDef]) -> Attributes {
+    Attributes {
+        typ: Some(DomaintypeId::from_external_id(type_name)),
+        attributes: attributes
+            .iter()
+            .map(|attribute| {
+                (
+                    attribute.preserve_inflection(),
+                    Attribute::new(attribute.preserve_inflection(), example_value(attribute)),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// The operations recording the same provenance as [graphql_examples] in
+/// `namespace`
+pub fn example_operations(
+    domain: &ChronicleDomainDef,
+    namespace: &NamespaceId,
+) -> Vec<ChronicleOperation> {
+    let mut operations = vec![];
+
+    for agent in &domain.agents {
+        let external_id = example_id(&agent.as_cli_name());
+        operations.push(ChronicleOperation::AgentExists(AgentExists::new(
+            namespace.clone(),
+            &external_id,
+        )));
+        operations.push(ChronicleOperation::SetAttributes(SetAttributes::Agent {
+            namespace: namespace.clone(),
+            id: AgentId::from_external_id(&external_id),
+            attributes: example_attributes(&agent.as_type_name(), &agent.attributes),
+        }));
+    }
+    for activity in &domain.activities {
+        let external_id = example_id(&activity.as_cli_name());
+        operations.push(ChronicleOperation::ActivityExists(ActivityExists {
+            namespace: namespace.clone(),
+            external_id: external_id.as_str().into(),
+        }));
+        operations.push(ChronicleOperation::SetAttributes(SetAttributes::Activity {
+            namespace: namespace.clone(),
+            id: ActivityId::from_external_id(&external_id),
+            attributes: example_attributes(&activity.as_type_name(), &activity.attributes),
+        }));
+    }
+    for entity in &domain.entities {
+        let external_id = example_id(&entity.as_cli_name());
+        operations.push(ChronicleOperation::EntityExists(EntityExists {
+            namespace: namespace.clone(),
+            external_id: external_id.as_str().into(),
+        }));
+        operations.push(ChronicleOperation::SetAttributes(SetAttributes::Entity {
+            namespace: namespace.clone(),
+            id: EntityId::from_external_id(&external_id),
+            attributes: example_attributes(&entity.as_type_name(), &entity.attributes),
+        }));
+    }
+
+    let subjects = Subjects::new(domain);
+    let role = subjects.role.as_ref().map(Role::from);
+    let agent = AgentId::from_external_id(&subjects.agent);
+    let activity = ActivityId::from_external_id(&subjects.activity);
+    let entity = EntityId::from_external_id(&subjects.entity);
+
+    operations.extend([
+        ChronicleOperation::WasAssociatedWith(WasAssociatedWith::new(
+            namespace,
+            &activity,
+            &agent,
+            role.clone(),
+        )),
+        ChronicleOperation::WasAttributedTo(WasAttributedTo::new(
+            namespace,
+            &entity,
+            &agent,
+            role.clone(),
+        )),
+        ChronicleOperation::AgentActsOnBehalfOf(ActsOnBehalfOf::new(
+            namespace,
+            &agent,
+            &AgentId::from_external_id(&subjects.delegate),
+            Some(&activity),
+            role,
+        )),
+        ChronicleOperation::ActivityUses(ActivityUses {
+            namespace: namespace.clone(),
+            id: entity.clone(),
+            activity: activity.clone(),
+        }),
+        ChronicleOperation::WasGeneratedBy(WasGeneratedBy {
+            namespace: namespace.clone(),
+            id: entity.clone(),
+            activity: activity.clone(),
+        }),
+        ChronicleOperation::WasInformedBy(WasInformedBy {
+            namespace: namespace.clone(),
+            activity,
+            informing_activity: ActivityId::from_external_id(&subjects.informing_activity),
+        }),
+        ChronicleOperation::EntityDerive(EntityDerive {
+            namespace: namespace.clone(),
+            id: entity,
+            used_id: EntityId::from_external_id(&subjects.used_entity),
+            activity_id: None,
+            typ: DerivationType::None,
+        }),
+    ]);
+
+    operations
+}
+
+/// [example_operations] as a JSON-LD document that `chronicle import` accepts
+pub fn json_ld_examples(domain: &ChronicleDomainDef, namespace: &NamespaceId) -> Value {
+    Value::Array(
+        example_operations(domain, namespace)
+            .iter()
+            .flat_map(|operation| match operation.to_json().0 {
+                Value::Array(objects) => objects,
+                object => vec![object],
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use common::prov::{operations::ChronicleOperation, NamespaceId};
+    use uuid::Uuid;
+
+    use crate::codegen::{ChronicleDomainDef, PrimitiveType};
+
+    use super::{graphql_examples, graphql_queries, json_ld_examples};
+
+    fn domain() -> ChronicleDomainDef {
+        ChronicleDomainDef::build("test")
+            .with_attribute_type("name", None, PrimitiveType::String)
+            .unwrap()
+            .with_repeated_attribute_type("score", None, PrimitiveType::Int)
+            .unwrap()
+            .with_agent("friend", None, |agent| {
+                agent.with_attribute("name")?.with_attribute("score")
+            })
+            .unwrap()
+            .with_activity("meeting", None, Ok)
+            .unwrap()
+            .with_entity("letter", None, |entity| entity.with_attribute("name"))
+            .unwrap()
+            .with_role("host")
+            .unwrap()
+            .build()
+    }
+
+    #[test]
+    fn graphql_defines_each_type_with_its_attributes() {
+        let examples = graphql_examples(&domain());
+
+        assert!(examples.contains(
+            "  defineFriendAgent(\n    externalId: \"example-friend-agent\"\n    \
+             attributes: {\n      nameAttribute: \"example\"\n      scoreAttribute: [1]\n    }\n"
+        ));
+        assert!(examples.contains(
+            "  defineMeetingActivity(\n    externalId: \"example-meeting-activity\"\n  )"
+        ));
+        assert!(examples
+            .contains("    responsible: { id: \"chronicle:agent:example-friend-agent\" }\n"));
+        assert!(examples.contains("    role: host\n"));
+    }
+
+    #[test]
+    fn graphql_queries_select_each_attribute() {
+        let queries = graphql_queries(&domain());
+
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[0].name, "Query agents of type FriendAgent");
+        assert!(queries[0].document.contains(
+            "  agentsByType(agentType: FriendAgent, first: 10) {\n    nodes {\n      \
+             ... on FriendAgent {\n        id\n        externalId\n        nameAttribute\n        \
+             scoreAttribute\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn json_ld_examples_can_be_imported() {
+        let namespace = NamespaceId::from_external_id("default", Uuid::nil());
+        let document = json_ld_examples(&domain(), &namespace);
+
+        let objects = document.as_array().unwrap();
+        assert_eq!(objects.len(), 13);
+        for object in objects {
+            let operation = ChronicleOperation::from_json(object).await.unwrap();
+            assert_eq!(operation.namespace(), &namespace);
+        }
+    }
+}
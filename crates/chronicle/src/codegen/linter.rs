@@ -118,6 +118,58 @@ fn check_domain_attributes(
     }
 }
 
+fn check_role_holders(domain: &model::DomainFileInput) {
+    let mut is_error = false;
+    for role in domain.roles.iter() {
+        if let model::RoleFileInput::Held { name, agents } = role {
+            for agent in agents {
+                if !domain.agents.contains_key(agent) {
+                    println!("role named {} is held by unknown agent {}", name, agent);
+                    is_error = true;
+                }
+            }
+        }
+    }
+    if is_error {
+        exit(2);
+    }
+}
+
+fn check_recipes(domain: &model::DomainFileInput) {
+    let mut is_error = false;
+    let roles: HashSet<&String> = domain
+        .roles
+        .iter()
+        .map(|role| match role {
+            model::RoleFileInput::Name(name) | model::RoleFileInput::Held { name, .. } => name,
+        })
+        .collect();
+    for (name, recipe) in domain.recipes.iter() {
+        if !domain.activities.contains_key(&recipe.activity) {
+            println!(
+                "recipe named {} records unknown activity {}",
+                name, recipe.activity
+            );
+            is_error = true;
+        }
+        for role in recipe.roles.iter() {
+            if !roles.contains(role) {
+                println!("recipe named {} has unknown role {}", name, role);
+                is_error = true;
+            }
+        }
+        for entity in recipe.uses.iter().chain(recipe.generates.iter()) {
+            if !domain.entities.contains_key(entity) {
+                println!("recipe named {} has unknown entity {}", name, entity);
+                is_error = true;
+            }
+        }
+    }
+    if is_error {
+        exit(2);
+    }
+}
+
 fn check_domain(domain: model::DomainFileInput) {
     let attributes = domain
         .attributes
@@ -127,6 +179,8 @@ fn check_domain(domain: model::DomainFileInput) {
     check_domain_attributes("agent", &attributes, domain.agents.iter().collect());
     check_domain_attributes("entity", &attributes, domain.entities.iter().collect());
     check_domain_attributes("activity", &attributes, domain.activities.iter().collect());
+    check_role_holders(&domain);
+    check_recipes(&domain);
 }
 
 pub fn check_files(filenames: Vec<&str>) {
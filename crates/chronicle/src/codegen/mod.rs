@@ -1,4 +1,6 @@
 #![allow(dead_code)]
+pub mod evolution;
+pub mod examples;
 pub mod linter;
 pub mod model;
 use std::{io::Write, path::Path};
@@ -7,7 +9,9 @@ use genco::prelude::*;
 
 pub use model::{AttributesTypeName, Builder, CliName, PrimitiveType, Property, TypeName};
 
-pub use self::model::{ActivityDef, AgentDef, AttributeDef, ChronicleDomainDef, EntityDef};
+pub use self::model::{
+    ActivityDef, AgentDef, AttributeDef, ChronicleDomainDef, EntityDef, RecipeDef,
+};
 
 fn agent_union_type_name() -> String {
     "Agent".to_owned()
@@ -24,6 +28,8 @@ fn activity_union_type_name() -> String {
 fn gen_attribute_scalars(attributes: &[AttributeDef]) -> rust::Tokens {
     let graphql_new_type = &rust::import("chronicle::async_graphql", "NewType");
     let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
+    let date_time = &rust::import("chronicle::chrono", "DateTime");
+    let utc = &rust::import("chronicle::chrono", "Utc");
     quote! {
         #(for attribute in attributes.iter() =>
         #[derive(Clone, #graphql_new_type)]
@@ -36,12 +42,88 @@ fn gen_attribute_scalars(attributes: &[AttributeDef]) -> rust::Tokens {
                 PrimitiveType::Bool => bool,
                 PrimitiveType::Int => i32,
                 PrimitiveType::JSON => #chronicle_json,
+                PrimitiveType::DateTime => #date_time<#utc>,
+                PrimitiveType::Float => f64,
+                PrimitiveType::Decimal => String,
+                PrimitiveType::Bytes => String,
             }
         ));
        )
     }
 }
 
+/// Convert the stored JSON value of an attribute to the value of its scalar.
+/// Values that do not match the attribute's primitive type resolve to null.
+fn gen_attribute_value(attribute: &AttributeDef) -> rust::Tokens {
+    let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
+    let date_time = &rust::import("chronicle::chrono", "DateTime");
+    let utc = &rust::import("chronicle::chrono", "Utc");
+
+    match attribute.primitive_type {
+        PrimitiveType::String | PrimitiveType::Bytes => quote! {
+            .and_then(|attr| attr.as_str().map(|attr| attr.to_owned()))
+        },
+        PrimitiveType::Bool => quote! {
+            .and_then(|attr| attr.as_bool())
+        },
+        PrimitiveType::Int => quote! {
+            .and_then(|attr| attr.as_i64().map(|attr| attr as _))
+        },
+        PrimitiveType::JSON => quote! {
+            .map(#chronicle_json)
+        },
+        PrimitiveType::DateTime => quote! {
+            .and_then(|attr| attr.as_str().and_then(|attr| #date_time::parse_from_rfc3339(attr).ok()))
+            .map(|attr| attr.with_timezone(&#utc))
+        },
+        PrimitiveType::Float => quote! {
+            .and_then(|attr| attr.as_f64())
+        },
+        PrimitiveType::Decimal => quote! {
+            .and_then(|attr| {
+                attr.as_str()
+                    .map(|attr| attr.to_owned())
+                    .or_else(|| attr.is_number().then(|| attr.to_string()))
+            })
+        },
+    }
+}
+
+/// The type of an attribute's resolver, a list of its scalar if it is repeated
+fn gen_attribute_type(attribute: &AttributeDef) -> rust::Tokens {
+    if attribute.repeated {
+        quote!(Option<Vec<#(attribute.as_scalar_type())>>)
+    } else {
+        quote!(Option<#(attribute.as_scalar_type())>)
+    }
+}
+
+/// Convert the stored JSON value of an attribute to its resolved value. Each
+/// element of a repeated attribute is converted separately, elements that do
+/// not match the attribute's primitive type are omitted.
+fn gen_attribute_resolution(attribute: &AttributeDef) -> rust::Tokens {
+    let serde_value = &rust::import("chronicle::serde_json", "Value");
+
+    if attribute.repeated {
+        quote! {
+            .and_then(|attr| match attr {
+                #serde_value::Array(values) => Some(
+                    values
+                        .into_iter()
+                        .filter_map(|attr| Some(attr)#(gen_attribute_value(attribute)).map(#(attribute.as_scalar_type())))
+                        .collect(),
+                ),
+                _ => None,
+            })
+        }
+    } else {
+        quote! {
+            #(gen_attribute_value(attribute))
+            .map(#(attribute.as_scalar_type()))
+        }
+    }
+}
+
 fn gen_association_and_attribution_unions() -> rust::Tokens {
     let simple_object = &rust::import("chronicle::async_graphql", "SimpleObject").qualified();
 
@@ -321,7 +403,6 @@ fn gen_activity_definition(activity: &ActivityDef) -> rust::Tokens {
     let domain_type_id = &rust::import("chronicle::common::prov", "DomaintypeId");
     let date_time = &rust::import("chronicle::chrono", "DateTime");
     let utc = &rust::import("chronicle::chrono", "Utc");
-    let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
 
     let end_doc = include_str!("../../../../domain_docs/end.md");
     let external_id_doc = include_str!("../../../../domain_docs/external_id.md");
@@ -330,6 +411,10 @@ fn gen_activity_definition(activity: &ActivityDef) -> rust::Tokens {
     let namespace_doc = include_str!("../../../../domain_docs/namespace.md");
     let start_doc = include_str!("../../../../domain_docs/start.md");
     let type_doc = include_str!("../../../../domain_docs/type.md");
+    let created_at_doc = include_str!("../../../../domain_docs/created_at.md");
+    let created_block_doc = include_str!("../../../../domain_docs/created_block.md");
+    let updated_at_doc = include_str!("../../../../domain_docs/updated_at.md");
+    let updated_block_doc = include_str!("../../../../domain_docs/updated_block.md");
     let used_doc = include_str!("../../../../domain_docs/used.md");
     let was_associated_with_doc = include_str!("../../../../domain_docs/was_associated_with.md");
     let was_informed_by_doc = include_str!("../../../../domain_docs/was_informed_by.md");
@@ -376,6 +461,26 @@ fn gen_activity_definition(activity: &ActivityDef) -> rust::Tokens {
             self.0.domaintype.as_deref().map(#domain_type_id::from_external_id)
         }
 
+        #[doc = #_(#created_at_doc)]
+        async fn created_at(&self) -> Option<#date_time<#utc>> {
+            self.0.created_at.map(|x| #date_time::from_naive_utc_and_offset(x, #utc))
+        }
+
+        #[doc = #_(#created_block_doc)]
+        async fn created_block(&self) -> Option<&str> {
+            self.0.created_block.as_deref()
+        }
+
+        #[doc = #_(#updated_at_doc)]
+        async fn updated_at(&self) -> Option<#date_time<#utc>> {
+            self.0.updated_at.map(|x| #date_time::from_naive_utc_and_offset(x, #utc))
+        }
+
+        #[doc = #_(#updated_block_doc)]
+        async fn updated_block(&self) -> Option<&str> {
+            self.0.updated_block.as_deref()
+        }
+
         #[doc = #_(#was_associated_with_doc)]
         async fn was_associated_with<'a>(
             &self,
@@ -429,33 +534,11 @@ fn gen_activity_definition(activity: &ActivityDef) -> rust::Tokens {
         #(if attribute.doc.is_some() {
             #[doc = #_(#(attribute.doc.as_ref().map(|s| s.to_owned()).unwrap_or_default()))]
         })
-        async fn #(attribute.as_property())<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<#(attribute.as_scalar_type())>> {
-            Ok(#(match attribute.primitive_type {
-              PrimitiveType::String =>
-                #activity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_str().map(|attr| attr.to_owned()))
-                    .map(#(attribute.as_scalar_type())),
-              PrimitiveType::Bool =>
-                #activity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_bool())
-                    .map(#(attribute.as_scalar_type())),
-              PrimitiveType::Int =>
-                #activity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_i64().map(|attr| attr as _))
-                    .map(#(attribute.as_scalar_type())),
-              PrimitiveType::JSON =>
-                #activity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .map(#chronicle_json)
-                    .map(#(attribute.as_scalar_type()))
-        }))
+        async fn #(attribute.as_property())<'a>(&self, ctx: &#context<'a>) -> #async_result<#(gen_attribute_type(attribute))> {
+            Ok(#activity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
+                .await
+                .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+                #(gen_attribute_resolution(attribute)))
         })
     }
     }
@@ -466,12 +549,13 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
     let entity_impl = &rust::import("chronicle::api::chronicle_graphql", "entity").qualified();
     let namespace = &rust::import("chronicle::api::chronicle_graphql", "Namespace").qualified();
     let entity_id = &rust::import("chronicle::common::prov", "EntityId").qualified();
+    let date_time = &rust::import("chronicle::chrono", "DateTime");
+    let utc = &rust::import("chronicle::chrono", "Utc");
 
     let object = rust::import("chronicle::async_graphql", "Object").qualified();
     let async_result = &rust::import("chronicle::async_graphql", "Result").qualified();
     let context = &rust::import("chronicle::async_graphql", "Context").qualified();
     let domain_type_id = &rust::import("chronicle::common::prov", "DomaintypeId");
-    let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
     let async_graphql_error_extensions =
         &rust::import("chronicle::async_graphql", "ErrorExtensions").qualified();
 
@@ -480,6 +564,10 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
     let id_doc = include_str!("../../../../domain_docs/id.md");
     let namespace_doc = include_str!("../../../../domain_docs/namespace.md");
     let type_doc = include_str!("../../../../domain_docs/type.md");
+    let created_at_doc = include_str!("../../../../domain_docs/created_at.md");
+    let created_block_doc = include_str!("../../../../domain_docs/created_block.md");
+    let updated_at_doc = include_str!("../../../../domain_docs/updated_at.md");
+    let updated_block_doc = include_str!("../../../../domain_docs/updated_block.md");
     let was_attributed_to_doc = include_str!("../../../../domain_docs/was_attributed_to.md");
     let was_derived_from_doc = include_str!("../../../../domain_docs/was_derived_from.md");
     let was_generated_by_doc = include_str!("../../../../domain_docs/was_generated_by.md");
@@ -518,6 +606,26 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
             self.0.domaintype.as_deref().map(#domain_type_id::from_external_id)
         }
 
+        #[doc = #_(#created_at_doc)]
+        async fn created_at(&self) -> Option<#date_time<#utc>> {
+            self.0.created_at.map(|x| #date_time::from_naive_utc_and_offset(x, #utc))
+        }
+
+        #[doc = #_(#created_block_doc)]
+        async fn created_block(&self) -> Option<&str> {
+            self.0.created_block.as_deref()
+        }
+
+        #[doc = #_(#updated_at_doc)]
+        async fn updated_at(&self) -> Option<#date_time<#utc>> {
+            self.0.updated_at.map(|x| #date_time::from_naive_utc_and_offset(x, #utc))
+        }
+
+        #[doc = #_(#updated_block_doc)]
+        async fn updated_block(&self) -> Option<&str> {
+            self.0.updated_block.as_deref()
+        }
+
         #[doc = #_(#was_attributed_to_doc)]
         async fn was_attributed_to<'a>(
             &self,
@@ -596,33 +704,11 @@ fn gen_entity_definition(entity: &EntityDef) -> rust::Tokens {
             #[doc = #_(#(attribute.doc.as_ref().map(|s| s.to_owned()).unwrap_or_default()))]
         })
         #[graphql(name = #_(#(attribute.preserve_inflection())))]
-        async fn #(attribute.as_property())<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<#(attribute.as_scalar_type())>> {
-            Ok(#(match attribute.primitive_type {
-              PrimitiveType::String =>
-                #entity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_str().map(|attr| attr.to_owned()))
-                    .map(#(attribute.as_scalar_type())),
-              PrimitiveType::Bool =>
-                #entity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_bool())
-                    .map(#(attribute.as_scalar_type())),
-              PrimitiveType::Int =>
-                #entity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_i64().map(|attr| attr as _))
-                    .map(#(attribute.as_scalar_type())),
-            PrimitiveType::JSON =>
-                #entity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .map(#chronicle_json)
-                    .map(#(attribute.as_scalar_type()))
-                }))
+        async fn #(attribute.as_property())<'a>(&self, ctx: &#context<'a>) -> #async_result<#(gen_attribute_type(attribute))> {
+            Ok(#entity_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
+                .await
+                .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+                #(gen_attribute_resolution(attribute)))
             })
         }
     }
@@ -638,8 +724,9 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
     let async_result = &rust::import("chronicle::async_graphql", "Result").qualified();
     let context = &rust::import("chronicle::async_graphql", "Context").qualified();
     let agent_id = &rust::import("chronicle::common::prov", "AgentId");
+    let date_time = &rust::import("chronicle::chrono", "DateTime");
+    let utc = &rust::import("chronicle::chrono", "Utc");
     let domain_type_id = &rust::import("chronicle::common::prov", "DomaintypeId");
-    let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
     let async_graphql_error_extensions =
         &rust::import("chronicle::async_graphql", "ErrorExtensions").qualified();
 
@@ -650,6 +737,10 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
     let identity_doc = include_str!("../../../../domain_docs/identity.md");
     let namespace_doc = include_str!("../../../../domain_docs/namespace.md");
     let type_doc = include_str!("../../../../domain_docs/type.md");
+    let created_at_doc = include_str!("../../../../domain_docs/created_at.md");
+    let created_block_doc = include_str!("../../../../domain_docs/created_block.md");
+    let updated_at_doc = include_str!("../../../../domain_docs/updated_at.md");
+    let updated_block_doc = include_str!("../../../../domain_docs/updated_block.md");
 
     quote! {
 
@@ -684,8 +775,8 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
         }
 
         #[doc = #_(#acted_on_behalf_of_doc)]
-        async fn acted_on_behalf_of<'a>(&self, ctx: &#context<'a>) -> #async_result<Vec<AgentRef>> {
-            Ok(#agent_impl::acted_on_behalf_of(self.0.id, ctx)
+        async fn acted_on_behalf_of<'a>(&self, ctx: &#context<'a>, at: Option<#date_time<#utc>>) -> #async_result<Vec<AgentRef>> {
+            Ok(#agent_impl::acted_on_behalf_of(self.0.id, at, ctx)
                 .await
                 .map_err(|e| #async_graphql_error_extensions::extend(&e))?
                 .into_iter()
@@ -711,33 +802,11 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
             #[doc = #_(#(attribute.doc.as_ref().map(|s| s.to_owned()).unwrap_or_default()))]
         })
         #[graphql(name = #_(#(attribute.preserve_inflection())))]
-        async fn #(attribute.as_property())<'a>(&self, ctx: &#context<'a>) -> #async_result<Option<#(attribute.as_scalar_type())>> {
-            Ok(#(match attribute.primitive_type {
-              PrimitiveType::String =>
-                #agent_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_str().map(|attr| attr.to_owned()))
-                    .map(#(attribute.as_scalar_type())),
-              PrimitiveType::Bool =>
-                #agent_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_bool())
-                    .map(#(attribute.as_scalar_type())),
-              PrimitiveType::Int =>
-                #agent_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .and_then(|attr| attr.as_i64().map(|attr| attr as _))
-                    .map(#(attribute.as_scalar_type())),
-              PrimitiveType::JSON =>
-                #agent_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
-                    .await
-                    .map_err(|e| #async_graphql_error_extensions::extend(&e))?
-                    .map(#chronicle_json)
-                    .map(#(attribute.as_scalar_type()))
-            }))
+        async fn #(attribute.as_property())<'a>(&self, ctx: &#context<'a>) -> #async_result<#(gen_attribute_type(attribute))> {
+            Ok(#agent_impl::load_attribute(self.0.id, #_(#(attribute.preserve_inflection())), ctx)
+                .await
+                .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+                #(gen_attribute_resolution(attribute)))
         })
 
         #[doc = #_(#type_doc)]
@@ -745,6 +814,26 @@ fn gen_agent_definition(agent: &AgentDef) -> rust::Tokens {
         async fn typ(&self) -> Option<#domain_type_id> {
             self.0.domaintype.as_deref().map(#domain_type_id::from_external_id)
         }
+
+        #[doc = #_(#created_at_doc)]
+        async fn created_at(&self) -> Option<#date_time<#utc>> {
+            self.0.created_at.map(|x| #date_time::from_naive_utc_and_offset(x, #utc))
+        }
+
+        #[doc = #_(#created_block_doc)]
+        async fn created_block(&self) -> Option<&str> {
+            self.0.created_block.as_deref()
+        }
+
+        #[doc = #_(#updated_at_doc)]
+        async fn updated_at(&self) -> Option<#date_time<#utc>> {
+            self.0.updated_at.map(|x| #date_time::from_naive_utc_and_offset(x, #utc))
+        }
+
+        #[doc = #_(#updated_block_doc)]
+        async fn updated_block(&self) -> Option<&str> {
+            self.0.updated_block.as_deref()
+        }
     }
     }
 }
@@ -810,9 +899,13 @@ fn gen_attribute_definition(typ: impl TypeName, attributes: &[AttributeDef]) ->
         &rust::import("chronicle::common::attributes", "Attribute").qualified();
     let abstract_attributes =
         &rust::import("chronicle::common::attributes", "Attributes").qualified();
+    let attribute_type_error =
+        &rust::import("chronicle::common::attributes", "AttributeTypeError").qualified();
     let input_object = rust::import("chronicle::async_graphql", "InputObject").qualified();
     let domain_type_id = rust::import("chronicle::common::prov", "DomaintypeId");
     let serde_value = &rust::import("chronicle::serde_json", "Value");
+    let date_time = &rust::import("chronicle::chrono", "DateTime");
+    let utc = &rust::import("chronicle::chrono", "Utc");
 
     if attributes.is_empty() {
         return quote! {};
@@ -824,31 +917,45 @@ fn gen_attribute_definition(typ: impl TypeName, attributes: &[AttributeDef]) ->
         pub struct #(typ.attributes_type_name_preserve_inflection()) {
             #(for attribute in attributes =>
                 #[graphql(name = #_(#(attribute.preserve_inflection())))]
-                pub #(&attribute.as_property()): #(
+                pub #(&attribute.as_property()): #(if attribute.repeated { Vec< })#(
                     match attribute.primitive_type {
                         PrimitiveType::String => String,
                         PrimitiveType::Bool => bool,
                         PrimitiveType::Int => i32,
                         PrimitiveType::JSON => Value,
-                    }),
+                        PrimitiveType::DateTime => #date_time<#utc>,
+                        PrimitiveType::Float => f64,
+                        PrimitiveType::Decimal => String,
+                        PrimitiveType::Bytes => String,
+                    })#(if attribute.repeated { > }),
             )
         }
 
 
-        #[allow(clippy::from_over_into)]
         #[allow(clippy::useless_conversion)]
-        impl From<#(typ.attributes_type_name_preserve_inflection())> for #abstract_attributes{
-            fn from(attributes: #(typ.attributes_type_name_preserve_inflection())) -> Self {
-                #abstract_attributes {
+        impl TryFrom<#(typ.attributes_type_name_preserve_inflection())> for #abstract_attributes{
+            type Error = #attribute_type_error;
+
+            fn try_from(attributes: #(typ.attributes_type_name_preserve_inflection())) -> Result<Self, Self::Error> {
+                Ok(#abstract_attributes {
                     typ: Some(#domain_type_id::from_external_id(#_(#(typ.as_type_name())))),
                     attributes: vec![
                     #(for attribute in attributes =>
                         (#_(#(&attribute.preserve_inflection())).to_owned() ,
-                            #abstract_attribute::new(#_(#(&attribute.preserve_inflection())),
-                            #serde_value::from(attributes.#(&attribute.as_property())))),
+                            #abstract_attribute::typed(#_(#(&attribute.preserve_inflection())),
+                            #_(#(format!("{:?}", attribute.primitive_type))),
+                            #(attribute.repeated),
+                            #serde_value::from(attributes.#(&attribute.as_property())#(
+                                if attribute.primitive_type == PrimitiveType::DateTime {
+                                    #(if attribute.repeated {
+                                        .into_iter().map(|attr| attr.to_rfc3339()).collect::<Vec<_>>()
+                                    } else {
+                                        .to_rfc3339()
+                                    })
+                                })))?),
                     )
                     ].into_iter().collect(),
-                }
+                })
             }
         }
     }
@@ -964,7 +1071,7 @@ fn gen_mappers(domain: &ChronicleDomainDef) -> rust::Tokens {
     }
     }
 }
-fn gen_query() -> rust::Tokens {
+fn gen_query(domain: &ChronicleDomainDef) -> rust::Tokens {
     let query_impl = &rust::import("chronicle::api::chronicle_graphql", "query").qualified();
 
     let graphql_object = &rust::import("chronicle::async_graphql", "Object");
@@ -978,6 +1085,36 @@ fn gen_query() -> rust::Tokens {
     let agent_id = &rust::import("chronicle::common::prov", "AgentIdOrExternal");
     let entity_id = &rust::import("chronicle::common::prov", "EntityIdOrExternal");
     let activity_id = &rust::import("chronicle::common::prov", "ActivityIdOrExternal");
+    let chronicle_json = &rust::import("chronicle::common::prov", "ChronicleJSON");
+    let export_impl = &rust::import("chronicle::api::chronicle_graphql", "export").qualified();
+    let export_job =
+        &rust::import("chronicle::api::chronicle_graphql::export", "ExportJob").qualified();
+    let server_info_impl =
+        &rust::import("chronicle::api::chronicle_graphql", "server_info").qualified();
+    let server_info = &rust::import(
+        "chronicle::api::chronicle_graphql::server_info",
+        "ServerInfo",
+    )
+    .qualified();
+    let receipt_impl = &rust::import("chronicle::api::chronicle_graphql", "receipt").qualified();
+    let receipt =
+        &rust::import("chronicle::api::chronicle_graphql::receipt", "Receipt").qualified();
+    let audit_impl = &rust::import("chronicle::api::chronicle_graphql", "audit").qualified();
+    let explain_impl = &rust::import("chronicle::api::chronicle_graphql", "explain").qualified();
+    let explanation =
+        &rust::import("chronicle::api::chronicle_graphql::explain", "Explanation").qualified();
+    let audit_record =
+        &rust::import("chronicle::api::chronicle_graphql::audit", "AuditRecord").qualified();
+    let search_impl = &rust::import("chronicle::api::chronicle_graphql", "search").qualified();
+    let federation_impl =
+        &rust::import("chronicle::api::chronicle_graphql", "federation").qualified();
+    let namespace_key = &rust::import(
+        "chronicle::api::chronicle_graphql::federation",
+        "NamespaceKey",
+    )
+    .qualified();
+    let search_hit =
+        &rust::import("chronicle::api::chronicle_graphql::search", "SearchHit").qualified();
     let empty_fields =
         &rust::import("chronicle::async_graphql::connection", "EmptyFields").qualified();
 
@@ -991,6 +1128,13 @@ fn gen_query() -> rust::Tokens {
     let agents_by_type_doc = include_str!("../../../../domain_docs/agents_by_type.md");
     let entities_by_type_doc = include_str!("../../../../domain_docs/entities_by_type.md");
     let entity_by_id_doc = include_str!("../../../../domain_docs/entity_by_id.md");
+    let lineage_doc = include_str!("../../../../domain_docs/lineage.md");
+    let export_job_doc = include_str!("../../../../domain_docs/export_job.md");
+    let server_info_doc = include_str!("../../../../domain_docs/server_info.md");
+    let receipt_doc = include_str!("../../../../domain_docs/receipt.md");
+    let search_doc = include_str!("../../../../domain_docs/search.md");
+    let audit_trail_doc = include_str!("../../../../domain_docs/audit_trail.md");
+    let explain_doc = include_str!("../../../../domain_docs/explain.md");
 
     quote! {
     #[derive(Copy, Clone)]
@@ -1063,6 +1207,7 @@ fn gen_query() -> rust::Tokens {
         ctx: &#graphql_context<'a>,
         agent_type: AgentType,
         namespace: Option<#graphql_id>,
+        modified_since: Option<DateTime<Utc>>,
         after: Option<String>,
         before: Option<String>,
         first: Option<i32>,
@@ -1072,6 +1217,7 @@ fn gen_query() -> rust::Tokens {
             ctx,
             agent_type.into(),
             namespace,
+            modified_since,
             after,
             before,
             first,
@@ -1101,6 +1247,7 @@ fn gen_query() -> rust::Tokens {
         ctx: &#graphql_context<'a>,
         activity_type: ActivityType,
         namespace: Option<#graphql_id>,
+        modified_since: Option<DateTime<Utc>>,
         after: Option<String>,
         before: Option<String>,
         first: Option<i32>,
@@ -1110,6 +1257,7 @@ fn gen_query() -> rust::Tokens {
             ctx,
             activity_type.into(),
             namespace,
+            modified_since,
             after,
             before,
             first,
@@ -1139,6 +1287,8 @@ fn gen_query() -> rust::Tokens {
         ctx: &#graphql_context<'a>,
         entity_type: EntityType,
         namespace: Option<#graphql_id>,
+        modified_since: Option<DateTime<Utc>>,
+        include_expired: Option<bool>,
         after: Option<String>,
         before: Option<String>,
         first: Option<i32>,
@@ -1148,6 +1298,8 @@ fn gen_query() -> rust::Tokens {
             ctx,
             entity_type.into(),
             namespace,
+            modified_since,
+            include_expired,
             after,
             before,
             first,
@@ -1208,7 +1360,266 @@ fn gen_query() -> rust::Tokens {
             .map_err(|e| #async_graphql_error_extensions::extend(&e))?
             .map(map_entity_to_domain_type))
     }
+
+    #[doc = #_(#lineage_doc)]
+    pub async fn lineage<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        id: #entity_id,
+        depth: u32,
+        namespace: Option<String>,
+    ) -> #graphql_result<#chronicle_json> {
+        #query_impl::lineage(ctx, id.into(), depth, namespace)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
+
+    #[doc = #_(#explain_doc)]
+    pub async fn explain<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        agent_id: Option<#agent_id>,
+        activity_id: Option<#activity_id>,
+        entity_id: Option<#entity_id>,
+        depth: Option<u32>,
+        namespace: Option<String>,
+    ) -> #graphql_result<#explanation> {
+        #explain_impl::explain(
+            ctx,
+            agent_id.map(Into::into),
+            activity_id.map(Into::into),
+            entity_id.map(Into::into),
+            depth,
+            namespace,
+        )
+        .await
+        .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
+
+    #[doc = #_(#export_job_doc)]
+    pub async fn export_job<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        id: #graphql_id,
+    ) -> #graphql_result<Option<#export_job>> {
+        #export_impl::export_job(ctx, id)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
+
+    #[doc = #_(#server_info_doc)]
+    pub async fn server_info<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+    ) -> #graphql_result<#server_info> {
+        #server_info_impl::server_info(ctx)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
+
+    #[doc = #_(#receipt_doc)]
+    pub async fn receipt<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        tx_id: String,
+    ) -> #graphql_result<Option<#receipt>> {
+        #receipt_impl::receipt(ctx, tx_id)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
+
+    #[doc = #_(#search_doc)]
+    pub async fn search<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        query: String,
+        namespace: Option<String>,
+        first: Option<i32>,
+    ) -> #graphql_result<Vec<#search_hit>> {
+        #search_impl::search(ctx, query, namespace, first)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
+
+    #[doc = #_(#audit_trail_doc)]
+    pub async fn audit_trail<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        agent_id: Option<#agent_id>,
+        activity_id: Option<#activity_id>,
+        entity_id: Option<#entity_id>,
+        namespace: Option<String>,
+        first: Option<i32>,
+    ) -> #graphql_result<Vec<#audit_record>> {
+        #audit_impl::audit_trail(
+            ctx,
+            agent_id.map(Into::into),
+            activity_id.map(Into::into),
+            entity_id.map(Into::into),
+            namespace,
+            first,
+        )
+        .await
+        .map_err(|e| #async_graphql_error_extensions::extend(&e))
+    }
+
+    #[graphql(entity)]
+    pub async fn find_prov_agent<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        #[graphql(key)] external_id: String,
+        #[graphql(key)] namespace: #namespace_key,
+    ) -> #graphql_result<Option<ProvAgent>> {
+        Ok(#federation_impl::agent(ctx, external_id, namespace, None)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+            .map(ProvAgent))
+    }
+
+    #(for agent in domain.agents.iter() =>
+    #[graphql(entity)]
+    pub async fn #(format!("find_{}", agent.as_property()))<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        #[graphql(key)] external_id: String,
+        #[graphql(key)] namespace: #namespace_key,
+    ) -> #graphql_result<Option<#(agent.as_type_name())>> {
+        Ok(#federation_impl::agent(ctx, external_id, namespace, Some(#_(#(agent.as_type_name()))))
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+            .map(#(agent.as_type_name())))
+    }
+    )
+
+    #[graphql(entity)]
+    pub async fn find_prov_activity<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        #[graphql(key)] external_id: String,
+        #[graphql(key)] namespace: #namespace_key,
+    ) -> #graphql_result<Option<ProvActivity>> {
+        Ok(#federation_impl::activity(ctx, external_id, namespace, None)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+            .map(ProvActivity))
+    }
+
+    #(for activity in domain.activities.iter() =>
+    #[graphql(entity)]
+    pub async fn #(format!("find_{}", activity.as_property()))<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        #[graphql(key)] external_id: String,
+        #[graphql(key)] namespace: #namespace_key,
+    ) -> #graphql_result<Option<#(activity.as_type_name())>> {
+        Ok(#federation_impl::activity(ctx, external_id, namespace, Some(#_(#(activity.as_type_name()))))
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+            .map(#(activity.as_type_name())))
+    }
+    )
+
+    #[graphql(entity)]
+    pub async fn find_prov_entity<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        #[graphql(key)] external_id: String,
+        #[graphql(key)] namespace: #namespace_key,
+    ) -> #graphql_result<Option<ProvEntity>> {
+        Ok(#federation_impl::entity(ctx, external_id, namespace, None)
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+            .map(ProvEntity))
     }
+
+    #(for entity in domain.entities.iter() =>
+    #[graphql(entity)]
+    pub async fn #(format!("find_{}", entity.as_property()))<'a>(
+        &self,
+        ctx: &#graphql_context<'a>,
+        #[graphql(key)] external_id: String,
+        #[graphql(key)] namespace: #namespace_key,
+    ) -> #graphql_result<Option<#(entity.as_type_name())>> {
+        Ok(#federation_impl::entity(ctx, external_id, namespace, Some(#_(#(entity.as_type_name()))))
+            .await
+            .map_err(|e| #async_graphql_error_extensions::extend(&e))?
+            .map(#(entity.as_type_name())))
+    }
+    )
+    }
+    }
+}
+
+fn gen_recipe_mutation(recipe: &RecipeDef, activity: &ActivityDef) -> rust::Tokens {
+    let graphql_result = &rust::import("chronicle::async_graphql", "Result");
+    let graphql_context = &rust::import("chronicle::async_graphql", "Context");
+    let async_graphql_error_extensions =
+        &rust::import("chronicle::async_graphql", "ErrorExtensions").qualified();
+
+    let submission = &rust::import("chronicle::api::chronicle_graphql", "Submission");
+    let impls = &rust::import("chronicle::api::chronicle_graphql", "mutation");
+
+    let entity_id = &rust::import("chronicle::common::prov", "EntityIdOrExternal");
+    let agent_id = &rust::import("chronicle::common::prov", "AgentIdOrExternal");
+    let domain_type_id = &rust::import("chronicle::common::prov", "DomaintypeId");
+    let prov_role = &rust::import("chronicle::common::prov", "Role").qualified();
+
+    let abstract_attributes =
+        &rust::import("chronicle::common::attributes", "Attributes").qualified();
+
+    let recipe_doc = include_str!("../../../../domain_docs/recipe.md");
+
+    quote! {
+        #[doc = #_(#recipe_doc)]
+        #(if recipe.doc.is_some() {
+            #[doc = ""]
+            #[doc = #_(#(recipe.doc.as_ref().map(|s| s.to_owned()).unwrap_or_default()))]
+        })
+        #[graphql(name = #_(#(recipe.as_method_name())))]
+        #[allow(clippy::too_many_arguments)]
+        pub async fn #(recipe.as_property())<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            external_id: String,
+            namespace: Option<String>,
+            #(if !activity.attributes.is_empty() {
+                attributes: #(activity.attributes_type_name_preserve_inflection()),
+            })
+            time: Option<DateTime<Utc>>,
+            #(for role in recipe.roles.iter() =>
+                #(RecipeDef::role_parameter(role)): #agent_id,
+            )
+            #(for entity in recipe.uses.iter() =>
+                #(RecipeDef::used_parameter(entity)): #entity_id,
+            )
+            #(for entity in recipe.generates.iter() =>
+                #(RecipeDef::generated_parameter(entity)): #entity_id,
+            )
+        ) -> async_graphql::#graphql_result<#submission> {
+            #(if activity.attributes.is_empty() {
+                let attributes = #abstract_attributes::type_only(Some(
+                    #domain_type_id::from_external_id(#_(#(activity.as_type_name())))
+                ));
+            } else {
+                let attributes = attributes.try_into().map_err(|e| #impls::attribute_type_error(ctx, e))?;
+            })
+            let associations = vec![
+                #(for role in recipe.roles.iter() =>
+                    (#(RecipeDef::role_parameter(role)).into(), Some(#prov_role::from(#_(#role)))),
+                )
+            ];
+            let used = vec![
+                #(for entity in recipe.uses.iter() =>
+                    #(RecipeDef::used_parameter(entity)).into(),
+                )
+            ];
+            let generated = vec![
+                #(for entity in recipe.generates.iter() =>
+                    #(RecipeDef::generated_parameter(entity)).into(),
+                )
+            ];
+            #impls::recipe(ctx, external_id, namespace, attributes, time, associations, used, generated).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
     }
 }
 
@@ -1222,6 +1633,14 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
 
     let submission = &rust::import("chronicle::api::chronicle_graphql", "Submission");
     let impls = &rust::import("chronicle::api::chronicle_graphql", "mutation");
+    let export_impl = &rust::import("chronicle::api::chronicle_graphql", "export").qualified();
+    let export_format =
+        &rust::import("chronicle::api::chronicle_graphql::export", "ExportFormat").qualified();
+    let export_job =
+        &rust::import("chronicle::api::chronicle_graphql::export", "ExportJob").qualified();
+    let graphql_id = &rust::import("chronicle::async_graphql", "ID");
+    let date_time = &rust::import("chronicle::chrono", "DateTime");
+    let utc = &rust::import("chronicle::chrono", "Utc");
 
     let entity_id = &rust::import("chronicle::common::prov", "EntityIdOrExternal");
     let agent_id = &rust::import("chronicle::common::prov", "AgentIdOrExternal");
@@ -1239,7 +1658,12 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
     let prov_activity_doc = include_str!("../../../../domain_docs/prov_activity.md");
     let prov_agent_doc = include_str!("../../../../domain_docs/prov_agent.md");
     let prov_entity_doc = include_str!("../../../../domain_docs/prov_entity.md");
+    let retract_association_doc = include_str!("../../../../domain_docs/retract_association.md");
+    let retract_attribute_doc = include_str!("../../../../domain_docs/retract_attribute.md");
+    let retract_attribution_doc = include_str!("../../../../domain_docs/retract_attribution.md");
     let start_doc = include_str!("../../../../domain_docs/start_activity.md");
+    let start_export_doc = include_str!("../../../../domain_docs/start_export.md");
+    let cancel_export_doc = include_str!("../../../../domain_docs/cancel_export.md");
     let used_doc = include_str!("../../../../domain_docs/used.md");
     let was_associated_with_doc = include_str!("../../../../domain_docs/was_associated_with.md");
     let was_attributed_to_doc = include_str!("../../../../domain_docs/was_attributed_to.md");
@@ -1249,6 +1673,18 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
     let was_quoted_from_doc = include_str!("../../../../domain_docs/was_quoted_from.md");
     let was_revision_of_doc = include_str!("../../../../domain_docs/was_revision_of.md");
 
+    let recipes = domain
+        .recipes
+        .iter()
+        .filter_map(|recipe| {
+            domain
+                .activities
+                .iter()
+                .find(|activity| activity.external_id == recipe.activity)
+                .map(|activity| gen_recipe_mutation(recipe, activity))
+        })
+        .collect::<Vec<_>>();
+
     quote! {
     #[derive(Copy, Clone)]
     pub struct Mutation;
@@ -1297,7 +1733,7 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
                 namespace: Option<String>,
                 attributes: #(agent.attributes_type_name_preserve_inflection()),
             ) -> async_graphql::#graphql_result<#submission> {
-                #impls::agent(ctx, external_id, namespace, attributes.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+                #impls::agent(ctx, external_id, namespace, attributes.try_into().map_err(|e| #impls::attribute_type_error(ctx, e))?).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
             }
             }
             )
@@ -1345,7 +1781,7 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
                 namespace: Option<String>,
                 attributes: #(activity.attributes_type_name_preserve_inflection()),
             ) -> async_graphql::#graphql_result<#submission> {
-                #impls::activity(ctx, external_id, namespace, attributes.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+                #impls::activity(ctx, external_id, namespace, attributes.try_into().map_err(|e| #impls::attribute_type_error(ctx, e))?).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
             }
             }
             )
@@ -1393,13 +1829,14 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
                 namespace: Option<String>,
                 attributes: #(entity.attributes_type_name_preserve_inflection()),
             ) -> async_graphql::#graphql_result<#submission> {
-                #impls::entity(ctx, external_id, namespace, attributes.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+                #impls::entity(ctx, external_id, namespace, attributes.try_into().map_err(|e| #impls::attribute_type_error(ctx, e))?).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
             }
             }
             )
         )
 
         #[doc = #_(#acted_on_behalf_of_doc)]
+        #[allow(clippy::too_many_arguments)]
         pub async fn acted_on_behalf_of<'a>(
             &self,
             ctx: &#graphql_context<'a>,
@@ -1408,9 +1845,11 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
             delegate: #agent_id,
             activity: Option<#activity_id>,
             role: RoleType,
+            valid_from: Option<DateTime<Utc>>,
+            valid_until: Option<DateTime<Utc>>,
         ) -> async_graphql::#graphql_result<#submission> {
             let activity = activity.map(|activity| activity.into());
-            #impls::acted_on_behalf_of(ctx, namespace, responsible.into(), delegate.into(), activity, role.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+            #impls::acted_on_behalf_of(ctx, namespace, responsible.into(), delegate.into(), activity, role.into(), valid_from, valid_until).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
         }
 
         #[doc = #_(#was_derived_from_doc)]
@@ -1510,15 +1949,41 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
             &self,
             ctx: &#graphql_context<'a>,
             namespace: Option<String>,
-            responsible: #agent_id,
+            responsible: Option<#agent_id>,
             activity: #activity_id,
             role: RoleType
         ) -> async_graphql::#graphql_result<#submission> {
-            #impls::was_associated_with(ctx, namespace, responsible.into(), activity.into(), role.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+            let responsible = responsible.map(|agent| agent.into());
+            #impls::was_associated_with(ctx, namespace, responsible, activity.into(), role.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
         }
 
         #[doc = #_(#was_attributed_to_doc)]
         pub async fn was_attributed_to<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            namespace: Option<String>,
+            responsible: Option<#agent_id>,
+            entity: #entity_id,
+            role: RoleType
+        ) -> async_graphql::#graphql_result<#submission> {
+            let responsible = responsible.map(|agent| agent.into());
+            #impls::was_attributed_to(ctx, namespace, responsible, entity.into(), role.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
+        #[doc = #_(#retract_association_doc)]
+        pub async fn retract_association<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            namespace: Option<String>,
+            responsible: #agent_id,
+            activity: #activity_id,
+            role: RoleType
+        ) -> async_graphql::#graphql_result<#submission> {
+            #impls::retract_association(ctx, namespace, responsible.into(), activity.into(), role.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
+        #[doc = #_(#retract_attribution_doc)]
+        pub async fn retract_attribution<'a>(
             &self,
             ctx: &#graphql_context<'a>,
             namespace: Option<String>,
@@ -1526,7 +1991,40 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
             entity: #entity_id,
             role: RoleType
         ) -> async_graphql::#graphql_result<#submission> {
-            #impls::was_attributed_to(ctx, namespace, responsible.into(), entity.into(), role.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+            #impls::retract_attribution(ctx, namespace, responsible.into(), entity.into(), role.into()).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
+        #[doc = #_(#retract_attribute_doc)]
+        pub async fn retract_agent_attribute<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            id: #agent_id,
+            namespace: Option<String>,
+            attribute: String,
+        ) -> async_graphql::#graphql_result<#submission> {
+            #impls::retract_agent_attribute(ctx, id.into(), namespace, attribute).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
+        #[doc = #_(#retract_attribute_doc)]
+        pub async fn retract_activity_attribute<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            id: #activity_id,
+            namespace: Option<String>,
+            attribute: String,
+        ) -> async_graphql::#graphql_result<#submission> {
+            #impls::retract_activity_attribute(ctx, id.into(), namespace, attribute).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
+        #[doc = #_(#retract_attribute_doc)]
+        pub async fn retract_entity_attribute<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            id: #entity_id,
+            namespace: Option<String>,
+            attribute: String,
+        ) -> async_graphql::#graphql_result<#submission> {
+            #impls::retract_entity_attribute(ctx, id.into(), namespace, attribute).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
         }
 
         #[doc = #_(#used_doc)]
@@ -1561,6 +2059,29 @@ fn gen_mutation(domain: &ChronicleDomainDef) -> rust::Tokens {
         ) -> async_graphql::#graphql_result<#submission> {
             #impls::was_generated_by(ctx, activity.into(), id.into(), namespace).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
         }
+
+        #(for recipe in recipes => #recipe)
+
+        #[doc = #_(#start_export_doc)]
+        pub async fn start_export<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            format: #export_format,
+            modified_since: Option<#date_time<#utc>>,
+            callback: Option<String>,
+            namespace: Option<String>,
+        ) -> async_graphql::#graphql_result<#export_job> {
+            #export_impl::start_export(ctx, namespace, format, modified_since, callback).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
+
+        #[doc = #_(#cancel_export_doc)]
+        pub async fn cancel_export<'a>(
+            &self,
+            ctx: &#graphql_context<'a>,
+            id: #graphql_id,
+        ) -> async_graphql::#graphql_result<#export_job> {
+            #export_impl::cancel_export(ctx, id).await.map_err(|e| #async_graphql_error_extensions::extend(&e))
+        }
     }
     }
 }
@@ -1570,16 +2091,20 @@ fn gen_graphql_type(domain: &ChronicleDomainDef) -> rust::Tokens {
         external_id: "ProvAgent".to_owned(),
         doc: Some(include_str!("../../../../domain_docs/prov_agent.md").to_string()),
         attributes: vec![],
+        id_strategy: None,
     };
     let prov_activity = ActivityDef {
         external_id: "ProvActivity".to_owned(),
         doc: Some(include_str!("../../../../domain_docs/prov_activity.md").to_string()),
         attributes: vec![],
+        id_strategy: None,
     };
     let prov_entity = EntityDef {
         external_id: "ProvEntity".to_owned(),
         doc: Some(include_str!("../../../../domain_docs/prov_entity.md").to_string()),
         attributes: vec![],
+        ttl: None,
+        id_strategy: None,
     };
 
     let chronicledomaindef = &rust::import("chronicle::codegen", "ChronicleDomainDef");
@@ -1606,7 +2131,7 @@ fn gen_graphql_type(domain: &ChronicleDomainDef) -> rust::Tokens {
     #(for agent in domain.agents.iter() => #(gen_agent_definition(agent)))
     #(for activity in domain.activities.iter() => #(gen_activity_definition(activity)))
     #(for entity in domain.entities.iter() => #(gen_entity_definition(entity)))
-    #(gen_query())
+    #(gen_query(domain))
     #(gen_mutation(domain))
 
     #[#tokio::main]
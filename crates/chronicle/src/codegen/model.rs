@@ -1,5 +1,7 @@
-use std::{collections::BTreeMap, path::Path, str::FromStr};
+use std::{collections::BTreeMap, path::Path, str::FromStr, time::Duration};
 
+use api::id_strategy::IdStrategy;
+use common::attributes::AttributeConstraints;
 use inflector::cases::{
     camelcase::to_camel_case, kebabcase::to_kebab_case, pascalcase::to_pascal_case,
     snakecase::to_snake_case,
@@ -20,6 +22,45 @@ pub enum ModelError {
 
     #[error("Model file invalid YAML: {0}")]
     ModelFileInvalidYaml(#[from] serde_yaml::Error),
+
+    #[error("Invalid TTL for {resource}: {ttl}, expected a number followed by s, m, h, d or w")]
+    InvalidTtl { resource: String, ttl: String },
+
+    #[error("Only entities can have a TTL, not {resource}")]
+    TtlNotSupported { resource: String },
+
+    #[error("Role {role} is held by agent {agent}, which is not defined")]
+    RoleHolderNotDefined { role: String, agent: String },
+
+    #[error("Recipe {recipe} refers to {kind} {reference}, which is not defined")]
+    RecipeReferenceNotDefined {
+        recipe: String,
+        kind: String,
+        reference: String,
+    },
+
+    #[error("Recipe {recipe} names {reference} more than once")]
+    RecipeReferenceRepeated { recipe: String, reference: String },
+}
+
+/// Parse a TTL such as `90s`, `30m`, `24h`, `7d` or `2w`
+pub(crate) fn parse_ttl(ttl: &str) -> Option<Duration> {
+    let ttl = ttl.trim();
+    let (count, unit) = ttl.split_at(ttl.find(|c: char| !c.is_ascii_digit())?);
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    count
+        .parse::<u64>()
+        .ok()
+        .filter(|count| *count > 0)
+        .and_then(|count| count.checked_mul(seconds))
+        .map(Duration::from_secs)
 }
 
 #[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
@@ -28,6 +69,14 @@ pub enum PrimitiveType {
     Bool,
     Int,
     JSON,
+    /// An RFC 3339 timestamp, stored as a string normalized to UTC
+    DateTime,
+    /// A 64 bit floating point number
+    Float,
+    /// A decimal number, stored as a string so that no precision is lost
+    Decimal,
+    /// Binary data, stored as a base64 encoded string
+    Bytes,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +84,12 @@ pub struct AttributeDef {
     typ: String,
     pub(crate) doc: Option<String>,
     pub(crate) primitive_type: PrimitiveType,
+    /// A repeated attribute holds a list of values of its primitive type
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) repeated: bool,
+    /// Constraints on values, enforced by the api when recording provenance
+    #[serde(flatten)]
+    pub(crate) constraints: AttributeConstraints,
 }
 
 impl TypeName for AttributeDef {
@@ -77,6 +132,8 @@ impl AttributeDef {
             typ: external_id,
             doc: attr.doc,
             primitive_type: attr.typ,
+            repeated: attr.repeated,
+            constraints: attr.constraints,
         }
     }
 }
@@ -142,6 +199,9 @@ pub struct AgentDef {
     pub(crate) external_id: String,
     pub(crate) doc: Option<String>,
     pub(crate) attributes: Vec<AttributeDef>,
+    /// How records of this type are named when created without an external id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) id_strategy: Option<IdStrategy>,
 }
 
 impl TypeName for &AgentDef {
@@ -164,6 +224,7 @@ impl AgentDef {
             external_id: external_id.as_ref().to_string(),
             doc,
             attributes,
+            id_strategy: None,
         }
     }
 
@@ -187,9 +248,12 @@ impl AgentDef {
                             typ: x.0.to_owned(),
                             doc: attr.doc.to_owned(),
                             primitive_type: attr.typ,
+                            repeated: attr.repeated,
+                            constraints: attr.constraints.clone(),
                         })
                 })
                 .collect::<Result<Vec<_>, _>>()?,
+            id_strategy: None,
         })
     }
 }
@@ -199,6 +263,12 @@ pub struct EntityDef {
     pub(crate) external_id: String,
     pub(crate) doc: Option<String>,
     pub(crate) attributes: Vec<AttributeDef>,
+    /// How long entities of this type live before they are expired
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) ttl: Option<String>,
+    /// How records of this type are named when created without an external id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) id_strategy: Option<IdStrategy>,
 }
 
 impl TypeName for &EntityDef {
@@ -221,18 +291,34 @@ impl EntityDef {
             external_id: external_id.as_ref().to_string(),
             doc,
             attributes,
+            ttl: None,
+            id_strategy: None,
         }
     }
 
+    /// How long entities of this type live, if they expire
+    pub(crate) fn ttl(&self) -> Option<Duration> {
+        self.ttl.as_deref().and_then(parse_ttl)
+    }
+
     pub(crate) fn from_input<'a>(
         external_id: String,
         doc: Option<String>,
+        ttl: Option<String>,
         attributes: &BTreeMap<String, AttributeFileInput>,
         attribute_references: impl Iterator<Item = &'a AttributeRef>,
     ) -> Result<Self, ModelError> {
+        if let Some(invalid) = ttl.as_deref().filter(|ttl| parse_ttl(ttl).is_none()) {
+            return Err(ModelError::InvalidTtl {
+                resource: external_id,
+                ttl: invalid.to_owned(),
+            });
+        }
+
         Ok(Self {
             external_id,
             doc,
+            ttl,
             attributes: attribute_references
                 .map(|x| {
                     attributes
@@ -244,9 +330,12 @@ impl EntityDef {
                             typ: x.0.to_owned(),
                             doc: attr.doc.to_owned(),
                             primitive_type: attr.typ,
+                            repeated: attr.repeated,
+                            constraints: attr.constraints.clone(),
                         })
                 })
                 .collect::<Result<Vec<_>, _>>()?,
+            id_strategy: None,
         })
     }
 }
@@ -256,6 +345,9 @@ pub struct ActivityDef {
     pub(crate) external_id: String,
     pub(crate) doc: Option<String>,
     pub(crate) attributes: Vec<AttributeDef>,
+    /// How records of this type are named when created without an external id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) id_strategy: Option<IdStrategy>,
 }
 
 impl TypeName for &ActivityDef {
@@ -278,6 +370,7 @@ impl ActivityDef {
             external_id: external_id.as_ref().to_string(),
             doc,
             attributes,
+            id_strategy: None,
         }
     }
 
@@ -301,9 +394,12 @@ impl ActivityDef {
                             typ: x.0.to_owned(),
                             doc: attr.doc.to_owned(),
                             primitive_type: attr.typ,
+                            repeated: attr.repeated,
+                            constraints: attr.constraints.clone(),
                         })
                 })
                 .collect::<Result<Vec<_>, _>>()?,
+            id_strategy: None,
         })
     }
 }
@@ -311,17 +407,27 @@ impl ActivityDef {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoleDef {
     pub(crate) external_id: String,
+    /// The agents whose types may hold the role, any agent may if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) agents: Vec<String>,
 }
 
 impl RoleDef {
     pub fn new(external_id: impl AsRef<str>) -> Self {
         Self {
             external_id: external_id.as_ref().to_string(),
+            agents: vec![],
         }
     }
 
-    pub fn from_role_file_input(external_id: String) -> Self {
-        RoleDef { external_id }
+    pub fn from_role_file_input(role: RoleFileInput) -> Self {
+        match role {
+            RoleFileInput::Name(external_id) => RoleDef::new(external_id),
+            RoleFileInput::Held { name, agents } => RoleDef {
+                external_id: name,
+                agents,
+            },
+        }
     }
 }
 
@@ -335,6 +441,44 @@ impl TypeName for &RoleDef {
     }
 }
 
+/// A composite operation recording an activity together with the agents
+/// associated with it by role, the entities it uses and those it generates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeDef {
+    pub(crate) external_id: String,
+    pub(crate) doc: Option<String>,
+    /// The external id of the activity type the recipe records
+    pub(crate) activity: String,
+    pub(crate) roles: Vec<String>,
+    pub(crate) uses: Vec<String>,
+    pub(crate) generates: Vec<String>,
+}
+
+impl RecipeDef {
+    pub(crate) fn as_method_name(&self) -> String {
+        to_camel_case(&self.external_id)
+    }
+
+    pub(crate) fn as_property(&self) -> String {
+        to_snake_case(&self.external_id)
+    }
+
+    /// The name of the parameter giving the agent that holds `role`
+    pub(crate) fn role_parameter(role: &str) -> String {
+        to_snake_case(role)
+    }
+
+    /// The name of the parameter giving the entity of type `entity` used
+    pub(crate) fn used_parameter(entity: &str) -> String {
+        to_snake_case(&format!("used_{entity}"))
+    }
+
+    /// The name of the parameter giving the entity of type `entity` generated
+    pub(crate) fn generated_parameter(entity: &str) -> String {
+        to_snake_case(&format!("generated_{entity}"))
+    }
+}
+
 fn type_name_for_kind(kind: &str, id: &str) -> String {
     if id == format!("Prov{kind}") {
         id.to_string()
@@ -364,6 +508,8 @@ pub struct ChronicleDomainDef {
     pub(crate) activities: Vec<ActivityDef>,
     pub(crate) roles_doc: Option<String>,
     pub(crate) roles: Vec<RoleDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) recipes: Vec<RecipeDef>,
 }
 
 pub struct AgentBuilder<'a>(&'a ChronicleDomainDef, AgentDef);
@@ -473,6 +619,26 @@ impl Builder {
             typ: external_id.as_ref().to_string(),
             doc,
             primitive_type: typ,
+            repeated: false,
+            constraints: AttributeConstraints::default(),
+        });
+
+        Ok(self)
+    }
+
+    /// Add an attribute holding a list of values of `typ`
+    pub(crate) fn with_repeated_attribute_type(
+        mut self,
+        external_id: impl AsRef<str>,
+        doc: Option<String>,
+        typ: PrimitiveType,
+    ) -> Result<Self, ModelError> {
+        self.0.attributes.push(AttributeDef {
+            typ: external_id.as_ref().to_string(),
+            doc,
+            primitive_type: typ,
+            repeated: true,
+            constraints: AttributeConstraints::default(),
         });
 
         Ok(self)
@@ -543,6 +709,10 @@ pub struct AttributeFileInput {
     doc: Option<String>,
     #[serde(rename = "type")]
     typ: PrimitiveType,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    repeated: bool,
+    #[serde(flatten)]
+    constraints: AttributeConstraints,
 }
 
 impl From<&AttributeDef> for AttributeFileInput {
@@ -550,6 +720,8 @@ impl From<&AttributeDef> for AttributeFileInput {
         Self {
             doc: attr.doc.to_owned(),
             typ: attr.primitive_type,
+            repeated: attr.repeated,
+            constraints: attr.constraints.clone(),
         }
     }
 }
@@ -562,6 +734,13 @@ pub struct AttributeRef(pub String);
 pub struct ResourceDef {
     pub(crate) doc: Option<String>,
     pub(crate) attributes: Vec<AttributeRef>,
+    /// How long entities of this type live, such as `24h`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) ttl: Option<String>,
+    /// How records of this type are named when created without an external
+    /// id: `uuid`, `ulid` or `hash-of-attributes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) id_strategy: Option<IdStrategy>,
 }
 
 impl From<&AgentDef> for ResourceDef {
@@ -573,6 +752,8 @@ impl From<&AgentDef> for ResourceDef {
                 .iter()
                 .map(|attr| AttributeRef(attr.typ.to_owned()))
                 .collect(),
+            ttl: None,
+            id_strategy: agent.id_strategy,
         }
     }
 }
@@ -586,6 +767,8 @@ impl From<&EntityDef> for ResourceDef {
                 .iter()
                 .map(|attr| AttributeRef(attr.typ.to_owned()))
                 .collect(),
+            ttl: entity.ttl.to_owned(),
+            id_strategy: entity.id_strategy,
         }
     }
 }
@@ -599,6 +782,56 @@ impl From<&ActivityDef> for ResourceDef {
                 .iter()
                 .map(|attr| AttributeRef(attr.typ.to_owned()))
                 .collect(),
+            ttl: None,
+            id_strategy: activity.id_strategy,
+        }
+    }
+}
+
+/// A role as written in a domain file, either its name alone or its name and
+/// the agents that may hold it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum RoleFileInput {
+    Name(String),
+    Held { name: String, agents: Vec<String> },
+}
+
+impl From<&RoleDef> for RoleFileInput {
+    fn from(role: &RoleDef) -> Self {
+        if role.agents.is_empty() {
+            RoleFileInput::Name(role.as_type_name())
+        } else {
+            RoleFileInput::Held {
+                name: role.as_type_name(),
+                agents: role.agents.clone(),
+            }
+        }
+    }
+}
+
+/// A recipe as written in a domain file
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecipeFileInput {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) doc: Option<String>,
+    pub(crate) activity: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) uses: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) generates: Vec<String>,
+}
+
+impl From<&RecipeDef> for RecipeFileInput {
+    fn from(recipe: &RecipeDef) -> Self {
+        Self {
+            doc: recipe.doc.to_owned(),
+            activity: recipe.activity.to_owned(),
+            roles: recipe.roles.clone(),
+            uses: recipe.uses.clone(),
+            generates: recipe.generates.clone(),
         }
     }
 }
@@ -611,7 +844,9 @@ pub struct DomainFileInput {
     pub(crate) entities: BTreeMap<String, ResourceDef>,
     pub(crate) activities: BTreeMap<String, ResourceDef>,
     pub(crate) roles_doc: Option<String>,
-    pub(crate) roles: Vec<String>,
+    pub(crate) roles: Vec<RoleFileInput>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) recipes: BTreeMap<String, RecipeFileInput>,
 }
 
 impl DomainFileInput {
@@ -663,7 +898,13 @@ impl From<&ChronicleDomainDef> for DomainFileInput {
 
         file.roles_doc = domain.roles_doc.to_owned();
 
-        file.roles = domain.roles.iter().map(|x| x.as_type_name()).collect();
+        file.roles = domain.roles.iter().map(RoleFileInput::from).collect();
+
+        file.recipes = domain
+            .recipes
+            .iter()
+            .map(|x| (x.external_id.clone(), RecipeFileInput::from(x)))
+            .collect();
 
         file
     }
@@ -710,31 +951,46 @@ impl ChronicleDomainDef {
             builder = builder.with_attribute_type(external_id, attr.doc.to_owned(), attr.typ)?;
         }
 
+        for (external_id, def) in model.agents.iter().chain(model.activities.iter()) {
+            if def.ttl.is_some() {
+                return Err(ModelError::TtlNotSupported {
+                    resource: external_id.to_owned(),
+                });
+            }
+        }
+
         for (external_id, def) in model.agents {
-            builder.0.agents.push(AgentDef::from_input(
+            let mut resource = AgentDef::from_input(
                 external_id,
                 def.doc,
                 &model.attributes,
                 def.attributes.iter(),
-            )?)
+            )?;
+            resource.id_strategy = def.id_strategy;
+            builder.0.agents.push(resource);
         }
 
         for (external_id, def) in model.entities {
-            builder.0.entities.push(EntityDef::from_input(
+            let mut resource = EntityDef::from_input(
                 external_id,
                 def.doc,
+                def.ttl,
                 &model.attributes,
                 def.attributes.iter(),
-            )?)
+            )?;
+            resource.id_strategy = def.id_strategy;
+            builder.0.entities.push(resource);
         }
 
         for (external_id, def) in model.activities {
-            builder.0.activities.push(ActivityDef::from_input(
+            let mut resource = ActivityDef::from_input(
                 external_id,
                 def.doc,
                 &model.attributes,
                 def.attributes.iter(),
-            )?)
+            )?;
+            resource.id_strategy = def.id_strategy;
+            builder.0.activities.push(resource);
         }
 
         if model.roles_doc.is_some() {
@@ -742,12 +998,83 @@ impl ChronicleDomainDef {
         }
 
         for role in model.roles {
-            builder.0.roles.push(RoleDef::from_role_file_input(role));
+            let role = RoleDef::from_role_file_input(role);
+            if let Some(agent) = role.agents.iter().find(|agent| {
+                !builder
+                    .0
+                    .agents
+                    .iter()
+                    .any(|def| &def.external_id == *agent)
+            }) {
+                return Err(ModelError::RoleHolderNotDefined {
+                    role: role.external_id,
+                    agent: agent.to_owned(),
+                });
+            }
+            builder.0.roles.push(role);
+        }
+
+        for (external_id, recipe) in model.recipes {
+            let recipe = RecipeDef {
+                external_id,
+                doc: recipe.doc,
+                activity: recipe.activity,
+                roles: recipe.roles,
+                uses: recipe.uses,
+                generates: recipe.generates,
+            };
+            builder.0.check_recipe(&recipe)?;
+            builder.0.recipes.push(recipe);
         }
 
         Ok(builder.build())
     }
 
+    /// Check that a recipe refers only to types and roles that are defined,
+    /// and names each at most once
+    fn check_recipe(&self, recipe: &RecipeDef) -> Result<(), ModelError> {
+        let not_defined = |kind: &str, reference: &str| ModelError::RecipeReferenceNotDefined {
+            recipe: recipe.external_id.to_owned(),
+            kind: kind.to_owned(),
+            reference: reference.to_owned(),
+        };
+
+        if !self
+            .activities
+            .iter()
+            .any(|def| def.external_id == recipe.activity)
+        {
+            return Err(not_defined("activity", &recipe.activity));
+        }
+
+        for role in &recipe.roles {
+            if !self.roles.iter().any(|def| &def.external_id == role) {
+                return Err(not_defined("role", role));
+            }
+        }
+
+        for entity in recipe.uses.iter().chain(recipe.generates.iter()) {
+            if !self.entities.iter().any(|def| &def.external_id == entity) {
+                return Err(not_defined("entity", entity));
+            }
+        }
+
+        for references in [&recipe.roles, &recipe.uses, &recipe.generates] {
+            if let Some((_, reference)) = references
+                .iter()
+                .enumerate()
+                .find(|(i, reference)| references[..*i].contains(reference))
+            {
+                return Err(ModelError::RecipeReferenceRepeated {
+                    recipe: recipe.external_id.to_owned(),
+                    reference: reference.to_owned(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn to_json_string(&self) -> Result<String, ModelError> {
         let input: DomainFileInput = self.into();
         let json = serde_json::to_string(&input)?;
@@ -1190,12 +1517,147 @@ pub mod test {
 
     use super::{AttributeDef, AttributeFileInput, PrimitiveType};
 
+    #[test]
+    fn extended_primitive_types_parse() -> Result<(), Box<dyn std::error::Error>> {
+        let domain = ChronicleDomainDef::from_input_string(
+            r#"
+    name: "chronicle"
+    attributes:
+      Recorded:
+        type: "DateTime"
+      Weight:
+        type: "Float"
+      Price:
+        type: "Decimal"
+      Thumbnail:
+        type: "Bytes"
+    entities:
+      sample:
+        attributes:
+          - Recorded
+          - Weight
+          - Price
+          - Thumbnail
+    agents: {}
+    activities: {}
+    roles: []
+    "#,
+        )?;
+
+        insta::assert_yaml_snapshot!(domain.attributes, @r###"
+        ---
+        - typ: Price
+          doc: ~
+          primitive_type: Decimal
+        - typ: Recorded
+          doc: ~
+          primitive_type: DateTime
+        - typ: Thumbnail
+          doc: ~
+          primitive_type: Bytes
+        - typ: Weight
+          doc: ~
+          primitive_type: Float
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_attributes_parse() -> Result<(), Box<dyn std::error::Error>> {
+        let domain = ChronicleDomainDef::from_input_string(
+            r#"
+    name: "chronicle"
+    attributes:
+      Tags:
+        type: "String"
+        repeated: true
+      Title:
+        type: "String"
+    entities:
+      document:
+        attributes:
+          - Tags
+          - Title
+    agents: {}
+    activities: {}
+    roles: []
+    "#,
+        )?;
+
+        insta::assert_yaml_snapshot!(domain.attributes, @r###"
+        ---
+        - typ: Tags
+          doc: ~
+          primitive_type: String
+          repeated: true
+        - typ: Title
+          doc: ~
+          primitive_type: String
+        "###);
+
+        assert!(domain.entities[0].attributes[0].repeated);
+
+        Ok(())
+    }
+
+    #[test]
+    fn attribute_constraints_parse() -> Result<(), Box<dyn std::error::Error>> {
+        let domain = ChronicleDomainDef::from_input_string(
+            r#"
+    name: "chronicle"
+    attributes:
+      Code:
+        type: "String"
+        pattern: "^[A-Z]{3}$"
+      Rating:
+        type: "Int"
+        minimum: 1
+        maximum: 5
+      Status:
+        type: "String"
+        enum: ["draft", "published"]
+    entities:
+      document:
+        attributes:
+          - Code
+          - Rating
+          - Status
+    agents: {}
+    activities: {}
+    roles: []
+    "#,
+        )?;
+
+        let constraints = domain.entities[0]
+            .attributes
+            .iter()
+            .map(|attr| attr.constraints.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(constraints[0].pattern.as_deref(), Some("^[A-Z]{3}$"));
+        assert_eq!(constraints[1].minimum, Some(1.into()));
+        assert_eq!(constraints[1].maximum, Some(5.into()));
+        assert_eq!(
+            constraints[2].allowed,
+            Some(vec!["draft".into(), "published".into()])
+        );
+        assert!(domain
+            .attributes
+            .iter()
+            .all(|attr| !attr.constraints.is_empty()));
+
+        Ok(())
+    }
+
     #[test]
     fn test_from_attribute_def_for_attribute_file_input() {
         let attr = AttributeDef {
             typ: "string".to_string(),
             doc: None,
             primitive_type: PrimitiveType::String,
+            repeated: false,
+            constraints: Default::default(),
         };
         let input = AttributeFileInput::from(&attr);
         insta::assert_yaml_snapshot!(input, @r###"
@@ -1643,4 +2105,138 @@ pub mod test {
         "###);
         Ok(())
     }
+
+    #[test]
+    fn entity_ttls_are_parsed() -> Result<(), Box<dyn std::error::Error>> {
+        let domain = ChronicleDomainDef::from_str(
+            r#"
+            name: access
+            attributes: {}
+            agents: {}
+            entities:
+              Credential:
+                attributes: []
+                ttl: 24h
+              Grant:
+                attributes: []
+            activities: {}
+            roles: []
+            "#,
+        )?;
+
+        let ttls = domain
+            .entities
+            .iter()
+            .map(|entity| (entity.external_id.as_str(), entity.ttl()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ttls,
+            [
+                ("Credential", Some(std::time::Duration::from_secs(86400))),
+                ("Grant", None)
+            ]
+        );
+
+        for invalid in ["24", "0h", "1y", "h"] {
+            let domain = format!(
+                "name: access\nattributes: {{}}\nagents: {{}}\nentities:\n  Credential:\n    attributes: []\n    ttl: \"{invalid}\"\nactivities: {{}}\nroles: []\n"
+            );
+            assert!(matches!(
+                ChronicleDomainDef::from_str(&domain),
+                Err(super::ModelError::InvalidTtl { .. })
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn roles_can_name_the_agents_that_hold_them() -> Result<(), Box<dyn std::error::Error>> {
+        let domain = ChronicleDomainDef::from_str(
+            r#"
+            name: band
+            attributes: {}
+            agents:
+              Musician:
+                attributes: []
+              Manager:
+                attributes: []
+            entities: {}
+            activities: {}
+            roles:
+              - MANAGER
+              - name: DRUMMER
+                agents:
+                  - Musician
+            "#,
+        )?;
+
+        let holders = domain
+            .roles
+            .iter()
+            .map(|role| (role.external_id.as_str(), role.agents.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            holders,
+            [
+                ("MANAGER", vec![]),
+                ("DRUMMER", vec!["Musician".to_owned()])
+            ]
+        );
+
+        let undefined = "name: band\nattributes: {}\nagents: {}\nentities: {}\nactivities: {}\nroles:\n  - name: DRUMMER\n    agents: [Musician]\n";
+        assert!(matches!(
+            ChronicleDomainDef::from_str(undefined),
+            Err(super::ModelError::RoleHolderNotDefined { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn recipes_refer_to_defined_types_and_roles() -> Result<(), Box<dyn std::error::Error>> {
+        let domain = |recipe: &str| {
+            ChronicleDomainDef::from_str(&format!(
+                r#"
+            name: certification
+            attributes: {{}}
+            agents:
+              Inspector:
+                attributes: []
+            entities:
+              Item:
+                attributes: []
+              Certificate:
+                attributes: []
+            activities:
+              ItemCertified:
+                attributes: []
+            roles:
+              - CERTIFIER
+            recipes:
+              ItemCertified: {recipe}
+            "#
+            ))
+        };
+
+        let certified = domain(
+            "{activity: ItemCertified, roles: [CERTIFIER], uses: [Item], generates: [Certificate]}",
+        )?;
+        let recipe = &certified.recipes[0];
+        assert_eq!(recipe.as_method_name(), "itemCertified");
+        assert_eq!(recipe.roles, ["CERTIFIER"]);
+        assert_eq!(recipe.uses, ["Item"]);
+        assert_eq!(recipe.generates, ["Certificate"]);
+
+        let input = DomainFileInput::from(&certified);
+        assert_eq!(input.recipes["ItemCertified"].activity, "ItemCertified");
+
+        assert!(matches!(
+            domain("{activity: ItemCertified, roles: [APPROVER]}"),
+            Err(super::ModelError::RecipeReferenceNotDefined { .. })
+        ));
+        assert!(matches!(
+            domain("{activity: ItemCertified, uses: [Item, Item]}"),
+            Err(super::ModelError::RecipeReferenceRepeated { .. })
+        ));
+        Ok(())
+    }
 }
@@ -10,5 +10,5 @@ pub use serde_json;
 pub use tokio;
 pub use uuid;
 
-pub use crate::bootstrap::bootstrap;
+pub use crate::bootstrap::{bootstrap, bootstrap_with_enrichment};
 pub use codegen::{generate_chronicle_domain_schema, Builder, PrimitiveType};
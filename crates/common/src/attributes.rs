@@ -1,9 +1,49 @@
 use std::collections::BTreeMap;
 
 use serde_json::Value;
+use thiserror::Error;
 
 use crate::prov::DomaintypeId;
 
+/// A value given for an attribute that is not of the primitive type its
+/// domain declares
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Attribute {attribute} expects a {expected} value, not {value}")]
+pub struct AttributeTypeError {
+    pub attribute: String,
+    pub expected: String,
+    pub value: Value,
+}
+
+/// True if `value` is padded base64 in the standard alphabet
+pub fn is_base64(value: &str) -> bool {
+    let data = value.trim_end_matches('=');
+    value.len() % 4 == 0
+        && value.len() - data.len() <= 2
+        && data
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+}
+
+/// True if `value` can be held by an attribute of the primitive type named
+/// `expected`. Values of unknown types and JSON attributes are not checked.
+pub fn is_of_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "String" => value.is_string(),
+        "Bool" => value.is_boolean(),
+        "Int" => value.is_i64(),
+        "Float" => value.is_number(),
+        "DateTime" => value
+            .as_str()
+            .is_some_and(|value| chrono::DateTime::parse_from_rfc3339(value).is_ok()),
+        "Decimal" => value
+            .as_str()
+            .is_some_and(|value| value.parse::<serde_json::Number>().is_ok()),
+        "Bytes" => value.as_str().is_some_and(is_base64),
+        _ => true,
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Attribute {
     pub typ: String,
@@ -17,6 +57,34 @@ impl Attribute {
             value,
         }
     }
+
+    /// An attribute holding `value`, failing unless it, or each of its
+    /// elements if the attribute is `repeated`, is of the primitive type named
+    /// `expected`
+    pub fn typed(
+        typ: impl AsRef<str>,
+        expected: &str,
+        repeated: bool,
+        value: Value,
+    ) -> Result<Self, AttributeTypeError> {
+        let mismatched = |value: &Value| AttributeTypeError {
+            attribute: typ.as_ref().to_owned(),
+            expected: expected.to_owned(),
+            value: value.clone(),
+        };
+
+        match &value {
+            Value::Array(values) if repeated => {
+                if let Some(value) = values.iter().find(|value| !is_of_type(expected, value)) {
+                    return Err(mismatched(value));
+                }
+            }
+            value if repeated || !is_of_type(expected, value) => return Err(mismatched(value)),
+            _ => {}
+        }
+
+        Ok(Self::new(typ, value))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -33,3 +101,54 @@ impl Attributes {
         }
     }
 }
+
+/// Constraints on the values of an attribute, declared alongside its type in
+/// the domain. Each element of a repeated attribute is checked separately.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct AttributeConstraints {
+    /// A regular expression that string values must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// The least numeric value allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<serde_json::Number>,
+    /// The greatest numeric value allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<serde_json::Number>,
+    /// The only values allowed
+    #[serde(default, rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub allowed: Option<Vec<Value>>,
+}
+
+impl AttributeConstraints {
+    /// True if no constraint is declared
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none()
+            && self.minimum.is_none()
+            && self.maximum.is_none()
+            && self.allowed.is_none()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::Attribute;
+
+    #[test]
+    fn values_must_be_of_the_attribute_type() {
+        assert!(Attribute::typed("Amount", "Decimal", false, json!("1024.50")).is_ok());
+        assert!(Attribute::typed("Digest", "Bytes", false, json!("aGk=")).is_ok());
+        assert!(Attribute::typed("Tags", "String", true, json!(["a", "b"])).is_ok());
+
+        let error = Attribute::typed("Amount", "Decimal", false, json!("lots")).unwrap_err();
+        assert_eq!(error.attribute, "Amount");
+        assert_eq!(error.expected, "Decimal");
+        assert_eq!(error.value, json!("lots"));
+
+        let error = Attribute::typed("Tags", "String", true, json!(["a", 2])).unwrap_err();
+        assert_eq!(error.value, json!(2));
+        assert!(Attribute::typed("Weight", "Float", false, json!(null)).is_err());
+    }
+}
@@ -3,6 +3,7 @@ use std::{path::PathBuf, pin::Pin, sync::Arc};
 use chrono::{DateTime, Utc};
 use derivative::*;
 use futures::AsyncRead;
+use k256::sha2::{Digest, Sha256};
 
 use serde::{Deserialize, Serialize};
 
@@ -11,7 +12,7 @@ use crate::{
     prov::{
         operations::{ChronicleOperation, DerivationType},
         ActivityId, AgentId, ChronicleIri, ChronicleTransactionId, EntityId, ExternalId,
-        NamespaceId, ProvModel, Role,
+        ExternalIdPart, NamespaceId, ProvModel, Role, SYSTEM_ID,
     },
 };
 
@@ -37,6 +38,15 @@ pub enum AgentCommand {
         activity: Option<ActivityId>,
         namespace: ExternalId,
         role: Option<Role>,
+        #[serde(default)]
+        valid_from: Option<DateTime<Utc>>,
+        #[serde(default)]
+        valid_until: Option<DateTime<Utc>>,
+    },
+    RetractAttribute {
+        id: AgentId,
+        namespace: ExternalId,
+        attribute: String,
     },
 }
 
@@ -86,6 +96,29 @@ pub enum ActivityCommand {
         responsible: AgentId,
         role: Option<Role>,
     },
+    RetractAssociation {
+        id: ActivityId,
+        namespace: ExternalId,
+        responsible: AgentId,
+        role: Option<Role>,
+    },
+    RetractAttribute {
+        id: ActivityId,
+        namespace: ExternalId,
+        attribute: String,
+    },
+    /// Record `external_id` as an activity performed at `time`, associated
+    /// with each agent in its role, using `used` and generating `generated`,
+    /// as a recipe of the domain describes
+    Recipe {
+        external_id: ExternalId,
+        namespace: ExternalId,
+        attributes: Attributes,
+        time: Option<DateTime<Utc>>,
+        associations: Vec<(AgentId, Option<Role>)>,
+        used: Vec<EntityId>,
+        generated: Vec<EntityId>,
+    },
 }
 
 impl ActivityCommand {
@@ -222,6 +255,26 @@ pub enum EntityCommand {
         activity: Option<ActivityId>,
         used_entity: EntityId,
     },
+    RetractAttribution {
+        id: EntityId,
+        namespace: ExternalId,
+        responsible: AgentId,
+        role: Option<Role>,
+    },
+    RetractAttribute {
+        id: EntityId,
+        namespace: ExternalId,
+        attribute: String,
+    },
+    /// Define `external_id` as a revision of `id`, with the attributes of `id`
+    /// overridden by `attributes`, expiring `id` if `expire` is set
+    Revise {
+        id: EntityId,
+        namespace: ExternalId,
+        external_id: ExternalId,
+        attributes: Attributes,
+        expire: bool,
+    },
 }
 
 impl EntityCommand {
@@ -257,6 +310,20 @@ impl EntityCommand {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryCommand {
     pub namespace: String,
+    /// Reply with the lineage of a single entity rather than the whole namespace
+    #[serde(default)]
+    pub lineage: Option<LineageQuery>,
+    /// Reply with the namespace as it stood once this block was synced
+    #[serde(default)]
+    pub as_of_block: Option<String>,
+}
+
+/// The provenance upstream of `entity`, reached through at most `depth`
+/// derivation, generation or usage relations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineageQuery {
+    pub entity: EntityId,
+    pub depth: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -270,6 +337,42 @@ pub struct ImportCommand {
     pub operations: Vec<ChronicleOperation>,
 }
 
+/// Rotate the chronicle signing key, importing the PKCS8 PEM encoded key at
+/// `import` or generating a new one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RotateKeyCommand {
+    pub import: Option<PathBuf>,
+}
+
+/// Compare the provenance held in the local store with that recomputed by
+/// replaying the ledger, for `namespace` or every known namespace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyCommand {
+    pub namespace: Option<ExternalId>,
+}
+
+/// Record a checkpoint of the digest of each namespace's derived state on the
+/// ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointCommand;
+
+/// Inspect the commands waiting in the outbox, or submit those held there
+/// while the ledger was unreachable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OutboxCommand {
+    List,
+    Flush,
+}
+
+/// A command carrying a client-supplied key. Once the command has been
+/// submitted, retrying it with the same key returns the original transaction
+/// rather than submitting it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotentCommand {
+    pub key: String,
+    pub command: Box<ApiCommand>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ApiCommand {
     NameSpace(NamespaceCommand),
@@ -279,6 +382,158 @@ pub enum ApiCommand {
     Query(QueryCommand),
     DepthCharge(DepthChargeCommand),
     Import(ImportCommand),
+    RotateKey(RotateKeyCommand),
+    Verify(VerifyCommand),
+    Checkpoint(CheckpointCommand),
+    Outbox(OutboxCommand),
+    Idempotent(IdempotentCommand),
+}
+
+impl ApiCommand {
+    /// Attach an idempotency key to the command, if one is supplied
+    pub fn with_idempotency_key(self, key: Option<String>) -> Self {
+        match key {
+            Some(key) => ApiCommand::Idempotent(IdempotentCommand {
+                key,
+                command: Box::new(self),
+            }),
+            None => self,
+        }
+    }
+
+    /// Separate the command from its idempotency key, the outermost if keys
+    /// are nested
+    pub fn split_idempotency_key(self) -> (Option<String>, ApiCommand) {
+        match self {
+            ApiCommand::Idempotent(IdempotentCommand { key, command }) => {
+                let mut command = *command;
+                while let ApiCommand::Idempotent(IdempotentCommand { command: inner, .. }) = command
+                {
+                    command = *inner;
+                }
+                (Some(key), command)
+            }
+            command => (None, command),
+        }
+    }
+
+    /// A digest of the command, to recognize a different command retried
+    /// under an idempotency key already used
+    pub fn digest(&self) -> Result<String, serde_json::Error> {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(self)?);
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// The external id of the namespace the command reads from or writes to
+    pub fn namespace(&self) -> ExternalId {
+        match self {
+            ApiCommand::NameSpace(NamespaceCommand::Create { external_id }) => external_id.clone(),
+            ApiCommand::Agent(
+                AgentCommand::Create { namespace, .. }
+                | AgentCommand::UseInContext { namespace, .. }
+                | AgentCommand::Delegate { namespace, .. }
+                | AgentCommand::RetractAttribute { namespace, .. },
+            ) => namespace.clone(),
+            ApiCommand::Activity(
+                ActivityCommand::Create { namespace, .. }
+                | ActivityCommand::Instant { namespace, .. }
+                | ActivityCommand::Start { namespace, .. }
+                | ActivityCommand::End { namespace, .. }
+                | ActivityCommand::Use { namespace, .. }
+                | ActivityCommand::Generate { namespace, .. }
+                | ActivityCommand::WasInformedBy { namespace, .. }
+                | ActivityCommand::Associate { namespace, .. }
+                | ActivityCommand::RetractAssociation { namespace, .. }
+                | ActivityCommand::RetractAttribute { namespace, .. }
+                | ActivityCommand::Recipe { namespace, .. },
+            ) => namespace.clone(),
+            ApiCommand::Entity(
+                EntityCommand::Create { namespace, .. }
+                | EntityCommand::Attribute { namespace, .. }
+                | EntityCommand::Derive { namespace, .. }
+                | EntityCommand::RetractAttribution { namespace, .. }
+                | EntityCommand::RetractAttribute { namespace, .. }
+                | EntityCommand::Revise { namespace, .. },
+            ) => namespace.clone(),
+            ApiCommand::Query(QueryCommand { namespace, .. }) => ExternalId::from(namespace),
+            ApiCommand::DepthCharge(DepthChargeCommand { namespace })
+            | ApiCommand::Import(ImportCommand { namespace, .. }) => {
+                namespace.external_id_part().clone()
+            }
+            ApiCommand::RotateKey(_) | ApiCommand::Checkpoint(_) | ApiCommand::Outbox(_) => {
+                ExternalId::from(SYSTEM_ID)
+            }
+            ApiCommand::Verify(VerifyCommand { namespace }) => namespace
+                .clone()
+                .unwrap_or_else(|| ExternalId::from(SYSTEM_ID)),
+            ApiCommand::Idempotent(IdempotentCommand { command, .. }) => command.namespace(),
+        }
+    }
+
+    /// True if the command only reads state
+    pub fn is_query(&self) -> bool {
+        match self {
+            ApiCommand::Idempotent(IdempotentCommand { command, .. }) => command.is_query(),
+            command => matches!(
+                command,
+                ApiCommand::Query(_)
+                    | ApiCommand::Verify(_)
+                    | ApiCommand::Outbox(OutboxCommand::List)
+            ),
+        }
+    }
+}
+
+/// A command waiting in the outbox, either to be handled or, while the ledger
+/// is unreachable, to be submitted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedCommand {
+    pub id: String,
+    pub kind: String,
+    pub identity: String,
+    pub queued_at: DateTime<Utc>,
+}
+
+/// The outcome of verifying one namespace against the ledger. The facts are
+/// those from [ProvModel::namespace_facts] found on only one side, and the
+/// unsynced transactions those committed to the ledger that affect the
+/// namespace but that the store has no record of applying
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceVerification {
+    pub namespace: NamespaceId,
+    pub local_digest: String,
+    pub ledger_digest: String,
+    pub local_only: Vec<String>,
+    pub ledger_only: Vec<String>,
+    pub unsynced: Vec<ChronicleTransactionId>,
+}
+
+impl NamespaceVerification {
+    pub fn new(
+        namespace: NamespaceId,
+        local: &ProvModel,
+        ledger: &ProvModel,
+        unsynced: Vec<ChronicleTransactionId>,
+    ) -> Self {
+        let local_facts = local.namespace_facts(&namespace);
+        let ledger_facts = ledger.namespace_facts(&namespace);
+
+        Self {
+            local_digest: local.namespace_digest(&namespace),
+            ledger_digest: ledger.namespace_digest(&namespace),
+            local_only: local_facts.difference(&ledger_facts).cloned().collect(),
+            ledger_only: ledger_facts.difference(&local_facts).cloned().collect(),
+            unsynced,
+            namespace,
+        }
+    }
+
+    /// True if the local store holds exactly the provenance on the ledger,
+    /// having applied every transaction that produced it
+    pub fn is_faithful(&self) -> bool {
+        self.local_digest == self.ledger_digest && self.unsynced.is_empty()
+    }
 }
 
 #[derive(Debug)]
@@ -295,6 +550,16 @@ pub enum ApiResponse {
         subject: ChronicleIri,
         prov: Box<ProvModel>,
         tx_id: ChronicleTransactionId,
+        /// Hex SHA-256 of the body of the submitted transaction, its
+        /// operations as compact JSON-LD, which the transaction's signed
+        /// receipt carries once it commits
+        operation_digest: String,
+    },
+    /// The command was submitted earlier under the same idempotency key, in
+    /// the transaction `tx_id`
+    AlreadySubmitted {
+        subject: ChronicleIri,
+        tx_id: ChronicleTransactionId,
     },
     /// The api has successfully executed the query
     QueryReply { prov: Box<ProvModel> },
@@ -305,6 +570,15 @@ pub enum ApiResponse {
     },
     /// The api has submitted the depth charge transaction to a ledger
     DepthChargeSubmitted { tx_id: ChronicleTransactionId },
+    /// The api has compared the local store with the ledger
+    Verified {
+        namespaces: Vec<NamespaceVerification>,
+    },
+    /// The ledger is unreachable, so the command is held in the outbox entry
+    /// `id` and will be submitted once it can be reached
+    Queued { id: String },
+    /// The commands in the outbox, in the order they were queued
+    Outbox { commands: Vec<QueuedCommand> },
 }
 
 impl ApiResponse {
@@ -312,11 +586,13 @@ impl ApiResponse {
         subject: impl Into<ChronicleIri>,
         prov: ProvModel,
         tx_id: ChronicleTransactionId,
+        operation_digest: String,
     ) -> Self {
         ApiResponse::Submission {
             subject: subject.into(),
             prov: Box::new(prov),
             tx_id,
+            operation_digest,
         }
     }
 
@@ -110,9 +110,21 @@ lazy_static! {
         "endTime": {
              "@id": "prov:endedAtTime",
         },
+
+        "validFrom": {
+             "@id": "chronicle:validFrom",
+        },
+
+        "validUntil": {
+             "@id": "chronicle:validUntil",
+        },
         "value": {
             "@id": "chronicle:value",
             "@type" : "@json",
         },
+        "retractedAttributes": {
+            "@id": "chronicle:retractedAttribute",
+            "@container": "@set"
+        },
     });
 }
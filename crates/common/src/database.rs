@@ -1,4 +1,7 @@
-use diesel::{r2d2::ConnectionManager, Connection, PgConnection};
+use diesel::{
+    r2d2::{ConnectionManager, R2D2Connection},
+    Connection, PgConnection,
+};
 use lazy_static::lazy_static;
 use r2d2::Pool;
 use std::{fmt::Display, time::Duration};
@@ -46,14 +49,17 @@ impl<'a> Default for TemporaryDatabase<'a> {
 }
 
 #[async_trait::async_trait]
-pub trait DatabaseConnector<X, E> {
-    async fn try_connect(&self) -> Result<(X, Pool<ConnectionManager<PgConnection>>), E>;
+pub trait DatabaseConnector<X, E, C = PgConnection>
+where
+    C: R2D2Connection + 'static,
+{
+    async fn try_connect(&self) -> Result<(X, Pool<ConnectionManager<C>>), E>;
     fn should_retry(&self, error: &E) -> bool;
 }
 
-pub async fn get_connection_with_retry<X, E: Display>(
-    connector: impl DatabaseConnector<X, E>,
-) -> Result<(X, Pool<ConnectionManager<PgConnection>>), E> {
+pub async fn get_connection_with_retry<X, E: Display, C: R2D2Connection + 'static>(
+    connector: impl DatabaseConnector<X, E, C>,
+) -> Result<(X, Pool<ConnectionManager<C>>), E> {
     let mut i = 1;
     let mut j = 1;
     loop {
@@ -1,6 +1,9 @@
-use std::{collections::BTreeSet, fmt};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
 
-use crate::prov::AgentId;
+use crate::prov::{AgentId, ExternalIdPart};
 
 use chronicle_signing::{ChronicleKnownKeyNamesSigner, SecretError};
 use k256::{
@@ -249,10 +252,100 @@ impl TryFrom<&SignedIdentity> for AuthId {
     }
 }
 
+/// Resolves the namespace used when a request omits one. A principal may be
+/// mapped to a home namespace by configuration, or carry it in a JWT claim.
+/// Principals are named `chronicle`, `anonymous`, or by the external id of
+/// their JWT identity.
+#[derive(Debug, Clone)]
+pub struct DefaultNamespaces {
+    principals: BTreeMap<String, String>,
+    claim: Option<String>,
+    fallback: String,
+}
+
+impl Default for DefaultNamespaces {
+    fn default() -> Self {
+        Self {
+            principals: BTreeMap::new(),
+            claim: None,
+            fallback: "default".to_owned(),
+        }
+    }
+}
+
+impl DefaultNamespaces {
+    /// Map `principal` to `namespace`, taking precedence over any JWT claim
+    pub fn with_principal(
+        mut self,
+        principal: impl Into<String>,
+        namespace: impl Into<String>,
+    ) -> Self {
+        self.principals.insert(principal.into(), namespace.into());
+        self
+    }
+
+    /// Read the home namespace of JWT identities from the named claim
+    pub fn with_claim(mut self, claim: impl Into<String>) -> Self {
+        self.claim = Some(claim.into());
+        self
+    }
+
+    /// The namespace used for principals without a mapping or claim
+    pub fn with_fallback(mut self, namespace: impl Into<String>) -> Self {
+        self.fallback = namespace.into();
+        self
+    }
+
+    pub fn for_identity(&self, identity: &AuthId) -> String {
+        let principal = match identity {
+            AuthId::Anonymous => "anonymous".to_owned(),
+            AuthId::Chronicle => "chronicle".to_owned(),
+            AuthId::JWT(JwtId { id, .. }) => id.external_id_part().to_string(),
+        };
+
+        if let Some(namespace) = self.principals.get(&principal) {
+            return namespace.clone();
+        }
+
+        if let (Some(claim), AuthId::JWT(JwtId { claims, .. })) = (&self.claim, identity) {
+            if let Some(Value::String(namespace)) = claims.get(claim) {
+                return namespace.clone();
+            }
+        }
+
+        self.fallback.clone()
+    }
+}
+
+/// The JWT claim whose string value is the external id of the agent an
+/// identity acts as, so that its mutations are attributed to that agent
+/// without it being named
+#[derive(Debug, Clone)]
+pub struct AgentClaim(String);
+
+impl AgentClaim {
+    pub fn new(claim: impl Into<String>) -> Self {
+        Self(claim.into())
+    }
+
+    /// The agent a JWT identity with the claim acts as, if any
+    pub fn agent_for(&self, identity: &AuthId) -> Option<AgentId> {
+        match identity {
+            AuthId::JWT(JwtId { claims, .. }) => match claims.get(&self.0) {
+                Some(Value::String(agent)) if !agent.is_empty() => {
+                    Some(AgentId::from_external_id(agent))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::prov::{ExternalId, ExternalIdPart};
+    use crate::prov::ExternalId;
     use serde_json::json;
 
     fn external_id_from_jwt_claims<'a>(claim_strings: impl Iterator<Item = &'a str>) -> ExternalId {
@@ -392,6 +485,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_default_namespaces() {
+        let claims = JwtClaims(
+            json!({
+                "sub": "John Doe",
+                "tenant": "accounts"
+            })
+            .as_object()
+            .unwrap()
+            .to_owned(),
+        );
+        let jwt = AuthId::from_jwt_claims(&claims, &BTreeSet::from(["sub".to_string()])).unwrap();
+
+        let defaults = DefaultNamespaces::default();
+        assert_eq!(defaults.for_identity(&jwt), "default");
+        assert_eq!(defaults.for_identity(&AuthId::chronicle()), "default");
+
+        let defaults = DefaultNamespaces::default()
+            .with_claim("tenant")
+            .with_fallback("shared")
+            .with_principal("chronicle", "system");
+        assert_eq!(defaults.for_identity(&jwt), "accounts");
+        assert_eq!(defaults.for_identity(&AuthId::chronicle()), "system");
+        assert_eq!(defaults.for_identity(&AuthId::anonymous()), "shared");
+
+        if let AuthId::JWT(JwtId { id, .. }) = &jwt {
+            let defaults = defaults.with_principal(id.external_id_part().to_string(), "payroll");
+            assert_eq!(defaults.for_identity(&jwt), "payroll");
+        } else {
+            panic!("did not receive expected JWT identity: {jwt}");
+        }
+    }
+
+    #[test]
+    fn test_agent_claim() {
+        let claims = JwtClaims(
+            json!({
+                "sub": "John Doe",
+                "preferred_username": "jdoe"
+            })
+            .as_object()
+            .unwrap()
+            .to_owned(),
+        );
+        let jwt = AuthId::from_jwt_claims(&claims, &BTreeSet::from(["sub".to_string()])).unwrap();
+
+        let agent_claim = AgentClaim::new("preferred_username");
+        assert_eq!(
+            agent_claim.agent_for(&jwt),
+            Some(AgentId::from_external_id("jdoe"))
+        );
+        assert_eq!(agent_claim.agent_for(&AuthId::chronicle()), None);
+        assert_eq!(agent_claim.agent_for(&AuthId::anonymous()), None);
+        assert_eq!(AgentClaim::new("email").agent_for(&jwt), None);
+    }
+
     #[test]
     fn test_opa_data_serialization() {
         let identity = AuthId::Chronicle;
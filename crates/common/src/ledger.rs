@@ -8,7 +8,8 @@ use crate::{
     prov::{
         operations::{
             ActivityExists, ActivityUses, ActsOnBehalfOf, AgentExists, ChronicleOperation,
-            CreateNamespace, EndActivity, EntityDerive, EntityExists, RegisterKey, SetAttributes,
+            CreateNamespace, EndActivity, EntityDerive, EntityExists, RegisterKey,
+            RetractAssociation, RetractAttribute, RetractAttribution, RotateKey, SetAttributes,
             StartActivity, WasAssociatedWith, WasAttributedTo, WasGeneratedBy, WasInformedBy,
         },
         to_json_ld::ToJson,
@@ -125,14 +126,23 @@ pub struct Commit {
     pub tx_id: ChronicleTransactionId,
     pub block_id: BlockId,
     pub delta: Box<ProvModel>,
+    /// The operations committed, empty if the transaction processor did not
+    /// record them
+    pub operations: Vec<ChronicleOperation>,
 }
 
 impl Commit {
-    pub fn new(tx_id: ChronicleTransactionId, block_id: BlockId, delta: Box<ProvModel>) -> Self {
+    pub fn new(
+        tx_id: ChronicleTransactionId,
+        block_id: BlockId,
+        delta: Box<ProvModel>,
+        operations: Vec<ChronicleOperation>,
+    ) -> Self {
         Commit {
             tx_id,
             block_id,
             delta,
+            operations,
         }
     }
 }
@@ -558,6 +568,77 @@ impl ChronicleOperation {
                     LedgerAddress::in_namespace(namespace, id.clone()),
                 ]
             }
+            ChronicleOperation::RetractAssociation(RetractAssociation {
+                id,
+                namespace,
+                activity_id,
+                agent_id,
+                ..
+            }) => vec![
+                LedgerAddress::namespace(namespace),
+                LedgerAddress::in_namespace(namespace, id.clone()),
+                LedgerAddress::in_namespace(namespace, activity_id.clone()),
+                LedgerAddress::in_namespace(namespace, agent_id.clone()),
+            ],
+            ChronicleOperation::RetractAttribution(RetractAttribution {
+                id,
+                namespace,
+                entity_id,
+                agent_id,
+                ..
+            }) => vec![
+                LedgerAddress::namespace(namespace),
+                LedgerAddress::in_namespace(namespace, id.clone()),
+                LedgerAddress::in_namespace(namespace, entity_id.clone()),
+                LedgerAddress::in_namespace(namespace, agent_id.clone()),
+            ],
+            ChronicleOperation::RetractAttribute(RetractAttribute::Agent {
+                id, namespace, ..
+            }) => {
+                vec![
+                    LedgerAddress::namespace(namespace),
+                    LedgerAddress::in_namespace(namespace, id.clone()),
+                ]
+            }
+            ChronicleOperation::RetractAttribute(RetractAttribute::Entity {
+                id,
+                namespace,
+                ..
+            }) => {
+                vec![
+                    LedgerAddress::namespace(namespace),
+                    LedgerAddress::in_namespace(namespace, id.clone()),
+                ]
+            }
+            ChronicleOperation::RetractAttribute(RetractAttribute::Activity {
+                id,
+                namespace,
+                ..
+            }) => {
+                vec![
+                    LedgerAddress::namespace(namespace),
+                    LedgerAddress::in_namespace(namespace, id.clone()),
+                ]
+            }
+            // Rotation requires the agent and both the outgoing and incoming identity
+            ChronicleOperation::RotateKey(RotateKey {
+                namespace,
+                id,
+                previous_key,
+                publickey,
+                ..
+            }) => vec![
+                LedgerAddress::namespace(namespace),
+                LedgerAddress::in_namespace(namespace, id.clone()),
+                LedgerAddress::in_namespace(
+                    namespace,
+                    IdentityId::from_external_id(id.external_id_part(), previous_key),
+                ),
+                LedgerAddress::in_namespace(
+                    namespace,
+                    IdentityId::from_external_id(id.external_id_part(), publickey),
+                ),
+            ],
         }
     }
 
@@ -15,10 +15,20 @@ use opa_tp_protocol::{
     OpaLedger,
 };
 use rust_embed::RustEmbed;
-use std::{net::SocketAddr, sync::Arc};
+use serde_json::{json, Value};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
-use tokio::sync::Mutex;
-use tracing::{debug, error, info, instrument};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, error, info, instrument, warn};
+use url::Url;
+use uuid::Uuid;
 
 #[derive(Debug, Error)]
 pub enum PolicyLoaderError {
@@ -389,34 +399,61 @@ pub trait OpaExecutor {
     async fn evaluate(&mut self, id: &AuthId, context: &OpaData) -> Result<(), OpaExecutorError>;
 }
 
+/// A shared handle to an OPA executor. Clones evaluate against the same
+/// executor, so replacing its policy takes effect for all of them.
 #[derive(Clone, Debug)]
 pub struct ExecutorContext {
     executor: Arc<Mutex<WasmtimeOpaExecutor>>,
-    hash: String,
+    decision_log: Option<DecisionLog>,
 }
 
 impl ExecutorContext {
     #[instrument(skip(self), level = "trace", ret(Debug))]
     pub async fn evaluate(&self, id: &AuthId, context: &OpaData) -> Result<(), OpaExecutorError> {
-        self.executor.lock().await.evaluate(id, context).await
+        let mut executor = self.executor.lock().await;
+        let started = Instant::now();
+        let result = executor.evaluate(id, context).await;
+        if let Some(decision_log) = &self.decision_log {
+            decision_log.record(executor.decision(id, &result, started.elapsed()));
+        }
+        result
     }
 
     pub fn from_loader<L: PolicyLoader>(loader: &L) -> Result<Self, OpaExecutorError> {
         Ok(Self {
             executor: Arc::new(Mutex::new(WasmtimeOpaExecutor::from_loader(loader)?)),
-            hash: loader.hash(),
+            decision_log: None,
         })
     }
 
-    pub fn hash(&self) -> &str {
-        &self.hash
+    /// Record every decision made by this context and its clones to `decision_log`
+    pub fn with_decision_log(mut self, decision_log: DecisionLog) -> Self {
+        self.decision_log = Some(decision_log);
+        self
+    }
+
+    /// Replace the policy evaluated by this context and its clones with the
+    /// one cached by `loader`. Evaluations in progress complete against the
+    /// previous policy.
+    #[instrument(skip(self, loader), fields(policy = loader.get_rule_name()))]
+    pub async fn replace<L: PolicyLoader>(&self, loader: &L) -> Result<(), OpaExecutorError> {
+        let executor = WasmtimeOpaExecutor::from_loader(loader)?;
+        *self.executor.lock().await = executor;
+        info!(hash = loader.hash(), "Replaced OPA policy");
+        Ok(())
+    }
+
+    pub async fn hash(&self) -> String {
+        self.executor.lock().await.hash.clone()
     }
 }
 
 #[derive(Debug)]
 pub struct WasmtimeOpaExecutor {
     opa: Opa,
+    policy: String,
     entrypoint: String,
+    hash: String,
 }
 
 impl WasmtimeOpaExecutor {
@@ -424,9 +461,37 @@ impl WasmtimeOpaExecutor {
     pub fn from_loader<L: PolicyLoader>(loader: &L) -> Result<Self, OpaExecutorError> {
         Ok(Self {
             opa: loader.build_opa()?,
+            policy: loader.get_rule_name().to_owned(),
             entrypoint: loader.get_entrypoint().to_owned(),
+            hash: loader.hash(),
         })
     }
+
+    /// The decision log entry for an evaluation of `id` that returned
+    /// `result`, in the format of OPA's decision logs. The policy is
+    /// identified as a bundle whose revision is its hash.
+    fn decision(
+        &self,
+        id: &AuthId,
+        result: &Result<(), OpaExecutorError>,
+        elapsed: Duration,
+    ) -> Value {
+        let mut decision = json!({
+            "decision_id": Uuid::new_v4(),
+            "labels": { "app": "chronicle" },
+            "bundles": { &self.policy: { "revision": &self.hash } },
+            "path": &self.entrypoint,
+            "input": id.identity().ok(),
+            "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Nanos, true),
+            "metrics": { "timer_rego_query_eval_ns": elapsed.as_nanos() as u64 },
+        });
+        match result {
+            Ok(()) => decision["result"] = json!(true),
+            Err(OpaExecutorError::AccessDenied) => decision["result"] = json!(false),
+            Err(e) => decision["error"] = json!({ "message": e.to_string() }),
+        }
+        decision
+    }
 }
 
 #[async_trait::async_trait]
@@ -442,6 +507,109 @@ impl OpaExecutor for WasmtimeOpaExecutor {
     }
 }
 
+/// The most decisions written in one request or append
+const DECISION_BATCH_SIZE: usize = 100;
+
+/// How many times a batch of decisions is sent to a collector before it is
+/// dropped
+const DECISION_LOG_ATTEMPTS: u32 = 3;
+
+/// Where OPA decisions are logged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecisionLogSink {
+    /// Appended to a file, one JSON decision per line
+    File(PathBuf),
+    /// Posted to a collector as JSON arrays of decisions
+    Http(Url),
+}
+
+impl DecisionLogSink {
+    /// An http or https URL names a collector, anything else a file
+    pub fn parse(target: &str) -> Self {
+        match target.parse::<Url>() {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Self::Http(url),
+            Ok(url) if url.scheme() == "file" => Self::File(PathBuf::from(url.path())),
+            _ => Self::File(PathBuf::from(target)),
+        }
+    }
+
+    async fn write(&self, client: &reqwest::Client, decisions: &[Value]) -> Result<(), String> {
+        match self {
+            Self::File(path) => {
+                let mut lines = Vec::new();
+                for decision in decisions {
+                    serde_json::to_writer(&mut lines, decision).map_err(|e| e.to_string())?;
+                    lines.push(b'\n');
+                }
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(&lines))
+                    .map_err(|e| e.to_string())
+            }
+            Self::Http(url) => client
+                .post(url.clone())
+                .json(decisions)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// A handle to a task writing OPA decisions to a [`DecisionLogSink`]. Decisions
+/// are queued so that evaluation does not wait on the sink, and written in
+/// batches in the order they were made.
+#[derive(Debug, Clone)]
+pub struct DecisionLog {
+    decisions: mpsc::UnboundedSender<Value>,
+}
+
+impl DecisionLog {
+    /// Start writing decisions to `sink`
+    pub fn spawn(sink: DecisionLogSink) -> Self {
+        let (decisions, mut queued) = mpsc::unbounded_channel::<Value>();
+        info!(?sink, "Logging OPA decisions");
+
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            while let Some(decision) = queued.recv().await {
+                let mut batch = vec![decision];
+                while batch.len() < DECISION_BATCH_SIZE {
+                    match queued.try_recv() {
+                        Ok(decision) => batch.push(decision),
+                        Err(_) => break,
+                    }
+                }
+
+                for attempt in 1..=DECISION_LOG_ATTEMPTS {
+                    match sink.write(&client, &batch).await {
+                        Ok(()) => break,
+                        Err(e) if attempt < DECISION_LOG_ATTEMPTS => {
+                            warn!(%e, attempt, "Failed to log OPA decisions, retrying");
+                            tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                        }
+                        Err(e) => {
+                            error!(%e, dropped = batch.len(), "Failed to log OPA decisions")
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { decisions }
+    }
+
+    fn record(&self, decision: Value) {
+        if self.decisions.send(decision).is_err() {
+            error!("OPA decision log has stopped");
+        }
+    }
+}
+
 #[derive(RustEmbed)]
 #[folder = "../../policies"]
 #[include = "bundle.tar.gz"]
@@ -552,6 +720,79 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn replaced_policy_applies_to_cloned_contexts() -> Result<(), OpaExecutorError> {
+        let (policy, entrypoint) = allow_all_users();
+        let loader = CliPolicyLoader::from_embedded_policy(&policy, &entrypoint)?;
+        let context = ExecutorContext::from_loader(&loader)?;
+        let clone = context.clone();
+        assert!(clone
+            .evaluate(&chronicle_id(), &chronicle_user_opa_data())
+            .await
+            .is_ok());
+
+        let deny_all =
+            CliPolicyLoader::from_embedded_policy(&policy, "allow_transactions/deny_all")?;
+        context.replace(&deny_all).await?;
+
+        assert!(matches!(
+            clone
+                .evaluate(&chronicle_id(), &chronicle_user_opa_data())
+                .await,
+            Err(OpaExecutorError::AccessDenied)
+        ));
+        assert_eq!(clone.hash().await, deny_all.hash());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decisions_are_logged_to_a_file() -> Result<(), OpaExecutorError> {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("decisions.log");
+        let (policy, entrypoint) = allow_all_users();
+        let allow = CliPolicyLoader::from_embedded_policy(&policy, &entrypoint)?;
+        let context = ExecutorContext::from_loader(&allow)?.with_decision_log(DecisionLog::spawn(
+            DecisionLogSink::parse(path.to_str().unwrap()),
+        ));
+
+        context
+            .evaluate(&chronicle_id(), &chronicle_user_opa_data())
+            .await?;
+        let deny_all =
+            CliPolicyLoader::from_embedded_policy(&policy, "allow_transactions/deny_all")?;
+        context.replace(&deny_all).await?;
+        assert!(context
+            .evaluate(&anonymous_user(), &anonymous_user_opa_data())
+            .await
+            .is_err());
+
+        let mut decisions = vec![];
+        for _ in 0..50 {
+            decisions = std::fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .map(|line| serde_json::from_str::<Value>(line).unwrap())
+                .collect();
+            if decisions.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        assert_eq!(decisions.len(), 2);
+        assert_eq!(decisions[0]["path"], entrypoint);
+        assert_eq!(decisions[0]["result"], true);
+        assert_eq!(decisions[0]["input"], chronicle_id().identity()?);
+        assert_eq!(
+            decisions[0]["bundles"][&policy]["revision"],
+            allow.hash().as_str()
+        );
+        assert_eq!(decisions[1]["path"], "allow_transactions/deny_all");
+        assert_eq!(decisions[1]["result"], false);
+        assert!(decisions[1]["metrics"]["timer_rego_query_eval_ns"].is_u64());
+        Ok(())
+    }
+
     const BUNDLE_FILE: &str = "bundle.tar.gz";
 
     fn embedded_policy_bundle() -> Result<Vec<u8>, PolicyLoaderError> {
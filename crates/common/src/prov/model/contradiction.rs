@@ -10,6 +10,10 @@ pub struct Contradiction {
     pub(crate) id: ChronicleIri,
     pub(crate) namespace: NamespaceId,
     pub(crate) contradiction: Vec<ContradictionDetail>,
+    /// The name of the operation that raised the contradiction, absent from
+    /// contradictions recorded before it was tracked
+    #[serde(default)]
+    pub(crate) operation: Option<String>,
 }
 
 impl std::error::Error for Contradiction {
@@ -25,6 +29,9 @@ impl std::error::Error for Contradiction {
 impl std::fmt::Display for Contradiction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Contradiction {{ ")?;
+        if let Some(operation) = &self.operation {
+            write!(f, "{operation}: ")?;
+        }
         for detail in &self.contradiction {
             match detail {
                 ContradictionDetail::AttributeValueChange {
@@ -43,6 +50,19 @@ impl std::fmt::Display for Contradiction {
                 ContradictionDetail::InvalidRange { start, end } => {
                     write!(f, "invalid range: {start} {end}")?;
                 }
+                ContradictionDetail::InvalidKeyTransition {
+                    previous,
+                    attempted,
+                } => {
+                    write!(f, "invalid key transition: {previous} {attempted}")?;
+                }
+                ContradictionDetail::RoleHolder { role, agent_type } => {
+                    write!(
+                        f,
+                        "role holder: {role} {}",
+                        agent_type.as_deref().unwrap_or("untyped")
+                    )?;
+                }
             }
         }
         write!(f, " }}")
@@ -60,6 +80,7 @@ impl Contradiction {
             id,
             namespace,
             contradiction: vec![ContradictionDetail::StartAlteration { value, attempted }],
+            operation: None,
         }
     }
 
@@ -73,6 +94,7 @@ impl Contradiction {
             id,
             namespace,
             contradiction: vec![ContradictionDetail::EndAlteration { value, attempted }],
+            operation: None,
         }
     }
 
@@ -86,6 +108,24 @@ impl Contradiction {
             id,
             namespace,
             contradiction: vec![ContradictionDetail::InvalidRange { start, end }],
+            operation: None,
+        }
+    }
+
+    pub fn invalid_key_transition(
+        id: ChronicleIri,
+        namespace: NamespaceId,
+        previous: String,
+        attempted: String,
+    ) -> Self {
+        Self {
+            id,
+            namespace,
+            contradiction: vec![ContradictionDetail::InvalidKeyTransition {
+                previous,
+                attempted,
+            }],
+            operation: None,
         }
     }
 
@@ -107,8 +147,45 @@ impl Contradiction {
                     },
                 )
                 .collect(),
+            operation: None,
+        }
+    }
+
+    pub fn role_holder(
+        id: ChronicleIri,
+        namespace: NamespaceId,
+        role: String,
+        agent_type: Option<String>,
+    ) -> Self {
+        Self {
+            id,
+            namespace,
+            contradiction: vec![ContradictionDetail::RoleHolder { role, agent_type }],
+            operation: None,
         }
     }
+
+    /// Record the name of the operation that raised this contradiction
+    pub fn with_operation(mut self, operation: impl ToString) -> Self {
+        self.operation = Some(operation.to_string());
+        self
+    }
+
+    pub fn id(&self) -> &ChronicleIri {
+        &self.id
+    }
+
+    pub fn namespace(&self) -> &NamespaceId {
+        &self.namespace
+    }
+
+    pub fn operation(&self) -> Option<&str> {
+        self.operation.as_deref()
+    }
+
+    pub fn details(&self) -> &[ContradictionDetail] {
+        &self.contradiction
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -130,4 +207,57 @@ pub enum ContradictionDetail {
         start: DateTime<Utc>,
         end: DateTime<Utc>,
     },
+    InvalidKeyTransition {
+        previous: String,
+        attempted: String,
+    },
+    /// An agent given a role its domain type may not hold
+    RoleHolder {
+        role: String,
+        agent_type: Option<String>,
+    },
+}
+
+impl ContradictionDetail {
+    /// The attribute or property the contradiction concerns
+    pub fn field(&self) -> &str {
+        match self {
+            Self::AttributeValueChange { name, .. } => name,
+            Self::StartAlteration { .. } => "startTime",
+            Self::EndAlteration { .. } => "endTime",
+            Self::InvalidRange { .. } => "range",
+            Self::InvalidKeyTransition { .. } => "publicKey",
+            Self::RoleHolder { .. } => "role",
+        }
+    }
+
+    /// The previously committed value, attribute values are rendered as JSON.
+    /// For an invalid range this is the start time, and for a role holder the
+    /// role.
+    pub fn committed(&self) -> String {
+        match self {
+            Self::AttributeValueChange { value, .. } => value.value.to_string(),
+            Self::StartAlteration { value, .. } | Self::EndAlteration { value, .. } => {
+                value.to_rfc3339()
+            }
+            Self::InvalidRange { start, .. } => start.to_rfc3339(),
+            Self::InvalidKeyTransition { previous, .. } => previous.clone(),
+            Self::RoleHolder { role, .. } => role.clone(),
+        }
+    }
+
+    /// The value that contradicted it. For an invalid range this is the end
+    /// time, and for a role holder the agent's domain type, empty if it has
+    /// none.
+    pub fn attempted(&self) -> String {
+        match self {
+            Self::AttributeValueChange { attempted, .. } => attempted.value.to_string(),
+            Self::StartAlteration { attempted, .. } | Self::EndAlteration { attempted, .. } => {
+                attempted.to_rfc3339()
+            }
+            Self::InvalidRange { end, .. } => end.to_rfc3339(),
+            Self::InvalidKeyTransition { attempted, .. } => attempted.clone(),
+            Self::RoleHolder { agent_type, .. } => agent_type.clone().unwrap_or_default(),
+        }
+    }
 }
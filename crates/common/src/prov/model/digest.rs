@@ -0,0 +1,376 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use k256::sha2::{Digest, Sha256};
+
+use super::{Attribute, ProvModel};
+use crate::prov::{NamespaceId, Role};
+
+fn timestamp(time: &Option<DateTime<Utc>>) -> String {
+    // The store keeps timestamps to microsecond precision
+    time.map(|time| time.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string())
+        .unwrap_or_default()
+}
+
+fn role(role: &Option<Role>) -> String {
+    role.as_ref()
+        .map(|role| role.to_string())
+        .unwrap_or_default()
+}
+
+fn attributes<'a>(attributes: impl IntoIterator<Item = (&'a String, &'a Attribute)>) -> String {
+    attributes
+        .into_iter()
+        .map(|(name, attribute)| format!("{name}={}", attribute.value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl ProvModel {
+    /// Merge a committed delta into this model, as the store does when it
+    /// syncs a transaction. Records in the delta replace those already present,
+    /// relations are added to and retracted relations removed from the model.
+    pub fn merge_delta(&mut self, delta: ProvModel) {
+        self.namespaces.extend(delta.namespaces);
+        self.agents.extend(delta.agents);
+        self.activities.extend(delta.activities);
+        self.entities.extend(delta.entities);
+        self.identities.extend(delta.identities);
+        self.has_identity.extend(delta.has_identity);
+
+        macro_rules! union {
+            ($($relation:ident),*) => {
+                $(
+                    for (key, values) in delta.$relation {
+                        self.$relation.entry(key).or_default().extend(values);
+                    }
+                )*
+            };
+        }
+
+        union!(
+            had_identity,
+            association,
+            derivation,
+            delegation,
+            acted_on_behalf_of,
+            generation,
+            usage,
+            was_informed_by,
+            generated,
+            attribution
+        );
+
+        for (key, retracted) in delta.retracted_association {
+            if let Some(associations) = self.association.get_mut(&key) {
+                associations.retain(|association| !retracted.contains(association));
+            }
+        }
+
+        for (key, retracted) in delta.retracted_attribution {
+            if let Some(attributions) = self.attribution.get_mut(&key) {
+                attributions.retain(|attribution| !retracted.contains(attribution));
+            }
+        }
+    }
+
+    /// A normalized, ordered projection of the records and relations in
+    /// `namespace`, one line per fact. Two models with the same facts for a
+    /// namespace agree on its provenance, however they were produced.
+    pub fn namespace_facts(&self, namespace: &NamespaceId) -> BTreeSet<String> {
+        let mut facts = BTreeSet::new();
+
+        for ((ns, _), agent) in &self.agents {
+            if ns == namespace {
+                facts.insert(format!(
+                    "agent {} {} [{}]",
+                    agent.id,
+                    agent
+                        .domaintypeid
+                        .as_ref()
+                        .map(|d| d.to_string())
+                        .unwrap_or_default(),
+                    attributes(&agent.attributes)
+                ));
+            }
+        }
+
+        for ((ns, _), identity) in &self.identities {
+            if ns == namespace {
+                facts.insert(format!("identity {} {}", identity.id, identity.public_key));
+            }
+        }
+
+        for ((ns, agent), (_, identity)) in &self.has_identity {
+            if ns == namespace {
+                facts.insert(format!("has_identity {agent} {identity}"));
+            }
+        }
+
+        for ((ns, agent), identities) in &self.had_identity {
+            if ns == namespace {
+                for (_, identity) in identities {
+                    facts.insert(format!("had_identity {agent} {identity}"));
+                }
+            }
+        }
+
+        for ((ns, _), activity) in &self.activities {
+            if ns == namespace {
+                facts.insert(format!(
+                    "activity {} {} {} {} [{}]",
+                    activity.id,
+                    activity
+                        .domaintypeid
+                        .as_ref()
+                        .map(|d| d.to_string())
+                        .unwrap_or_default(),
+                    timestamp(&activity.started),
+                    timestamp(&activity.ended),
+                    attributes(&activity.attributes)
+                ));
+            }
+        }
+
+        for ((ns, _), entity) in &self.entities {
+            if ns == namespace {
+                facts.insert(format!(
+                    "entity {} {} [{}]",
+                    entity.id,
+                    entity
+                        .domaintypeid
+                        .as_ref()
+                        .map(|d| d.to_string())
+                        .unwrap_or_default(),
+                    attributes(&entity.attributes)
+                ));
+            }
+        }
+
+        for ((ns, _), associations) in &self.association {
+            if ns == namespace {
+                for association in associations {
+                    facts.insert(format!(
+                        "association {} {} {}",
+                        association.activity_id,
+                        association.agent_id,
+                        role(&association.role)
+                    ));
+                }
+            }
+        }
+
+        for ((ns, _), delegations) in self.delegation.iter().chain(&self.acted_on_behalf_of) {
+            if ns == namespace {
+                for delegation in delegations {
+                    facts.insert(format!(
+                        "delegation {} {} {} {} {} {}",
+                        delegation.delegate_id,
+                        delegation.responsible_id,
+                        delegation
+                            .activity_id
+                            .as_ref()
+                            .map(|a| a.to_string())
+                            .unwrap_or_default(),
+                        role(&delegation.role),
+                        timestamp(&delegation.valid_from),
+                        timestamp(&delegation.valid_until)
+                    ));
+                }
+            }
+        }
+
+        for ((ns, _), derivations) in &self.derivation {
+            if ns == namespace {
+                for derivation in derivations {
+                    facts.insert(format!(
+                        "derivation {} {} {} {:?}",
+                        derivation.generated_id,
+                        derivation.used_id,
+                        derivation
+                            .activity_id
+                            .as_ref()
+                            .map(|a| a.to_string())
+                            .unwrap_or_default(),
+                        derivation.typ
+                    ));
+                }
+            }
+        }
+
+        for ((ns, _), generations) in &self.generation {
+            if ns == namespace {
+                for generation in generations {
+                    facts.insert(format!(
+                        "generation {} {}",
+                        generation.generated_id, generation.activity_id
+                    ));
+                }
+            }
+        }
+
+        for ((ns, _), usages) in &self.usage {
+            if ns == namespace {
+                for usage in usages {
+                    facts.insert(format!("usage {} {}", usage.activity_id, usage.entity_id));
+                }
+            }
+        }
+
+        for ((ns, activity), informing) in &self.was_informed_by {
+            if ns == namespace {
+                for (_, informing) in informing {
+                    facts.insert(format!("was_informed_by {activity} {informing}"));
+                }
+            }
+        }
+
+        for ((ns, _), attributions) in &self.attribution {
+            if ns == namespace {
+                for attribution in attributions {
+                    facts.insert(format!(
+                        "attribution {} {} {}",
+                        attribution.entity_id,
+                        attribution.agent_id,
+                        role(&attribution.role)
+                    ));
+                }
+            }
+        }
+
+        facts
+    }
+
+    /// Hex encoded SHA-256 digest of the [ProvModel::namespace_facts] of
+    /// `namespace`
+    pub fn namespace_digest(&self, namespace: &NamespaceId) -> String {
+        let mut hasher = Sha256::new();
+        for fact in self.namespace_facts(namespace) {
+            hasher.update(fact.as_bytes());
+            hasher.update(b"\n");
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use crate::prov::{
+        operations::{
+            ActivityExists, ActivityUses, ActsOnBehalfOf, AgentExists, ChronicleOperation,
+            CreateNamespace, EntityExists, RegisterKey, WasGeneratedBy,
+        },
+        ActivityId, AgentId, EntityId, NamespaceId, ProvModel,
+    };
+
+    fn namespace() -> NamespaceId {
+        NamespaceId::from_external_id(
+            "testns",
+            Uuid::parse_str("5a0ab5b8-eeb7-4812-9fe3-6dd69bd20cea").unwrap(),
+        )
+    }
+
+    fn operations() -> Vec<ChronicleOperation> {
+        let namespace = namespace();
+        vec![
+            ChronicleOperation::CreateNamespace(CreateNamespace::new(
+                namespace.clone(),
+                "testns",
+                Uuid::parse_str("5a0ab5b8-eeb7-4812-9fe3-6dd69bd20cea").unwrap(),
+            )),
+            ChronicleOperation::ActivityExists(ActivityExists {
+                namespace: namespace.clone(),
+                external_id: "activity".into(),
+            }),
+            ChronicleOperation::EntityExists(EntityExists {
+                namespace: namespace.clone(),
+                external_id: "used".into(),
+            }),
+            ChronicleOperation::ActivityUses(ActivityUses {
+                namespace: namespace.clone(),
+                id: EntityId::from_external_id("used"),
+                activity: ActivityId::from_external_id("activity"),
+            }),
+            ChronicleOperation::EntityExists(EntityExists {
+                namespace: namespace.clone(),
+                external_id: "generated".into(),
+            }),
+            ChronicleOperation::WasGeneratedBy(WasGeneratedBy {
+                namespace,
+                id: EntityId::from_external_id("generated"),
+                activity: ActivityId::from_external_id("activity"),
+            }),
+        ]
+    }
+
+    #[test]
+    fn merged_deltas_digest_as_single_model() {
+        let operations = operations();
+
+        let whole = ProvModel::from_tx(&operations).unwrap();
+
+        let mut merged = ProvModel::default();
+        for operation in &operations {
+            merged.merge_delta(ProvModel::from_tx(std::iter::once(operation)).unwrap());
+        }
+
+        assert_eq!(
+            whole.namespace_digest(&namespace()),
+            merged.namespace_digest(&namespace())
+        );
+        assert_ne!(
+            whole.namespace_digest(&namespace()),
+            ProvModel::default().namespace_digest(&namespace())
+        );
+    }
+
+    #[test]
+    fn facts_are_scoped_to_namespace() {
+        let model = ProvModel::from_tx(&operations()).unwrap();
+
+        let other = NamespaceId::from_external_id(
+            "otherns",
+            Uuid::parse_str("6a0ab5b8-eeb7-4812-9fe3-6dd69bd20cea").unwrap(),
+        );
+
+        assert!(model.namespace_facts(&other).is_empty());
+        assert_eq!(model.namespace_facts(&namespace()).len(), 5);
+    }
+
+    #[test]
+    fn delegation_validity_and_identities_are_facts() {
+        let namespace = namespace();
+        let responsible = AgentId::from_external_id("responsible");
+        let delegate = AgentId::from_external_id("delegate");
+
+        let digest = |valid_until, publickey: Option<&str>| {
+            let mut operations = vec![
+                ChronicleOperation::AgentExists(AgentExists::new(namespace.clone(), "responsible")),
+                ChronicleOperation::AgentExists(AgentExists::new(namespace.clone(), "delegate")),
+                ChronicleOperation::AgentActsOnBehalfOf(
+                    ActsOnBehalfOf::new(&namespace, &responsible, &delegate, None, None)
+                        .with_validity(None, valid_until),
+                ),
+            ];
+            if let Some(publickey) = publickey {
+                operations.push(ChronicleOperation::RegisterKey(RegisterKey {
+                    namespace: namespace.clone(),
+                    id: delegate.clone(),
+                    publickey: publickey.to_owned(),
+                }));
+            }
+            ProvModel::from_tx(&operations)
+                .unwrap()
+                .namespace_digest(&namespace)
+        };
+
+        let until = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).single();
+
+        assert_ne!(digest(None, None), digest(until, None));
+        assert_ne!(digest(None, None), digest(None, Some("02aa")));
+        assert_ne!(digest(None, Some("02aa")), digest(None, Some("02bb")));
+    }
+}
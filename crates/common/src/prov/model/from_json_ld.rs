@@ -17,8 +17,8 @@ use crate::{
         operations::{
             ActivityExists, ActivityUses, ActsOnBehalfOf, AgentExists, ChronicleOperation,
             CreateNamespace, DerivationType, EndActivity, EntityDerive, EntityExists, RegisterKey,
-            SetAttributes, StartActivity, WasAssociatedWith, WasAttributedTo, WasGeneratedBy,
-            WasInformedBy,
+            RetractAssociation, RetractAttribute, RetractAttribution, RotateKey, SetAttributes,
+            StartActivity, WasAssociatedWith, WasAttributedTo, WasGeneratedBy, WasInformedBy,
         },
         vocab::{Chronicle, ChronicleOperations, Prov},
         ActivityId, AgentId, DomaintypeId, EntityId, ExternalIdPart, IdentityId, NamespaceId, Role,
@@ -26,7 +26,7 @@ use crate::{
     },
 };
 
-use super::{Activity, Agent, Entity, Identity, ProcessorError, ProvModel};
+use super::{Activity, Agent, Delegation, Entity, Identity, ProcessorError, ProvModel};
 
 pub struct ContextLoader;
 
@@ -114,6 +114,12 @@ fn extract_scalar_prop<'a>(
     }
 }
 
+fn extract_retracted_attributes(node: &Node<IriBuf, BlankIdBuf, ()>) -> Vec<String> {
+    node.get(&id_from_iri(&Chronicle::RetractedAttribute))
+        .filter_map(|o| o.as_str().map(|typename| typename.to_owned()))
+        .collect()
+}
+
 fn extract_namespace(agent: &Node<IriBuf, BlankIdBuf, ()>) -> Result<NamespaceId, ProcessorError> {
     Ok(NamespaceId::try_from(Iri::from_str(
         extract_scalar_prop(&Chronicle::HasNamespace, agent)?
@@ -287,12 +293,27 @@ impl ProvModel {
             .map(|x| ActivityId::try_from(x.as_iri()))
             .transpose()?;
 
-        self.qualified_delegation(
-            &namespace_id,
-            &responsible_id,
-            &delegate_id,
-            activity_id,
-            role,
+        let valid_from = extract_scalar_prop(&Chronicle::ValidFrom, delegation)
+            .ok()
+            .and_then(|x| x.as_str().map(DateTime::parse_from_rfc3339))
+            .transpose()?
+            .map(DateTime::<Utc>::from);
+
+        let valid_until = extract_scalar_prop(&Chronicle::ValidUntil, delegation)
+            .ok()
+            .and_then(|x| x.as_str().map(DateTime::parse_from_rfc3339))
+            .transpose()?
+            .map(DateTime::<Utc>::from);
+
+        self.add_delegation(
+            Delegation::new(
+                &namespace_id,
+                &delegate_id,
+                &responsible_id,
+                activity_id.as_ref(),
+                role,
+            )
+            .with_validity(valid_from, valid_until),
         );
         Ok(())
     }
@@ -326,7 +347,12 @@ impl ProvModel {
             })
             .and_then(|x| Ok(ActivityId::try_from(x.as_iri())?))?;
 
-        self.qualified_association(&namespace_id, &activity_id, &agent_id, role);
+        if association.has_type(&id_from_iri(&Chronicle::Retracted)) {
+            self.qualified_association(&namespace_id, &activity_id, &agent_id, role.clone());
+            self.retract_association(&namespace_id, &activity_id, &agent_id, role);
+        } else {
+            self.qualified_association(&namespace_id, &activity_id, &agent_id, role);
+        }
 
         Ok(())
     }
@@ -360,7 +386,12 @@ impl ProvModel {
             })
             .and_then(|x| Ok(EntityId::try_from(x.as_iri())?))?;
 
-        self.qualified_attribution(&namespace_id, &entity_id, &agent_id, role);
+        if attribution.has_type(&id_from_iri(&Chronicle::Retracted)) {
+            self.qualified_attribution(&namespace_id, &entity_id, &agent_id, role.clone());
+            self.retract_attribution(&namespace_id, &entity_id, &agent_id, role);
+        } else {
+            self.qualified_attribution(&namespace_id, &entity_id, &agent_id, role);
+        }
 
         Ok(())
     }
@@ -397,6 +428,10 @@ impl ProvModel {
             self.had_identity(namespaceid.clone(), &id, &identity?);
         }
 
+        for typename in extract_retracted_attributes(agent) {
+            self.retracted_attribute(&namespaceid, id.clone(), typename);
+        }
+
         let agent = Agent::exists(namespaceid, id).has_attributes(attributes);
 
         self.add_agent(agent);
@@ -440,6 +475,10 @@ impl ProvModel {
 
         let attributes = Self::extract_attributes(activity)?;
 
+        for typename in extract_retracted_attributes(activity) {
+            self.retracted_attribute(&namespaceid, id.clone(), typename);
+        }
+
         let mut activity = Activity::exists(namespaceid.clone(), id).has_attributes(attributes);
 
         if let Some(started) = started {
@@ -573,6 +612,11 @@ impl ProvModel {
         }
 
         let attributes = Self::extract_attributes(entity)?;
+
+        for typename in extract_retracted_attributes(entity) {
+            self.retracted_attribute(&namespaceid, id.clone(), typename);
+        }
+
         self.add_entity(Entity::exists(namespaceid, id).has_attributes(attributes));
 
         Ok(())
@@ -589,8 +633,12 @@ trait Operation {
     fn optional_role(&self) -> Option<Role>;
     fn identity(&self) -> Option<IdentityId>;
     fn key(&self) -> String;
+    fn previous_key(&self) -> String;
+    fn key_transition_signature(&self) -> String;
     fn start_time(&self) -> String;
     fn locator(&self) -> Option<String>;
+    fn valid_from(&self) -> Option<DateTime<Utc>>;
+    fn valid_until(&self) -> Option<DateTime<Utc>>;
     fn end_time(&self) -> String;
     fn entity(&self) -> EntityId;
     fn used_entity(&self) -> EntityId;
@@ -598,6 +646,7 @@ trait Operation {
     fn domain(&self) -> Option<DomaintypeId>;
     fn attributes(&self) -> BTreeMap<String, Attribute>;
     fn informing_activity(&self) -> ActivityId;
+    fn attribute_name(&self) -> String;
 }
 
 impl Operation for Node<IriBuf, BlankIdBuf, ()> {
@@ -636,6 +685,16 @@ impl Operation for Node<IriBuf, BlankIdBuf, ()> {
         String::from(objects.next().unwrap().as_str().unwrap())
     }
 
+    fn previous_key(&self) -> String {
+        let mut objects = self.get(&id_from_iri(&ChronicleOperations::PreviousPublicKey));
+        String::from(objects.next().unwrap().as_str().unwrap())
+    }
+
+    fn key_transition_signature(&self) -> String {
+        let mut objects = self.get(&id_from_iri(&ChronicleOperations::KeyTransitionSignature));
+        String::from(objects.next().unwrap().as_str().unwrap())
+    }
+
     fn start_time(&self) -> String {
         let mut objects = self.get(&id_from_iri(&ChronicleOperations::StartActivityTime));
         let time = objects.next().unwrap().as_str().unwrap();
@@ -761,11 +820,30 @@ impl Operation for Node<IriBuf, BlankIdBuf, ()> {
         Some(locator.as_str().unwrap().to_owned())
     }
 
+    fn valid_from(&self) -> Option<DateTime<Utc>> {
+        let mut objects = self.get(&id_from_iri(&ChronicleOperations::ValidFrom));
+        objects
+            .next()
+            .map(|time| time.as_str().unwrap().parse().unwrap())
+    }
+
+    fn valid_until(&self) -> Option<DateTime<Utc>> {
+        let mut objects = self.get(&id_from_iri(&ChronicleOperations::ValidUntil));
+        objects
+            .next()
+            .map(|time| time.as_str().unwrap().parse().unwrap())
+    }
+
     fn informing_activity(&self) -> ActivityId {
         let mut name_objects = self.get(&id_from_iri(&ChronicleOperations::InformingActivityName));
         let external_id = name_objects.next().unwrap().as_str().unwrap();
         ActivityId::from_external_id(external_id)
     }
+
+    fn attribute_name(&self) -> String {
+        let mut objects = self.get(&id_from_iri(&ChronicleOperations::AttributeName));
+        String::from(objects.next().unwrap().as_str().unwrap())
+    }
 }
 
 impl ChronicleOperation {
@@ -816,7 +894,8 @@ impl ChronicleOperation {
                         &delegate_id,
                         activity_id.as_ref(),
                         o.optional_role(),
-                    ),
+                    )
+                    .with_validity(o.valid_from(), o.valid_until()),
                 ))
             } else if o.has_type(&id_from_iri(&ChronicleOperations::RegisterKey)) {
                 let namespace = o.namespace();
@@ -953,6 +1032,59 @@ impl ChronicleOperation {
                     activity,
                     informing_activity,
                 }))
+            } else if o.has_type(&id_from_iri(&ChronicleOperations::RetractAssociation)) {
+                Ok(ChronicleOperation::RetractAssociation(
+                    RetractAssociation::new(
+                        &o.namespace(),
+                        &o.activity(),
+                        &o.agent(),
+                        o.optional_role(),
+                    ),
+                ))
+            } else if o.has_type(&id_from_iri(&ChronicleOperations::RetractAttribution)) {
+                Ok(ChronicleOperation::RetractAttribution(
+                    RetractAttribution::new(
+                        &o.namespace(),
+                        &o.entity(),
+                        &o.agent(),
+                        o.optional_role(),
+                    ),
+                ))
+            } else if o.has_type(&id_from_iri(&ChronicleOperations::RetractAttribute)) {
+                let namespace = o.namespace();
+                let typename = o.attribute_name();
+
+                let retraction = {
+                    if o.has_key(&Term::Id(id_from_iri(&ChronicleOperations::EntityName))) {
+                        RetractAttribute::Entity {
+                            namespace,
+                            id: o.entity(),
+                            typename,
+                        }
+                    } else if o.has_key(&Term::Id(id_from_iri(&ChronicleOperations::AgentName))) {
+                        RetractAttribute::Agent {
+                            namespace,
+                            id: o.agent(),
+                            typename,
+                        }
+                    } else {
+                        RetractAttribute::Activity {
+                            namespace,
+                            id: o.activity(),
+                            typename,
+                        }
+                    }
+                };
+
+                Ok(ChronicleOperation::RetractAttribute(retraction))
+            } else if o.has_type(&id_from_iri(&ChronicleOperations::RotateKey)) {
+                Ok(ChronicleOperation::RotateKey(RotateKey {
+                    namespace: o.namespace(),
+                    id: o.agent(),
+                    previous_key: o.previous_key(),
+                    publickey: o.key(),
+                    signature: o.key_transition_signature(),
+                }))
             } else {
                 error!("Unknown operation: {:?}", o.type_entry());
                 unreachable!()
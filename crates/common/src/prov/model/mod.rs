@@ -1,11 +1,16 @@
 mod contradiction;
-pub use contradiction::Contradiction;
+mod digest;
+mod roles;
+pub use contradiction::{Contradiction, ContradictionDetail};
+pub use roles::RoleConstraints;
 pub mod transaction;
 pub use transaction::ChronicleTransaction;
 
+use chronicle_signing::verify_key_transition;
 use chrono::{DateTime, Utc};
 use iref::IriBuf;
 use json_ld::NoLoader;
+use k256::ecdsa::VerifyingKey;
 use lazy_static::lazy_static;
 use locspan::Meta;
 use rdf_types::{vocabulary::no_vocabulary_mut, BlankIdBuf};
@@ -32,7 +37,8 @@ use super::{
     operations::{
         ActivityExists, ActivityUses, ActsOnBehalfOf, AgentExists, ChronicleOperation,
         CreateNamespace, DerivationType, EndActivity, EntityDerive, EntityExists, RegisterKey,
-        SetAttributes, StartActivity, WasAssociatedWith, WasGeneratedBy, WasInformedBy,
+        RetractAssociation, RetractAttribute, RetractAttribution, RotateKey, SetAttributes,
+        StartActivity, WasAssociatedWith, WasGeneratedBy, WasInformedBy,
     },
     ActivityId, AgentId, AssociationId, AttributionId, ChronicleIri, DelegationId, DomaintypeId,
     EntityId, ExternalId, ExternalIdPart, IdentityId, NamespaceId, Role, UuidPart,
@@ -107,7 +113,7 @@ pub enum ChronicleTransactionIdError {
     InvalidTransactionId { id: String },
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone)]
 pub struct ChronicleTransactionId(String);
 
 impl Display for ChronicleTransactionId {
@@ -305,6 +311,8 @@ pub struct Delegation {
     pub responsible_id: AgentId,
     pub activity_id: Option<ActivityId>,
     pub role: Option<Role>,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
 }
 
 impl Delegation {
@@ -327,8 +335,29 @@ impl Delegation {
             responsible_id: responsible_id.clone(),
             activity_id: activity_id.cloned(),
             role,
+            valid_from: None,
+            valid_until: None,
         }
     }
+
+    /// Limit the delegation to the period between `valid_from` and
+    /// `valid_until`, either of which may be open
+    pub fn with_validity(
+        mut self,
+        valid_from: Option<DateTime<Utc>>,
+        valid_until: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.valid_from = valid_from;
+        self.valid_until = valid_until;
+        self
+    }
+
+    /// Whether the delegation holds at `time`, from the start of its validity
+    /// up to but excluding its end
+    pub fn is_valid_at(&self, time: DateTime<Utc>) -> bool {
+        self.valid_from.map_or(true, |from| from <= time)
+            && self.valid_until.map_or(true, |until| time < until)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
@@ -406,6 +435,7 @@ type NamespacedAgent = NamespacedId<AgentId>;
 type NamespacedEntity = NamespacedId<EntityId>;
 type NamespacedActivity = NamespacedId<ActivityId>;
 type NamespacedIdentity = NamespacedId<IdentityId>;
+type NamespacedIri = NamespacedId<ChronicleIri>;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProvModel {
@@ -425,6 +455,9 @@ pub struct ProvModel {
     pub was_informed_by: BTreeMap<NamespacedActivity, BTreeSet<NamespacedActivity>>,
     pub generated: BTreeMap<NamespacedActivity, BTreeSet<GeneratedEntity>>,
     pub attribution: BTreeMap<NamespacedEntity, BTreeSet<Attribution>>,
+    pub retracted_association: BTreeMap<NamespacedActivity, BTreeSet<Association>>,
+    pub retracted_attribution: BTreeMap<NamespacedEntity, BTreeSet<Attribution>>,
+    pub retracted_attributes: BTreeMap<NamespacedIri, BTreeSet<String>>,
 }
 
 impl ProvModel {
@@ -470,29 +503,57 @@ impl ProvModel {
         activity_id: Option<ActivityId>,
         role: Option<Role>,
     ) {
-        let delegation = Delegation {
-            namespace_id: namespace_id.clone(),
-            id: DelegationId::from_component_ids(
-                delegate_id,
-                responsible_id,
-                activity_id.as_ref(),
-                role.as_ref(),
-            ),
-            responsible_id: responsible_id.clone(),
-            delegate_id: delegate_id.clone(),
-            activity_id,
+        self.add_delegation(Delegation::new(
+            namespace_id,
+            delegate_id,
+            responsible_id,
+            activity_id.as_ref(),
             role,
-        };
+        ));
+    }
+
+    /// Append a delegation to the model, taking the bounds of its validity
+    /// that it leaves open from any delegation of the same id it replaces
+    pub fn add_delegation(&mut self, delegation: Delegation) {
+        let delegation = self.with_prior_validity(delegation);
+        let namespace_id = delegation.namespace_id.clone();
+        let responsible_key = (namespace_id.clone(), delegation.responsible_id.clone());
+        let delegate_key = (namespace_id, delegation.delegate_id.clone());
+
+        if let Some(delegations) = self.delegation.get_mut(&responsible_key) {
+            delegations.retain(|d| d.id != delegation.id);
+        }
+        if let Some(delegations) = self.acted_on_behalf_of.get_mut(&delegate_key) {
+            delegations.retain(|d| d.id != delegation.id);
+        }
+
         self.delegation
-            .entry((namespace_id.clone(), responsible_id.clone()))
+            .entry(responsible_key)
             .or_default()
             .insert(delegation.clone());
         self.acted_on_behalf_of
-            .entry((namespace_id.clone(), delegate_id.clone()))
+            .entry(delegate_key)
             .or_default()
             .insert(delegation);
     }
 
+    /// `delegation` with the bounds of its validity that it leaves open taken
+    /// from the delegation of the same id in the model, if any
+    fn with_prior_validity(&self, mut delegation: Delegation) -> Delegation {
+        if let Some(prior) = self
+            .delegation
+            .get(&(
+                delegation.namespace_id.clone(),
+                delegation.responsible_id.clone(),
+            ))
+            .and_then(|delegations| delegations.iter().find(|d| d.id == delegation.id))
+        {
+            delegation.valid_from = delegation.valid_from.or(prior.valid_from);
+            delegation.valid_until = delegation.valid_until.or(prior.valid_until);
+        }
+        delegation
+    }
+
     pub fn qualified_association(
         &mut self,
         namespace_id: &NamespaceId,
@@ -500,16 +561,41 @@ impl ProvModel {
         agent_id: &AgentId,
         role: Option<Role>,
     ) {
-        self.association
-            .entry((namespace_id.clone(), activity_id.clone()))
-            .or_default()
-            .insert(Association {
-                namespace_id: namespace_id.clone(),
-                id: AssociationId::from_component_ids(agent_id, activity_id, role.as_ref()),
-                agent_id: agent_id.clone(),
-                activity_id: activity_id.clone(),
-                role,
-            });
+        let association = Association::new(namespace_id, agent_id, activity_id, role);
+
+        let key = (namespace_id.clone(), activity_id.clone());
+        if let Some(retracted) = self.retracted_association.get_mut(&key) {
+            retracted.remove(&association);
+            if retracted.is_empty() {
+                self.retracted_association.remove(&key);
+            }
+        }
+
+        self.association.entry(key).or_default().insert(association);
+    }
+
+    /// Move an association to the retracted set, if it is currently present
+    pub fn retract_association(
+        &mut self,
+        namespace_id: &NamespaceId,
+        activity_id: &ActivityId,
+        agent_id: &AgentId,
+        role: Option<Role>,
+    ) {
+        let association = Association::new(namespace_id, agent_id, activity_id, role);
+        let key = (namespace_id.clone(), activity_id.clone());
+
+        if let Some(associations) = self.association.get_mut(&key) {
+            if associations.remove(&association) {
+                if associations.is_empty() {
+                    self.association.remove(&key);
+                }
+                self.retracted_association
+                    .entry(key)
+                    .or_default()
+                    .insert(association);
+            }
+        }
     }
 
     pub fn was_generated_by(
@@ -571,16 +657,72 @@ impl ProvModel {
         agent_id: &AgentId,
         role: Option<Role>,
     ) {
-        self.attribution
-            .entry((namespace_id.clone(), entity_id.clone()))
+        let attribution = Attribution::new(namespace_id, agent_id, entity_id, role);
+
+        let key = (namespace_id.clone(), entity_id.clone());
+        if let Some(retracted) = self.retracted_attribution.get_mut(&key) {
+            retracted.remove(&attribution);
+            if retracted.is_empty() {
+                self.retracted_attribution.remove(&key);
+            }
+        }
+
+        self.attribution.entry(key).or_default().insert(attribution);
+    }
+
+    /// Move an attribution to the retracted set, if it is currently present
+    pub fn retract_attribution(
+        &mut self,
+        namespace_id: &NamespaceId,
+        entity_id: &EntityId,
+        agent_id: &AgentId,
+        role: Option<Role>,
+    ) {
+        let attribution = Attribution::new(namespace_id, agent_id, entity_id, role);
+        let key = (namespace_id.clone(), entity_id.clone());
+
+        if let Some(attributions) = self.attribution.get_mut(&key) {
+            if attributions.remove(&attribution) {
+                if attributions.is_empty() {
+                    self.attribution.remove(&key);
+                }
+                self.retracted_attribution
+                    .entry(key)
+                    .or_default()
+                    .insert(attribution);
+            }
+        }
+    }
+
+    /// Record that the named attribute of `id` has been retracted
+    pub fn retracted_attribute(
+        &mut self,
+        namespace_id: &NamespaceId,
+        id: impl Into<ChronicleIri>,
+        typename: impl AsRef<str>,
+    ) {
+        self.retracted_attributes
+            .entry((namespace_id.clone(), id.into()))
             .or_default()
-            .insert(Attribution {
-                namespace_id: namespace_id.clone(),
-                id: AttributionId::from_component_ids(agent_id, entity_id, role.as_ref()),
-                agent_id: agent_id.clone(),
-                entity_id: entity_id.clone(),
-                role,
-            });
+            .insert(typename.as_ref().to_owned());
+    }
+
+    /// Attributes that are set again are no longer retracted
+    fn restore_retracted_attributes<'a>(
+        &mut self,
+        namespace_id: &NamespaceId,
+        id: impl Into<ChronicleIri>,
+        typenames: impl Iterator<Item = &'a String>,
+    ) {
+        let key = (namespace_id.clone(), id.into());
+        if let Some(retracted) = self.retracted_attributes.get_mut(&key) {
+            for typename in typenames {
+                retracted.remove(typename);
+            }
+            if retracted.is_empty() {
+                self.retracted_attributes.remove(&key);
+            }
+        }
     }
 
     pub fn had_identity(&mut self, namespace: NamespaceId, agent: &AgentId, identity: &IdentityId) {
@@ -698,6 +840,11 @@ impl ProvModel {
     /// contradiction, but attempt to apply as much of the operation as possible
     #[instrument(skip(self,tx), level = "trace", name="apply_chronicle_operation", fields(op = ?tx, model= ?self), ret(Debug))]
     pub fn apply(&mut self, tx: &ChronicleOperation) -> Result<(), Contradiction> {
+        self.apply_operation(tx)
+            .map_err(|contradiction| contradiction.with_operation(tx.name()))
+    }
+
+    fn apply_operation(&mut self, tx: &ChronicleOperation) -> Result<(), Contradiction> {
         let tx = tx.to_owned();
         match tx {
             ChronicleOperation::CreateNamespace(CreateNamespace {
@@ -725,6 +872,8 @@ impl ProvModel {
                 activity_id,
                 role,
                 responsible_id,
+                valid_from,
+                valid_until,
             }) => {
                 self.namespace_context(&namespace);
                 self.agent_context(&namespace, &delegate_id);
@@ -734,14 +883,30 @@ impl ProvModel {
                     self.activity_context(&namespace, &activity_id);
                 }
 
-                self.qualified_delegation(
-                    &namespace,
-                    &responsible_id,
-                    &delegate_id,
-                    activity_id,
-                    role,
+                let delegation = self.with_prior_validity(
+                    Delegation::new(
+                        &namespace,
+                        &delegate_id,
+                        &responsible_id,
+                        activity_id.as_ref(),
+                        role,
+                    )
+                    .with_validity(valid_from, valid_until),
                 );
 
+                if let (Some(from), Some(until)) = (delegation.valid_from, delegation.valid_until) {
+                    if until <= from {
+                        return Err(Contradiction::invalid_range(
+                            delegation.id.into(),
+                            namespace,
+                            from,
+                            until,
+                        ));
+                    }
+                }
+
+                self.add_delegation(delegation);
+
                 Ok(())
             }
             ChronicleOperation::RegisterKey(RegisterKey {
@@ -966,6 +1131,12 @@ impl ProvModel {
                     )?;
                 };
 
+                self.restore_retracted_attributes(
+                    &namespace,
+                    id.clone(),
+                    attributes.attributes.keys(),
+                );
+
                 self.modify_entity(&namespace, &id, move |entity| {
                     entity.domaintypeid = attributes.typ.clone();
                     entity.attributes = attributes.attributes;
@@ -994,6 +1165,12 @@ impl ProvModel {
                     )?;
                 };
 
+                self.restore_retracted_attributes(
+                    &namespace,
+                    id.clone(),
+                    attributes.attributes.keys(),
+                );
+
                 self.modify_activity(&namespace, &id, move |activity| {
                     activity.domaintypeid = attributes.typ.clone();
                     activity.attributes = attributes.attributes;
@@ -1022,6 +1199,12 @@ impl ProvModel {
                     )?;
                 };
 
+                self.restore_retracted_attributes(
+                    &namespace,
+                    id.clone(),
+                    attributes.attributes.keys(),
+                );
+
                 self.modify_agent(&namespace, &id, move |agent| {
                     agent.domaintypeid = attributes.typ.clone();
                     agent.attributes = attributes.attributes;
@@ -1029,6 +1212,141 @@ impl ProvModel {
 
                 Ok(())
             }
+            ChronicleOperation::RetractAssociation(RetractAssociation {
+                id: _,
+                role,
+                namespace,
+                activity_id,
+                agent_id,
+            }) => {
+                self.namespace_context(&namespace);
+                self.retract_association(&namespace, &activity_id, &agent_id, role);
+
+                Ok(())
+            }
+            ChronicleOperation::RetractAttribution(RetractAttribution {
+                id: _,
+                role,
+                namespace,
+                entity_id,
+                agent_id,
+            }) => {
+                self.namespace_context(&namespace);
+                self.retract_attribution(&namespace, &entity_id, &agent_id, role);
+
+                Ok(())
+            }
+            ChronicleOperation::RetractAttribute(RetractAttribute::Entity {
+                namespace,
+                id,
+                typename,
+            }) => {
+                self.namespace_context(&namespace);
+
+                if let Some(entity) = self.entities.get_mut(&(namespace.clone(), id.clone())) {
+                    if entity.attributes.remove(&typename).is_some() {
+                        self.retracted_attribute(&namespace, id, typename);
+                    }
+                }
+
+                Ok(())
+            }
+            ChronicleOperation::RetractAttribute(RetractAttribute::Activity {
+                namespace,
+                id,
+                typename,
+            }) => {
+                self.namespace_context(&namespace);
+
+                if let Some(activity) = self.activities.get_mut(&(namespace.clone(), id.clone())) {
+                    if activity.attributes.remove(&typename).is_some() {
+                        self.retracted_attribute(&namespace, id, typename);
+                    }
+                }
+
+                Ok(())
+            }
+            ChronicleOperation::RetractAttribute(RetractAttribute::Agent {
+                namespace,
+                id,
+                typename,
+            }) => {
+                self.namespace_context(&namespace);
+
+                if let Some(agent) = self.agents.get_mut(&(namespace.clone(), id.clone())) {
+                    if agent.attributes.remove(&typename).is_some() {
+                        self.retracted_attribute(&namespace, id, typename);
+                    }
+                }
+
+                Ok(())
+            }
+            ChronicleOperation::RotateKey(RotateKey {
+                namespace,
+                id,
+                previous_key,
+                publickey,
+                signature,
+            }) => {
+                self.namespace_context(&namespace);
+                self.agent_context(&namespace, &id);
+
+                if !Self::valid_key_transition(&previous_key, &publickey, &signature) {
+                    return Err(Contradiction::invalid_key_transition(
+                        id.into(),
+                        namespace,
+                        previous_key,
+                        publickey,
+                    ));
+                }
+
+                let previous_identity =
+                    IdentityId::from_external_id(id.external_id_part(), &previous_key);
+
+                match self
+                    .has_identity
+                    .get(&(namespace.clone(), id.clone()))
+                    .map(|(_, current)| current.clone())
+                {
+                    // The outgoing key was never registered, record it so
+                    // that it is retained as a past identity
+                    None => self.new_identity(&namespace, &id, &previous_key),
+                    Some(current) if current != previous_identity => {
+                        return Err(Contradiction::invalid_key_transition(
+                            id.into(),
+                            namespace,
+                            previous_key,
+                            publickey,
+                        ));
+                    }
+                    Some(_) => {}
+                }
+
+                self.new_identity(&namespace, &id, &publickey);
+
+                Ok(())
+            }
+        }
+    }
+
+    // Keys and signature are hex encoded, anything that does not decode is an
+    // invalid transition rather than an error
+    fn valid_key_transition(previous_key: &str, publickey: &str, signature: &str) -> bool {
+        let decode_key = |key: &str| {
+            hex::decode(key)
+                .ok()
+                .and_then(|key| VerifyingKey::from_sec1_bytes(&key).ok())
+        };
+
+        match (
+            decode_key(previous_key),
+            decode_key(publickey),
+            hex::decode(signature),
+        ) {
+            (Some(previous), Some(next), Ok(signature)) => {
+                verify_key_transition(&previous, &next, &signature)
+            }
+            _ => false,
         }
     }
 
@@ -1043,14 +1361,10 @@ impl ProvModel {
         let contradictions = attempted
             .attributes
             .iter()
-            .filter_map(|(current_name, current_value)| {
-                if let Some(attempted_value) = current.get(current_name) {
+            .filter_map(|(name, attempted_value)| {
+                if let Some(current_value) = current.get(name) {
                     if current_value != attempted_value {
-                        Some((
-                            current_name.clone(),
-                            current_value.clone(),
-                            attempted_value.clone(),
-                        ))
+                        Some((name.clone(), current_value.clone(), attempted_value.clone()))
                     } else {
                         None
                     }
@@ -1268,6 +1582,59 @@ impl CompactedJson {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, TimeZone, Utc};
+    use uuid::Uuid;
+
+    use crate::prov::{
+        operations::{ActsOnBehalfOf, ChronicleOperation},
+        to_json_ld::ToJson,
+        AgentId, NamespaceId, ProvModel,
+    };
+
+    #[tokio::test]
+    async fn delegations_hold_over_their_validity() {
+        let namespace = NamespaceId::from_external_id("testns", Uuid::nil());
+        let responsible = AgentId::from_external_id("chair");
+        let delegate = AgentId::from_external_id("supervisor");
+        let from = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        let acts_on_behalf_of = |valid_from, valid_until| {
+            ChronicleOperation::AgentActsOnBehalfOf(
+                ActsOnBehalfOf::new(&namespace, &responsible, &delegate, None, None)
+                    .with_validity(valid_from, valid_until),
+            )
+        };
+
+        let operation = acts_on_behalf_of(Some(from), None);
+        assert_eq!(
+            ChronicleOperation::from_json(&operation.to_json().0)
+                .await
+                .unwrap(),
+            operation
+        );
+
+        let mut model = ProvModel::default();
+        model.apply(&operation).unwrap();
+        model.apply(&acts_on_behalf_of(None, Some(until))).unwrap();
+
+        let delegations = &model.acted_on_behalf_of[&(namespace.clone(), delegate.clone())];
+        assert_eq!(delegations.len(), 1);
+        let delegation = delegations.iter().next().unwrap();
+        assert_eq!(delegation.valid_from, Some(from));
+        assert_eq!(delegation.valid_until, Some(until));
+        assert!(!delegation.is_valid_at(from - Duration::days(1)));
+        assert!(delegation.is_valid_at(from));
+        assert!(!delegation.is_valid_at(until));
+
+        assert!(model
+            .apply(&acts_on_behalf_of(Some(until), Some(from)))
+            .is_err());
+    }
+}
+
 /// Property testing of prov models created and round tripped via JSON / LD
 #[cfg(test)]
 pub mod proptest;
@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::prov::{
+    operations::{ActsOnBehalfOf, ChronicleOperation, WasAssociatedWith, WasAttributedTo},
+    DomaintypeId, ExternalIdPart,
+};
+
+use super::{Contradiction, ProvModel};
+
+/// The agent types that may hold each role of a domain that constrains it.
+/// Roles without constraints may be held by any agent.
+#[derive(Debug, Clone, Default)]
+pub struct RoleConstraints {
+    holders: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl RoleConstraints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow only agents of `agent_types` to hold `role`
+    pub fn with_holders(
+        mut self,
+        role: impl Into<String>,
+        agent_types: impl IntoIterator<Item = DomaintypeId>,
+    ) -> Self {
+        self.holders.entry(role.into()).or_default().extend(
+            agent_types
+                .into_iter()
+                .map(|typ| typ.external_id_part().to_string()),
+        );
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.holders.is_empty()
+    }
+
+    /// Check that an operation giving an agent a role gives it to an agent of
+    /// a type that may hold it, where `model` holds the agent as it is before
+    /// the operation is applied. The agent a delegation gives a role to is the
+    /// delegate.
+    pub fn check(
+        &self,
+        model: &ProvModel,
+        operation: &ChronicleOperation,
+    ) -> Result<(), Contradiction> {
+        let (namespace, agent_id, role) = match operation {
+            ChronicleOperation::AgentActsOnBehalfOf(ActsOnBehalfOf {
+                namespace,
+                delegate_id,
+                role: Some(role),
+                ..
+            }) => (namespace, delegate_id, role),
+            ChronicleOperation::WasAssociatedWith(WasAssociatedWith {
+                namespace,
+                agent_id,
+                role: Some(role),
+                ..
+            })
+            | ChronicleOperation::WasAttributedTo(WasAttributedTo {
+                namespace,
+                agent_id,
+                role: Some(role),
+                ..
+            }) => (namespace, agent_id, role),
+            _ => return Ok(()),
+        };
+
+        let holders = match self.holders.get(role.as_str()) {
+            Some(holders) => holders,
+            None => return Ok(()),
+        };
+
+        let agent_type = model
+            .agents
+            .get(&(namespace.clone(), agent_id.clone()))
+            .and_then(|agent| agent.domaintypeid.as_ref())
+            .map(|typ| typ.external_id_part().to_string());
+
+        if agent_type
+            .as_ref()
+            .is_some_and(|agent_type| holders.contains(agent_type))
+        {
+            Ok(())
+        } else {
+            Err(Contradiction::role_holder(
+                agent_id.clone().into(),
+                namespace.clone(),
+                role.to_string(),
+                agent_type,
+            )
+            .with_operation(operation.name()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use uuid::Uuid;
+
+    use crate::{
+        attributes::Attributes,
+        prov::{
+            operations::{AgentExists, ChronicleOperation, SetAttributes, WasAssociatedWith},
+            ActivityId, AgentId, DomaintypeId, NamespaceId, ProvModel, Role,
+        },
+    };
+
+    use super::RoleConstraints;
+
+    #[test]
+    fn only_permitted_agent_types_hold_a_constrained_role() {
+        let namespace = NamespaceId::from_external_id("testns", Uuid::nil());
+        let constraints = RoleConstraints::new()
+            .with_holders("DRUMMER", [DomaintypeId::from_external_id("MusicianAgent")]);
+
+        let mut model = ProvModel::default();
+        for (agent, typ) in [("ringo", "MusicianAgent"), ("brian", "ManagerAgent")] {
+            model
+                .apply(&ChronicleOperation::AgentExists(AgentExists {
+                    namespace: namespace.clone(),
+                    external_id: agent.into(),
+                }))
+                .unwrap();
+            model
+                .apply(&ChronicleOperation::SetAttributes(SetAttributes::Agent {
+                    namespace: namespace.clone(),
+                    id: AgentId::from_external_id(agent),
+                    attributes: Attributes::type_only(Some(DomaintypeId::from_external_id(typ))),
+                }))
+                .unwrap();
+        }
+
+        let associate = |agent: &str, role: &str| {
+            ChronicleOperation::WasAssociatedWith(WasAssociatedWith::new(
+                &namespace,
+                &ActivityId::from_external_id("recording"),
+                &AgentId::from_external_id(agent),
+                Some(Role::from(role)),
+            ))
+        };
+
+        assert!(constraints
+            .check(&model, &associate("ringo", "DRUMMER"))
+            .is_ok());
+        assert!(constraints
+            .check(&model, &associate("brian", "MANAGER"))
+            .is_ok());
+
+        let contradiction = constraints
+            .check(&model, &associate("brian", "DRUMMER"))
+            .unwrap_err();
+        assert_eq!(contradiction.details()[0].field(), "role");
+        assert_eq!(contradiction.details()[0].attempted(), "ManagerAgent");
+    }
+}
@@ -114,101 +114,138 @@ impl ToJson for ProvModel {
 
                 Self::write_attributes(&mut agentdoc, agent.attributes.values());
 
+                if let Some(retracted) = self
+                    .retracted_attributes
+                    .get(&(agent.namespaceid.clone(), ChronicleIri::from(id.clone())))
+                {
+                    Self::write_retracted_attributes(&mut agentdoc, retracted.iter());
+                }
+
                 doc.push(Value::Object(agentdoc));
             }
         }
 
-        for (_, associations) in self.association.iter() {
-            for association in associations {
-                if let Value::Object(mut associationdoc) = json!({
-                    "@id": association.id.de_compact(),
-                    "@type": [Iri::from(Prov::Association).as_str()],
-                }) {
-                    let mut values = Vec::new();
-
-                    values.push(json!({
-                        "@id": Value::String(association.agent_id.de_compact()),
-                    }));
+        let associations = self
+            .association
+            .values()
+            .flatten()
+            .map(|association| (association, false))
+            .chain(
+                self.retracted_association
+                    .values()
+                    .flatten()
+                    .map(|association| (association, true)),
+            );
 
-                    associationdoc.insert(
-                        Iri::from(Prov::Responsible).to_string(),
-                        Value::Array(values),
-                    );
+        for (association, retracted) in associations {
+            let mut typ = vec![Iri::from(Prov::Association).to_string()];
+            if retracted {
+                typ.push(Iri::from(Chronicle::Retracted).to_string());
+            }
 
-                    associationdoc.insert(
-                        Iri::from(Prov::HadActivity).to_string(),
-                        Value::Array(vec![json!({
-                            "@id": Value::String(association.activity_id.de_compact()),
-                        })]),
-                    );
+            if let Value::Object(mut associationdoc) = json!({
+                "@id": association.id.de_compact(),
+                "@type": typ,
+            }) {
+                let mut values = Vec::new();
 
-                    if let Some(role) = &association.role {
-                        associationdoc.insert(
-                            Iri::from(Prov::HadRole).to_string(),
-                            json!([{ "@value": role.to_string()}]),
-                        );
-                    }
+                values.push(json!({
+                    "@id": Value::String(association.agent_id.de_compact()),
+                }));
 
-                    let mut values = Vec::new();
+                associationdoc.insert(
+                    Iri::from(Prov::Responsible).to_string(),
+                    Value::Array(values),
+                );
 
-                    values.push(json!({
-                        "@id": Value::String(association.namespace_id.de_compact()),
-                    }));
+                associationdoc.insert(
+                    Iri::from(Prov::HadActivity).to_string(),
+                    Value::Array(vec![json!({
+                        "@id": Value::String(association.activity_id.de_compact()),
+                    })]),
+                );
 
+                if let Some(role) = &association.role {
                     associationdoc.insert(
-                        Iri::from(Chronicle::HasNamespace).to_string(),
-                        Value::Array(values),
+                        Iri::from(Prov::HadRole).to_string(),
+                        json!([{ "@value": role.to_string()}]),
                     );
-
-                    doc.push(Value::Object(associationdoc));
                 }
+
+                let mut values = Vec::new();
+
+                values.push(json!({
+                    "@id": Value::String(association.namespace_id.de_compact()),
+                }));
+
+                associationdoc.insert(
+                    Iri::from(Chronicle::HasNamespace).to_string(),
+                    Value::Array(values),
+                );
+
+                doc.push(Value::Object(associationdoc));
             }
         }
 
-        for (_, attributions) in self.attribution.iter() {
-            for attribution in attributions {
-                if let Value::Object(mut attribution_doc) = json!({
-                    "@id": attribution.id.de_compact(),
-                    "@type": [Iri::from(Prov::Attribution).as_str()],
-                }) {
-                    let mut values = Vec::new();
-
-                    values.push(json!({
-                        "@id": Value::String(attribution.agent_id.de_compact()),
-                    }));
+        let attributions = self
+            .attribution
+            .values()
+            .flatten()
+            .map(|attribution| (attribution, false))
+            .chain(
+                self.retracted_attribution
+                    .values()
+                    .flatten()
+                    .map(|attribution| (attribution, true)),
+            );
 
-                    attribution_doc.insert(
-                        Iri::from(Prov::Responsible).to_string(),
-                        Value::Array(values),
-                    );
+        for (attribution, retracted) in attributions {
+            let mut typ = vec![Iri::from(Prov::Attribution).to_string()];
+            if retracted {
+                typ.push(Iri::from(Chronicle::Retracted).to_string());
+            }
 
-                    attribution_doc.insert(
-                        Iri::from(Prov::HadEntity).to_string(),
-                        Value::Array(vec![json!({
-                            "@id": Value::String(attribution.entity_id.de_compact()),
-                        })]),
-                    );
+            if let Value::Object(mut attribution_doc) = json!({
+                "@id": attribution.id.de_compact(),
+                "@type": typ,
+            }) {
+                let mut values = Vec::new();
 
-                    if let Some(role) = &attribution.role {
-                        attribution_doc.insert(
-                            Iri::from(Prov::HadRole).to_string(),
-                            json!([{ "@value": role.to_string()}]),
-                        );
-                    }
+                values.push(json!({
+                    "@id": Value::String(attribution.agent_id.de_compact()),
+                }));
 
-                    let mut values = Vec::new();
+                attribution_doc.insert(
+                    Iri::from(Prov::Responsible).to_string(),
+                    Value::Array(values),
+                );
 
-                    values.push(json!({
-                        "@id": Value::String(attribution.namespace_id.de_compact()),
-                    }));
+                attribution_doc.insert(
+                    Iri::from(Prov::HadEntity).to_string(),
+                    Value::Array(vec![json!({
+                        "@id": Value::String(attribution.entity_id.de_compact()),
+                    })]),
+                );
 
+                if let Some(role) = &attribution.role {
                     attribution_doc.insert(
-                        Iri::from(Chronicle::HasNamespace).to_string(),
-                        Value::Array(values),
+                        Iri::from(Prov::HadRole).to_string(),
+                        json!([{ "@value": role.to_string()}]),
                     );
-
-                    doc.push(Value::Object(attribution_doc));
                 }
+
+                let mut values = Vec::new();
+
+                values.push(json!({
+                    "@id": Value::String(attribution.namespace_id.de_compact()),
+                }));
+
+                attribution_doc.insert(
+                    Iri::from(Chronicle::HasNamespace).to_string(),
+                    Value::Array(values),
+                );
+
+                doc.push(Value::Object(attribution_doc));
             }
         }
 
@@ -253,6 +290,20 @@ impl ToJson for ProvModel {
                         Value::Array(delegate_ids),
                     );
 
+                    if let Some(time) = delegation.valid_from {
+                        delegationdoc.insert(
+                            Iri::from(Chronicle::ValidFrom).to_string(),
+                            json!([{ "@value": time.to_rfc3339()}]),
+                        );
+                    }
+
+                    if let Some(time) = delegation.valid_until {
+                        delegationdoc.insert(
+                            Iri::from(Chronicle::ValidUntil).to_string(),
+                            json!([{ "@value": time.to_rfc3339()}]),
+                        );
+                    }
+
                     let mut values = Vec::new();
 
                     values.push(json!({
@@ -362,6 +413,13 @@ impl ToJson for ProvModel {
 
                 Self::write_attributes(&mut activitydoc, activity.attributes.values());
 
+                if let Some(retracted) = self
+                    .retracted_attributes
+                    .get(&(activity.namespaceid.clone(), ChronicleIri::from(id.clone())))
+                {
+                    Self::write_retracted_attributes(&mut activitydoc, retracted.iter());
+                }
+
                 doc.push(Value::Object(activitydoc));
             }
         }
@@ -472,6 +530,13 @@ impl ToJson for ProvModel {
 
                 Self::write_attributes(&mut entitydoc, entity.attributes.values());
 
+                if let Some(retracted) = self
+                    .retracted_attributes
+                    .get(&(entity.namespaceid.clone(), ChronicleIri::from(id.clone())))
+                {
+                    Self::write_retracted_attributes(&mut entitydoc, retracted.iter());
+                }
+
                 doc.push(Value::Object(entitydoc));
             }
         }
@@ -496,6 +561,20 @@ impl ProvModel {
             json!([{"@value" : Value::Object(attribute_node), "@type": "@json"}]),
         );
     }
+
+    fn write_retracted_attributes<'a, I: Iterator<Item = &'a String>>(
+        doc: &mut serde_json::Map<String, Value>,
+        typenames: I,
+    ) {
+        doc.insert(
+            Chronicle::RetractedAttribute.as_iri().to_string(),
+            Value::Array(
+                typenames
+                    .map(|typename| json!({ "@value": typename }))
+                    .collect(),
+            ),
+        );
+    }
 }
 
 impl ToJson for ChronicleOperation {
@@ -548,6 +627,8 @@ impl ToJson for ChronicleOperation {
                 activity_id,
                 role,
                 responsible_id,
+                valid_from,
+                valid_until,
             }) => {
                 let mut o = Value::new_operation(ChronicleOperations::AgentActsOnBehalfOf);
 
@@ -582,6 +663,20 @@ impl ToJson for ChronicleOperation {
                     );
                 }
 
+                if let Some(time) = valid_from {
+                    o.has_value(
+                        OperationValue::string(time.to_rfc3339()),
+                        ChronicleOperations::ValidFrom,
+                    );
+                }
+
+                if let Some(time) = valid_until {
+                    o.has_value(
+                        OperationValue::string(time.to_rfc3339()),
+                        ChronicleOperations::ValidUntil,
+                    );
+                }
+
                 o
             }
             ChronicleOperation::RegisterKey(RegisterKey {
@@ -1007,6 +1102,204 @@ impl ToJson for ChronicleOperation {
                     o.has_value(OperationValue::string(role), ChronicleOperations::Role);
                 }
 
+                o
+            }
+            ChronicleOperation::RetractAssociation(RetractAssociation {
+                id: _,
+                role,
+                namespace,
+                activity_id,
+                agent_id,
+            }) => {
+                let mut o = Value::new_operation(ChronicleOperations::RetractAssociation);
+
+                o.has_value(
+                    OperationValue::string(namespace.external_id_part()),
+                    ChronicleOperations::NamespaceName,
+                );
+
+                o.has_value(
+                    OperationValue::string(namespace.uuid_part()),
+                    ChronicleOperations::NamespaceUuid,
+                );
+
+                o.has_value(
+                    OperationValue::string(activity_id.external_id_part()),
+                    ChronicleOperations::ActivityName,
+                );
+
+                o.has_value(
+                    OperationValue::string(agent_id.external_id_part()),
+                    ChronicleOperations::AgentName,
+                );
+
+                if let Some(role) = role {
+                    o.has_value(OperationValue::string(role), ChronicleOperations::Role);
+                }
+
+                o
+            }
+            ChronicleOperation::RetractAttribution(RetractAttribution {
+                id: _,
+                role,
+                namespace,
+                entity_id,
+                agent_id,
+            }) => {
+                let mut o = Value::new_operation(ChronicleOperations::RetractAttribution);
+
+                o.has_value(
+                    OperationValue::string(namespace.external_id_part()),
+                    ChronicleOperations::NamespaceName,
+                );
+
+                o.has_value(
+                    OperationValue::string(namespace.uuid_part()),
+                    ChronicleOperations::NamespaceUuid,
+                );
+
+                o.has_value(
+                    OperationValue::string(entity_id.external_id_part()),
+                    ChronicleOperations::EntityName,
+                );
+
+                o.has_value(
+                    OperationValue::string(agent_id.external_id_part()),
+                    ChronicleOperations::AgentName,
+                );
+
+                if let Some(role) = role {
+                    o.has_value(OperationValue::string(role), ChronicleOperations::Role);
+                }
+
+                o
+            }
+            ChronicleOperation::RetractAttribute(RetractAttribute::Entity {
+                namespace,
+                id,
+                typename,
+            }) => {
+                let mut o = Value::new_operation(ChronicleOperations::RetractAttribute);
+
+                o.has_value(
+                    OperationValue::string(namespace.external_id_part()),
+                    ChronicleOperations::NamespaceName,
+                );
+
+                o.has_value(
+                    OperationValue::string(namespace.uuid_part()),
+                    ChronicleOperations::NamespaceUuid,
+                );
+
+                o.has_value(
+                    OperationValue::string(id.external_id_part()),
+                    ChronicleOperations::EntityName,
+                );
+
+                o.has_value(
+                    OperationValue::string(typename),
+                    ChronicleOperations::AttributeName,
+                );
+
+                o
+            }
+            ChronicleOperation::RetractAttribute(RetractAttribute::Activity {
+                namespace,
+                id,
+                typename,
+            }) => {
+                let mut o = Value::new_operation(ChronicleOperations::RetractAttribute);
+
+                o.has_value(
+                    OperationValue::string(namespace.external_id_part()),
+                    ChronicleOperations::NamespaceName,
+                );
+
+                o.has_value(
+                    OperationValue::string(namespace.uuid_part()),
+                    ChronicleOperations::NamespaceUuid,
+                );
+
+                o.has_value(
+                    OperationValue::string(id.external_id_part()),
+                    ChronicleOperations::ActivityName,
+                );
+
+                o.has_value(
+                    OperationValue::string(typename),
+                    ChronicleOperations::AttributeName,
+                );
+
+                o
+            }
+            ChronicleOperation::RetractAttribute(RetractAttribute::Agent {
+                namespace,
+                id,
+                typename,
+            }) => {
+                let mut o = Value::new_operation(ChronicleOperations::RetractAttribute);
+
+                o.has_value(
+                    OperationValue::string(namespace.external_id_part()),
+                    ChronicleOperations::NamespaceName,
+                );
+
+                o.has_value(
+                    OperationValue::string(namespace.uuid_part()),
+                    ChronicleOperations::NamespaceUuid,
+                );
+
+                o.has_value(
+                    OperationValue::string(id.external_id_part()),
+                    ChronicleOperations::AgentName,
+                );
+
+                o.has_value(
+                    OperationValue::string(typename),
+                    ChronicleOperations::AttributeName,
+                );
+
+                o
+            }
+            ChronicleOperation::RotateKey(RotateKey {
+                namespace,
+                id,
+                previous_key,
+                publickey,
+                signature,
+            }) => {
+                let mut o = Value::new_operation(ChronicleOperations::RotateKey);
+
+                o.has_value(
+                    OperationValue::string(namespace.external_id_part()),
+                    ChronicleOperations::NamespaceName,
+                );
+
+                o.has_value(
+                    OperationValue::string(namespace.uuid_part()),
+                    ChronicleOperations::NamespaceUuid,
+                );
+
+                o.has_value(
+                    OperationValue::string(id.external_id_part()),
+                    ChronicleOperations::AgentName,
+                );
+
+                o.has_value(
+                    OperationValue::string(previous_key.to_owned()),
+                    ChronicleOperations::PreviousPublicKey,
+                );
+
+                o.has_value(
+                    OperationValue::string(publickey.to_owned()),
+                    ChronicleOperations::PublicKey,
+                );
+
+                o.has_value(
+                    OperationValue::string(signature.to_owned()),
+                    ChronicleOperations::KeyTransitionSignature,
+                );
+
                 o
             }
         };
@@ -12,8 +12,8 @@ use uuid::Uuid;
 use crate::attributes::Attributes;
 
 use super::{
-    ActivityId, AgentId, AssociationId, AttributionId, DelegationId, EntityId, ExternalId,
-    NamespaceId, Role,
+    ActivityId, AgentId, AssociationId, AttributionId, ChronicleIri, DelegationId, EntityId,
+    ExternalId, NamespaceId, Role,
 };
 
 #[derive(
@@ -139,6 +139,12 @@ pub struct ActsOnBehalfOf {
     pub responsible_id: AgentId,
     pub delegate_id: AgentId,
     pub namespace: NamespaceId,
+    /// When the delegation starts to hold, if not from the outset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// When the delegation stops holding, if ever
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<DateTime<Utc>>,
 }
 
 impl ActsOnBehalfOf {
@@ -161,8 +167,22 @@ impl ActsOnBehalfOf {
             activity_id: activity_id.cloned(),
             responsible_id: responsible_id.clone(),
             delegate_id: delegate_id.clone(),
+            valid_from: None,
+            valid_until: None,
         }
     }
+
+    /// Limit the delegation to the period between `valid_from` and
+    /// `valid_until`, either of which may be open
+    pub fn with_validity(
+        mut self,
+        valid_from: Option<DateTime<Utc>>,
+        valid_until: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.valid_from = valid_from;
+        self.valid_until = valid_until;
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
@@ -172,6 +192,18 @@ pub struct RegisterKey {
     pub publickey: String,
 }
 
+/// Replace an agent's current key, the transition from `previous_key` to
+/// `publickey` is signed by the previous key. Keys and signature are hex
+/// encoded, the keys as compressed SEC1 points.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct RotateKey {
+    pub namespace: NamespaceId,
+    pub id: AgentId,
+    pub previous_key: String,
+    pub publickey: String,
+    pub signature: String,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct ActivityExists {
     pub namespace: NamespaceId,
@@ -299,6 +331,83 @@ pub enum SetAttributes {
     },
 }
 
+/// Remove a previously recorded association, the association is kept on the
+/// ledger flagged as retracted
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct RetractAssociation {
+    pub id: AssociationId,
+    pub role: Option<Role>,
+    pub namespace: NamespaceId,
+    pub activity_id: ActivityId,
+    pub agent_id: AgentId,
+}
+
+impl RetractAssociation {
+    pub fn new(
+        namespace: &NamespaceId,
+        activity_id: &ActivityId,
+        agent_id: &AgentId,
+        role: Option<Role>,
+    ) -> Self {
+        Self {
+            id: AssociationId::from_component_ids(agent_id, activity_id, role.as_ref()),
+            role,
+            namespace: namespace.clone(),
+            activity_id: activity_id.clone(),
+            agent_id: agent_id.clone(),
+        }
+    }
+}
+
+/// Remove a previously recorded attribution, the attribution is kept on the
+/// ledger flagged as retracted
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct RetractAttribution {
+    pub id: AttributionId,
+    pub role: Option<Role>,
+    pub namespace: NamespaceId,
+    pub entity_id: EntityId,
+    pub agent_id: AgentId,
+}
+
+impl RetractAttribution {
+    pub fn new(
+        namespace: &NamespaceId,
+        entity_id: &EntityId,
+        agent_id: &AgentId,
+        role: Option<Role>,
+    ) -> Self {
+        Self {
+            id: AttributionId::from_component_ids(agent_id, entity_id, role.as_ref()),
+            role,
+            namespace: namespace.clone(),
+            entity_id: entity_id.clone(),
+            agent_id: agent_id.clone(),
+        }
+    }
+}
+
+/// Remove a single attribute by type name, allowing it to be set again with a
+/// different value
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub enum RetractAttribute {
+    Entity {
+        namespace: NamespaceId,
+        id: EntityId,
+        typename: String,
+    },
+    Agent {
+        namespace: NamespaceId,
+        id: AgentId,
+        typename: String,
+    },
+    Activity {
+        namespace: NamespaceId,
+        id: ActivityId,
+        typename: String,
+    },
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub enum ChronicleOperation {
     CreateNamespace(CreateNamespace),
@@ -316,6 +425,10 @@ pub enum ChronicleOperation {
     WasAssociatedWith(WasAssociatedWith),
     WasAttributedTo(WasAttributedTo),
     WasInformedBy(WasInformedBy),
+    RetractAssociation(RetractAssociation),
+    RetractAttribution(RetractAttribution),
+    RetractAttribute(RetractAttribute),
+    RotateKey(RotateKey),
 }
 
 impl ChronicleOperation {
@@ -341,6 +454,107 @@ impl ChronicleOperation {
             ChronicleOperation::WasAssociatedWith(o) => &o.namespace,
             ChronicleOperation::WasAttributedTo(o) => &o.namespace,
             ChronicleOperation::WasInformedBy(o) => &o.namespace,
+            ChronicleOperation::RetractAssociation(o) => &o.namespace,
+            ChronicleOperation::RetractAttribution(o) => &o.namespace,
+            ChronicleOperation::RetractAttribute(o) => match o {
+                RetractAttribute::Activity { namespace, .. } => namespace,
+                RetractAttribute::Agent { namespace, .. } => namespace,
+                RetractAttribute::Entity { namespace, .. } => namespace,
+            },
+            ChronicleOperation::RotateKey(o) => &o.namespace,
+        }
+    }
+
+    /// The records the operation creates or relates, as used to index the
+    /// audit log. Only `CreateNamespace` has the namespace itself as a subject.
+    pub fn subjects(&self) -> Vec<ChronicleIri> {
+        match self {
+            ChronicleOperation::CreateNamespace(o) => vec![o.id.clone().into()],
+            ChronicleOperation::AgentExists(o) => {
+                vec![AgentId::from_external_id(&o.external_id).into()]
+            }
+            ChronicleOperation::AgentActsOnBehalfOf(o) => {
+                let mut subjects = vec![
+                    o.delegate_id.clone().into(),
+                    o.responsible_id.clone().into(),
+                ];
+                subjects.extend(o.activity_id.clone().map(ChronicleIri::from));
+                subjects
+            }
+            ChronicleOperation::RegisterKey(o) => vec![o.id.clone().into()],
+            ChronicleOperation::RotateKey(o) => vec![o.id.clone().into()],
+            ChronicleOperation::ActivityExists(o) => {
+                vec![ActivityId::from_external_id(&o.external_id).into()]
+            }
+            ChronicleOperation::StartActivity(o) => vec![o.id.clone().into()],
+            ChronicleOperation::EndActivity(o) => vec![o.id.clone().into()],
+            ChronicleOperation::ActivityUses(o) => {
+                vec![o.activity.clone().into(), o.id.clone().into()]
+            }
+            ChronicleOperation::EntityExists(o) => {
+                vec![EntityId::from_external_id(&o.external_id).into()]
+            }
+            ChronicleOperation::WasGeneratedBy(o) => {
+                vec![o.id.clone().into(), o.activity.clone().into()]
+            }
+            ChronicleOperation::EntityDerive(o) => {
+                let mut subjects = vec![o.id.clone().into(), o.used_id.clone().into()];
+                subjects.extend(o.activity_id.clone().map(ChronicleIri::from));
+                subjects
+            }
+            ChronicleOperation::SetAttributes(o) => match o {
+                SetAttributes::Activity { id, .. } => vec![id.clone().into()],
+                SetAttributes::Agent { id, .. } => vec![id.clone().into()],
+                SetAttributes::Entity { id, .. } => vec![id.clone().into()],
+            },
+            ChronicleOperation::WasAssociatedWith(o) => {
+                vec![o.activity_id.clone().into(), o.agent_id.clone().into()]
+            }
+            ChronicleOperation::WasAttributedTo(o) => {
+                vec![o.entity_id.clone().into(), o.agent_id.clone().into()]
+            }
+            ChronicleOperation::WasInformedBy(o) => {
+                vec![
+                    o.activity.clone().into(),
+                    o.informing_activity.clone().into(),
+                ]
+            }
+            ChronicleOperation::RetractAssociation(o) => {
+                vec![o.activity_id.clone().into(), o.agent_id.clone().into()]
+            }
+            ChronicleOperation::RetractAttribution(o) => {
+                vec![o.entity_id.clone().into(), o.agent_id.clone().into()]
+            }
+            ChronicleOperation::RetractAttribute(o) => match o {
+                RetractAttribute::Activity { id, .. } => vec![id.clone().into()],
+                RetractAttribute::Agent { id, .. } => vec![id.clone().into()],
+                RetractAttribute::Entity { id, .. } => vec![id.clone().into()],
+            },
+        }
+    }
+
+    /// The name of the operation, as used when reporting contradictions
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChronicleOperation::CreateNamespace(_) => "CreateNamespace",
+            ChronicleOperation::AgentExists(_) => "AgentExists",
+            ChronicleOperation::AgentActsOnBehalfOf(_) => "AgentActsOnBehalfOf",
+            ChronicleOperation::RegisterKey(_) => "RegisterKey",
+            ChronicleOperation::ActivityExists(_) => "ActivityExists",
+            ChronicleOperation::StartActivity(_) => "StartActivity",
+            ChronicleOperation::EndActivity(_) => "EndActivity",
+            ChronicleOperation::ActivityUses(_) => "ActivityUses",
+            ChronicleOperation::EntityExists(_) => "EntityExists",
+            ChronicleOperation::WasGeneratedBy(_) => "WasGeneratedBy",
+            ChronicleOperation::EntityDerive(_) => "EntityDerive",
+            ChronicleOperation::SetAttributes(_) => "SetAttributes",
+            ChronicleOperation::WasAssociatedWith(_) => "WasAssociatedWith",
+            ChronicleOperation::WasAttributedTo(_) => "WasAttributedTo",
+            ChronicleOperation::WasInformedBy(_) => "WasInformedBy",
+            ChronicleOperation::RetractAssociation(_) => "RetractAssociation",
+            ChronicleOperation::RetractAttribution(_) => "RetractAttribution",
+            ChronicleOperation::RetractAttribute(_) => "RetractAttribute",
+            ChronicleOperation::RotateKey(_) => "RotateKey",
         }
     }
 }
@@ -25,6 +25,10 @@ pub enum ChronicleOperations {
     DelegateId,
     #[iri("chronicleop:responsibleId")]
     ResponsibleId,
+    #[iri("chronicleop:validFrom")]
+    ValidFrom,
+    #[iri("chronicleop:validUntil")]
+    ValidUntil,
     #[iri("chronicleop:RegisterKey")]
     RegisterKey,
     #[iri("chronicleop:publicKey")]
@@ -79,6 +83,20 @@ pub enum ChronicleOperations {
     InformingActivityName,
     #[iri("chronicleop:Generated")]
     Generated,
+    #[iri("chronicleop:RetractAssociation")]
+    RetractAssociation,
+    #[iri("chronicleop:RetractAttribution")]
+    RetractAttribution,
+    #[iri("chronicleop:RetractAttribute")]
+    RetractAttribute,
+    #[iri("chronicleop:attributeName")]
+    AttributeName,
+    #[iri("chronicleop:RotateKey")]
+    RotateKey,
+    #[iri("chronicleop:previousPublicKey")]
+    PreviousPublicKey,
+    #[iri("chronicleop:keyTransitionSignature")]
+    KeyTransitionSignature,
 }
 
 #[derive(IriEnum, Clone, Copy, PartialEq, Eq, Hash)]
@@ -163,6 +181,14 @@ pub enum Chronicle {
     WasInformedBy,
     #[iri("chronicle:generated")]
     Generated,
+    #[iri("chronicle:Retracted")]
+    Retracted,
+    #[iri("chronicle:retractedAttribute")]
+    RetractedAttribute,
+    #[iri("chronicle:validFrom")]
+    ValidFrom,
+    #[iri("chronicle:validUntil")]
+    ValidUntil,
 }
 
 /// Operations to format specific Iri kinds, using percentage encoding to ensure they are infallible
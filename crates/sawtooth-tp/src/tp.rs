@@ -180,11 +180,11 @@ impl TP for ChronicleTransactionHandler {
         let mut model = ProvModel::default();
 
         // Now apply operations to the model
-        for operation in operations.tx {
+        for operation in &operations.tx {
             Self::enforce_opa(
                 opa_executor.clone(),
                 &operations.identity,
-                &operation,
+                operation,
                 &state,
             )
             .await?;
@@ -261,7 +261,7 @@ impl TP for ChronicleTransactionHandler {
         }
 
         // Finally emit the delta as an event
-        let ev = chronicle_committed(span, delta, &operations.identity)
+        let ev = chronicle_committed(span, delta, &operations.tx, &operations.identity)
             .await
             .map_err(|e| ApplyError::InternalError(e.to_string()))?;
 
@@ -582,6 +582,8 @@ pub mod test {
             delegate_id,
             activity_id: Some(activity_id),
             role: Some(role),
+            valid_from: None,
+            valid_until: None,
         })
     }
 
@@ -617,11 +619,7 @@ pub mod test {
             signed_identity,
         );
 
-        let submit_tx = ChronicleSubmitTransaction {
-            tx,
-            signer: secrets.clone(),
-            policy_name: None,
-        };
+        let submit_tx = ChronicleSubmitTransaction::new(tx, secrets.clone(), None);
 
         let message_builder = MessageBuilder::new_deterministic("TEST", "1.0");
         // Get a signed tx from sawtooth protocol